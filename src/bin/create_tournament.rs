@@ -17,7 +17,9 @@ async fn main() {
     tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
 
     // Establish database connection
-    let db = uc::database::connect().expect("Failed to connect to database");
+    let db = uc::database::connect()
+        .await
+        .expect("Failed to connect to database");
 
     db.tournaments
         .create_tournament(
@@ -25,5 +27,6 @@ async fn main() {
             "UC11",
             TournamentRestrictions::new(Rank::SPlus, 100f64, 10),
         )
+        .await
         .unwrap();
 }
@@ -15,8 +15,17 @@ async fn main() {
 
     tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
 
+    if uc::receipt::key().is_none() {
+        tracing::warn!(
+            "{} is not set, registrations won't get receipts",
+            uc::receipt::SECRET_ENV
+        );
+    }
+
     // Establish database connection
-    let db = uc::database::connect().expect("Failed to connect to database");
+    let db = uc::database::connect()
+        .await
+        .expect("Failed to connect to database");
 
     let mut bot = uc::discord::new_client(db).await;
     if let Err(why) = bot.start().await {
@@ -0,0 +1,105 @@
+//! Writes a generated dataset into a development database, see `uc_helper_rust::fixtures`
+//!
+//! ```text
+//! cargo run --bin seed_fixtures -- --database uc_helper_dev --players 5000 --registrations 300 --seed 7
+//! ```
+//!
+//! Everything in the target database gets replaced, so it refuses to touch the production database.
+
+use std::str::FromStr;
+
+use chrono::Utc;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+use uc::fixtures::{self, FixtureConfig};
+use uc::prelude::*;
+use uc_helper_rust as uc;
+
+const USAGE: &str =
+    "Usage: seed_fixtures [--database name] [--players n] [--registrations n] [--days n] \
+[--seed n] [--unranked share] [--linked share] [--checked-in share] [--max-rank rank]";
+
+fn parse<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+fn parse_args(args: &[String]) -> Result<(FixtureConfig, String), String> {
+    let mut config = FixtureConfig::default();
+    let mut database = "uc_helper_dev".to_string();
+
+    let mut iter = args.iter();
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--database" => database = value.clone(),
+            "--players" => config.players = parse(flag, value)?,
+            "--registrations" => config.registrations = parse(flag, value)?,
+            "--days" => config.registration_days = parse(flag, value)?,
+            "--seed" => config.seed = parse(flag, value)?,
+            "--unranked" => config.unranked_share = parse(flag, value)?,
+            "--linked" => config.linked_share = parse(flag, value)?,
+            "--checked-in" => config.checked_in_share = parse(flag, value)?,
+            "--max-rank" => {
+                config.max_rank = Rank::try_from_str(value).map_err(|err| err.to_string())?
+            }
+            _ => return Err(format!("Unknown flag {}", flag)),
+        }
+    }
+
+    if database == uc::database::DATABASE_NAME {
+        return Err("Refusing to replace the production database".to_string());
+    }
+    Ok((config, database))
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+
+    // Set up logging
+    let subscriber = FmtSubscriber::builder()
+        .with_env_filter(EnvFilter::from_default_env())
+        .finish();
+
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to start the logger");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (config, database) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let generated = fixtures::generate(&config, Utc::now());
+    let violations = generated.invariant_violations(&config);
+    if !violations.is_empty() {
+        for violation in &violations {
+            tracing::error!("{}", violation);
+        }
+        panic!("Generated dataset is inconsistent");
+    }
+
+    let db = uc::database::connect_to(&database)
+        .await
+        .expect("Failed to connect to database");
+    generated
+        .write(&db)
+        .await
+        .expect("Failed to write the dataset to the database");
+
+    println!(
+        "Wrote {} players ({} ranked) and tournament {} with {} registrations and {} check-ins to {}",
+        generated.players.len(),
+        generated.leaderboard().len(),
+        fixtures::TOURNAMENT_SHORTHAND,
+        generated.tournament.registered_players().len(),
+        generated.tournament.check_ins.len(),
+        database
+    );
+}
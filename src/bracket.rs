@@ -0,0 +1,122 @@
+//! Bracket related computations that don't need the database or Discord
+//!
+//! Everything in here works on already loaded data, so it can be reproduced by anyone with the same input.
+
+#![warn(missing_docs)]
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::database::players::PlayerEntry;
+use crate::database::tournaments::TournamentEntry;
+use crate::tetrio::Rank;
+
+pub mod preview;
+pub mod split;
+
+#[derive(Debug, Clone)]
+/// A registered player with everything needed to place them in a bracket
+pub struct SeededPlayer {
+    /// Player's Tetrio ID
+    pub tetrio_id: String,
+    /// Player's current username, or the Tetrio ID if no data is available
+    pub username: String,
+    /// Username the player registered with, `None` for registrations from before this was tracked
+    pub registered_as: Option<String>,
+    /// Player's linked Discord ID
+    pub discord_id: Option<u64>,
+    /// Rating used for seeding, `None` if the player was unranked in the snapshot and now
+    pub rating: Option<f64>,
+    /// Rank used for seeding
+    pub rank: Rank,
+    /// Whether the rating was taken from the snapshot or from current data
+    pub from_snapshot: bool,
+    /// Whether the player is a Tetrio supporter, according to current data
+    pub supporter: bool,
+    /// Whether the account is verified, according to current data
+    pub verified: bool,
+    /// Country code of the player, according to current data
+    pub country: Option<String>,
+}
+
+/// Orders the registered players of a tournament by seed
+///
+/// Uses the snapshot TR where available and falls back to the current TR otherwise.
+/// Players without any rating are appended at the end in registration order.
+///
+/// `players` should contain the player entries of the registered players, entries of players that
+/// are not registered are ignored.
+pub fn seed_order(tournament: &TournamentEntry, players: &[PlayerEntry]) -> Vec<SeededPlayer> {
+    let mut seeded: Vec<SeededPlayer> = tournament
+        .registered_players()
+        .iter()
+        .map(|reg| {
+            let entry = players.iter().find(|p| p.tetrio_id == reg.tetrio_id);
+            let current = entry.and_then(|e| e.tetrio_data.as_ref());
+            let snapshot = tournament
+                .snapshot()
+                .iter()
+                .find(|u| u._id == reg.tetrio_id);
+
+            let (rating, rank, from_snapshot) = match (snapshot, current) {
+                (Some(snap), _) => (
+                    Some(snap.league.rating),
+                    Rank::from_str(&snap.league.rank).unwrap(),
+                    true,
+                ),
+                (None, Some(cur)) if cur.league.rating >= 0f64 => (
+                    Some(cur.league.rating),
+                    Rank::from_str(&cur.league.rank).unwrap(),
+                    false,
+                ),
+                _ => (None, Rank::Unranked, false),
+            };
+
+            SeededPlayer {
+                tetrio_id: reg.tetrio_id.clone(),
+                username: current.map_or(reg.tetrio_id.clone(), |c| c.username.clone()),
+                registered_as: reg.as_registered_username.clone(),
+                discord_id: entry.and_then(|e| e.discord_id),
+                rating,
+                rank,
+                from_snapshot,
+                supporter: current.map_or(false, |c| c.supporter.unwrap_or(false)),
+                verified: current.map_or(false, |c| c.verified),
+                country: current.and_then(|c| c.country.clone()),
+            }
+        })
+        .collect();
+
+    // stable sort, so unrated players keep their registration order
+    seeded.sort_by(|a, b| match (a.rating, b.rating) {
+        (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+
+    seeded
+}
+
+/// Top seeds that haven't checked in yet
+///
+/// Looks at the first `top_n` players of `seed_order` and returns those whose Discord ID is not in
+/// `checked_in`, together with their seed (1-indexed). Players without a linked Discord account
+/// can't check in, so they're always considered missing.
+pub fn missing_seeds<'a>(
+    seed_order: &'a [SeededPlayer],
+    checked_in: &HashSet<u64>,
+    top_n: usize,
+) -> Vec<(usize, &'a SeededPlayer)> {
+    seed_order
+        .iter()
+        .enumerate()
+        .take(top_n)
+        .filter(|(_, player)| {
+            player
+                .discord_id
+                .map_or(true, |discord_id| !checked_in.contains(&discord_id))
+        })
+        .map(|(index, player)| (index + 1, player))
+        .collect()
+}
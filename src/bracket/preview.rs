@@ -0,0 +1,203 @@
+//! Round one pairings of a seeded bracket, as a preview before the bracket is published
+//!
+//! The bracket is padded to the next power of two, the missing players are byes. Seeds are placed with
+//! the standard recursive bracket order: starting from `[1, 2]`, every seed `s` is replaced with
+//! `s, size + 1 - s` until the bracket is full. So seed 1 plays the lowest seed, seed 2 the second
+//! lowest, and the top seeds get the byes. The first round of the winners bracket of a double
+//! elimination bracket is the same as in single elimination.
+//!
+//! Nothing here is persisted, the bracket itself is still managed on Challonge.
+
+use crate::bracket::SeededPlayer;
+
+/// Size of the bracket for an amount of players, the next power of two (at least 2)
+pub fn bracket_size(players: usize) -> usize {
+    players.max(2).next_power_of_two()
+}
+
+/// Rounds of the winners bracket for an amount of players, from round one to the winners final
+pub fn winners_rounds(players: usize) -> usize {
+    bracket_size(players).trailing_zeros() as usize
+}
+
+/// Seeds (1-indexed) in the order they appear in the bracket, top to bottom
+///
+/// `size` has to be a power of two. For 8 this is `[1, 8, 4, 5, 2, 7, 3, 6]`.
+pub fn bracket_order(size: usize) -> Vec<usize> {
+    let mut order = vec![1];
+    let mut current = 1;
+    while current < size {
+        current *= 2;
+        order = order
+            .iter()
+            .flat_map(|seed| vec![*seed, current + 1 - seed])
+            .collect();
+    }
+    order
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A first round match, with seeds (1-indexed)
+pub struct Pairing {
+    /// Position of the match in the bracket, top to bottom, starting at 1
+    pub match_number: usize,
+    /// Better seed of the match
+    pub high_seed: usize,
+    /// Worse seed of the match, `None` if the high seed gets a bye
+    pub low_seed: Option<usize>,
+}
+
+impl Pairing {
+    /// Whether the high seed advances without playing
+    pub fn is_bye(&self) -> bool {
+        self.low_seed.is_none()
+    }
+}
+
+/// First round pairings for an amount of seeded players, top to bottom
+pub fn round_one(players: usize) -> Vec<Pairing> {
+    bracket_order(bracket_size(players))
+        .chunks(2)
+        .enumerate()
+        .map(|(index, pair)| {
+            let (high, low) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+            Pairing {
+                match_number: index + 1,
+                high_seed: high,
+                low_seed: Some(low).filter(|seed| *seed <= players),
+            }
+        })
+        .collect()
+}
+
+/// Reasons why a pairing might be awkward, empty if there are none
+///
+/// Currently only flags players from the same country. There are no teams in UC.
+pub fn awkward_reasons(a: &SeededPlayer, b: &SeededPlayer) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if let (Some(country_a), Some(country_b)) = (&a.country, &b.country) {
+        if country_a.eq_ignore_ascii_case(country_b) {
+            reasons.push(format!("same country ({})", country_a.to_uppercase()));
+        }
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (high seed, low seed) of every round one match, top to bottom, 0 for a bye
+    fn table(players: usize) -> Vec<(usize, usize)> {
+        round_one(players)
+            .iter()
+            .map(|p| (p.high_seed, p.low_seed.unwrap_or(0)))
+            .collect()
+    }
+
+    fn byes(players: usize) -> Vec<usize> {
+        round_one(players)
+            .iter()
+            .filter(|p| p.is_bye())
+            .map(|p| p.high_seed)
+            .collect()
+    }
+
+    #[test]
+    fn eight_players() {
+        assert_eq!(bracket_size(8), 8);
+        assert_eq!(winners_rounds(8), 3);
+        assert_eq!(table(8), vec![(1, 8), (4, 5), (2, 7), (3, 6)]);
+        assert!(byes(8).is_empty());
+    }
+
+    #[test]
+    fn twelve_players() {
+        assert_eq!(bracket_size(12), 16);
+        assert_eq!(winners_rounds(12), 4);
+        assert_eq!(
+            table(12),
+            vec![
+                (1, 0),
+                (8, 9),
+                (4, 0),
+                (5, 12),
+                (2, 0),
+                (7, 10),
+                (3, 0),
+                (6, 11)
+            ]
+        );
+        assert_eq!(byes(12), vec![1, 4, 2, 3]);
+    }
+
+    #[test]
+    fn sixteen_players() {
+        assert_eq!(bracket_size(16), 16);
+        assert_eq!(winners_rounds(16), 4);
+        assert_eq!(
+            bracket_order(16),
+            vec![1, 16, 8, 9, 4, 13, 5, 12, 2, 15, 7, 10, 3, 14, 6, 11]
+        );
+        assert_eq!(
+            table(16),
+            vec![
+                (1, 16),
+                (8, 9),
+                (4, 13),
+                (5, 12),
+                (2, 15),
+                (7, 10),
+                (3, 14),
+                (6, 11)
+            ]
+        );
+        assert!(byes(16).is_empty());
+    }
+
+    #[test]
+    fn thirty_three_players() {
+        assert_eq!(bracket_size(33), 64);
+        assert_eq!(winners_rounds(33), 6);
+
+        // everyone but the two lowest seeds gets a bye, those two play in the second match
+        let pairings = round_one(33);
+        assert_eq!(pairings.len(), 32);
+        let mut with_bye = byes(33);
+        with_bye.sort_unstable();
+        assert_eq!(with_bye, (1..=31).collect::<Vec<_>>());
+        let played: Vec<&Pairing> = pairings.iter().filter(|p| !p.is_bye()).collect();
+        assert_eq!(
+            played,
+            vec![&Pairing {
+                match_number: 2,
+                high_seed: 32,
+                low_seed: Some(33)
+            }]
+        );
+    }
+
+    #[test]
+    fn every_seed_appears_once() {
+        for players in 2..=70 {
+            let mut seeds: Vec<usize> = round_one(players)
+                .iter()
+                .flat_map(|p| vec![Some(p.high_seed), p.low_seed])
+                .flatten()
+                .collect();
+            seeds.sort_unstable();
+            assert_eq!(
+                seeds,
+                (1..=players).collect::<Vec<_>>(),
+                "{} players",
+                players
+            );
+            assert_eq!(
+                byes(players).len(),
+                bracket_size(players) - players,
+                "{} players",
+                players
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Reproducible assignment of seeded players to the two halves of a double elimination bracket
+//!
+//! Players are distributed in serpentine order (A, B, B, A, A, B, ...), so both halves end up with
+//! a similar strength. Players with the same rating (rounded to a whole TR) form a band, whose order
+//! is shuffled with a seeded RNG before distributing, since their relative seeding is arbitrary anyway.
+//!
+//! Given the same seed order and RNG seed, the resulting split is always identical.
+
+use crate::bracket::SeededPlayer;
+use crate::rng::{fnv1a, SplitMix64};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// One of the two halves of the bracket
+pub enum Side {
+    /// Upper half
+    A,
+    /// Lower half
+    B,
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::A => f.write_str("A"),
+            Side::B => f.write_str("B"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Result of [`split()`], both sides contain Tetrio IDs in seed order
+pub struct SplitResult {
+    /// RNG seed used for the split
+    pub seed: u64,
+    /// Players assigned to side A
+    pub side_a: Vec<String>,
+    /// Players assigned to side B
+    pub side_b: Vec<String>,
+}
+
+/// Derives the default RNG seed from the tournament shorthand and the snapshot date
+///
+/// The snapshot date is expected as a UNIX timestamp in seconds.
+pub fn default_seed(shorthand: &str, snapshot_timestamp: i64) -> u64 {
+    fnv1a(&format!("{}:{}", shorthand, snapshot_timestamp))
+}
+
+/// Which side a player at a given position (0-indexed) gets assigned to in serpentine order
+pub fn serpentine_side(position: usize) -> Side {
+    match (position / 2 % 2, position % 2) {
+        (0, 0) | (1, 1) => Side::A,
+        _ => Side::B,
+    }
+}
+
+/// Band used to group players with an equal rating
+fn rating_band(player: &SeededPlayer) -> Option<i64> {
+    player.rating.map(|r| r.round() as i64)
+}
+
+/// Assigns players (in seed order) to bracket sides
+///
+/// Side sizes differ by at most one.
+pub fn split(seed_order: &[SeededPlayer], seed: u64) -> SplitResult {
+    let mut rng = SplitMix64::new(seed);
+    let mut order: Vec<&SeededPlayer> = seed_order.iter().collect();
+
+    // Shuffle bands of equal rating
+    let mut start = 0;
+    while start < order.len() {
+        let band = rating_band(order[start]);
+        let mut end = start + 1;
+        while end < order.len() && rating_band(order[end]) == band {
+            end += 1;
+        }
+        rng.shuffle(&mut order[start..end]);
+        start = end;
+    }
+
+    let mut result = SplitResult {
+        seed,
+        side_a: Vec::new(),
+        side_b: Vec::new(),
+    };
+
+    for (position, player) in order.iter().enumerate() {
+        match serpentine_side(position) {
+            Side::A => result.side_a.push(player.tetrio_id.clone()),
+            Side::B => result.side_b.push(player.tetrio_id.clone()),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::Rank;
+
+    // Players in seed order, every `band` players share a rating
+    fn players(count: usize, band: usize) -> Vec<SeededPlayer> {
+        (0..count)
+            .map(|i| SeededPlayer {
+                tetrio_id: format!("player{}", i),
+                username: format!("player{}", i),
+                registered_as: None,
+                discord_id: None,
+                rating: Some(20000f64 - (i / band) as f64 * 100f64),
+                rank: Rank::SPlus,
+                from_snapshot: true,
+                supporter: false,
+                verified: false,
+                country: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_split() {
+        let seed_order = players(33, 4);
+        let seed = default_seed("UC12", 1615744800);
+        assert_eq!(split(&seed_order, seed), split(&seed_order, seed));
+        assert_eq!(seed, default_seed("UC12", 1615744800));
+        assert_ne!(seed, default_seed("UC12", 1615744801));
+    }
+
+    #[test]
+    fn bands_are_shuffled_by_the_seed() {
+        let seed_order = players(32, 32);
+        let splits: Vec<SplitResult> = (0..8).map(|seed| split(&seed_order, seed)).collect();
+        assert!(splits.iter().any(|s| s.side_a != splits[0].side_a));
+    }
+
+    #[test]
+    fn distinct_ratings_follow_the_serpentine_order() {
+        let result = split(&players(6, 1), 1);
+        assert_eq!(result.side_a, vec!["player0", "player3", "player4"]);
+        assert_eq!(result.side_b, vec!["player1", "player2", "player5"]);
+    }
+
+    #[test]
+    fn sides_differ_by_at_most_one() {
+        for count in 0..=40 {
+            for band in &[1, 3, 40] {
+                let result = split(&players(count, *band), count as u64);
+                let (a, b) = (result.side_a.len(), result.side_b.len());
+                assert!(
+                    (a as i64 - b as i64).abs() <= 1,
+                    "{} players: {} and {}",
+                    count,
+                    a,
+                    b
+                );
+                assert_eq!(a + b, count);
+            }
+        }
+    }
+}
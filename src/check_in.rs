@@ -0,0 +1,246 @@
+//! Steps of creating a check-in message, and how to undo them
+//!
+//! Posting the message and tracking it in the database can't happen atomically, so the check-in
+//! is created in [`Step`]s. The tracking is reserved first, so nothing else creates a check-in in
+//! the meantime, then the message is posted, its ID is written and finally the bot seeds the
+//! reaction. If a step fails, the [`Compensation`]s from [`compensations()`] undo everything that
+//! was already done, so there is never an untracked check-in message players can react to.
+
+#![warn(missing_docs)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A step of creating the check-in message, in the order they run
+pub enum Step {
+    /// Writing the pending marker on the tournament
+    Reserve,
+    /// Posting the check-in message
+    Post,
+    /// Writing the message ID and clearing the pending marker
+    Finalize,
+    /// Adding the bot's own reaction to the message
+    Seed,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Step::Reserve => "reserving the check-in",
+            Step::Post => "posting the message",
+            Step::Finalize => "saving the message ID",
+            Step::Seed => "adding the reaction",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Undoes the effect of a step that already succeeded
+pub enum Compensation {
+    /// Deletes the posted message
+    DeleteMessage,
+    /// Removes the pending marker
+    ClearReservation,
+    /// Removes the message ID from the tournament, if it's still the posted message
+    UntrackMessage,
+}
+
+impl std::fmt::Display for Compensation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Compensation::DeleteMessage => "deleted the message",
+            Compensation::ClearReservation => "cleared the reservation",
+            Compensation::UntrackMessage => "removed the message ID",
+        })
+    }
+}
+
+/// What has to be undone if `failed` fails, in the order it has to run
+///
+/// The message is deleted before anything in the database is touched, so players can't react to a
+/// message that is no longer tracked.
+pub fn compensations(failed: Step) -> Vec<Compensation> {
+    match failed {
+        Step::Reserve => vec![],
+        Step::Post => vec![Compensation::ClearReservation],
+        Step::Finalize => vec![Compensation::DeleteMessage, Compensation::ClearReservation],
+        Step::Seed => vec![Compensation::DeleteMessage, Compensation::UntrackMessage],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A failed check-in creation, after compensating
+pub struct Failure {
+    /// Step that failed
+    pub step: Step,
+    /// Why it failed
+    pub error: String,
+    /// Compensations that failed themselves, with the reason
+    pub failed_compensations: Vec<(Compensation, String)>,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed while {}: {}", self.step, self.error)?;
+        let undone: Vec<String> = compensations(self.step)
+            .iter()
+            .filter(|c| !self.failed_compensations.iter().any(|(f, _)| f == *c))
+            .map(|c| c.to_string())
+            .collect();
+        if !undone.is_empty() {
+            write!(f, "\nRolled back: {}", undone.join(", "))?;
+        }
+        for (compensation, error) in &self.failed_compensations {
+            write!(
+                f,
+                "\nCould not roll back ({} failed): {}",
+                compensation, error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: [Step; 4] = [Step::Reserve, Step::Post, Step::Finalize, Step::Seed];
+
+    #[derive(Debug, Default, PartialEq)]
+    // What the steps leave behind in Discord and the database
+    struct World {
+        reserved: bool,
+        posted: bool,
+        tracked: bool,
+        seeded: bool,
+    }
+
+    impl World {
+        fn run(&mut self, step: Step) {
+            match step {
+                Step::Reserve => self.reserved = true,
+                Step::Post => self.posted = true,
+                Step::Finalize => {
+                    self.tracked = true;
+                    self.reserved = false;
+                }
+                Step::Seed => self.seeded = true,
+            }
+        }
+
+        fn undo(&mut self, compensation: Compensation) {
+            match compensation {
+                Compensation::DeleteMessage => {
+                    self.posted = false;
+                    self.seeded = false;
+                }
+                Compensation::ClearReservation => self.reserved = false,
+                Compensation::UntrackMessage => self.tracked = false,
+            }
+        }
+    }
+
+    // Runs every step before `failed`, then the compensations for it
+    fn fail_at(failed: Step) -> World {
+        let mut world = World::default();
+        for step in STEPS.iter().take_while(|step| **step != failed) {
+            world.run(*step);
+        }
+        for compensation in compensations(failed) {
+            world.undo(compensation);
+        }
+        world
+    }
+
+    #[test]
+    fn all_steps_leave_a_tracked_message() {
+        let mut world = World::default();
+        for step in STEPS.iter() {
+            world.run(*step);
+        }
+        assert_eq!(
+            world,
+            World {
+                reserved: false,
+                posted: true,
+                tracked: true,
+                seeded: true
+            }
+        );
+    }
+
+    #[test]
+    fn failure_at_any_step_leaves_nothing_behind() {
+        for step in STEPS.iter() {
+            assert_eq!(fail_at(*step), World::default(), "failed at {:?}", step);
+        }
+    }
+
+    #[test]
+    fn compensations_per_step() {
+        assert!(compensations(Step::Reserve).is_empty());
+        assert_eq!(
+            compensations(Step::Post),
+            vec![Compensation::ClearReservation]
+        );
+        assert_eq!(
+            compensations(Step::Finalize),
+            vec![Compensation::DeleteMessage, Compensation::ClearReservation]
+        );
+        assert_eq!(
+            compensations(Step::Seed),
+            vec![Compensation::DeleteMessage, Compensation::UntrackMessage]
+        );
+    }
+
+    #[test]
+    fn message_is_deleted_before_the_database_is_touched() {
+        for step in STEPS.iter() {
+            let compensations = compensations(*step);
+            if let Some(position) = compensations
+                .iter()
+                .position(|c| *c == Compensation::DeleteMessage)
+            {
+                assert_eq!(position, 0, "{:?}", step);
+            }
+        }
+    }
+
+    #[test]
+    fn failure_reports_what_was_rolled_back() {
+        let failure = Failure {
+            step: Step::Seed,
+            error: "Missing permissions".to_string(),
+            failed_compensations: vec![],
+        };
+        assert_eq!(
+            failure.to_string(),
+            "Failed while adding the reaction: Missing permissions\n\
+             Rolled back: deleted the message, removed the message ID"
+        );
+
+        let failure = Failure {
+            step: Step::Finalize,
+            error: "Connection failed".to_string(),
+            failed_compensations: vec![(
+                Compensation::DeleteMessage,
+                "Unknown message".to_string(),
+            )],
+        };
+        assert_eq!(
+            failure.to_string(),
+            "Failed while saving the message ID: Connection failed\n\
+             Rolled back: cleared the reservation\n\
+             Could not roll back (deleted the message failed): Unknown message"
+        );
+
+        let failure = Failure {
+            step: Step::Reserve,
+            error: "A check-in message is already being created".to_string(),
+            failed_compensations: vec![],
+        };
+        assert_eq!(
+            failure.to_string(),
+            "Failed while reserving the check-in: A check-in message is already being created"
+        );
+    }
+}
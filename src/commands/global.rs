@@ -4,6 +4,7 @@ use serenity::model::prelude::*;
 use serenity::prelude::*;
 
 use crate::discord;
+use crate::discord::util::say;
 
 #[derive(Deserialize)]
 struct FaqField {
@@ -86,7 +87,7 @@ async fn who_is(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let reply = match args.current() {
         Some(args) => {
             let db = crate::discord::get_database(ctx).await;
-            match db.players.get_player_by_tetrio(args) {
+            match db.players.get_player_by_tetrio(args).await {
                 Ok(player) => match player {
                     Some(player) => match player.discord_id {
                         Some(discord_id) => {
@@ -128,7 +129,7 @@ async fn who_is(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         None => "No username provided".to_string(),
     };
 
-    msg.channel_id.say(&ctx.http, reply).await?;
+    say(&ctx, msg.channel_id, reply).await?;
 
     Ok(())
 }
@@ -1,5 +1,5 @@
+pub mod global;
 pub mod owner;
 pub mod player;
 pub mod staff;
 pub mod tournament;
-pub mod global;
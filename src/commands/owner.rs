@@ -1,17 +1,1187 @@
+use std::str::FromStr;
+
+use serenity::builder::CreateEmbed;
 use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::http::AttachmentType;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 
+use crate::database::flags::{
+    diff_flags, export_flags, parse_flag_file, parse_flag_value, plan_sync, Feature,
+};
+use crate::database::jobs::{
+    AnnouncementPayload, JobKind, RegistrationPhase, ReminderPayload, ScheduledJob,
+};
+use crate::database::players::DuplicateKey;
+use crate::discord::auto_update::AutoUpdateStatus;
+use crate::discord::janitor::JanitorStatus;
+use crate::discord::news::NewsWatcherStatus;
+use crate::discord::report::{send_report, Report};
+use crate::discord::scheduler::SchedulerStatus;
+use crate::discord::util::{
+    confirm_prompt, discord_timestamp, message_ref, parse_datetime, parse_registration_rows, say,
+    split_flags, update_participant_role,
+};
+use crate::discord::{
+    BotConfig, DegradedServings, IdCollection, PingCooldowns, RefreshCooldowns, SettingsCache,
+};
+use crate::metrics::Metrics;
+use crate::status::{RuntimeStatus, StatusReport};
+use crate::tasks::TaskRegistry;
+use crate::tetrio;
+
+// Most used commands listed by `.bot_stats`
+const BOT_STATS_COMMANDS: usize = 15;
+
 #[command]
 async fn owner_ping(ctx: &Context, msg: &Message) -> CommandResult {
-    msg.channel_id.say(&ctx.http, "Pong!").await?;
+    say(&ctx, msg.channel_id, "Pong!").await?;
     Ok(())
 }
 
 #[command]
 async fn owner_echo(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    say(&ctx, msg.channel_id, args.current().unwrap_or("Nothing")).await?;
+    Ok(())
+}
+
+#[command]
+#[sub_commands(janitor_run)]
+async fn janitor(ctx: &Context, msg: &Message) -> CommandResult {
+    say(
+        &ctx,
+        msg.channel_id,
+        "Use `.janitor run` to run the janitor now",
+    )
+    .await?;
+    Ok(())
+}
+
+#[command("run")]
+async fn janitor_run(ctx: &Context, msg: &Message) -> CommandResult {
+    let summary = crate::discord::janitor::run(&ctx.data).await;
+    say(&ctx, msg.channel_id, summary).await?;
+    Ok(())
+}
+
+#[command]
+#[sub_commands(auto_update_status, auto_update_on, auto_update_off)]
+/// Shows whether ranked players are refreshed in the background, same as `.auto_update status`
+async fn auto_update(ctx: &Context, msg: &Message) -> CommandResult {
+    auto_update_report(&ctx, &msg).await
+}
+
+#[command("status")]
+/// Shows whether ranked players are refreshed in the background and how the last refresh went
+async fn auto_update_status(ctx: &Context, msg: &Message) -> CommandResult {
+    auto_update_report(&ctx, &msg).await
+}
+
+#[command("on")]
+/// Starts refreshing ranked players in the background, with the interval from `AUTO_UPDATE_MINUTES`
+async fn auto_update_on(ctx: &Context, msg: &Message) -> CommandResult {
+    set_auto_update(&ctx, &msg, true).await
+}
+
+#[command("off")]
+/// Stops refreshing ranked players in the background, a running refresh still finishes
+async fn auto_update_off(ctx: &Context, msg: &Message) -> CommandResult {
+    set_auto_update(&ctx, &msg, false).await
+}
+
+async fn auto_update_report(ctx: &Context, msg: &Message) -> CommandResult {
+    let data_read = ctx.data.read().await;
+    let rows = match data_read.get::<AutoUpdateStatus>() {
+        Some(status) => status.lock().await.report(),
+        None => Vec::new(),
+    };
+    drop(data_read);
+
+    let mut report = Report::new("Auto update", &["Setting", "Value"]);
+    for (setting, value) in rows {
+        report.push_row(vec![setting, value]);
+    }
+    report.push_note(
+        "Use `.auto_update on` or `.auto_update off` to toggle it until the next restart",
+    );
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+// Toggles the background refresh until the next restart, `AUTO_UPDATE_MINUTES` decides after that
+async fn set_auto_update(ctx: &Context, msg: &Message, enabled: bool) -> CommandResult {
+    if let Some(status) = ctx.data.read().await.get::<AutoUpdateStatus>() {
+        let mut status = status.lock().await;
+        status.enabled = enabled;
+        tracing::info!(
+            target: "audit",
+            "{} turned the auto update {} (every {} minutes)",
+            msg.author.id,
+            if enabled { "on" } else { "off" },
+            status.interval.as_secs() / 60
+        );
+    }
+    let reply = if enabled {
+        "Ranked players are now refreshed in the background, until the next restart"
+    } else {
+        "Ranked players are no longer refreshed in the background, until the next restart"
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[sub_commands(bot_stats_reset)]
+/// Shows how much the bot was used since it started or the counters were reset, along with the uptime
+async fn bot_stats(ctx: &Context, msg: &Message) -> CommandResult {
+    let metrics = match ctx.data.read().await.get::<Metrics>() {
+        Some(metrics) => metrics.snapshot(),
+        None => return Ok(()),
+    };
+
+    let commands = metrics
+        .commands
+        .iter()
+        .take(BOT_STATS_COMMANDS)
+        .map(|(command, count)| format!("`{}` {}", command, count))
+        .collect::<Vec<_>>();
+    let failures = metrics
+        .registration_failures
+        .iter()
+        .map(|(kind, count)| format!("`{}` {}", kind, count))
+        .collect::<Vec<_>>();
+    let total_commands: u64 = metrics.commands.iter().map(|(_, count)| count).sum();
+    let total_failures: u64 = metrics
+        .registration_failures
+        .iter()
+        .map(|(_, count)| count)
+        .sum();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Bot stats")
+                    .description(format!(
+                        "Up since {}, counting since {}",
+                        discord_timestamp(metrics.started_at, 'R'),
+                        discord_timestamp(metrics.counting_since, 'R')
+                    ))
+                    .field(
+                        format!("Commands ({})", total_commands),
+                        non_empty_lines(commands),
+                        false,
+                    )
+                    .field(
+                        "Registrations",
+                        format!(
+                            "{} succeeded, {} failed",
+                            metrics.registrations, total_failures
+                        ),
+                        true,
+                    )
+                    .field(
+                        "Tetrio API",
+                        format!(
+                            "{} requests, {} failed",
+                            metrics.tetrio_requests, metrics.tetrio_failures
+                        ),
+                        true,
+                    )
+                    .field("Database errors", metrics.database_errors, true)
+                    .field("Registration failures", non_empty_lines(failures), false)
+                    .footer(|f| f.text("Use `.bot_stats reset` to set every counter back to zero"))
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[command("reset")]
+/// Sets every counter of `.bot_stats` back to zero, the uptime keeps going
+async fn bot_stats_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    if let Some(metrics) = ctx.data.read().await.get::<Metrics>() {
+        metrics.reset(chrono::Utc::now());
+    }
+    tracing::info!(target: "audit", "{} reset the bot stats", msg.author.id);
+    say(&ctx, msg.channel_id, "Bot stats were reset").await?;
+    Ok(())
+}
+
+// Embed fields can't be empty
+fn non_empty_lines(lines: Vec<String>) -> String {
+    if lines.is_empty() {
+        "-".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+#[command]
+/// Dumps the effective runtime configuration and state of every subsystem
+async fn status(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut status = RuntimeStatus::new();
+    status.register(&BotConfig);
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => status.register(&tournament),
+        Ok(None) => status.register(&NoActiveTournament),
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+
+    status.register(&tetrio::breaker_snapshot());
+    status.register(&db.flags.snapshot());
+
+    {
+        let data_read = ctx.data.read().await;
+        if let Some(ids) = data_read.get::<IdCollection>() {
+            status.register(&*ids.lock().await);
+        }
+        if let Some(cooldowns) = data_read.get::<PingCooldowns>() {
+            status.register(&*cooldowns.lock().await);
+        }
+        if let Some(cooldowns) = data_read.get::<RefreshCooldowns>() {
+            status.register(&*cooldowns.lock().await);
+        }
+        if let Some(degraded) = data_read.get::<DegradedServings>() {
+            status.register(&*degraded.lock().await);
+        }
+        if let Some(settings) = data_read.get::<SettingsCache>() {
+            status.register(&*settings.lock().await);
+        }
+        if let Some(janitor) = data_read.get::<JanitorStatus>() {
+            status.register(&*janitor.lock().await);
+        }
+        if let Some(scheduler) = data_read.get::<SchedulerStatus>() {
+            status.register(&*scheduler.lock().await);
+        }
+        if let Some(news) = data_read.get::<NewsWatcherStatus>() {
+            status.register(&*news.lock().await);
+        }
+        if let Some(auto_update) = data_read.get::<AutoUpdateStatus>() {
+            status.register(&*auto_update.lock().await);
+        }
+        if let Some(tasks) = data_read.get::<TaskRegistry>() {
+            status.register(&*tasks.lock().await);
+        }
+    }
+
+    let mut report = Report::new("Runtime status", &["Subsystem", "Setting", "Value"]);
+    for row in status.rows() {
+        report.push_row(row);
+    }
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[sub_commands(flags_set, flags_export)]
+/// Lists every feature flag with its effective value and where it comes from
+async fn flags(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+
+    let mut report = Report::new("Feature flags", &["Flag", "Enabled", "Source", "Override"]);
+    for feature in Feature::ALL.iter() {
+        let (enabled, source) = db.flags.resolve(*feature).await;
+        report.push_row(vec![
+            feature.to_string(),
+            enabled.to_string(),
+            source.to_string(),
+            feature.env_var(),
+        ]);
+    }
+
+    match db.flags.unknown_flags().await {
+        Ok(unknown) if !unknown.is_empty() => report.push_note(&format!(
+            "Unknown flags in the database: {}",
+            unknown.join(", ")
+        )),
+        Ok(_) => {}
+        Err(err) => report.push_note(&format!("Could not read stored flags: {}", err)),
+    }
+    report.push_note("Use `.flags set <flag> <on/off>` to toggle a flag");
+    report
+        .push_note("Use `.flags export` and `.sync_from_file` to copy flags between environments");
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command("set")]
+#[usage("<flag> <on/off>")]
+#[example("news_poller on")]
+async fn flags_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let feature = match args.single::<String>().map(|f| Feature::from_str(&f)) {
+        Ok(Ok(feature)) => feature,
+        Ok(Err(err)) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+        Err(_) => {
+            say(&ctx, msg.channel_id, "No flag provided").await?;
+            return Ok(());
+        }
+    };
+    let enabled = match args.current().and_then(parse_flag_value) {
+        Some(enabled) => enabled,
+        None => {
+            say(&ctx, msg.channel_id, "Expected `on` or `off`").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    if let Err(err) = db.flags.set_flag(feature, enabled, msg.author.id.0).await {
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+
+    let (effective, source) = db.flags.resolve(feature).await;
+    let reply = if effective == enabled {
+        format!(
+            "`{}` is now {}",
+            feature,
+            if enabled { "on" } else { "off" }
+        )
+    } else {
+        format!(
+            "`{}` was saved as {}, but is overridden by `{}` ({})",
+            feature,
+            if enabled { "on" } else { "off" },
+            feature.env_var(),
+            source
+        )
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+#[command("export")]
+/// Exports the stored flags as a JSON file, which `.sync_from_file` can apply on another environment
+async fn flags_export(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let values = match db.flags.stored_values().await {
+        Ok(values) => values,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let export = export_flags(&values);
     msg.channel_id
-        .say(&ctx.http, args.current().unwrap_or("Nothing"))
+        .send_files(
+            &ctx.http,
+            vec![AttachmentType::from((export.as_bytes(), "flags.json"))],
+            |m| {
+                m.content(format!("{} stored flags", values.len()))
+                    .allowed_mentions(|am| am.empty_parse())
+            },
+        )
         .await?;
     Ok(())
 }
+
+#[command]
+#[usage("<attached flags.json> [--allow-removals]")]
+/// Applies a flag export from another environment, after showing what would change.
+/// Flags that aren't in the file are only removed with `--allow-removals`.
+async fn sync_from_file(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (_, flags) = split_flags(&args, &[]);
+    let allow_removals = flags.contains_key("allow-removals");
+
+    let attachment = match msg.attachments.first() {
+        Some(attachment) => attachment,
+        None => {
+            say(&ctx, msg.channel_id, "No file attached").await?;
+            return Ok(());
+        }
+    };
+    let content = attachment.download().await?;
+    let incoming = match parse_flag_file(&String::from_utf8_lossy(&content)) {
+        Ok(incoming) => incoming,
+        Err(err) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("Could not read the file: {}", err),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let live = match db.flags.stored_values().await {
+        Ok(live) => live,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let plan = plan_sync(&diff_flags(&live, &incoming), allow_removals);
+    if plan.apply.is_empty() && plan.held_back.is_empty() {
+        say(
+            &ctx,
+            msg.channel_id,
+            "Nothing to sync, the flags are identical",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut lines: Vec<String> = plan.apply.iter().map(|diff| diff.to_string()).collect();
+    if !plan.held_back.is_empty() {
+        lines.push(format!(
+            "Not removing without --allow-removals: {}",
+            plan.held_back
+                .iter()
+                .map(|diff| diff.name.clone())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+    if plan.apply.is_empty() {
+        say(
+            &ctx,
+            msg.channel_id,
+            format!("```diff\n{}\n```", lines.join("\n")),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let prompt = format!(
+        "```diff\n{}\n```Apply {} changes?",
+        lines.join("\n"),
+        plan.apply.len()
+    );
+    if !confirm_prompt(&ctx, &msg, &prompt).await? {
+        say(&ctx, msg.channel_id, "Cancelled, nothing was changed").await?;
+        return Ok(());
+    }
+
+    // Every flag is applied on its own, a failure doesn't roll back the ones before it
+    let mut failed = Vec::new();
+    for diff in &plan.apply {
+        let result = match diff.after {
+            Some(enabled) => match Feature::from_str(&diff.name) {
+                Ok(feature) => db.flags.set_flag(feature, enabled, msg.author.id.0).await,
+                Err(err) => Err(err),
+            },
+            None => db.flags.remove_flag(&diff.name, msg.author.id.0).await,
+        };
+        if let Err(err) = result {
+            failed.push(format!("{} ({})", diff.name, err));
+        }
+    }
+
+    let applied = plan.apply.len() - failed.len();
+    let reply = if failed.is_empty() {
+        format!("Applied {} changes", applied)
+    } else {
+        format!("Applied {} changes, failed: {}", applied, failed.join(", "))
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+struct NoActiveTournament;
+
+impl StatusReport for NoActiveTournament {
+    fn name(&self) -> String {
+        "Active tournament".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Tournament".to_string(), "none".to_string())]
+    }
+}
+
+#[command]
+#[sub_commands(jobs_retry, jobs_announce, jobs_remind)]
+/// Lists scheduled jobs that are pending, running or failed
+async fn jobs(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let jobs = match db.jobs.get_open_jobs().await {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let mut report = Report::new(
+        "Scheduled jobs",
+        &[
+            "ID",
+            "Kind",
+            "Status",
+            "Due",
+            "Every",
+            "Attempts",
+            "Last error",
+        ],
+    );
+    for job in &jobs {
+        report.push_row(vec![
+            job._id.to_hex(),
+            job.kind.to_string(),
+            job.status.to_string(),
+            job.due_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            job.every_minutes
+                .map_or("-".to_string(), |every| format!("{}m", every)),
+            job.attempts.to_string(),
+            job.last_error.clone().unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+    if jobs.is_empty() {
+        report.push_note("No open jobs");
+    }
+    report.push_note("Use `.jobs retry <id>` to run a failed job again");
+    report.push_note("Use `.jobs announce` or `.jobs remind` to schedule a job in this channel");
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command("retry")]
+#[usage("<id>")]
+async fn jobs_retry(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let id = match args.current() {
+        Some(id) => id,
+        None => {
+            say(&ctx, msg.channel_id, "No job ID provided").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let reply = match db.jobs.retry(id, chrono::Utc::now()).await {
+        Ok(()) => {
+            tracing::info!(target: "audit", "{} retried job {}", msg.author.id, id);
+            format!("Job {} is due again", id)
+        }
+        Err(err) => format!(
+            "Could not retry job {} (only failed jobs can be retried): {}",
+            id, err
+        ),
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+#[command("announce")]
+#[usage("<tournament> <open/close> <UTC date>")]
+#[example("UC12 open \"2021-03-14 18:00\"")]
+/// Schedules an announcement in this channel that registration opens or closes
+async fn jobs_announce(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, _) = split_flags(&args, &[]);
+    let (tournament, phase, due_at) = match positional.as_slice() {
+        [tournament, phase, date] => (
+            tournament,
+            RegistrationPhase::from_str(phase),
+            parse_datetime(date),
+        ),
+        _ => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Expected a tournament, `open` or `close` and a date",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let (phase, due_at) = match (phase, due_at) {
+        (Ok(phase), Some(due_at)) => (phase, due_at),
+        (Err(err), _) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+        (_, None) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Invalid date, use `YYYY-MM-DD HH:MM` (UTC)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let payload = AnnouncementPayload {
+        tournament: tournament.clone(),
+        phase,
+        channel_id: msg.channel_id.0,
+    };
+    schedule(
+        &ctx,
+        msg,
+        ScheduledJob::new(
+            JobKind::RegistrationAnnouncement,
+            due_at,
+            bson::to_document(&payload).expect("Bad document"),
+            None,
+        ),
+    )
+    .await
+}
+
+#[command("remind")]
+#[usage("<tournament> <UTC date> [--every <minutes>]")]
+#[example("UC12 \"2021-03-20 17:00\" --every 30")]
+/// Schedules a check-in reminder in this channel, optionally repeating
+async fn jobs_remind(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, flags) = split_flags(&args, &["every"]);
+    let (tournament, due_at) = match positional.as_slice() {
+        [tournament, date] => (tournament, parse_datetime(date)),
+        _ => {
+            say(&ctx, msg.channel_id, "Expected a tournament and a date").await?;
+            return Ok(());
+        }
+    };
+    let due_at = match due_at {
+        Some(due_at) => due_at,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Invalid date, use `YYYY-MM-DD HH:MM` (UTC)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let every = match flags.get("every").map(|every| every.parse::<i64>()) {
+        None => None,
+        Some(Ok(every)) if every > 0 => Some(every),
+        Some(_) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "`--every` takes a positive amount of minutes",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let payload = ReminderPayload {
+        tournament: tournament.clone(),
+        channel_id: msg.channel_id.0,
+    };
+    schedule(
+        &ctx,
+        msg,
+        ScheduledJob::new(
+            JobKind::CheckInReminder,
+            due_at,
+            bson::to_document(&payload).expect("Bad document"),
+            every,
+        ),
+    )
+    .await
+}
+
+// Saves a job, unless its tournament doesn't exist
+async fn schedule(ctx: &Context, msg: &Message, job: ScheduledJob) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = job
+        .payload
+        .get_str("tournament")
+        .unwrap_or_default()
+        .to_string();
+    match db.tournaments.get_tournament(&tournament).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    }
+
+    let reply = match db.jobs.schedule(&job).await {
+        Ok(()) => {
+            tracing::info!(
+                target: "audit",
+                "{} scheduled {} job {} at {}",
+                msg.author.id,
+                job.kind,
+                job._id,
+                *job.due_at
+            );
+            format!(
+                "Scheduled {} for {} (ID {})",
+                job.kind,
+                job.due_at.format("%Y-%m-%d %H:%M UTC"),
+                job._id
+            )
+        }
+        Err(err) => err.to_string(),
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+// Guild the settings commands apply to, the UC server if used in DMs
+fn settings_guild(msg: &Message) -> u64 {
+    msg.guild_id
+        .map_or(crate::discord::UC_GUILD_ID, |guild_id| guild_id.0)
+}
+
+// Parses a channel mention or ID
+fn parse_channel_arg(arg: Option<&str>) -> Result<u64, &'static str> {
+    match arg {
+        Some(arg) => serenity::utils::parse_channel(arg)
+            .or_else(|| arg.parse::<u64>().ok())
+            .ok_or("Channel provided was not valid (use a mention or ID)"),
+        None => Err("Missing argument (channel)"),
+    }
+}
+
+// Parses a role mention or ID
+fn parse_role_arg(arg: Option<&str>) -> Result<u64, &'static str> {
+    match arg {
+        Some(arg) => serenity::utils::parse_role(arg)
+            .or_else(|| arg.parse::<u64>().ok())
+            .ok_or("Role provided was not valid (use a mention or ID)"),
+        None => Err("Missing argument (role)"),
+    }
+}
+
+#[command]
+#[sub_commands(
+    settings_show,
+    settings_add_bot_channel,
+    settings_remove_bot_channel,
+    settings_set_log_channel,
+    settings_add_staff_role,
+    settings_remove_staff_role
+)]
+/// Shows the settings of this server, same as `.settings show`
+async fn settings(ctx: &Context, msg: &Message) -> CommandResult {
+    show_settings(&ctx, msg).await
+}
+
+#[command("show")]
+/// Shows the settings of this server
+async fn settings_show(ctx: &Context, msg: &Message) -> CommandResult {
+    show_settings(&ctx, msg).await
+}
+
+async fn show_settings(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let settings = match db.settings.get_guild(settings_guild(msg)).await {
+        Ok(settings) => settings,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    crate::discord::cache_guild_settings(&ctx, settings.clone()).await;
+
+    let channel = |id: Option<u64>| id.map_or("-".to_string(), |id| format!("<#{}>", id));
+    let bot_channels: Vec<String> = settings
+        .allowed_bot_channels
+        .iter()
+        .map(|id| format!("<#{}>", id))
+        .collect();
+
+    let mut report = Report::new(
+        &format!("Settings of {}", settings.guild_id),
+        &["Setting", "Value"],
+    );
+    report.push_row(vec![
+        "Bot channels".to_string(),
+        if bot_channels.is_empty() {
+            "-".to_string()
+        } else {
+            bot_channels.join(", ")
+        },
+    ]);
+    report.push_row(vec![
+        "Check-in log".to_string(),
+        channel(settings.check_in_log_channel),
+    ]);
+    report.push_row(vec![
+        "Registration channel".to_string(),
+        channel(settings.registration_channel),
+    ]);
+    report.push_row(vec![
+        "Staff roles".to_string(),
+        if settings.staff_roles.is_empty() {
+            format!("Any role named `{}`", settings.staff_role_name)
+        } else {
+            settings
+                .staff_roles
+                .iter()
+                .map(|id| format!("<@&{}>", id))
+                .collect::<Vec<String>>()
+                .join(", ")
+        },
+    ]);
+    report.push_note("Use `.settings add_bot_channel #channel` to allow commands in a channel");
+    report.push_note("Use `.settings set_log_channel #channel` to move the check-in log");
+    report.push_note("Use `.settings add_staff_role @role` to allow staff commands for a role");
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command("add_bot_channel")]
+#[usage("<channel>")]
+#[example("#bot-spam")]
+/// Allows player and tournament commands in a channel
+async fn settings_add_bot_channel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let channel_id = match parse_channel_arg(args.current()) {
+        Ok(channel_id) => channel_id,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db
+        .settings
+        .add_bot_channel(settings_guild(msg), channel_id)
+        .await
+    {
+        Ok(settings) => {
+            crate::discord::cache_guild_settings(&ctx, settings).await;
+            tracing::info!(target: "audit", "{} allowed bot commands in {}", msg.author.id, channel_id);
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("Commands are allowed in <#{}>", channel_id),
+            )
+            .await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("remove_bot_channel")]
+#[usage("<channel>")]
+#[example("#bot-spam")]
+/// Disallows player and tournament commands in a channel
+async fn settings_remove_bot_channel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let channel_id = match parse_channel_arg(args.current()) {
+        Ok(channel_id) => channel_id,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db
+        .settings
+        .remove_bot_channel(settings_guild(msg), channel_id)
+        .await
+    {
+        Ok(settings) => {
+            crate::discord::cache_guild_settings(&ctx, settings).await;
+            tracing::info!(target: "audit", "{} disallowed bot commands in {}", msg.author.id, channel_id);
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("Commands are not allowed in <#{}> anymore", channel_id),
+            )
+            .await?;
+        }
+        Err(crate::database::DatabaseError::NotFound) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("<#{}> is not a bot channel", channel_id),
+            )
+            .await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("set_log_channel")]
+#[usage("<channel/none>")]
+#[example("#check-in-log")]
+/// Sets the channel check-in problems are reported in
+///
+/// Without a log channel, the channel called `check-in-log` is used.
+async fn settings_set_log_channel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let channel_id = match args.current() {
+        Some("none") => None,
+        arg => match parse_channel_arg(arg) {
+            Ok(channel_id) => Some(channel_id),
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db
+        .settings
+        .set_check_in_log_channel(settings_guild(msg), channel_id)
+        .await
+    {
+        Ok(settings) => {
+            crate::discord::cache_guild_settings(&ctx, settings).await;
+            tracing::info!(target: "audit", "{} set the check-in log to {:?}", msg.author.id, channel_id);
+            let reply = match channel_id {
+                Some(channel_id) => format!("Check-in problems are reported in <#{}>", channel_id),
+                None => "Check-in problems are reported in `#check-in-log`".to_string(),
+            };
+            say(&ctx, msg.channel_id, reply).await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("add_staff_role")]
+#[usage("<role>")]
+#[example("@UC Staff")]
+/// Allows a role to use staff commands
+///
+/// As long as no staff role is set, every role called `Staff` counts.
+async fn settings_add_staff_role(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let role_id = match parse_role_arg(args.current()) {
+        Ok(role_id) => role_id,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db
+        .settings
+        .add_staff_role(settings_guild(msg), role_id)
+        .await
+    {
+        Ok(settings) => {
+            crate::discord::cache_guild_settings(&ctx, settings).await;
+            tracing::info!(target: "audit", "{} added staff role {}", msg.author.id, role_id);
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("<@&{}> can use staff commands", role_id),
+            )
+            .await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command("remove_staff_role")]
+#[usage("<role>")]
+#[example("@UC Staff")]
+/// Disallows a role to use staff commands
+///
+/// Removing the last staff role makes every role called `Staff` count again.
+async fn settings_remove_staff_role(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let role_id = match parse_role_arg(args.current()) {
+        Ok(role_id) => role_id,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db
+        .settings
+        .remove_staff_role(settings_guild(msg), role_id)
+        .await
+    {
+        Ok(settings) => {
+            crate::discord::cache_guild_settings(&ctx, settings).await;
+            tracing::info!(target: "audit", "{} removed staff role {}", msg.author.id, role_id);
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("<@&{}> can't use staff commands anymore", role_id),
+            )
+            .await?;
+        }
+        Err(crate::database::DatabaseError::NotFound) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("<@&{}> is not a staff role", role_id),
+            )
+            .await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+    Ok(())
+}
+
+#[command]
+#[usage("[--tournament <shorthand>] <rows or attached file>")]
+#[example("\n@user,username\n@other_user,other_username")]
+/// Registers a list of players to the active tournament, bypassing restrictions.
+/// Every line is a `discord_mention,tetrio_username` pair, either in the message or in an attached text/CSV file.
+/// Rows are registered one after another, a failing row doesn't stop the rest.
+/// Use `--tournament` to pick the tournament if more than one is active.
+async fn staff_register_bulk(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    // Leaves room between rows, every registration requests the player from the Tetrio API
+    const ROW_DELAY: std::time::Duration = std::time::Duration::from_millis(1500);
+    const MAX_LISTED_FAILURES: usize = 20;
+
+    let (_, flags) = split_flags(&args, &["tournament"]);
+    let tournament = flags.get("tournament").map(|s| s.as_str());
+
+    let mut text = args.rest().to_string();
+    if let Some(attachment) = msg.attachments.first() {
+        let content = attachment.download().await?;
+        text.push('\n');
+        text.push_str(&String::from_utf8_lossy(&content));
+    }
+
+    let rows = parse_registration_rows(&text);
+    if rows.is_empty() {
+        say(&ctx, msg.channel_id, "No rows provided").await?;
+        return Ok(());
+    }
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let db = crate::discord::get_database(&ctx).await;
+    let mut registered = 0;
+    let mut failures: Vec<(String, String)> = Vec::new();
+
+    for (i, (line, parsed)) in rows.into_iter().enumerate() {
+        let (discord_id, username) = match parsed {
+            Ok(pair) => pair,
+            Err(err) => {
+                failures.push((line, err));
+                continue;
+            }
+        };
+
+        if let Some(guild_id) = msg.guild_id {
+            if guild_id.member(&ctx.http, discord_id).await.is_err() {
+                failures.push((line, "User is not in the server".to_string()));
+                continue;
+            }
+        }
+
+        if i > 0 {
+            tokio::time::sleep(ROW_DELAY).await;
+        }
+
+        match db
+            .tournaments
+            .register_to_active(
+                &db.players,
+                tournament,
+                Some(username.as_str()),
+                discord_id,
+                true,
+                Some(message_ref(msg)),
+            )
+            .await
+        {
+            Ok((_, tournament, _)) => {
+                registered += 1;
+                update_participant_role(&ctx, &tournament, discord_id, true).await;
+            }
+            Err(err) => failures.push((line, err.to_string())),
+        }
+    }
+    typing.stop();
+
+    tracing::info!(
+        target: "audit",
+        "{} bulk registered {} players, {} failed",
+        msg.author.id,
+        registered,
+        failures.len()
+    );
+
+    let mut embed = CreateEmbed::default();
+    embed.title("Bulk registration");
+    embed.description(format!(
+        "Registered {} players, {} rows failed",
+        registered,
+        failures.len()
+    ));
+    for (line, err) in failures.iter().take(MAX_LISTED_FAILURES) {
+        embed.field(line, err, false);
+    }
+    if failures.len() > MAX_LISTED_FAILURES {
+        embed.footer(|f| {
+            f.text(format!(
+                "{} more failures not shown",
+                failures.len() - MAX_LISTED_FAILURES
+            ))
+        });
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.set_embed(embed).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+    Ok(())
+}
+
+#[command]
+/// Lists players that share a Discord ID or a Tetrio ID.
+/// The unique indexes on the player collection can't be created while these exist, fix them with `.staff_unlink`.
+async fn find_duplicates(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let groups = match db.players.find_duplicate_links().await {
+        Ok(groups) => groups,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let mut report = Report::new(
+        "Duplicate player entries",
+        &["Shared", "Tetrio ID", "Username", "Discord ID", "Verified"],
+    );
+    for group in &groups {
+        let shared = match &group.key {
+            DuplicateKey::DiscordId(discord_id) => format!("Discord {}", discord_id),
+            DuplicateKey::TetrioId(tetrio_id) => format!("Tetrio {}", tetrio_id),
+        };
+        for entry in &group.entries {
+            report.push_row(vec![
+                shared.clone(),
+                entry.tetrio_id.clone(),
+                entry
+                    .tetrio_data
+                    .as_ref()
+                    .map_or("?".to_string(), |data| data.username.clone()),
+                entry
+                    .discord_id
+                    .map_or("-".to_string(), |id| id.to_string()),
+                if entry.verified_link { "yes" } else { "no" }.to_string(),
+            ]);
+        }
+    }
+    if groups.is_empty() {
+        report.push_note("No duplicates, the unique indexes can be created");
+    } else {
+        report.push_note(&format!("{} conflicts", groups.len()));
+    }
+    send_report(&ctx, msg.channel_id, &report).await
+}
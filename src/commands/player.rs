@@ -1,54 +1,181 @@
+use std::str::FromStr;
+
 use serenity::framework::standard::{macros::command, Args, CommandResult};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::utils;
 
-use crate::database::players::PlayerEntry;
+use crate::database::players::{AccountStatus, PlayerEntry, VerifyLinkError};
+use crate::database::tournaments::RegistrationError;
 use crate::database::DatabaseError;
 use crate::discord;
+use crate::discord::messages::{self, Language, LocalizedMessage, MessageKey};
 use crate::discord::util::*;
+use crate::discord::{DegradedServings, RefreshCooldowns};
+use crate::tetrio::leaderboard::LeaderboardUser;
+use crate::tetrio::{self, Rank, TetrioApiError};
+
+// How long a user has to wait between two forced `.stats` refreshes
+const FRESH_COOLDOWN_SECONDS: i64 = 60;
+
+// Data older than this gets a hint about `--fresh` in the footer
+const FRESH_HINT_MINUTES: i64 = 10;
 
 #[command]
-#[usage("[tetrio username / tetrio id / discord mention]")]
+#[usage("[tetrio username / tetrio id / discord mention] [--fresh]")]
 #[example("caboozled_pie")]
 #[example("5e47696db7c60f23a497ee6c")]
 #[example("@IceDynamix")]
+#[example("caboozled_pie --fresh")]
 /// Retrieve a players stats by username, Tetrio ID or Discord user ping.
 /// If neither is passed then it will use the Tetr.io account linked with the current Discord user.
+/// Stats can be up to 45 minutes old, use `--fresh` (or `-f`/`fresh`) to request them again, once per minute.
 async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let database = discord::get_database(&ctx).await;
 
-    let lookup = if let Some(content) = args.current() {
+    let (positional, flags) = split_flags(&args, &[]);
+    let mut fresh = flags.contains_key("fresh");
+    let positional: Vec<String> = positional
+        .into_iter()
+        .filter(|arg| {
+            let is_flag = arg == "-f" || arg.eq_ignore_ascii_case("fresh");
+            fresh |= is_flag;
+            !is_flag
+        })
+        .collect();
+
+    if fresh {
+        let allowed = match ctx.data.read().await.get::<RefreshCooldowns>() {
+            Some(cooldowns) => cooldowns.lock().await.try_refresh(
+                msg.author.id.0,
+                chrono::Duration::seconds(FRESH_COOLDOWN_SECONDS),
+                chrono::Utc::now(),
+            ),
+            None => Ok(()),
+        };
+        if let Err(again_at) = allowed {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "You can only refresh once per minute, try again {}",
+                    discord_timestamp(again_at, 'R')
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    // Only usernames can be looked up without the database, mentions need the link
+    let mut username = None;
+    let lookup = if let Some(content) = positional.get(0).map(|s| s.as_str()) {
         if let Some(id) = utils::parse_mention(content) {
             (
-                database.players.get_player_by_discord(id),
+                database.players.get_player_by_discord(id).await,
                 "Mentioned user is not linked to a Tetr.io user",
             )
         } else {
+            username = Some(content.to_lowercase());
             (
                 database
                     .players
-                    .get_player_by_tetrio(&content.to_lowercase()),
+                    .get_player_by_tetrio(&content.to_lowercase())
+                    .await,
                 "Player does not exist",
             )
         }
     } else {
         (
-            database.players.get_player_by_discord(msg.author.id.0),
+            database
+                .players
+                .get_player_by_discord(msg.author.id.0)
+                .await,
             "Your account is not linked to a Tetr.io user",
         )
     };
 
-    match lookup.0.unwrap() {
+    let entry = match lookup.0 {
+        Ok(entry) => entry,
+        Err(err) => {
+            tracing::warn!("{}", err);
+            match username {
+                Some(username) if err.is_connection_error() => {
+                    return stats_live(&ctx, msg, &username).await;
+                }
+                _ => {
+                    say(&ctx, msg.channel_id, err).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    match entry {
         None => {
-            msg.channel_id.say(&ctx.http, lookup.1).await?;
+            say(&ctx, msg.channel_id, lookup.1).await?;
         }
         Some(entry) => {
-            let updated_entry = database.players.update_player(&entry.tetrio_id).unwrap();
+            let (updated_entry, is_stale) = match database
+                .players
+                .update_player(&entry.tetrio_id, fresh)
+                .await
+            {
+                Ok(updated_entry) if updated_entry.status != AccountStatus::Active => {
+                    let embed = unavailable_account_embed(&updated_entry, updated_entry.status);
+                    msg.channel_id
+                        .send_message(&ctx.http, |m| m.set_embed(embed))
+                        .await?;
+                    return Ok(());
+                }
+                Ok(updated_entry) => (updated_entry, false),
+                Err(DatabaseError::NotFound) => {
+                    let embed = unavailable_account_embed(&entry, AccountStatus::NotFound);
+                    msg.channel_id
+                        .send_message(&ctx.http, |m| m.set_embed(embed))
+                        .await?;
+                    return Ok(());
+                }
+                Err(err) if is_tetrio_down(&err) && entry.tetrio_data.is_some() => (entry, true),
+                Err(err) => {
+                    tracing::warn!("{}", err);
+                    let reply = match &err {
+                        DatabaseError::TetrioApiError(api_err) => {
+                            tetrio_error_reply(api_err).to_string()
+                        }
+                        _ => err.to_string(),
+                    };
+                    say(&ctx, msg.channel_id, reply).await?;
+                    return Ok(());
+                }
+            };
+
+            let active = database.tournaments.get_active().await.ok().flatten();
+            let mut embed = player_data_to_embed(&updated_entry, active.as_ref());
+            if let Some(refreshed_at) = updated_entry.refreshed_at() {
+                let minutes = (chrono::Utc::now() - refreshed_at).num_minutes().max(0);
+                let mut footer = match minutes {
+                    0 => "Data from just now".to_string(),
+                    1 => "Data from 1 minute ago".to_string(),
+                    minutes => format!("Data from {} minutes ago", minutes),
+                };
+                if is_stale {
+                    footer = format!("{} ({})", TETRIO_DOWN_MESSAGE, footer.to_lowercase());
+                } else if minutes >= FRESH_HINT_MINUTES {
+                    footer.push_str(", use --fresh to refresh");
+                }
+                if updated_entry.verified_link {
+                    footer.push_str(" • Link verified through the Tetr.io bio");
+                }
+                embed.footer(|f| f.text(footer));
+            } else if is_stale {
+                embed.footer(|f| {
+                    f.text(format!("{} (data might be outdated)", TETRIO_DOWN_MESSAGE))
+                });
+            }
+
             msg.channel_id
-                .send_message(&ctx.http, |m| {
-                    m.set_embed(player_data_to_embed(&updated_entry))
-                })
+                .send_message(&ctx.http, |m| m.set_embed(embed))
                 .await?;
         }
     }
@@ -56,52 +183,402 @@ async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     Ok(())
 }
 
+// Serves `.stats` straight from the Tetrio API while the database is down
+// Nothing is written, and whether the player is linked can't be known
+async fn stats_live(ctx: &Context, msg: &Message, username: &str) -> CommandResult {
+    let user = match tetrio::user::request(username).await {
+        Ok(response) => response.data.user.data,
+        Err(TetrioApiError::NotFound) => {
+            say(&ctx, msg.channel_id, "Player does not exist on Tetr.io").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            tracing::warn!("{}", err);
+            say(&ctx, msg.channel_id, tetrio_error_reply(&err)).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(degraded) = ctx.data.read().await.get::<DegradedServings>() {
+        degraded.lock().await.0 += 1;
+    }
+
+    let mut embed = leaderboard_user_to_embed(&user);
+    embed.footer(|f| f.text(DATABASE_DOWN_MESSAGE));
+    msg.channel_id
+        .send_message(&ctx.http, |m| m.set_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
 #[command]
-#[usage("<tetr.io username or id>")]
+#[usage("<tetrio username / tetrio id>")]
+#[example("caboozled_pie")]
+/// Lists the previous usernames of a player, as far as the bot noticed them.
+/// Players can be looked up by their previous usernames in other commands as well.
+async fn aka(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let username = match args.current() {
+        Some(username) => username.to_lowercase(),
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "No username provided (`.aka <username>`)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let db = discord::get_database(&ctx).await;
+    let reply = match db.players.get_player_by_tetrio(&username).await {
+        Ok(Some(player)) => {
+            let current = player
+                .tetrio_data
+                .as_ref()
+                .map_or(player.tetrio_id.as_str(), |data| data.username.as_str());
+            if player.previous_usernames.is_empty() {
+                format!("There are no known previous usernames of `{}`", current)
+            } else {
+                // stored oldest first, the most recent rename is the most interesting
+                let names: Vec<String> = player
+                    .previous_usernames
+                    .iter()
+                    .rev()
+                    .map(|name| format!("`{}`", name))
+                    .collect();
+                format!("`{}` was previously known as {}", current, names.join(", "))
+            }
+        }
+        Ok(None) => format!("Tetr.io user `{}` was not found", username),
+        Err(err) => {
+            tracing::warn!("{}", err);
+            err.to_string()
+        }
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("match_ready")]
+#[usage("<tetrio username / tetrio id> <tetrio username / tetrio id>")]
+#[example("caboozled_pie icedynamix")]
+/// Compares the stats of two players side by side, like before a bracket match.
+/// Warns if one of them isn't registered to the ongoing tournament, the comparison is shown anyway.
+async fn versus(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let usernames = match (args.single::<String>(), args.single::<String>()) {
+        (Ok(first), Ok(second)) => [first.to_lowercase(), second.to_lowercase()],
+        _ => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Two usernames are needed (`.versus <user1> <user2>`)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    if usernames[0] == usernames[1] {
+        say(
+            &ctx,
+            msg.channel_id,
+            "Two different players are needed (`.versus <user1> <user2>`)",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let db = discord::get_database(&ctx).await;
+    let mut players = Vec::new();
+    for username in &usernames {
+        let reply = match db.players.update_player(username, false).await {
+            Ok(entry) if entry.tetrio_data.is_some() => {
+                players.push(entry);
+                continue;
+            }
+            Ok(_) | Err(DatabaseError::NotFound) => {
+                format!("Player `{}` does not exist on Tetr.io", username)
+            }
+            Err(DatabaseError::TetrioApiError(err)) => {
+                tracing::warn!("{}", err);
+                tetrio_error_reply(&err).to_string()
+            }
+            Err(err) => {
+                tracing::warn!("{}", err);
+                err.to_string()
+            }
+        };
+        say(&ctx, msg.channel_id, reply).await?;
+        return Ok(());
+    }
+
+    let tournament = db.tournaments.get_active().await.ok().flatten();
+    let warnings: Vec<String> = match &tournament {
+        Some(tournament) => players
+            .iter()
+            .filter(|player| !tournament.player_is_registered(player))
+            .map(|player| {
+                let username = player.tetrio_data.as_ref().unwrap().username.as_str();
+                format!("⚠️ `{}` is not registered to {}", username, tournament.name)
+            })
+            .collect(),
+        None => {
+            vec!["⚠️ There is no tournament ongoing, registrations weren't checked".to_string()]
+        }
+    };
+
+    let mut embed = versus_embed(
+        players[0].tetrio_data.as_ref().unwrap(),
+        players[1].tetrio_data.as_ref().unwrap(),
+    );
+    if let Some(tournament) = &tournament {
+        badge_embed(&mut embed, tournament);
+    }
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            if !warnings.is_empty() {
+                m.content(warnings.join("\n"));
+            }
+            m.set_embed(embed).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+// Tetra League matches shown by `.recent`
+const RECENT_MATCHES: usize = 5;
+
+#[command]
+#[usage("[tetr.io username or id]")]
+#[example("caboozled_pie")]
+/// Shows the last 5 Tetra League matches of a player with their score and opponent.
+/// Uses your linked account if no username is provided.
+async fn recent(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = discord::get_database(&ctx).await;
+
+    let tetrio_id = match args.current() {
+        Some(username) => username.to_lowercase(),
+        None => match db.players.get_player_by_discord(msg.author.id.0).await {
+            Ok(Some(entry)) => entry.tetrio_id,
+            Ok(None) => {
+                say(&ctx, msg.channel_id, "There is no Tetr.io account linked to you right now, please provide a username. `.recent [username]`").await?;
+                return Ok(());
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let entry = match db.players.update_player(&tetrio_id, false).await {
+        Ok(entry) if entry.tetrio_data.is_some() => entry,
+        Ok(_) | Err(DatabaseError::NotFound) => {
+            say(&ctx, msg.channel_id, "Player does not exist on Tetr.io").await?;
+            return Ok(());
+        }
+        Err(DatabaseError::TetrioApiError(api_err)) => {
+            say(&ctx, msg.channel_id, tetrio_error_reply(&api_err)).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let username = &entry.tetrio_data.as_ref().unwrap().username;
+
+    let records = match tetrio::stream::request(&entry.tetrio_id).await {
+        Ok(response) => response.data.records,
+        Err(err) => {
+            tracing::warn!("{}", err);
+            say(&ctx, msg.channel_id, tetrio_error_reply(&err)).await?;
+            return Ok(());
+        }
+    };
+    let matches = tetrio::stream::recent_matches(&records, &entry.tetrio_id, RECENT_MATCHES);
+
+    let lines: Vec<String> = matches
+        .iter()
+        .map(|played| {
+            format!(
+                "**{}** {}-{} vs {} ({})",
+                if played.won() { "W" } else { "L" },
+                played.rounds_won,
+                played.rounds_lost,
+                played.opponent,
+                discord_timestamp(played.played_at, 'R')
+            )
+        })
+        .collect();
+    let description = if lines.is_empty() {
+        "No recent Tetra League matches".to_string()
+    } else {
+        lines.join("\n")
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!("Recent matches of {}", username))
+                    .url(format!("https://ch.tetr.io/u/{}", entry.tetrio_id))
+                    .description(description)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[usage("[page]")]
+#[example("")]
+#[example("3")]
+/// Shows the linked players of the server sorted by TR, 10 per page.
+/// Players are only listed while they're ranked.
+async fn leaderboard(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const PAGE_SIZE: usize = 10;
+
+    let db = discord::get_database(&ctx).await;
+    let page = args
+        .current()
+        .and_then(|p| p.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let offset = (page - 1) * PAGE_SIZE;
+
+    let (players, total) = match db.players.get_leaderboard_page(offset, PAGE_SIZE).await {
+        Ok(result) => result,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let pages = ((total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    if players.is_empty() {
+        let reply = format!("There are only {} pages", pages);
+        say(&ctx, msg.channel_id, reply).await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = players
+        .iter()
+        .enumerate()
+        .filter_map(|(i, player)| {
+            let data = player.tetrio_data.as_ref()?;
+            let rank = Rank::from_str(&data.league.rank).unwrap_or(Rank::Unranked);
+            let line = format!(
+                "`#{}` {} {} ({:.0} TR)",
+                offset + i + 1,
+                rank.to_emoji(),
+                data.username,
+                data.league.rating
+            );
+            Some(if player.discord_id == Some(msg.author.id.0) {
+                format!("**{}**", line)
+            } else {
+                line
+            })
+        })
+        .collect();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Server leaderboard")
+                    .description(lines.join("\n"))
+                    .footer(|f| {
+                        f.text(format!(
+                            "Page {}/{}, {} linked players, use .leaderboard <page> for more",
+                            page, pages, total
+                        ))
+                    })
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[usage("[tetr.io username or id]")]
 #[example("caboozled_pie")]
 #[example("5e47696db7c60f23a497ee6c")]
+#[example("")]
 /// Will make the bot "remember" that you are a specified Tetr.io user.
 /// Useful for registration or for easy stat/player lookup
 /// It will retain the link, even if you change your username
+/// Without a username, the Tetr.io account that has your Discord connected in its settings is offered instead.
 async fn link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let reply = match args.current() {
+    let username = match args.current() {
+        Some(username) => Some(username.to_string()),
+        None => match tetrio::search::request(msg.author.id.0).await {
+            Ok(response) => match response.data {
+                Some(found) => {
+                    let prompt = format!(
+                        "No tetr.io user was specified, but `{}` has your Discord connected on Tetr.io. Link it?",
+                        found.user.username
+                    );
+                    if confirm_prompt(&ctx, &msg, &prompt).await? {
+                        Some(found.user.id)
+                    } else {
+                        react_deny(&ctx, &msg).await;
+                        return Ok(());
+                    }
+                }
+                None => None,
+            },
+            Err(err) => {
+                tracing::warn!("Could not search the linked account: {}", err);
+                None
+            }
+        },
+    };
+
+    let reply = match username {
         None => {
             react_deny(&ctx, &msg).await;
             Some(
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "No tetr.io user was specified, run `help link` for more information",
-                    )
-                    .await?,
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    "No tetr.io user was specified, run `help link` for more information",
+                )
+                .await?,
             )
         }
-        Some(args) => {
+        Some(username) => {
             let db = crate::discord::get_database(ctx).await;
-            match db.players.link(msg.author.id.0, args) {
+            match db.players.link(msg.author.id.0, &username).await {
                 Ok(entry) => {
                     rename_user_to_tetrio(&ctx, msg, &entry).await?;
                     react_confirm(&ctx, &msg).await;
-                    Some(msg.channel_id
-                        .send_message(&ctx.http, |m| m.set_embed(player_data_to_embed(&entry)))
-                        .await?)
+                    let active = db.tournaments.get_active().await.ok().flatten();
+                    Some(
+                        msg.channel_id
+                            .send_message(&ctx.http, |m| {
+                                m.set_embed(player_data_to_embed(&entry, active.as_ref()))
+                            })
+                            .await?,
+                    )
                 }
-                Err(err) => match err {
-                    DatabaseError::DuplicateDiscordEntry => {
-                        Some(msg.channel_id
-                            .say(&ctx.http, "You're already linked to a Tetr.io user! Use the `unlink` command before linking to another Tetr.io user")
-                            .await?)
-                    }
-                    DatabaseError::DuplicateTetrioEntry => {
-                        Some(msg.channel_id
-                            .say(&ctx.http, "You're trying to link a user who is already linked to someone else!")
-                            .await?)
-                    }
-                    _ => {
+                Err(err) => {
+                    if !matches!(
+                        err,
+                        DatabaseError::DuplicateDiscordEntry
+                            | DatabaseError::DuplicateTetrioEntry
+                            | DatabaseError::NotFound
+                    ) {
                         tracing::warn!("{}", err);
-                        Some(msg.channel_id.say(&ctx.http, err).await?)
                     }
-                },
+                    let language = messages::language_of(&db, msg.author.id.0).await;
+                    let reply = messages::database_error(&err).render(language);
+                    Some(say(&ctx, msg.channel_id, reply).await?)
+                }
             }
         }
     };
@@ -111,6 +588,114 @@ async fn link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     Ok(())
 }
 
+#[command]
+#[usage("<tetr.io username or id>")]
+#[example("caboozled_pie")]
+/// Starts linking you to a Tetr.io user in a way that proves the account is yours.
+/// You get a token to put in your Tetr.io bio, then use `confirm_link` to finish linking.
+/// Verified links are marked as such in `stats`.
+async fn verify_link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let username = match args.current() {
+        Some(username) => username,
+        None => {
+            react_deny(&ctx, &msg).await;
+            let reply = say(
+                &ctx,
+                msg.channel_id,
+                "No tetr.io user was specified, run `help verify_link` for more information",
+            )
+            .await?;
+            delay_delete(&ctx, Some(reply)).await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(ctx).await;
+    let reply = match db
+        .players
+        .start_verified_link(msg.author.id.0, username)
+        .await
+    {
+        Ok((entry, pending)) => {
+            react_confirm(&ctx, &msg).await;
+            let username = entry
+                .tetrio_data
+                .map_or(entry.tetrio_id, |data| data.username);
+            format!(
+                "Put `{}` anywhere in the bio of {} (Tetr.io → Config → Account → About me), then use `.confirm_link` {}. You can remove it again afterwards.",
+                pending.token,
+                username,
+                discord_timestamp(*pending.expires_at, 'R')
+            )
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            match err {
+                DatabaseError::DuplicateDiscordEntry => "You're already linked to a Tetr.io user! Use the `unlink` command before linking to another Tetr.io user".to_string(),
+                DatabaseError::DuplicateTetrioEntry => "You're trying to link a user who is already linked to someone else! Contact staff if that account is yours".to_string(),
+                DatabaseError::NotFound => "Could not find that user on Tetr.io, check the spelling!".to_string(),
+                DatabaseError::TetrioApiError(api_err) => {
+                    tracing::warn!("{}", api_err);
+                    tetrio_error_reply(&api_err).to_string()
+                }
+                _ => {
+                    tracing::warn!("{}", err);
+                    err.to_string()
+                }
+            }
+        }
+    };
+
+    let reply = say(&ctx, msg.channel_id, reply).await?;
+    delay_delete(&ctx, Some(reply)).await?;
+    Ok(())
+}
+
+#[command]
+/// Finishes linking you to the Tetr.io user from `verify_link`, once the token is in the bio.
+/// Tetr.io caches profiles for a minute, so try again shortly if the token isn't found right away.
+async fn confirm_link(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(ctx).await;
+    let reply = match db.players.confirm_verified_link(msg.author.id.0).await {
+        Ok(entry) => {
+            rename_user_to_tetrio(&ctx, msg, &entry).await?;
+            react_confirm(&ctx, &msg).await;
+            let active = db.tournaments.get_active().await.ok().flatten();
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.content("Your account is verified and linked!")
+                        .set_embed(player_data_to_embed(&entry, active.as_ref()))
+                        .allowed_mentions(|am| am.empty_parse())
+                })
+                .await?
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            let reply = match err {
+                VerifyLinkError::TokenMismatch { .. } => format!(
+                    "{}. Tetr.io caches profiles for a minute, so try again shortly if you just changed it",
+                    err
+                ),
+                VerifyLinkError::DatabaseError(DatabaseError::TetrioApiError(api_err)) => {
+                    tracing::warn!("{}", api_err);
+                    tetrio_error_reply(&api_err).to_string()
+                }
+                VerifyLinkError::DatabaseError(DatabaseError::DuplicateDiscordEntry) => {
+                    "You're already linked to a Tetr.io user!".to_string()
+                }
+                VerifyLinkError::DatabaseError(DatabaseError::DuplicateTetrioEntry) => {
+                    "Someone else linked this user in the meantime, contact staff if that account is yours".to_string()
+                }
+                _ => err.to_string(),
+            };
+            say(&ctx, msg.channel_id, reply).await?
+        }
+    };
+
+    delay_delete(&ctx, Some(reply)).await?;
+    Ok(())
+}
+
 pub async fn rename_user_to_tetrio(
     ctx: &&Context,
     msg: &Message,
@@ -122,9 +707,12 @@ pub async fn rename_user_to_tetrio(
             .edit(&ctx.http, |member| member.nickname(&tetrio_data.username))
             .await
         {
-            msg.channel_id
-                .say(&ctx.http, format!("Could not change nickname ({})", e))
-                .await?;
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("Could not change nickname ({})", e),
+            )
+            .await?;
         }
     }
 
@@ -138,7 +726,7 @@ async fn unlink(ctx: &Context, msg: &Message) -> CommandResult {
 
     let mut player_entry: Option<PlayerEntry> = None;
 
-    let unlink_reply = match db.players.unlink_by_discord(msg.author.id.0) {
+    let unlink_reply = match db.players.unlink_by_discord(msg.author.id.0).await {
         Ok(entry) => {
             react_confirm(&ctx, &msg).await;
             player_entry = Some(entry);
@@ -146,33 +734,249 @@ async fn unlink(ctx: &Context, msg: &Message) -> CommandResult {
         }
         Err(err) => match err {
             DatabaseError::NotFound => {
-                Some(msg.channel_id.say(&ctx.http, "There is no Tetr.io user linked to you right now, use the `link` command to link one").await?)
+                Some(say(&ctx, msg.channel_id, "There is no Tetr.io user linked to you right now, use the `link` command to link one").await?)
             }
             _ => {
-                Some(msg.channel_id.say(&ctx.http, err).await?)
+                Some(say(&ctx, msg.channel_id, err).await?)
             }
         },
     };
 
+    // Only tournaments the player was registered to get a message
     if let Some(entry) = player_entry {
-        let unregister_reply = if db
+        let withdrawn = match db
             .tournaments
-            .unregister_by_tetrio(&db.players, &entry.tetrio_id)
-            .is_ok()
+            .withdraw_from_active(&entry, Some("unlinked"))
+            .await
         {
-            Some(
-                msg.channel_id
-                    .say(&ctx.http, "Unregistered from the ongoing tournament")
-                    .await?,
-            )
-        } else {
-            None
+            Ok(withdrawn) => withdrawn,
+            Err(err) => {
+                tracing::warn!(
+                    "Could not withdraw {} after unlinking: {}",
+                    entry.tetrio_id,
+                    err
+                );
+                Vec::new()
+            }
         };
 
-        delay_delete(&ctx, unregister_reply).await?;
+        let mut replies = Vec::new();
+        for tournament in &withdrawn {
+            fill_from_waitlist(&ctx, &db, tournament).await;
+            replies.push(
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    badged(tournament, &format!("Withdrew from {}", tournament.name)),
+                )
+                .await?,
+            );
+        }
+        for reply in replies {
+            delay_delete(&ctx, Some(reply)).await?;
+        }
     }
 
     delay_delete(&ctx, unlink_reply).await?;
 
     Ok(())
 }
+
+#[command]
+#[usage("<on/off>")]
+#[example("on")]
+/// Whether you want to be included by name in research datasets of tournaments you took part in.
+/// If it's off (the default), you only appear under a pseudonym with your rank group.
+async fn datasharing(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let enabled = match args.current().map(|a| a.to_lowercase()).as_deref() {
+        Some("on") => true,
+        Some("off") => false,
+        _ => {
+            say(&ctx, msg.channel_id, "Expected `on` or `off`").await?;
+            return Ok(());
+        }
+    };
+
+    let db = discord::get_database(&ctx).await;
+    let reply = match db.players.set_data_sharing(msg.author.id.0, enabled).await {
+        Ok(()) if enabled => {
+            "You will be included by name, with detailed stats, in research datasets".to_string()
+        }
+        Ok(()) => "You will only appear under a pseudonym in research datasets".to_string(),
+        Err(DatabaseError::NotFound) => {
+            "There is no Tetr.io user linked to you right now, use the `link` command to link one"
+                .to_string()
+        }
+        Err(err) => err.to_string(),
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[language code]")]
+#[example("es")]
+#[example("pt")]
+#[example("en")]
+/// Sets the language the bot uses for registration and link errors, without a code it shows the current one.
+/// Available are English (`en`), Spanish (`es`) and Portuguese (`pt`), you need a linked Tetr.io account.
+async fn language(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = discord::get_database(&ctx).await;
+    let current = messages::language_of(&db, msg.author.id.0).await;
+
+    let code = match args.current() {
+        Some(code) => code,
+        None => {
+            let reply = LocalizedMessage::new(MessageKey::LanguageCurrent)
+                .with("language", current.name())
+                .with("languages", Language::list());
+            say(&ctx, msg.channel_id, reply.render(current)).await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match Language::from_code(code) {
+        None => LocalizedMessage::new(MessageKey::LanguageUnknown)
+            .with("code", code)
+            .with("languages", Language::list())
+            .render(current),
+        Some(language) => match db
+            .players
+            .set_language(msg.author.id.0, Some(language.code()))
+            .await
+        {
+            Ok(()) => LocalizedMessage::new(MessageKey::LanguageSet)
+                .with("language", language.name())
+                .render(language),
+            Err(DatabaseError::NotFound) => {
+                LocalizedMessage::new(MessageKey::NotLinked).render(current)
+            }
+            Err(err) => {
+                tracing::warn!("{}", err);
+                messages::database_error(&err).render(current)
+            }
+        },
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+// Rank, TR, RD and games of one side of `.announcement_stats`
+fn league_column(user: Option<&LeaderboardUser>) -> String {
+    match user {
+        None => "Unranked".to_string(),
+        Some(user) => {
+            let league = &user.league;
+            format!(
+                "Rank: {}\nTR: {:.0}\nRD: {:.1}\nGames: {}",
+                league.rank.parse::<Rank>().unwrap_or(Rank::Unranked),
+                league.rating,
+                league.rd.unwrap_or_default(),
+                league.gamesplayed
+            )
+        }
+    }
+}
+
+#[command]
+#[usage("[tetr.io username or id]")]
+#[example("caboozled_pie")]
+/// Compares your stats from the announcement of the ongoing tournament with your current ones.
+/// Also shows whether you currently meet the restrictions. Uses your linked account if no username is provided.
+async fn announcement_stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = discord::get_database(&ctx).await;
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let snapshot_at = match tournament.snapshot_at() {
+        Some(snapshot_at) => snapshot_at,
+        None => {
+            let reply = format!(
+                "There are no announcement stats for {} yet, they are taken once the tournament is announced",
+                tournament.name
+            );
+            say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+            return Ok(());
+        }
+    };
+
+    let tetrio_id = match args.current() {
+        Some(username) => username.to_lowercase(),
+        None => match db.players.get_player_by_discord(msg.author.id.0).await {
+            Ok(Some(entry)) => entry.tetrio_id,
+            Ok(None) => {
+                say(&ctx, msg.channel_id, "There is no Tetr.io account linked to you right now, please provide a username. `.announcement_stats [username]`").await?;
+                return Ok(());
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let entry = match db.players.update_player(&tetrio_id, false).await {
+        Ok(entry) if entry.tetrio_data.is_some() => entry,
+        Ok(_) | Err(DatabaseError::NotFound) => {
+            say(&ctx, msg.channel_id, "Player does not exist on Tetr.io").await?;
+            return Ok(());
+        }
+        Err(DatabaseError::TetrioApiError(api_err)) => {
+            say(&ctx, msg.channel_id, tetrio_error_reply(&api_err)).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let current = entry.tetrio_data.as_ref().unwrap();
+
+    let account_created_at = match tournament.restrictions.min_account_age_days {
+        Some(_) => db.players.account_created_at(&entry).await.ok().flatten(),
+        None => None,
+    };
+    let verdict = match tournament.check_player(&entry, account_created_at).await {
+        Ok(()) => "Currently meets the restrictions".to_string(),
+        Err(RegistrationError::Ineligible(_)) => {
+            "Currently does not meet the restrictions, see `.can_participate` for details"
+                .to_string()
+        }
+        Err(RegistrationError::DatabaseError(DatabaseError::TetrioApiError(api_err))) => {
+            tetrio_error_reply(&api_err).to_string()
+        }
+        Err(err) => format!("Currently does not meet the restrictions: {}", err),
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!(
+                    "{} {}: announcement vs current stats",
+                    tournament_badge(&tournament),
+                    current.username
+                ))
+                .url(format!("https://ch.tetr.io/u/{}", current._id))
+                .field(
+                    format!("Announcement ({})", snapshot_at.format("%Y-%m-%d")),
+                    league_column(tournament.snapshot_of(&entry.tetrio_id)),
+                    true,
+                )
+                .field("Current", league_column(Some(current)), true)
+                .field("Restrictions", verdict, false)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
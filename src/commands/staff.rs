@@ -1,65 +1,629 @@
+use std::collections::HashMap;
+
+use bson::{doc, DateTime as BsonDateTime};
+use chrono::Utc;
 use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::http::AttachmentType;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
+use tokio::time;
 
-use crate::database::tournaments::RegistrationError;
+use crate::bracket;
+use crate::bracket::{preview, split};
+use crate::database::flags::Feature;
+use crate::database::players::{find_by_identifier, DiscordAccountStatus};
+use crate::database::tournaments::{
+    BracketSplit, RegOrder, RegistrationError, RegistrationStatus, StaffAlert, TournamentEntry,
+    UnregisteredBy, OVERRANKED_REASON,
+};
+use crate::database::usage;
 use crate::database::DatabaseError;
+use crate::database::LocalDatabase;
+use crate::dataset;
+use crate::discord::report::{bar, csv_line, send_report, Report};
 use crate::discord::util::*;
+use crate::distribution;
+use crate::dupes::{self, DupeCandidate, DupeWeights};
+use crate::eligibility::{estimated_games_to_rd, verdict_to_lines, Style};
+use crate::review::{self, Effect, ReviewSession};
+use crate::tasks::{CancelReason, TaskHandle, TaskRegistry};
+use crate::tetrio::latency::{CacheStatus, Family, Outcome, Stats, BUCKETS_MS};
+use crate::tetrio::leaderboard::LeaderboardUser;
+use crate::tetrio::{self, Rank};
+use crate::timezone::{self, OffsetHistogram};
 
 #[command]
+/// Updates every player on the leaderboard. Can be stopped with `.tasks cancel`.
 async fn update_all(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let _update = wait_for_updates(&ctx, &msg, &db).await?;
     let typing = msg.channel_id.start_typing(&ctx.http)?;
 
+    let update_db = db.clone();
+    let (task, result) = run_long_task(&ctx, "Leaderboard update", move |task| async move {
+        update_db
+            .players
+            .update_from_leaderboard_cancellable(&task)
+            .await
+    })
+    .await;
+    typing.stop();
+    report_long_task(&ctx, &msg, &task, result).await
+}
+
+// Takes the update lock, so bulk updates of player data run one after another
+// Tells the user if the command has to wait for another update first
+async fn wait_for_updates<'a>(
+    ctx: &Context,
+    msg: &Message,
+    db: &'a LocalDatabase,
+) -> serenity::Result<tokio::sync::MutexGuard<'a, ()>> {
+    if let Some(guard) = db.try_lock_updates() {
+        return Ok(guard);
+    }
+    say(
+        &ctx,
+        msg.channel_id,
+        "Another update is running, this one starts once it's done",
+    )
+    .await?;
+    Ok(db.lock_updates().await)
+}
+
+#[command]
+/// Updates every registered player of the active tournament. Can be stopped with `.tasks cancel`.
+/// Unranked players are requested one by one, deleted accounts are skipped.
+async fn update_registered(ctx: &Context, msg: &Message) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-    match db.players.update_from_leaderboard() {
-        Ok(_) => {
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let registered = tournament.registered_players().len();
+
+    let _update = wait_for_updates(&ctx, &msg, &db).await?;
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let update_db = db.clone();
+    let (task, result) = run_long_task(&ctx, "Registered player update", move |task| async move {
+        update_db
+            .players
+            .update_registered_cancellable(&tournament, &task)
+            .await
+    })
+    .await;
+    typing.stop();
+
+    match result {
+        Ok(updated) if task.cancel_reason().is_none() => {
             react_confirm(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "Updated {} players, skipped {}",
+                    updated.len(),
+                    registered.saturating_sub(updated.len())
+                ),
+            )
+            .await?;
+            Ok(())
+        }
+        result => report_long_task(&ctx, &msg, &task, result.map(|_| ())).await,
+    }
+}
+
+#[command]
+/// Updates the registered players of the active tournament, then withdraws everyone whose current rank is above the cap.
+/// Lists the withdrawn players with their rank and tells them in their DMs. Nobody is withdrawn before the announcement snapshot is taken.
+async fn purge_overranked(ctx: &Context, msg: &Message) -> CommandResult {
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
         }
         Err(err) => {
-            tracing::warn!("{}", err);
-            msg.channel_id.say(&ctx.http, err).await?;
+            typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
         }
+    };
+    let max_rank = tournament.restrictions.max_rank;
+
+    let _update = wait_for_updates(&ctx, &msg, &db).await?;
+    let update_db = db.clone();
+    let (task, result) = run_long_task(&ctx, "Registered player update", move |task| async move {
+        update_db
+            .players
+            .update_registered_cancellable(&tournament, &task)
+            .await
+            .map(|_| ())
+    })
+    .await;
+    if result.is_err() || task.cancel_reason().is_some() {
+        typing.stop();
+        return report_long_task(&ctx, &msg, &task, result).await;
     }
 
+    let removed = db.tournaments.purge_ineligible(&db.players).await;
     typing.stop();
+    let removed = match removed {
+        Ok(removed) => removed,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    for player in &removed {
+        tracing::info!(
+            target: "audit",
+            "{} purged {} from the active tournament ({})",
+            msg.author.id,
+            player.tetrio_id,
+            OVERRANKED_REASON
+        );
+    }
+
+    let tournament = db.tournaments.get_active().await.ok().flatten();
+    let mut not_told = Vec::new();
+    if let Some(tournament) = tournament.as_ref().filter(|_| !removed.is_empty()) {
+        fill_from_waitlist(&ctx, &db, tournament).await;
+        for player in &removed {
+            let by = UnregisteredBy::Automatic;
+            not_told.push(
+                notify_removed(&ctx, tournament, player, by, Some(OVERRANKED_REASON))
+                    .await
+                    .err(),
+            );
+        }
+    }
+    let lines: Vec<String> = removed
+        .iter()
+        .enumerate()
+        .map(|(i, player)| {
+            let (username, rank) = player.tetrio_data.as_ref().map_or(
+                (player.tetrio_id.clone(), "?".to_string()),
+                |data| {
+                    (
+                        data.username.clone(),
+                        data.league
+                            .rank
+                            .parse::<Rank>()
+                            .unwrap_or(Rank::Unranked)
+                            .to_string(),
+                    )
+                },
+            );
+            let mut line = format!(
+                "`{}`: current rank {} (≤ {} required)",
+                username, rank, max_rank
+            );
+            if let Some(Some(why)) = not_told.get(i) {
+                line.push_str(&format!(", could not tell them ({})", why));
+            }
+            line
+        })
+        .collect();
+    let mut description = if lines.is_empty() {
+        "Nobody is above the rank cap".to_string()
+    } else {
+        lines.join("\n")
+    };
+    if description.len() > 2000 {
+        let mut end = 2000;
+        while !description.is_char_boundary(end) {
+            end -= 1;
+        }
+        description.truncate(end);
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                if let Some(tournament) = &tournament {
+                    badge_embed(e, tournament);
+                }
+                e.title(format!("Withdrew {} overranked players", removed.len()))
+                    .description(description)
+            })
+            .allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
     Ok(())
 }
 
 #[command]
-async fn update_registered(ctx: &Context, msg: &Message) -> CommandResult {
+#[usage("[--dm]")]
+#[example("--dm")]
+/// Updates the registered players of the active tournament and lists everyone whose RD is above the limit.
+/// With `--dm`, every listed player with a linked Discord account gets a warning in their DMs.
+async fn rd_check(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (_, flags) = split_flags(&args, &[]);
+    let send_dms = flags.contains_key("dm");
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let _update = wait_for_updates(&ctx, &msg, &db).await?;
     let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let update_db = db.clone();
+    let (task, result) = run_long_task(&ctx, "Registered player update", move |task| async move {
+        update_db
+            .players
+            .update_registered_cancellable(&tournament, &task)
+            .await
+            .map(|_| ())
+    })
+    .await;
+    if result.is_err() || task.cancel_reason().is_some() {
+        typing.stop();
+        return report_long_task(&ctx, &msg, &task, result).await;
+    }
+
+    // Players might have withdrawn while the update was running
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let max_rd = tournament.restrictions.max_rd;
+    let registered = tournament.registered_players();
+    let ids: Vec<&str> = registered
+        .iter()
+        .map(|entry| entry.tetrio_id.as_str())
+        .collect();
+    let players = db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": ids}}, None)
+        .await;
+    typing.stop();
+    let players = match players {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    // Unranked players have no RD to speak of, the eligibility check catches them anyway
+    let mut affected: Vec<(String, f64, Option<u64>)> = players
+        .iter()
+        .filter_map(|player| {
+            let data = player.tetrio_data.as_ref()?;
+            let rd = data.league.rd?;
+            if rd <= max_rd {
+                return None;
+            }
+            Some((data.username.clone(), rd, player.discord_id))
+        })
+        .collect();
+    affected.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut report = Report::new(
+        &format!("RD above {:.0} in {}", max_rd, tournament.shorthand),
+        &["Username", "RD", "Games needed", "Linked"],
+    );
+    for (username, rd, discord_id) in &affected {
+        report.push_row(vec![
+            username.clone(),
+            format!("{:.1}", rd),
+            format!("~{}", estimated_games_to_rd(*rd, max_rd)),
+            if discord_id.is_some() { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+    if affected.is_empty() {
+        report.push_note("Everyone is within the RD limit");
+    }
+
+    if send_dms {
+        let mut failed = Vec::new();
+        let mut sent = 0;
+        for (username, rd, discord_id) in &affected {
+            let discord_id = match discord_id {
+                Some(discord_id) => *discord_id,
+                None => continue,
+            };
+            let text = badged(
+                &tournament,
+                &format!(
+                    "Your RD is {:.1}, but {} requires at most {:.0}. Play roughly {} ranked games before the announcement to stay eligible.",
+                    rd,
+                    tournament.name,
+                    max_rd,
+                    estimated_games_to_rd(*rd, max_rd)
+                ),
+            );
+            let dm = match UserId(discord_id).create_dm_channel(&ctx.http).await {
+                Ok(channel) => say(&ctx, channel.id, text).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            match dm {
+                Ok(()) => sent += 1,
+                Err(err) => {
+                    tracing::warn!("Could not warn {} about their RD: {}", discord_id, err);
+                    failed.push(username.as_str());
+                }
+            }
+        }
+        report.push_note(&format!("Sent {} DMs", sent));
+        if !failed.is_empty() {
+            report.push_note(&format!(
+                "Could not DM {} players: {}",
+                failed.len(),
+                failed.join(", ")
+            ));
+        }
+        tracing::info!(
+            target: "audit",
+            "{} sent RD warnings to {} players of {}",
+            msg.author.id,
+            sent,
+            tournament.shorthand
+        );
+    }
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+// New ranked games a registrant should have played since registering, if `.inactive_check` gets no threshold
+const DEFAULT_MIN_NEW_GAMES: i64 = 5;
+
+#[command]
+#[usage("[min new games]")]
+#[example("")]
+#[example("10")]
+/// Lists registered players who played fewer new ranked games since registering than the threshold (5 by default).
+/// Uses the stored player data, run `.update_registered` first for current numbers.
+/// Registrations from before the game count was recorded are listed separately.
+async fn inactive_check(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let min_new_games = match args.current() {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(min_new_games) => min_new_games,
+            Err(_) => {
+                say(&ctx, msg.channel_id, "Threshold has to be a number").await?;
+                return Ok(());
+            }
+        },
+        None => DEFAULT_MIN_NEW_GAMES,
+    };
+
     let db = crate::discord::get_database(&ctx).await;
-    let tour = db.tournaments.get_active().unwrap().unwrap();
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let registered = tournament.registered_players();
+    let ids: Vec<&str> = registered
+        .iter()
+        .map(|entry| entry.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": ids}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-    match db.players.update_registered(tour) {
-        Ok(_) => {
+    let now = Utc::now();
+    let mut inactive = Vec::new();
+    let mut unknown = Vec::new();
+    for entry in &registered {
+        let data = players
+            .iter()
+            .find(|player| player.tetrio_id == entry.tetrio_id)
+            .and_then(|player| player.tetrio_data.as_ref());
+        let name = entry.display_name(data.map(|data| data.username.as_str()));
+        let registered_ago = format!("{}d ago", (now - *entry.date).num_days());
+        match data.and_then(|data| entry.games_since_registration(data.league.gamesplayed)) {
+            Some(new_games) if new_games < min_new_games => {
+                inactive.push((new_games, name, registered_ago))
+            }
+            Some(_) => {}
+            None => unknown.push(vec![name, registered_ago]),
+        }
+    }
+    inactive.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+    let mut report = Report::new(
+        &format!(
+            "Fewer than {} new ranked games in {}",
+            min_new_games, tournament.shorthand
+        ),
+        &["Username", "New games", "Registered"],
+    );
+    for (new_games, name, registered_ago) in inactive {
+        report.push_row(vec![name, new_games.to_string(), registered_ago]);
+    }
+    report.push_note(&format!(
+        "{} of {} registered players checked, {} unknown",
+        registered.len() - unknown.len(),
+        registered.len(),
+        unknown.len()
+    ));
+    send_report(&ctx, msg.channel_id, &report).await?;
+
+    if !unknown.is_empty() {
+        let mut report = Report::new(
+            "Unknown, registered before game counts were recorded",
+            &["Username", "Registered"],
+        );
+        for row in unknown {
+            report.push_row(row);
+        }
+        send_report(&ctx, msg.channel_id, &report).await?;
+    }
+    Ok(())
+}
+
+async fn report_long_task(
+    ctx: &Context,
+    msg: &Message,
+    task: &TaskHandle,
+    result: Result<(), DatabaseError>,
+) -> CommandResult {
+    match result {
+        Ok(_) if task.cancel_reason().is_none() => {
             react_confirm(&ctx, &msg).await;
         }
+        Ok(_) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, task.summary()).await?;
+        }
         Err(err) => {
             tracing::warn!("{}", err);
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
         }
     }
+    Ok(())
+}
+
+#[command]
+/// Shows whether requests to Tetr.io are currently going through
+async fn tetrio_status(ctx: &Context, msg: &Message) -> CommandResult {
+    let (state, failures) = crate::tetrio::breaker_status();
+    let latency = crate::tetrio::latency_snapshot();
+    let now = Utc::now();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Tetr.io API status")
+                    .field("Circuit breaker", state.to_string(), true)
+                    .field("Recent consecutive failures", failures, true);
+                for family in Family::ALL.iter() {
+                    let total = latency.cumulative(*family);
+                    if total.requests() == 0 {
+                        continue;
+                    }
+                    let hour = latency.last_hour(*family, now);
+                    e.field(
+                        format!("{} requests", family),
+                        format!(
+                            "Last hour: {}\nSince startup: {}",
+                            latency_summary(&hour),
+                            latency_summary(total)
+                        ),
+                        false,
+                    );
+                }
+                e
+            })
+        })
+        .await?;
 
-    typing.stop();
     Ok(())
 }
 
+// One line of latency percentiles, cache ratios and failures
+fn latency_summary(stats: &Stats) -> String {
+    if stats.requests() == 0 {
+        return "no requests".to_string();
+    }
+    let quantile = |q: f64| match stats.latency.quantile(q) {
+        Some(u64::MAX) => format!(">{}ms", BUCKETS_MS[BUCKETS_MS.len() - 1]),
+        Some(millis) => format!("≤{}ms", millis),
+        None => "-".to_string(),
+    };
+    format!(
+        "{} requests, p50 {}, p95 {}, hit/miss/awaited {:.0}%/{:.0}%/{:.0}%, {} API errors, {} network errors, {} rate limited",
+        stats.requests(),
+        quantile(0.5),
+        quantile(0.95),
+        stats.cache_ratio(CacheStatus::Hit) * 100.0,
+        stats.cache_ratio(CacheStatus::Miss) * 100.0,
+        stats.cache_ratio(CacheStatus::Awaited) * 100.0,
+        stats.outcome(Outcome::ApiError),
+        stats.outcome(Outcome::NetworkError),
+        stats.outcome(Outcome::RateLimited)
+    )
+}
+
 #[command]
+#[usage("[tournament]")]
+#[example("UC12")]
+#[example("")]
+/// Sets a tournament active, other active tournaments stay active.
+/// Without a tournament, every tournament is set inactive.
 async fn set_active(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-    match db.tournaments.set_active(args.current()) {
+    match db.tournaments.set_active(args.current()).await {
         Ok(entry) => {
             react_confirm(&ctx, &msg).await;
             if entry.is_none() {
-                msg.channel_id
-                    .say(&ctx.http, "Set all tournaments to inactive")
-                    .await?;
+                say(&ctx, msg.channel_id, "Set all tournaments to inactive").await?;
             }
         }
         Err(err) => {
             tracing::warn!("{}", err);
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[usage("<tournament>")]
+#[example("UC12")]
+/// Sets a single tournament inactive, other active tournaments stay active.
+async fn set_inactive(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = match args.current() {
+        Some(name) => name,
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db.tournaments.set_inactive(name).await {
+        Ok(_) => react_confirm(&ctx, &msg).await,
+        Err(err) => {
+            tracing::warn!("{}", err);
+            say(&ctx, msg.channel_id, err).await?;
         }
     }
 
@@ -67,8 +631,32 @@ async fn set_active(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-async fn staff_register(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let discord_account_to_link = match args.current() {
+#[usage("<mention> [username] [--backdate <date>] [--tournament <shorthand>]")]
+#[example("@user")]
+#[example("@user username --backdate \"2021-03-14 18:30\"")]
+#[example("@user username --tournament UC12")]
+/// Registers a player to the active tournament, bypassing restrictions.
+/// Use `--backdate` with a UTC date to set the registration date, for example if the bot was down during the deadline.
+/// Use `--tournament` to pick the tournament if more than one is active.
+async fn staff_register(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, flags) = split_flags(&args, &["backdate", "tournament"]);
+    let backdate = match flags.get("backdate") {
+        Some(input) => match parse_datetime(input) {
+            Some(date) => Some(date),
+            None => {
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    "Invalid backdate, use a UTC date like `2021-03-14 18:30`",
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let discord_account_to_link = match positional.get(0) {
         Some(arg) => {
             let discord_id = serenity::utils::parse_mention(arg);
             match discord_id {
@@ -80,47 +668,64 @@ async fn staff_register(ctx: &Context, msg: &Message, mut args: Args) -> Command
                         .await
                         .is_err()
                     {
-                        msg.channel_id
-                            .say(&ctx.http, "Mentioned user is not in the server!")
-                            .await?;
+                        say(&ctx, msg.channel_id, "Mentioned user is not in the server!").await?;
                         return Ok(());
                     }
 
                     discord_id
                 }
                 None => {
-                    msg.channel_id
-                        .say(
-                            &ctx.http,
-                            "Discord user provided was not valid (use a mention/ping)",
-                        )
-                        .await?;
+                    say(
+                        &ctx,
+                        msg.channel_id,
+                        "Discord user provided was not valid (use a mention/ping)",
+                    )
+                    .await?;
                     return Ok(());
                 }
             }
         }
         None => {
-            msg.channel_id
-                .say(&ctx.http, "No Discord user provided (use a mention/ping)")
-                .await?;
+            say(
+                &ctx,
+                msg.channel_id,
+                "No Discord user provided (use a mention/ping)",
+            )
+            .await?;
             return Ok(());
         }
     };
 
-    args.advance();
-
     let db = crate::discord::get_database(&ctx).await;
-    let reply = match db.tournaments.register_to_active(
-        &db.players,
-        args.current(),
-        discord_account_to_link,
-        true,
-    ) {
-        Ok(entry) => {
+    let reply = match db
+        .tournaments
+        .register_to_active_with_date(
+            &db.players,
+            flags.get("tournament").map(|s| s.as_str()),
+            positional.get(1).map(|s| s.as_str()),
+            discord_account_to_link,
+            true,
+            backdate,
+            Some(message_ref(msg)),
+        )
+        .await
+    {
+        // staff registrations bypass the cap, so nobody ends up on the waitlist here
+        Ok((entry, tournament, _)) => {
             react_confirm(&ctx, &msg).await;
+            update_participant_role(&ctx, &tournament, discord_account_to_link, true).await;
+            let mut embed = player_data_to_embed(&entry, Some(&tournament));
+            badge_embed(&mut embed, &tournament);
             Some(
                 msg.channel_id
-                    .send_message(&ctx.http, |m| m.set_embed(player_data_to_embed(&entry)))
+                    .send_message(&ctx.http, |m| {
+                        m.content(badged(
+                            &tournament,
+                            &format!("Registered to {}", tournament.name),
+                        ))
+                        .set_embed(embed)
+                        .allowed_mentions(|am| am.empty_parse())
+                    })
                     .await?,
             )
         }
@@ -130,7 +735,28 @@ async fn staff_register(ctx: &Context, msg: &Message, mut args: Args) -> Command
                 RegistrationError::AlreadyRegistered => {
                     "The player is already registered!".to_string()
                 }
+                RegistrationError::NotQualified(parent) => format!(
+                    "The player did not qualify through {}, use `.qualify` first",
+                    parent
+                ),
+                RegistrationError::AccountUnavailable(status) => {
+                    format!("The player's Tetr.io account is {}", status)
+                }
+                RegistrationError::LinkedToDifferentAccount { linked, .. } => format!(
+                    "The user is linked to {}, use `.staff_unlink` first to link another account",
+                    linked
+                ),
+                RegistrationError::InvalidBackdate {
+                    earliest, latest, ..
+                } => format!(
+                    "The backdate has to be between {} and {}",
+                    earliest.format("%Y-%m-%d %H:%M UTC"),
+                    latest.format("%Y-%m-%d %H:%M UTC")
+                ),
                 RegistrationError::DatabaseError(err) => match err {
+                    DatabaseError::TetrioApiError(api_err) => {
+                        tetrio_error_reply(&api_err).to_string()
+                    }
                     DatabaseError::DuplicateDiscordEntry => {
                         "The user is already linked!".to_string()
                     }
@@ -144,9 +770,13 @@ async fn staff_register(ctx: &Context, msg: &Message, mut args: Args) -> Command
             };
 
             Some(
-                msg.channel_id
-                    .say(&ctx.http, format!("<@{}> {}", msg.author.id, reply))
-                    .await?,
+                say_pinging(
+                    &ctx,
+                    msg.channel_id,
+                    format!("<@{}> {}", msg.author.id, reply),
+                    &[msg.author.id.0],
+                )
+                .await?,
             )
         }
     };
@@ -157,25 +787,84 @@ async fn staff_register(ctx: &Context, msg: &Message, mut args: Args) -> Command
 }
 
 #[command]
+#[usage("<username> [reason] [--purge]")]
+#[example("caboozled_pie schedule conflict")]
+#[example("caboozled_pie --purge")]
+/// Withdraws a player from the active tournament, keeping the registration so they can return.
+/// Use `--purge` to remove the registration entirely. The player is told in their DMs, along with the reason.
 async fn staff_unregister(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-    let username = match args.current() {
-        Some(username) => username,
+    let (positional, flags) = split_flags(&args, &[]);
+    let username = match positional.get(0) {
+        Some(username) => username.as_str(),
         None => {
-            msg.channel_id
-                .say(&ctx.http, "No username provided")
-                .await?;
+            say(&ctx, msg.channel_id, "No username provided").await?;
             return Ok(());
         }
     };
+    let reason = match positional[1..].join(" ") {
+        reason if reason.is_empty() => None,
+        reason => Some(reason),
+    };
+    let purge = flags.contains_key("purge");
 
-    match db.tournaments.unregister_by_tetrio(&db.players, username) {
-        Ok(_) => {
+    let result = if purge {
+        db.tournaments
+            .unregister_by_tetrio(
+                &db.players,
+                username,
+                UnregisteredBy::Staff {
+                    discord_id: msg.author.id.0,
+                },
+                reason.as_deref(),
+            )
+            .await
+    } else {
+        match db
+            .tournaments
+            .withdraw_by_tetrio(&db.players, username, reason.as_deref())
+            .await
+        {
+            Ok(tournament) => match db.players.get_player_by_tetrio(username).await {
+                Ok(Some(player)) => Ok((player, tournament)),
+                Ok(None) => Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
+                Err(err) => Err(err.into()),
+            },
+            Err(err) => Err(err),
+        }
+    };
+
+    match result {
+        Ok((player, tournament)) => {
             react_confirm(&ctx, &msg).await;
+            let action = if purge { "Removed" } else { "Withdrew" };
+            tracing::info!(
+                target: "audit",
+                "{} {} {} from {}",
+                msg.author.id,
+                action.to_lowercase(),
+                username,
+                tournament.shorthand
+            );
+            if let Some(discord_id) = player.discord_id {
+                update_participant_role(&ctx, &tournament, discord_id, false).await;
+            }
+            fill_from_waitlist(&ctx, &db, &tournament).await;
+
+            let mut reply = format!("{} `{}` from {}", action, username, tournament.name);
+            let by = UnregisteredBy::Staff {
+                discord_id: msg.author.id.0,
+            };
+            if let Err(why) =
+                notify_removed(&ctx, &tournament, &player, by, reason.as_deref()).await
+            {
+                reply.push_str(&format!(", could not tell them ({})", why));
+            }
+            say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
         }
         Err(err) => {
             react_deny(&ctx, &msg).await;
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
         }
     };
 
@@ -191,23 +880,23 @@ async fn staff_link(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
             Some(discord_id) => discord_id,
             None => {
                 react_deny(&ctx, &msg).await;
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "First argument was not a mention (`.staff_link <mention> <username>`)",
-                    )
-                    .await?;
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    "First argument was not a mention (`.staff_link <mention> <username>`)",
+                )
+                .await?;
                 return Ok(());
             }
         },
         None => {
             react_deny(&ctx, &msg).await;
-            msg.channel_id
-                .say(
-                    &ctx.http,
-                    "Discord mention/ping missing (`.staff_link <mention> <username>`)",
-                )
-                .await?;
+            say(
+                &ctx,
+                msg.channel_id,
+                "Discord mention/ping missing (`.staff_link <mention> <username>`)",
+            )
+            .await?;
             return Ok(());
         }
     };
@@ -218,23 +907,23 @@ async fn staff_link(ctx: &Context, msg: &Message, mut args: Args) -> CommandResu
         Some(username) => username,
         None => {
             react_deny(&ctx, &msg).await;
-            msg.channel_id
-                .say(
-                    &ctx.http,
-                    "Username missing (`.staff_link <mention> <username>`)",
-                )
-                .await?;
+            say(
+                &ctx,
+                msg.channel_id,
+                "Username missing (`.staff_link <mention> <username>`)",
+            )
+            .await?;
             return Ok(());
         }
     };
 
-    match db.players.link(discord_id, username) {
+    match db.players.link(discord_id, username).await {
         Ok(_) => {
             react_confirm(&ctx, &msg).await;
         }
         Err(err) => {
             react_deny(&ctx, &msg).await;
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
         }
     }
 
@@ -247,27 +936,25 @@ async fn staff_unlink(ctx: &Context, msg: &Message, args: Args) -> CommandResult
 
     match args.current() {
         None => {
-            msg.channel_id
-                .say(&ctx.http, "No username or mention provided")
-                .await?;
+            say(&ctx, msg.channel_id, "No username or mention provided").await?;
         }
         Some(arg) => match serenity::utils::parse_mention(arg) {
-            Some(discord_id) => match db.players.unlink_by_discord(discord_id) {
+            Some(discord_id) => match db.players.unlink_by_discord(discord_id).await {
                 Ok(_) => {
                     react_confirm(&ctx, &msg).await;
                 }
                 Err(err) => {
                     react_deny(&ctx, &msg).await;
-                    msg.channel_id.say(&ctx.http, err).await?;
+                    say(&ctx, msg.channel_id, err).await?;
                 }
             },
-            None => match db.players.unlink_by_tetrio(arg) {
+            None => match db.players.unlink_by_tetrio(arg).await {
                 Ok(_) => {
                     react_confirm(&ctx, &msg).await;
                 }
                 Err(err) => {
                     react_deny(&ctx, &msg).await;
-                    msg.channel_id.say(&ctx.http, err).await?;
+                    say(&ctx, msg.channel_id, err).await?;
                 }
             },
         },
@@ -275,3 +962,2491 @@ async fn staff_unlink(ctx: &Context, msg: &Message, args: Args) -> CommandResult
 
     Ok(())
 }
+
+#[command]
+#[usage("<mention or Discord ID>")]
+#[example("@IceDynamix")]
+/// Looks up the Tetr.io account that has the member's Discord connected in its settings, without linking it.
+/// Use `.staff_link` to link the suggested account.
+async fn suggest_link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let discord_id = match args
+        .current()
+        .and_then(|arg| serenity::utils::parse_mention(arg).or_else(|| arg.parse::<u64>().ok()))
+    {
+        Some(discord_id) => discord_id,
+        None => {
+            say(&ctx, msg.channel_id, "No mention or Discord ID provided").await?;
+            return Ok(());
+        }
+    };
+
+    let found = match tetrio::search::request(discord_id).await {
+        Ok(response) => response.data,
+        Err(err) => {
+            tracing::warn!("{}", err);
+            say(&ctx, msg.channel_id, tetrio_error_reply(&err)).await?;
+            return Ok(());
+        }
+    };
+    let found = match found {
+        Some(found) => found.user,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "<@{}> has no Tetr.io account with their Discord connected",
+                    discord_id
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let mut lines = vec![format!(
+        "<@{}> has their Discord connected on `{}` ({})",
+        discord_id, found.username, found.id
+    )];
+    match db.players.get_player_by_discord(discord_id).await {
+        Ok(Some(linked)) if linked.tetrio_id == found.id => {
+            lines.push("They're already linked to that account".to_string())
+        }
+        Ok(Some(linked)) => lines.push(format!(
+            "They're linked to a different account ({}), `.staff_unlink` first",
+            linked.tetrio_id
+        )),
+        Ok(None) => {}
+        Err(err) => lines.push(err.to_string()),
+    }
+    match db.players.get_player_by_tetrio(&found.id).await {
+        Ok(Some(entry)) => {
+            if let Some(other) = entry.discord_id.filter(|other| *other != discord_id) {
+                lines.push(format!(
+                    "`{}` is linked to <@{}> in the bot",
+                    found.username, other
+                ));
+            }
+        }
+        Ok(None) => {}
+        Err(err) => lines.push(err.to_string()),
+    }
+    if lines.len() == 1 {
+        lines.push(format!(
+            "Link with `.staff_link <@{}> {}`",
+            discord_id, found.username
+        ));
+    }
+
+    say(&ctx, msg.channel_id, lines.join("\n")).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[seed] [--force]")]
+#[example("")]
+#[example("1234567890 --force")]
+/// Splits the registered players of the active tournament into two bracket sides.
+/// Players are distributed in serpentine seed order, players with an equal TR are shuffled with the RNG seed.
+/// If no seed is given, it's derived from the tournament shorthand and the snapshot date, so anyone can re-run it.
+/// Use `--force` to replace an existing split.
+async fn bracket_split(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, flags) = split_flags(&args, &[]);
+    let force = flags.contains_key("force");
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(previous) = &tournament.bracket_split {
+        if !force {
+            react_deny(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "The bracket was already split with seed `{}`, use `--force` to split again",
+                    previous.seed
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let snapshot_at = match tournament.snapshot_at() {
+        Some(ts) => ts,
+        None => {
+            react_deny(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                "There is no snapshot to seed the players with",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let seed = match positional.first() {
+        Some(arg) => match arg.parse::<u64>() {
+            Ok(seed) => seed,
+            Err(_) => {
+                react_deny(&ctx, &msg).await;
+                say(&ctx, msg.channel_id, "The seed has to be a positive number").await?;
+                return Ok(());
+            }
+        },
+        None => split::default_seed(&tournament.shorthand, snapshot_at.timestamp()),
+    };
+
+    let registered: Vec<String> = tournament
+        .registered_players()
+        .iter()
+        .map(|reg| reg.tetrio_id.clone())
+        .collect();
+
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": registered}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let seed_order = bracket::seed_order(&tournament, &players);
+    let result = split::split(&seed_order, seed);
+
+    let to_persist = BracketSplit {
+        seed: seed.to_string(),
+        created_at: BsonDateTime::from(Utc::now()),
+        side_a: result.side_a.clone(),
+        side_b: result.side_b.clone(),
+    };
+
+    if let Err(err) = db
+        .tournaments
+        .set_bracket_split(&tournament.shorthand, &to_persist)
+        .await
+    {
+        react_deny(&ctx, &msg).await;
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+
+    let mut report = Report::new(
+        &format!("{} bracket split", tournament.shorthand),
+        &[
+            "Side",
+            "Seed",
+            "Registered as",
+            "Current name",
+            "TR",
+            "Supporter",
+            "Verified",
+        ],
+    );
+
+    for (side, ids) in &[
+        (split::Side::A, &result.side_a),
+        (split::Side::B, &result.side_b),
+    ] {
+        for id in ids.iter() {
+            if let Some((index, player)) = seed_order
+                .iter()
+                .enumerate()
+                .find(|(_, p)| &p.tetrio_id == id)
+            {
+                report.push_row(vec![
+                    side.to_string(),
+                    (index + 1).to_string(),
+                    player
+                        .registered_as
+                        .clone()
+                        .unwrap_or_else(|| "-".to_string()),
+                    player.username.clone(),
+                    player
+                        .rating
+                        .map_or("-".to_string(), |r| format!("{:.0}", r)),
+                    flag_cell(player.supporter),
+                    flag_cell(player.verified),
+                ]);
+            }
+        }
+    }
+
+    report.push_note(&format!(
+        "Side A: {} players, side B: {} players",
+        result.side_a.len(),
+        result.side_b.len()
+    ));
+    report.push_note(&format!(
+        "RNG seed: `{}` (verify with `.bracket_split {} --force`)",
+        seed, seed
+    ));
+
+    react_confirm(&ctx, &msg).await;
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+/// Previews the round one pairings of the active tournament, seeded like `.bracket_split`.
+/// Top seeds get the byes. Pairings of players from the same country are flagged.
+/// Nothing is saved, the published bracket stays the source of truth.
+async fn pairings_preview(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let registered: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|r| r.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": registered}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let seed_order = bracket::seed_order(&tournament, &players);
+    if seed_order.len() < 2 {
+        say(
+            &ctx,
+            msg.channel_id,
+            "Not enough registered players for a bracket",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let player_cell = |seed: usize| {
+        let player = &seed_order[seed - 1];
+        format!(
+            "{}. {} ({}, {})",
+            seed,
+            player.username,
+            player.rank,
+            player
+                .rating
+                .map_or("unrated".to_string(), |r| format!("{:.0} TR", r))
+        )
+    };
+
+    let pairings = preview::round_one(seed_order.len());
+    let mut report = Report::new(
+        &format!("{} round one pairings", tournament.shorthand),
+        &["Match", "High seed", "Low seed", "Flags"],
+    );
+    let mut flagged = 0;
+    for pairing in &pairings {
+        let (low_cell, flags) = match pairing.low_seed {
+            Some(low) => {
+                let reasons = preview::awkward_reasons(
+                    &seed_order[pairing.high_seed - 1],
+                    &seed_order[low - 1],
+                );
+                if !reasons.is_empty() {
+                    flagged += 1;
+                }
+                (player_cell(low), reasons.join(", "))
+            }
+            None => ("bye".to_string(), String::new()),
+        };
+        report.push_row(vec![
+            pairing.match_number.to_string(),
+            player_cell(pairing.high_seed),
+            low_cell,
+            flags,
+        ]);
+    }
+
+    report.push_note(&format!(
+        "{} players in a bracket of {} ({} winners rounds), {} byes, {} flagged pairings",
+        seed_order.len(),
+        preview::bracket_size(seed_order.len()),
+        preview::winners_rounds(seed_order.len()),
+        pairings.iter().filter(|p| p.is_bye()).count(),
+        flagged
+    ));
+    report.push_note("Preview only, nothing was saved");
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("[tournament] [--checked-in]")]
+#[example("")]
+#[example("UC12 --checked-in")]
+/// Exports the registered players for Challonge's bulk add, seeded by announcement TR.
+/// Each line is the Tetr.io username, the seed and the Discord ID.
+/// Use `--checked-in` to only include players who checked in.
+async fn export_challonge(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, flags) = split_flags(&args, &[]);
+    let checked_in_only = flags.contains_key("checked-in");
+
+    let tournament = match db
+        .tournaments
+        .resolve_active(positional.first().map(|s| s.as_str()))
+        .await
+    {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let csv = match db
+        .tournaments
+        .export_participants_challonge(&tournament.shorthand, &db.players, checked_in_only)
+        .await
+    {
+        Ok(csv) => csv,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let count = csv.lines().count();
+    if count == 0 {
+        let reply = if checked_in_only {
+            "Nobody checked in yet, nothing to export"
+        } else {
+            "Nobody is registered yet, nothing to export"
+        };
+        say(&ctx, msg.channel_id, badged(&tournament, reply)).await?;
+        return Ok(());
+    }
+
+    let file_name = format!("{}_challonge.csv", tournament.shorthand.to_lowercase());
+    msg.channel_id
+        .send_files(
+            &ctx.http,
+            vec![AttachmentType::from((csv.as_bytes(), file_name.as_str()))],
+            |m| {
+                m.content(badged(
+                    &tournament,
+                    &format!(
+                        "{} {} players, paste the file into Challonge's bulk add",
+                        count,
+                        if checked_in_only {
+                            "checked in"
+                        } else {
+                            "registered"
+                        }
+                    ),
+                ))
+                .allowed_mentions(|am| am.empty_parse())
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[usage("<username|mention>")]
+#[example("username")]
+#[example("@user")]
+/// Lists every register, withdraw and unregister event of a player in the active tournament.
+async fn registration_history(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+
+    let player = match args.current() {
+        Some(arg) => match serenity::utils::parse_mention(arg) {
+            Some(discord_id) => db.players.get_player_by_discord(discord_id).await,
+            None => db.players.get_player_by_tetrio(arg).await,
+        },
+        None => {
+            say(&ctx, msg.channel_id, "No username or mention provided").await?;
+            return Ok(());
+        }
+    };
+
+    let player = match player {
+        Ok(Some(player)) => player,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Player not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let username = player
+        .tetrio_data
+        .as_ref()
+        .map_or(player.tetrio_id.as_str(), |data| data.username.as_str());
+    let mut report = Report::new(
+        &format!(
+            "Registration history of {} in {}",
+            username, tournament.shorthand
+        ),
+        &["When", "Event"],
+    );
+    let history = tournament.registration_history(&player.tetrio_id);
+    for event in &history {
+        report.push_row(vec![
+            event.at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            event.kind.to_string(),
+        ]);
+    }
+    if history.is_empty() {
+        report.push_note("Never registered to this tournament");
+    }
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("<username|mention>")]
+#[example("username")]
+#[example("@user")]
+/// Shows the linked accounts of a player and their registration in the active tournament.
+async fn lookup(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+
+    let player = match args.current() {
+        Some(arg) => match serenity::utils::parse_mention(arg) {
+            Some(discord_id) => db.players.get_player_by_discord(discord_id).await,
+            None => db.players.get_player_by_tetrio(arg).await,
+        },
+        None => {
+            say(&ctx, msg.channel_id, "No username or mention provided").await?;
+            return Ok(());
+        }
+    };
+
+    let player = match player {
+        Ok(Some(player)) => player,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Player not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let mut lines = vec![format!(
+        "Tetr.io: `{}` ({})",
+        player
+            .tetrio_data
+            .as_ref()
+            .map_or(player.tetrio_id.as_str(), |data| data.username.as_str()),
+        player.tetrio_id
+    )];
+    lines.push(match player.discord_id {
+        Some(discord_id) => format!("Discord: <@{}>", discord_id),
+        None => "Discord: not linked".to_string(),
+    });
+
+    match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => match tournament.registration(&player.tetrio_id) {
+            Some(reg) => {
+                let mut line = format!(
+                    "Registered to {} on {}",
+                    tournament.name,
+                    reg.date.format("%Y-%m-%d %H:%M UTC")
+                );
+                if reg.is_backdated() {
+                    line.push_str(&format!(
+                        " (backdated, recorded on {})",
+                        reg.recorded_at().format("%Y-%m-%d %H:%M UTC")
+                    ));
+                }
+                if let Some(source) = &reg.source {
+                    line.push_str(&format!(", via {}", source));
+                }
+                match &reg.status {
+                    RegistrationStatus::Withdrawn { at, reason } => line.push_str(&format!(
+                        ", withdrew on {} ({})",
+                        at.format("%Y-%m-%d %H:%M UTC"),
+                        reason.as_deref().unwrap_or("no reason given")
+                    )),
+                    RegistrationStatus::Disqualified { at, reason, by } => line.push_str(&format!(
+                        ", disqualified on {} by <@{}> ({})",
+                        at.format("%Y-%m-%d %H:%M UTC"),
+                        by,
+                        reason
+                    )),
+                    RegistrationStatus::Active => {}
+                }
+                if !reg.notes.is_empty() {
+                    line.push_str(&format!(", {} staff notes", reg.notes.len()));
+                }
+                lines.push(badged(&tournament, &line));
+            }
+            None => lines.push(badged(
+                &tournament,
+                &format!("Not registered to {}", tournament.name),
+            )),
+        },
+        Ok(None) => lines.push("No active tournament".to_string()),
+        Err(err) => lines.push(err.to_string()),
+    }
+
+    say(&ctx, msg.channel_id, lines.join("\n")).await?;
+
+    Ok(())
+}
+
+#[command]
+#[usage("[clean]")]
+#[example("")]
+#[example("clean")]
+/// Lists linked players whose Discord account has been deleted.
+/// Use `clean` to unlink them after confirming.
+async fn orphaned_links(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let clean = args.current().map_or(false, |arg| arg == "clean");
+    let guild_id = msg.guild_id.unwrap();
+    let db = crate::discord::get_database(&ctx).await;
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+
+    let linked = match db
+        .players
+        .get_players(doc! {"discord_id": {"$exists": true, "$ne": null}}, None)
+        .await
+    {
+        Ok(linked) => linked,
+        Err(err) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    // Resolve accounts one by one to stay well below the Discord rate limits
+    let mut statuses = HashMap::new();
+    for discord_id in linked.iter().filter_map(|p| p.discord_id) {
+        let status = resolve_discord_account(&ctx, guild_id, discord_id).await;
+        statuses.insert(discord_id, status);
+        time::sleep(time::Duration::from_millis(250)).await;
+    }
+
+    let unresolved = statuses
+        .values()
+        .filter(|s| **s == DiscordAccountStatus::Unresolved)
+        .count();
+
+    let orphaned = match db
+        .players
+        .find_orphaned_links(|discord_id| {
+            statuses
+                .get(&discord_id)
+                .copied()
+                .unwrap_or(DiscordAccountStatus::Unresolved)
+        })
+        .await
+    {
+        Ok(orphaned) => orphaned,
+        Err(err) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    typing.stop();
+
+    let mut report = Report::new("Orphaned links", &["Tetr.io", "Discord ID"]);
+    for player in &orphaned {
+        report.push_row(vec![
+            player
+                .tetrio_data
+                .as_ref()
+                .map_or(player.tetrio_id.clone(), |d| d.username.clone()),
+            player.discord_id.unwrap_or_default().to_string(),
+        ]);
+    }
+    report.push_note(&format!(
+        "{} linked accounts checked, {} deleted, {} could not be resolved",
+        statuses.len(),
+        orphaned.len(),
+        unresolved
+    ));
+    send_report(&ctx, msg.channel_id, &report).await?;
+
+    if !clean || orphaned.is_empty() {
+        return Ok(());
+    }
+
+    let prompt = format!("Unlink {} deleted Discord accounts?", orphaned.len());
+    if !confirm_prompt(&ctx, &msg, &prompt).await? {
+        say(&ctx, msg.channel_id, "Cancelled").await?;
+        return Ok(());
+    }
+
+    let mut unlinked = 0;
+    for player in &orphaned {
+        match db.players.unlink_by_tetrio(&player.tetrio_id).await {
+            Ok(_) => {
+                unlinked += 1;
+                tracing::info!(
+                    target: "audit",
+                    "{} unlinked {} from deleted Discord account {}",
+                    msg.author.id,
+                    player.tetrio_id,
+                    player.discord_id.unwrap_or_default()
+                );
+            }
+            Err(err) => tracing::warn!("Could not unlink {}: {}", player.tetrio_id, err),
+        }
+    }
+
+    say(
+        &ctx,
+        msg.channel_id,
+        format!("Unlinked {}/{} players", unlinked, orphaned.len()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[command]
+/// Lists linked players whose Tetr.io account was deleted or banned.
+/// Accounts are only checked when their data is refreshed, so this shows what the bot noticed so far.
+async fn audit_links(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let players = match db.players.get_unavailable_links().await {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let active = db.tournaments.get_active().await.ok().flatten();
+
+    let mut report = Report::new(
+        "Links to unavailable accounts",
+        &[
+            "Tetr.io",
+            "Tetr.io ID",
+            "Discord ID",
+            "Status",
+            "Registered",
+        ],
+    );
+    for player in &players {
+        let registered = active.as_ref().map_or(false, |tournament| {
+            tournament
+                .registration(&player.tetrio_id)
+                .map_or(false, |r| r.is_active())
+        });
+        report.push_row(vec![
+            player
+                .tetrio_data
+                .as_ref()
+                .map_or(player.tetrio_id.clone(), |d| d.username.clone()),
+            player.tetrio_id.clone(),
+            player.discord_id.unwrap_or_default().to_string(),
+            player.status.to_string(),
+            if registered { "yes" } else { "no" }.to_string(),
+        ]);
+    }
+    report.push_note(&format!(
+        "{} linked players with a deleted or banned account",
+        players.len()
+    ));
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("<tournament>")]
+#[example("UC12")]
+/// Shows which parts of a tournament's setup are done, and how to do the rest.
+async fn setup_status(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = match args.current() {
+        Some(name) => name,
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    match db.tournaments.get_tournament(name).await {
+        Ok(Some(tournament)) => {
+            let embed = setup_status_embed(&tournament.shorthand, &tournament.setup_status());
+            msg.channel_id
+                .send_message(&ctx.http, |m| m.set_embed(embed))
+                .await?;
+        }
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[usage("<usernames or attached text file>")]
+#[example("caboozled_pie, icedynamix")]
+/// Looks up a list of Tetr.io users at once (newline, comma or space separated, up to 200).
+/// Shows who they're linked to, whether that account is on the server and whether they're registered.
+async fn who_is_bulk(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const MAX_INPUTS: usize = 200;
+
+    let mut text = args.rest().to_string();
+    if let Some(attachment) = msg.attachments.first() {
+        let content = attachment.download().await?;
+        text.push('\n');
+        text.push_str(&String::from_utf8_lossy(&content));
+    }
+
+    let identifiers = parse_identifiers(&text);
+    if identifiers.is_empty() {
+        say(&ctx, msg.channel_id, "No usernames provided").await?;
+        return Ok(());
+    }
+    if identifiers.len() > MAX_INPUTS {
+        say(
+            &ctx,
+            msg.channel_id,
+            format!(
+                "Too many usernames ({}), at most {} can be looked up at once",
+                identifiers.len(),
+                MAX_INPUTS
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let db = crate::discord::get_database(&ctx).await;
+
+    let players = match db.players.get_players_by_identifiers(&identifiers).await {
+        Ok(players) => players,
+        Err(err) => {
+            typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let tournament = db.tournaments.get_active().await.ok().flatten();
+
+    let mut report = Report::new(
+        "Bulk lookup",
+        &["Input", "Username", "Discord", "On server", "Registered"],
+    );
+    let mut unresolved = 0;
+
+    for identifier in &identifiers {
+        let player = match find_by_identifier(&players, identifier) {
+            Some(player) => player,
+            None => {
+                unresolved += 1;
+                let suggestions = db
+                    .players
+                    .suggest_usernames(identifier, 3)
+                    .await
+                    .unwrap_or_default();
+                let note = if suggestions.is_empty() {
+                    "not found".to_string()
+                } else {
+                    format!("not found, did you mean {}?", suggestions.join("/"))
+                };
+                report.push_row(vec![
+                    identifier.clone(),
+                    note,
+                    "-".to_string(),
+                    "-".to_string(),
+                    "-".to_string(),
+                ]);
+                continue;
+            }
+        };
+
+        let on_server = match player.discord_id {
+            Some(discord_id) => {
+                let is_member = GuildId(crate::discord::UC_GUILD_ID)
+                    .member(&ctx.http, discord_id)
+                    .await
+                    .is_ok();
+                flag_cell(is_member)
+            }
+            None => "-".to_string(),
+        };
+
+        report.push_row(vec![
+            identifier.clone(),
+            player
+                .tetrio_data
+                .as_ref()
+                .map_or(player.tetrio_id.clone(), |d| d.username.clone()),
+            player
+                .discord_id
+                .map_or("not linked".to_string(), |id| id.to_string()),
+            on_server,
+            tournament.as_ref().map_or("-".to_string(), |t| {
+                flag_cell(t.player_is_registered(player))
+            }),
+        ]);
+    }
+
+    typing.stop();
+    report.push_note(&format!(
+        "{} looked up, {} not found",
+        identifiers.len(),
+        unresolved
+    ));
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("<receipt code> [Tetr.io username or ID]")]
+#[example("3KQ9-X1ZD")]
+#[example("3KQ9-X1ZD caboozled_pie")]
+/// Checks whether a registration receipt was issued by the bot for the active tournament.
+/// If a player is given, only their registration is checked.
+async fn verify_receipt(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let code = match args.single::<String>() {
+        Ok(code) => code,
+        Err(_) => {
+            say(&ctx, msg.channel_id, "No receipt code provided").await?;
+            return Ok(());
+        }
+    };
+
+    if crate::receipt::key().is_none() {
+        say(
+            &ctx,
+            msg.channel_id,
+            "Receipts are not enabled, no secret is configured",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let player = match args.current() {
+        Some(username) => match db.players.get_player_by_tetrio(username).await {
+            Ok(Some(player)) => Some(player),
+            Ok(None) => {
+                say(&ctx, msg.channel_id, "Could not find specified user!").await?;
+                return Ok(());
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let matched = tournament.find_receipt(&code, player.as_ref().map(|p| p.tetrio_id.as_str()));
+    let reply = match (matched, &player) {
+        (Some(registration), _) => {
+            let username = match &player {
+                Some(player) => player.tetrio_data.as_ref().map(|d| d.username.clone()),
+                None => db
+                    .players
+                    .get_player_by_tetrio(&registration.tetrio_id)
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|p| p.tetrio_data)
+                    .map(|d| d.username),
+            };
+            format!(
+                "✅ `{}` is valid, it belongs to the registration of {} on {}",
+                code,
+                registration.display_name(username.as_deref()),
+                registration.date.format("%Y-%m-%d %H:%M UTC")
+            )
+        }
+        (None, Some(player)) if tournament.registration(&player.tetrio_id).is_none() => format!(
+            "❌ `{}` is **not valid**, {} is not registered",
+            code, player.tetrio_id
+        ),
+        (None, Some(player)) => format!(
+            "❌ `{}` does **not** match the registration of {}",
+            code,
+            player
+                .tetrio_data
+                .as_ref()
+                .map_or(player.tetrio_id.clone(), |d| d.username.clone())
+        ),
+        (None, None) => format!(
+            "❌ `{}` does **not** match any registration of {}",
+            code, tournament.name
+        ),
+    };
+
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[tournament]")]
+#[example("")]
+#[example("UC12")]
+/// Shows which UTC offsets the registered players are in, based on their countries,
+/// and suggests the two 4 hour match windows that work for the most players.
+/// Uses the active tournament if none is provided.
+async fn timezone_report(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const WINDOW_HOURS: i32 = 4;
+    const BAR_WIDTH: usize = 20;
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match args.current() {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => db.tournaments.get_active().await,
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let registered: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|r| r.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": registered}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    // Registrations without a player entry count as unknown as well
+    let mut histogram = OffsetHistogram::from_countries(
+        players
+            .iter()
+            .map(|p| p.tetrio_data.as_ref().and_then(|d| d.country.as_deref())),
+    );
+    histogram.unknown += tournament
+        .registered_players()
+        .len()
+        .saturating_sub(players.len());
+
+    let max = histogram
+        .counts
+        .values()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(histogram.unknown);
+
+    let mut report = Report::new(
+        &format!("{} time zones", tournament.shorthand),
+        &["UTC offset", "Players", "Histogram"],
+    );
+    for (offset, count) in &histogram.counts {
+        report.push_row(vec![
+            format!("{:+}", offset),
+            count.to_string(),
+            bar(*count, max, BAR_WIDTH),
+        ]);
+    }
+    report.push_row(vec![
+        "unknown".to_string(),
+        histogram.unknown.to_string(),
+        bar(histogram.unknown, max, BAR_WIDTH),
+    ]);
+
+    let windows = timezone::best_windows(&histogram, WINDOW_HOURS, 2);
+    if windows.is_empty() {
+        report.push_note("No match window suggestions, no player has a known country");
+    }
+    for window in &windows {
+        report.push_note(&format!(
+            "Suggested window: {} ({}/{} players with a known country have it between {}:00 and {}:00 local time)",
+            window,
+            window.players,
+            histogram.known(),
+            timezone::PLAY_START_HOUR,
+            timezone::PLAY_END_HOUR
+        ));
+    }
+    report.push_note(
+        "Offsets are approximated from countries (main time zone, no daylight saving time)",
+    );
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("[tournament]")]
+#[example("")]
+#[example("UC12")]
+/// Shows how many players per rank are eligible by rank, with an SVG bar chart for the announcement.
+/// Only the current rank is checked, RD and ranked games are ignored.
+/// Uses the active tournament if none is provided.
+async fn distribution(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match args.current() {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => db.tournaments.get_active().await,
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let ranks = match db
+        .players
+        .eligible_distribution(&tournament.restrictions)
+        .await
+    {
+        Ok(ranks) => ranks,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let lines: Vec<String> = ranks
+        .iter()
+        .rev()
+        .map(|(rank, count)| {
+            format!(
+                "{} {} ({:.1}%)",
+                rank.to_emoji(),
+                count,
+                distribution::percentage(&ranks, *rank)
+            )
+        })
+        .collect();
+    let svg = distribution::to_svg(&ranks);
+    let file_name = format!("{}_distribution.svg", tournament.shorthand.to_lowercase());
+
+    msg.channel_id
+        .send_files(
+            &ctx.http,
+            vec![AttachmentType::from((svg.as_bytes(), file_name.as_str()))],
+            |m| {
+                m.embed(|e| {
+                    badge_embed(e, &tournament);
+                    e.title(format!("{}: Rank distribution", tournament.shorthand))
+                        .description(if lines.is_empty() {
+                            "No eligible players".to_string()
+                        } else {
+                            lines.join("\n")
+                        })
+                        .footer(|f| {
+                            f.text(format!(
+                                "{} players up to {}, by current rank only",
+                                distribution::total(&ranks),
+                                tournament.restrictions.max_rank
+                            ))
+                        })
+                })
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[sub_commands(tasks_cancel)]
+/// Lists long running tasks, like player updates, with their progress.
+/// Use `.tasks cancel <id>` to stop one.
+async fn tasks(ctx: &Context, msg: &Message) -> CommandResult {
+    let now = Utc::now();
+    let mut report = Report::new(
+        "Running tasks",
+        &["ID", "Task", "Progress", "Elapsed", "Times out in"],
+    );
+
+    {
+        let data_read = ctx.data.read().await;
+        if let Some(registry) = data_read.get::<TaskRegistry>() {
+            for task in registry.lock().await.running() {
+                let (done, total) = task.progress();
+                let state = match task.cancel_reason() {
+                    Some(reason) => format!("{}/{} ({})", done, total, reason),
+                    None => format!("{}/{}", done, total),
+                };
+                report.push_row(vec![
+                    task.id.to_string(),
+                    task.description.clone(),
+                    state,
+                    format!("{}s", (now - task.started_at).num_seconds()),
+                    format!("{}s", (task.deadline - now).num_seconds().max(0)),
+                ]);
+            }
+        }
+    }
+
+    if report.rows.is_empty() {
+        say(&ctx, msg.channel_id, "No long running tasks right now").await?;
+        return Ok(());
+    }
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command("cancel")]
+#[usage("<task id>")]
+#[example("3")]
+async fn tasks_cancel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let id = match args.current().and_then(|id| id.parse::<u64>().ok()) {
+        Some(id) => id,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Missing or invalid task ID, see `.tasks`",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let cancelled = {
+        let data_read = ctx.data.read().await;
+        match data_read.get::<TaskRegistry>() {
+            Some(registry) => registry
+                .lock()
+                .await
+                .cancel(id, CancelReason::Requested(msg.author.id.0)),
+            None => None,
+        }
+    };
+
+    let reply = match cancelled {
+        Some(true) => {
+            tracing::info!(target: "audit", "Task {} cancelled by {}", id, msg.author.id);
+            format!("Cancelling task {}, it stops after the current item", id)
+        }
+        Some(false) => format!("Task {} is already being cancelled", id),
+        None => format!("There is no running task with ID {}", id),
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[--min-score <score>]")]
+#[example("")]
+#[example("--min-score 4")]
+/// Lists pairs of registered players that might be the same person on two Discord and Tetr.io accounts.
+/// Looks at similar Discord names, server join times, registration times and Tetr.io names.
+/// Only uses cached data, so players that aren't cached have fewer signals.
+async fn possible_dupes(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (_, flags) = split_flags(&args, &["min-score"]);
+    let mut weights = DupeWeights::default();
+    if let Some(min_score) = flags.get("min-score") {
+        match min_score.parse::<f64>() {
+            Ok(min_score) => weights.threshold = min_score,
+            Err(_) => {
+                say(&ctx, msg.channel_id, "The minimum score has to be a number").await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let registered: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|r| r.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": registered}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let guild_id = GuildId(crate::discord::UC_GUILD_ID);
+    let mut candidates = Vec::new();
+    for seeded in bracket::seed_order(&tournament, &players) {
+        let registered_at = match tournament.registration(&seeded.tetrio_id) {
+            Some(registration) => *registration.date,
+            None => continue,
+        };
+
+        let mut discord_names = Vec::new();
+        let mut joined_guild_at = None;
+        if let Some(discord_id) = seeded.discord_id {
+            match ctx.cache.member(guild_id, discord_id).await {
+                Some(member) => {
+                    discord_names.push(member.user.name.clone());
+                    discord_names.extend(member.nick.clone());
+                    joined_guild_at = member.joined_at;
+                }
+                None => discord_names.extend(ctx.cache.user(discord_id).await.map(|u| u.name)),
+            }
+        }
+
+        candidates.push(DupeCandidate {
+            tetrio_id: seeded.tetrio_id,
+            tetrio_username: seeded.username,
+            discord_id: seeded.discord_id,
+            discord_names,
+            joined_guild_at,
+            registered_at,
+            rank: seeded.rank,
+        });
+    }
+
+    let pairs = dupes::possible_dupes(&candidates, &weights);
+
+    let mut report = Report::new(
+        &format!("{} possible duplicate registrations", tournament.shorthand),
+        &["Score", "Player A", "Player B", "Signals"],
+    );
+    for pair in &pairs {
+        let (a, b) = (&candidates[pair.a], &candidates[pair.b]);
+        let signals: Vec<String> = pair.signals.iter().map(|s| s.to_string()).collect();
+        report.push_row(vec![
+            format!("{:.1}", pair.score),
+            a.tetrio_username.clone(),
+            b.tetrio_username.clone(),
+            signals.join("; "),
+        ]);
+    }
+    report.push_note(&format!(
+        "{} pairs with a score of at least {:.1} among {} players",
+        pairs.len(),
+        weights.threshold,
+        candidates.len()
+    ));
+    report.push_note("This is a heuristic, verify every pair manually before acting on it");
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command("usage")]
+#[usage("[command] [days]")]
+#[example("")]
+#[example("faq 60")]
+/// Shows how often commands were used per day over the last days (30 by default).
+/// Counts every command if none is provided. Usage of the last few minutes might not show up yet.
+async fn command_usage(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const BAR_WIDTH: usize = 30;
+    const MAX_DAYS: i64 = 366;
+
+    // A single number is the amount of days, everything else is the command
+    let mut command = None;
+    let mut days = 30;
+    for arg in args.raw_quoted() {
+        match arg.parse::<i64>() {
+            Ok(n) => days = n.max(1).min(MAX_DAYS),
+            Err(_) => command = Some(arg.trim_start_matches(crate::discord::PREFIX).to_string()),
+        }
+    }
+
+    let db = crate::discord::get_database(&ctx).await;
+    let now = Utc::now();
+    let rollups = match db.usage.get_usage(command.as_deref(), days, now).await {
+        Ok(rollups) => rollups,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let totals = usage::daily_totals(&rollups, days, now);
+    let max = totals
+        .iter()
+        .map(|(_, invocations, _, _)| *invocations as usize)
+        .max()
+        .unwrap_or(0);
+
+    let title = match &command {
+        Some(command) => format!("Usage of .{}, last {} days", command, days),
+        None => format!("Command usage, last {} days", days),
+    };
+    let mut report = Report::new(&title, &["Date", "Uses", "Errors", "Users", ""]);
+    for (date, invocations, errors, users) in &totals {
+        report.push_row(vec![
+            date.clone(),
+            invocations.to_string(),
+            errors.to_string(),
+            users.to_string(),
+            bar(*invocations as usize, max, BAR_WIDTH),
+        ]);
+    }
+
+    let invocations: i64 = totals.iter().map(|(_, i, _, _)| i).sum();
+    let errors: i64 = totals.iter().map(|(_, _, e, _)| e).sum();
+    report.push_note(&format!(
+        "Total: {} uses, {} errors, at most {} users on a single day",
+        invocations,
+        errors,
+        totals.iter().map(|(_, _, _, u)| *u).max().unwrap_or(0)
+    ));
+
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("<tournament> [--research]")]
+#[example("UC12")]
+#[example("UC12 --research")]
+/// Publishes an anonymized dataset of a tournament's registrations as CSV.
+/// Everyone appears under a pseudonym with only their rank group.
+/// With `--research`, players who enabled `.datasharing` are included by name with detailed stats.
+async fn publish_dataset(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, flags) = split_flags(&args, &[]);
+    let research = flags.contains_key("research");
+
+    let key = match dataset::key() {
+        Some(key) => key,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "Datasets are disabled, `{}` is not set",
+                    dataset::SECRET_ENV
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match positional.get(0) {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => {
+            say(&ctx, msg.channel_id, "No tournament provided").await?;
+            return Ok(());
+        }
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let registered_ids: Vec<String> = tournament
+        .registered_players()
+        .iter()
+        .map(|r| r.tetrio_id.clone())
+        .collect();
+    let players = match db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": registered_ids.clone()}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let mut rows = Vec::new();
+    let (mut shared, mut pseudonymous) = (0, 0);
+    for tetrio_id in &registered_ids {
+        let player = players.iter().find(|p| &p.tetrio_id == tetrio_id);
+        let consent = if research && player.map_or(false, |p| p.data_sharing) {
+            shared += 1;
+            dataset::Consent::Shared
+        } else {
+            pseudonymous += 1;
+            dataset::Consent::Pseudonymous
+        };
+        let data = dataset::PlayerData {
+            tetrio_id,
+            consent,
+            announcement: tournament.snapshot().iter().find(|u| &u._id == tetrio_id),
+            current: player.and_then(|p| p.tetrio_data.as_ref()),
+        };
+        rows.push(dataset::row(&data, key, &tournament.shorthand));
+    }
+    // Registration order could be matched with the public player list, so rows are sorted by pseudonym
+    let pseudonym_column = dataset::Field::ALL
+        .iter()
+        .position(|f| *f == dataset::Field::Pseudonym)
+        .unwrap_or(0);
+    rows.sort_by(|a, b| a[pseudonym_column].cmp(&b[pseudonym_column]));
+
+    let header: Vec<String> = dataset::Field::ALL
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect();
+    let mut lines = vec![csv_line(&header)];
+    lines.extend(rows.iter().map(|row| csv_line(row)));
+    let csv = lines.join("\n");
+
+    let kind = if research { "research" } else { "public" };
+    let file_name = format!(
+        "{}_{}_dataset.csv",
+        tournament.shorthand.to_lowercase(),
+        kind
+    );
+    tracing::info!(
+        target: "audit",
+        "{} published the {} dataset of {}",
+        msg.author.id,
+        kind,
+        tournament.shorthand
+    );
+    msg.channel_id
+        .send_files(
+            &ctx.http,
+            vec![AttachmentType::from((csv.as_bytes(), file_name.as_str()))],
+            |m| {
+                m.content(badged(
+                    &tournament,
+                    &format!(
+                        "Published the {} dataset: {} players by name with detailed stats, {} under a pseudonym with only their rank group",
+                        kind, shared, pseudonymous
+                    ),
+                ))
+                .allowed_mentions(|am| am.empty_parse())
+            },
+        )
+        .await?;
+    Ok(())
+}
+
+#[command]
+#[usage("<finals> <username...> | <finals> --parent <qualifier|none> [--skip-restrictions]")]
+#[example("UC12F caboozled_pie osk")]
+#[example("UC12F --parent UC12Q --skip-restrictions")]
+#[example("UC12F --parent none")]
+/// Manages the players who qualified for a finals tournament.
+/// `--parent` links the finals to its qualifier, from then on only qualified players can register.
+/// With `--skip-restrictions`, qualified players don't have to meet the stat restrictions of the finals.
+/// Otherwise, the given players are added to the qualified players.
+async fn qualify(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, flags) = split_flags(&args, &["parent", "from-results"]);
+
+    let finals = match positional.get(0) {
+        Some(finals) => finals,
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (finals tournament)").await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(parent) = flags.get("parent") {
+        let parent = if parent.eq_ignore_ascii_case("none") {
+            None
+        } else {
+            Some(parent.as_str())
+        };
+        let skip_restrictions = flags.contains_key("skip-restrictions");
+        match db
+            .tournaments
+            .set_parent(finals, parent, skip_restrictions)
+            .await
+        {
+            Ok(tournament) => {
+                tracing::info!(
+                    target: "audit",
+                    "{} set the qualifier of {} to {:?} (skip restrictions: {})",
+                    msg.author.id,
+                    tournament.shorthand,
+                    parent,
+                    skip_restrictions
+                );
+                let reply = tournament
+                    .qualifier_summary()
+                    .unwrap_or_else(|| "Registration is open to everyone again".to_string());
+                say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+            }
+            Err(DatabaseError::NotFound) => {
+                say(&ctx, msg.channel_id, "Tournament not found").await?;
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    let tournament = match db.tournaments.get_tournament(finals).await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let parent = match &tournament.parent_tournament {
+        Some(parent) => parent.clone(),
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "{} has no qualifier yet, link one with `.qualify {} --parent <qualifier>`",
+                    tournament.shorthand, tournament.shorthand
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if flags.contains_key("from-results") {
+        // Placements aren't recorded anywhere yet, so there is nothing to take the top cut from
+        say(
+            &ctx,
+            msg.channel_id,
+            format!(
+                "There are no recorded results for {}, please list the qualified players by name",
+                parent
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let usernames = &positional[1..];
+    if usernames.is_empty() {
+        say(&ctx, msg.channel_id, "Missing argument (usernames)").await?;
+        return Ok(());
+    }
+
+    let mut qualified = Vec::new();
+    let mut unknown = Vec::new();
+    for username in usernames {
+        match db.players.update_player(username, false).await {
+            Ok(player) => qualified.push(player.tetrio_id),
+            Err(_) => unknown.push(username.as_str()),
+        }
+    }
+
+    if !qualified.is_empty() {
+        if let Err(err) = db
+            .tournaments
+            .qualify(&tournament.shorthand, &qualified)
+            .await
+        {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+        tracing::info!(
+            target: "audit",
+            "{} qualified {} for {}",
+            msg.author.id,
+            qualified.join(", "),
+            tournament.shorthand
+        );
+    }
+
+    let mut reply = format!("{} players qualified through {}", qualified.len(), parent);
+    if !unknown.is_empty() {
+        reply.push_str(&format!(
+            "\nCould not find {}",
+            unknown
+                .iter()
+                .map(|name| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+
+    Ok(())
+}
+
+// How long a review session waits for the next reaction before it ends
+const REVIEW_TIMEOUT: time::Duration = time::Duration::from_secs(10 * 60);
+
+// How long the review browser waits for a reason or note
+const REVIEW_REPLY_TIMEOUT: time::Duration = time::Duration::from_secs(2 * 60);
+
+// Rank, TR, RD and ranked games of some league data
+fn league_summary(user: &LeaderboardUser) -> String {
+    format!(
+        "{} {:.0} TR, {:.0} RD, {} games",
+        user.league.rank.parse::<Rank>().unwrap_or(Rank::Unranked),
+        user.league.rating,
+        user.league.rd.unwrap_or(999f64),
+        user.league.gamesplayed
+    )
+}
+
+// Everything about a registration that matters when reviewing it, like `.lookup` with stats and the verdict
+async fn review_page(db: &LocalDatabase, tournament: &TournamentEntry, tetrio_id: &str) -> String {
+    let registration = match tournament.registration(tetrio_id) {
+        Some(registration) => registration,
+        None => return format!("`{}` is not registered anymore", tetrio_id),
+    };
+    let player = db
+        .players
+        .get_player_by_tetrio(tetrio_id)
+        .await
+        .ok()
+        .flatten();
+    let current = player.as_ref().and_then(|p| p.tetrio_data.as_ref());
+
+    let mut lines = vec![format!(
+        "Tetr.io: `{}` ({})",
+        current.map_or(tetrio_id, |data| data.username.as_str()),
+        tetrio_id
+    )];
+    lines.push(match player.as_ref().and_then(|p| p.discord_id) {
+        Some(discord_id) => format!("Discord: <@{}>", discord_id),
+        None => "Discord: not linked".to_string(),
+    });
+
+    let mut registered = format!(
+        "Registered on {}",
+        registration.date.format("%Y-%m-%d %H:%M UTC")
+    );
+    if let Some(name) = &registration.as_registered_username {
+        registered.push_str(&format!(" as `{}`", name));
+    }
+    if let Some(source) = &registration.source {
+        registered.push_str(&format!(", via {}", source));
+    }
+    lines.push(registered);
+    lines.push(match &registration.status {
+        RegistrationStatus::Active => "Status: active".to_string(),
+        RegistrationStatus::Withdrawn { at, reason } => format!(
+            "Status: withdrew on {} ({})",
+            at.format("%Y-%m-%d %H:%M UTC"),
+            reason.as_deref().unwrap_or("no reason given")
+        ),
+        RegistrationStatus::Disqualified { at, reason, by } => format!(
+            "Status: disqualified on {} by <@{}> ({})",
+            at.format("%Y-%m-%d %H:%M UTC"),
+            by,
+            reason
+        ),
+    });
+    lines.push(match (registration.reviewed_by, registration.reviewed_at) {
+        (Some(by), Some(at)) => format!(
+            "Reviewed by <@{}> on {}",
+            by,
+            at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        _ => "Not reviewed yet".to_string(),
+    });
+
+    lines.push(String::new());
+    lines.push(match tournament.snapshot_at() {
+        None => "Announcement: no snapshot yet".to_string(),
+        Some(_) => match tournament.snapshot().iter().find(|u| u._id == tetrio_id) {
+            Some(snapshot) => format!("Announcement: {}", league_summary(snapshot)),
+            None => "Announcement: unranked".to_string(),
+        },
+    });
+    lines.push(match current {
+        Some(current) => format!("Current: {}", league_summary(current)),
+        None => "Current: unknown".to_string(),
+    });
+
+    if let (Some(player), Some(current)) = (player.as_ref(), current) {
+        let account_created_at = match tournament.restrictions.min_account_age_days {
+            Some(_) => db.players.account_created_at(player).await.ok().flatten(),
+            None => None,
+        };
+        lines.push(String::new());
+        match tournament
+            .eligibility_or_preview(current, account_created_at)
+            .await
+        {
+            Ok(verdict) => lines.extend(verdict_to_lines(&verdict, Style::Discord)),
+            Err(err) => lines.push(format!("Could not check eligibility: {}", err)),
+        }
+    }
+
+    let alerts: Vec<&StaffAlert> = tournament
+        .alerts
+        .iter()
+        .filter(|alert| alert.tetrio_id == tetrio_id)
+        .collect();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} notes, {} alerts",
+        registration.notes.len(),
+        alerts.len()
+    ));
+    for note in registration.notes.iter().rev().take(3) {
+        lines.push(format!(
+            "📝 {} (<@{}>, {})",
+            sanitize_mentions(&note.text),
+            note.by,
+            note.at.format("%Y-%m-%d")
+        ));
+    }
+    if let Some(alert) = alerts.last() {
+        lines.push(format!("⚠️ {}", alert.text));
+    }
+
+    lines.join("\n")
+}
+
+// Asks the author of `msg` for a line of text, `None` if they cancel or don't answer in time
+async fn ask_text(
+    ctx: &Context,
+    msg: &Message,
+    prompt: &str,
+) -> Result<Option<String>, SerenityError> {
+    let prompt_msg = say(&ctx, msg.channel_id, prompt).await?;
+    let reply = msg
+        .author
+        .await_reply(&ctx)
+        .channel_id(msg.channel_id)
+        .timeout(REVIEW_REPLY_TIMEOUT)
+        .await;
+    let _ = prompt_msg.delete(&ctx.http).await;
+
+    Ok(reply.and_then(|reply| {
+        let text = reply.content.trim().to_string();
+        if text.is_empty() || text.eq_ignore_ascii_case("cancel") {
+            None
+        } else {
+            Some(text)
+        }
+    }))
+}
+
+#[command]
+#[usage("[--seed] [--unreviewed]")]
+#[example("")]
+#[example("--seed --unreviewed")]
+/// Opens a browser over the registrations of the active tournament, one player per page.
+/// React with ⏮️/⏭️ to navigate, 🚫 to disqualify, 📝 to add a note and ✅ to mark as reviewed.
+/// Use `--seed` to sort by seed instead of registration date and `--unreviewed` to skip reviewed players.
+/// The session ends after 10 minutes without a reaction.
+async fn review(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (_, flags) = split_flags(&args, &[]);
+    let order = if flags.contains_key("seed") {
+        RegOrder::Seed
+    } else {
+        RegOrder::RegistrationDate
+    };
+    let filter = if flags.contains_key("unreviewed") {
+        review::Filter::Unreviewed
+    } else {
+        review::Filter::All
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "There is no active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let (registrations, _) = tournament.registrations_page(0, usize::MAX, order);
+    let reviewed = registrations
+        .iter()
+        .filter(|r| r.reviewed_by.is_some())
+        .map(|r| r.tetrio_id.clone())
+        .collect();
+    let ids = registrations.into_iter().map(|r| r.tetrio_id).collect();
+    let mut session = ReviewSession::new(ids, reviewed, filter);
+
+    let mut page = say(&ctx, msg.channel_id, "Loading registrations...").await?;
+    for action in review::Action::ALL.iter() {
+        page.react(&ctx.http, ReactionType::Unicode(action.emoji().to_string()))
+            .await?;
+    }
+
+    let mut effect = session.show();
+    loop {
+        match &effect {
+            Effect::Show(tetrio_id) => {
+                // Read again on every page, so changes from this session and from others show up
+                let tournament = match db.tournaments.get_tournament(&tournament.shorthand).await {
+                    Ok(Some(tournament)) => tournament,
+                    _ => break,
+                };
+                let (position, total) = session.position();
+                let mut description = review_page(&db, &tournament, tetrio_id).await;
+                if description.len() > 2000 {
+                    let mut end = 2000;
+                    while !description.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    description.truncate(end);
+                }
+                page.edit(&ctx, |m| {
+                    m.content("").embed(|e| {
+                        badge_embed(e, &tournament);
+                        e.title(format!(
+                            "{}: Review {}/{}",
+                            tournament.shorthand, position, total
+                        ))
+                        .description(description)
+                    })
+                })
+                .await?;
+            }
+            Effect::Empty => {
+                page.edit(&ctx, |m| m.content("Nothing to review")).await?;
+                break;
+            }
+            _ => {}
+        }
+
+        let reaction = match page
+            .await_reaction(&ctx)
+            .author_id(msg.author.id)
+            .timeout(REVIEW_TIMEOUT)
+            .await
+        {
+            Some(reaction) => reaction,
+            None => break,
+        };
+        let reaction = reaction.as_inner_ref();
+        // Removing the reaction right away lets staff press it again
+        let _ = reaction.delete(&ctx.http).await;
+        let action = match review::Action::from_emoji(&reaction.emoji.to_string()) {
+            Some(action) => action,
+            None => continue,
+        };
+
+        effect = match session.handle(action) {
+            Effect::AskDisqualifyReason(tetrio_id) => {
+                let prompt = format!(
+                    "Reason for disqualifying `{}`? Reply with `cancel` to keep them.",
+                    tetrio_id
+                );
+                if let Some(reason) = ask_text(&ctx, msg, &prompt).await? {
+                    match db
+                        .tournaments
+                        .disqualify(&tournament.shorthand, &tetrio_id, &reason, msg.author.id.0)
+                        .await
+                    {
+                        Ok(()) => tracing::info!(
+                            target: "audit",
+                            "{} disqualified {} from {} ({})",
+                            msg.author.id,
+                            tetrio_id,
+                            tournament.shorthand,
+                            reason
+                        ),
+                        Err(err) => {
+                            say(&ctx, msg.channel_id, err).await?;
+                        }
+                    }
+                }
+                session.show()
+            }
+            Effect::AskNote(tetrio_id) => {
+                let prompt = format!("Note for `{}`? Reply with `cancel` to skip it.", tetrio_id);
+                if let Some(text) = ask_text(&ctx, msg, &prompt).await? {
+                    if let Err(err) = db
+                        .tournaments
+                        .add_registration_note(
+                            &tournament.shorthand,
+                            &tetrio_id,
+                            &text,
+                            msg.author.id.0,
+                        )
+                        .await
+                    {
+                        say(&ctx, msg.channel_id, err).await?;
+                    }
+                }
+                session.show()
+            }
+            Effect::MarkReviewed(tetrio_id) => {
+                match db
+                    .tournaments
+                    .mark_reviewed(&tournament.shorthand, &tetrio_id, msg.author.id.0)
+                    .await
+                {
+                    Ok(()) => session.reviewed(&tetrio_id),
+                    Err(err) => {
+                        say(&ctx, msg.channel_id, err).await?;
+                        session.show()
+                    }
+                }
+            }
+            other => other,
+        };
+    }
+
+    let _ = page.delete_reactions(&ctx.http).await;
+    let _ = page.edit(&ctx, |m| m.content("Review session ended")).await;
+    Ok(())
+}
+
+#[command]
+#[usage(
+    "<tournament> <announcement> [registration opens] [registration closes] [tournament starts]"
+)]
+#[example("UC12 2021-03-01T18:00 2021-03-01T18:00 2021-03-08T18:00 2021-03-13T16:00")]
+#[example("UC12 - - 2021-03-09T18:00")]
+#[example("UC12 - none")]
+/// Sets the lifecycle dates of a tournament, in UTC or RFC 3339 with a timezone.
+/// Use `-` to keep a date and `none` to clear it, dates that aren't given are kept.
+/// Registrations are only accepted between registration opening and closing, unless staff register someone.
+async fn set_dates(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, _) = split_flags(&args, &[]);
+
+    let tournament = match positional.get(0) {
+        Some(name) => match db.tournaments.get_tournament(name).await {
+            Ok(Some(tournament)) => tournament,
+            Ok(None) => {
+                say(&ctx, msg.channel_id, "Tournament not found").await?;
+                return Ok(());
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+    };
+
+    let mut dates = tournament.dates.clone();
+    let fields = vec![
+        &mut dates.announcement_at,
+        &mut dates.registration_opens_at,
+        &mut dates.registration_closes_at,
+        &mut dates.tournament_starts_at,
+    ];
+    for (field, input) in fields.into_iter().zip(positional.iter().skip(1)) {
+        match input.as_str() {
+            "-" => {}
+            "none" => *field = None,
+            _ => match parse_datetime(input) {
+                Some(date) => *field = Some(BsonDateTime::from(date)),
+                None => {
+                    say(
+                        &ctx,
+                        msg.channel_id,
+                        format!(
+                            "`{}` is not a date, use `2021-03-14T18:30` (UTC) or RFC 3339",
+                            input
+                        ),
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            },
+        }
+    }
+
+    let tournament = match db
+        .tournaments
+        .set_dates(&tournament.shorthand, &dates)
+        .await
+    {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    tracing::info!(
+        target: "audit",
+        "{} set the dates of {} to {:?}",
+        msg.author.id,
+        tournament.shorthand,
+        tournament.dates
+    );
+
+    let lines: Vec<String> = tournament
+        .dates
+        .list()
+        .iter()
+        .map(|(name, date)| format!("{}: {}", name, discord_timestamp(*date, 'f')))
+        .collect();
+    let reply = if lines.is_empty() {
+        "No dates are set, registration is open whenever the tournament is active".to_string()
+    } else {
+        lines.join("\n")
+    };
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("<tournament> <cap|none>")]
+#[example("UC12 128")]
+#[example("UC12 none")]
+/// Caps how many players can register, later registrations go on the waitlist.
+/// Raising the cap promotes players from the waitlist right away, lowering it doesn't remove anyone.
+async fn set_cap(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) => name,
+        Err(_) => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+    };
+    let cap = match args.current() {
+        Some("none") => None,
+        Some(arg) => match arg.parse::<u32>() {
+            Ok(cap) => Some(cap),
+            Err(_) => {
+                say(&ctx, msg.channel_id, "Cap has to be a number or `none`").await?;
+                return Ok(());
+            }
+        },
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (cap)").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.set_max_participants(&name, cap).await {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    tracing::info!(
+        target: "audit",
+        "{} set the registration cap of {} to {:?}",
+        msg.author.id,
+        tournament.shorthand,
+        cap
+    );
+    fill_from_waitlist(&ctx, &db, &tournament).await;
+
+    let reply = match cap {
+        Some(cap) => format!(
+            "{} is capped at {} players ({} registered, {} waiting)",
+            tournament.name,
+            cap,
+            tournament.registered_players().len(),
+            tournament.waitlist.len()
+        ),
+        None => format!("{} is not capped anymore", tournament.name),
+    };
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[tournament]")]
+#[example("UC12")]
+/// Shows the waitlist of the active tournament, in the order players get promoted.
+async fn waitlist(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.resolve_active(args.current()).await {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let ids: Vec<&str> = tournament
+        .waitlist
+        .iter()
+        .map(|entry| entry.tetrio_id.as_str())
+        .collect();
+    let players = db
+        .players
+        .get_players(doc! {"tetrio_id": {"$in": ids}}, None)
+        .await
+        .unwrap_or_default();
+
+    let mut report = Report::new(
+        &format!("Waitlist of {}", tournament.shorthand),
+        &["#", "Username", "Waiting since"],
+    );
+    for (i, entry) in tournament.waitlist.iter().enumerate() {
+        let current = players
+            .iter()
+            .find(|player| player.tetrio_id == entry.tetrio_id)
+            .and_then(|player| player.tetrio_data.as_ref())
+            .map(|data| data.username.as_str());
+        report.push_row(vec![
+            (i + 1).to_string(),
+            entry.display_name(current),
+            entry.recorded_at().format("%Y-%m-%d %H:%M UTC").to_string(),
+        ]);
+    }
+    match tournament.restrictions.max_participants {
+        Some(cap) => report.push_note(&format!(
+            "{} of {} spots taken",
+            tournament.registered_players().len(),
+            cap
+        )),
+        None => report.push_note("Not capped, use `.set_cap` to limit registrations"),
+    }
+    send_report(&ctx, msg.channel_id, &report).await
+}
+
+#[command]
+#[usage("<role mention or ID|none> [tournament]")]
+#[example("@Participant")]
+#[example("@Participant UC12")]
+#[example("none")]
+/// Sets the role registered players get, use `none` to stop handing out a role.
+/// Players get the role when they register and lose it when they withdraw, use `.sync_roles` to fix up existing registrations.
+async fn set_participant_role(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, _) = split_flags(&args, &[]);
+
+    let role_id = match positional.get(0).map(|s| s.as_str()) {
+        Some("none") => None,
+        Some(arg) => match serenity::utils::parse_role(arg).or_else(|| arg.parse::<u64>().ok()) {
+            Some(role_id) => Some(role_id),
+            None => {
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    "Role provided was not valid (use a mention or ID)",
+                )
+                .await?;
+                return Ok(());
+            }
+        },
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (role)").await?;
+            return Ok(());
+        }
+    };
+
+    let tournament = match positional.get(1) {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => db.tournaments.get_active().await,
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let tournament = match db
+        .tournaments
+        .set_participant_role(&tournament.shorthand, role_id)
+        .await
+    {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    tracing::info!(
+        target: "audit",
+        "{} set the participant role of {} to {:?}",
+        msg.author.id,
+        tournament.shorthand,
+        role_id
+    );
+
+    let reply = match role_id {
+        Some(role_id) => format!(
+            "Registered players of {} get <@&{}> from now on",
+            tournament.name, role_id
+        ),
+        None => format!(
+            "Registered players of {} don't get a role anymore",
+            tournament.name
+        ),
+    };
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[tournament]")]
+#[example("")]
+#[example("UC12")]
+/// Gives the participant role to every registered player and takes it from everyone else.
+/// Registered players who aren't in the server are skipped.
+async fn sync_roles(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let name = args.current().map(|s| s.to_string());
+
+    let tournament = match &name {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => db.tournaments.get_active().await,
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let role_id = match tournament.participant_role {
+        Some(role_id) => RoleId(role_id),
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "This tournament has no participant role, use `.set_participant_role` first",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut registered: Vec<u64> = match db
+        .tournaments
+        .get_registered_players(&db.players, Some(&tournament.shorthand))
+        .await
+    {
+        Ok(players) => players.iter().filter_map(|p| p.discord_id).collect(),
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let guild_id = GuildId(crate::discord::UC_GUILD_ID);
+    let (mut added, mut removed, mut failed) = (0, 0, 0);
+    let mut after: Option<UserId> = None;
+    loop {
+        let members = match guild_id.members(&ctx.http, Some(1000), after).await {
+            Ok(members) => members,
+            Err(err) => {
+                let _ = typing.stop();
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    format!("Could not fetch members ({})", err),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        for member in &members {
+            let user_id = member.user.id.0;
+            let should_have = match registered.iter().position(|id| *id == user_id) {
+                Some(index) => {
+                    registered.swap_remove(index);
+                    true
+                }
+                None => false,
+            };
+            let result = match (should_have, member.roles.contains(&role_id)) {
+                (true, false) => {
+                    added += 1;
+                    ctx.http
+                        .add_member_role(guild_id.0, user_id, role_id.0)
+                        .await
+                }
+                (false, true) => {
+                    removed += 1;
+                    ctx.http
+                        .remove_member_role(guild_id.0, user_id, role_id.0)
+                        .await
+                }
+                _ => continue,
+            };
+            if let Err(err) = result {
+                tracing::warn!("Could not update the roles of {}: {}", user_id, err);
+                failed += 1;
+            }
+        }
+
+        match members.last() {
+            Some(last) if members.len() == 1000 => after = Some(last.user.id),
+            _ => break,
+        }
+    }
+    let _ = typing.stop();
+
+    tracing::info!(
+        target: "audit",
+        "{} synced the participant role of {}, {} added, {} removed, {} failed",
+        msg.author.id,
+        tournament.shorthand,
+        added,
+        removed,
+        failed
+    );
+    say(
+        &ctx,
+        msg.channel_id,
+        badged(
+            &tournament,
+            &format!(
+                "Added the role to {} players and removed it from {} users, {} changes failed. {} registered players are not in the server.",
+                added,
+                removed,
+                failed,
+                registered.len()
+            ),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command]
+#[usage("<channel mention or ID|none>")]
+#[example("#announcements")]
+#[example("none")]
+/// Sets the channel rank-ups of linked players are announced in, use `none` to stop the announcements.
+/// Announcements also need the `news_poller` feature flag.
+async fn set_news_channel(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let channel_id = match args.current() {
+        Some("none") => None,
+        Some(arg) => {
+            match serenity::utils::parse_channel(arg).or_else(|| arg.parse::<u64>().ok()) {
+                Some(channel_id) => Some(channel_id),
+                None => {
+                    say(
+                        &ctx,
+                        msg.channel_id,
+                        "Channel provided was not valid (use a mention or ID)",
+                    )
+                    .await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (channel)").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    if let Err(err) = db.settings.set_news_channel(channel_id).await {
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+    tracing::info!(
+        target: "audit",
+        "{} set the news channel to {:?}",
+        msg.author.id,
+        channel_id
+    );
+
+    let reply = match channel_id {
+        Some(channel_id) if db.flags.is_enabled(Feature::NewsPoller).await => {
+            format!("Rank-ups are announced in <#{}> from now on", channel_id)
+        }
+        Some(channel_id) => format!(
+            "Rank-ups will be announced in <#{}> once the `{}` flag is enabled",
+            channel_id,
+            Feature::NewsPoller
+        ),
+        None => "Rank-ups are not announced anymore".to_string(),
+    };
+    say(&ctx, msg.channel_id, reply).await?;
+    Ok(())
+}
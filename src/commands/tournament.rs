@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -7,60 +9,168 @@ use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::{collector::ReactionAction, http::AttachmentType};
 
-use crate::database::tournaments::{RegistrationError, TournamentEntry};
+use crate::bracket;
+use crate::check_in::{self, Compensation, Step};
+use crate::database::flags::Feature;
+use crate::database::players::PlayerEntry;
+use crate::database::tournaments::{
+    carry_over_check_ins, is_stale_check_in, CheckInRecord, RegOrder, RegistrationError,
+    RegistrationOutcome, StaffAlert, TournamentEntry, TournamentRestrictions,
+};
 use crate::database::{DatabaseError, LocalDatabase};
+use crate::discord::messages::{self, LocalizedMessage, MessageKey};
+use crate::discord::report::{send_report, Report};
 use crate::discord::util::*;
-use crate::discord::IdCollection;
 use crate::discord::CONFIRM_EMOJI;
+use crate::discord::{IdCollection, PingCooldowns, Shutdown};
+use crate::eligibility::{self, verdict_to_lines, Style};
+use crate::member_watch::{self, Action, MemberEvent};
+use crate::tasks::TaskHandle;
+use crate::tetrio::Rank;
 
 #[command]
-#[usage("[Tetr.io username or ID]")]
+#[usage("[tournament] [Tetr.io username or ID]")]
 #[example("caboozled_pie")]
 #[example("5e47696db7c60f23a497ee6c")]
+#[example("UC12 caboozled_pie")]
 /// Will register you to the ongoing tournament.
 /// If no account is linked, then it will link you with the provided username.
-async fn register(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+/// If you're linked to a different account, you're asked whether to link the provided one instead.
+/// If more than one tournament is ongoing, put the shorthand of the tournament first.
+async fn register(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-    let reply = match db.tournaments.register_to_active(
-        &db.players,
-        args.current(),
-        msg.author.id.0,
-        false,
-    ) {
-        Ok(entry) => {
+
+    let first = args.single_quoted::<String>().ok();
+    let second = args.single_quoted::<String>().ok();
+    // A single argument is a tournament if one with that shorthand is active, otherwise a username
+    let active = match (&first, &second) {
+        (Some(_), None) => db.tournaments.get_all_active().await.unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let is_active = |name: &str| {
+        active
+            .iter()
+            .any(|t| t.shorthand.eq_ignore_ascii_case(name))
+    };
+    let (tournament, username) = match (first, second) {
+        (Some(tournament), Some(username)) => (Some(tournament), Some(username)),
+        (Some(first), None) if is_active(&first) => (Some(first), None),
+        (first, _) => (None, first),
+    };
+
+    let register = || {
+        db.tournaments.register_to_active(
+            &db.players,
+            tournament.as_deref(),
+            username.as_deref(),
+            msg.author.id.0,
+            false,
+            Some(message_ref(msg)),
+        )
+    };
+    let mut result = register().await;
+
+    // Switching accounts needs an explicit confirmation, the old link is gone afterwards
+    if let Err(RegistrationError::LinkedToDifferentAccount { linked, requested }) = &result {
+        let prompt = format!(
+            "You're linked to {}. Unlink it and register as {} instead?",
+            linked, requested
+        );
+        let cancelled = format!("Cancelled, you're still linked to {}", linked);
+        if !confirm_prompt(&ctx, &msg, &prompt).await? {
+            let reply = say(&ctx, msg.channel_id, cancelled).await?;
+            delay_delete(&ctx, Some(reply)).await?;
+            return Ok(());
+        }
+        result = match db.players.unlink_by_discord(msg.author.id.0).await {
+            Ok(_) => register().await,
+            Err(err) => Err(RegistrationError::DatabaseError(err)),
+        };
+    }
+
+    let reply = match result {
+        Ok((_, tournament, RegistrationOutcome::Waitlisted(position))) => {
+            react_confirm(&ctx, &msg).await;
+            let content = format!(
+                "{} is full, you're #{} on the waitlist. You'll get a DM once a spot opens up!",
+                tournament.name, position
+            );
+            Some(
+                say_pinging(
+                    &ctx,
+                    msg.channel_id,
+                    format!("<@{}> {}", msg.author.id, badged(&tournament, &content)),
+                    &[msg.author.id.0],
+                )
+                .await?,
+            )
+        }
+        Ok((entry, tournament, RegistrationOutcome::Registered)) => {
             react_confirm(&ctx, &msg).await;
             super::player::rename_user_to_tetrio(&ctx, msg, &entry).await?;
+            update_participant_role(&ctx, &tournament, msg.author.id.0, true).await;
+            let mut embed = player_data_to_embed(&entry, Some(&tournament));
+            badge_embed(&mut embed, &tournament);
+
+            let registration = tournament.registration(&entry.tetrio_id);
+            let mut content = match registration.filter(|r| r.reactivated_at.is_some()) {
+                Some(r) => format!(
+                    "You're registered to {} again, your original registration from {} was restored!",
+                    tournament.name,
+                    r.date.format("%Y-%m-%d %H:%M UTC")
+                ),
+                None => format!("You're now registered to {}!", tournament.name),
+            };
+            let receipt = registration.and_then(|r| tournament.receipt_of(r));
+            if let Some(receipt) = &receipt {
+                content.push_str(&format!(
+                    " Your receipt is `{}`, use `.receipt` to see it again",
+                    receipt
+                ));
+                // The reply gets deleted, so keep a copy in the DMs. Closed DMs are fine.
+                let _ = msg
+                    .author
+                    .direct_message(&ctx.http, |m| {
+                        m.content(badged(
+                            &tournament,
+                            &format!(
+                                "Registration receipt for {}: `{}`",
+                                tournament.name, receipt
+                            ),
+                        ))
+                    })
+                    .await;
+            }
+
             Some(
                 msg.channel_id
-                    .send_message(&ctx.http, |m| m.set_embed(player_data_to_embed(&entry)))
+                    .send_message(&ctx.http, |m| {
+                        m.content(badged(&tournament, &content))
+                            .set_embed(embed)
+                            .allowed_mentions(|am| am.empty_parse())
+                    })
                     .await?,
             )
         }
         Err(err) => {
             react_deny(&ctx, &msg).await;
-            let reply = match err {
-                RegistrationError::MissingArgument(_) =>
-                    "There is no Tetr.io account linked to you right now, please provide a username. `.register [username]`".to_string(),
-                RegistrationError::AlreadyRegistered => "You're already registered!".to_string(),
-                RegistrationError::RdTooHigh { .. } // TODO: refer to a faq command for rd
-                | RegistrationError::NoTournamentActive
-                | RegistrationError::CurrentRankTooHigh { .. }
-                | RegistrationError::AnnouncementRankTooHigh { .. }
-                | RegistrationError::NotEnoughGames { .. }
-                | RegistrationError::UnrankedOnAnnouncementDay(_) => format!("{}", err),
-                RegistrationError::DatabaseError(err) => match err {
-                    DatabaseError::DuplicateDiscordEntry => "You're already linked to someone else! Use the `unlink` command if you'd like to link to someone else.".to_string(),
-                    DatabaseError::DuplicateTetrioEntry => "Someone else has already linked this user!".to_string(),
-                    DatabaseError::NotFound => "Could not find specified user!".to_string(),
-                    _ => format!("{:?}", err)
-                },
-                _ => format!("{:?}", err)
+            let language = messages::language_of(&db, msg.author.id.0).await;
+            let reply = messages::registration_error(&err).render(language);
+
+            // Registration always targets the active tournament, so badge that one if there is any
+            let reply = match db.tournaments.get_active().await {
+                Ok(Some(tournament)) => badged(&tournament, &reply),
+                _ => reply,
             };
 
             Some(
-                msg.channel_id
-                    .say(&ctx.http, format!("<@{}> {}", msg.author.id, reply))
-                    .await?,
+                say_pinging(
+                    &ctx,
+                    msg.channel_id,
+                    format!("<@{}> {}", msg.author.id, reply),
+                    &[msg.author.id.0],
+                )
+                .await?,
             )
         }
     };
@@ -71,391 +181,2634 @@ async fn register(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-/// Unregisters you from the ongoing tournament.
-async fn unregister(ctx: &Context, msg: &Message) -> CommandResult {
+#[usage("[Tetr.io username or ID]")]
+#[example("caboozled_pie")]
+/// Checks whether you (or the specified player) meet the restrictions of the ongoing tournament.
+/// Uses your linked account if no username is provided.
+/// Before the announcement stats are taken, this shows a preview based on current stats.
+async fn can_participate(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-    let reply = match db
-        .tournaments
-        .unregister_by_discord(&db.players, msg.author.id.0)
-    {
-        Ok(_) => {
-            react_confirm(&ctx, &msg).await;
-            None
+
+    let tetrio_id = match args.current() {
+        Some(username) => username.to_lowercase(),
+        None => match db.players.get_player_by_discord(msg.author.id.0).await {
+            Ok(Some(entry)) => entry.tetrio_id,
+            Ok(None) => {
+                say(&ctx, msg.channel_id, "There is no Tetr.io account linked to you right now, please provide a username. `.can_participate [username]`",
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Err(err) => {
+                say(&ctx, msg.channel_id, err).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
         }
         Err(err) => {
-            react_deny(&ctx, &msg).await;
-            match err {
-                RegistrationError::DatabaseError(err) => match err {
-                    DatabaseError::NotFound => Some(msg.channel_id.say(&ctx.http, err).await?),
-                    _ => {
-                        tracing::warn!("{}", err);
-                        Some(msg.channel_id.say(&ctx.http, err).await?)
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match db.players.update_player(&tetrio_id, false).await {
+        Ok(entry) if entry.tetrio_data.is_some() => {
+            let account_created_at = match tournament.restrictions.min_account_age_days {
+                Some(_) => db.players.account_created_at(&entry).await.ok().flatten(),
+                None => None,
+            };
+            match tournament
+                .eligibility_or_preview(entry.tetrio_data.as_ref().unwrap(), account_created_at)
+                .await
+            {
+                Ok(verdict) => {
+                    let mut lines = verdict_to_lines(&verdict, Style::Discord);
+                    if let Err(err) = tournament.check_qualified(&entry.tetrio_id) {
+                        lines.insert(0, err.to_string());
                     }
-                },
-                _ => {
-                    tracing::warn!("{}", err);
-                    Some(msg.channel_id.say(&ctx.http, err).await?)
+                    badged(&tournament, &lines.join("\n"))
                 }
+                Err(RegistrationError::DatabaseError(DatabaseError::TetrioApiError(api_err))) => {
+                    tetrio_error_reply(&api_err).to_string()
+                }
+                Err(err) => err.to_string(),
             }
         }
+        Ok(_) | Err(DatabaseError::NotFound) => "Player does not exist on Tetr.io".to_string(),
+        Err(DatabaseError::TetrioApiError(api_err)) => tetrio_error_reply(&api_err).to_string(),
+        Err(err) => err.to_string(),
     };
 
-    delay_delete(&ctx, reply).await?;
+    say(&ctx, msg.channel_id, reply).await?;
+
     Ok(())
 }
 
 #[command]
-#[owners_only]
-async fn add_snapshot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    match args.current() {
-        None => {
-            msg.channel_id
-                .say(&ctx.http, "Missing argument (tournament)")
-                .await?;
-        }
-        Some(arg) => {
-            let db = crate::discord::get_database(&ctx).await;
-
-            let tournament = match db.tournaments.get_tournament(arg) {
-                Ok(tournament_option) => match tournament_option {
-                    Some(tournament) => tournament,
-                    None => {
-                        react_deny(&ctx, &msg).await;
-                        msg.channel_id
-                            .say(&ctx.http, "Tournament not found")
-                            .await?;
-                        return Ok(());
-                    }
-                },
-                Err(err) => {
-                    react_deny(&ctx, &msg).await;
-                    msg.channel_id.say(&ctx.http, err).await?;
-                    return Ok(());
-                }
-            };
-
-            let mut replies = vec![
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "Updating all player stats, could take a few minutes...",
-                    )
-                    .await?,
-            ];
+#[usage("[page] [--seed] | --ranks")]
+#[example("")]
+#[example("2 --seed")]
+#[example("--ranks")]
+/// Lists the players registered to the ongoing tournament, 20 per page.
+/// Sorted by registration date, or by seed with `--seed`.
+/// With `--ranks`, everyone is grouped by their current rank instead.
+async fn player_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const PAGE_SIZE: usize = 20;
 
-            // let typing = msg.channel_id.start_typing(&ctx.http)?;
-            // let update_result = db.players.update_from_leaderboard();
-            // typing.stop();
-
-            // if let Err(err) = update_result {
-            //     react_deny(&ctx, &msg).await;
-            //     msg.channel_id.say(&ctx.http, err).await?;
-            //     return Ok(());
-            // }
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, flags) = split_flags(&args, &[]);
+    let page = positional
+        .get(0)
+        .and_then(|p| p.parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let order = if flags.contains_key("seed") {
+        RegOrder::Seed
+    } else {
+        RegOrder::RegistrationDate
+    };
 
-            // replies.push(
-            //     msg.channel_id
-            //         .say(&ctx.http, "Finished updating all players")
-            //         .await?,
-            // );
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-            replies.push(
-                msg.channel_id
-                    .say(&ctx.http, "Creating snapshot...")
-                    .await?,
-            );
+    if flags.contains_key("ranks") {
+        return player_list_by_rank(&ctx, &msg, &db, &tournament).await;
+    }
 
-            match db.tournaments.add_snapshot(&tournament.shorthand) {
-                Ok(_) => {
-                    react_confirm(&ctx, &msg).await;
-                    tokio::time::sleep(Duration::from_secs(10)).await;
-                    for reply in replies {
-                        reply.delete(&ctx.http).await?;
-                    }
-                }
-                Err(err) => {
-                    react_deny(&ctx, &msg).await;
-                    msg.channel_id.say(&ctx.http, err).await?;
-                }
-            }
+    let offset = (page - 1) * PAGE_SIZE;
+    let (entries, total) = match db
+        .tournaments
+        .get_registrations_page(&tournament.shorthand, offset, PAGE_SIZE, order)
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
         }
     };
 
-    Ok(())
+    let ids: Vec<&str> = entries.iter().map(|e| e.tetrio_id.as_str()).collect();
+    let players = db
+        .players
+        .get_players(bson::doc! {"tetrio_id": {"$in": ids}}, None)
+        .await
+        .unwrap_or_default();
+
+    let mut report = Report::new(
+        &format!("{} Registered players", tournament_badge(&tournament)),
+        &["#", "Player", "Registered"],
+    );
+    for (i, entry) in entries.iter().enumerate() {
+        let current = players
+            .iter()
+            .find(|p| p.tetrio_id == entry.tetrio_id)
+            .and_then(|p| p.tetrio_data.as_ref())
+            .map(|data| data.username.as_str());
+        report.push_row(vec![
+            (offset + i + 1).to_string(),
+            entry.display_name(current),
+            entry.date.format("%Y-%m-%d %H:%M").to_string(),
+        ]);
+    }
+    let pages = ((total + PAGE_SIZE - 1) / PAGE_SIZE).max(1);
+    report.push_note(&format!("Page {}/{}, {} players", page, pages, total));
+    if let Some(summary) = tournament.qualifier_summary() {
+        report.push_note(&summary);
+    }
+
+    send_report(&ctx, msg.channel_id, &report).await
 }
 
+// Characters allowed in an embed field value
+const EMBED_FIELD_LIMIT: usize = 1024;
 #[command]
-#[owners_only]
-async fn create_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+#[aliases("countries")]
+/// Shows which countries the players registered to the ongoing tournament come from.
+/// Players who hide their country on Tetr.io are counted as unknown.
+async fn country_stats(ctx: &Context, msg: &Message) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-    let tournament = match db.tournaments.get_active() {
-        Ok(tournament) => match tournament {
-            Some(tournament) => tournament,
-            None => {
-                react_deny(&ctx, &msg).await;
-                msg.channel_id
-                    .say(&ctx.http, "No active tournament")
-                    .await?;
-                return Ok(());
-            }
-        },
+    let ids: Vec<String> = tournament
+        .registered_players()
+        .iter()
+        .map(|entry| entry.tetrio_id.clone())
+        .collect();
+    if ids.is_empty() {
+        say(
+            &ctx,
+            msg.channel_id,
+            badged(&tournament, "Nobody is registered yet"),
+        )
+        .await?;
+        return Ok(());
+    }
+    let registered = ids.len() as u64;
+    let ids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+    let distribution = db.players.country_distribution(&ids).await;
+    let mut distribution = match distribution {
+        Ok(distribution) => distribution,
         Err(err) => {
-            react_deny(&ctx, &msg).await;
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
             return Ok(());
         }
     };
 
-    let check_in_msg = msg
-        .channel_id
+    // registered players without a player entry have no country either
+    let counted: u64 = distribution.iter().map(|(_, count)| count).sum();
+    let unknown = distribution
+        .iter()
+        .position(|(country, _)| country.is_none())
+        .map_or(0, |i| distribution.remove(i).1)
+        + registered.saturating_sub(counted);
+
+    let mut lines: Vec<String> = distribution
+        .iter()
+        .filter_map(|(country, count)| country.as_ref().map(|country| (country, count)))
+        .map(|(country, count)| {
+            format!(
+                "{} `{}` {}",
+                country_flag(country).unwrap_or_else(|| "🏳️".to_string()),
+                country,
+                count
+            )
+        })
+        .collect();
+    let countries = lines.len();
+    if unknown > 0 {
+        lines.push(format!("❔ Unknown {}", unknown));
+    }
+    let mut description = lines.join("\n");
+    if description.len() > 2000 {
+        let mut end = 2000;
+        while !description.is_char_boundary(end) {
+            end -= 1;
+        }
+        description.truncate(end);
+    }
+
+    msg.channel_id
         .send_message(&ctx.http, |m| {
             m.embed(|e| {
-                e.title(format!("{}: Check-in", tournament.shorthand))
-                    .description(format!(
-                        "React to this message with {} in order to check-in! Unreact to check-out.",
-                        crate::discord::CONFIRM_EMOJI
-                    ))
+                badge_embed(e, &tournament);
+                e.title("Registered players by country")
+                    .description(description)
+                    .footer(|f| {
+                        f.text(format!(
+                            "{} players from {} countries",
+                            registered, countries
+                        ))
+                    })
             })
         })
         .await?;
 
-    if let Err(err) = db
-        .tournaments
-        .set_check_in_msg(&tournament.shorthand, check_in_msg.id.0)
-    {
-        react_deny(&ctx, &msg).await;
-        msg.channel_id
-            .say(
-                &ctx.http,
-                format!(
-                    "Could not set check-in message in tournament db ({:?})",
-                    err
-                ),
-            )
-            .await?;
-    } else {
-        msg.delete(&ctx.http).await?;
-        init_checkin_reaction_handling(&ctx, db, tournament, &msg, &check_in_msg).await?;
-    }
-
     Ok(())
 }
 
 #[command]
-#[owners_only]
-async fn resume_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+#[usage("[tournament]")]
+#[example("")]
+#[example("UC12")]
+/// Shows the restrictions and state of the ongoing tournament, or of the named one.
+/// Lists every tournament if none is ongoing.
+async fn tournament_info(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let db = crate::discord::get_database(&ctx).await;
-
-    let tournament = match db.tournaments.get_active() {
-        Ok(tournament) => match tournament {
-            Some(tournament) => tournament,
-            None => {
-                react_deny(&ctx, &msg).await;
-                msg.channel_id
-                    .say(&ctx.http, "No active tournament")
-                    .await?;
-                return Ok(());
-            }
-        },
+    let tournament = match args.current() {
+        Some(name) => db.tournaments.get_tournament(name).await,
+        None => db.tournaments.get_active().await,
+    };
+    let tournament = match tournament {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) if args.current().is_some() => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "No tournament with that name or shorthand",
+            )
+            .await?;
+            return Ok(());
+        }
+        Ok(None) => return tournament_overview(&ctx, &msg, &db).await,
         Err(err) => {
-            react_deny(&ctx, &msg).await;
-            msg.channel_id.say(&ctx.http, err).await?;
+            say(&ctx, msg.channel_id, err).await?;
             return Ok(());
         }
     };
 
-    let check_in_msg = match tournament.check_in_msg {
-        Some(msg_id) => msg_id,
-        None => {
-            react_deny(&ctx, &msg).await;
-            msg.channel_id
-                .say(&ctx.http, "No check-in message found")
-                .await?;
+    let restrictions = &tournament.restrictions;
+    let mut lines = vec![
+        format!(
+            "Max announcement rank: {}",
+            restrictions.max_rank.to_emoji()
+        ),
+        format!(
+            "Max current rank: {}",
+            (restrictions.max_rank + 1).to_emoji()
+        ),
+        format!(
+            "Max peak rank: {}",
+            restrictions.highest_rank_limit().to_emoji()
+        ),
+        format!("Max RD: {}", restrictions.max_rd),
+        format!("Min ranked games: {}", restrictions.min_ranked_games),
+    ];
+    if let Some(days) = restrictions.min_account_age_days {
+        lines.push(format!("Min account age: {} days", days));
+    }
+    if restrictions.allow_unranked {
+        lines.push("Unranked players allowed, their current games and RD count".to_string());
+    }
+
+    let registered = tournament.registered_players().len();
+    let registrations = match restrictions.max_participants {
+        Some(max) => format!("{}/{}", registered, max),
+        None => registered.to_string(),
+    };
+    let snapshot = match tournament.snapshot_at() {
+        Some(date) => discord_timestamp(date, 'f'),
+        None => "Not taken yet".to_string(),
+    };
+    let check_in = if tournament.check_in_msg.is_some() {
+        "Posted"
+    } else {
+        "Not posted"
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                badge_embed(e, &tournament);
+                e.title(&tournament.name)
+                    .field("Shorthand", &tournament.shorthand, true)
+                    .field("Registrations", registrations, true)
+                    .field(
+                        "Status",
+                        if tournament.is_active() {
+                            "Active"
+                        } else {
+                            "Inactive"
+                        },
+                        true,
+                    )
+                    .field("Restrictions", lines.join("\n"), false)
+                    .field("Stat snapshot", snapshot, true)
+                    .field("Check-in message", check_in, true)
+                    .field(
+                        "Created",
+                        discord_timestamp(tournament.created_at(), 'D'),
+                        true,
+                    )
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+// `.tournament_info` without an active tournament, every tournament and whether it's active
+async fn tournament_overview(ctx: &Context, msg: &Message, db: &LocalDatabase) -> CommandResult {
+    let tournaments = match db.tournaments.get_all().await {
+        Ok(tournaments) => tournaments,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
             return Ok(());
         }
     };
+    if tournaments.is_empty() {
+        say(&ctx, msg.channel_id, "There are no tournaments yet").await?;
+        return Ok(());
+    }
 
-    // TODO: hardcoded IDs
-    let check_in_msg = ctx
-        .http
-        .get_message(822933717453504562, check_in_msg)
-        .await?;
+    let mut report = Report::new("Tournaments", &["Shorthand", "Name", "Active", "Created"]);
+    for tournament in &tournaments {
+        report.push_row(vec![
+            tournament.shorthand.clone(),
+            tournament.name.clone(),
+            if tournament.is_active() { "yes" } else { "no" }.to_string(),
+            tournament.created_at().format("%Y-%m-%d").to_string(),
+        ]);
+    }
+    report.push_note("No tournament is active right now");
 
-    init_checkin_reaction_handling(&ctx, db, tournament, &msg, &check_in_msg).await
+    send_report(&ctx, msg.channel_id, &report).await
 }
 
-async fn init_checkin_reaction_handling(
+// Fields allowed in an embed
+const EMBED_MAX_FIELDS: usize = 25;
+// Characters allowed in all fields of an embed together, leaving room for the title and footer
+const EMBED_FIELDS_TOTAL: usize = 5500;
+
+// `.player_list --ranks`, registrants grouped by current rank, highest rank first
+// Ranks with more players than fit in a field get more fields, and more embeds if needed
+async fn player_list_by_rank(
     ctx: &Context,
-    db: Arc<LocalDatabase>,
-    tournament: TournamentEntry,
     msg: &Message,
-    check_in_msg: &Message,
+    db: &LocalDatabase,
+    tournament: &TournamentEntry,
 ) -> CommandResult {
-    react_confirm(&ctx, &check_in_msg).await;
-    let mut reaction_collector = check_in_msg
-        .await_reactions(&ctx)
-        .added(true)
-        .removed(true)
-        .await;
-
-    let channels = msg
-        .guild_id
-        .expect("Guild not cached")
-        .channels(&ctx.http)
+    let players = match db
+        .tournaments
+        .get_registered_players(&db.players, Some(&tournament.shorthand))
         .await
-        .expect("Could not get channels");
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-    let mut log_channel = None;
+    let rank_of = |player: &PlayerEntry| {
+        player
+            .tetrio_data
+            .as_ref()
+            .and_then(|data| Rank::from_str(&data.league.rank).ok())
+            .unwrap_or(Rank::Unranked)
+    };
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for rank in Rank::iter().rev() {
+        let names: Vec<String> = players
+            .iter()
+            .filter(|player| rank_of(player) == *rank)
+            .map(|player| {
+                player
+                    .tetrio_data
+                    .as_ref()
+                    .map_or(player.tetrio_id.clone(), |data| data.username.clone())
+            })
+            .collect();
+        if names.is_empty() {
+            continue;
+        }
 
-    for (_, channel) in channels.iter() {
-        if channel.name == "check-in-log" {
-            log_channel = Some(channel.clone());
+        let title = format!("{} {} ({})", rank.to_emoji(), rank, names.len());
+        for (i, chunk) in chunk_joined(&names, ", ", EMBED_FIELD_LIMIT)
+            .into_iter()
+            .enumerate()
+        {
+            let name = if i == 0 {
+                title.clone()
+            } else {
+                format!("{} (continued)", title)
+            };
+            fields.push((name, chunk));
         }
     }
 
-    if let Some(log_channel) = log_channel {
-        while let Some(action) = reaction_collector.next().await {
-            if let Err(e) =
-                handle_checkin_reaction(&ctx, &db, &tournament, &log_channel, action).await
-            {
-                tracing::error!("Error during check-in handling: {}", e);
-            }
+    if fields.is_empty() {
+        say(
+            &ctx,
+            msg.channel_id,
+            badged(tournament, "Nobody is registered yet"),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut pages: Vec<Vec<(String, String)>> = Vec::new();
+    let mut page: Vec<(String, String)> = Vec::new();
+    let mut page_size = 0;
+    for field in fields {
+        let field_size = field.0.chars().count() + field.1.chars().count();
+        if !page.is_empty()
+            && (page.len() == EMBED_MAX_FIELDS || page_size + field_size > EMBED_FIELDS_TOTAL)
+        {
+            pages.push(std::mem::take(&mut page));
+            page_size = 0;
         }
+        page_size += field_size;
+        page.push(field);
+    }
+    pages.push(page);
+
+    let total = pages.len();
+    for (i, page) in pages.iter().enumerate() {
+        msg.channel_id
+            .send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    badge_embed(e, tournament);
+                    if total > 1 {
+                        e.title(format!("Registered players by rank ({}/{})", i + 1, total));
+                    } else {
+                        e.title("Registered players by rank");
+                    }
+                    e.fields(
+                        page.iter()
+                            .map(|(name, value)| (name.clone(), value.clone(), false)),
+                    )
+                    .footer(|f| f.text(format!("{} players", players.len())))
+                })
+            })
+            .await?;
     }
 
     Ok(())
 }
 
-async fn handle_checkin_reaction(
-    ctx: &Context,
-    db: &Arc<LocalDatabase>,
-    tournament: &TournamentEntry,
-    log_channel: &GuildChannel,
-    action: Arc<ReactionAction>,
-) -> CommandResult {
-    let confirm_emoji = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
-
-    let data_read = ctx.data.read().await;
-    let mut invalid_checked_in = data_read
-        .get::<IdCollection>()
-        .expect("Expected database in TypeMap")
-        .lock()
-        .await;
+#[command]
+/// Shows the receipt code of your registration to the ongoing tournament.
+/// Staff can confirm that the code belongs to your registration.
+async fn receipt(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
 
-    match action.as_ref() {
-        ReactionAction::Added(reaction) | ReactionAction::Removed(reaction)
-            if reaction.emoji == confirm_emoji =>
-        {
-            let discord_id = reaction.user_id.unwrap().0;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-            // Prevent rate limit from unregistered people spamming reactions
-            if invalid_checked_in.0.contains(&discord_id) {
-                return Ok(());
-            }
+    let registration = match db.players.get_player_by_discord(msg.author.id.0).await {
+        Ok(Some(player)) => tournament.registration(&player.tetrio_id),
+        Ok(None) => None,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-            let player = match db.players.get_player_by_discord(discord_id) {
-                Ok(player) => match player {
-                    Some(player) => player,
-                    None => {
-                        log_channel.say(&ctx.http, format!("<@{}> Your Discord user is not linked to a Tetrio account! You most likely haven't registered at all.", discord_id)).await?;
-                        invalid_checked_in.0.insert(discord_id);
-                        return Ok(());
-                    }
-                },
-                Err(err) => {
-                    log_channel.say(&ctx.http, err).await?;
-                    return Ok(());
+    let reply = match registration {
+        None => "You're not registered!".to_string(),
+        Some(registration) => match tournament.receipt_of(registration) {
+            Some(receipt) => format!(
+                "Your receipt is `{}` (registered on {})",
+                receipt,
+                registration.date.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => "Receipts are not enabled right now".to_string(),
+        },
+    };
+
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+#[command]
+#[aliases("unregister")]
+#[usage("[reason]")]
+#[example("")]
+#[example("schedule conflict")]
+/// Withdraws you from the ongoing tournament.
+/// Your registration is kept, so registering again before the deadline gets you your spot back.
+async fn withdraw(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let reason = args.rest().trim();
+    let reason = if reason.is_empty() {
+        None
+    } else {
+        Some(reason)
+    };
+    let reply = match db
+        .tournaments
+        .withdraw_by_discord(&db.players, msg.author.id.0, reason)
+        .await
+    {
+        Ok(tournament) => {
+            react_confirm(&ctx, &msg).await;
+            update_participant_role(&ctx, &tournament, msg.author.id.0, false).await;
+            fill_from_waitlist(&ctx, &db, &tournament).await;
+            let reply = badged(
+                &tournament,
+                &format!(
+                    "You withdrew from {}. Use `.register` to return.",
+                    tournament.name
+                ),
+            );
+            Some(say(&ctx, msg.channel_id, reply).await?)
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            let reply = match err {
+                // Withdrawing only looks up the Discord account
+                RegistrationError::DatabaseError(DatabaseError::NotFound) => {
+                    LocalizedMessage::new(MessageKey::NotLinked)
+                }
+                _ => {
+                    tracing::warn!("{}", err);
+                    messages::registration_error(&err)
                 }
             };
+            let language = messages::language_of(&db, msg.author.id.0).await;
+            Some(say(&ctx, msg.channel_id, reply.render(language)).await?)
+        }
+    };
 
-            let player_is_registered = tournament.player_is_registered(&player);
+    delay_delete(&ctx, reply).await?;
+    Ok(())
+}
 
-            let reply = match action.as_ref() {
-                ReactionAction::Added(_) if player_is_registered => Some("You have checked-in successfully. Please stand by until the tournament begins. Instructions on how to play in the tournament will be posted once the bracket is finalized."),
-                ReactionAction::Added(_) if !player_is_registered => {
-                    invalid_checked_in.0.insert(discord_id);
-                    Some("You weren't registered! Please do keep in mind that registering *(which happens in the week before the tournament)* and checking in *(which happens just before the tournament)* are two different processes.")
-                },
-                ReactionAction::Removed(_) if player_is_registered => Some("You have checked-out successfully. If you'd like to check back in, then react to the check-in message again."),
-                _ => None
-            };
+#[command]
+#[owners_only]
+#[usage("<name> <shorthand> [max rank] [max rd] [min ranked games] [--max-highest-rank <rank>] [--allow-unranked] [--resume]")]
+#[example("\"Underdogs Cup 12\" UC12 s+ 100 10")]
+#[example("\"Underdogs Cup 12\" UC12 s+ 100 10 --max-highest-rank ss")]
+#[example("\"Community Cup 3\" CC3 a 120 20 --allow-unranked")]
+#[example("\"Underdogs Cup 12\" UC12 --resume")]
+/// Creates a tournament and shows what's left to set up.
+/// The shorthand has to be 2 to 10 letters and digits, the name at most 100 characters.
+/// The highest rank players may have ever reached is one above the max rank, unless `--max-highest-rank` is given.
+/// With `--allow-unranked`, players who were unranked on announcement day can register if their current games and RD are fine.
+/// Use `--resume` to continue setting up a tournament that already exists, restrictions are only needed when creating.
+async fn create_tournament(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, flags) = split_flags(&args, &["max-highest-rank"]);
+    let resume = flags.contains_key("resume");
 
-            if let Some(reply) = reply {
-                log_channel
-                    .say(&ctx.http, format!("<@{}> {}", discord_id, reply))
-                    .await?;
+    let (name, shorthand) = match (positional.get(0), positional.get(1)) {
+        (Some(name), Some(shorthand)) => (name, shorthand),
+        _ => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Missing arguments (name and shorthand)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let restrictions = match (positional.get(2), positional.get(3), positional.get(4)) {
+        (Some(rank), Some(rd), Some(games)) => match (rd.parse::<f64>(), games.parse::<i64>()) {
+            (Ok(rd), Ok(games)) => {
+                let max_rank = Rank::try_from_str(rank);
+                let max_highest_rank = flags
+                    .get("max-highest-rank")
+                    .map(|peak| Rank::try_from_str(peak))
+                    .transpose();
+                let (max_rank, max_highest_rank) = match (max_rank, max_highest_rank) {
+                    (Ok(max_rank), Ok(max_highest_rank)) => (max_rank, max_highest_rank),
+                    (Err(err), _) | (_, Err(err)) => {
+                        say(&ctx, msg.channel_id, err).await?;
+                        return Ok(());
+                    }
+                };
+                let mut restrictions = TournamentRestrictions::new(max_rank, rd, games);
+                if let Some(peak) = max_highest_rank {
+                    restrictions = restrictions.with_max_highest_rank(peak);
+                }
+                if flags.contains_key("allow-unranked") {
+                    restrictions = restrictions.with_allow_unranked();
+                }
+                Some(restrictions)
+            }
+            _ => {
+                say(
+                    &ctx,
+                    msg.channel_id,
+                    "Max RD and min ranked games have to be numbers",
+                )
+                .await?;
+                return Ok(());
             }
+        },
+        _ => None,
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let result = match (restrictions, resume) {
+        (Some(restrictions), true) => {
+            db.tournaments
+                .create_or_resume_tournament(name, shorthand, restrictions)
+                .await
+        }
+        (Some(restrictions), false) => {
+            db.tournaments
+                .create_tournament(name, shorthand, restrictions)
+                .await
+        }
+        (None, true) => match db.tournaments.get_tournament(shorthand).await {
+            Ok(Some(existing)) if existing.name == *name => Ok(existing),
+            Ok(Some(_)) => Err(DatabaseError::DuplicateTournamentEntry),
+            Ok(None) => Err(DatabaseError::NotFound),
+            Err(err) => Err(err),
+        },
+        (None, false) => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Missing restrictions (max rank, max RD and min ranked games)",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    match result {
+        Ok(tournament) => {
+            react_confirm(&ctx, &msg).await;
+            let restrictions = &tournament.restrictions;
+            let mut embed = setup_status_embed(&tournament.shorthand, &tournament.setup_status());
+            embed.field(
+                "Restrictions",
+                format!(
+                    "Max rank: {}\nMax peak rank: {}\nMax RD: {}\nMin ranked games: {}",
+                    restrictions.max_rank,
+                    restrictions.highest_rank_limit(),
+                    restrictions.max_rd,
+                    restrictions.min_ranked_games
+                ),
+                false,
+            );
+            msg.channel_id
+                .send_message(&ctx.http, |m| m.set_embed(embed))
+                .await?;
+        }
+        Err(DatabaseError::DuplicateTournamentEntry) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, "A tournament with that name or shorthand already exists, use `--resume` to continue setting it up",
+                )
+                .await?;
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
         }
-        _ => {}
     }
 
     Ok(())
 }
 
+// A leaderboard younger than this is reused by `.add_snapshot`, so retrying after a restart doesn't download it again
+const LEADERBOARD_MAX_AGE_MINUTES: i64 = 15;
+
 #[command]
 #[owners_only]
-async fn export_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+#[usage("<tournament> [label] [--force]")]
+#[example("UC12")]
+#[example("UC12 announcement --force")]
+/// Takes a stat snapshot and updates every ranked player from the same leaderboard.
+/// A leaderboard requested in the last 15 minutes is reused.
+/// The first snapshot of a tournament is used for eligibility checks, later ones are only stored unless `--force` is used.
+async fn add_snapshot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, flags) = split_flags(&args, &[]);
+    let force = flags.contains_key("force");
+    let (name, label) = match positional.as_slice() {
+        [name] => (name, None),
+        [name, label] => (name, Some(label.as_str())),
+        [] => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+        _ => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Too many arguments, labels can't contain spaces",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
     let db = crate::discord::get_database(&ctx).await;
-    let tournament = match db.tournaments.get_active() {
-        Ok(tournament) => match tournament {
+
+    let tournament = match db.tournaments.get_tournament(name).await {
+        Ok(tournament_option) => match tournament_option {
             Some(tournament) => tournament,
             None => {
-                msg.channel_id
-                    .say(&ctx.http, "No active tournament")
-                    .await?;
+                react_deny(&ctx, &msg).await;
+                say(&ctx, msg.channel_id, "Tournament not found").await?;
                 return Ok(());
             }
         },
         Err(err) => {
-            msg.channel_id.say(&ctx.http, err).await?;
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
             return Ok(());
         }
     };
 
-    let confirm_emoji = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+    let mut replies = vec![
+        say(
+            &ctx,
+            msg.channel_id,
+            "Requesting the leaderboard, could take a few minutes...",
+        )
+        .await?,
+    ];
 
-    let channel_id = 822933717453504562; // TODO: this is hardcoded but im lazy
-    let message_id = match tournament.check_in_msg {
-        Some(msg_id) => msg_id,
+    let response = match crate::tetrio::leaderboard::request_cached(chrono::Duration::minutes(
+        LEADERBOARD_MAX_AGE_MINUTES,
+    ))
+    .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, DatabaseError::TetrioApiError(err)).await?;
+            return Ok(());
+        }
+    };
+
+    // The player collection gets the same leaderboard, so it doesn't have to be requested again
+    let updated = {
+        let _update = db.lock_updates().await;
+        let task = TaskHandle::detached("Leaderboard update");
+        db.players.update_with_leaderboard(&response, &task).await
+    };
+    if let Err(err) = updated {
+        replies.push(
+            say(
+                &ctx,
+                msg.channel_id,
+                format!("Could not update players from the leaderboard: {}", err),
+            )
+            .await?,
+        );
+    }
+    let users = response.data.users;
+
+    // Replacing the official snapshot changes eligibility, so staff have to see how much
+    let overwrite = match tournament.snapshot_at() {
+        None => false,
+        Some(_) if !force => false,
+        Some(taken_at) => {
+            let registered: Vec<&str> = tournament
+                .registered_players()
+                .iter()
+                .map(|entry| entry.tetrio_id.as_str())
+                .collect();
+            let impact = eligibility::snapshot_impact(
+                &tournament.restrictions,
+                tournament.snapshot(),
+                taken_at,
+                &users,
+                chrono::Utc::now(),
+                &registered,
+            );
+            let prompt = format!(
+                "{} already uses the snapshot `{}`, taken at {} UTC.\n\
+                Replacing it changes the verdict of {} out of {} registered players \
+                ({} would become eligible, {} ineligible). Use the new one instead?",
+                tournament.shorthand,
+                tournament.snapshot_label().unwrap_or_default(),
+                taken_at.format("%Y-%m-%d %H:%M"),
+                impact.changed(),
+                registered.len(),
+                impact.became_eligible,
+                impact.became_ineligible,
+            );
+            if !confirm_prompt(&ctx, &msg, &prompt).await? {
+                say(&ctx, msg.channel_id, "Cancelled, the snapshot was kept").await?;
+                return Ok(());
+            }
+            true
+        }
+    };
+
+    replies.push(say(&ctx, msg.channel_id, "Creating snapshot...").await?);
+
+    match db
+        .tournaments
+        .save_snapshot(&tournament.shorthand, &users, label, overwrite)
+        .await
+    {
+        Ok(snapshot) if !snapshot.official => {
+            react_confirm(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                badged(
+                    &tournament,
+                    &format!(
+                        "Stored the snapshot `{}`, eligibility checks still use `{}`. \
+                        Use `.use_snapshot {} {}` to switch or `--force` next time",
+                        snapshot.label,
+                        tournament.snapshot_label().unwrap_or_default(),
+                        tournament.shorthand,
+                        snapshot.label
+                    ),
+                ),
+            )
+            .await?;
+        }
+        Ok(_) => {
+            react_confirm(&ctx, &msg).await;
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            for reply in replies {
+                reply.delete(&ctx.http).await?;
+            }
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[usage("<tournament>")]
+#[example("UC12")]
+/// Lists the stored stat snapshots of a tournament and which one eligibility checks use.
+async fn list_snapshots(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = match args.current() {
+        Some(name) => name,
         None => {
-            msg.channel_id
-                .say(&ctx.http, "No check-in message found")
-                .await?;
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
             return Ok(());
         }
     };
 
-    let message = ctx.http.get_message(channel_id, message_id).await?;
+    let db = crate::discord::get_database(&ctx).await;
+    let snapshots = match db.tournaments.get_snapshots(name).await {
+        Ok(snapshots) => snapshots,
+        Err(DatabaseError::NotFound) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
 
-    let mut users = Vec::new();
-    const PAGE_SIZE: u8 = 100;
+    let mut report = Report::new(
+        &format!("Snapshots of {}", name),
+        &["Label", "Taken at (UTC)", "Players", "Official"],
+    );
+    for snapshot in &snapshots {
+        report.push_row(vec![
+            snapshot.label.clone(),
+            snapshot.taken_at.format("%Y-%m-%d %H:%M").to_string(),
+            snapshot.players.to_string(),
+            if snapshot.official { "yes" } else { "" }.to_string(),
+        ]);
+    }
+    if snapshots.is_empty() {
+        report.push_note("There are no snapshots yet, use `.add_snapshot` to take one");
+    } else {
+        report
+            .push_note("Use `.use_snapshot <tournament> <label>` to change the official snapshot");
+    }
+    send_report(&ctx, msg.channel_id, &report).await
+}
 
-    loop {
-        let mut page = message
-            .reaction_users(
-                &ctx.http,
-                confirm_emoji.clone(),
-                Some(PAGE_SIZE),
-                users.last().map(|u: &User| u.id),
+#[command]
+#[owners_only]
+#[usage("<tournament> <label>")]
+#[example("UC12 legacy")]
+/// Chooses which stored stat snapshot eligibility checks of a tournament use, see `.list_snapshots`.
+async fn use_snapshot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (positional, _) = split_flags(&args, &[]);
+    let (name, label) = match positional.as_slice() {
+        [name, label] => (name, label),
+        _ => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "Usage: `.use_snapshot <tournament> <label>`",
             )
             .await?;
+            return Ok(());
+        }
+    };
 
-        let is_incomplete_page = page.len() < PAGE_SIZE.into();
-        users.append(&mut page);
-        if is_incomplete_page {
-            break;
+    let db = crate::discord::get_database(&ctx).await;
+    match db.tournaments.set_official_snapshot(name, label).await {
+        Ok(tournament) => {
+            react_confirm(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                badged(
+                    &tournament,
+                    &format!("Eligibility checks now use the snapshot `{}`", label),
+                ),
+            )
+            .await?;
+        }
+        Err(DatabaseError::NotFound) => {
+            react_deny(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                "Tournament or snapshot not found, see `.list_snapshots`",
+            )
+            .await?;
+        }
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
         }
     }
 
-    let user_ids: Vec<String> = users.iter().map(|u| u.id.0.to_string()).collect();
-    let line_separated = user_ids.join("\n");
+    Ok(())
+}
+
+// Usernames shown per list of `.snapshot_diff`, short enough to fit in an embed field
+const SNAPSHOT_DIFF_NAMES: usize = 30;
+
+// Joins up to `limit` names, mentioning how many were left out
+fn truncated_names(names: &[String], limit: usize) -> String {
+    if names.is_empty() {
+        return "-".to_string();
+    }
+    let shown = names[..names.len().min(limit)].join(", ");
+    if names.len() > limit {
+        format!("{} and {} more", shown, names.len() - limit)
+    } else {
+        shown
+    }
+}
+
+#[command]
+#[owners_only]
+#[usage("<tournament>")]
+#[example("UC12")]
+/// Shows what retaking the stat snapshot would change, compared to the last leaderboard update.
+/// Lists registered players who would become ineligible, ranked players who would become eligible and everyone whose rank changed.
+async fn snapshot_diff(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = match args.current() {
+        Some(name) => name,
+        None => {
+            say(&ctx, msg.channel_id, "Missing argument (tournament)").await?;
+            return Ok(());
+        }
+    };
+
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_tournament(name).await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Tournament not found").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let taken_at = match tournament.snapshot_at() {
+        Some(taken_at) => taken_at,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                badged(&tournament, "There is no snapshot yet, nothing to compare"),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+    let diff = match db
+        .tournaments
+        .snapshot_diff(&tournament.shorthand, &db.players)
+        .await
+    {
+        Ok(diff) => diff,
+        Err(err) => {
+            let _ = typing.stop();
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let _ = typing.stop();
+
+    let rank_changes: Vec<String> = diff
+        .rank_changed
+        .iter()
+        .map(|(username, old, new)| format!("{} ({} → {})", username, old, new))
+        .collect();
+    let fields = vec![
+        (
+            format!("Would become ineligible ({})", diff.became_ineligible.len()),
+            truncated_names(&diff.became_ineligible, SNAPSHOT_DIFF_NAMES),
+        ),
+        (
+            format!("Would become eligible ({})", diff.became_eligible.len()),
+            truncated_names(&diff.became_eligible, SNAPSHOT_DIFF_NAMES),
+        ),
+        (
+            format!("Rank changed ({})", rank_changes.len()),
+            truncated_names(&rank_changes, SNAPSHOT_DIFF_NAMES),
+        ),
+    ];
 
-    // Send as txt file
-    let attachment = AttachmentType::from((line_separated.as_bytes(), "checked_in.txt"));
     msg.channel_id
-        .send_files(&ctx.http, vec![attachment], |m| m)
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(format!(
+                    "{} Snapshot diff for {}",
+                    tournament_badge(&tournament),
+                    tournament.name
+                ))
+                .description(format!(
+                    "Current snapshot taken {}, compared to the player data of the last leaderboard update",
+                    discord_timestamp(taken_at, 'f')
+                ));
+                for (name, value) in fields {
+                    e.field(name, value, false);
+                }
+                e
+            })
+        })
         .await?;
+    Ok(())
+}
+
+#[command]
+#[owners_only]
+#[usage("[--repost]")]
+/// Posts the check-in message of the active tournament in this channel.
+/// Use `--repost` to replace a deleted check-in message, everyone who checked in stays checked in.
+async fn create_check_in(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (_, flags) = split_flags(&args, &[]);
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(tournament) => match tournament {
+            Some(tournament) => tournament,
+            None => {
+                react_deny(&ctx, &msg).await;
+                say(&ctx, msg.channel_id, "No active tournament").await?;
+                return Ok(());
+            }
+        },
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    if flags.contains_key("repost") {
+        return repost_check_in(&ctx, db, tournament, &msg).await;
+    }
+
+    match open_check_in(&ctx, &db, &tournament, msg.channel_id).await {
+        Ok(check_in_msg) => {
+            msg.delete(&ctx.http).await?;
+            init_checkin_reaction_handling(&ctx, db, tournament, &msg, &check_in_msg).await?;
+        }
+        Err(failure) => {
+            tracing::warn!(
+                "Could not create the check-in message of {}: {:?}",
+                tournament.shorthand,
+                failure
+            );
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, failure).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Creates and tracks the check-in message step by step, see `crate::check_in`
+// If a step fails, everything done before it is undone
+async fn open_check_in(
+    ctx: &Context,
+    db: &LocalDatabase,
+    tournament: &TournamentEntry,
+    channel_id: ChannelId,
+) -> Result<Message, check_in::Failure> {
+    let shorthand = &tournament.shorthand;
+
+    if let Err(err) = db.tournaments.reserve_check_in(shorthand).await {
+        return Err(compensate(ctx, db, shorthand, Step::Reserve, err.to_string(), None).await);
+    }
+
+    let posted = match post_check_in_message(&ctx, channel_id, &tournament).await {
+        Ok(posted) => posted,
+        Err(err) => {
+            return Err(compensate(ctx, db, shorthand, Step::Post, err.to_string(), None).await)
+        }
+    };
+
+    if let Err(err) = db
+        .tournaments
+        .finalize_check_in(shorthand, channel_id.0, posted.id.0)
+        .await
+    {
+        return Err(compensate(
+            ctx,
+            db,
+            shorthand,
+            Step::Finalize,
+            err.to_string(),
+            Some(&posted),
+        )
+        .await);
+    }
+
+    let seed = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+    if let Err(err) = posted.react(&ctx.http, seed).await {
+        return Err(compensate(
+            ctx,
+            db,
+            shorthand,
+            Step::Seed,
+            err.to_string(),
+            Some(&posted),
+        )
+        .await);
+    }
+
+    Ok(posted)
+}
+
+// Runs the compensations for a failed step, collecting the ones that failed themselves
+async fn compensate(
+    ctx: &Context,
+    db: &LocalDatabase,
+    shorthand: &str,
+    step: Step,
+    error: String,
+    posted: Option<&Message>,
+) -> check_in::Failure {
+    let mut failed_compensations = Vec::new();
+    for compensation in check_in::compensations(step) {
+        let result = match (compensation, posted) {
+            (Compensation::DeleteMessage, Some(posted)) => {
+                posted.delete(&ctx.http).await.map_err(|e| e.to_string())
+            }
+            (Compensation::DeleteMessage, None) => Ok(()),
+            (Compensation::ClearReservation, _) => db
+                .tournaments
+                .clear_check_in_reservation(shorthand)
+                .await
+                .map_err(|e| e.to_string()),
+            (Compensation::UntrackMessage, Some(posted)) => db
+                .tournaments
+                .untrack_check_in_msg(shorthand, posted.id.0)
+                .await
+                .map_err(|e| e.to_string()),
+            (Compensation::UntrackMessage, None) => Ok(()),
+        };
+        if let Err(err) = result {
+            tracing::error!(
+                "Could not roll back the check-in ({}): {}",
+                compensation,
+                err
+            );
+            failed_compensations.push((compensation, err));
+        }
+    }
+
+    check_in::Failure {
+        step,
+        error,
+        failed_compensations,
+    }
+}
+
+// Posts a check-in message, without setting it in the tournament
+async fn post_check_in_message(
+    ctx: &Context,
+    channel_id: ChannelId,
+    tournament: &TournamentEntry,
+) -> Result<Message, SerenityError> {
+    channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                badge_embed(e, &tournament);
+                e.title(format!("{}: Check-in", tournament.shorthand))
+                    .description(format!(
+                        "React to this message with {} in order to check-in! Unreact to check-out.",
+                        crate::discord::CONFIRM_EMOJI
+                    ))
+            })
+        })
+        .await
+}
+
+// Replaces the check-in message, carrying over everyone who checked in on the old one
+async fn repost_check_in(
+    ctx: &Context,
+    db: Arc<LocalDatabase>,
+    tournament: TournamentEntry,
+    msg: &Message,
+) -> CommandResult {
+    let old_message_id = match tournament.check_in_msg {
+        Some(msg_id) => msg_id,
+        None => {
+            react_deny(&ctx, &msg).await;
+            say(
+                &ctx,
+                msg.channel_id,
+                "No check-in message to repost, use `.create_check_in` without `--repost`",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    // Reactions on the old message only exist if it wasn't deleted, the recorded check-ins are kept either way
+    let channel_id = check_in_channel(&tournament);
+    let reacted: Vec<u64> = match fetch_checked_in(&ctx, channel_id, old_message_id).await {
+        Ok(users) => users.iter().filter(|u| !u.bot).map(|u| u.id.0).collect(),
+        Err(err) if is_unknown_message(&err) => Vec::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let check_ins = carry_over_check_ins(
+        &tournament.check_ins,
+        &reacted,
+        tournament.check_in_generation,
+    );
+
+    let check_in_msg = post_check_in_message(&ctx, channel_id, &tournament).await?;
+    if let Err(err) = db
+        .tournaments
+        .repost_check_in(
+            &tournament.shorthand,
+            channel_id.0,
+            check_in_msg.id.0,
+            &check_ins,
+        )
+        .await
+    {
+        react_deny(&ctx, &msg).await;
+        check_in_msg.delete(&ctx.http).await?;
+        say(
+            &ctx,
+            msg.channel_id,
+            format!("Could not set the new check-in message ({:?})", err),
+        )
+        .await?;
+        return Ok(());
+    }
+    tracing::info!(
+        target: "audit",
+        "{} reposted the check-in message of {}, carried over {} check-ins",
+        msg.author.id,
+        tournament.shorthand,
+        check_ins.len()
+    );
+
+    // The old message might still be around if this wasn't a repair
+    let _ = ctx.http.delete_message(channel_id.0, old_message_id).await;
+
+    react_confirm(&ctx, &msg).await;
+    say(
+        &ctx,
+        msg.channel_id,
+        badged(
+            &tournament,
+            &format!(
+                "Check-in message reposted, {} players stay checked in",
+                check_ins.len()
+            ),
+        ),
+    )
+    .await?;
+
+    let tournament = db
+        .tournaments
+        .get_tournament(&tournament.shorthand)
+        .await?
+        .unwrap_or(tournament);
+    init_checkin_reaction_handling(&ctx, db, tournament, &msg, &check_in_msg).await
+}
+
+// Alert for a check-in message that doesn't exist anymore
+pub fn check_in_lost_alert(tournament: &TournamentEntry) -> String {
+    badged(
+        tournament,
+        "The check-in message was deleted, so nobody can check in right now. Use `.create_check_in --repost` to post a new one, everyone who checked in stays checked in.",
+    )
+}
+
+// Alerts staff about a deleted check-in message and replies with the same alert
+async fn report_lost_check_in(
+    ctx: &Context,
+    msg: &Message,
+    tournament: &TournamentEntry,
+) -> CommandResult {
+    let alert = check_in_lost_alert(tournament);
+    alert_staff(&ctx, &alert).await;
+    react_deny(&ctx, &msg).await;
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                badge_embed(e, tournament);
+                e.title("Check-in message not found").description(format!(
+                    "Message {} in <#{}> doesn't exist anymore. Use `.create_check_in --repost` to post a new one, everyone who checked in stays checked in.",
+                    tournament.check_in_msg.unwrap_or_default(),
+                    check_in_channel(tournament)
+                ))
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+// Alerts staff when a registered player leaves the server, or comes back after being withdrawn for it
+//
+// Called from the member events, so errors are only logged, the database might be down for a moment
+pub async fn member_changed(ctx: &Context, discord_id: u64, event: MemberEvent) {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!("Could not check member {}: {}", discord_id, err);
+            return;
+        }
+    };
+    let player = match db.players.get_player_by_discord(discord_id).await {
+        Ok(player) => player,
+        Err(err) => {
+            tracing::warn!("Could not check member {}: {}", discord_id, err);
+            return;
+        }
+    };
+    let registration = player
+        .as_ref()
+        .and_then(|p| tournament.registration(&p.tetrio_id));
+
+    let auto_withdraw = db.flags.is_enabled(Feature::AutoWithdrawOnLeave).await;
+    let action = member_watch::decide(
+        event,
+        player.as_ref(),
+        registration.map(|r| &r.status),
+        auto_withdraw,
+    );
+    let (player, registration) = match (action, player.as_ref(), registration) {
+        (Action::Ignore, _, _) => return,
+        (_, Some(player), Some(registration)) => (player, registration),
+        _ => return,
+    };
+
+    let username = registration
+        .as_registered_username
+        .clone()
+        .or_else(|| player.tetrio_data.as_ref().map(|d| d.username.clone()))
+        .unwrap_or_else(|| player.tetrio_id.clone());
+
+    let text = match action {
+        Action::OfferReinstatement => format!(
+            "{} (<@{}>) rejoined the server after being withdrawn for leaving. Use `.staff_register <@{}>` to reinstate them with their original registration date.",
+            username, discord_id, discord_id
+        ),
+        _ => {
+            let (ordered, _) = tournament.registrations_page(0, usize::MAX, RegOrder::Seed);
+            let seed = match ordered
+                .iter()
+                .position(|r| r.tetrio_id == registration.tetrio_id)
+            {
+                Some(index) => format!("seed {}", index + 1),
+                None => "no seed".to_string(),
+            };
+            let checked_in = tournament.recorded_check_in(discord_id)
+                == Some(tournament.check_in_generation);
+            let follow_up = if action == Action::Withdraw {
+                match db.tournaments.withdraw_by_discord(
+                    &db.players,
+                    discord_id,
+                    Some(member_watch::LEFT_SERVER_REASON),
+                )
+                .await {
+                    Ok(tournament) => {
+                        fill_from_waitlist(&ctx, &db, &tournament).await;
+                        "They were withdrawn automatically.".to_string()
+                    }
+                    Err(err) => format!("Could not withdraw them: {}", err),
+                }
+            } else {
+                format!(
+                    "Use `.staff_unregister {} {}` to withdraw them.",
+                    username,
+                    member_watch::LEFT_SERVER_REASON
+                )
+            };
+            format!(
+                "{} (<@{}>) left the server ({}, registered {}, {}). {}",
+                username,
+                discord_id,
+                seed,
+                registration.date.format("%Y-%m-%d %H:%M UTC"),
+                if checked_in {
+                    "checked in"
+                } else {
+                    "not checked in"
+                },
+                follow_up
+            )
+        }
+    };
+
+    let text = badged(&tournament, &text);
+    alert_staff(&ctx, &text).await;
+    let alert = StaffAlert {
+        at: bson::DateTime::from(chrono::Utc::now()),
+        tetrio_id: player.tetrio_id.clone(),
+        text,
+    };
+    if let Err(err) = db
+        .tournaments
+        .add_alert(&tournament.shorthand, &alert)
+        .await
+    {
+        tracing::warn!("Could not record alert for {}: {}", player.tetrio_id, err);
+    }
+}
+
+#[command]
+#[owners_only]
+async fn resume_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(tournament) => match tournament {
+            Some(tournament) => tournament,
+            None => {
+                react_deny(&ctx, &msg).await;
+                say(&ctx, msg.channel_id, "No active tournament").await?;
+                return Ok(());
+            }
+        },
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let check_in_msg = match tournament.check_in_msg {
+        Some(msg_id) => msg_id,
+        None => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, "No check-in message found").await?;
+            return Ok(());
+        }
+    };
+
+    let check_in_msg = match ctx
+        .http
+        .get_message(check_in_channel(&tournament).0, check_in_msg)
+        .await
+    {
+        Ok(check_in_msg) => check_in_msg,
+        Err(err) if is_unknown_message(&err) => {
+            return report_lost_check_in(&ctx, &msg, &tournament).await
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    // Reactions that changed while the bot was offline were never recorded, unless the check-in is closed
+    let synced = if tournament.check_in_closed {
+        Ok((0, 0))
+    } else {
+        sync_check_ins(&ctx, &db, &tournament, check_in_msg.id.0).await
+    };
+    match synced {
+        Ok((added, removed)) if added + removed > 0 => {
+            say(
+                &ctx,
+                msg.channel_id,
+                badged(
+                    &tournament,
+                    &format!(
+                        "Recorded {} missed check-ins and {} missed check-outs",
+                        added, removed
+                    ),
+                ),
+            )
+            .await?;
+        }
+        Ok(_) => {}
+        Err(err) => tracing::warn!("Could not sync check-ins: {}", err),
+    }
+
+    init_checkin_reaction_handling(&ctx, db, tournament, &msg, &check_in_msg).await
+}
+
+async fn init_checkin_reaction_handling(
+    ctx: &Context,
+    db: Arc<LocalDatabase>,
+    tournament: TournamentEntry,
+    msg: &Message,
+    check_in_msg: &Message,
+) -> CommandResult {
+    react_confirm(&ctx, &check_in_msg).await;
+    let mut reaction_collector = check_in_msg
+        .await_reactions(&ctx)
+        .added(true)
+        .removed(true)
+        .await;
+
+    let guild_id = msg.guild_id.expect("Guild not cached");
+    let log_channel = match check_in_log_channel(&ctx, guild_id).await {
+        Some(log_channel) => log_channel,
+        None => return Ok(()),
+    };
+
+    let shutdown = ctx
+        .data
+        .read()
+        .await
+        .get::<Shutdown>()
+        .cloned()
+        .expect("Expected Shutdown in TypeMap");
+    let mut shutdown = shutdown.guard().await;
+    let mut processed = 0;
+    loop {
+        // A reaction that's being handled is always finished before shutting down
+        let action = tokio::select! {
+            action = reaction_collector.next() => action,
+            _ = shutdown.triggered() => {
+                tracing::info!(
+                    "Stopped check-in handling of {} for shutdown after {} reactions, use .resume_check_in to continue",
+                    tournament.shorthand,
+                    processed
+                );
+                break;
+            }
+        };
+        let action = match action {
+            Some(action) => action,
+            None => break,
+        };
+        if let Err(e) = handle_checkin_reaction(&ctx, &db, &tournament, log_channel, action).await {
+            tracing::error!("Error during check-in handling: {}", e);
+        }
+        processed += 1;
+    }
+
+    Ok(())
+}
+
+// Channel check-in replies go to, guilds that never set one keep using the one called check-in-log
+async fn check_in_log_channel(ctx: &Context, guild_id: GuildId) -> Option<ChannelId> {
+    let settings = crate::discord::get_guild_settings(&ctx, guild_id.0).await;
+    match settings.check_in_log_channel {
+        Some(channel_id) => Some(ChannelId(channel_id)),
+        None => guild_id
+            .channels(&ctx.http)
+            .await
+            .expect("Could not get channels")
+            .values()
+            .find(|channel| channel.name == "check-in-log")
+            .map(|channel| channel.id),
+    }
+}
+
+async fn handle_checkin_reaction(
+    ctx: &Context,
+    db: &Arc<LocalDatabase>,
+    tournament: &TournamentEntry,
+    log_channel: ChannelId,
+    action: Arc<ReactionAction>,
+) -> CommandResult {
+    let confirm_emoji = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+
+    let data_read = ctx.data.read().await;
+    let mut invalid_checked_in = data_read
+        .get::<IdCollection>()
+        .expect("Expected database in TypeMap")
+        .lock()
+        .await;
+
+    match action.as_ref() {
+        ReactionAction::Added(reaction) | ReactionAction::Removed(reaction)
+            if reaction.emoji == confirm_emoji =>
+        {
+            let discord_id = reaction.user_id.unwrap().0;
+
+            // Prevent rate limit from unregistered people spamming reactions
+            if invalid_checked_in.contains(discord_id) {
+                return Ok(());
+            }
+
+            let player = match db.players.get_player_by_discord(discord_id).await {
+                Ok(player) => match player {
+                    Some(player) => player,
+                    None => {
+                        say_pinging(&ctx, log_channel, badged(tournament, &format!("<@{}> Your Discord user is not linked to a Tetrio account! You most likely haven't registered at all.", discord_id)), &[discord_id]).await?;
+                        invalid_checked_in.insert(discord_id);
+                        return Ok(());
+                    }
+                },
+                Err(err) => {
+                    say(&ctx, log_channel, err).await?;
+                    return Ok(());
+                }
+            };
+
+            // Check against the current state, the check-in could have been reset in the meantime
+            let tournament = match db.tournaments.get_tournament(&tournament.shorthand).await {
+                Ok(Some(current)) => current,
+                Ok(None) => tournament.clone(),
+                Err(err) => {
+                    say(&ctx, log_channel, err).await?;
+                    return Ok(());
+                }
+            };
+            let tournament = &tournament;
+
+            // Once closed, the check-in list stays as it is, only staff can still change it
+            if tournament.check_in_closed {
+                if let ReactionAction::Added(_) = action.as_ref() {
+                    say_pinging(
+                        &ctx,
+                        log_channel,
+                        badged(tournament, &format!("<@{}> The check-in is closed already. If you couldn't check in in time, please contact staff.", discord_id)),
+                        &[discord_id],
+                    )
+                    .await?;
+                }
+                return Ok(());
+            }
+
+            let player_is_registered = tournament.player_is_registered(&player);
+
+            // Removing a reaction from before the last reset isn't a check-out, the player
+            // wasn't checked in for the current generation in the first place
+            let stale = is_stale_check_in(
+                tournament.recorded_check_in(discord_id),
+                tournament.check_in_generation,
+            );
+            if player_is_registered {
+                let result = match action.as_ref() {
+                    ReactionAction::Added(_) => {
+                        db.tournaments
+                            .set_checked_in(
+                                &tournament.shorthand,
+                                discord_id,
+                                &player.tetrio_id,
+                                tournament.check_in_generation,
+                            )
+                            .await
+                    }
+                    _ => {
+                        db.tournaments
+                            .remove_checked_in(&tournament.shorthand, discord_id)
+                            .await
+                    }
+                };
+                if let Err(err) = result {
+                    tracing::error!("Could not record check-in of {}: {}", discord_id, err);
+                }
+            }
+
+            let reply = match action.as_ref() {
+                ReactionAction::Added(_) if player_is_registered => Some("You have checked-in successfully. Please stand by until the tournament begins. Instructions on how to play in the tournament will be posted once the bracket is finalized."),
+                ReactionAction::Added(_) if !player_is_registered => {
+                    invalid_checked_in.insert(discord_id);
+                    Some("You weren't registered! Please do keep in mind that registering *(which happens in the week before the tournament)* and checking in *(which happens just before the tournament)* are two different processes.")
+                },
+                ReactionAction::Removed(_) if player_is_registered && !stale => Some("You have checked-out successfully. If you'd like to check back in, then react to the check-in message again."),
+                _ => None
+            };
+
+            if let Some(reply) = reply {
+                say_pinging(
+                    &ctx,
+                    log_channel,
+                    badged(tournament, &format!("<@{}> {}", discord_id, reply)),
+                    &[discord_id],
+                )
+                .await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+// Channel of check-in messages posted before the channel was stored with the tournament
+pub const CHECK_IN_CHANNEL_ID: u64 = 822933717453504562;
+
+// Channel the check-in message of a tournament is in
+pub fn check_in_channel(tournament: &TournamentEntry) -> ChannelId {
+    ChannelId(tournament.check_in_channel.unwrap_or(CHECK_IN_CHANNEL_ID))
+}
+
+// Everyone who reacted to the check-in message
+async fn fetch_checked_in(
+    ctx: &Context,
+    channel_id: ChannelId,
+    message_id: u64,
+) -> Result<Vec<User>, SerenityError> {
+    let confirm_emoji = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+    let message = ctx.http.get_message(channel_id.0, message_id).await?;
+
+    let mut users = Vec::new();
+    const PAGE_SIZE: u8 = 100;
+
+    loop {
+        let mut page = message
+            .reaction_users(
+                &ctx.http,
+                confirm_emoji.clone(),
+                Some(PAGE_SIZE),
+                users.last().map(|u: &User| u.id),
+            )
+            .await?;
+
+        let is_incomplete_page = page.len() < PAGE_SIZE.into();
+        users.append(&mut page);
+        if is_incomplete_page {
+            break;
+        }
+    }
+
+    Ok(users)
+}
+
+// Everyone who is checked in right now, ignoring reactions from before the last reset
+async fn fetch_live_check_ins(
+    ctx: &Context,
+    tournament: &TournamentEntry,
+    message_id: u64,
+) -> Result<Vec<User>, SerenityError> {
+    let users = fetch_checked_in(&ctx, check_in_channel(tournament), message_id).await?;
+    let reacted: Vec<u64> = users.iter().map(|u| u.id.0).collect();
+    let live = tournament.live_check_ins(&reacted);
+    Ok(users
+        .into_iter()
+        .filter(|u| live.contains(&u.id.0))
+        .collect())
+}
+
+// Records check-ins of registered players who reacted without being recorded, and removes records
+// of players whose reaction is gone. Carried over check-ins have no reaction and are kept.
+// Returns how many check-ins were added and removed.
+async fn sync_check_ins(
+    ctx: &Context,
+    db: &LocalDatabase,
+    tournament: &TournamentEntry,
+    message_id: u64,
+) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let reacted: Vec<u64> = fetch_live_check_ins(&ctx, tournament, message_id)
+        .await?
+        .iter()
+        .map(|u| u.id.0)
+        .collect();
+    let recorded: Vec<&CheckInRecord> = tournament.checked_in();
+
+    let missing: Vec<u64> = reacted
+        .iter()
+        .copied()
+        .filter(|id| recorded.iter().all(|record| record.discord_id != *id))
+        .collect();
+    let players = db
+        .players
+        .get_players(bson::doc! {"discord_id": {"$in": missing}}, None)
+        .await?;
+    let mut added = 0;
+    for player in players
+        .iter()
+        .filter(|player| tournament.player_is_registered(player))
+    {
+        db.tournaments
+            .set_checked_in(
+                &tournament.shorthand,
+                player.discord_id.unwrap(),
+                &player.tetrio_id,
+                tournament.check_in_generation,
+            )
+            .await?;
+        added += 1;
+    }
+
+    let mut removed = 0;
+    for record in recorded.iter().filter(|record| {
+        !record.carried_over && record.forced_by.is_none() && !reacted.contains(&record.discord_id)
+    }) {
+        db.tournaments
+            .remove_checked_in(&tournament.shorthand, record.discord_id)
+            .await?;
+        removed += 1;
+    }
+
+    Ok((added, removed))
+}
+
+#[command]
+/// Shows how many registered players of the ongoing tournament are checked in.
+async fn check_in_status(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, RegistrationError::NoTournamentActive).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let (checked_in, registered) = check_in_counts(&tournament);
+    let reply = match tournament.check_in_msg {
+        Some(_) => format!(
+            "{} of {} registered players are checked in, {} are missing{}",
+            checked_in,
+            registered,
+            registered.saturating_sub(checked_in),
+            if tournament.check_in_closed {
+                ". The check-in is closed"
+            } else {
+                ""
+            }
+        ),
+        None => format!(
+            "The check-in hasn't started yet, {} players are registered",
+            registered
+        ),
+    };
+    say(&ctx, msg.channel_id, badged(&tournament, &reply)).await?;
+    Ok(())
+}
+
+// Checked in players who are still registered, and the amount of registered players
+fn check_in_counts(tournament: &TournamentEntry) -> (usize, usize) {
+    let registered: HashSet<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|reg| reg.tetrio_id.as_str())
+        .collect();
+    let checked_in = tournament
+        .checked_in()
+        .iter()
+        .filter(|record| {
+            record
+                .tetrio_id
+                .as_deref()
+                .map_or(true, |id| registered.contains(id))
+        })
+        .count();
+    (checked_in, registered.len())
+}
+
+#[command]
+#[owners_only]
+async fn export_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(tournament) => match tournament {
+            Some(tournament) => tournament,
+            None => {
+                say(&ctx, msg.channel_id, "No active tournament").await?;
+                return Ok(());
+            }
+        },
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    // The recorded check-ins are used, so the export still works if the check-in message is gone
+    let records = tournament.checked_in();
+    let user_ids: Vec<String> = records.iter().map(|r| r.discord_id.to_string()).collect();
+    let line_separated = user_ids.join("\n");
+
+    // Registration-time names are the source of truth for brackets and sign-up sheets,
+    // the current name is included to spot renames
+    let registered_ids: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|r| r.tetrio_id.as_str())
+        .collect();
+    let players = db
+        .players
+        .get_players(bson::doc! {"tetrio_id": {"$in": registered_ids}}, None)
+        .await
+        .unwrap_or_default();
+
+    let mut report = Report::new(
+        "checked_in",
+        &[
+            "Discord ID",
+            "Tetrio ID",
+            "Registered as",
+            "Current name",
+            "Registered in channel",
+            "Checked in at",
+        ],
+    );
+    for record in &records {
+        let player = players.iter().find(|p| match &record.tetrio_id {
+            Some(tetrio_id) => &p.tetrio_id == tetrio_id,
+            None => p.discord_id == Some(record.discord_id),
+        });
+        let registration = player.and_then(|p| {
+            tournament
+                .registered_players()
+                .into_iter()
+                .find(|r| r.tetrio_id == p.tetrio_id)
+        });
+        report.push_row(vec![
+            record.discord_id.to_string(),
+            player.map_or("-".to_string(), |p| p.tetrio_id.clone()),
+            registration
+                .and_then(|r| r.as_registered_username.clone())
+                .unwrap_or_else(|| "-".to_string()),
+            player
+                .and_then(|p| p.tetrio_data.as_ref())
+                .map_or("-".to_string(), |d| d.username.clone()),
+            registration
+                .and_then(|r| r.source)
+                .map_or("-".to_string(), |source| source.channel_id().to_string()),
+            record.at.map_or("-".to_string(), |at| at.to_rfc3339()),
+        ]);
+    }
+    let csv = report.to_csv();
+
+    // Send as txt file, along with the names as CSV
+    let attachments = vec![
+        AttachmentType::from((line_separated.as_bytes(), "checked_in.txt")),
+        AttachmentType::from((csv.as_bytes(), "checked_in.csv")),
+    ];
+    let live: Vec<u64> = records.iter().map(|r| r.discord_id).collect();
+    let content = match tournament.reconfirmed(&live) {
+        Some((reconfirmed, previous)) => format!(
+            "{} checked in, re-confirmed {} of {} (check-in generation {})",
+            records.len(),
+            reconfirmed,
+            previous,
+            tournament.check_in_generation
+        ),
+        None => format!("{} checked in", records.len()),
+    };
+    let content = match tournament.qualifier_summary() {
+        Some(summary) => format!("{} ({})", content, summary),
+        None => content,
+    };
+    msg.channel_id
+        .send_files(&ctx.http, attachments, |m| {
+            m.content(content).allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[usage("[--keep-reactions]")]
+#[example("")]
+#[example("--keep-reactions")]
+/// Resets the check-in of the active tournament, for example after the schedule changed.
+/// Everyone who is checked in right now is saved and has to check in again.
+/// Reactions are removed from the check-in message, unless `--keep-reactions` is used,
+/// in which case the old reactions simply don't count anymore.
+async fn reset_checkin(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (_, flags) = split_flags(&args, &[]);
+    let keep_reactions = flags.contains_key("keep-reactions");
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let message_id = match tournament.check_in_msg {
+        Some(msg_id) => msg_id,
+        None => {
+            say(&ctx, msg.channel_id, "No check-in message found").await?;
+            return Ok(());
+        }
+    };
+
+    let reacted: Vec<u64> =
+        match fetch_checked_in(&ctx, check_in_channel(&tournament), message_id).await {
+            Ok(users) => users.iter().filter(|u| !u.bot).map(|u| u.id.0).collect(),
+            Err(err) if is_unknown_message(&err) => {
+                return report_lost_check_in(&ctx, &msg, &tournament).await
+            }
+            Err(err) => return Err(err.into()),
+        };
+    let (snapshot, generation) = tournament.check_in_reset(&reacted, chrono::Utc::now());
+
+    let prompt = format!(
+        "Reset the check-in of {}? {} players are checked in and will have to check in again.",
+        tournament.shorthand,
+        snapshot.discord_ids.len()
+    );
+    if !confirm_prompt(&ctx, &msg, &prompt).await? {
+        say(&ctx, msg.channel_id, "Cancelled").await?;
+        return Ok(());
+    }
+
+    if let Err(err) = db
+        .tournaments
+        .reset_check_in(&tournament.shorthand, &snapshot, generation)
+        .await
+    {
+        react_deny(&ctx, &msg).await;
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+    tracing::info!(
+        target: "audit",
+        "{} reset the check-in of {} to generation {} ({} were checked in)",
+        msg.author.id,
+        tournament.shorthand,
+        generation,
+        snapshot.discord_ids.len()
+    );
+
+    let instructions = if keep_reactions {
+        format!(
+            "If your {} is still on the check-in message, remove it and react again.",
+            CONFIRM_EMOJI
+        )
+    } else {
+        let check_in_msg = match ctx
+            .http
+            .get_message(check_in_channel(&tournament).0, message_id)
+            .await
+        {
+            Ok(check_in_msg) => check_in_msg,
+            Err(err) if is_unknown_message(&err) => {
+                return report_lost_check_in(&ctx, &msg, &tournament).await
+            }
+            Err(err) => return Err(err.into()),
+        };
+        check_in_msg.delete_reactions(&ctx.http).await?;
+        react_confirm(&ctx, &check_in_msg).await;
+        format!(
+            "React to the check-in message with {} again.",
+            CONFIRM_EMOJI
+        )
+    };
+
+    say_pinging_everyone(&ctx, check_in_channel(&tournament), badged(
+                &tournament,
+                &format!(
+                    "@here The schedule has changed, so the check-in was reset and everyone has to check in again to confirm they can still play. {}",
+                    instructions
+                ),
+            ),
+        )
+        .await?;
+
+    react_confirm(&ctx, &msg).await;
+    say(
+        &ctx,
+        msg.channel_id,
+        format!(
+            "Check-in reset, {} players have to re-confirm",
+            snapshot.discord_ids.len()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+// Active tournament with a check-in and the registered, linked player named in the arguments
+// Replies and returns `None` if any of them is missing
+async fn check_in_target(
+    ctx: &Context,
+    msg: &Message,
+    db: &LocalDatabase,
+    args: &Args,
+) -> Result<Option<(TournamentEntry, PlayerEntry, u64)>, SerenityError> {
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(None);
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(None);
+        }
+    };
+    if tournament.check_in_msg.is_none() {
+        say(&ctx, msg.channel_id, "The check-in hasn't started yet").await?;
+        return Ok(None);
+    }
+
+    let player = match args.current() {
+        Some(arg) => match serenity::utils::parse_mention(arg) {
+            Some(discord_id) => db.players.get_player_by_discord(discord_id).await,
+            None => db.players.get_player_by_tetrio(arg).await,
+        },
+        None => {
+            say(&ctx, msg.channel_id, "No username or mention provided").await?;
+            return Ok(None);
+        }
+    };
+    let player = match player {
+        Ok(Some(player)) => player,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "Player not found").await?;
+            return Ok(None);
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(None);
+        }
+    };
+
+    let discord_id = match player.discord_id {
+        Some(discord_id) => discord_id,
+        None => {
+            say(
+                &ctx,
+                msg.channel_id,
+                "The player isn't linked to a Discord account",
+            )
+            .await?;
+            return Ok(None);
+        }
+    };
+    if !tournament.player_is_registered(&player) {
+        say(&ctx, msg.channel_id, RegistrationError::NotRegistered).await?;
+        return Ok(None);
+    }
+
+    Ok(Some((tournament, player, discord_id)))
+}
+
+#[command]
+#[usage("<username|mention>")]
+#[example("icedynamix")]
+#[example("@IceDynamix")]
+/// Checks a registered player of the active tournament in by hand, for players who can't react to the check-in message.
+/// Works while the check-in is closed as well.
+async fn force_check_in(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (tournament, player, discord_id) = match check_in_target(&ctx, &msg, &db, &args).await? {
+        Some(target) => target,
+        None => {
+            react_deny(&ctx, &msg).await;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = db
+        .tournaments
+        .force_check_in(
+            &tournament.shorthand,
+            discord_id,
+            &player.tetrio_id,
+            tournament.check_in_generation,
+            msg.author.id.0,
+        )
+        .await
+    {
+        react_deny(&ctx, &msg).await;
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+    tracing::info!(
+        target: "audit",
+        "{} checked {} in to {} by hand",
+        msg.author.id,
+        player.tetrio_id,
+        tournament.shorthand
+    );
+
+    react_confirm(&ctx, &msg).await;
+    say(
+        &ctx,
+        msg.channel_id,
+        badged(&tournament, &format!("<@{}> is checked in", discord_id)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command]
+#[usage("<username|mention>")]
+#[example("icedynamix")]
+#[example("@IceDynamix")]
+/// Checks a player of the active tournament out by hand, their reaction on the check-in message is removed as well.
+async fn force_check_out(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let (tournament, player, discord_id) = match check_in_target(&ctx, &msg, &db, &args).await? {
+        Some(target) => target,
+        None => {
+            react_deny(&ctx, &msg).await;
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = db
+        .tournaments
+        .remove_checked_in(&tournament.shorthand, discord_id)
+        .await
+    {
+        react_deny(&ctx, &msg).await;
+        say(&ctx, msg.channel_id, err).await?;
+        return Ok(());
+    }
+    tracing::info!(
+        target: "audit",
+        "{} checked {} out of {} by hand",
+        msg.author.id,
+        player.tetrio_id,
+        tournament.shorthand
+    );
+
+    // A reaction that's left would count as a check-in again once the check-ins are synced
+    if let Some(message_id) = tournament.check_in_msg {
+        let confirm_emoji = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+        if let Err(err) = ctx
+            .http
+            .delete_reaction(
+                check_in_channel(&tournament).0,
+                message_id,
+                Some(discord_id),
+                &confirm_emoji,
+            )
+            .await
+        {
+            tracing::warn!(
+                "Could not remove the check-in reaction of {}: {}",
+                discord_id,
+                err
+            );
+        }
+    }
+
+    react_confirm(&ctx, &msg).await;
+    say(
+        &ctx,
+        msg.channel_id,
+        badged(&tournament, &format!("<@{}> is checked out", discord_id)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[command]
+/// Closes the check-in of the active tournament, usually once the bracket is made.
+/// Reactions to the check-in message don't change anything afterwards, use `force_check_in` for late players.
+/// A summary is posted to the check-in log.
+async fn close_check_in(ctx: &Context, msg: &Message) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    if tournament.check_in_msg.is_none() {
+        say(&ctx, msg.channel_id, "The check-in hasn't started yet").await?;
+        return Ok(());
+    }
+
+    let tournament = match db.tournaments.close_check_in(&tournament.shorthand).await {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            react_deny(&ctx, &msg).await;
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+    let (checked_in, registered) = check_in_counts(&tournament);
+    tracing::info!(
+        target: "audit",
+        "{} closed the check-in of {} ({} of {} checked in)",
+        msg.author.id,
+        tournament.shorthand,
+        checked_in,
+        registered
+    );
+
+    let summary = badged(
+        &tournament,
+        &format!(
+            "The check-in of {} is closed, {} of {} registered players checked in",
+            tournament.name, checked_in, registered
+        ),
+    );
+    if let Some(guild_id) = msg.guild_id {
+        if let Some(log_channel) = check_in_log_channel(&ctx, guild_id).await {
+            say(&ctx, log_channel, &summary).await?;
+        }
+    }
+
+    react_confirm(&ctx, &msg).await;
+    say(&ctx, msg.channel_id, summary).await?;
+    Ok(())
+}
+
+#[command]
+#[usage("[tournament]")]
+#[example("")]
+#[example("UC12")]
+/// Exports the registered players of the active tournament in seed order as a CSV file.
+/// Seeds use the snapshot TR, or the current TR for players missing from the snapshot.
+/// Unranked players are listed at the bottom.
+async fn seeding(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let db = crate::discord::get_database(&ctx).await;
+    let tournament = match db.tournaments.resolve_active(args.current()).await {
+        Ok(tournament) => tournament,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let ids: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|reg| reg.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(bson::doc! {"tetrio_id": {"$in": ids}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let seed_order = bracket::seed_order(&tournament, &players);
+    let mut report = Report::new(
+        "seeding",
+        &["seed", "username", "tetrio_id", "rank", "TR", "discord_id"],
+    );
+    for (i, player) in seed_order.iter().enumerate() {
+        report.push_row(vec![
+            (i + 1).to_string(),
+            player.username.clone(),
+            player.tetrio_id.clone(),
+            player.rank.to_string(),
+            player
+                .rating
+                .map_or("-".to_string(), |rating| format!("{:.2}", rating)),
+            player
+                .discord_id
+                .map_or("-".to_string(), |id| id.to_string()),
+        ]);
+    }
+    let csv = report.to_csv();
+
+    let from_current = seed_order
+        .iter()
+        .filter(|p| p.rating.is_some() && !p.from_snapshot)
+        .count();
+    let unranked = seed_order.iter().filter(|p| p.rating.is_none()).count();
+    let content = format!(
+        "{} players, {} seeded by current TR, {} unranked",
+        seed_order.len(),
+        from_current,
+        unranked
+    );
+    let file_name = format!("{}_seeding.csv", tournament.shorthand);
+    let attachments = vec![AttachmentType::from((csv.as_bytes(), file_name.as_str()))];
+    msg.channel_id
+        .send_files(&ctx.http, attachments, |m| {
+            m.content(badged(&tournament, &content))
+                .allowed_mentions(|am| am.empty_parse())
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[usage("[top_n] [--ping]")]
+#[example("")]
+#[example("8 --ping")]
+/// Lists the top seeds (16 by default) of the active tournament that haven't checked in yet.
+/// Use `--ping` to mention them in the check-in channel, which can only be done once every few minutes.
+async fn missing_seeds(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const MENTIONS_PER_MESSAGE: usize = 20;
+
+    let db = crate::discord::get_database(&ctx).await;
+    let (positional, flags) = split_flags(&args, &[]);
+    let top_n = positional
+        .get(0)
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(16);
+
+    let tournament = match db.tournaments.get_active().await {
+        Ok(Some(tournament)) => tournament,
+        Ok(None) => {
+            say(&ctx, msg.channel_id, "No active tournament").await?;
+            return Ok(());
+        }
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let message_id = match tournament.check_in_msg {
+        Some(msg_id) => msg_id,
+        None => {
+            say(&ctx, msg.channel_id, "No check-in message found").await?;
+            return Ok(());
+        }
+    };
+
+    let checked_in: HashSet<u64> = match fetch_live_check_ins(&ctx, &tournament, message_id).await {
+        Ok(users) => users.iter().map(|u| u.id.0).collect(),
+        Err(err) if is_unknown_message(&err) => {
+            return report_lost_check_in(&ctx, &msg, &tournament).await
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let ids: Vec<&str> = tournament
+        .registered_players()
+        .iter()
+        .map(|reg| reg.tetrio_id.as_str())
+        .collect();
+    let players = match db
+        .players
+        .get_players(bson::doc! {"tetrio_id": {"$in": ids}}, None)
+        .await
+    {
+        Ok(players) => players,
+        Err(err) => {
+            say(&ctx, msg.channel_id, err).await?;
+            return Ok(());
+        }
+    };
+
+    let seed_order = bracket::seed_order(&tournament, &players);
+    let missing = bracket::missing_seeds(&seed_order, &checked_in, top_n);
+
+    let format_date = |date: Option<chrono::DateTime<chrono::Utc>>| {
+        date.map_or("-".to_string(), |d| d.format("%Y-%m-%d %H:%M").to_string())
+    };
+
+    let mut report = Report::new(
+        &format!(
+            "{} Top {} seeds missing from check-in",
+            tournament_badge(&tournament),
+            top_n
+        ),
+        &["Seed", "Player", "Discord", "Linked", "Last refresh"],
+    );
+    for (seed, player) in &missing {
+        let entry = players.iter().find(|p| p.tetrio_id == player.tetrio_id);
+        report.push_row(vec![
+            seed.to_string(),
+            player.username.clone(),
+            player
+                .discord_id
+                .map_or("not linked".to_string(), |id| id.to_string()),
+            format_date(entry.and_then(|e| e.linked_at())),
+            format_date(entry.and_then(|e| e.refreshed_at())),
+        ]);
+    }
+    report.push_note(&format!(
+        "{}/{} of the top seeds are missing",
+        missing.len(),
+        top_n.min(seed_order.len())
+    ));
+    send_report(&ctx, msg.channel_id, &report).await?;
+
+    if !flags.contains_key("ping") {
+        return Ok(());
+    }
+
+    let mention_ids: Vec<u64> = missing.iter().filter_map(|(_, p)| p.discord_id).collect();
+    if mention_ids.is_empty() {
+        return Ok(());
+    }
+
+    let cooldown = std::env::var("PING_COOLDOWN_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<i64>().ok())
+        .unwrap_or(5);
+
+    {
+        let data_read = ctx.data.read().await;
+        let mut cooldowns = data_read
+            .get::<PingCooldowns>()
+            .expect("Expected ping cooldowns in TypeMap")
+            .lock()
+            .await;
+
+        let key = format!("missing_seeds:{}", tournament.shorthand);
+        if let Err(until) = cooldowns.try_ping(
+            &key,
+            chrono::Duration::minutes(cooldown),
+            chrono::Utc::now(),
+        ) {
+            say(
+                &ctx,
+                msg.channel_id,
+                format!(
+                    "Missing seeds were pinged recently, try again after {}",
+                    until.format("%H:%M UTC")
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let chunks = mention_ids.chunks(MENTIONS_PER_MESSAGE.max(1));
+    for (mentions, ids) in chunk_mentions(&mention_ids, MENTIONS_PER_MESSAGE)
+        .iter()
+        .zip(chunks)
+    {
+        say_pinging(
+            &ctx,
+            check_in_channel(&tournament),
+            badged(
+                &tournament,
+                &format!("Check-in is closing soon, please check in! {}", mentions),
+            ),
+            ids,
+        )
+        .await?;
+    }
+
+    tracing::info!(
+        "{} pinged {} missing seeds of {}",
+        msg.author.id,
+        mention_ids.len(),
+        tournament.shorthand
+    );
+    react_confirm(&ctx, &msg).await;
 
     Ok(())
 }
@@ -5,10 +5,15 @@
 //!
 //! # Example
 //!
-//! ```
-//! let db = uc_helper_rust::database::connect()?;
-//! let player = db.players.get_player_by_tetrio("icedynamix")?;
-//! let tournament = db.tournaments.get_tournament("UC7")?;
+//! ```no_run
+//! use uc_helper_rust::database::DatabaseError;
+//!
+//! # async fn lookup() -> Result<(), DatabaseError> {
+//! let db = uc_helper_rust::database::connect().await?;
+//! let player = db.players.get_player_by_tetrio("icedynamix").await?;
+//! let tournament = db.tournaments.get_tournament("UC7").await?;
+//! # Ok(())
+//! # }
 //! ```
 
 #![warn(missing_docs)]
@@ -16,52 +21,136 @@
 use std::env;
 use std::sync::Arc;
 
-use bson::Document;
-use mongodb::sync::{Client, Collection, Database};
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use mongodb::options::Collation;
+use mongodb::{Client, Collection, Cursor, Database};
 use serde::de::DeserializeOwned;
+use serenity::futures::stream::TryStreamExt;
 use serenity::prelude::TypeMapKey;
 use thiserror::Error;
+use tokio::sync::{Mutex, MutexGuard};
 use tracing::info;
 
-use crate::database::players::PlayerCollection;
-use crate::database::tournaments::TournamentCollection;
+use crate::database::flags::FlagCollection;
+use crate::database::jobs::JobCollection;
+use crate::database::players::{PlayerCollection, PlayerEntry};
+use crate::database::settings::SettingsCollection;
+use crate::database::tournaments::{TournamentCollection, TournamentEntry};
+use crate::database::usage::UsageCollection;
+use crate::tasks::TaskHandle;
 use crate::tetrio::TetrioApiError;
 
+pub mod flags;
+pub mod jobs;
 pub mod players;
+pub mod settings;
 pub mod tournaments;
+pub mod usage;
 
 /// Database name to use in MongoDB
-const DATABASE_NAME: &str = "uc_helper";
+pub const DATABASE_NAME: &str = "uc_helper";
 
 type DatabaseResult<T> = Result<T, DatabaseError>;
 
+/// Wraps an error returned by MongoDB
+///
+/// Commands only show a generic message for these, so the actual error is logged here.
+fn mongo_error(err: mongodb::error::Error) -> DatabaseError {
+    tracing::error!("MongoDB error: {}", err);
+    crate::metrics::global().record_database_error();
+    DatabaseError::Mongo(err)
+}
+
+/// Creates a unique index on a collection, logging instead of failing if that's not possible
+///
+/// The driver has no index helpers, so the command is run directly. Creating an existing index is a
+/// no-op, so this runs on every startup. Fails if the collection already contains duplicates, which
+/// have to be cleaned up by hand first. An existing index of the same name with other options has to
+/// be dropped by hand as well. With a `collation`, values that compare equal under it are duplicates.
+async fn ensure_unique_index(
+    database: &Database,
+    collection: &str,
+    name: &str,
+    key: Document,
+    partial_filter: Option<Document>,
+    collation: Option<Collation>,
+) {
+    let mut index = doc! {"key": key, "name": name, "unique": true};
+    if let Some(filter) = partial_filter {
+        index.insert("partialFilterExpression", filter);
+    }
+    if let Some(collation) = collation {
+        index.insert(
+            "collation",
+            bson::to_document(&collation).expect("could not convert to bson"),
+        );
+    }
+    if let Err(err) = database
+        .run_command(doc! {"createIndexes": collection, "indexes": [index]}, None)
+        .await
+    {
+        tracing::warn!("Could not create unique index {}: {}", name, err);
+    }
+}
+
+/// Name of the unique index a write violated, `None` if the write failed for another reason
+fn violated_unique_index(err: &mongodb::error::Error) -> Option<&str> {
+    match err.kind.as_ref() {
+        mongodb::error::ErrorKind::WriteError(mongodb::error::WriteFailure::WriteError(write))
+            if write.code == 11000 =>
+        {
+            // "E11000 duplicate key error collection: uc_helper.players index: <name> dup key: ..."
+            write
+                .message
+                .split("index: ")
+                .nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .or(Some(""))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a document into a given structure
+fn parse_entry<T: DeserializeOwned>(doc: Document) -> DatabaseResult<T> {
+    bson::from_document(doc).map_err(|err| {
+        tracing::error!("Could not parse document: {}", err);
+        DatabaseError::CouldNotParse(err.to_string())
+    })
+}
+
 /// Generic function that finds an entry and parses it into a given structure
-fn get_entry<T: DeserializeOwned>(
+async fn get_entry<T: DeserializeOwned>(
     collection: &Collection,
     filter: impl Into<Option<Document>>,
 ) -> DatabaseResult<Option<T>> {
-    match collection.find_one(filter, None) {
-        Ok(entry) => {
-            let doc: Option<Document> = entry;
-            Ok(doc.map(|d| bson::from_document(d).expect("could not convert to document")))
-        }
-        Err(_) => Err(DatabaseError::ConnectionFailed),
+    match collection
+        .find_one(filter, None)
+        .await
+        .map_err(mongo_error)?
+    {
+        Some(doc) => parse_entry(doc).map(Some),
+        None => Ok(None),
     }
 }
 
 /// Generic function that finds a list of entries and parses them into a given structure
-fn get_entries<T: DeserializeOwned>(
+async fn get_entries<T: DeserializeOwned>(
     collection: &Collection,
     filter: impl Into<Option<Document>>,
 ) -> DatabaseResult<Vec<T>> {
-    match collection.find(filter, None) {
-        Ok(result) => Ok(result
-            .map(|doc| {
-                bson::from_document(doc.expect("bad entry")).expect("could not convert to document")
-            })
-            .collect()),
-        Err(_) => Err(DatabaseError::ConnectionFailed),
+    let cursor = collection.find(filter, None).await.map_err(mongo_error)?;
+    collect_entries(cursor).await
+}
+
+/// Reads every document of a cursor and parses them into a given structure
+async fn collect_entries<T: DeserializeOwned>(mut cursor: Cursor) -> DatabaseResult<Vec<T>> {
+    let mut entries = Vec::new();
+    while let Some(doc) = cursor.try_next().await.map_err(mongo_error)? {
+        entries.push(parse_entry(doc)?);
     }
+    Ok(entries)
 }
 
 #[derive(Error, Debug)]
@@ -70,6 +159,9 @@ pub enum DatabaseError {
     #[error("Connection to database failed")]
     /// Connection to database could not be established
     ConnectionFailed,
+    #[error("Something went wrong while accessing the database")]
+    /// MongoDB returned an error, the message stays generic so it can be shown to users
+    Mongo(#[from] mongodb::error::Error),
     #[error("Could not find item")]
     /// Some item was not present in the database
     NotFound,
@@ -94,9 +186,36 @@ pub enum DatabaseError {
     #[error("Something happened while requesting data from the Tetrio API")]
     /// Tetrio API Error
     TetrioApiError(#[from] TetrioApiError),
+    #[error("Invalid input: {0}")]
+    /// Some input did not pass validation
+    InvalidInput(String),
     #[error("User is trying to link user that's already linked to them")]
     /// User is trying to link themself to the same person
     AlreadyLinked,
+    #[error("A snapshot already exists, taken at {taken_at}")]
+    /// The tournament already has a stat snapshot and it wasn't supposed to be replaced
+    SnapshotExists {
+        /// When the existing snapshot was taken
+        taken_at: DateTime<Utc>,
+    },
+}
+
+impl DatabaseError {
+    /// Whether the database itself couldn't be reached, as opposed to a missing or invalid entry
+    ///
+    /// Commands that can work without the database use this to decide whether to fall back to the
+    /// Tetrio API.
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            DatabaseError::ConnectionFailed => true,
+            DatabaseError::Mongo(err) => matches!(
+                err.kind.as_ref(),
+                mongodb::error::ErrorKind::ServerSelectionError { .. }
+                    | mongodb::error::ErrorKind::Io(_)
+            ),
+            _ => false,
+        }
+    }
 }
 
 /// Represents the database and provides access to the wrapped collections
@@ -106,19 +225,89 @@ pub struct LocalDatabase {
     pub players: PlayerCollection,
     /// Represents the tournament collection
     pub tournaments: TournamentCollection,
+    /// Represents the feature flag collection
+    pub flags: FlagCollection,
+    /// Represents the command usage collection
+    pub usage: UsageCollection,
+    /// Represents the scheduled jobs collection
+    pub jobs: JobCollection,
+    /// Represents the settings collection
+    pub settings: SettingsCollection,
+    /// Held while player data is updated in bulk, so two updates never interleave their writes
+    update_lock: Mutex<()>,
+}
+
+/// Bulk updates of player data, see [`LocalDatabase::lock_updates()`]
+impl LocalDatabase {
+    /// Waits until no other bulk update of player data is running, the lock is held until the guard is dropped
+    ///
+    /// [`LocalDatabase::update_from_leaderboard()`] and [`LocalDatabase::update_registered()`] take
+    /// this lock on their own, use it when calling the collection methods directly.
+    pub async fn lock_updates(&self) -> MutexGuard<'_, ()> {
+        self.update_lock.lock().await
+    }
+
+    /// Same as [`LocalDatabase::lock_updates()`], but returns `None` instead of waiting if an update is running
+    pub fn try_lock_updates(&self) -> Option<MutexGuard<'_, ()>> {
+        self.update_lock.try_lock().ok()
+    }
+
+    /// [`PlayerCollection::update_from_leaderboard()`], waits for running updates first
+    pub async fn update_from_leaderboard(&self) -> DatabaseResult<()> {
+        let _update = self.lock_updates().await;
+        self.players.update_from_leaderboard().await
+    }
+
+    /// Updates every ranked player from the leaderboard, for refreshes in the background
+    ///
+    /// Unlike [`LocalDatabase::update_from_leaderboard()`], this doesn't wait for a running update.
+    /// Returns `None` if another update was running, otherwise how many players were updated.
+    pub async fn refresh_ranked_players(&self) -> DatabaseResult<Option<usize>> {
+        let _update = match self.try_lock_updates() {
+            Some(guard) => guard,
+            None => return Ok(None),
+        };
+        let task = TaskHandle::detached("Automatic leaderboard update");
+        self.players
+            .update_from_leaderboard_cancellable(&task)
+            .await?;
+        Ok(Some(task.progress().0))
+    }
+
+    /// [`PlayerCollection::update_registered()`], waits for running updates first
+    pub async fn update_registered(
+        &self,
+        tournament: &TournamentEntry,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        let _update = self.lock_updates().await;
+        self.players.update_registered(tournament).await
+    }
 }
 
 /// Establishes a connection to MongoDB database as provided by the `DATABASE_URL` environment variable.
-pub fn connect() -> Result<LocalDatabase, DatabaseError> {
+pub async fn connect() -> Result<LocalDatabase, DatabaseError> {
+    connect_to(DATABASE_NAME).await
+}
+
+/// Same as [`connect()`], but uses a database with a different name, for example for local development data
+pub async fn connect_to(name: &str) -> Result<LocalDatabase, DatabaseError> {
     let url = env::var("DATABASE_URL").expect("url must be set");
-    info!("Connecting to database");
-    let client = Client::with_uri_str(&url).map_err(|_| DatabaseError::ConnectionFailed)?;
+    info!("Connecting to database {}", name);
+    let client = Client::with_uri_str(&url).await.map_err(|err| {
+        tracing::error!("Could not connect to the database: {}", err);
+        DatabaseError::ConnectionFailed
+    })?;
 
-    let database = client.database(DATABASE_NAME);
+    let database = client.database(name);
 
     Ok(LocalDatabase {
-        players: PlayerCollection::new(&database),
-        tournaments: TournamentCollection::new(&database),
+        players: PlayerCollection::new(&database).await,
+        tournaments: TournamentCollection::new(&database).await,
+        flags: FlagCollection::new(&database),
+        usage: UsageCollection::new(&database),
+        jobs: JobCollection::new(&database),
+        settings: SettingsCollection::new(&database),
+        update_lock: Mutex::new(()),
         _database: database,
     })
 }
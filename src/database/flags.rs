@@ -0,0 +1,688 @@
+//! Feature flags, to enable unfinished features only on specific environments
+//!
+//! A flag is resolved in the order environment override (e.g. `FLAG_SLASH_COMMANDS=on`), then
+//! database, then the default of the feature. Database values are cached, since some flags are
+//! checked on hot paths, and the cache is invalidated whenever a flag is toggled.
+//!
+//! Stored flags can be exported to a JSON file with [`export_flags()`] and synced into another
+//! environment from such a file, see [`parse_flag_file()`], [`diff_flags()`] and [`plan_sync()`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use bson::{doc, DateTime as BsonDateTime};
+use chrono::Utc;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{mongo_error, DatabaseError, DatabaseResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// An opt-in feature that can be toggled at runtime
+pub enum Feature {
+    /// Registering slash commands on startup
+    SlashCommands,
+    /// HTTP server for stream overlays
+    OverlayServer,
+    /// Polling Tetrio news for rank ups
+    NewsPoller,
+    /// Channel name counters for registrations
+    Counters,
+    /// Requiring a verified account when linking
+    VerifiedLinkRequirement,
+    /// Withdrawing registered players who leave the server
+    AutoWithdrawOnLeave,
+}
+
+impl Feature {
+    /// Every known feature
+    pub const ALL: [Feature; 6] = [
+        Feature::SlashCommands,
+        Feature::OverlayServer,
+        Feature::NewsPoller,
+        Feature::Counters,
+        Feature::VerifiedLinkRequirement,
+        Feature::AutoWithdrawOnLeave,
+    ];
+
+    /// Name used in the database and in commands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::SlashCommands => "slash_commands",
+            Feature::OverlayServer => "overlay_server",
+            Feature::NewsPoller => "news_poller",
+            Feature::Counters => "counters",
+            Feature::VerifiedLinkRequirement => "verified_link_requirement",
+            Feature::AutoWithdrawOnLeave => "auto_withdraw_on_leave",
+        }
+    }
+
+    /// Whether the feature is enabled if it's neither overridden nor set in the database
+    pub fn default_enabled(&self) -> bool {
+        false
+    }
+
+    /// Environment variable that overrides the flag, like `FLAG_SLASH_COMMANDS`
+    pub fn env_var(&self) -> String {
+        format!("FLAG_{}", self.name().to_uppercase())
+    }
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Feature {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .iter()
+            .find(|f| f.name() == s.to_lowercase())
+            .copied()
+            .ok_or_else(|| DatabaseError::InvalidInput(format!("Unknown feature flag `{}`", s)))
+    }
+}
+
+/// Parses a flag value like `on`, `off`, `true` or `0`
+pub fn parse_flag_value(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "on" | "true" | "1" | "yes" | "enabled" => Some(true),
+        "off" | "false" | "0" | "no" | "disabled" => Some(false),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Where the effective value of a flag comes from
+pub enum FlagSource {
+    /// Environment variable
+    Environment,
+    /// Flag collection
+    Database,
+    /// Default of the feature
+    Default,
+}
+
+impl std::fmt::Display for FlagSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlagSource::Environment => f.write_str("env"),
+            FlagSource::Database => f.write_str("database"),
+            FlagSource::Default => f.write_str("default"),
+        }
+    }
+}
+
+/// Resolves the effective value of a flag
+///
+/// An environment override wins over the database value, which wins over the default.
+/// Environment values that can't be parsed are ignored.
+pub fn resolve(
+    default: bool,
+    stored: Option<bool>,
+    env_override: Option<&str>,
+) -> (bool, FlagSource) {
+    if let Some(value) = env_override.and_then(parse_flag_value) {
+        return (value, FlagSource::Environment);
+    }
+    match stored {
+        Some(value) => (value, FlagSource::Database),
+        None => (default, FlagSource::Default),
+    }
+}
+
+/// Names that don't belong to any known feature, sorted
+pub fn unknown_flag_names<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut unknown: Vec<String> = names
+        .filter(|name| Feature::from_str(name).is_err())
+        .cloned()
+        .collect();
+    unknown.sort();
+    unknown
+}
+
+#[derive(Debug, Clone, Default)]
+/// Cached flag values and environment overrides at one point in time, see [`FlagCollection::snapshot()`]
+pub struct FlagSnapshot {
+    /// Stored values, `None` if they weren't loaded from the database yet
+    pub stored: Option<HashMap<String, bool>>,
+    /// Raw environment overrides of the features that have one
+    pub overrides: HashMap<Feature, String>,
+}
+
+impl FlagSnapshot {
+    /// Effective value of a flag and where it comes from, like [`resolve()`]
+    pub fn resolve(&self, feature: Feature) -> (bool, FlagSource) {
+        let stored = self
+            .stored
+            .as_ref()
+            .and_then(|values| values.get(feature.name()).copied());
+        resolve(
+            feature.default_enabled(),
+            stored,
+            self.overrides.get(&feature).map(String::as_str),
+        )
+    }
+
+    /// Names of stored flags that don't belong to any known feature
+    pub fn unknown(&self) -> Vec<String> {
+        self.stored
+            .as_ref()
+            .map_or_else(Vec::new, |values| unknown_flag_names(values.keys()))
+    }
+}
+
+/// Top level key of the flags in an export file
+pub const EXPORT_KEY: &str = "flags";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A flag file that can't be synced, with the path of the offending key
+pub struct FlagFileError {
+    /// Path of the offending key, like `flags.news_poller`, empty for the whole file
+    pub path: String,
+    /// What's wrong with it
+    pub message: String,
+}
+
+impl FlagFileError {
+    fn new(path: &str, message: &str) -> FlagFileError {
+        FlagFileError {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for FlagFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "`{}`: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Serializes stored flag values into the export format, `{"flags": {"<name>": <enabled>}}`
+pub fn export_flags(values: &BTreeMap<String, bool>) -> String {
+    let mut export = BTreeMap::new();
+    export.insert(EXPORT_KEY, values);
+    serde_json::to_string_pretty(&export).expect("bad json")
+}
+
+/// Parses an export file, see [`export_flags()`]
+///
+/// Every flag has to belong to a known feature and be a boolean. Other top level keys are
+/// rejected, so a typo doesn't silently sync nothing.
+pub fn parse_flag_file(text: &str) -> Result<BTreeMap<String, bool>, FlagFileError> {
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|err| FlagFileError::new("", &format!("Invalid JSON: {}", err)))?;
+
+    let object = value
+        .as_object()
+        .ok_or_else(|| FlagFileError::new("", "Expected an object at the top level"))?;
+    if let Some(key) = object.keys().find(|key| *key != EXPORT_KEY) {
+        return Err(FlagFileError::new(
+            key,
+            "Unknown key, only `flags` can be synced",
+        ));
+    }
+
+    let flags = match object.get(EXPORT_KEY) {
+        Some(flags) => flags
+            .as_object()
+            .ok_or_else(|| FlagFileError::new(EXPORT_KEY, "Expected an object of flags"))?,
+        None => return Err(FlagFileError::new("", "No `flags` key in the file")),
+    };
+
+    let mut values = BTreeMap::new();
+    for (name, value) in flags {
+        let path = format!("{}.{}", EXPORT_KEY, name);
+        let feature = Feature::from_str(name)
+            .map_err(|_| FlagFileError::new(&path, "Unknown feature flag"))?;
+        let enabled = value
+            .as_bool()
+            .ok_or_else(|| FlagFileError::new(&path, "Expected `true` or `false`"))?;
+        values.insert(feature.name().to_string(), enabled);
+    }
+    Ok(values)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Difference of a single flag between the database and a file
+pub struct FlagDiff {
+    /// Name of the flag
+    pub name: String,
+    /// Stored value, `None` if it's not stored
+    pub before: Option<bool>,
+    /// Value in the file, `None` if it's not in the file
+    pub after: Option<bool>,
+}
+
+impl FlagDiff {
+    /// Whether applying this removes the stored value
+    pub fn is_removal(&self) -> bool {
+        self.after.is_none()
+    }
+}
+
+impl std::fmt::Display for FlagDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.before, self.after) {
+            (None, Some(after)) => write!(f, "+ {}: {}", self.name, after),
+            (Some(before), Some(after)) => write!(f, "~ {}: {} -> {}", self.name, before, after),
+            (Some(before), None) => write!(f, "- {}: {}", self.name, before),
+            (None, None) => write!(f, "  {}", self.name),
+        }
+    }
+}
+
+/// Added, changed and removed flags between the stored values and the values of a file
+///
+/// Identical values are skipped. Sorted by name.
+pub fn diff_flags(
+    live: &BTreeMap<String, bool>,
+    incoming: &BTreeMap<String, bool>,
+) -> Vec<FlagDiff> {
+    let mut names: Vec<&String> = live.keys().chain(incoming.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| FlagDiff {
+            name: name.clone(),
+            before: live.get(name).copied(),
+            after: incoming.get(name).copied(),
+        })
+        .filter(|diff| diff.before != diff.after)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Changes to apply, in order
+pub struct SyncPlan {
+    /// Changes that get applied, additions and changes first, then removals
+    pub apply: Vec<FlagDiff>,
+    /// Removals that are skipped because they weren't allowed
+    pub held_back: Vec<FlagDiff>,
+}
+
+/// Orders the diff for applying
+///
+/// Removals go last and are only applied if `allow_removals` is set, otherwise they are held back.
+pub fn plan_sync(diffs: &[FlagDiff], allow_removals: bool) -> SyncPlan {
+    let (removals, updates): (Vec<FlagDiff>, Vec<FlagDiff>) =
+        diffs.iter().cloned().partition(|diff| diff.is_removal());
+
+    let mut plan = SyncPlan {
+        apply: updates,
+        held_back: Vec::new(),
+    };
+    if allow_removals {
+        plan.apply.extend(removals);
+    } else {
+        plan.held_back = removals;
+    }
+    plan
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// A single change of a flag
+pub struct FlagChange {
+    /// New value
+    pub enabled: bool,
+    /// Discord ID of whoever changed it
+    pub changed_by: u64,
+    /// When it was changed
+    pub changed_at: BsonDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Represents a flag as it's saved in the collection
+pub struct FlagEntry {
+    /// Name of the flag, see [`Feature::name()`]
+    pub name: String,
+    /// Whether the flag is enabled
+    pub enabled: bool,
+    /// Every change of the flag, oldest first
+    #[serde(default)]
+    pub history: Vec<FlagChange>,
+}
+
+/// Wrapper around the flag collection, with a cache of the stored values
+pub struct FlagCollection {
+    collection: Collection,
+    cache: RwLock<Option<HashMap<String, bool>>>,
+}
+
+impl FlagCollection {
+    /// Creates the wrapper
+    pub fn new(database: &Database) -> FlagCollection {
+        FlagCollection {
+            collection: database.collection("flags"),
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// All stored flags, including ones with unknown names
+    pub async fn get_flags(&self) -> DatabaseResult<Vec<FlagEntry>> {
+        crate::database::get_entries(&self.collection, None).await
+    }
+
+    /// Stored values of every flag, including ones with unknown names
+    pub async fn stored_values(&self) -> DatabaseResult<BTreeMap<String, bool>> {
+        Ok(self
+            .get_flags()
+            .await?
+            .into_iter()
+            .map(|entry| (entry.name, entry.enabled))
+            .collect())
+    }
+
+    /// Loads the stored flag values into the cache, if it's empty
+    async fn fill_cache(&self) -> DatabaseResult<()> {
+        if self.cache.read().unwrap().is_some() {
+            return Ok(());
+        }
+
+        let values: HashMap<String, bool> = self
+            .get_flags()
+            .await?
+            .into_iter()
+            .map(|entry| (entry.name, entry.enabled))
+            .collect();
+
+        for name in unknown_flag_names(values.keys()) {
+            tracing::warn!("Unknown feature flag `{}` in database", name);
+        }
+
+        *self.cache.write().unwrap() = Some(values);
+        Ok(())
+    }
+
+    /// Effective value of a flag and where it comes from
+    ///
+    /// Falls back to the default if the database can't be reached.
+    pub async fn resolve(&self, feature: Feature) -> (bool, FlagSource) {
+        let stored = match self.fill_cache().await {
+            Ok(()) => self
+                .cache
+                .read()
+                .unwrap()
+                .as_ref()
+                .and_then(|values| values.get(feature.name()).copied()),
+            Err(_) => None,
+        };
+        let env_override = std::env::var(feature.env_var()).ok();
+        resolve(feature.default_enabled(), stored, env_override.as_deref())
+    }
+
+    /// Cached values and current environment overrides, without loading anything from the database
+    pub fn snapshot(&self) -> FlagSnapshot {
+        FlagSnapshot {
+            stored: self.cache.read().unwrap().clone(),
+            overrides: Feature::ALL
+                .iter()
+                .filter_map(|feature| {
+                    std::env::var(feature.env_var())
+                        .ok()
+                        .map(|value| (*feature, value))
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether a feature is enabled
+    pub async fn is_enabled(&self, feature: Feature) -> bool {
+        self.resolve(feature).await.0
+    }
+
+    /// Names of stored flags that don't belong to any known feature
+    pub async fn unknown_flags(&self) -> DatabaseResult<Vec<String>> {
+        self.fill_cache().await?;
+        Ok(self.snapshot().unknown())
+    }
+
+    /// Sets a flag in the database and records the change
+    ///
+    /// An environment override still takes precedence afterwards.
+    pub async fn set_flag(
+        &self,
+        feature: Feature,
+        enabled: bool,
+        changed_by: u64,
+    ) -> DatabaseResult<()> {
+        let change = FlagChange {
+            enabled,
+            changed_by,
+            changed_at: BsonDateTime::from(Utc::now()),
+        };
+        let change = bson::to_document(&change).expect("bad document");
+
+        let options = mongodb::options::UpdateOptions::builder()
+            .upsert(true)
+            .build();
+        self.collection
+            .update_one(
+                doc! {"name": feature.name()},
+                doc! {
+                    "$set": {"enabled": enabled},
+                    "$push": {"history": change}
+                },
+                options,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        *self.cache.write().unwrap() = None;
+        tracing::info!(
+            target: "audit",
+            "Feature flag {} set to {} by {}",
+            feature,
+            enabled,
+            changed_by
+        );
+        Ok(())
+    }
+
+    /// Deletes a stored flag, so it falls back to its default
+    ///
+    /// The history of the flag is deleted with it, the removal is only kept in the audit log.
+    pub async fn remove_flag(&self, name: &str, removed_by: u64) -> DatabaseResult<()> {
+        let result = self
+            .collection
+            .delete_one(doc! {"name": name}, None)
+            .await
+            .map_err(mongo_error)?;
+        if result.deleted_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+
+        *self.cache.write().unwrap() = None;
+        tracing::info!(
+            target: "audit",
+            "Feature flag {} removed by {}",
+            name,
+            removed_by
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_wins_over_database_and_default() {
+        assert_eq!(
+            resolve(false, Some(false), Some("on")),
+            (true, FlagSource::Environment)
+        );
+        assert_eq!(
+            resolve(true, None, Some(" Disabled ")),
+            (false, FlagSource::Environment)
+        );
+        assert_eq!(
+            resolve(false, Some(true), None),
+            (true, FlagSource::Database)
+        );
+        assert_eq!(resolve(true, None, None), (true, FlagSource::Default));
+    }
+
+    #[test]
+    fn unparseable_overrides_are_ignored() {
+        assert_eq!(
+            resolve(false, Some(true), Some("maybe")),
+            (true, FlagSource::Database)
+        );
+        assert_eq!(resolve(false, None, Some("")), (false, FlagSource::Default));
+    }
+
+    #[test]
+    fn snapshot_resolves_every_level() {
+        let mut stored = HashMap::new();
+        stored.insert("news_poller".to_string(), true);
+        stored.insert("counters".to_string(), true);
+        let mut overrides = HashMap::new();
+        overrides.insert(Feature::Counters, "off".to_string());
+        let snapshot = FlagSnapshot {
+            stored: Some(stored),
+            overrides,
+        };
+
+        assert_eq!(
+            snapshot.resolve(Feature::Counters),
+            (false, FlagSource::Environment)
+        );
+        assert_eq!(
+            snapshot.resolve(Feature::NewsPoller),
+            (true, FlagSource::Database)
+        );
+        assert_eq!(
+            snapshot.resolve(Feature::SlashCommands),
+            (false, FlagSource::Default)
+        );
+    }
+
+    #[test]
+    fn unknown_stored_names_are_reported() {
+        let names = vec![
+            "zebra".to_string(),
+            "news_poller".to_string(),
+            "NEWS_POLLER".to_string(),
+            "old_flag".to_string(),
+        ];
+        assert_eq!(unknown_flag_names(names.iter()), vec!["old_flag", "zebra"]);
+
+        let mut stored = HashMap::new();
+        stored.insert("old_flag".to_string(), true);
+        let snapshot = FlagSnapshot {
+            stored: Some(stored),
+            overrides: HashMap::new(),
+        };
+        assert_eq!(snapshot.unknown(), vec!["old_flag"]);
+        assert!(FlagSnapshot::default().unknown().is_empty());
+    }
+
+    fn values(entries: &[(&str, bool)]) -> BTreeMap<String, bool> {
+        entries
+            .iter()
+            .map(|(name, enabled)| (name.to_string(), *enabled))
+            .collect()
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let stored = values(&[("counters", true), ("news_poller", false)]);
+        assert_eq!(parse_flag_file(&export_flags(&stored)), Ok(stored));
+    }
+
+    #[test]
+    fn parse_errors_carry_the_key_path() {
+        let path = |text: &str| parse_flag_file(text).unwrap_err().path;
+        assert_eq!(path("not json"), "");
+        assert_eq!(path("[]"), "");
+        assert_eq!(path("{}"), "");
+        assert_eq!(path(r#"{"flgs": {}}"#), "flgs");
+        assert_eq!(path(r#"{"flags": []}"#), "flags");
+        assert_eq!(path(r#"{"flags": {"nope": true}}"#), "flags.nope");
+        assert_eq!(
+            path(r#"{"flags": {"counters": true, "news_poller": "on"}}"#),
+            "flags.news_poller"
+        );
+
+        let err = parse_flag_file(r#"{"flags": {"nope": true}}"#).unwrap_err();
+        assert_eq!(err.to_string(), "`flags.nope`: Unknown feature flag");
+    }
+
+    #[test]
+    fn identical_entries_are_skipped() {
+        let live = values(&[("counters", true), ("news_poller", false)]);
+        assert!(diff_flags(&live, &live).is_empty());
+
+        let incoming = values(&[("counters", true), ("news_poller", true)]);
+        assert_eq!(
+            diff_flags(&live, &incoming),
+            vec![FlagDiff {
+                name: "news_poller".to_string(),
+                before: Some(false),
+                after: Some(true),
+            }]
+        );
+    }
+
+    #[test]
+    fn updates_are_applied_before_removals() {
+        let live = values(&[
+            ("counters", true),
+            ("news_poller", false),
+            ("old_flag", true),
+        ]);
+        let incoming = values(&[("auto_withdraw_on_leave", true), ("news_poller", true)]);
+        let diffs = diff_flags(&live, &incoming);
+        let names = |diffs: &[FlagDiff]| -> Vec<String> {
+            diffs.iter().map(|diff| diff.name.clone()).collect()
+        };
+        assert_eq!(
+            names(&diffs),
+            vec![
+                "auto_withdraw_on_leave",
+                "counters",
+                "news_poller",
+                "old_flag"
+            ]
+        );
+
+        let plan = plan_sync(&diffs, true);
+        assert_eq!(
+            names(&plan.apply),
+            vec![
+                "auto_withdraw_on_leave",
+                "news_poller",
+                "counters",
+                "old_flag"
+            ]
+        );
+        assert!(plan.held_back.is_empty());
+        assert_eq!(plan.apply[0].to_string(), "+ auto_withdraw_on_leave: true");
+        assert_eq!(plan.apply[1].to_string(), "~ news_poller: false -> true");
+        assert_eq!(plan.apply[2].to_string(), "- counters: true");
+    }
+
+    #[test]
+    fn removals_are_held_back_unless_allowed() {
+        let live = values(&[("counters", true), ("news_poller", false)]);
+        let incoming = values(&[("news_poller", true)]);
+        let plan = plan_sync(&diff_flags(&live, &incoming), false);
+        assert_eq!(plan.apply.len(), 1);
+        assert!(plan.apply.iter().all(|diff| !diff.is_removal()));
+        assert_eq!(plan.held_back.len(), 1);
+        assert_eq!(plan.held_back[0].name, "counters");
+    }
+}
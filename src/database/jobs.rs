@@ -0,0 +1,392 @@
+//! Persistent scheduled jobs, for everything that has to happen at a certain time
+//!
+//! Jobs are stored in the `scheduled_jobs` collection and picked up by a single dispatcher (see
+//! `discord::scheduler`). A job is claimed by atomically switching it from pending to running with
+//! [`JobCollection::claim_due()`], so it never runs twice, even across restarts. Jobs that were
+//! running when the bot stopped are marked as failed on startup with [`JobCollection::interrupted()`]
+//! instead of being run again, since they might have already done their work. Those can be retried
+//! by hand.
+//!
+//! The state transitions themselves are pure, see [`after_success()`] and [`after_failure()`].
+
+use std::str::FromStr;
+
+use bson::oid::ObjectId;
+use bson::{doc, DateTime as BsonDateTime, Document};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{mongo_error, DatabaseError, DatabaseResult};
+
+/// Failed attempts after which a job is given up on
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled with every failed attempt
+const BASE_BACKOFF_MINUTES: i64 = 1;
+
+/// Longest delay between retries
+const MAX_BACKOFF_MINUTES: i64 = 60;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// What a job does, every kind has its own handler and payload
+pub enum JobKind {
+    /// Announces that registration opens or closes, see [`AnnouncementPayload`]
+    RegistrationAnnouncement,
+    /// Reminds registered players to check in, see [`ReminderPayload`]
+    CheckInReminder,
+}
+
+impl JobKind {
+    /// Every kind of job
+    pub const ALL: [JobKind; 2] = [JobKind::RegistrationAnnouncement, JobKind::CheckInReminder];
+
+    /// Name used in the database and in commands
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobKind::RegistrationAnnouncement => "registration_announcement",
+            JobKind::CheckInReminder => "check_in_reminder",
+        }
+    }
+}
+
+impl std::fmt::Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for JobKind {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        JobKind::ALL
+            .iter()
+            .find(|k| k.name() == s.to_lowercase())
+            .copied()
+            .ok_or_else(|| DatabaseError::InvalidInput(format!("Unknown job kind {}", s)))
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Where a job is in its lifecycle
+pub enum JobStatus {
+    /// Waiting to become due
+    Pending,
+    /// Claimed by the dispatcher and running right now
+    Running,
+    /// Finished, only one-shot jobs end up here
+    Done,
+    /// Gave up after too many attempts, or was interrupted by a restart
+    Failed,
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Whether registration opens or closes
+pub enum RegistrationPhase {
+    /// Registration opens
+    Open,
+    /// Registration closes
+    Close,
+}
+
+impl FromStr for RegistrationPhase {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "open" => Ok(RegistrationPhase::Open),
+            "close" => Ok(RegistrationPhase::Close),
+            _ => Err(DatabaseError::InvalidInput(format!(
+                "Unknown registration phase {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// Payload of a [`JobKind::RegistrationAnnouncement`]
+pub struct AnnouncementPayload {
+    /// Shorthand of the tournament
+    pub tournament: String,
+    /// Whether registration opens or closes
+    pub phase: RegistrationPhase,
+    /// Channel to post the announcement in
+    pub channel_id: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// Payload of a [`JobKind::CheckInReminder`]
+pub struct ReminderPayload {
+    /// Shorthand of the tournament
+    pub tournament: String,
+    /// Channel to post the reminder in
+    pub channel_id: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Represents a job as it's saved in the collection
+pub struct ScheduledJob {
+    /// ID of the job
+    pub _id: ObjectId,
+    /// What the job does
+    pub kind: JobKind,
+    /// When the job should run next
+    pub due_at: BsonDateTime,
+    /// Kind specific data, like [`AnnouncementPayload`]
+    pub payload: Document,
+    /// Where the job is in its lifecycle
+    pub status: JobStatus,
+    /// Failed attempts since the last success
+    pub attempts: u32,
+    /// Minutes between runs of a recurring job, `None` for one-shot jobs
+    pub every_minutes: Option<i64>,
+    /// Error of the last failed attempt
+    pub last_error: Option<String>,
+    /// When the job was last claimed
+    pub claimed_at: Option<BsonDateTime>,
+}
+
+impl ScheduledJob {
+    /// Creates a new pending job
+    pub fn new(
+        kind: JobKind,
+        due_at: DateTime<Utc>,
+        payload: Document,
+        every_minutes: Option<i64>,
+    ) -> ScheduledJob {
+        ScheduledJob {
+            _id: ObjectId::new(),
+            kind,
+            due_at: BsonDateTime::from(due_at),
+            payload,
+            status: JobStatus::Pending,
+            attempts: 0,
+            every_minutes,
+            last_error: None,
+            claimed_at: None,
+        }
+    }
+
+    /// Parses the payload into the type of the job kind
+    pub fn payload<T: serde::de::DeserializeOwned>(&self) -> DatabaseResult<T> {
+        bson::from_document(self.payload.clone())
+            .map_err(|e| DatabaseError::CouldNotParse(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Fields of a job that change after it ran
+pub struct Transition {
+    /// New status
+    pub status: JobStatus,
+    /// When the job should run next
+    pub due_at: DateTime<Utc>,
+    /// Failed attempts since the last success
+    pub attempts: u32,
+}
+
+/// Delay before retrying a job that failed `attempts` times
+///
+/// Starts at a minute and doubles with every attempt, up to an hour.
+pub fn backoff(attempts: u32) -> Duration {
+    // capping the shift keeps it from overflowing, 2^6 minutes is already above the maximum
+    let minutes = BASE_BACKOFF_MINUTES << attempts.saturating_sub(1).min(6);
+    Duration::minutes(minutes.min(MAX_BACKOFF_MINUTES))
+}
+
+/// What happens to a job that ran successfully
+///
+/// One-shot jobs are done. Recurring jobs are due again at their next occurrence after `now`,
+/// occurrences that were missed (e.g. while the bot was down) are skipped instead of run one after
+/// another.
+pub fn after_success(job: &ScheduledJob, now: DateTime<Utc>) -> Transition {
+    match job.every_minutes {
+        Some(every) if every > 0 => {
+            let every = Duration::minutes(every);
+            let mut due_at = *job.due_at + every;
+            while due_at <= now {
+                due_at = due_at + every;
+            }
+            Transition {
+                status: JobStatus::Pending,
+                due_at,
+                attempts: 0,
+            }
+        }
+        _ => Transition {
+            status: JobStatus::Done,
+            due_at: *job.due_at,
+            attempts: 0,
+        },
+    }
+}
+
+/// What happens to a job that failed
+///
+/// The job is retried after [`backoff()`], until it failed [`MAX_ATTEMPTS`] times.
+pub fn after_failure(job: &ScheduledJob, now: DateTime<Utc>) -> Transition {
+    let attempts = job.attempts + 1;
+    if attempts >= MAX_ATTEMPTS {
+        Transition {
+            status: JobStatus::Failed,
+            due_at: *job.due_at,
+            attempts,
+        }
+    } else {
+        Transition {
+            status: JobStatus::Pending,
+            due_at: now + backoff(attempts),
+            attempts,
+        }
+    }
+}
+
+/// Wrapper around the scheduled jobs collection
+pub struct JobCollection {
+    collection: Collection,
+}
+
+impl JobCollection {
+    /// Creates the wrapper
+    pub fn new(database: &Database) -> JobCollection {
+        JobCollection {
+            collection: database.collection("scheduled_jobs"),
+        }
+    }
+
+    /// Adds a job
+    pub async fn schedule(&self, job: &ScheduledJob) -> DatabaseResult<()> {
+        let document = bson::to_document(job).expect("Bad document");
+        tracing::info!("Scheduling {} job {} at {}", job.kind, job._id, *job.due_at);
+        self.collection
+            .insert_one(document, None)
+            .await
+            .map(|_| ())
+            .map_err(mongo_error)
+    }
+
+    /// Claims the job that has been due the longest, `None` if nothing is due
+    ///
+    /// Claiming switches the job to running in a single update, so a job is only ever claimed once.
+    pub async fn claim_due(&self, now: DateTime<Utc>) -> DatabaseResult<Option<ScheduledJob>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! {"due_at": 1})
+            .return_document(ReturnDocument::After)
+            .build();
+        let claimed = self
+            .collection
+            .find_one_and_update(
+                doc! {"status": "pending", "due_at": {"$lte": now}},
+                doc! {"$set": {"status": "running", "claimed_at": now}},
+                options,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        match claimed {
+            Some(document) => bson::from_document(document)
+                .map(Some)
+                .map_err(|e| DatabaseError::CouldNotParse(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the outcome of a run, with the error if it failed
+    pub async fn finish(
+        &self,
+        job: &ScheduledJob,
+        transition: Transition,
+        error: Option<&str>,
+    ) -> DatabaseResult<()> {
+        let status = bson::to_bson(&transition.status).expect("could not convert to bson");
+        self.collection
+            .update_one(
+                doc! {"_id": &job._id, "status": "running"},
+                doc! {"$set": {
+                    "status": status,
+                    "due_at": transition.due_at,
+                    "attempts": transition.attempts,
+                    "last_error": error
+                }},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(mongo_error)
+    }
+
+    /// Marks every running job as failed, returns how many there were
+    ///
+    /// Only call this on startup, before the dispatcher runs. Those jobs were interrupted and might
+    /// have already done their work, so they aren't run again automatically.
+    pub async fn interrupted(&self) -> DatabaseResult<i64> {
+        self.collection
+            .update_many(
+                doc! {"status": "running"},
+                doc! {"$set": {"status": "failed", "last_error": "interrupted by a restart"}},
+                None,
+            )
+            .await
+            .map(|result| result.modified_count)
+            .map_err(mongo_error)
+    }
+
+    /// Jobs that are pending, running or failed, ordered by when they are due
+    pub async fn get_open_jobs(&self) -> DatabaseResult<Vec<ScheduledJob>> {
+        let options = FindOptions::builder().sort(doc! {"due_at": 1}).build();
+        let cursor = self
+            .collection
+            .find(
+                doc! {"status": {"$in": ["pending", "running", "failed"]}},
+                options,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        crate::database::collect_entries(cursor).await
+    }
+
+    /// Makes a failed job pending again, due right away and with its attempts reset
+    pub async fn retry(&self, id: &str, now: DateTime<Utc>) -> DatabaseResult<()> {
+        let id = ObjectId::with_string(id)
+            .map_err(|_| DatabaseError::InvalidInput(format!("Invalid job ID {}", id)))?;
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": id, "status": "failed"},
+                doc! {"$set": {"status": "pending", "due_at": now, "attempts": 0}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Wipes all jobs from the collection
+    ///
+    /// Created for testing purposes, don't actually use this on a live database please
+    pub async fn remove_all(&self) -> DatabaseResult<()> {
+        self.collection.drop(None).await.map_err(mongo_error)
+    }
+}
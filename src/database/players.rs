@@ -6,26 +6,43 @@
 //! # Example
 //!
 //! ```
-//! let db = uc_helper_rust::database::connect()?;
-//! let player = db.players.get_player_by_tetrio("icedynamix")?;
-//! db.players.update_from_leaderboard()?;
+//! let db = uc_helper_rust::database::connect().await?;
+//! let player = db.players.get_player_by_tetrio("icedynamix").await?;
+//! db.players.update_from_leaderboard().await?;
 //! ```
 
-use bson::{doc, DateTime, Document};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Instant;
+
+use bson::{doc, Bson, DateTime, Document};
 use chrono::{Duration, TimeZone, Utc};
-use mongodb::sync::{Collection, Database};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
+use serenity::futures::stream::TryStreamExt;
+use thiserror::Error;
 
-use crate::database::{DatabaseError, DatabaseResult};
+use crate::database::{mongo_error, violated_unique_index, DatabaseError, DatabaseResult};
+use crate::tasks::TaskHandle;
 use crate::tetrio;
-use crate::tetrio::leaderboard::LeaderboardUser;
-use crate::tetrio::CacheData;
+use crate::tetrio::leaderboard::{LeaderboardData, LeaderboardUser};
+use crate::tetrio::{CacheData, Rank, SuccessfulResponse, TetrioApiError};
 
-use super::tournaments::TournamentEntry;
+use super::tournaments::{TournamentEntry, TournamentRestrictions};
 
 /// Collection name to use in the MongoDB database
 const COLLECTION_NAME: &str = "players";
 
+/// Name of the unique index on the Tetrio ID
+pub const TETRIO_ID_INDEX: &str = "tetrio_id_unique";
+
+/// Name of the unique index on the linked Discord ID, which only covers linked players
+pub const DISCORD_ID_INDEX: &str = "discord_id_unique";
+
+/// Amount of players written with a single bulk update in [`PlayerCollection::update_from_leaderboard()`]
+pub const BULK_CHUNK_SIZE: usize = 1000;
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// Represents an entry as it's saved in the collection
 ///
@@ -43,6 +60,37 @@ pub struct PlayerEntry {
     pub tetrio_data: Option<LeaderboardUser>,
     /// Cache data about the Tetrio API user data
     pub cache_data: Option<CacheData>,
+    /// When the Tetrio account was created
+    ///
+    /// Only the user endpoint returns this, so it's missing for players only updated from the
+    /// leaderboard, and for accounts from before Tetrio recorded join dates.
+    #[serde(default)]
+    pub account_created_at: Option<DateTime>,
+    /// Whether the player agreed to be included by name in research datasets, see [`crate::dataset`]
+    #[serde(default)]
+    pub data_sharing: bool,
+    /// Highest rank the bot has seen the player at, updated whenever their data is refreshed
+    ///
+    /// Only covers ranks since the player was first added, see [`PlayerEntry::peak_rank()`].
+    #[serde(default)]
+    pub highest_rank: Option<String>,
+    /// Whether the link was made by proving ownership of the Tetrio account, see [`PlayerCollection::confirm_verified_link()`]
+    #[serde(default)]
+    pub verified_link: bool,
+    /// Verification a Discord user started for this Tetrio account, see [`PlayerCollection::start_verified_link()`]
+    #[serde(default)]
+    pub pending_link: Option<PendingLink>,
+    /// Whether the Tetrio account still exists, updated whenever the player's data is refreshed
+    #[serde(default)]
+    pub status: AccountStatus,
+    /// Usernames the player had before, oldest first, so they can still be looked up by them
+    ///
+    /// Only covers renames the bot noticed while refreshing the player, see [`previous_usernames_after()`].
+    #[serde(default)]
+    pub previous_usernames: Vec<String>,
+    /// Code of the language the player wants replies in, English if `None`, see [`crate::discord::messages`]
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 impl PlayerEntry {
@@ -54,48 +102,520 @@ impl PlayerEntry {
             link_timestamp: None,
             tetrio_data: None,
             cache_data: None,
+            account_created_at: None,
+            data_sharing: false,
+            highest_rank: None,
+            verified_link: false,
+            pending_link: None,
+            status: AccountStatus::Active,
+            previous_usernames: Vec::new(),
+            language: None,
         }
     }
 
+    /// Sets when the Discord account was linked, for entries that weren't linked with [`PlayerCollection::link()`]
+    pub fn with_link_timestamp(mut self, linked_at: chrono::DateTime<Utc>) -> PlayerEntry {
+        self.link_timestamp = Some(DateTime::from(linked_at));
+        self
+    }
+
+    /// Whether a username or Tetrio ID refers to this player, ignoring case
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use uc_helper_rust::fixtures::{self, FixtureConfig};
+    ///
+    /// let config = FixtureConfig { players: 20, registrations: 0, ..FixtureConfig::default() };
+    /// let player = fixtures::generate(&config, Utc::now()).players.remove(0);
+    /// let username = player.tetrio_data.as_ref().unwrap().username.clone();
+    ///
+    /// assert!(player.is_account(&player.tetrio_id.to_uppercase()));
+    /// assert!(player.is_account(&username.to_uppercase()));
+    /// assert!(!player.is_account("someone_else"));
+    /// ```
+    pub fn is_account(&self, tetrio: &str) -> bool {
+        self.tetrio_id.eq_ignore_ascii_case(tetrio)
+            || self
+                .tetrio_data
+                .as_ref()
+                .map_or(false, |data| data.username.eq_ignore_ascii_case(tetrio))
+    }
+
+    /// When the Discord account was linked
+    pub fn linked_at(&self) -> Option<chrono::DateTime<Utc>> {
+        self.link_timestamp.map(|ts| *ts)
+    }
+
+    /// When the Tetrio data was last refreshed
+    pub fn refreshed_at(&self) -> Option<chrono::DateTime<Utc>> {
+        self.cache_data
+            .as_ref()
+            .map(|cache| Utc.timestamp(cache.cached_at / 1000, 0))
+    }
+
+    /// Current Tetra League rank from the cached Tetrio data, [`Rank::Unranked`] without any
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use uc_helper_rust::database::players::PlayerEntry;
+    /// use uc_helper_rust::fixtures::{self, FixtureConfig};
+    /// use uc_helper_rust::prelude::*;
+    ///
+    /// assert_eq!(PlayerEntry::new("5e47696db7c60f23a497ee6c", None).rank(), Rank::Unranked);
+    ///
+    /// let config = FixtureConfig { players: 20, registrations: 0, ..FixtureConfig::default() };
+    /// let player = fixtures::generate(&config, Utc::now()).players.remove(0);
+    /// let league_rank = player.tetrio_data.as_ref().unwrap().league.rank.clone();
+    /// assert_eq!(player.rank().to_str(), league_rank);
+    /// ```
+    pub fn rank(&self) -> Rank {
+        self.tetrio_data
+            .as_ref()
+            .and_then(|data| Rank::from_str(&data.league.rank).ok())
+            .unwrap_or(Rank::Unranked)
+    }
+
+    /// Highest rank the bot has seen the player at, `None` if it never saw them ranked
+    pub fn peak_rank(&self) -> Option<Rank> {
+        self.highest_rank
+            .as_deref()
+            .and_then(|rank| Rank::from_str(rank).ok())
+            .filter(|rank| *rank != Rank::Unranked)
+    }
+
     /// Parse a [`bson::Document`] to [`PlayerEntry`]
-    pub fn from_document(doc: Document) -> PlayerEntry {
-        bson::from_document(doc).expect("bad entry")
+    ///
+    /// Fails with [`DatabaseError::CouldNotParse`] if the document is malformed.
+    pub fn from_document(doc: Document) -> DatabaseResult<PlayerEntry> {
+        crate::database::parse_entry(doc)
     }
 
-    /// Whether the data is considered cached (saved for less than 10 minutes)
+    /// Whether the data is considered cached, using the default timeout of [`DEFAULT_CACHE_MINUTES`]
     ///
-    /// Using [`PlayerEntry::cache_data.cached_until`](`crate::tetrio::CacheData`) is not an option, since the amount
-    /// of time that Tetrio caches the data server side for is different between endpoints
-    /// (compare user endpoint 1min vs leaderboard endpoint 1h).
+    /// See [`PlayerEntry::is_cached_with()`].
     pub fn is_cached(&self) -> bool {
-        let cache_timeout = Duration::minutes(45);
+        self.is_cached_with(Duration::minutes(DEFAULT_CACHE_MINUTES))
+    }
 
-        if self.tetrio_data.is_some() {
-            if let Some(cache_data) = &self.cache_data {
-                let last_cached = Utc.timestamp(cache_data.cached_at / 1000, 0);
-                let now = Utc::now();
+    /// Whether the data was saved recently enough to not request it again
+    ///
+    /// Data counts as cached until `timeout` after it was saved, or until the API's own
+    /// [`cached_until`](`crate::tetrio::CacheData`) if that's later. The API caches endpoints for
+    /// very different amounts of time (compare user endpoint 1min vs leaderboard endpoint 1h), so it
+    /// only ever extends our own timeout. Timestamps in the future (clock skew between us and the
+    /// API) count as cached, otherwise every lookup would request the player again.
+    pub fn is_cached_with(&self, timeout: Duration) -> bool {
+        self.is_cached_at(timeout, Utc::now())
+    }
 
-                if now <= last_cached.checked_add_signed(cache_timeout).unwrap_or(now) {
-                    return true;
-                }
+    /// Does the work of [`PlayerEntry::is_cached_with()`] at a given time
+    fn is_cached_at(&self, timeout: Duration, now: chrono::DateTime<Utc>) -> bool {
+        let cache_data = match (&self.tetrio_data, &self.cache_data) {
+            (Some(_), Some(cache_data)) => cache_data,
+            _ => return false,
+        };
+
+        let (cached_at, cached_until) = match (
+            Utc.timestamp_millis_opt(cache_data.cached_at).single(),
+            Utc.timestamp_millis_opt(cache_data.cached_until).single(),
+        ) {
+            (Some(cached_at), Some(cached_until)) => (cached_at, cached_until),
+            _ => {
+                tracing::warn!(
+                    "Invalid cache timestamps for {} ({:?}), requesting again",
+                    self.tetrio_id,
+                    cache_data
+                );
+                return false;
             }
+        };
+
+        if cached_at > now {
+            return true;
+        }
+        match cached_at.checked_add_signed(timeout) {
+            Some(fresh_until) => now <= fresh_until.max(cached_until),
+            // a timeout too long to add up never runs out
+            None => true,
+        }
+    }
+}
+
+/// How long player data counts as cached if [`CACHE_MINUTES_ENV`] isn't set
+pub const DEFAULT_CACHE_MINUTES: i64 = 45;
+
+/// Environment variable for how long player data counts as cached, in minutes
+pub const CACHE_MINUTES_ENV: &str = "PLAYER_CACHE_MINUTES";
+
+/// How long a verification token can be used after it was handed out
+pub const VERIFICATION_TIMEOUT_MINUTES: i64 = 30;
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Link a Discord user wants to make, waiting for the token to show up in the Tetrio bio
+pub struct PendingLink {
+    /// Discord user who started the verification
+    pub discord_id: u64,
+    /// Token that has to be put in the bio
+    pub token: String,
+    /// When the token stops being accepted
+    pub expires_at: DateTime,
+}
+
+impl PendingLink {
+    /// Starts a verification with a fresh token
+    pub fn new(discord_id: u64, now: chrono::DateTime<Utc>) -> PendingLink {
+        PendingLink {
+            discord_id,
+            token: verification_token(discord_id, now),
+            expires_at: DateTime::from(now + Duration::minutes(VERIFICATION_TIMEOUT_MINUTES)),
         }
-        false
+    }
+
+    /// Whether the token can't be used anymore
+    pub fn is_expired(&self, now: chrono::DateTime<Utc>) -> bool {
+        now > *self.expires_at
+    }
+
+    /// Whether the token appears in a bio, ignoring case
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use uc_helper_rust::database::players::PendingLink;
+    ///
+    /// let pending = PendingLink::new(1234, Utc::now());
+    /// let bio = format!("hi! {}", pending.token.to_lowercase());
+    /// assert!(pending.matches_bio(&bio));
+    /// assert!(!pending.matches_bio("hi!"));
+    /// ```
+    pub fn matches_bio(&self, bio: &str) -> bool {
+        bio.to_uppercase().contains(&self.token)
+    }
+}
+
+/// Short token to put in the Tetrio bio, like `UC-7KQ2MX4P`
+///
+/// The token doesn't have to be secret, only someone with access to the account can put it in the
+/// bio. It only has to be unpredictable enough to not be in a bio already, so the randomly keyed
+/// hasher of the standard library is good enough.
+fn verification_token(discord_id: u64, now: chrono::DateTime<Utc>) -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    // no 0/O and 1/I, the token is typed by hand
+    const ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+    let mut hasher = RandomState::new().build_hasher();
+    (discord_id, now.timestamp_nanos()).hash(&mut hasher);
+    let mut value = hasher.finish();
+
+    let mut token = String::from("UC-");
+    for _ in 0..8 {
+        token.push(ALPHABET[(value % ALPHABET.len() as u64) as usize] as char);
+        value /= ALPHABET.len() as u64;
+    }
+    token
+}
+
+#[derive(Error, Debug)]
+/// Something that prevents a verified link from being completed
+pub enum VerifyLinkError {
+    #[error("There is no verification in progress, use `.verify_link <username>` first")]
+    /// The Discord user didn't start a verification
+    NoPendingLink,
+    #[error("The verification token expired, use `.verify_link <username>` to get a new one")]
+    /// The token is older than [`VERIFICATION_TIMEOUT_MINUTES`]
+    Expired,
+    #[error("The token `{token}` is not in the bio of {username} yet")]
+    /// The bio doesn't contain the token
+    TokenMismatch {
+        /// Tetrio username of the account that is being verified
+        username: String,
+        /// Token that has to be in the bio
+        token: String,
+    },
+    #[error("{0}")]
+    /// Something went wrong with the database or the Tetrio API
+    DatabaseError(#[from] DatabaseError),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// State of a player's Tetrio account, as of the last refresh
+pub enum AccountStatus {
+    /// Account exists and can play
+    Active,
+    /// The user endpoint doesn't know the account anymore, usually because it was deleted
+    NotFound,
+    /// Account was banned
+    Banned,
+}
+
+impl Default for AccountStatus {
+    fn default() -> Self {
+        AccountStatus::Active
+    }
+}
+
+impl AccountStatus {
+    /// Status of an account with the given Tetrio role
+    pub fn from_role(role: &str) -> AccountStatus {
+        match role {
+            "banned" => AccountStatus::Banned,
+            _ => AccountStatus::Active,
+        }
+    }
+}
+
+impl std::fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountStatus::Active => f.write_str("active"),
+            AccountStatus::NotFound => f.write_str("deleted"),
+            AccountStatus::Banned => f.write_str("banned"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Outcome of looking up the Discord account of a link
+pub enum DiscordAccountStatus {
+    /// Account is a member of the server
+    Member,
+    /// Account exists, but isn't a member of the server
+    NotInGuild,
+    /// Discord does not know the account anymore (404 on the user object)
+    Deleted,
+    /// Lookup failed for another reason, nothing can be said about the account
+    Unresolved,
+}
+
+/// Peak rank to store after seeing a player at their current rank
+///
+/// Unranked doesn't count as a rank, so it never replaces a known peak.
+fn next_highest_rank(known_peak: Option<Rank>, current_data: &LeaderboardUser) -> Option<String> {
+    let current_rank = Rank::from_str(&current_data.league.rank).unwrap_or(Rank::Unranked);
+    known_peak
+        .max(Some(current_rank).filter(|rank| *rank != Rank::Unranked))
+        .map(|rank| rank.to_str().to_string())
+}
+
+/// Previous usernames of a player after seeing them as `current`, `None` if nothing changed
+///
+/// The last known username is added if it differs from the current one. If the player went back
+/// to one of their old names, it's removed from the list, since it's their current name again.
+///
+/// ```
+/// use uc_helper_rust::database::players::previous_usernames_after;
+///
+/// let previous = vec!["caboozled".to_string()];
+/// assert_eq!(previous_usernames_after(&previous, Some("pie"), "pie"), None);
+/// assert_eq!(previous_usernames_after(&[], None, "pie"), None);
+/// assert_eq!(
+///     previous_usernames_after(&previous, Some("pie"), "caboozled_pie"),
+///     Some(vec!["caboozled".to_string(), "pie".to_string()])
+/// );
+/// assert_eq!(
+///     previous_usernames_after(&previous, Some("pie"), "caboozled"),
+///     Some(vec!["pie".to_string()])
+/// );
+/// ```
+pub fn previous_usernames_after(
+    previous: &[String],
+    last_known: Option<&str>,
+    current: &str,
+) -> Option<Vec<String>> {
+    let last_known = last_known.filter(|name| !name.eq_ignore_ascii_case(current))?;
+    let mut names: Vec<String> = previous
+        .iter()
+        .filter(|name| {
+            !name.eq_ignore_ascii_case(current) && !name.eq_ignore_ascii_case(last_known)
+        })
+        .cloned()
+        .collect();
+    names.push(last_known.to_lowercase());
+    Some(names)
+}
+
+/// Update statement that writes a leaderboard user, adding a new player entry if there is none
+///
+/// Does the same as [`PlayerCollection::update()`], as a statement for a bulk update.
+///
+/// `previous_usernames` is only written if the player was renamed, see [`previous_usernames_after()`].
+fn leaderboard_upsert(
+    user: &LeaderboardUser,
+    cache_data: &CacheData,
+    known_peak: Option<Rank>,
+    previous_usernames: Option<Vec<String>>,
+) -> Document {
+    // fields of a new entry, except the ones that are set anyway
+    let mut on_insert = bson::to_document(&PlayerEntry::new(&user._id, None)).unwrap();
+    for key in &[
+        "tetrio_id",
+        "tetrio_data",
+        "cache_data",
+        "highest_rank",
+        "status",
+    ] {
+        on_insert.remove(key);
+    }
+
+    let mut set = doc! {
+        "tetrio_data": bson::to_document(user).unwrap(),
+        "cache_data": bson::to_document(cache_data).unwrap(),
+        "highest_rank": next_highest_rank(known_peak, user),
+        "status": bson::to_bson(&AccountStatus::from_role(&user.role)).unwrap(),
+    };
+    // a field can't be in both `$set` and `$setOnInsert`
+    if let Some(previous_usernames) = previous_usernames {
+        on_insert.remove("previous_usernames");
+        set.insert("previous_usernames", previous_usernames);
+    }
+
+    doc! {
+        "q": {"tetrio_id": &user._id},
+        "u": {"$set": set, "$setOnInsert": on_insert},
+        "upsert": true,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Documents a bulk update matched, modified and added
+struct BulkCounts {
+    matched: i64,
+    modified: i64,
+    upserted: i64,
+}
+
+impl BulkCounts {
+    fn add(&mut self, other: BulkCounts) {
+        self.matched += other.matched;
+        self.modified += other.modified;
+        self.upserted += other.upserted;
     }
 }
 
+#[derive(Debug, Clone, Default)]
+/// Sorting and paging for [`PlayerCollection::get_players()`]
+///
+/// Everything is done by the database, so only the requested page is ever loaded.
+///
+/// ```
+/// use uc_helper_rust::database::players::PlayerQuery;
+///
+/// // Second page of the top players, 10 per page
+/// let query = PlayerQuery::new()
+///     .sort_by("tetrio_data.league.rating", false)
+///     .skip(10)
+///     .limit(10);
+/// ```
+pub struct PlayerQuery {
+    sort: Option<Document>,
+    skip: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl PlayerQuery {
+    /// No sorting, all players
+    pub fn new() -> PlayerQuery {
+        PlayerQuery::default()
+    }
+
+    /// Sorts by a field, can be called multiple times to break ties with later fields
+    pub fn sort_by(mut self, field: &str, ascending: bool) -> PlayerQuery {
+        self.sort
+            .get_or_insert_with(Document::new)
+            .insert(field, if ascending { 1 } else { -1 });
+        self
+    }
+
+    /// Skips the first players
+    pub fn skip(mut self, skip: usize) -> PlayerQuery {
+        self.skip = Some(skip as i64);
+        self
+    }
+
+    /// Returns at most this many players
+    pub fn limit(mut self, limit: usize) -> PlayerQuery {
+        self.limit = Some(limit as i64);
+        self
+    }
+
+    fn to_options(&self) -> FindOptions {
+        FindOptions::builder()
+            .sort(self.sort.clone())
+            .skip(self.skip)
+            .limit(self.limit)
+            .build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The value several player entries share, see [`PlayerCollection::find_duplicate_links()`]
+pub enum DuplicateKey {
+    /// Several Tetrio accounts are linked to the same Discord account
+    DiscordId(u64),
+    /// The same Tetrio account has several entries
+    TetrioId(String),
+}
+
+#[derive(Debug)]
+/// Player entries that conflict with each other
+pub struct DuplicateGroup {
+    /// What the entries share
+    pub key: DuplicateKey,
+    /// The conflicting entries
+    pub entries: Vec<PlayerEntry>,
+}
+
 /// Main wrapper for a MongoDB collection to manage players
 pub struct PlayerCollection {
     collection: Collection,
+    /// Needed for commands the collection wrapper doesn't offer, like bulk updates
+    database: Database,
+    /// How long player data counts as cached, see [`PlayerEntry::is_cached_with()`]
+    cache_timeout: Duration,
 }
 
 impl PlayerCollection {
     /// Constructs the wrapper struct for the MongoDB collection
     ///
     /// If the collection does not exist, then it will be created implicitly when a new entry is added.
-    pub fn new(database: &Database) -> PlayerCollection {
+    /// The cache timeout is read from [`CACHE_MINUTES_ENV`], [`DEFAULT_CACHE_MINUTES`] if it's not set.
+    pub async fn new(database: &Database) -> PlayerCollection {
+        let cache_minutes = match std::env::var(CACHE_MINUTES_ENV) {
+            Ok(minutes) => minutes.parse::<i64>().unwrap_or_else(|_| {
+                tracing::warn!(
+                    "{} is not a number of minutes, using {}",
+                    CACHE_MINUTES_ENV,
+                    DEFAULT_CACHE_MINUTES
+                );
+                DEFAULT_CACHE_MINUTES
+            }),
+            Err(_) => DEFAULT_CACHE_MINUTES,
+        };
+        crate::database::ensure_unique_index(
+            database,
+            COLLECTION_NAME,
+            TETRIO_ID_INDEX,
+            doc! {"tetrio_id": 1},
+            None,
+            None,
+        )
+        .await;
+        // Unlinked players store `null`, which a sparse index would still cover, so only numbers are indexed
+        crate::database::ensure_unique_index(
+            database,
+            COLLECTION_NAME,
+            DISCORD_ID_INDEX,
+            doc! {"discord_id": 1},
+            Some(doc! {"discord_id": {"$gt": 0}}),
+            None,
+        )
+        .await;
+
         PlayerCollection {
             collection: database.collection(COLLECTION_NAME),
+            database: database.clone(),
+            cache_timeout: Duration::minutes(cache_minutes.max(0)),
         }
     }
 
@@ -103,60 +623,141 @@ impl PlayerCollection {
     ///
     /// Implicitly adds a new player if they don't already exist, no "add" function required.
     /// This usually only happens when the player is unranked.
-    pub fn update_player(&self, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
-        tracing::info!("Updating {}", tetrio_id);
-        let previous_entry = self.get_player_by_tetrio(tetrio_id)?;
-        let is_cached = previous_entry.map_or(false, |e| e.is_cached());
-
-        if is_cached {
-            Ok(self.get_player_by_tetrio(tetrio_id)?.unwrap()) // eh who cares about performance
-        } else {
-            let (new_data, cache_data) = match tetrio::user::request(tetrio_id) {
-                Ok(response) => (response.data.user, response.cache),
-                Err(_) => return Err(DatabaseError::NotFound),
-            };
+    /// With `force`, the API is requested even if the stored data is still cached.
+    pub async fn update_player(&self, tetrio_id: &str, force: bool) -> DatabaseResult<PlayerEntry> {
+        let tetrio_id = tetrio_id.to_lowercase();
+        tracing::info!(
+            "Updating {}{}",
+            tetrio_id,
+            if force { " (forced)" } else { "" }
+        );
+
+        match self.get_player_by_tetrio(&tetrio_id).await? {
+            Some(entry) if !force && entry.is_cached_with(self.cache_timeout) => Ok(entry),
+            // only found by a previous username, which Tetrio doesn't know them by anymore
+            Some(entry) if !entry.is_account(&tetrio_id) => {
+                self.update_from_user_endpoint(&entry.tetrio_id).await
+            }
+            _ => self.update_from_user_endpoint(&tetrio_id).await,
+        }
+    }
+
+    /// Requests the user endpoint and writes the result to the collection, ignoring the cache
+    async fn update_from_user_endpoint(&self, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
+        let (user, cache_data) = match tetrio::user::request(tetrio_id).await {
+            Ok(response) => (response.data.user, response.cache),
+            Err(TetrioApiError::NotFound) => {
+                self.mark_not_found(tetrio_id).await?;
+                return Err(DatabaseError::NotFound);
+            }
+            Err(err) => return Err(DatabaseError::TetrioApiError(err)),
+        };
+
+        if let Some(ts) = user.ts {
+            self.collection
+                .update_one(
+                    doc! {"tetrio_id": &user.data._id},
+                    doc! {"$set": {"account_created_at": ts}},
+                    None,
+                )
+                .await
+                .map_err(mongo_error)?;
+        }
+
+        self.update(user.data, &cache_data).await
+    }
+
+    /// Marks a stored player as no longer existing, after the user endpoint didn't find them
+    ///
+    /// Nothing happens for players that aren't stored, their data is kept for staff to look at.
+    async fn mark_not_found(&self, tetrio_id: &str) -> DatabaseResult<()> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"$or": [{"tetrio_id": tetrio_id.to_lowercase()}, {"tetrio_data.username": tetrio_id.to_lowercase()}]},
+                doc! {"$set": {"status": bson::to_bson(&AccountStatus::NotFound).unwrap()}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        if result.modified_count > 0 {
+            tracing::warn!("{} does not exist on Tetrio anymore", tetrio_id);
+        }
+        Ok(())
+    }
+
+    /// When the account of a player was created
+    ///
+    /// Uses the stored value if there is one, otherwise requests the user endpoint, since the
+    /// leaderboard does not contain the creation date. Returns `None` for accounts from before Tetrio
+    /// recorded join dates.
+    pub async fn account_created_at(
+        &self,
+        player: &PlayerEntry,
+    ) -> DatabaseResult<Option<chrono::DateTime<Utc>>> {
+        if let Some(created_at) = player.account_created_at {
+            return Ok(Some(*created_at));
+        }
 
-            self.update(new_data, &cache_data)
+        let updated = self.update_from_user_endpoint(&player.tetrio_id).await?;
+        if updated.account_created_at.is_none() {
+            tracing::info!(
+                "{} has no account creation date, the account predates join dates",
+                player.tetrio_id
+            );
         }
+        Ok(updated.account_created_at.map(|ts| *ts))
     }
 
     /// Writes the updated player data to the collection
     ///
     /// Doesn't do any requesting or cache checking, and should thus only be used internally.
     /// You're looking for [`update_player()`] or [`update_from_leaderboard()`] instead.
-    fn update(
+    async fn update(
         &self,
         new_data: LeaderboardUser,
         cache_data: &CacheData,
     ) -> DatabaseResult<PlayerEntry> {
-        if self
-            .collection
-            .count_documents(doc! {"tetrio_id": &new_data._id}, None)
-            .unwrap()
-            == 0
-        {
-            tracing::info!("{} not in database, adding as new", new_data.username);
-            let player_entry = PlayerEntry::new(&new_data._id, None);
-            if self
-                .collection
-                .insert_one(bson::to_document(&player_entry).unwrap(), None)
-                .is_err()
-            {
-                return Err(DatabaseError::CouldNotPush);
+        let previous = match self.get_player_by_tetrio(&new_data._id).await? {
+            Some(previous) => previous,
+            None => {
+                tracing::info!("{} not in database, adding as new", new_data.username);
+                let player_entry = PlayerEntry::new(&new_data._id, None);
+                self.collection
+                    .insert_one(bson::to_document(&player_entry).unwrap(), None)
+                    .await
+                    .map_err(mongo_error)?;
+                player_entry
             }
+        };
+
+        let highest_rank = next_highest_rank(previous.peak_rank(), &new_data);
+        let status = bson::to_bson(&AccountStatus::from_role(&new_data.role)).unwrap();
+        let last_username = previous
+            .tetrio_data
+            .as_ref()
+            .map(|data| data.username.as_str());
+        let previous_usernames = previous_usernames_after(
+            &previous.previous_usernames,
+            last_username,
+            &new_data.username,
+        );
+        if let (Some(old), Some(_)) = (last_username, &previous_usernames) {
+            tracing::info!("{} was renamed to {}", old, new_data.username);
         }
 
         let tetrio_data_doc = bson::to_document(&new_data).unwrap();
         let cache_data = bson::to_document(&cache_data).unwrap();
+        let mut set = doc! {"tetrio_data": tetrio_data_doc, "cache_data": cache_data, "highest_rank": highest_rank, "status": status};
+        if let Some(previous_usernames) = previous_usernames {
+            set.insert("previous_usernames", previous_usernames);
+        }
         self.collection
-            .update_one(
-                doc! {"tetrio_id": &new_data._id},
-                doc! {"$set":{"tetrio_data": tetrio_data_doc, "cache_data": cache_data}},
-                None,
-            )
-            .expect("could not update player");
+            .update_one(doc! {"tetrio_id": &new_data._id}, doc! {"$set": set}, None)
+            .await
+            .map_err(mongo_error)?;
 
-        Ok(self.get_player_by_tetrio(&new_data._id)?.unwrap())
+        Ok(self.get_player_by_tetrio(&new_data._id).await?.unwrap())
     }
 
     /// Uses the Tetrio leaderboard endpoint to update all currently ranked players
@@ -168,44 +769,286 @@ impl PlayerCollection {
     /// New ranked players will be added and currently ranked players will be updated.
     /// Currently unranked players will not be updated.
     ///
-    /// Takes a long time to update, since most of the time is spent making database updates.
-    pub fn update_from_leaderboard(&self) -> DatabaseResult<()> {
+    /// The players are written with bulk updates of [`BULK_CHUNK_SIZE`] players each, so this
+    /// only takes a few seconds, even for the whole leaderboard.
+    pub async fn update_from_leaderboard(&self) -> DatabaseResult<()> {
+        self.update_from_leaderboard_cancellable(&TaskHandle::detached("Leaderboard update"))
+            .await
+    }
+
+    /// Same as [`PlayerCollection::update_from_leaderboard()`], but stops early once the task is cancelled
+    ///
+    /// Progress is tracked on the task handle, every user counts as a unit of work. Cancelling
+    /// takes effect between two bulk updates.
+    pub async fn update_from_leaderboard_cancellable(
+        &self,
+        task: &TaskHandle,
+    ) -> DatabaseResult<()> {
+        let response = tetrio::leaderboard::request()
+            .await
+            .map_err(DatabaseError::TetrioApiError)?;
+        self.update_with_leaderboard(&response, task).await
+    }
+
+    /// Same as [`PlayerCollection::update_from_leaderboard_cancellable()`], with a leaderboard that was already requested
+    ///
+    /// Lets commands that need the leaderboard for something else too, like a snapshot, request it only once.
+    pub async fn update_with_leaderboard(
+        &self,
+        response: &SuccessfulResponse<LeaderboardData>,
+        task: &TaskHandle,
+    ) -> DatabaseResult<()> {
         tracing::info!("Started updating via leaderboard");
-        let response = tetrio::leaderboard::request().map_err(DatabaseError::TetrioApiError)?;
+        let started = Instant::now();
+        task.set_total(response.data.users.len());
 
-        for user in response.data.users {
-            self.update(user, &response.cache)?;
+        let peaks = self.known_peaks().await?;
+        let usernames = self.known_usernames().await?;
+        let mut counts = BulkCounts::default();
+        for chunk in response.data.users.chunks(BULK_CHUNK_SIZE) {
+            if task.is_cancelled() {
+                tracing::info!("{}", task.summary());
+                break;
+            }
+            let updates = chunk
+                .iter()
+                .map(|user| {
+                    let previous_usernames =
+                        usernames.get(&user._id).and_then(|(last, previous)| {
+                            previous_usernames_after(previous, Some(last), &user.username)
+                        });
+                    leaderboard_upsert(
+                        user,
+                        &response.cache,
+                        peaks.get(&user._id).copied(),
+                        previous_usernames,
+                    )
+                })
+                .collect();
+            counts.add(self.bulk_update(updates).await?);
+            task.advance_by(chunk.len());
         }
 
+        tracing::info!(
+            "Updated via leaderboard in {:.1}s: {} matched, {} modified, {} upserted",
+            started.elapsed().as_secs_f64(),
+            counts.matched,
+            counts.modified,
+            counts.upserted
+        );
         Ok(())
     }
 
-    /// Uses the Tetrio leaderboard endpoint to update all currently registered players
+    /// Peak ranks of every player who has one, by Tetrio ID
+    async fn known_peaks(&self) -> DatabaseResult<HashMap<String, Rank>> {
+        let options = FindOptions::builder()
+            .projection(doc! {"tetrio_id": 1, "highest_rank": 1})
+            .build();
+        let mut cursor = self
+            .collection
+            .find(doc! {"highest_rank": {"$type": "string"}}, options)
+            .await
+            .map_err(mongo_error)?;
+
+        let mut peaks = HashMap::new();
+        while let Some(document) = cursor.try_next().await.map_err(mongo_error)? {
+            if let (Ok(tetrio_id), Ok(rank)) = (
+                document.get_str("tetrio_id"),
+                document.get_str("highest_rank"),
+            ) {
+                if let Ok(rank) = Rank::from_str(rank) {
+                    peaks.insert(tetrio_id.to_string(), rank);
+                }
+            }
+        }
+        Ok(peaks)
+    }
+
+    /// Last known username and previous usernames of every player with Tetrio data, by Tetrio ID
+    async fn known_usernames(&self) -> DatabaseResult<HashMap<String, (String, Vec<String>)>> {
+        let options = FindOptions::builder()
+            .projection(doc! {"tetrio_id": 1, "tetrio_data.username": 1, "previous_usernames": 1})
+            .build();
+        let mut cursor = self
+            .collection
+            .find(doc! {"tetrio_data.username": {"$type": "string"}}, options)
+            .await
+            .map_err(mongo_error)?;
+
+        let mut usernames = HashMap::new();
+        while let Some(document) = cursor.try_next().await.map_err(mongo_error)? {
+            let username = document
+                .get_document("tetrio_data")
+                .and_then(|data| data.get_str("username"));
+            if let (Ok(tetrio_id), Ok(username)) = (document.get_str("tetrio_id"), username) {
+                let previous = document
+                    .get_array("previous_usernames")
+                    .map(|names| {
+                        names
+                            .iter()
+                            .filter_map(|name| name.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                usernames.insert(tetrio_id.to_string(), (username.to_string(), previous));
+            }
+        }
+        Ok(usernames)
+    }
+
+    /// Sends update statements in a single `update` command, see [`leaderboard_upsert()`]
     ///
-    /// Relatively efficient, since it only uses a single request to the Tetrio API.
-    /// Cache timeouts are ignored here, since what has been requested with the
-    /// single request is already requested, so there is no harm in updating it anyway.
+    /// The driver doesn't have bulk writes, so the command is run directly. The statements are
+    /// unordered, a failing statement doesn't stop the others.
+    async fn bulk_update(&self, updates: Vec<Document>) -> DatabaseResult<BulkCounts> {
+        let reply = self
+            .database
+            .run_command(
+                doc! {"update": COLLECTION_NAME, "updates": updates, "ordered": false},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if let Ok(errors) = reply.get_array("writeErrors") {
+            if !errors.is_empty() {
+                tracing::error!(
+                    "Bulk update failed for {} players: {:?}",
+                    errors.len(),
+                    errors
+                );
+                return Err(DatabaseError::CouldNotPush);
+            }
+        }
+
+        let count = |key: &str| match reply.get(key) {
+            Some(Bson::Int32(n)) => *n as i64,
+            Some(Bson::Int64(n)) => *n,
+            _ => 0,
+        };
+        let upserted = reply.get_array("upserted").map_or(0, |u| u.len() as i64);
+        Ok(BulkCounts {
+            matched: count("n") - upserted,
+            modified: count("nModified"),
+            upserted,
+        })
+    }
+
+    /// Updates every registered player of a tournament, returns the updated entries
     ///
-    /// New ranked players will be added and currently ranked players will be updated.
-    /// Currently unranked players will not be updated.
+    /// Ranked players are updated with a single request to the leaderboard endpoint, which ignores
+    /// cache timeouts like [`PlayerCollection::update_from_leaderboard()`]. Everyone who isn't on the
+    /// leaderboard, usually because they are unranked, is updated with [`PlayerCollection::update_player()`],
+    /// so cached entries aren't requested again.
+    ///
+    /// Accounts that don't exist anymore are logged and skipped instead of aborting the update, they
+    /// are missing from the returned entries.
     ///
     /// Is a lot quicker than update_from_leaderboard()
-    pub fn update_registered(&self, tournament: TournamentEntry) -> DatabaseResult<()> {
-        tracing::info!("Started updating via leaderboard");
-        let response = tetrio::leaderboard::request().map_err(DatabaseError::TetrioApiError)?;
+    pub async fn update_registered(
+        &self,
+        tournament: &TournamentEntry,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        self.update_registered_cancellable(
+            tournament,
+            &TaskHandle::detached("Registered player update"),
+        )
+        .await
+    }
 
-        let registered: Vec<String> = tournament
-            .registered_players
+    /// Same as [`PlayerCollection::update_registered()`], but stops early once the task is cancelled
+    ///
+    /// Progress is tracked on the task handle, every registered player counts as a unit of work.
+    pub async fn update_registered_cancellable(
+        &self,
+        tournament: &TournamentEntry,
+        task: &TaskHandle,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        tracing::info!(
+            "Started updating registered players of {}",
+            tournament.shorthand
+        );
+        let mut remaining: HashSet<String> = tournament
+            .registered_players()
             .iter()
             .map(|reg| reg.tetrio_id.clone())
             .collect();
+        task.set_total(remaining.len());
 
+        let response = tetrio::leaderboard::request()
+            .await
+            .map_err(DatabaseError::TetrioApiError)?;
+        let mut updated = Vec::new();
         for user in response.data.users {
-            if registered.contains(&user._id) {
-                self.update(user, &response.cache)?;
+            if task.is_cancelled() {
+                tracing::info!("{}", task.summary());
+                return Ok(updated);
+            }
+            if remaining.remove(&user._id) {
+                updated.push(self.update(user, &response.cache).await?);
+                task.advance();
             }
         }
 
+        // whoever is left isn't on the leaderboard, so they have to be requested one by one
+        for tetrio_id in remaining {
+            if task.is_cancelled() {
+                tracing::info!("{}", task.summary());
+                break;
+            }
+            match self.update_player(&tetrio_id, false).await {
+                Ok(entry) => updated.push(entry),
+                Err(DatabaseError::NotFound) => {
+                    tracing::warn!(
+                        "Registered player {} doesn't exist anymore, skipping",
+                        tetrio_id
+                    )
+                }
+                Err(err) => return Err(err),
+            }
+            task.advance();
+        }
+
+        Ok(updated)
+    }
+
+    /// Sets whether a linked player agreed to share their data in research datasets
+    pub async fn set_data_sharing(&self, discord_id: u64, enabled: bool) -> DatabaseResult<()> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"discord_id": discord_id},
+                doc! {"$set": {"data_sharing": enabled}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Sets the language a linked player wants replies in, `None` goes back to the default
+    pub async fn set_language(
+        &self,
+        discord_id: u64,
+        language: Option<&str>,
+    ) -> DatabaseResult<()> {
+        let result = self
+            .collection
+            .update_one(
+                doc! {"discord_id": discord_id},
+                doc! {"$set": {"language": language}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
         Ok(())
     }
 
@@ -214,119 +1057,677 @@ impl PlayerCollection {
     /// Adds the [`PlayerEntry.discord_id`](PlayerEntry) field.
     ///
     /// Performs duplicate checks to make sure that keys cannot be added in incorrect ways.
-    pub fn link(&self, discord_id: u64, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
+    pub async fn link(&self, discord_id: u64, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
         tracing::info!("Linking {} to {}", tetrio_id, discord_id);
-        if let Some(entry) = self.get_player_by_discord(discord_id)? {
-            let data = entry.tetrio_data.expect("Expected data");
-            return if tetrio_id == data._id || tetrio_id == data.username {
+        if let Some(entry) = self.get_player_by_discord(discord_id).await? {
+            return if entry.is_account(tetrio_id) {
                 Err(DatabaseError::AlreadyLinked)
             } else {
                 Err(DatabaseError::DuplicateDiscordEntry)
             };
         }
 
-        let entry = self.update_player(&tetrio_id.to_lowercase())?; // if the specified player doesnt exist then this will err
+        let entry = self.update_player(&tetrio_id.to_lowercase(), false).await?; // if the specified player doesnt exist then this will err
 
         if entry.discord_id.map_or(false, |id| id != discord_id) {
             return Err(DatabaseError::DuplicateTetrioEntry);
         }
 
+        // Another link could have slipped in since the checks, the unique indexes catch that
         self.collection
             .update_one(
                 doc! {"tetrio_id": entry.tetrio_id},
-                doc! {"$set":{"discord_id": discord_id, "link_timestamp": Utc::now()}},
+                doc! {"$set":{"discord_id": discord_id, "link_timestamp": Utc::now(), "verified_link": false}},
                 None,
             )
-            .map_err(|_| DatabaseError::CouldNotPush)?;
+            .await
+            .map_err(|err| match violated_unique_index(&err) {
+                Some(DISCORD_ID_INDEX) => DatabaseError::DuplicateDiscordEntry,
+                Some(TETRIO_ID_INDEX) => DatabaseError::DuplicateTetrioEntry,
+                _ => mongo_error(err),
+            })?;
 
-        Ok(self.get_player_by_discord(discord_id)?.unwrap())
+        Ok(self.get_player_by_discord(discord_id).await?.unwrap())
     }
 
-    /// Undoes the link made by [`PlayerCollection.link()`]
+    /// Groups of players that share a Discord ID or a Tetrio ID, which the unique indexes should prevent
     ///
-    /// Performs the search via a document filter, should only be used internally.
-    /// You're probably looking for [`PlayerCollection.unlink_by_discord()`] or
-    /// [`PlayerCollection.unlink_by_tetrio()`] instead.
-    fn unlink(&self, filter: Document) -> DatabaseResult<PlayerEntry> {
-        let filter_results = self.get_players(filter.clone())?;
-        let entry = match filter_results.first() {
-            Some(entry) => entry,
-            None => return Err(DatabaseError::NotFound),
+    /// Meant for cleaning up data from before the indexes existed, index creation fails as long as
+    /// there are duplicates. Every group has at least two entries.
+    pub async fn find_duplicate_links(&self) -> DatabaseResult<Vec<DuplicateGroup>> {
+        let mut groups = Vec::new();
+        for (field, filter) in &[
+            ("discord_id", doc! {"discord_id": {"$gt": 0}}),
+            ("tetrio_id", doc! {}),
+        ] {
+            let pipeline = vec![
+                doc! {"$match": filter.clone()},
+                doc! {"$group": {"_id": format!("${}", field), "entries": {"$push": "$$ROOT"}, "count": {"$sum": 1}}},
+                doc! {"$match": {"count": {"$gt": 1}}},
+            ];
+            let mut cursor = self
+                .collection
+                .aggregate(pipeline, None)
+                .await
+                .map_err(mongo_error)?;
+
+            while let Some(group) = cursor.try_next().await.map_err(mongo_error)? {
+                let entries = group
+                    .get_array("entries")
+                    .map_err(|e| DatabaseError::CouldNotParse(e.to_string()))?
+                    .iter()
+                    .filter_map(|entry| entry.as_document().cloned())
+                    .map(crate::database::parse_entry)
+                    .collect::<DatabaseResult<Vec<PlayerEntry>>>()?;
+                let key = match entries.first() {
+                    Some(entry) if *field == "discord_id" => {
+                        DuplicateKey::DiscordId(entry.discord_id.unwrap_or_default())
+                    }
+                    Some(entry) => DuplicateKey::TetrioId(entry.tetrio_id.clone()),
+                    None => continue,
+                };
+                groups.push(DuplicateGroup { key, entries });
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Starts a verified link, returns the token the user has to put in their Tetrio bio
+    ///
+    /// Fails like [`PlayerCollection::link()`] if either side is already linked. Verifications the
+    /// Discord user started before are dropped, only the newest token counts.
+    pub async fn start_verified_link(
+        &self,
+        discord_id: u64,
+        tetrio_id: &str,
+    ) -> DatabaseResult<(PlayerEntry, PendingLink)> {
+        if self.get_player_by_discord(discord_id).await?.is_some() {
+            return Err(DatabaseError::DuplicateDiscordEntry);
+        }
+
+        let entry = self.update_player(&tetrio_id.to_lowercase(), false).await?;
+        if entry.discord_id.is_some() {
+            return Err(DatabaseError::DuplicateTetrioEntry);
+        }
+
+        self.collection
+            .update_many(
+                doc! {"pending_link.discord_id": discord_id},
+                doc! {"$unset": {"pending_link": ""}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        let pending = PendingLink::new(discord_id, Utc::now());
+        tracing::info!(
+            "{} started verifying their link to {}",
+            discord_id,
+            entry.tetrio_id
+        );
+        self.collection
+            .update_one(
+                doc! {"tetrio_id": &entry.tetrio_id},
+                doc! {"$set": {"pending_link": bson::to_document(&pending).unwrap()}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        Ok((entry, pending))
+    }
+
+    /// Completes a verified link if the token is in the Tetrio bio
+    ///
+    /// The bio is requested from the user endpoint every time, since it's not cached in the
+    /// database. Expired verifications are dropped.
+    pub async fn confirm_verified_link(
+        &self,
+        discord_id: u64,
+    ) -> Result<PlayerEntry, VerifyLinkError> {
+        let entry: PlayerEntry = crate::database::get_entry(
+            &self.collection,
+            doc! {"pending_link.discord_id": discord_id},
+        )
+        .await?
+        .ok_or(VerifyLinkError::NoPendingLink)?;
+        let pending = entry.pending_link.clone().unwrap();
+
+        if pending.is_expired(Utc::now()) {
+            self.collection
+                .update_one(
+                    doc! {"tetrio_id": &entry.tetrio_id},
+                    doc! {"$unset": {"pending_link": ""}},
+                    None,
+                )
+                .await
+                .map_err(mongo_error)?;
+            return Err(VerifyLinkError::Expired);
+        }
+
+        let user = match tetrio::user::request(&entry.tetrio_id).await {
+            Ok(response) => response.data.user,
+            Err(TetrioApiError::NotFound) => return Err(DatabaseError::NotFound.into()),
+            Err(err) => return Err(DatabaseError::TetrioApiError(err).into()),
         };
+        if !pending.matches_bio(user.bio.as_deref().unwrap_or_default()) {
+            return Err(VerifyLinkError::TokenMismatch {
+                username: user.data.username,
+                token: pending.token,
+            });
+        }
 
+        self.link(discord_id, &entry.tetrio_id).await?;
         self.collection
             .update_one(
-                filter,
-                doc! {"$unset": {"discord_id": "", "link_timestamp": ""}},
+                doc! {"tetrio_id": &entry.tetrio_id},
+                doc! {"$set": {"verified_link": true}, "$unset": {"pending_link": ""}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        Ok(self.get_player_by_discord(discord_id).await?.unwrap())
+    }
+
+    /// Drops verifications whose token expired before `now`, returns how many were dropped
+    pub async fn expire_pending_links(&self, now: chrono::DateTime<Utc>) -> DatabaseResult<i64> {
+        self.collection
+            .update_many(
+                doc! {"pending_link.expires_at": {"$lt": DateTime::from(now)}},
+                doc! {"$unset": {"pending_link": ""}},
                 None,
             )
-            .map_err(|_| DatabaseError::CouldNotPush)?;
+            .await
+            .map(|result| result.modified_count)
+            .map_err(mongo_error)
+    }
+
+    /// Undoes the link made by [`PlayerCollection.link()`]
+    ///
+    /// Performs the search via a document filter, should only be used internally.
+    /// You're probably looking for [`PlayerCollection.unlink_by_discord()`] or
+    /// [`PlayerCollection.unlink_by_tetrio()`] instead.
+    ///
+    /// Returns the entry as it was before unlinking, so it still contains the Discord ID.
+    async fn unlink(&self, filter: Document) -> DatabaseResult<PlayerEntry> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+        let unlinked = self
+            .collection
+            .find_one_and_update(
+                filter,
+                doc! {"$unset": {"discord_id": "", "link_timestamp": "", "verified_link": ""}},
+                options,
+            )
+            .await
+            .map_err(mongo_error)?;
 
-        Ok(entry.clone())
+        match unlinked {
+            Some(document) => crate::database::parse_entry(document),
+            None => Err(DatabaseError::NotFound),
+        }
     }
 
     /// Undoes the link made by [`PlayerCollection.link()`] for a specified Tetrio user
-    pub fn unlink_by_tetrio(&self, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
-        if let Some(entry) = self.get_player_by_tetrio(tetrio_id)? {
-            if entry.discord_id.is_none() {
-                Err(DatabaseError::FieldNotSet)
-            } else {
-                self.unlink(doc! {"$or": [{"tetrio_id": tetrio_id.to_lowercase()}, {"tetrio_data.username": tetrio_id.to_lowercase()}]})
+    ///
+    /// Returns the entry as it was before unlinking, fails with [`DatabaseError::FieldNotSet`] if the
+    /// player isn't linked.
+    pub async fn unlink_by_tetrio(&self, tetrio_id: &str) -> DatabaseResult<PlayerEntry> {
+        match self.get_player_by_tetrio(tetrio_id).await? {
+            Some(entry) if entry.discord_id.is_none() => Err(DatabaseError::FieldNotSet),
+            Some(entry) => {
+                self.unlink(doc! {"tetrio_id": &entry.tetrio_id, "discord_id": {"$exists": true}})
+                    .await
             }
-        } else {
-            Err(DatabaseError::NotFound)
+            None => Err(DatabaseError::NotFound),
         }
     }
 
     /// Undoes the link made by [`PlayerCollection.link()`] for a specified Discord user ID
-    pub fn unlink_by_discord(&self, discord_id: u64) -> DatabaseResult<PlayerEntry> {
-        if self.get_player_by_discord(discord_id)?.is_some() {
-            self.unlink(doc! {"discord_id": discord_id})
-        } else {
-            Err(DatabaseError::NotFound)
-        }
+    ///
+    /// Returns the entry as it was before unlinking, fails with [`DatabaseError::NotFound`] if no
+    /// player is linked to the Discord user.
+    pub async fn unlink_by_discord(&self, discord_id: u64) -> DatabaseResult<PlayerEntry> {
+        self.unlink(doc! {"discord_id": discord_id}).await
+    }
+
+    /// Linked players whose Tetrio account was deleted or banned, see [`AccountStatus`]
+    pub async fn get_unavailable_links(&self) -> DatabaseResult<Vec<PlayerEntry>> {
+        let active = bson::to_bson(&AccountStatus::Active).unwrap();
+        self.get_players(
+            doc! {
+                "discord_id": {"$exists": true, "$ne": null},
+                "status": {"$exists": true, "$ne": active},
+            },
+            None,
+        )
+        .await
     }
 
-    /// Gets current player data for a specified Tetrio user
-    pub fn get_player_by_tetrio(&self, tetrio_id: &str) -> DatabaseResult<Option<PlayerEntry>> {
-        crate::database::get_entry(
+    /// Finds links whose Discord account has been deleted
+    ///
+    /// `resolver` is called with every linked Discord ID. Only accounts resolving to
+    /// [`DiscordAccountStatus::Deleted`] are returned, players who simply left the server or could not
+    /// be resolved are kept.
+    pub async fn find_orphaned_links(
+        &self,
+        mut resolver: impl FnMut(u64) -> DiscordAccountStatus,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        let linked = self
+            .get_players(doc! {"discord_id": {"$exists": true, "$ne": null}}, None)
+            .await?;
+
+        Ok(linked
+            .into_iter()
+            .filter(|entry| {
+                entry.discord_id.map_or(false, |discord_id| {
+                    resolver(discord_id) == DiscordAccountStatus::Deleted
+                })
+            })
+            .collect())
+    }
+
+    /// Gets current player data for a specified Tetrio user, by ID or by current or previous username
+    ///
+    /// Case doesn't matter. If someone else had the username before, the player who has it now wins.
+    pub async fn get_player_by_tetrio(
+        &self,
+        tetrio_id: &str,
+    ) -> DatabaseResult<Option<PlayerEntry>> {
+        let matches = crate::database::get_entries(
             &self.collection,
-            doc! {"$or": [{"tetrio_id": tetrio_id.to_lowercase()}, {"tetrio_data.username": tetrio_id.to_lowercase()}]},
+            identifiers_filter(&[tetrio_id.to_string()]),
         )
+        .await?;
+        Ok(find_by_identifier(&matches, tetrio_id).cloned())
+    }
+
+    /// Gets the players matching any of the given Tetrio IDs or usernames with a single query
+    ///
+    /// Use [`find_by_identifier()`] to match the results back to the inputs.
+    pub async fn get_players_by_identifiers(
+        &self,
+        identifiers: &[String],
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        self.get_players(identifiers_filter(identifiers), None)
+            .await
+    }
+
+    /// Usernames starting with the same characters as `input`, for suggestions when nothing matched
+    pub async fn suggest_usernames(
+        &self,
+        input: &str,
+        limit: usize,
+    ) -> DatabaseResult<Vec<String>> {
+        let prefix: String = input.to_lowercase().chars().take(3).collect();
+        if prefix.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let escaped: String = prefix
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    c.to_string()
+                } else {
+                    format!("\\{}", c)
+                }
+            })
+            .collect();
+
+        Ok(self
+            .get_players(
+                doc! {"tetrio_data.username": {"$regex": format!("^{}", escaped)}},
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter_map(|p| p.tetrio_data.map(|d| d.username))
+            .take(limit)
+            .collect())
     }
 
     /// Gets current player data for the Tetrio user linked with the specified Discord user ID
-    pub fn get_player_by_discord(&self, discord_id: u64) -> DatabaseResult<Option<PlayerEntry>> {
-        crate::database::get_entry(&self.collection, doc! {"discord_id": discord_id})
+    pub async fn get_player_by_discord(
+        &self,
+        discord_id: u64,
+    ) -> DatabaseResult<Option<PlayerEntry>> {
+        crate::database::get_entry(&self.collection, doc! {"discord_id": discord_id}).await
     }
 
-    /// Gets a list of players specified by a document filter
-    pub fn get_players(
+    /// Gets a list of players specified by a document filter, optionally sorted and paged
+    pub async fn get_players(
         &self,
         filter: impl Into<Option<Document>>,
+        query: impl Into<Option<PlayerQuery>>,
     ) -> DatabaseResult<Vec<PlayerEntry>> {
-        crate::database::get_entries(&self.collection, filter)
+        match query.into() {
+            None => crate::database::get_entries(&self.collection, filter).await,
+            Some(query) => {
+                let cursor = self
+                    .collection
+                    .find(filter, query.to_options())
+                    .await
+                    .map_err(mongo_error)?;
+                crate::database::collect_entries(cursor).await
+            }
+        }
+    }
+
+    /// Filter for the players shown in [`PlayerCollection::get_leaderboard_page()`]
+    fn leaderboard_filter() -> Document {
+        let unavailable = bson::to_bson(&[AccountStatus::NotFound, AccountStatus::Banned]).unwrap();
+        doc! {
+            "discord_id": {"$exists": true, "$ne": null},
+            "tetrio_data.league.rank": {"$ne": Rank::Unranked.to_str()},
+            "status": {"$nin": unavailable},
+        }
+    }
+
+    /// A page of the ranked, linked players sorted by TR, and the total amount of such players
+    ///
+    /// Players whose account was deleted or banned are left out.
+    pub async fn get_leaderboard_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> DatabaseResult<(Vec<PlayerEntry>, usize)> {
+        let filter = PlayerCollection::leaderboard_filter();
+        let total = self
+            .collection
+            .count_documents(filter.clone(), None)
+            .await
+            .map_err(mongo_error)?;
+        let query = PlayerQuery::new()
+            .sort_by("tetrio_data.league.rating", false)
+            .sort_by("tetrio_id", true)
+            .skip(offset)
+            .limit(limit.max(1));
+        let players = self.get_players(filter, query).await?;
+        Ok((players, total.max(0) as usize))
+    }
+
+    /// Amount of ranked players per current rank that pass the rank restriction of a tournament
+    ///
+    /// Only the current rank is checked against [`TournamentRestrictions::max_rank`]. The highest rank
+    /// would need a news request per player, and rating deviation and ranked games are ignored, so
+    /// this overestimates how many players can actually register. Unranked players are never counted.
+    /// Ranks without any players are left out.
+    pub async fn eligible_distribution(
+        &self,
+        restrictions: &TournamentRestrictions,
+    ) -> DatabaseResult<BTreeMap<Rank, u64>> {
+        let ranks: Vec<&str> = Rank::iter()
+            .filter(|rank| **rank != Rank::Unranked && **rank <= restrictions.max_rank)
+            .map(|rank| rank.to_str())
+            .collect();
+        let pipeline = vec![
+            doc! {"$match": {"tetrio_data.league.rank": {"$in": ranks}}},
+            doc! {"$group": {"_id": "$tetrio_data.league.rank", "count": {"$sum": 1}}},
+        ];
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline, None)
+            .await
+            .map_err(mongo_error)?;
+
+        let mut distribution = BTreeMap::new();
+        while let Some(group) = cursor.try_next().await.map_err(mongo_error)? {
+            let rank = group
+                .get_str("_id")
+                .map_err(|e| DatabaseError::CouldNotParse(e.to_string()))?;
+            let count = group
+                .get_i32("count")
+                .map_err(|e| DatabaseError::CouldNotParse(e.to_string()))?;
+            distribution.insert(Rank::from_str(rank).unwrap(), count as u64);
+        }
+        Ok(distribution)
+    }
+
+    /// Amount of players per country among the given players, most players first
+    ///
+    /// Players who hide their country are counted under `None`, players without an entry are left out.
+    /// The counting happens in the database, so only one document per country gets loaded.
+    pub async fn country_distribution(
+        &self,
+        tetrio_ids: &[&str],
+    ) -> DatabaseResult<Vec<(Option<String>, u64)>> {
+        let pipeline = vec![
+            doc! {"$match": {"tetrio_id": {"$in": tetrio_ids}}},
+            doc! {"$group": {"_id": "$tetrio_data.country", "count": {"$sum": 1}}},
+        ];
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline, None)
+            .await
+            .map_err(mongo_error)?;
+
+        let mut distribution = Vec::new();
+        while let Some(group) = cursor.try_next().await.map_err(mongo_error)? {
+            let country = group
+                .get_str("_id")
+                .ok()
+                .filter(|country| !country.is_empty())
+                .map(|country| country.to_uppercase());
+            let count = group
+                .get_i32("count")
+                .map_err(|e| DatabaseError::CouldNotParse(e.to_string()))?;
+            distribution.push((country, count as u64));
+        }
+        // missing and empty countries can end up in separate groups
+        distribution.sort_by(|a, b| a.0.cmp(&b.0));
+        distribution.dedup_by(|later, first| {
+            if later.0 == first.0 {
+                first.1 += later.1;
+                true
+            } else {
+                false
+            }
+        });
+        distribution.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(distribution)
+    }
+
+    /// Inserts player entries as they are, without requesting anything from Tetrio
+    ///
+    /// Meant for generated data, see [`crate::fixtures`]. Existing players aren't checked for duplicates.
+    pub async fn insert_players(&self, players: &[PlayerEntry]) -> DatabaseResult<()> {
+        if players.is_empty() {
+            return Ok(());
+        }
+        let documents: Vec<Document> = players
+            .iter()
+            .map(|player| bson::to_document(player).expect("could not convert to document"))
+            .collect();
+        match self.collection.insert_many(documents, None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
     }
 
     /// Removes players matching a filter from the collection
     ///
     /// Should be used very rarely, since there is no real need to remove any entries.
-    pub fn remove_players(&self, filter: Document) -> DatabaseResult<()> {
+    pub async fn remove_players(&self, filter: Document) -> DatabaseResult<()> {
         tracing::info!("Deleting players with filter {:?}", filter);
-        match self.collection.delete_many(filter, None) {
+        match self.collection.delete_many(filter, None).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(DatabaseError::ConnectionFailed),
+            Err(err) => Err(mongo_error(err)),
         }
     }
 
     /// Wipes all entries from the collection
     ///
     /// Created for testing purposes, don't actually use this on a live database please
-    pub fn remove_all(&self) -> DatabaseResult<()> {
+    pub async fn remove_all(&self) -> DatabaseResult<()> {
         tracing::info!("Deleting the entire collection for some reason??");
-        match self.collection.drop(None) {
+        match self.collection.drop(None).await {
             Ok(_) => Ok(()),
-            Err(_) => Err(DatabaseError::ConnectionFailed),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+}
+
+/// Filter matching players by Tetrio ID, username or previous username, see [`PlayerCollection::get_players_by_identifiers()`]
+pub fn identifiers_filter(identifiers: &[String]) -> Document {
+    let lowercase: Vec<String> = identifiers.iter().map(|i| i.to_lowercase()).collect();
+    doc! {"$or": [
+        {"tetrio_id": {"$in": lowercase.clone()}},
+        {"tetrio_data.username": {"$in": lowercase.clone()}},
+        {"previous_usernames": {"$in": lowercase}},
+    ]}
+}
+
+/// Finds the player matching an identifier (Tetrio ID or username, case-insensitive)
+///
+/// Previous usernames only count if no player has the identifier as their current ID or username.
+///
+/// ```
+/// use uc_helper_rust::database::players::{find_by_identifier, PlayerEntry};
+///
+/// let mut renamed = PlayerEntry::new("5e47696db7c60f23a497ee6c", None);
+/// renamed.previous_usernames = vec!["caboozled".to_string()];
+/// let other = PlayerEntry::new("caboozled", None);
+///
+/// let players = vec![renamed.clone(), other];
+/// assert_eq!(find_by_identifier(&players, "Caboozled").unwrap().tetrio_id, "caboozled");
+/// assert_eq!(find_by_identifier(&players[..1], "CABOOZLED").unwrap().tetrio_id, renamed.tetrio_id);
+/// assert!(find_by_identifier(&players, "pie").is_none());
+/// ```
+pub fn find_by_identifier<'a>(
+    players: &'a [PlayerEntry],
+    identifier: &str,
+) -> Option<&'a PlayerEntry> {
+    players
+        .iter()
+        .find(|p| p.is_account(identifier))
+        .or_else(|| {
+            players.iter().find(|p| {
+                p.previous_usernames
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(identifier))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::leaderboard::LeagueData;
+
+    fn now() -> chrono::DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    fn entry(cache_data: Option<CacheData>) -> PlayerEntry {
+        let mut entry = PlayerEntry::new("5e47696db7c60f23a497ee6c", None);
+        entry.tetrio_data = Some(LeaderboardUser {
+            _id: "5e47696db7c60f23a497ee6c".to_string(),
+            username: "caboozled_pie".to_string(),
+            role: "user".to_string(),
+            country: None,
+            supporter: Some(false),
+            verified: false,
+            league: LeagueData {
+                gamesplayed: 50,
+                gameswon: 25,
+                rating: 20000f64,
+                rank: "s".to_string(),
+                glicko: Some(1500f64),
+                rd: Some(80f64),
+                apm: Some(30f64),
+                pps: Some(1.5),
+                vs: Some(60f64),
+            },
+        });
+        entry.cache_data = cache_data;
+        entry
+    }
+
+    // Saved `saved_ago` minutes before now, with the API caching it for `api_minutes`
+    fn cached(saved_ago: i64, api_minutes: i64) -> PlayerEntry {
+        let cached_at = now() - Duration::minutes(saved_ago);
+        entry(Some(CacheData {
+            status: "miss".to_string(),
+            cached_at: cached_at.timestamp_millis(),
+            cached_until: (cached_at + Duration::minutes(api_minutes)).timestamp_millis(),
+        }))
+    }
+
+    fn timeout() -> Duration {
+        Duration::minutes(45)
+    }
+
+    #[test]
+    fn never_saved_is_not_cached() {
+        assert!(!entry(None).is_cached_at(timeout(), now()));
+
+        let mut no_data = cached(1, 1);
+        no_data.tetrio_data = None;
+        assert!(!no_data.is_cached_at(timeout(), now()));
+    }
+
+    #[test]
+    fn cached_within_the_timeout() {
+        assert!(cached(0, 1).is_cached_at(timeout(), now()));
+        assert!(cached(10, 1).is_cached_at(timeout(), now()));
+        assert!(cached(45, 1).is_cached_at(timeout(), now()));
+        assert!(!cached(46, 1).is_cached_at(timeout(), now()));
+        assert!(!cached(10, 1).is_cached_at(Duration::minutes(5), now()));
+    }
+
+    #[test]
+    fn longer_api_cache_extends_the_timeout() {
+        assert!(cached(50, 60).is_cached_at(timeout(), now()));
+        assert!(!cached(61, 60).is_cached_at(timeout(), now()));
+    }
+
+    #[test]
+    fn future_timestamps_count_as_cached() {
+        assert!(cached(-5, 1).is_cached_at(timeout(), now()));
+        assert!(cached(-5, 1).is_cached_at(Duration::zero(), now()));
+    }
+
+    #[test]
+    fn invalid_timestamps_are_not_cached() {
+        let invalid = entry(Some(CacheData {
+            status: "hit".to_string(),
+            cached_at: i64::MAX,
+            cached_until: i64::MAX,
+        }));
+        assert!(!invalid.is_cached_at(timeout(), now()));
+    }
+
+    #[test]
+    fn huge_timeouts_do_not_overflow() {
+        assert!(cached(10, 1).is_cached_at(Duration::max_value(), now()));
+    }
+
+    #[test]
+    fn verifications_expire_after_the_timeout() {
+        let pending = PendingLink::new(1, now());
+        let timeout = Duration::minutes(VERIFICATION_TIMEOUT_MINUTES);
+        assert!(!pending.is_expired(now()));
+        assert!(!pending.is_expired(now() + timeout));
+        assert!(pending.is_expired(now() + timeout + Duration::seconds(1)));
+    }
+
+    #[test]
+    fn malformed_documents_are_parse_errors() {
+        let document = bson::to_document(&entry(None)).unwrap();
+        let parsed = PlayerEntry::from_document(document).unwrap();
+        assert_eq!(parsed.tetrio_id, "5e47696db7c60f23a497ee6c");
+
+        match PlayerEntry::from_document(doc! { "tetrio_id": 5 }) {
+            Err(DatabaseError::CouldNotParse(_)) => {}
+            other => panic!(
+                "expected a parse error, got {:?}",
+                other.map(|e| e.tetrio_id)
+            ),
         }
     }
 }
@@ -0,0 +1,273 @@
+//! Bot settings that staff can change at runtime
+//!
+//! Bot-wide settings are kept in a single document, so they can be read with a single query.
+//! Settings that belong to a Discord server are kept in one document per guild, keyed by the guild id.
+//! A missing document or field means the setting was never set.
+
+use bson::doc;
+use mongodb::options::{ReplaceOptions, UpdateOptions};
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{mongo_error, DatabaseError, DatabaseResult};
+
+/// ID of the settings document
+const SETTINGS_ID: &str = "global";
+
+/// Staff role name used if a guild never set one
+pub const DEFAULT_STAFF_ROLE_NAME: &str = "Staff";
+
+/// Channels where player and tournament commands were allowed before they were configurable
+///
+/// Used until a guild saves its own settings, so a fresh database behaves like the old bot.
+pub const DEFAULT_BOT_CHANNELS: [u64; 3] = [
+    901939376815218719, // register
+    752703502173863966, // bot spam
+    776806403884056616, // bot testing
+];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+/// Represents the settings document as it's saved in the collection
+pub struct Settings {
+    /// Channel rank-ups of linked players are announced in, no announcements if not set
+    #[serde(default)]
+    pub news_channel: Option<u64>,
+    /// ID of the newest news post that was processed, so restarts don't announce anything twice
+    #[serde(default)]
+    pub last_news_post: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// Represents the settings of a single Discord server
+pub struct GuildSettings {
+    /// Discord ID of the guild, used as the document ID
+    #[serde(rename = "_id")]
+    pub guild_id: u64,
+    /// Channels where player and tournament commands are allowed for non-staff
+    #[serde(default)]
+    pub allowed_bot_channels: Vec<u64>,
+    /// Channel check-in problems are reported in
+    #[serde(default)]
+    pub check_in_log_channel: Option<u64>,
+    /// Channel players are supposed to register in
+    #[serde(default)]
+    pub registration_channel: Option<u64>,
+    /// Name of the role that may use staff commands, only used if no staff roles are set
+    #[serde(default = "default_staff_role_name")]
+    pub staff_role_name: String,
+    /// IDs of the roles that may use staff commands
+    #[serde(default)]
+    pub staff_roles: Vec<u64>,
+}
+
+fn default_staff_role_name() -> String {
+    DEFAULT_STAFF_ROLE_NAME.to_string()
+}
+
+impl GuildSettings {
+    /// Settings of a guild that never saved any
+    pub fn new(guild_id: u64) -> GuildSettings {
+        GuildSettings {
+            guild_id,
+            allowed_bot_channels: DEFAULT_BOT_CHANNELS.to_vec(),
+            check_in_log_channel: None,
+            registration_channel: None,
+            staff_role_name: default_staff_role_name(),
+            staff_roles: Vec::new(),
+        }
+    }
+
+    /// Whether non-staff may use player and tournament commands in a channel
+    pub fn is_bot_channel(&self, channel_id: u64) -> bool {
+        self.allowed_bot_channels.contains(&channel_id)
+    }
+}
+
+/// Wrapper around the settings collection
+pub struct SettingsCollection {
+    collection: Collection,
+}
+
+impl SettingsCollection {
+    /// Creates the wrapper
+    pub fn new(database: &Database) -> SettingsCollection {
+        SettingsCollection {
+            collection: database.collection("settings"),
+        }
+    }
+
+    /// Current settings, defaults if nothing was ever set
+    pub async fn get(&self) -> DatabaseResult<Settings> {
+        Ok(
+            crate::database::get_entry(&self.collection, doc! {"_id": SETTINGS_ID})
+                .await?
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Sets a single field of the settings document, creating it if necessary
+    async fn set(&self, field: &str, value: bson::Bson) -> DatabaseResult<()> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.collection
+            .update_one(
+                doc! {"_id": SETTINGS_ID},
+                doc! {"$set": {field: value}},
+                options,
+            )
+            .await
+            .map_err(mongo_error)?;
+        Ok(())
+    }
+
+    /// Sets the channel rank-ups are announced in, `None` stops the announcements
+    pub async fn set_news_channel(&self, channel_id: Option<u64>) -> DatabaseResult<()> {
+        tracing::info!("Setting the news channel to {:?}", channel_id);
+        self.set("news_channel", bson::to_bson(&channel_id).unwrap())
+            .await
+    }
+
+    /// Remembers the newest processed news post
+    pub async fn set_last_news_post(&self, post_id: &str) -> DatabaseResult<()> {
+        self.set("last_news_post", post_id.into()).await
+    }
+
+    /// Settings of a guild, defaults if nothing was ever set
+    pub async fn get_guild(&self, guild_id: u64) -> DatabaseResult<GuildSettings> {
+        Ok(
+            crate::database::get_entry(&self.collection, doc! {"_id": guild_id})
+                .await?
+                .unwrap_or_else(|| GuildSettings::new(guild_id)),
+        )
+    }
+
+    /// Saves the whole settings document of a guild
+    ///
+    /// Returns the saved settings, so callers can update their cached copy.
+    async fn save_guild(&self, settings: GuildSettings) -> DatabaseResult<GuildSettings> {
+        let document = match bson::to_document(&settings) {
+            Ok(document) => document,
+            Err(err) => return Err(DatabaseError::CouldNotParse(err.to_string())),
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection
+            .replace_one(doc! {"_id": settings.guild_id}, document, options)
+            .await
+            .map_err(mongo_error)?;
+        Ok(settings)
+    }
+
+    /// Allows player and tournament commands in a channel
+    ///
+    /// Adding a channel that's already allowed does nothing.
+    pub async fn add_bot_channel(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> DatabaseResult<GuildSettings> {
+        let mut settings = self.get_guild(guild_id).await?;
+        if !settings.is_bot_channel(channel_id) {
+            tracing::info!("Allowing bot commands in {} on {}", channel_id, guild_id);
+            settings.allowed_bot_channels.push(channel_id);
+        }
+        self.save_guild(settings).await
+    }
+
+    /// Disallows player and tournament commands in a channel
+    pub async fn remove_bot_channel(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> DatabaseResult<GuildSettings> {
+        let mut settings = self.get_guild(guild_id).await?;
+        if !settings.is_bot_channel(channel_id) {
+            return Err(DatabaseError::NotFound);
+        }
+        tracing::info!("Disallowing bot commands in {} on {}", channel_id, guild_id);
+        settings.allowed_bot_channels.retain(|id| *id != channel_id);
+        self.save_guild(settings).await
+    }
+
+    /// Sets the channel check-in problems are reported in
+    pub async fn set_check_in_log_channel(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+    ) -> DatabaseResult<GuildSettings> {
+        tracing::info!(
+            "Setting the check-in log of {} to {:?}",
+            guild_id,
+            channel_id
+        );
+        let mut settings = self.get_guild(guild_id).await?;
+        settings.check_in_log_channel = channel_id;
+        self.save_guild(settings).await
+    }
+
+    /// Sets the channel players are supposed to register in
+    pub async fn set_registration_channel(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+    ) -> DatabaseResult<GuildSettings> {
+        tracing::info!(
+            "Setting the registration channel of {} to {:?}",
+            guild_id,
+            channel_id
+        );
+        let mut settings = self.get_guild(guild_id).await?;
+        settings.registration_channel = channel_id;
+        self.save_guild(settings).await
+    }
+
+    /// Sets the name of the role that may use staff commands
+    pub async fn set_staff_role_name(
+        &self,
+        guild_id: u64,
+        name: &str,
+    ) -> DatabaseResult<GuildSettings> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(DatabaseError::InvalidInput(
+                "Staff role name can't be empty".to_string(),
+            ));
+        }
+        tracing::info!("Setting the staff role of {} to {}", guild_id, name);
+        let mut settings = self.get_guild(guild_id).await?;
+        settings.staff_role_name = name.to_string();
+        self.save_guild(settings).await
+    }
+
+    /// Allows a role to use staff commands
+    ///
+    /// Once a guild has staff roles, the staff role name isn't matched anymore. Adding a role that's
+    /// already allowed does nothing.
+    pub async fn add_staff_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+    ) -> DatabaseResult<GuildSettings> {
+        let mut settings = self.get_guild(guild_id).await?;
+        if !settings.staff_roles.contains(&role_id) {
+            tracing::info!("Adding staff role {} on {}", role_id, guild_id);
+            settings.staff_roles.push(role_id);
+        }
+        self.save_guild(settings).await
+    }
+
+    /// Disallows a role to use staff commands
+    ///
+    /// Removing the last staff role makes the staff role name count again.
+    pub async fn remove_staff_role(
+        &self,
+        guild_id: u64,
+        role_id: u64,
+    ) -> DatabaseResult<GuildSettings> {
+        let mut settings = self.get_guild(guild_id).await?;
+        if !settings.staff_roles.contains(&role_id) {
+            return Err(DatabaseError::NotFound);
+        }
+        tracing::info!("Removing staff role {} on {}", role_id, guild_id);
+        settings.staff_roles.retain(|id| *id != role_id);
+        self.save_guild(settings).await
+    }
+}
@@ -1,6 +1,8 @@
 //! Wrapper for the tournament collection and methods that can be used to modify the collection
 //!
-//! There will only be one active tournament at a time, unless the database is edited by hand.
+//! Multiple tournaments can be active at the same time, for example a qualifier and its finals.
+//! Commands that only make sense for a single tournament use [`TournamentCollection::get_active()`],
+//! registration goes through [`TournamentCollection::resolve_active()`] to pick one.
 //!
 //! # Example
 //!
@@ -9,34 +11,54 @@
 //! use chrono::{DateTime, Utc, Duration};
 //! use uc_helper_rust::tetrio::Rank;
 //!
-//! let db = uc_helper_rust::database::connect()?;
+//! let db = uc_helper_rust::database::connect().await?;
 //!
 //! // Update all ranked players
-//! db.players.update_from_leaderboard()?;
+//! db.players.update_from_leaderboard().await?;
 //!
 //! // Create a tournament
 //! let restrictions = tournaments::TournamentRestrictions::default();
-//! let tournament = db.tournaments.create_tournament("Test Tournament 1", "TT1", restrictions)?;
+//! let tournament = db.tournaments.create_tournament("Test Tournament 1", "TT1", restrictions).await?;
 //!
-//! // Set tournament as active
-//! db.tournaments.set_active(Some(&tournament.shorthand))?; // Using None would set all tournaments to inactive
+//! // Set tournament as active, other active tournaments stay active
+//! db.tournaments.set_active(Some(&tournament.shorthand)).await?; // Using None would set all tournaments to inactive
+//!
+//! // Set it inactive again
+//! db.tournaments.set_inactive(&tournament.shorthand).await?;
 //! ```
 
 use std::str::FromStr;
 
 use bson::{doc, DateTime as BsonDateTime, Document};
-use chrono::{DateTime, Utc};
-use mongodb::sync::{Collection, Database};
+use chrono::{DateTime, Duration, Utc};
+use mongodb::options::{AggregateOptions, Collation, FindOneOptions};
+use mongodb::{Collection, Database};
 use serde::{Deserialize, Serialize};
+use serenity::futures::stream::TryStreamExt;
 use thiserror::Error;
 
-use crate::database::players::{PlayerCollection, PlayerEntry};
-use crate::database::{DatabaseError, DatabaseResult};
+use crate::database::players::{AccountStatus, PlayerCollection, PlayerEntry};
+use crate::database::{mongo_error, violated_unique_index, DatabaseError, DatabaseResult};
+use crate::eligibility;
+use crate::eligibility::{
+    Check, Criterion, EligibilityMode, EligibilityVerdict, Measure, SnapshotDiff,
+};
+use crate::receipt::{self, ReceiptKey};
 use crate::tetrio;
 use crate::tetrio::{leaderboard::LeaderboardUser, Rank};
 
 const COLLECTION_NAME: &str = "tournaments";
 
+/// Name of the collection every stat snapshot is kept in
+const SNAPSHOT_COLLECTION_NAME: &str = "snapshots";
+
+/// Hours before the announcement that a backdated registration may still be dated to
+const BACKDATE_GRACE_HOURS: i64 = 1;
+
+/// Minutes after which a pending check-in no longer blocks creating another one, in case the bot
+/// stopped halfway through creating it
+const PENDING_CHECK_IN_MINUTES: i64 = 5;
+
 type RegistrationResult = Result<(), RegistrationError>;
 
 #[derive(Error, Debug)]
@@ -44,62 +66,15 @@ type RegistrationResult = Result<(), RegistrationError>;
 ///
 /// Contains all relevant information to create a meaningful error message
 pub enum RegistrationError {
-    #[error("Current rank is too high (currently `{rank}`, ≤ `{expected}` required)")]
-    /// User's current rank is outside of the restrictions
-    CurrentRankTooHigh {
-        /// Current rank
-        rank: Rank,
-        /// Required rank
-        expected: Rank,
-    },
-    #[error("Highest-ever rank is too high (is `{rank}`, ≤ `{expected}` required)")]
-    /// User's highest-ever rank is outside of the restrictions
-    HighestRankTooHigh {
-        /// Current rank
-        rank: Rank,
-        /// Required rank
-        expected: Rank,
-    },
-    #[error(
-        "Rank was too high on announcement day (was `{rank}` by `{date}`, ≤ `{expected}` required)"
-    )]
-    /// User's announcement rank was outside of the restrictions
-    AnnouncementRankTooHigh {
-        /// Announcement rank
-        rank: Rank,
-        /// Required rank
-        expected: Rank,
-        /// Announcement date
-        date: DateTime<Utc>,
-    },
-    #[error("Not enough ranked games played until announcement day (was `{value}` by `{date}`, ≥ `{expected}` required)")]
-    /// User's ranked game count is outside of the restrictions
-    NotEnoughGames {
-        /// Current value
-        value: i64,
-        /// Expected value
-        expected: i64,
-        /// Announcement date
-        date: DateTime<Utc>,
-    },
-    #[error(
-        "RD was too high at announcement day (was `{value}` by `{date}`, ≥ `{expected}` required)"
-    )]
-    /// User's rating deviation is outside of the restrictions
-    RdTooHigh {
-        /// Current value
-        value: f64,
-        /// Expected value
-        expected: f64,
-        /// Announcement date
-        date: DateTime<Utc>,
-    },
-    #[error("Player was unranked on announcement day (`{0}`)")]
-    /// User was unranked on announcement day
-    UnrankedOnAnnouncementDay(DateTime<Utc>),
+    #[error("Player does not meet the tournament restrictions")]
+    /// Player's stats are outside of the restrictions, see [`eligibility::verdict_to_lines()`] for an explanation
+    Ineligible(EligibilityVerdict),
     #[error("There is no tournament ongoing")]
     /// There is no active tournament
     NoTournamentActive,
+    #[error("Multiple tournaments are ongoing, please specify one of {}", .0.join(", "))]
+    /// Multiple tournaments are active and none was specified, contains their shorthands
+    AmbiguousTournament(Vec<String>),
     #[error("Something was missing while registering (`{0}`)")]
     /// Missing information to register the user
     MissingArgument(String),
@@ -115,6 +90,88 @@ pub enum RegistrationError {
     #[error("Player stat snapshot is missing")]
     /// Snapshot is missing
     SnapshotMissing,
+    #[error("Player was disqualified from this tournament")]
+    /// Player was disqualified by staff and can't register again
+    Disqualified,
+    #[error("Player reached {rank} before, but the highest allowed rank is {expected}")]
+    /// Player's peak rank is above [`TournamentRestrictions::highest_rank_limit()`], every other restriction is met
+    HighestRankTooHigh {
+        /// Highest rank the player reached
+        rank: Rank,
+        /// Highest rank allowed
+        expected: Rank,
+    },
+    #[error("Registration opens at {0}")]
+    /// Registration hasn't opened yet, contains when it opens
+    RegistrationNotOpen(DateTime<Utc>),
+    #[error("Registration closed at {0}")]
+    /// Registration has closed already, contains when it closed
+    RegistrationClosed(DateTime<Utc>),
+    #[error("Player did not qualify through `{0}`")]
+    /// The tournament only accepts players who qualified through its parent tournament, see [`TournamentEntry::check_qualified()`]
+    NotQualified(String),
+    #[error(
+        "Registration date `{date}` is outside of the allowed range (`{earliest}` to `{latest}`)"
+    )]
+    /// A backdated registration date is outside of the allowed range
+    InvalidBackdate {
+        /// Requested registration date
+        date: DateTime<Utc>,
+        /// Earliest allowed registration date
+        earliest: DateTime<Utc>,
+        /// Latest allowed registration date
+        latest: DateTime<Utc>,
+    },
+    #[error("The Tetrio account is {0}")]
+    /// The player's Tetrio account was deleted or banned, or there is no data about it
+    AccountUnavailable(AccountStatus),
+    #[error("User is already on the waitlist (#{0})")]
+    /// User is already on the waitlist, contains their position starting at 1
+    AlreadyWaitlisted(usize),
+    #[error("User is linked to {linked}, not {requested}")]
+    /// User is linked to another Tetrio account than the one they tried to register with
+    ///
+    /// The link is left alone, unlinking first is up to the user.
+    LinkedToDifferentAccount {
+        /// Username of the linked account
+        linked: String,
+        /// Username or Tetrio ID the user tried to register with
+        requested: String,
+    },
+}
+
+impl RegistrationError {
+    /// Name of the variant, for counting failures by kind in [`crate::metrics`]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RegistrationError::Ineligible(_) => "ineligible",
+            RegistrationError::NoTournamentActive => "no_tournament_active",
+            RegistrationError::AmbiguousTournament(_) => "ambiguous_tournament",
+            RegistrationError::MissingArgument(_) => "missing_argument",
+            RegistrationError::DatabaseError(_) => "database_error",
+            RegistrationError::AlreadyRegistered => "already_registered",
+            RegistrationError::NotRegistered => "not_registered",
+            RegistrationError::SnapshotMissing => "snapshot_missing",
+            RegistrationError::Disqualified => "disqualified",
+            RegistrationError::HighestRankTooHigh { .. } => "highest_rank_too_high",
+            RegistrationError::RegistrationNotOpen(_) => "registration_not_open",
+            RegistrationError::RegistrationClosed(_) => "registration_closed",
+            RegistrationError::NotQualified(_) => "not_qualified",
+            RegistrationError::InvalidBackdate { .. } => "invalid_backdate",
+            RegistrationError::AccountUnavailable(_) => "account_unavailable",
+            RegistrationError::AlreadyWaitlisted(_) => "already_waitlisted",
+            RegistrationError::LinkedToDifferentAccount { .. } => "linked_to_different_account",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a successful registration turned out
+pub enum RegistrationOutcome {
+    /// The player takes part in the tournament
+    Registered,
+    /// The tournament is full and the player was put on the waitlist, contains their position starting at 1
+    Waitlisted(usize),
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -128,6 +185,20 @@ pub struct TournamentRestrictions {
     pub max_rd: f64,
     /// Minimum amount of played ranked games a user needs to have to have in order to register
     pub min_ranked_games: i64,
+    /// Minimum age of the Tetrio account in days at the time of registering, for open tournaments
+    #[serde(default)]
+    pub min_account_age_days: Option<i64>,
+    /// Highest rank a user is allowed to have ever reached, `max_rank + 1` if not set
+    #[serde(default)]
+    pub max_highest_rank: Option<Rank>,
+    /// Most players that can be registered at once, later registrations go on the waitlist
+    #[serde(default)]
+    pub max_participants: Option<u32>,
+    /// Whether players who were unranked on announcement day may register
+    ///
+    /// Their current ranked games and RD are checked instead, see [`crate::eligibility::evaluate()`].
+    #[serde(default)]
+    pub allow_unranked: bool,
 }
 
 impl TournamentRestrictions {
@@ -137,7 +208,77 @@ impl TournamentRestrictions {
             max_rank,
             max_rd,
             min_ranked_games,
+            min_account_age_days: None,
+            max_highest_rank: None,
+            max_participants: None,
+            allow_unranked: false,
+        }
+    }
+
+    /// Sets the most players that can be registered at once
+    pub fn with_max_participants(mut self, max_participants: u32) -> TournamentRestrictions {
+        self.max_participants = Some(max_participants);
+        self
+    }
+
+    /// Sets the minimum account age in days
+    pub fn with_min_account_age(mut self, days: i64) -> TournamentRestrictions {
+        self.min_account_age_days = Some(days);
+        self
+    }
+
+    /// Sets the highest rank a user is allowed to have ever reached
+    pub fn with_max_highest_rank(mut self, rank: Rank) -> TournamentRestrictions {
+        self.max_highest_rank = Some(rank);
+        self
+    }
+
+    /// Lets players who were unranked on announcement day register
+    pub fn with_allow_unranked(mut self) -> TournamentRestrictions {
+        self.allow_unranked = true;
+        self
+    }
+
+    /// Highest rank a user is allowed to have ever reached
+    ///
+    /// ```
+    /// use uc_helper_rust::database::tournaments::TournamentRestrictions;
+    /// use uc_helper_rust::tetrio::Rank;
+    ///
+    /// let restrictions = TournamentRestrictions::new(Rank::SPlus, 100f64, 10);
+    /// assert_eq!(restrictions.highest_rank_limit(), Rank::SS);
+    /// assert_eq!(restrictions.with_max_highest_rank(Rank::S).highest_rank_limit(), Rank::S);
+    /// ```
+    pub fn highest_rank_limit(&self) -> Rank {
+        self.max_highest_rank.unwrap_or(self.max_rank + 1)
+    }
+
+    /// Checks whether the restrictions make sense
+    pub fn validate(&self) -> DatabaseResult<()> {
+        if !self.max_rd.is_finite() || self.max_rd <= 0f64 {
+            return Err(DatabaseError::InvalidInput(format!(
+                "Max RD has to be a positive number (was {})",
+                self.max_rd
+            )));
+        }
+
+        if self.min_ranked_games < 0 {
+            return Err(DatabaseError::InvalidInput(format!(
+                "Min ranked games can't be negative (was {})",
+                self.min_ranked_games
+            )));
+        }
+
+        if let Some(days) = self.min_account_age_days {
+            if days < 0 {
+                return Err(DatabaseError::InvalidInput(format!(
+                    "Min account age can't be negative (was {})",
+                    days
+                )));
+            }
         }
+
+        Ok(())
     }
 }
 
@@ -147,25 +288,711 @@ impl Default for TournamentRestrictions {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Order of registrations when requesting them page by page
+pub enum RegOrder {
+    /// Earliest registration first
+    RegistrationDate,
+    /// Highest snapshot TR first, players without snapshot data last (by registration date)
+    Seed,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+/// Whether a registration counts or the player withdrew
+pub enum RegistrationStatus {
+    /// Registered and taking part
+    Active,
+    /// Withdrew from the tournament, the entry is kept so the player can return
+    Withdrawn {
+        /// When the player withdrew
+        at: BsonDateTime,
+        /// Reason given when withdrawing
+        reason: Option<String>,
+    },
+    /// Disqualified by staff, unlike a withdrawal the player can't register again
+    Disqualified {
+        /// When the player was disqualified
+        at: BsonDateTime,
+        /// Reason given by staff
+        reason: String,
+        /// Discord ID of the staff member
+        by: u64,
+    },
+}
+
+impl Default for RegistrationStatus {
+    fn default() -> Self {
+        RegistrationStatus::Active
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// A note staff left on a registration
+pub struct RegistrationNote {
+    /// When the note was added
+    pub at: BsonDateTime,
+    /// Discord ID of the staff member
+    pub by: u64,
+    /// Text of the note
+    pub text: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// The Discord message a registration was made with, for moderation context
+///
+/// Only identifiers are stored, never the content of the message.
+pub enum MessageRef {
+    /// A command in a server channel
+    Guild {
+        /// ID of the server
+        guild_id: u64,
+        /// ID of the channel
+        channel_id: u64,
+        /// ID of the message
+        message_id: u64,
+    },
+    /// A command sent in direct messages, which staff can't jump to
+    DirectMessage {
+        /// ID of the DM channel
+        channel_id: u64,
+        /// ID of the message
+        message_id: u64,
+    },
+}
+
+impl MessageRef {
+    /// Creates a reference, messages without a guild are direct messages
+    pub fn new(guild_id: Option<u64>, channel_id: u64, message_id: u64) -> MessageRef {
+        match guild_id {
+            Some(guild_id) => MessageRef::Guild {
+                guild_id,
+                channel_id,
+                message_id,
+            },
+            None => MessageRef::DirectMessage {
+                channel_id,
+                message_id,
+            },
+        }
+    }
+
+    /// ID of the channel the message was sent in
+    pub fn channel_id(&self) -> u64 {
+        match self {
+            MessageRef::Guild { channel_id, .. } | MessageRef::DirectMessage { channel_id, .. } => {
+                *channel_id
+            }
+        }
+    }
+
+    /// Link that jumps to the message, `None` for direct messages
+    pub fn jump_url(&self) -> Option<String> {
+        match self {
+            MessageRef::Guild {
+                guild_id,
+                channel_id,
+                message_id,
+            } => Some(format!(
+                "https://discord.com/channels/{}/{}/{}",
+                guild_id, channel_id, message_id
+            )),
+            MessageRef::DirectMessage { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for MessageRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.jump_url() {
+            Some(url) => f.write_str(&url),
+            None => f.write_str("direct message"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 /// Represents a registration in a tournament entry
 pub struct RegistrationEntry {
-    /// When the entry was created
+    /// When the player registered, can differ from `recorded_at` if staff backdated the registration
     pub date: BsonDateTime,
     /// ID of the registered player
     pub tetrio_id: String,
+    /// When the entry was actually inserted, missing for entries created before this was tracked
+    #[serde(default)]
+    recorded_at: Option<BsonDateTime>,
+    /// Username the player had when registering, missing for entries created before this was tracked
+    ///
+    /// Brackets and sign-up sheets use this name, so it stays the source of truth even if the player renames.
+    #[serde(default)]
+    pub as_registered_username: Option<String>,
+    /// Receipt code handed to the player, missing for older entries or if receipts are disabled (see [`crate::receipt`])
+    #[serde(default)]
+    pub receipt: Option<String>,
+    /// Whether the registration counts, entries created before withdrawals existed are active
+    #[serde(default)]
+    pub status: RegistrationStatus,
+    /// When the player last registered again after withdrawing
+    #[serde(default)]
+    pub reactivated_at: Option<BsonDateTime>,
+    /// Message of the last registration command, missing for older entries and entries created outside of Discord
+    #[serde(default)]
+    pub source: Option<MessageRef>,
+    /// Notes staff left on the registration, oldest first
+    #[serde(default)]
+    pub notes: Vec<RegistrationNote>,
+    /// Discord ID of the staff member who marked the registration as reviewed
+    #[serde(default)]
+    pub reviewed_by: Option<u64>,
+    /// When the registration was marked as reviewed
+    #[serde(default)]
+    pub reviewed_at: Option<BsonDateTime>,
+    /// Ranked games the player had played when registering, missing for entries created before this was tracked
+    #[serde(default)]
+    pub games_at_registration: Option<i64>,
 }
 
 impl RegistrationEntry {
     /// Creates a new registration entry
     pub fn new(tetrio_id: &str) -> RegistrationEntry {
+        RegistrationEntry::backdated(tetrio_id, Utc::now())
+    }
+
+    /// Creates a registration entry with an explicit registration date, which is also used as the insertion time
+    pub fn with_date(tetrio_id: &str, date: DateTime<Utc>) -> RegistrationEntry {
         RegistrationEntry {
-            date: BsonDateTime::from(Utc::now()),
+            date: BsonDateTime::from(date),
             tetrio_id: tetrio_id.to_string(),
+            recorded_at: None,
+            as_registered_username: None,
+            receipt: None,
+            status: RegistrationStatus::Active,
+            reactivated_at: None,
+            source: None,
+            notes: Vec::new(),
+            reviewed_by: None,
+            reviewed_at: None,
+            games_at_registration: None,
+        }
+    }
+
+    /// Whether the registration counts
+    ///
+    /// Everything that counts, seeds or exports registrations should only look at active ones,
+    /// see [`TournamentEntry::registered_players()`].
+    pub fn is_active(&self) -> bool {
+        self.status == RegistrationStatus::Active
+    }
+
+    /// Records the username the player registered with
+    pub fn registered_as(mut self, username: &str) -> RegistrationEntry {
+        self.as_registered_username = Some(username.to_string());
+        self
+    }
+
+    /// Records how many ranked games the player had played when registering
+    pub fn with_games_played(mut self, games_played: i64) -> RegistrationEntry {
+        self.games_at_registration = Some(games_played);
+        self
+    }
+
+    /// Ranked games played since registering, `None` if the count at registration isn't known
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use uc_helper_rust::database::tournaments::RegistrationEntry;
+    ///
+    /// let entry = RegistrationEntry::new("5e47696db7c60f23a497ee6c").with_games_played(120);
+    /// assert_eq!(Some(15), entry.games_since_registration(135));
+    /// assert_eq!(None, RegistrationEntry::new("5e47696db7c60f23a497ee6c").games_since_registration(135));
+    /// ```
+    pub fn games_since_registration(&self, games_played: i64) -> Option<i64> {
+        self.games_at_registration
+            .map(|at_registration| (games_played - at_registration).max(0))
+    }
+
+    /// Records the message the registration was made with
+    pub fn sourced_from(mut self, source: Option<MessageRef>) -> RegistrationEntry {
+        self.source = source;
+        self
+    }
+
+    /// Signs the registration with a receipt code for the given tournament
+    pub fn with_receipt(mut self, shorthand: &str, key: &ReceiptKey) -> RegistrationEntry {
+        self.receipt = Some(key.generate(shorthand, &self.tetrio_id, *self.date));
+        self
+    }
+
+    /// Name to show for this registration, given the player's current username
+    ///
+    /// Shows `newname (was oldname)` if the player renamed since registering, and falls back
+    /// to the Tetrio ID if no name is known at all.
+    pub fn display_name(&self, current_username: Option<&str>) -> String {
+        match (current_username, self.as_registered_username.as_deref()) {
+            (Some(current), Some(registered)) if current != registered => {
+                format!("{} (was {})", current, registered)
+            }
+            (Some(current), _) => current.to_string(),
+            (None, Some(registered)) => registered.to_string(),
+            (None, None) => self.tetrio_id.clone(),
+        }
+    }
+
+    /// Creates a registration entry with an explicit registration date, recording the current time as insertion time
+    pub fn backdated(tetrio_id: &str, date: DateTime<Utc>) -> RegistrationEntry {
+        RegistrationEntry {
+            recorded_at: Some(BsonDateTime::from(Utc::now())),
+            ..RegistrationEntry::with_date(tetrio_id, date)
+        }
+    }
+
+    /// When the entry was actually inserted
+    ///
+    /// Falls back to the registration date for entries created before this was tracked.
+    pub fn recorded_at(&self) -> DateTime<Utc> {
+        *self.recorded_at.unwrap_or(self.date)
+    }
+
+    /// Whether the registration date differs from the insertion time (by more than a minute)
+    pub fn is_backdated(&self) -> bool {
+        (self.recorded_at() - *self.date).num_minutes().abs() >= 1
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// Who removed a registration
+pub enum UnregisteredBy {
+    /// The player removed their own registration
+    Player,
+    /// A staff member removed the registration
+    Staff {
+        /// Discord ID of the staff member
+        discord_id: u64,
+    },
+    /// The bot removed the registration, for example because the player isn't eligible anymore
+    Automatic,
+}
+
+impl std::fmt::Display for UnregisteredBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnregisteredBy::Player => f.write_str("the player"),
+            UnregisteredBy::Staff { discord_id } => write!(f, "staff ({})", discord_id),
+            UnregisteredBy::Automatic => f.write_str("the bot"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// A registration that was removed from a tournament
+///
+/// Kept so the original registration date can be restored if the player registers again, which
+/// matters for first come first served seeding.
+pub struct UnregisteredEntry {
+    /// The registration as it was when it was removed
+    pub registration: RegistrationEntry,
+    /// When the registration was removed
+    pub unregistered_at: BsonDateTime,
+    /// Who removed the registration
+    pub by: UnregisteredBy,
+    /// Why the registration was removed, if a reason was given
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Something that happened to a player's registration, see [`TournamentEntry::registration_history()`]
+pub enum RegistrationEventKind {
+    /// The player registered, contains the registration date that counts for seeding
+    Registered(DateTime<Utc>),
+    /// The player withdrew
+    Withdrawn(Option<String>),
+    /// The player registered again after withdrawing
+    Reactivated,
+    /// Staff disqualified the player
+    Disqualified(String),
+    /// The registration was removed, with the reason if one was given
+    Unregistered(UnregisteredBy, Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single entry of a player's registration history
+pub struct RegistrationEvent {
+    /// When it happened
+    pub at: DateTime<Utc>,
+    /// What happened
+    pub kind: RegistrationEventKind,
+}
+
+impl std::fmt::Display for RegistrationEventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationEventKind::Registered(date) => write!(
+                f,
+                "Registered (counts from {})",
+                date.format("%Y-%m-%d %H:%M UTC")
+            ),
+            RegistrationEventKind::Withdrawn(Some(reason)) => write!(f, "Withdrew ({})", reason),
+            RegistrationEventKind::Withdrawn(None) => f.write_str("Withdrew"),
+            RegistrationEventKind::Reactivated => f.write_str("Registered again"),
+            RegistrationEventKind::Disqualified(reason) => {
+                write!(f, "Disqualified ({})", reason)
+            }
+            RegistrationEventKind::Unregistered(by, Some(reason)) => {
+                write!(f, "Unregistered by {} ({})", by, reason)
+            }
+            RegistrationEventKind::Unregistered(by, None) => write!(f, "Unregistered by {}", by),
+        }
+    }
+}
+
+impl RegistrationEntry {
+    /// Events recorded on this registration, in no particular order
+    fn events(&self) -> Vec<RegistrationEvent> {
+        let mut events = vec![RegistrationEvent {
+            at: self.recorded_at(),
+            kind: RegistrationEventKind::Registered(*self.date),
+        }];
+        // a withdrawal is overwritten when reactivating, only the reactivation is left then
+        if let Some(at) = self.reactivated_at {
+            events.push(RegistrationEvent {
+                at: *at,
+                kind: RegistrationEventKind::Reactivated,
+            });
+        }
+        match &self.status {
+            RegistrationStatus::Active => {}
+            RegistrationStatus::Withdrawn { at, reason } => events.push(RegistrationEvent {
+                at: **at,
+                kind: RegistrationEventKind::Withdrawn(reason.clone()),
+            }),
+            RegistrationStatus::Disqualified { at, reason, .. } => events.push(RegistrationEvent {
+                at: **at,
+                kind: RegistrationEventKind::Disqualified(reason.clone()),
+            }),
+        }
+        events
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+/// A check-in, tagged with the check-in generation it was made in
+///
+/// Players check in by reacting to the check-in message, every reaction is recorded here, so the
+/// check-ins are known without reading the reactions again. The generation tells whether the
+/// check-in was made after the last reset (refer to [`TournamentCollection::reset_check_in()`]).
+pub struct CheckInRecord {
+    /// Discord ID of the player who checked in
+    pub discord_id: u64,
+    /// Tetrio ID of the player who checked in, `None` for records from before it was stored
+    #[serde(default)]
+    pub tetrio_id: Option<String>,
+    /// When the player checked in, `None` for records from before it was stored
+    #[serde(default)]
+    pub at: Option<BsonDateTime>,
+    /// Check-in generation at the time of checking in
+    pub generation: u32,
+    /// Whether the check-in was made on a check-in message that has since been reposted
+    ///
+    /// Carried over check-ins count without a reaction, since the reaction was lost with the old message.
+    #[serde(default)]
+    pub carried_over: bool,
+    /// Discord ID of the staff member who checked the player in by hand, see [`TournamentCollection::force_check_in()`]
+    ///
+    /// Forced check-ins count without a reaction, like carried over ones.
+    #[serde(default)]
+    pub forced_by: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Check-in list as it was right before a reset
+pub struct CheckInSnapshot {
+    /// Generation the check-ins belonged to
+    pub generation: u32,
+    /// Discord IDs of everyone who was checked in
+    pub discord_ids: Vec<u64>,
+    /// When the check-in was reset
+    pub reset_at: BsonDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Something staff were alerted about, kept with the tournament for later reference
+pub struct StaffAlert {
+    /// When the alert was raised
+    pub at: BsonDateTime,
+    /// Tetrio ID of the player the alert is about
+    pub tetrio_id: String,
+    /// Text of the alert
+    pub text: String,
+}
+
+/// Withdrawal reason for players whose rank went above the cap, see [`TournamentCollection::purge_ineligible()`]
+pub const OVERRANKED_REASON: &str = "rank above the cap";
+
+/// Check-ins to keep when the check-in message is reposted
+///
+/// Keeps the recorded check-ins of the current generation and adds records for `reacted` (the users who
+/// reacted to the old message, if it could still be read) that don't have one yet. Stale reactions are
+/// dropped. Every kept record is marked as carried over, with one record per player.
+pub fn carry_over_check_ins(
+    records: &[CheckInRecord],
+    reacted: &[u64],
+    generation: u32,
+) -> Vec<CheckInRecord> {
+    let mut carried: Vec<CheckInRecord> = records
+        .iter()
+        .filter(|record| record.generation == generation)
+        .map(|record| CheckInRecord {
+            carried_over: true,
+            ..record.clone()
+        })
+        .collect();
+    let reacted = reacted.iter().copied().filter(|id| {
+        let recorded_generation = records
+            .iter()
+            .find(|record| record.discord_id == *id)
+            .map(|record| record.generation);
+        !is_stale_check_in(recorded_generation, generation)
+    });
+
+    for discord_id in reacted {
+        if carried.iter().all(|record| record.discord_id != discord_id) {
+            carried.push(CheckInRecord {
+                discord_id,
+                tetrio_id: None,
+                at: None,
+                forced_by: None,
+                generation,
+                carried_over: true,
+            });
+        }
+    }
+    carried
+}
+
+/// Highest rank a player reached, taken from their rankup news posts
+///
+/// `None` if there are no rankup posts.
+async fn highest_rank(tetrio_id: &str) -> Result<Option<Rank>, DatabaseError> {
+    // No need to cache the results, register isn't called often enough for the same endpoint to require caching
+    let posts = tetrio::news::request(&format!("user_{}", tetrio_id))
+        .await
+        .map_err(DatabaseError::TetrioApiError)?
+        .data
+        .news;
+
+    Ok(posts
+        .iter()
+        .filter(|post| post.post_type == "rankup")
+        .map(|post| Rank::from_str(post.data["rank"].as_str().unwrap()).unwrap())
+        .max())
+}
+
+/// Checks a player against the stat restrictions, without touching the database or the Tetrio API
+///
+/// Does what [`TournamentEntry::check_player()`] does with everything passed in: `snapshot` is the
+/// player's data on announcement day (`None` if they were unranked back then), `snapshot_at` when the
+/// snapshot was taken (`None` if there is none yet) and `highest_rank` the highest rank known from
+/// rankup posts and [`PlayerEntry::peak_rank()`]. Players who only fail the peak rank check get
+/// [`RegistrationError::HighestRankTooHigh`], everyone else who fails gets
+/// [`RegistrationError::Ineligible`] with the full verdict.
+pub fn check_eligibility(
+    restrictions: &TournamentRestrictions,
+    snapshot: Option<&LeaderboardUser>,
+    snapshot_at: Option<DateTime<Utc>>,
+    current: &LeaderboardUser,
+    highest_rank: Option<Rank>,
+    account_created_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> RegistrationResult {
+    let snapshot_at = snapshot_at.ok_or(RegistrationError::SnapshotMissing)?;
+    let verdict = eligibility::evaluate(
+        restrictions,
+        snapshot,
+        snapshot_at,
+        current,
+        highest_rank,
+        account_created_at,
+        now,
+    );
+    if verdict.is_eligible() {
+        return Ok(());
+    }
+
+    let failures: Vec<&Check> = verdict.failures().collect();
+    match failures.as_slice() {
+        [Check {
+            criterion: Criterion::HighestRank,
+            value: Some(Measure::Rank(rank)),
+            ..
+        }] => Err(RegistrationError::HighestRankTooHigh {
+            rank: *rank,
+            expected: restrictions.highest_rank_limit(),
+        }),
+        _ => Err(RegistrationError::Ineligible(verdict)),
+    }
+}
+
+/// Whether a check-in reaction predates the last reset and should be ignored
+///
+/// `recorded_generation` is the generation of the player's [`CheckInRecord`], if there is one.
+/// Before the first reset every reaction counts, since check-ins weren't recorded in the past.
+pub fn is_stale_check_in(recorded_generation: Option<u32>, current_generation: u32) -> bool {
+    current_generation > 0 && recorded_generation.map_or(true, |g| g < current_generation)
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Result of the last bracket side split (refer to [`crate::bracket::split`])
+pub struct BracketSplit {
+    /// RNG seed used for the split, saved as a string since it can exceed the signed integer range
+    pub seed: String,
+    /// When the split was made
+    pub created_at: BsonDateTime,
+    /// Tetrio IDs assigned to side A, in seed order
+    pub side_a: Vec<String>,
+    /// Tetrio IDs assigned to side B, in seed order
+    pub side_b: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+/// Dates in the lifecycle of a tournament, unset dates aren't enforced
+pub struct TournamentDates {
+    /// When the tournament is announced
+    pub announcement_at: Option<BsonDateTime>,
+    /// When registration opens
+    pub registration_opens_at: Option<BsonDateTime>,
+    /// When registration closes
+    pub registration_closes_at: Option<BsonDateTime>,
+    /// When the tournament starts
+    pub tournament_starts_at: Option<BsonDateTime>,
+}
+
+impl TournamentDates {
+    /// Creates dates with nothing set
+    pub fn new() -> TournamentDates {
+        TournamentDates::default()
+    }
+
+    /// Sets when the tournament is announced
+    pub fn announcement_at(mut self, date: DateTime<Utc>) -> TournamentDates {
+        self.announcement_at = Some(BsonDateTime::from(date));
+        self
+    }
+
+    /// Sets when registration opens
+    pub fn registration_opens_at(mut self, date: DateTime<Utc>) -> TournamentDates {
+        self.registration_opens_at = Some(BsonDateTime::from(date));
+        self
+    }
+
+    /// Sets when registration closes
+    pub fn registration_closes_at(mut self, date: DateTime<Utc>) -> TournamentDates {
+        self.registration_closes_at = Some(BsonDateTime::from(date));
+        self
+    }
+
+    /// Sets when the tournament starts
+    pub fn tournament_starts_at(mut self, date: DateTime<Utc>) -> TournamentDates {
+        self.tournament_starts_at = Some(BsonDateTime::from(date));
+        self
+    }
+
+    /// Every set date with its name, in lifecycle order
+    pub fn list(&self) -> Vec<(&'static str, DateTime<Utc>)> {
+        vec![
+            ("Announcement", self.announcement_at),
+            ("Registration opens", self.registration_opens_at),
+            ("Registration closes", self.registration_closes_at),
+            ("Tournament starts", self.tournament_starts_at),
+        ]
+        .into_iter()
+        .filter_map(|(name, date)| date.map(|date| (name, *date)))
+        .collect()
+    }
+
+    /// Verifies that the set dates are in lifecycle order
+    pub fn validate(&self) -> DatabaseResult<()> {
+        for pair in self.list().windows(2) {
+            if pair[0].1 > pair[1].1 {
+                return Err(DatabaseError::InvalidInput(format!(
+                    "{} has to be before {}",
+                    pair[0].0, pair[1].0
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether registering at `date` is within the registration window
+    ///
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use uc_helper_rust::database::tournaments::{RegistrationError, TournamentDates};
+    ///
+    /// let now = Utc::now();
+    /// let dates = TournamentDates::new()
+    ///     .registration_opens_at(now)
+    ///     .registration_closes_at(now + Duration::days(7));
+    /// assert!(dates.check_registration(now + Duration::days(1)).is_ok());
+    /// assert!(matches!(
+    ///     dates.check_registration(now - Duration::hours(1)),
+    ///     Err(RegistrationError::RegistrationNotOpen(_))
+    /// ));
+    /// assert!(matches!(
+    ///     dates.check_registration(now + Duration::days(8)),
+    ///     Err(RegistrationError::RegistrationClosed(_))
+    /// ));
+    /// assert!(TournamentDates::new().check_registration(now).is_ok());
+    /// ```
+    pub fn check_registration(&self, date: DateTime<Utc>) -> RegistrationResult {
+        match (self.registration_opens_at, self.registration_closes_at) {
+            (Some(opens), _) if date < *opens => {
+                Err(RegistrationError::RegistrationNotOpen(*opens))
+            }
+            (_, Some(closes)) if date >= *closes => {
+                Err(RegistrationError::RegistrationClosed(*closes))
+            }
+            _ => Ok(()),
         }
     }
 }
 
+/// Label of a snapshot taken before snapshots had labels
+pub const LEGACY_SNAPSHOT_LABEL: &str = "legacy";
+
+#[derive(Deserialize, Serialize, Debug)]
+/// Represents a stat snapshot as it's saved in the snapshot collection
+///
+/// The snapshot a tournament uses is copied into its [`TournamentEntry`], so eligibility checks don't
+/// need another query (refer to [`TournamentCollection::set_official_snapshot()`]).
+pub struct StoredSnapshot {
+    /// Shorthand of the tournament the snapshot belongs to
+    pub tournament: String,
+    /// Name of the snapshot, unique per tournament
+    pub label: String,
+    /// When the snapshot was taken
+    pub taken_at: BsonDateTime,
+    /// Ranked players at that time
+    pub users: Vec<LeaderboardUser>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+/// A stored snapshot without its leaderboard data (refer to [`TournamentCollection::get_snapshots()`])
+pub struct SnapshotInfo {
+    /// Name of the snapshot
+    pub label: String,
+    /// When the snapshot was taken
+    pub taken_at: BsonDateTime,
+    /// How many ranked players the snapshot contains
+    pub players: usize,
+    /// Whether the tournament uses this snapshot for eligibility checks
+    #[serde(skip)]
+    pub official: bool,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 /// Represents an entry as it's saved in the collection
 pub struct TournamentEntry {
@@ -179,14 +1006,63 @@ pub struct TournamentEntry {
     pub restrictions: TournamentRestrictions,
     /// List of registrations
     pub registered_players: Vec<RegistrationEntry>,
+    /// Registrations that were removed, oldest first (refer to [`TournamentCollection::unregister_by_tetrio()`])
+    #[serde(default)]
+    pub unregistered_players: Vec<UnregisteredEntry>,
+    /// Players waiting for a spot once the tournament is full, first come first served
+    /// (refer to [`TournamentRestrictions::max_participants`])
+    #[serde(default)]
+    pub waitlist: Vec<RegistrationEntry>,
     /// Snapshot of stats to use for checking announcement stats (refer to [`TournamentCollection::add_snapshot()`])
     player_stats_snapshot: Vec<LeaderboardUser>,
     /// When the snapshot was made
     snapshot_at: Option<BsonDateTime>,
+    /// Label of the snapshot in use, `None` for snapshots taken before snapshots had labels
+    #[serde(default)]
+    snapshot_label: Option<String>,
     /// Whether the tournament is active right now
     active: bool,
     /// Check-in message
     pub check_in_msg: Option<u64>,
+    /// Channel of the check-in message, `None` for messages posted before the channel was stored
+    #[serde(default)]
+    pub check_in_channel: Option<u64>,
+    /// When the creation of a check-in message started, unset once it's finished (see [`crate::check_in`])
+    #[serde(default)]
+    pub pending_check_in: Option<BsonDateTime>,
+    /// Last bracket side split
+    #[serde(default)]
+    pub bracket_split: Option<BracketSplit>,
+    /// How often the check-in was reset, see [`TournamentCollection::reset_check_in()`]
+    #[serde(default)]
+    pub check_in_generation: u32,
+    /// Whether reactions to the check-in message are ignored, see [`TournamentCollection::close_check_in()`]
+    #[serde(default)]
+    pub check_in_closed: bool,
+    /// Check-ins recorded by the reaction handler
+    #[serde(default)]
+    pub check_ins: Vec<CheckInRecord>,
+    /// Check-in lists from before each reset, oldest first
+    #[serde(default)]
+    pub previous_check_ins: Vec<CheckInSnapshot>,
+    /// Alerts raised about registered players, oldest first
+    #[serde(default)]
+    pub alerts: Vec<StaffAlert>,
+    /// Shorthand of the qualifier feeding this tournament, only qualified players can register if set
+    #[serde(default)]
+    pub parent_tournament: Option<String>,
+    /// Tetrio IDs of the players who qualified through the parent tournament
+    #[serde(default)]
+    pub qualified_players: Vec<String>,
+    /// Whether qualified players skip the stat restrictions, they already passed them in the qualifier
+    #[serde(default)]
+    pub qualified_skip_restrictions: bool,
+    /// Lifecycle dates, see [`TournamentCollection::set_dates()`]
+    #[serde(default)]
+    pub dates: TournamentDates,
+    /// Discord role given to registered players, see [`TournamentCollection::set_participant_role()`]
+    #[serde(default)]
+    pub participant_role: Option<u64>,
 }
 
 impl TournamentEntry {
@@ -202,395 +1078,2977 @@ impl TournamentEntry {
             created_at: BsonDateTime::from(Utc::now()),
             restrictions,
             registered_players: Vec::new(),
+            unregistered_players: Vec::new(),
+            waitlist: Vec::new(),
             player_stats_snapshot: Vec::new(),
             snapshot_at: None,
+            snapshot_label: None,
             active: false,
             check_in_msg: None,
+            check_in_channel: None,
+            pending_check_in: None,
+            bracket_split: None,
+            check_in_generation: 0,
+            check_in_closed: false,
+            check_ins: Vec::new(),
+            previous_check_ins: Vec::new(),
+            alerts: Vec::new(),
+            parent_tournament: None,
+            qualified_players: Vec::new(),
+            qualified_skip_restrictions: false,
+            dates: TournamentDates::default(),
+            participant_role: None,
         }
     }
 
-    /// Verify whether a player can participate in this tournament
-    ///
-    /// Uses snapshot data, so [`TournamentCollection::add_snapshot()`] must have been called at least
-    /// once before.
-    fn check_player_stats(&self, current_data: &LeaderboardUser) -> RegistrationResult {
-        let snapshot_at = match self.snapshot_at {
-            None => return Err(RegistrationError::SnapshotMissing),
-            Some(ts) => *ts,
-        };
-
-        let snapshot_data = self
-            .player_stats_snapshot
-            .iter()
-            .find(|u| current_data._id == u._id);
-
-        match snapshot_data {
-            None => Err(RegistrationError::UnrankedOnAnnouncementDay(snapshot_at)),
-            Some(snap) => {
-                let announce_rank = Rank::from_str(&snap.league.rank).unwrap();
-                if announce_rank > self.restrictions.max_rank {
-                    return Err(RegistrationError::AnnouncementRankTooHigh {
-                        rank: announce_rank,
-                        expected: self.restrictions.max_rank,
-                        date: snapshot_at,
-                    });
-                }
-
-                let games_played = snap.league.gamesplayed;
-                if games_played < self.restrictions.min_ranked_games {
-                    return Err(RegistrationError::NotEnoughGames {
-                        value: games_played,
-                        expected: self.restrictions.min_ranked_games,
-                        date: snapshot_at,
-                    });
-                }
-
-                let rd = snap.league.rd.unwrap_or(999f64);
-                if rd > self.restrictions.max_rd {
-                    return Err(RegistrationError::RdTooHigh {
-                        value: rd,
-                        expected: self.restrictions.max_rd,
-                        date: snapshot_at,
-                    });
-                }
-
-                let current_rank = Rank::from_str(&current_data.league.rank).unwrap();
-                if current_rank > self.restrictions.max_rank {
-                    return Err(RegistrationError::CurrentRankTooHigh {
-                        rank: current_rank,
-                        expected: self.restrictions.max_rank,
-                    });
-                }
-
-                // No need to cache the results, register isn't called often enough for the same endpoint to require caching
-                let posts = tetrio::news::request(&format!("user_{}", current_data._id))
-                    .unwrap()
-                    .data
-                    .news;
+    /// When the tournament entry was created
+    pub fn created_at(&self) -> DateTime<Utc> {
+        *self.created_at
+    }
 
-                let highest_rank = posts
-                    .iter()
-                    .filter(|post| post.post_type == "rankup")
-                    .map(|post| Rank::from_str(post.data["rank"].as_str().unwrap()).unwrap())
-                    .max();
-
-                // If there were no rankup posts, then it means that they never ranked up after the news post system was implemented.
-                // Therefore, the current rank must be the highest rank
-                if let Some(highest_rank) = highest_rank {
-                    if highest_rank > self.restrictions.max_rank + 1 {
-                        return Err(RegistrationError::CurrentRankTooHigh {
-                            rank: highest_rank,
-                            expected: self.restrictions.max_rank,
-                        });
-                    }
-                }
+    /// Whether the tournament is active right now
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
 
-                Ok(())
-            }
-        }
+    /// List of active registrations, players who withdrew are left out
+    pub fn registered_players(&self) -> Vec<&RegistrationEntry> {
+        self.registered_players
+            .iter()
+            .filter(|entry| entry.is_active())
+            .collect()
     }
 
-    /// Whether a user is registered to this tournament or not
-    pub fn player_is_registered(&self, player: &PlayerEntry) -> bool {
+    /// Number of active registrations, same as the length of [`TournamentEntry::registered_players()`]
+    pub fn registration_count(&self) -> usize {
         self.registered_players
             .iter()
-            .any(|entry| entry.tetrio_id == player.tetrio_id)
+            .filter(|entry| entry.is_active())
+            .count()
     }
-}
+
+    /// Whether the registration cap is reached, new registrations go on the waitlist then
+    ///
+    /// ```
+    /// use uc_helper_rust::database::tournaments::{RegistrationEntry, TournamentEntryBuilder, TournamentRestrictions};
+    /// use uc_helper_rust::tetrio::Rank;
+    ///
+    /// let restrictions = TournamentRestrictions::new(Rank::SPlus, 100f64, 10).with_max_participants(1);
+    /// let tournament = TournamentEntryBuilder::new("Capped", "UCC", restrictions).build().unwrap();
+    /// assert!(!tournament.is_full());
+    ///
+    /// let restrictions = TournamentRestrictions::new(Rank::SPlus, 100f64, 10).with_max_participants(1);
+    /// let tournament = TournamentEntryBuilder::new("Capped", "UCC", restrictions)
+    ///     .registration(RegistrationEntry::new("a"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(tournament.is_full());
+    /// ```
+    pub fn is_full(&self) -> bool {
+        match self.restrictions.max_participants {
+            Some(cap) => self.registered_players().len() >= cap as usize,
+            None => false,
+        }
+    }
+
+    /// Position of a player on the waitlist, starting at 1
+    pub fn waitlist_position(&self, tetrio_id: &str) -> Option<usize> {
+        self.waitlist
+            .iter()
+            .position(|entry| entry.tetrio_id == tetrio_id)
+            .map(|i| i + 1)
+    }
+
+    /// List of all registrations, including players who withdrew
+    pub fn all_registrations(&self) -> &[RegistrationEntry] {
+        &self.registered_players
+    }
+
+    /// A page of registrations and the total amount of registrations
+    ///
+    /// Computed in memory, see [`TournamentCollection::get_registrations_page()`] to let the database do this.
+    pub fn registrations_page(
+        &self,
+        offset: usize,
+        limit: usize,
+        order: RegOrder,
+    ) -> (Vec<RegistrationEntry>, usize) {
+        let mut sorted = self.registered_players();
+        sorted.sort_by_key(|reg| *reg.date);
+
+        if order == RegOrder::Seed {
+            let rating = |reg: &RegistrationEntry| {
+                self.player_stats_snapshot
+                    .iter()
+                    .find(|u| u._id == reg.tetrio_id)
+                    .map(|u| u.league.rating)
+            };
+
+            // stable sort, so equal and missing ratings stay in registration order
+            sorted.sort_by(|a, b| match (rating(a), rating(b)) {
+                (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            });
+        }
+
+        let total = sorted.len();
+        let page = sorted
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+
+        (page, total)
+    }
+
+    /// Which pieces of configuration are present
+    pub fn setup_status(&self) -> SetupStatus {
+        SetupStatus {
+            steps: vec![
+                (
+                    SetupStep::Restrictions,
+                    self.restrictions.validate().is_ok(),
+                ),
+                (SetupStep::Active, self.active),
+                (SetupStep::Snapshot, self.snapshot_at.is_some()),
+                (SetupStep::CheckIn, self.check_in_msg.is_some()),
+            ],
+        }
+    }
+
+    /// Range in which staff is allowed to backdate a registration
+    ///
+    /// Starts when the tournament was announced (the snapshot was taken, or the tournament was created
+    /// if there is no snapshot yet) minus a small grace period. Ends when registration closes plus the
+    /// same grace period, or now if no closing date is set.
+    pub fn backdate_bounds(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let grace = Duration::hours(BACKDATE_GRACE_HOURS);
+        let start = self.snapshot_at().unwrap_or_else(|| self.created_at());
+        let end = match self.dates.registration_closes_at {
+            Some(closes_at) => *closes_at + grace,
+            None => now,
+        };
+        (start - grace, end)
+    }
+
+    /// Verifies that a backdated registration date is within [`TournamentEntry::backdate_bounds()`]
+    pub fn check_backdate(&self, date: DateTime<Utc>, now: DateTime<Utc>) -> RegistrationResult {
+        let (earliest, latest) = self.backdate_bounds(now);
+        if date < earliest || date > latest {
+            return Err(RegistrationError::InvalidBackdate {
+                date,
+                earliest,
+                latest,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stat snapshot taken with [`TournamentCollection::add_snapshot()`], empty if none was taken yet
+    pub fn snapshot(&self) -> &[LeaderboardUser] {
+        &self.player_stats_snapshot
+    }
+
+    /// When the stat snapshot was taken
+    pub fn snapshot_at(&self) -> Option<DateTime<Utc>> {
+        self.snapshot_at.map(|ts| *ts)
+    }
+
+    /// Label of the stat snapshot in use, `None` if there is no snapshot yet
+    ///
+    /// Snapshots taken before snapshots had labels are called [`LEGACY_SNAPSHOT_LABEL`].
+    pub fn snapshot_label(&self) -> Option<&str> {
+        match (&self.snapshot_label, self.snapshot_at) {
+            (Some(label), _) => Some(label),
+            (None, Some(_)) => Some(LEGACY_SNAPSHOT_LABEL),
+            (None, None) => None,
+        }
+    }
+
+    /// Snapshot stats of a player, `None` if there is no snapshot yet or they weren't ranked back then
+    pub fn snapshot_of(&self, tetrio_id: &str) -> Option<&LeaderboardUser> {
+        self.player_stats_snapshot
+            .iter()
+            .find(|u| u._id == tetrio_id)
+    }
+
+    /// Evaluates the tournament restrictions for a player
+    ///
+    /// Uses snapshot data, so [`TournamentCollection::add_snapshot()`] must have been called at least
+    /// once before. Requests the player's news to find their highest rank.
+    ///
+    /// `account_created_at` is only used if the restrictions have a minimum account age, see
+    /// [`PlayerCollection::account_created_at()`].
+    pub async fn eligibility(
+        &self,
+        current_data: &LeaderboardUser,
+        account_created_at: Option<DateTime<Utc>>,
+    ) -> Result<EligibilityVerdict, RegistrationError> {
+        self.eligibility_with_peak(current_data, account_created_at, None)
+            .await
+    }
+
+    /// Same as [`TournamentEntry::eligibility()`], but also counts a peak rank known from elsewhere,
+    /// like [`PlayerEntry::peak_rank()`]
+    async fn eligibility_with_peak(
+        &self,
+        current_data: &LeaderboardUser,
+        account_created_at: Option<DateTime<Utc>>,
+        known_peak: Option<Rank>,
+    ) -> Result<EligibilityVerdict, RegistrationError> {
+        let snapshot_at = match self.snapshot_at {
+            None => return Err(RegistrationError::SnapshotMissing),
+            Some(ts) => *ts,
+        };
+
+        let snapshot_data = self.snapshot_of(&current_data._id);
+
+        let highest_rank = highest_rank(&current_data._id).await?.max(known_peak);
+
+        Ok(eligibility::evaluate(
+            &self.restrictions,
+            snapshot_data,
+            snapshot_at,
+            current_data,
+            highest_rank,
+            account_created_at,
+            Utc::now(),
+        ))
+    }
+
+    /// Same as [`TournamentEntry::eligibility()`], but evaluates a preview with current stats only
+    /// if there is no snapshot yet
+    ///
+    /// Meant for showing players where they stand, registrations always need the final verdict.
+    pub async fn eligibility_or_preview(
+        &self,
+        current_data: &LeaderboardUser,
+        account_created_at: Option<DateTime<Utc>>,
+    ) -> Result<EligibilityVerdict, RegistrationError> {
+        match eligibility::mode_for(self.snapshot_at()) {
+            EligibilityMode::Final => self.eligibility(current_data, account_created_at).await,
+            EligibilityMode::Preview => Ok(eligibility::evaluate_preview(
+                &self.restrictions,
+                current_data,
+                highest_rank(&current_data._id).await?,
+                account_created_at,
+                Utc::now(),
+            )),
+        }
+    }
+
+    /// Verify whether a player can participate in this tournament
+    ///
+    /// See [`TournamentEntry::eligibility()`].
+    /// Players who only fail the peak rank check get [`RegistrationError::HighestRankTooHigh`].
+    /// Players without recorded rank ups never count as having exceeded it.
+    async fn check_player_stats(
+        &self,
+        current_data: &LeaderboardUser,
+        account_created_at: Option<DateTime<Utc>>,
+        known_peak: Option<Rank>,
+    ) -> RegistrationResult {
+        // checked before requesting the news, check_eligibility() would fail anyway
+        if self.snapshot_at.is_none() {
+            return Err(RegistrationError::SnapshotMissing);
+        }
+        let highest_rank = highest_rank(&current_data._id).await?.max(known_peak);
+
+        check_eligibility(
+            &self.restrictions,
+            self.snapshot_of(&current_data._id),
+            self.snapshot_at(),
+            current_data,
+            highest_rank,
+            account_created_at,
+            Utc::now(),
+        )
+    }
+
+    /// Whether a player would pass the stat restrictions if they registered right now
+    ///
+    /// Runs the same checks as a registration, including the peak rank stored on the player entry,
+    /// see [`TournamentEntry::eligibility()`] for `account_created_at`. Qualifier exemptions and
+    /// staff bypasses aren't considered.
+    pub async fn check_player(
+        &self,
+        player: &PlayerEntry,
+        account_created_at: Option<DateTime<Utc>>,
+    ) -> RegistrationResult {
+        let current_data = player
+            .tetrio_data
+            .as_ref()
+            .ok_or_else(|| RegistrationError::MissingArgument("tetrio_data".to_string()))?;
+        self.check_player_stats(current_data, account_created_at, player.peak_rank())
+            .await
+    }
+
+    /// Whether a player may register with regard to the qualifier
+    ///
+    /// Tournaments without a parent tournament accept everyone.
+    ///
+    /// ```
+    /// use uc_helper_rust::database::tournaments::{RegistrationError, TournamentEntryBuilder, TournamentRestrictions};
+    /// use uc_helper_rust::tetrio::Rank;
+    ///
+    /// let restrictions = || TournamentRestrictions::new(Rank::SPlus, 100f64, 10);
+    /// let open = TournamentEntryBuilder::new("Open", "UCO", restrictions()).build().unwrap();
+    /// assert!(open.check_qualified("5e47696db7c60f23a497ee6c").is_ok());
+    ///
+    /// let finals = TournamentEntryBuilder::new("Finals", "UCF", restrictions())
+    ///     .qualifier("UCQ", vec!["5e47696db7c60f23a497ee6c".to_string()])
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(finals.check_qualified("5e47696db7c60f23a497ee6c").is_ok());
+    /// assert!(matches!(
+    ///     finals.check_qualified("5f5b6c1bd2a8e5a1f0b1c2d3"),
+    ///     Err(RegistrationError::NotQualified(parent)) if parent == "UCQ"
+    /// ));
+    /// ```
+    pub fn check_qualified(&self, tetrio_id: &str) -> RegistrationResult {
+        match &self.parent_tournament {
+            Some(parent) if !self.qualified_players.iter().any(|id| id == tetrio_id) => {
+                Err(RegistrationError::NotQualified(parent.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the stat restrictions are skipped for a registration, only for qualified players when enabled
+    fn skips_restrictions(&self, tetrio_id: &str) -> bool {
+        self.qualified_skip_restrictions
+            && self.parent_tournament.is_some()
+            && self.check_qualified(tetrio_id).is_ok()
+    }
+
+    /// Short description of the qualifier linkage, `None` if the tournament has no parent tournament
+    pub fn qualifier_summary(&self) -> Option<String> {
+        let parent = self.parent_tournament.as_ref()?;
+        Some(format!(
+            "Finals of {}, {} qualified players{}",
+            parent,
+            self.qualified_players.len(),
+            if self.qualified_skip_restrictions {
+                ", stat restrictions skipped"
+            } else {
+                ""
+            }
+        ))
+    }
+
+    /// Every register, withdraw and unregister event of a player, oldest first
+    ///
+    /// Registrations created before unregistering was recorded only show up if they're still there.
+    pub fn registration_history(&self, tetrio_id: &str) -> Vec<RegistrationEvent> {
+        let mut events = Vec::new();
+        for removed in self
+            .unregistered_players
+            .iter()
+            .filter(|removed| removed.registration.tetrio_id == tetrio_id)
+        {
+            events.extend(removed.registration.events());
+            events.push(RegistrationEvent {
+                at: *removed.unregistered_at,
+                kind: RegistrationEventKind::Unregistered(removed.by, removed.reason.clone()),
+            });
+        }
+        if let Some(registration) = self.registration(tetrio_id) {
+            events.extend(registration.events());
+        }
+        events.sort_by_key(|event| event.at);
+        events
+    }
+
+    /// Registration date of the first removed registration of a player, if they were unregistered before
+    pub fn previous_registration_date(&self, tetrio_id: &str) -> Option<DateTime<Utc>> {
+        self.unregistered_players
+            .iter()
+            .filter(|removed| removed.registration.tetrio_id == tetrio_id)
+            .map(|removed| *removed.registration.date)
+            .min()
+    }
+
+    /// Registration of a player, by Tetrio ID
+    pub fn registration(&self, tetrio_id: &str) -> Option<&RegistrationEntry> {
+        self.registered_players
+            .iter()
+            .find(|entry| entry.tetrio_id == tetrio_id)
+    }
+
+    /// Receipt code of a registration
+    ///
+    /// Entries from before receipts were stored get their code computed on the fly.
+    /// `None` if receipts are disabled.
+    pub fn receipt_of(&self, registration: &RegistrationEntry) -> Option<String> {
+        registration.receipt.clone().or_else(|| {
+            receipt::key().map(|key| {
+                key.generate(&self.shorthand, &registration.tetrio_id, *registration.date)
+            })
+        })
+    }
+
+    /// Finds the registration a receipt code belongs to
+    ///
+    /// Only checks the given Tetrio ID if one is provided. Always `None` if receipts are disabled.
+    pub fn find_receipt(&self, code: &str, tetrio_id: Option<&str>) -> Option<&RegistrationEntry> {
+        let key = receipt::key()?;
+        self.registered_players
+            .iter()
+            .filter(|entry| tetrio_id.map_or(true, |id| entry.tetrio_id == id))
+            .find(|entry| key.verify(code, &self.shorthand, &entry.tetrio_id, *entry.date))
+    }
+
+    /// Whether a user is registered to this tournament or not, withdrawn players don't count
+    pub fn player_is_registered(&self, player: &PlayerEntry) -> bool {
+        self.registered_players
+            .iter()
+            .any(|entry| entry.tetrio_id == player.tetrio_id && entry.is_active())
+    }
+
+    /// Generation of a player's recorded check-in, if there is one
+    pub fn recorded_check_in(&self, discord_id: u64) -> Option<u32> {
+        self.check_ins
+            .iter()
+            .find(|record| record.discord_id == discord_id)
+            .map(|record| record.generation)
+    }
+
+    /// Recorded check-ins of the current generation
+    ///
+    /// Unlike [`TournamentEntry::live_check_ins()`], this doesn't need the reactions on the check-in
+    /// message, so it still works if the message is gone.
+    pub fn checked_in(&self) -> Vec<&CheckInRecord> {
+        self.check_ins
+            .iter()
+            .filter(|record| record.generation == self.check_in_generation)
+            .collect()
+    }
+
+    /// Registered players as lines of Challonge's bulk add, `name,seed,misc` without a header
+    ///
+    /// The name is the current username (`players` should contain the registered players' entries),
+    /// misc is the linked Discord ID. Seeds follow the snapshot TR, players missing from the snapshot
+    /// are seeded last in registration order. With `checked_in_only`, players without a recorded
+    /// check-in are left out.
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use uc_helper_rust::fixtures::{self, FixtureConfig};
+    ///
+    /// let generated = fixtures::generate(&FixtureConfig { players: 200, registrations: 20, ..FixtureConfig::default() }, Utc::now());
+    /// let tournament = &generated.tournament;
+    ///
+    /// let all = tournament.challonge_participants(&generated.players, false);
+    /// let lines: Vec<&str> = all.lines().collect();
+    /// assert_eq!(lines.len(), tournament.registered_players().len());
+    /// assert!(lines[0].contains(",1,"));
+    ///
+    /// let checked_in = tournament.challonge_participants(&generated.players, true);
+    /// assert_eq!(checked_in.lines().count(), tournament.checked_in().len());
+    /// assert!(checked_in.lines().count() < lines.len());
+    /// ```
+    pub fn challonge_participants(&self, players: &[PlayerEntry], checked_in_only: bool) -> String {
+        let checked_in = self.checked_in();
+        let mut participants: Vec<(&RegistrationEntry, Option<&PlayerEntry>, Option<f64>)> = self
+            .registered_players()
+            .into_iter()
+            .map(|registration| {
+                let player = players
+                    .iter()
+                    .find(|p| p.tetrio_id == registration.tetrio_id);
+                let rating = self
+                    .player_stats_snapshot
+                    .iter()
+                    .find(|u| u._id == registration.tetrio_id)
+                    .map(|u| u.league.rating);
+                (registration, player, rating)
+            })
+            .filter(|(registration, player, _)| {
+                !checked_in_only
+                    || checked_in.iter().any(|record| match &record.tetrio_id {
+                        Some(tetrio_id) => tetrio_id == &registration.tetrio_id,
+                        None => player.and_then(|p| p.discord_id) == Some(record.discord_id),
+                    })
+            })
+            .collect();
+
+        participants.sort_by(|a, b| match (a.2, b.2) {
+            (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => (*a.0.date).cmp(&*b.0.date),
+        });
+
+        participants
+            .iter()
+            .enumerate()
+            .map(|(index, (registration, player, _))| {
+                let name = player
+                    .and_then(|p| p.tetrio_data.as_ref())
+                    .map(|data| data.username.clone())
+                    .or_else(|| registration.as_registered_username.clone())
+                    .unwrap_or_else(|| registration.tetrio_id.clone());
+                let discord_id = player
+                    .and_then(|p| p.discord_id)
+                    .map_or(String::new(), |id| id.to_string());
+                format!("{},{},{}", name, index + 1, discord_id)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Everyone who is checked in right now, given the users who reacted to the check-in message
+    ///
+    /// Reactions from before the last reset are left out, check-ins carried over from a reposted
+    /// check-in message and forced ones are added. Every player is listed once.
+    pub fn live_check_ins(&self, reacted: &[u64]) -> Vec<u64> {
+        let mut live: Vec<u64> = reacted
+            .iter()
+            .copied()
+            .filter(|id| !is_stale_check_in(self.recorded_check_in(*id), self.check_in_generation))
+            .collect();
+        for record in &self.check_ins {
+            if (record.carried_over || record.forced_by.is_some())
+                && record.generation == self.check_in_generation
+                && !live.contains(&record.discord_id)
+            {
+                live.push(record.discord_id);
+            }
+        }
+        live
+    }
+
+    /// Snapshot of the current check-ins and the generation that follows the reset
+    pub fn check_in_reset(&self, reacted: &[u64], now: DateTime<Utc>) -> (CheckInSnapshot, u32) {
+        let snapshot = CheckInSnapshot {
+            generation: self.check_in_generation,
+            discord_ids: self.live_check_ins(reacted),
+            reset_at: BsonDateTime::from(now),
+        };
+        (snapshot, self.check_in_generation + 1)
+    }
+
+    /// How many players of the last snapshot checked in again, and the size of the snapshot
+    ///
+    /// `None` if the check-in was never reset.
+    pub fn reconfirmed(&self, live: &[u64]) -> Option<(usize, usize)> {
+        self.previous_check_ins.last().map(|snapshot| {
+            let reconfirmed = snapshot
+                .discord_ids
+                .iter()
+                .filter(|id| live.contains(id))
+                .count();
+            (reconfirmed, snapshot.discord_ids.len())
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A piece of tournament configuration, see [`SetupStatus`]
+pub enum SetupStep {
+    /// Restrictions pass [`TournamentRestrictions::validate()`]
+    Restrictions,
+    /// Tournament is set as active
+    Active,
+    /// Stat snapshot was taken on announcement day
+    Snapshot,
+    /// Check-in message was posted
+    CheckIn,
+}
+
+#[derive(Debug, Clone)]
+/// Which pieces of a tournament's configuration are present
+pub struct SetupStatus {
+    /// Every step in the order they're usually done, with whether it's done
+    pub steps: Vec<(SetupStep, bool)>,
+}
+
+impl SetupStatus {
+    /// Whether every step is done
+    pub fn is_complete(&self) -> bool {
+        self.steps.iter().all(|(_, done)| *done)
+    }
+
+    /// Steps that are not done yet
+    pub fn missing(&self) -> Vec<SetupStep> {
+        self.steps
+            .iter()
+            .filter(|(_, done)| !done)
+            .map(|(step, _)| *step)
+            .collect()
+    }
+}
+
+/// Builder for [`TournamentEntry`], for when more than the basic information is known upfront
+///
+/// # Example
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use uc_helper_rust::database::tournaments::{RegistrationEntry, TournamentEntryBuilder, TournamentRestrictions};
+/// use uc_helper_rust::tetrio::Rank;
+///
+/// let tournament = TournamentEntryBuilder::new("Test Tournament 1", "TT1", TournamentRestrictions::new(Rank::SPlus, 100f64, 10))
+///     .created_at(Utc.ymd(2021, 3, 1).and_hms(0, 0, 0))
+///     .registration(RegistrationEntry::with_date("5e47696db7c60f23a497ee6c", Utc.ymd(2021, 3, 2).and_hms(12, 0, 0)))
+///     .active(true)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(tournament.registered_players().len(), 1);
+/// ```
+pub struct TournamentEntryBuilder {
+    entry: TournamentEntry,
+}
+
+impl TournamentEntryBuilder {
+    /// Starts building a tournament entry with the required information
+    pub fn new(
+        name: &str,
+        shorthand: &str,
+        restrictions: TournamentRestrictions,
+    ) -> TournamentEntryBuilder {
+        TournamentEntryBuilder {
+            entry: TournamentEntry::new(name, shorthand, restrictions),
+        }
+    }
+
+    /// Sets when the tournament entry was created (defaults to now)
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> TournamentEntryBuilder {
+        self.entry.created_at = BsonDateTime::from(created_at);
+        self
+    }
+
+    /// Sets whether the tournament is active (defaults to `false`)
+    pub fn active(mut self, active: bool) -> TournamentEntryBuilder {
+        self.entry.active = active;
+        self
+    }
+
+    /// Sets the stat snapshot and when it was taken (defaults to no snapshot)
+    pub fn snapshot(
+        mut self,
+        users: Vec<LeaderboardUser>,
+        taken_at: DateTime<Utc>,
+    ) -> TournamentEntryBuilder {
+        self.entry.player_stats_snapshot = users;
+        self.entry.snapshot_at = Some(BsonDateTime::from(taken_at));
+        self
+    }
+
+    /// Adds a registration
+    pub fn registration(mut self, registration: RegistrationEntry) -> TournamentEntryBuilder {
+        self.entry.registered_players.push(registration);
+        self
+    }
+
+    /// Adds multiple registrations
+    pub fn registrations(
+        mut self,
+        registrations: Vec<RegistrationEntry>,
+    ) -> TournamentEntryBuilder {
+        self.entry.registered_players.extend(registrations);
+        self
+    }
+
+    /// Sets the check-in message and the channel it's in (defaults to none)
+    pub fn check_in_msg(mut self, channel_id: u64, message_id: u64) -> TournamentEntryBuilder {
+        self.entry.check_in_channel = Some(channel_id);
+        self.entry.check_in_msg = Some(message_id);
+        self
+    }
+
+    /// Sets the recorded check-ins (defaults to none)
+    pub fn check_ins(mut self, check_ins: Vec<CheckInRecord>) -> TournamentEntryBuilder {
+        self.entry.check_ins = check_ins;
+        self
+    }
+
+    /// Links the tournament to its qualifier and sets the qualified players (defaults to no qualifier)
+    pub fn qualifier(mut self, parent: &str, qualified: Vec<String>) -> TournamentEntryBuilder {
+        self.entry.parent_tournament = Some(parent.to_string());
+        self.entry.qualified_players = qualified;
+        self
+    }
+
+    /// Sets the lifecycle dates (defaults to none)
+    pub fn dates(mut self, dates: TournamentDates) -> TournamentEntryBuilder {
+        self.entry.dates = dates;
+        self
+    }
+
+    /// Validates the restrictions and dates and returns the finished entry
+    pub fn build(self) -> DatabaseResult<TournamentEntry> {
+        self.entry.restrictions.validate()?;
+        self.entry.dates.validate()?;
+        Ok(self.entry)
+    }
+}
 
 /// Main wrapper for a MongoDB collection to manage tournaments
 pub struct TournamentCollection {
     collection: Collection,
+    snapshots: Collection,
+}
+
+/// Name of the unique index on the tournament shorthand, which compares shorthands regardless of case
+pub const SHORTHAND_INDEX: &str = "shorthand_unique";
+
+/// Allowed length of a tournament shorthand, in characters
+pub const SHORTHAND_LENGTH: std::ops::RangeInclusive<usize> = 2..=10;
+
+/// Longest allowed tournament name, in characters
+pub const MAX_NAME_LENGTH: usize = 100;
+
+/// Trims a tournament shorthand and checks that it's 2 to 10 alphanumeric characters
+///
+/// ```
+/// use uc_helper_rust::database::tournaments::normalize_shorthand;
+///
+/// assert_eq!(normalize_shorthand(" UC12 ").unwrap(), "UC12");
+/// assert!(normalize_shorthand("UC").is_ok());
+/// assert!(normalize_shorthand("U").is_err());
+/// assert!(normalize_shorthand("UNDERDOGS12").is_err());
+/// assert!(normalize_shorthand("UC 12").is_err());
+/// assert!(normalize_shorthand("UC-12").is_err());
+/// assert!(normalize_shorthand("   ").is_err());
+/// ```
+pub fn normalize_shorthand(shorthand: &str) -> DatabaseResult<String> {
+    let shorthand = shorthand.trim();
+    if !SHORTHAND_LENGTH.contains(&shorthand.chars().count()) {
+        return Err(DatabaseError::InvalidInput(format!(
+            "The shorthand has to be {} to {} characters long (was `{}`)",
+            SHORTHAND_LENGTH.start(),
+            SHORTHAND_LENGTH.end(),
+            shorthand
+        )));
+    }
+    if !shorthand.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(DatabaseError::InvalidInput(format!(
+            "The shorthand can only contain letters and digits (was `{}`)",
+            shorthand
+        )));
+    }
+    Ok(shorthand.to_string())
+}
+
+/// Trims a tournament name and checks that it's neither empty nor longer than [`MAX_NAME_LENGTH`]
+///
+/// ```
+/// use uc_helper_rust::database::tournaments::normalize_tournament_name;
+///
+/// assert_eq!(normalize_tournament_name(" Underdogs Cup 12\n").unwrap(), "Underdogs Cup 12");
+/// assert!(normalize_tournament_name("").is_err());
+/// assert!(normalize_tournament_name("  ").is_err());
+/// assert!(normalize_tournament_name(&"a".repeat(100)).is_ok());
+/// assert!(normalize_tournament_name(&"a".repeat(101)).is_err());
+/// ```
+pub fn normalize_tournament_name(name: &str) -> DatabaseResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DatabaseError::InvalidInput(
+            "The tournament name can't be empty".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(DatabaseError::InvalidInput(format!(
+            "The tournament name can be at most {} characters long",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Compares strings regardless of case, for looking up shorthands
+fn case_insensitive() -> Collation {
+    // strength 2 compares letters and accents, but not case
+    Collation::builder().locale("en").strength(2).build()
 }
 
-impl TournamentCollection {
-    /// Constructs the wrapper struct for the MongoDB collection
-    ///
-    /// If the collection does not exist, then it will be created implicitly when a new entry is added.
-    pub fn new(database: &Database) -> TournamentCollection {
-        TournamentCollection {
-            collection: database.collection(COLLECTION_NAME),
+/// Filter expression for a tournament that still has room below the cap
+///
+/// Withdrawn and disqualified entries stay in the array, so only active ones are counted.
+fn below_cap(cap: u32) -> Document {
+    doc! {"$lt": [
+        {"$size": {"$filter": {
+            "input": "$registered_players",
+            "cond": {"$not": [{"$in": ["$$this.status.state", ["withdrawn", "disqualified"]]}]}
+        }}},
+        cap as i64
+    ]}
+}
+
+/// Name of the unique index on the tournament and label of a stored snapshot
+pub const SNAPSHOT_LABEL_INDEX: &str = "snapshot_label_unique";
+
+impl TournamentCollection {
+    /// Constructs the wrapper struct for the MongoDB collection
+    ///
+    /// If the collection does not exist, then it will be created implicitly when a new entry is added.
+    /// Makes sure shorthands and snapshot labels are unique on the database side, see
+    /// [`SHORTHAND_INDEX`] and [`SNAPSHOT_LABEL_INDEX`].
+    pub async fn new(database: &Database) -> TournamentCollection {
+        crate::database::ensure_unique_index(
+            database,
+            COLLECTION_NAME,
+            SHORTHAND_INDEX,
+            doc! {"shorthand": 1},
+            None,
+            Some(case_insensitive()),
+        )
+        .await;
+        crate::database::ensure_unique_index(
+            database,
+            SNAPSHOT_COLLECTION_NAME,
+            SNAPSHOT_LABEL_INDEX,
+            doc! {"tournament": 1, "label": 1},
+            None,
+            None,
+        )
+        .await;
+
+        TournamentCollection {
+            collection: database.collection(COLLECTION_NAME),
+            snapshots: database.collection(SNAPSHOT_COLLECTION_NAME),
+        }
+    }
+
+    /// Create a tournament entry with specified information
+    ///
+    /// The name and shorthand are trimmed and validated first, see [`normalize_tournament_name()`]
+    /// and [`normalize_shorthand()`]. Shorthands differing only in case count as duplicates.
+    pub async fn create_tournament(
+        &self,
+        name: &str,
+        shorthand: &str,
+        restrictions: TournamentRestrictions,
+    ) -> DatabaseResult<TournamentEntry> {
+        let name = normalize_tournament_name(name)?;
+        let shorthand = normalize_shorthand(shorthand)?;
+        tracing::info!("Creating tournament {} ({})", name, shorthand);
+        let entry = TournamentEntryBuilder::new(&name, &shorthand, restrictions).build()?;
+
+        if self.get_tournament(&name).await?.is_some()
+            || self.get_tournament(&shorthand).await?.is_some()
+        {
+            return Err(DatabaseError::DuplicateTournamentEntry);
+        }
+
+        // Another create could have slipped in since the check, the unique index catches that
+        match self.insert_tournament(&entry).await {
+            Err(DatabaseError::Mongo(err)) if violated_unique_index(&err).is_some() => {
+                Err(DatabaseError::DuplicateTournamentEntry)
+            }
+            result => result.map(|_| entry),
+        }
+    }
+
+    /// Inserts a finished tournament entry as it is
+    ///
+    /// Used for generated data (see [`crate::fixtures`]), you're probably looking for
+    /// [`TournamentCollection::create_tournament()`]. Doesn't check for duplicates.
+    pub async fn insert_tournament(&self, entry: &TournamentEntry) -> DatabaseResult<()> {
+        match self
+            .collection
+            .insert_one(
+                bson::to_document(entry).expect("could not convert to document"),
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    /// Wipes all entries from the collection
+    ///
+    /// Meant for development databases, see [`crate::fixtures`]
+    pub async fn remove_all(&self) -> DatabaseResult<()> {
+        tracing::info!("Deleting all tournaments");
+        // Only the documents, so the label index stays
+        self.snapshots
+            .delete_many(doc! {}, None)
+            .await
+            .map_err(mongo_error)?;
+        match self.collection.drop(None).await {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    /// Same as [`TournamentCollection::create_tournament()`], but returns the existing entry if a
+    /// tournament with the same name and shorthand already exists
+    ///
+    /// Used to continue setting up a tournament if a previous attempt failed after the insert.
+    pub async fn create_or_resume_tournament(
+        &self,
+        name: &str,
+        shorthand: &str,
+        restrictions: TournamentRestrictions,
+    ) -> DatabaseResult<TournamentEntry> {
+        let (name, shorthand) = (name.trim(), shorthand.trim());
+        match self.get_tournament(shorthand).await? {
+            Some(existing)
+                if existing.name == name && existing.shorthand.eq_ignore_ascii_case(shorthand) =>
+            {
+                tracing::info!("Resuming setup of tournament {} ({})", name, shorthand);
+                Ok(existing)
+            }
+            Some(_) => Err(DatabaseError::DuplicateTournamentEntry),
+            None => self.create_tournament(name, shorthand, restrictions).await,
+        }
+    }
+
+    /// Which pieces of configuration a tournament has, see [`TournamentEntry::setup_status()`]
+    pub async fn setup_status(&self, name: &str) -> DatabaseResult<SetupStatus> {
+        match self.get_tournament(name).await? {
+            Some(tournament) => Ok(tournament.setup_status()),
+            None => Err(DatabaseError::NotFound),
+        }
+    }
+
+    /// Gets a tournament by name or shorthand, regardless of case and surrounding whitespace
+    pub async fn get_tournament(&self, name: &str) -> DatabaseResult<Option<TournamentEntry>> {
+        let name = name.trim();
+        // same collation as the shorthand index, so the index can be used for the shorthand
+        let options = FindOneOptions::builder()
+            .collation(case_insensitive())
+            .build();
+        match self
+            .collection
+            .find_one(doc! {"$or":[{"name": name}, {"shorthand": name}]}, options)
+            .await
+            .map_err(mongo_error)?
+        {
+            Some(document) => crate::database::parse_entry(document).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stored shorthand of a tournament, found by name or shorthand regardless of case
+    ///
+    /// Updates don't use the case insensitive collation, so writes look the tournament up first
+    /// and filter on the exact shorthand.
+    async fn stored_shorthand(&self, name: &str) -> DatabaseResult<String> {
+        self.get_tournament(name)
+            .await?
+            .map(|tournament| tournament.shorthand)
+            .ok_or(DatabaseError::NotFound)
+    }
+
+    /// A page of registrations of a tournament and the total amount of registrations
+    ///
+    /// The database does the sorting and slicing, so the rest of the tournament document never gets loaded.
+    /// Falls back to [`TournamentEntry::registrations_page()`] if the aggregation fails, for example
+    /// because the server version doesn't support it.
+    pub async fn get_registrations_page(
+        &self,
+        name: &str,
+        offset: usize,
+        limit: usize,
+        order: RegOrder,
+    ) -> DatabaseResult<(Vec<RegistrationEntry>, usize)> {
+        let name = name.trim();
+        let sort = match order {
+            RegOrder::RegistrationDate => doc! {"registered_players.date": 1},
+            RegOrder::Seed => doc! {"seed_rating": -1, "registered_players.date": 1},
+        };
+
+        let pipeline = vec![
+            doc! {"$match": {"$or":[{"name": name}, {"shorthand": name}]}},
+            doc! {"$unwind": "$registered_players"},
+            doc! {"$match": {"registered_players.status.state": {"$nin": ["withdrawn", "disqualified"]}}},
+            doc! {"$addFields": {"seed_rating": {"$let": {
+                "vars": {"snap": {"$arrayElemAt": [{"$filter": {
+                    "input": "$player_stats_snapshot",
+                    "cond": {"$eq": ["$$this._id", "$registered_players.tetrio_id"]}
+                }}, 0]}},
+                "in": "$$snap.league.rating"
+            }}}},
+            doc! {"$sort": sort},
+            doc! {"$facet": {
+                "entries": [
+                    {"$skip": offset as i64},
+                    {"$limit": limit.max(1) as i64},
+                    {"$replaceRoot": {"newRoot": "$registered_players"}}
+                ],
+                "total": [{"$count": "count"}]
+            }},
+        ];
+
+        let options = AggregateOptions::builder()
+            .collation(case_insensitive())
+            .build();
+        let result = match self.collection.aggregate(pipeline, options).await {
+            Ok(mut cursor) => cursor.try_next().await.ok().flatten(),
+            Err(_) => None,
+        };
+
+        let facet = match result {
+            Some(facet) => facet,
+            None => {
+                tracing::warn!(
+                    "Registration page aggregation failed, loading {} in memory",
+                    name
+                );
+                return match self.get_tournament(name).await? {
+                    Some(tournament) => Ok(tournament.registrations_page(offset, limit, order)),
+                    None => Err(DatabaseError::NotFound),
+                };
+            }
+        };
+
+        let total = facet
+            .get_array("total")
+            .ok()
+            .and_then(|total| total.first())
+            .and_then(|count| count.as_document())
+            .and_then(|count| count.get_i32("count").ok())
+            .unwrap_or(0) as usize;
+
+        let entries = match facet.get_array("entries") {
+            Ok(entries) if limit > 0 => entries
+                .iter()
+                .filter_map(|entry| entry.as_document())
+                .map(|entry| crate::database::parse_entry(entry.clone()))
+                .collect::<DatabaseResult<_>>()?,
+            _ => Vec::new(),
+        };
+
+        Ok((entries, total))
+    }
+
+    /// Registers a player to an active tournament
+    ///
+    /// Will call [`PlayerCollection::link()`] internally, so the player is always linked.
+    /// If no username is given, then it will try to use the linked player.
+    /// `tournament` is the name or shorthand of the tournament, only needed if more than one is
+    /// active (see [`TournamentCollection::resolve_active()`]).
+    /// `source` is the message of the registration command, if there is one.
+    ///
+    /// Returns the registered player and the tournament they were registered to, including the new registration.
+    /// If the tournament is full (see [`TournamentEntry::is_full()`]), the player is put on the waitlist
+    /// instead, unless restrictions are bypassed.
+    pub async fn register_to_active(
+        &self,
+        players: &PlayerCollection,
+        tournament: Option<&str>,
+        tetrio_id: Option<&str>,
+        discord_id: u64,
+        bypass_restrictions: bool,
+        source: Option<MessageRef>,
+    ) -> Result<(PlayerEntry, TournamentEntry, RegistrationOutcome), RegistrationError> {
+        self.register_to_active_with_date(
+            players,
+            tournament,
+            tetrio_id,
+            discord_id,
+            bypass_restrictions,
+            None,
+            source,
+        )
+        .await
+    }
+
+    /// Registers a player to the active tournament with an explicit registration date
+    ///
+    /// Same as [`TournamentCollection::register_to_active()`], but if `date` is `Some`, it's used as
+    /// the registration date instead of the current time. The date has to be within
+    /// [`TournamentEntry::backdate_bounds()`]. This is meant for staff only, for example to register
+    /// players who couldn't register while the bot was down.
+    ///
+    /// Every attempt is counted in [`crate::metrics`], failures by [`RegistrationError::kind()`].
+    pub async fn register_to_active_with_date(
+        &self,
+        players: &PlayerCollection,
+        tournament: Option<&str>,
+        tetrio_id: Option<&str>,
+        discord_id: u64,
+        bypass_restrictions: bool,
+        date: Option<DateTime<Utc>>,
+        source: Option<MessageRef>,
+    ) -> Result<(PlayerEntry, TournamentEntry, RegistrationOutcome), RegistrationError> {
+        let result = self
+            .try_register(
+                players,
+                tournament,
+                tetrio_id,
+                discord_id,
+                bypass_restrictions,
+                date,
+                source,
+            )
+            .await;
+        crate::metrics::global().record_registration(match &result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err.kind()),
+        });
+        result
+    }
+
+    /// Does the work of [`TournamentCollection::register_to_active_with_date()`]
+    async fn try_register(
+        &self,
+        players: &PlayerCollection,
+        tournament: Option<&str>,
+        tetrio_id: Option<&str>,
+        discord_id: u64,
+        bypass_restrictions: bool,
+        date: Option<DateTime<Utc>>,
+        source: Option<MessageRef>,
+    ) -> Result<(PlayerEntry, TournamentEntry, RegistrationOutcome), RegistrationError> {
+        let mut tournament = self.resolve_active(tournament).await?;
+
+        if let Some(date) = date {
+            tournament.check_backdate(date, Utc::now())?;
+        }
+
+        // Use the linked player if no username is provided or it's the linked one
+        // Link takes care of the case where the tetrio id is linked to someone else
+        let linked = players.get_player_by_discord(discord_id).await?;
+        let mut player = match (tetrio_id, linked) {
+            (None, Some(linked_entry)) => linked_entry,
+            (None, None) => {
+                return Err(RegistrationError::MissingArgument("username".to_string()));
+            }
+            (Some(id), Some(linked_entry)) if linked_entry.is_account(id) => linked_entry,
+            (Some(id), Some(linked_entry)) => {
+                // relinking wouldn't withdraw the linked account, it would stay registered as well
+                if tournament
+                    .registration(&linked_entry.tetrio_id)
+                    .map_or(false, |entry| entry.is_active())
+                {
+                    return Err(RegistrationError::AlreadyRegistered);
+                }
+                if let Some(position) = tournament.waitlist_position(&linked_entry.tetrio_id) {
+                    return Err(RegistrationError::AlreadyWaitlisted(position));
+                }
+                return Err(RegistrationError::LinkedToDifferentAccount {
+                    linked: linked_entry
+                        .tetrio_data
+                        .map_or(linked_entry.tetrio_id, |data| data.username),
+                    requested: id.to_string(),
+                });
+            }
+            (Some(id), None) => players.link(discord_id, id).await?,
+        };
+
+        // players missing from the snapshot are checked with current stats, the stored ones may be stale
+        if !bypass_restrictions
+            && tournament.restrictions.allow_unranked
+            && tournament.snapshot_at.is_some()
+            && tournament.snapshot_of(&player.tetrio_id).is_none()
+        {
+            player = players.update_player(&player.tetrio_id, false).await?;
+        }
+
+        if player.status != AccountStatus::Active {
+            return Err(RegistrationError::AccountUnavailable(player.status));
+        }
+        let stats = player
+            .tetrio_data
+            .as_ref()
+            .ok_or(RegistrationError::AccountUnavailable(
+                AccountStatus::NotFound,
+            ))?;
+        tracing::info!(
+            "Registering {} to tournament {} ({})",
+            &stats.username,
+            tournament.name,
+            source.map_or("no source".to_string(), |source| source.to_string())
+        );
+
+        tournament.check_qualified(&player.tetrio_id)?;
+
+        // staff can register outside of the window, backdated registrations are checked at their date
+        if !bypass_restrictions {
+            tournament
+                .dates
+                .check_registration(date.unwrap_or_else(Utc::now))?;
+        }
+
+        // throws an error if invalid
+        if !bypass_restrictions && !tournament.skips_restrictions(&player.tetrio_id) {
+            let account_created_at = match tournament.restrictions.min_account_age_days {
+                Some(_) => players.account_created_at(&player).await?,
+                None => None,
+            };
+            tournament
+                .check_player_stats(stats, account_created_at, player.peak_rank())
+                .await?;
+        }
+
+        let registered_as = stats.username.clone();
+        let games_played = stats.league.gamesplayed;
+        let tetrio_id = player.tetrio_id;
+        if let Some(position) = tournament.waitlist_position(&tetrio_id) {
+            return Err(RegistrationError::AlreadyWaitlisted(position));
+        }
+        let waitlisted = !bypass_restrictions && tournament.is_full();
+        match tournament.registration(&tetrio_id) {
+            Some(entry) if entry.is_active() => return Err(RegistrationError::AlreadyRegistered),
+            Some(RegistrationEntry {
+                status: RegistrationStatus::Disqualified { .. },
+                ..
+            }) => return Err(RegistrationError::Disqualified),
+            // withdrawn players go on the waitlist too, promoting them reactivates the registration
+            Some(_) if !waitlisted => {
+                self.reactivate(&mut tournament, &tetrio_id, source).await?;
+                return Ok((
+                    players.get_player_by_discord(discord_id).await?.unwrap(),
+                    tournament,
+                    RegistrationOutcome::Registered,
+                ));
+            }
+            _ => {}
+        }
+
+        let mut reg_entry = match (date, tournament.previous_registration_date(&tetrio_id)) {
+            (Some(date), _) => {
+                tracing::info!("Backdating registration of {} to {}", tetrio_id, date);
+                RegistrationEntry::backdated(&tetrio_id, date)
+            }
+            // players who were unregistered keep their original spot
+            (None, Some(original)) => {
+                tracing::info!(
+                    "Restoring registration date of {} to {}",
+                    tetrio_id,
+                    original
+                );
+                RegistrationEntry::backdated(&tetrio_id, original)
+            }
+            (None, None) => RegistrationEntry::new(&tetrio_id),
+        }
+        .registered_as(&registered_as)
+        .with_games_played(games_played)
+        .sourced_from(source);
+        if let Some(key) = receipt::key() {
+            reg_entry = reg_entry.with_receipt(&tournament.shorthand, key);
+        }
+        let reg_document = bson::to_document(&reg_entry).expect("bad document");
+
+        let mut full = waitlisted;
+        if !full {
+            // is_full() only saw the fetched copy, the filter keeps concurrent registrations below the cap
+            let mut filter = doc! {"shorthand": &tournament.shorthand};
+            if let (false, Some(cap)) = (
+                bypass_restrictions,
+                tournament.restrictions.max_participants,
+            ) {
+                filter.insert("$expr", below_cap(cap));
+            }
+            let result = self
+                .collection
+                .update_one(
+                    filter,
+                    doc! {"$push": {"registered_players": reg_document.clone()}},
+                    None,
+                )
+                .await
+                .map_err(mongo_error)?;
+            if result.modified_count == 0 {
+                full = true;
+                tournament = self
+                    .get_tournament(&tournament.shorthand)
+                    .await?
+                    .ok_or(DatabaseError::NotFound)?;
+            }
+        }
+
+        let outcome = if full {
+            tracing::info!(
+                "{} is full, putting {} on the waitlist",
+                tournament.name,
+                tetrio_id
+            );
+            self.collection
+                .update_one(
+                    doc! {"shorthand": &tournament.shorthand},
+                    doc! {"$push": {"waitlist": reg_document}},
+                    None,
+                )
+                .await
+                .map_err(mongo_error)?;
+            RegistrationOutcome::Waitlisted(tournament.waitlist.len() + 1)
+        } else {
+            RegistrationOutcome::Registered
+        };
+        match outcome {
+            RegistrationOutcome::Registered => tournament.registered_players.push(reg_entry),
+            RegistrationOutcome::Waitlisted(_) => tournament.waitlist.push(reg_entry),
+        }
+
+        Ok((
+            players.get_player_by_discord(discord_id).await?.unwrap(),
+            tournament,
+            outcome,
+        ))
+    }
+
+    /// Moves players from the waitlist into the registrations until the tournament is full again
+    ///
+    /// Players are promoted in the order they joined the waitlist. Players who withdrew before get
+    /// their original registration back, disqualified players are dropped from the waitlist.
+    /// Returns the updated tournament and the promoted registrations.
+    pub async fn promote_waitlisted(
+        &self,
+        name: &str,
+    ) -> DatabaseResult<(TournamentEntry, Vec<RegistrationEntry>)> {
+        let mut tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        let mut promoted = Vec::new();
+
+        while !tournament.is_full() && !tournament.waitlist.is_empty() {
+            let entry = tournament.waitlist.remove(0);
+            let existing = tournament.registration(&entry.tetrio_id).cloned();
+
+            let mut update = doc! {"$pull": {"waitlist": {"tetrio_id": &entry.tetrio_id}}};
+            if existing.is_none() {
+                let reg_document = bson::to_document(&entry).expect("bad document");
+                update.insert("$push", doc! {"registered_players": reg_document});
+            }
+            self.collection
+                .update_one(doc! {"shorthand": &tournament.shorthand}, update, None)
+                .await
+                .map_err(mongo_error)?;
+
+            match existing {
+                Some(RegistrationEntry {
+                    status: RegistrationStatus::Disqualified { .. },
+                    ..
+                }) => {
+                    tracing::info!(
+                        "Dropping disqualified {} from the waitlist of {}",
+                        entry.tetrio_id,
+                        tournament.name
+                    );
+                    continue;
+                }
+                Some(_) => {
+                    self.reactivate(&mut tournament, &entry.tetrio_id, entry.source)
+                        .await
+                        .map_err(|err| match err {
+                            RegistrationError::DatabaseError(err) => err,
+                            err => DatabaseError::InvalidInput(err.to_string()),
+                        })?;
+                }
+                None => tournament.registered_players.push(entry.clone()),
+            }
+
+            tracing::info!(
+                "Promoted {} from the waitlist of {}",
+                entry.tetrio_id,
+                tournament.name
+            );
+            promoted.push(entry);
+        }
+
+        Ok((tournament, promoted))
+    }
+
+    /// Sets the most players that can be registered at once, `None` removes the cap
+    ///
+    /// Lowering the cap doesn't remove anyone, but nobody is promoted from the waitlist until
+    /// enough players left. Raising it doesn't promote anyone on its own, see
+    /// [`TournamentCollection::promote_waitlisted()`].
+    pub async fn set_max_participants(
+        &self,
+        name: &str,
+        max_participants: Option<u32>,
+    ) -> DatabaseResult<TournamentEntry> {
+        if max_participants == Some(0) {
+            return Err(DatabaseError::InvalidInput(
+                "The cap has to be at least 1".to_string(),
+            ));
+        }
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+
+        tracing::info!(
+            "Setting the registration cap of {} to {:?}",
+            tournament.shorthand,
+            max_participants
+        );
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$set": {"restrictions.max_participants": max_participants}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(self.get_tournament(&tournament.shorthand).await?.unwrap()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    /// Removes a player from the waitlist of a tournament, returns whether they were on it
+    async fn leave_waitlist(
+        &self,
+        tournament: &mut TournamentEntry,
+        tetrio_id: &str,
+    ) -> DatabaseResult<bool> {
+        if tournament.waitlist_position(tetrio_id).is_none() {
+            return Ok(false);
+        }
+        tracing::info!(
+            "Removing {} from the waitlist of {}",
+            tetrio_id,
+            tournament.name
+        );
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$pull": {"waitlist": {"tetrio_id": tetrio_id}}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        tournament
+            .waitlist
+            .retain(|entry| entry.tetrio_id != tetrio_id);
+        Ok(true)
+    }
+
+    /// Reactivates the withdrawn registration of a player
+    ///
+    /// The original registration date is kept, so the player gets their spot back.
+    /// The source is replaced with the message that reactivated the registration.
+    async fn reactivate(
+        &self,
+        tournament: &mut TournamentEntry,
+        tetrio_id: &str,
+        source: Option<MessageRef>,
+    ) -> RegistrationResult {
+        tracing::info!(
+            "Reactivating registration of {} in tournament {}",
+            tetrio_id,
+            tournament.name
+        );
+
+        let now = Utc::now();
+        let active = bson::to_bson(&RegistrationStatus::Active).expect("could not convert to bson");
+        let source_bson = bson::to_bson(&source).expect("could not convert to bson");
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand, "registered_players.tetrio_id": tetrio_id},
+                doc! {"$set": {
+                    "registered_players.$.status": active,
+                    "registered_players.$.reactivated_at": BsonDateTime::from(now),
+                    "registered_players.$.source": source_bson
+                }},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if let Some(entry) = tournament
+            .registered_players
+            .iter_mut()
+            .find(|entry| entry.tetrio_id == tetrio_id)
+        {
+            entry.status = RegistrationStatus::Active;
+            entry.reactivated_at = Some(BsonDateTime::from(now));
+            entry.source = source;
+        }
+        Ok(())
+    }
+
+    /// Withdraws a player from the current tournament, keeping the registration
+    ///
+    /// Function to be used internally, you're probably looking for
+    /// [`withdraw_by_tetrio()`] or [`withdraw_by_discord()`]
+    async fn withdraw(
+        &self,
+        player: &PlayerEntry,
+        mut tournament: TournamentEntry,
+        reason: Option<&str>,
+    ) -> Result<TournamentEntry, RegistrationError> {
+        if !tournament.player_is_registered(player) {
+            return match self
+                .leave_waitlist(&mut tournament, &player.tetrio_id)
+                .await?
+            {
+                true => Ok(tournament),
+                false => Err(RegistrationError::NotRegistered),
+            };
+        }
+
+        tracing::info!(
+            "Withdrawing {} from tournament {}",
+            &player.tetrio_id,
+            tournament.name
+        );
+
+        let status = bson::to_bson(&RegistrationStatus::Withdrawn {
+            at: BsonDateTime::from(Utc::now()),
+            reason: reason.map(|r| r.to_string()),
+        })
+        .expect("could not convert to bson");
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand, "registered_players.tetrio_id": &player.tetrio_id},
+                doc! {"$set": {"registered_players.$.status": status}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        Ok(tournament)
+    }
+
+    /// Withdraws every registered player of the active tournament whose current rank is above the max rank
+    ///
+    /// Works on the stored player data, so the registered players should be updated first, see
+    /// [`PlayerCollection::update_registered()`]. Candidates are confirmed with the full eligibility
+    /// check, so a missing snapshot never removes anyone. Registrations are withdrawn with
+    /// [`OVERRANKED_REASON`] instead of being deleted, so staff can still see them.
+    ///
+    /// Returns the withdrawn players.
+    pub async fn purge_ineligible(
+        &self,
+        players: &PlayerCollection,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        let tournament = self.get_active().await?.ok_or(DatabaseError::NotFound)?;
+        let ids: Vec<&str> = tournament
+            .registered_players()
+            .iter()
+            .map(|reg| reg.tetrio_id.as_str())
+            .collect();
+        let registered = players
+            .get_players(doc! {"tetrio_id": {"$in": ids}}, None)
+            .await?;
+
+        let mut removed = Vec::new();
+        for player in registered {
+            let current = match &player.tetrio_data {
+                Some(current) => current,
+                None => continue,
+            };
+            // only players above the cap right now are candidates, that doesn't need the news posts
+            if Rank::from_str(&current.league.rank).unwrap() <= tournament.restrictions.max_rank {
+                continue;
+            }
+
+            let verdict = match tournament.eligibility(current, None).await {
+                Ok(verdict) => verdict,
+                Err(RegistrationError::SnapshotMissing) => continue,
+                Err(RegistrationError::DatabaseError(err)) => return Err(err),
+                Err(err) => {
+                    tracing::warn!("Could not check {}: {}", player.tetrio_id, err);
+                    continue;
+                }
+            };
+            if verdict
+                .failures()
+                .any(|check| check.criterion == Criterion::CurrentRank)
+            {
+                let tournament = self.get_tournament(&tournament.shorthand).await?.unwrap();
+                match self
+                    .withdraw(&player, tournament, Some(OVERRANKED_REASON))
+                    .await
+                {
+                    Ok(_) => removed.push(player),
+                    Err(RegistrationError::DatabaseError(err)) => return Err(err),
+                    Err(err) => tracing::warn!("Could not withdraw {}: {}", player.tetrio_id, err),
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Withdraws a player specified by username or ID from the active tournament
+    ///
+    /// Returns the tournament the player withdrew from. Registering again reactivates the registration.
+    pub async fn withdraw_by_tetrio(
+        &self,
+        players: &PlayerCollection,
+        tetrio_id: &str,
+        reason: Option<&str>,
+    ) -> Result<TournamentEntry, RegistrationError> {
+        let tournament = match self.get_active().await? {
+            Some(t) => t,
+            None => {
+                return Err(RegistrationError::NoTournamentActive);
+            }
+        };
+
+        let specified = match players.get_player_by_tetrio(tetrio_id).await? {
+            Some(p) => p,
+            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
+        };
+
+        self.withdraw(&specified, tournament, reason).await
+    }
+
+    /// Withdraws a player from every active tournament they're registered to or waiting for
+    ///
+    /// Returns the tournaments the player withdrew from, empty if they weren't registered anywhere.
+    /// Used when a player unlinks, so `player` can be the entry from before unlinking.
+    pub async fn withdraw_from_active(
+        &self,
+        player: &PlayerEntry,
+        reason: Option<&str>,
+    ) -> DatabaseResult<Vec<TournamentEntry>> {
+        let mut withdrawn = Vec::new();
+        for tournament in self.get_all_active().await? {
+            if !tournament.player_is_registered(player)
+                && tournament.waitlist_position(&player.tetrio_id).is_none()
+            {
+                continue;
+            }
+            match self.withdraw(player, tournament, reason).await {
+                Ok(tournament) => withdrawn.push(tournament),
+                Err(RegistrationError::DatabaseError(err)) => return Err(err),
+                Err(err) => tracing::warn!("Could not withdraw {}: {}", player.tetrio_id, err),
+            }
+        }
+        Ok(withdrawn)
+    }
+
+    /// Withdraws a player specified by Discord ID from the active tournament
+    ///
+    /// Returns the tournament the player withdrew from. Registering again reactivates the registration.
+    pub async fn withdraw_by_discord(
+        &self,
+        players: &PlayerCollection,
+        discord_id: u64,
+        reason: Option<&str>,
+    ) -> Result<TournamentEntry, RegistrationError> {
+        let tournament = match self.get_active().await? {
+            Some(t) => t,
+            None => {
+                return Err(RegistrationError::NoTournamentActive);
+            }
+        };
+
+        let specified = match players.get_player_by_discord(discord_id).await? {
+            Some(p) => p,
+            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
+        };
+
+        self.withdraw(&specified, tournament, reason).await
+    }
+
+    /// Removes a player's registration from the current tournament
+    ///
+    /// The registration is moved to the unregistered players, so registering again restores the
+    /// original registration date (refer to [`TournamentEntry::registration_history()`]).
+    ///
+    /// Function to be used internally, you're probably looking for
+    /// [`unregister_by_tetrio()`] or [`unregister_by_discord()`]
+    async fn unregister(
+        &self,
+        player: &PlayerEntry,
+        mut tournament: TournamentEntry,
+        by: UnregisteredBy,
+        reason: Option<&str>,
+    ) -> Result<TournamentEntry, RegistrationError> {
+        let registration = match tournament.registration(&player.tetrio_id) {
+            Some(registration) => registration.clone(),
+            None => {
+                return match self
+                    .leave_waitlist(&mut tournament, &player.tetrio_id)
+                    .await?
+                {
+                    true => Ok(tournament),
+                    false => Err(RegistrationError::NotRegistered),
+                };
+            }
+        };
+
+        tracing::info!(
+            "Unregistering {} from tournament {} (by {})",
+            &player.tetrio_id,
+            tournament.name,
+            by
+        );
+
+        let removed = UnregisteredEntry {
+            registration,
+            unregistered_at: BsonDateTime::from(Utc::now()),
+            by,
+            reason: reason.map(|r| r.to_string()),
+        };
+        let removed_document = bson::to_document(&removed).expect("bad document");
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {
+                    "$pull": {"registered_players": {"tetrio_id": &player.tetrio_id}},
+                    "$push": {"unregistered_players": removed_document}
+                },
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        tournament
+            .registered_players
+            .retain(|reg| reg.tetrio_id != player.tetrio_id);
+        tournament.unregistered_players.push(removed);
+        Ok(tournament)
+    }
+
+    /// Removes the registration of a player specified by username or ID from the active tournament
+    ///
+    /// Returns the removed player and the tournament they were unregistered from. Unlike [`withdraw_by_tetrio()`],
+    /// the registration doesn't count anymore, only its original date is kept for the history.
+    pub async fn unregister_by_tetrio(
+        &self,
+        players: &PlayerCollection,
+        tetrio_id: &str,
+        by: UnregisteredBy,
+        reason: Option<&str>,
+    ) -> Result<(PlayerEntry, TournamentEntry), RegistrationError> {
+        let tournament = match self.get_active().await? {
+            Some(t) => t,
+            None => {
+                return Err(RegistrationError::NoTournamentActive);
+            }
+        };
+
+        let specified = match players.get_player_by_tetrio(tetrio_id).await? {
+            Some(p) => p,
+            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
+        };
+
+        let tournament = self.unregister(&specified, tournament, by, reason).await?;
+        Ok((specified, tournament))
+    }
+
+    /// Removes the registration of a player specified by Discord ID from the active tournament
+    ///
+    /// Returns the removed player and the tournament they were unregistered from. Unlike [`withdraw_by_discord()`],
+    /// the registration doesn't count anymore, only its original date is kept for the history.
+    pub async fn unregister_by_discord(
+        &self,
+        players: &PlayerCollection,
+        discord_id: u64,
+        by: UnregisteredBy,
+        reason: Option<&str>,
+    ) -> Result<(PlayerEntry, TournamentEntry), RegistrationError> {
+        let tournament = match self.get_active().await? {
+            Some(t) => t,
+            None => {
+                return Err(RegistrationError::NoTournamentActive);
+            }
+        };
+
+        let specified = match players.get_player_by_discord(discord_id).await? {
+            Some(p) => p,
+            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
+        };
+
+        let tournament = self.unregister(&specified, tournament, by, reason).await?;
+        Ok((specified, tournament))
+    }
+
+    /// Adds a stat snapshot of the current leaderboard entry to a specified tournament
+    ///
+    /// This data is used to compare announcement stats when registering.
+    /// It's around 4MB in size (as measured in March 2021), which is why every snapshot is a document
+    /// of its own and only the one in use is copied into the tournament entry.
+    ///
+    /// The snapshot is labeled with `label`, or when it was taken if that's `None`. It's only used
+    /// right away if the tournament has no snapshot yet or `overwrite` is set, otherwise it's kept for
+    /// [`TournamentCollection::set_official_snapshot()`]. Use [`TournamentCollection::save_snapshot()`]
+    /// if the leaderboard was already requested.
+    pub async fn add_snapshot(
+        &self,
+        name: &str,
+        label: Option<&str>,
+        overwrite: bool,
+    ) -> DatabaseResult<SnapshotInfo> {
+        // Will ensure that unranked players are not in the snapshot and are therefore easy to identify,
+        // since the players collection doesn't remove them when they become unranked
+        let users = match tetrio::leaderboard::request().await {
+            Ok(response) => response.data.users,
+            Err(e) => return Err(DatabaseError::TetrioApiError(e)),
+        };
+
+        self.save_snapshot(name, &users, label, overwrite).await
+    }
+
+    /// Compares the stat snapshot of a tournament with the current data in the player collection
+    ///
+    /// Shows what retaking the snapshot would change, see [`eligibility::snapshot_diff()`]. Ranked
+    /// players are taken from the player collection, so it's only as recent as the last
+    /// [`PlayerCollection::update_from_leaderboard()`]. Fails with [`DatabaseError::FieldNotSet`] if
+    /// the tournament has no snapshot yet.
+    pub async fn snapshot_diff(
+        &self,
+        name: &str,
+        players: &PlayerCollection,
+    ) -> DatabaseResult<SnapshotDiff> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        let taken_at = tournament.snapshot_at().ok_or(DatabaseError::FieldNotSet)?;
+
+        let current: Vec<LeaderboardUser> = players
+            .get_players(
+                doc! {"tetrio_data.league.rank": {"$ne": Rank::Unranked.to_str()}},
+                None,
+            )
+            .await?
+            .into_iter()
+            .filter_map(|entry| entry.tetrio_data)
+            .collect();
+        let registered: Vec<&str> = tournament
+            .registered_players()
+            .iter()
+            .map(|entry| entry.tetrio_id.as_str())
+            .collect();
+
+        Ok(eligibility::snapshot_diff(
+            &tournament.restrictions,
+            tournament.snapshot(),
+            taken_at,
+            &current,
+            Utc::now(),
+            &registered,
+        ))
+    }
+
+    /// Saves already requested leaderboard data as a stat snapshot of a specified tournament
+    ///
+    /// Same as [`TournamentCollection::add_snapshot()`], for callers that need to look at the data
+    /// before committing it. Labels have to be unique per tournament.
+    pub async fn save_snapshot(
+        &self,
+        name: &str,
+        users: &[LeaderboardUser],
+        label: Option<&str>,
+        overwrite: bool,
+    ) -> DatabaseResult<SnapshotInfo> {
+        let tournament = match self.get_tournament(name).await? {
+            Some(t) => t,
+            None => return Err(DatabaseError::NotFound),
+        };
+
+        let taken_at = Utc::now();
+        let label = match label.map(str::trim) {
+            Some("") => {
+                return Err(DatabaseError::InvalidInput(
+                    "Snapshot label can't be empty".to_string(),
+                ))
+            }
+            Some(label) if label.contains(char::is_whitespace) => {
+                return Err(DatabaseError::InvalidInput(
+                    "Snapshot labels can't contain spaces".to_string(),
+                ))
+            }
+            Some(label) => label.to_string(),
+            None => taken_at.format("%Y-%m-%d_%H%M").to_string(),
+        };
+
+        tracing::info!(
+            "Adding stat snapshot {} for tournament {}",
+            label,
+            tournament.shorthand
+        );
+
+        let snapshot = StoredSnapshot {
+            tournament: tournament.shorthand.clone(),
+            label: label.clone(),
+            taken_at: BsonDateTime::from(taken_at),
+            users: users.to_vec(),
+        };
+        let document = bson::to_document(&snapshot).expect("Bad document");
+        if let Err(err) = self.snapshots.insert_one(document, None).await {
+            return Err(match violated_unique_index(&err) {
+                Some(_) => DatabaseError::InvalidInput(format!(
+                    "{} already has a snapshot called {}",
+                    tournament.shorthand, label
+                )),
+                None => mongo_error(err),
+            });
+        }
+
+        let official = tournament.snapshot_at().is_none() || overwrite;
+        if official {
+            self.use_snapshot(&tournament, &snapshot).await?;
+        }
+
+        Ok(SnapshotInfo {
+            label,
+            taken_at: snapshot.taken_at,
+            players: users.len(),
+            official,
+        })
+    }
+
+    /// Every stored snapshot of a specified tournament, oldest first
+    ///
+    /// A snapshot taken before snapshots had labels is included as [`LEGACY_SNAPSHOT_LABEL`].
+    pub async fn get_snapshots(&self, name: &str) -> DatabaseResult<Vec<SnapshotInfo>> {
+        let tournament = match self.get_tournament(name).await? {
+            Some(t) => t,
+            None => return Err(DatabaseError::NotFound),
+        };
+
+        let pipeline = vec![
+            doc! {"$match": {"tournament": &tournament.shorthand}},
+            doc! {"$project": {"label": 1, "taken_at": 1, "players": {"$size": "$users"}}},
+            doc! {"$sort": {"taken_at": 1}},
+        ];
+        let mut cursor = self
+            .snapshots
+            .aggregate(pipeline, None)
+            .await
+            .map_err(mongo_error)?;
+
+        let mut snapshots: Vec<SnapshotInfo> = Vec::new();
+        if let (None, Some(taken_at)) = (&tournament.snapshot_label, tournament.snapshot_at) {
+            snapshots.push(SnapshotInfo {
+                label: LEGACY_SNAPSHOT_LABEL.to_string(),
+                taken_at,
+                players: tournament.player_stats_snapshot.len(),
+                official: true,
+            });
+        }
+        while let Some(document) = cursor.try_next().await.map_err(mongo_error)? {
+            let mut info: SnapshotInfo = crate::database::parse_entry(document)?;
+            info.official = tournament.snapshot_label.as_deref() == Some(info.label.as_str());
+            // an archived legacy snapshot is already listed
+            if !snapshots.iter().any(|s| s.label == info.label) {
+                snapshots.push(info);
+            }
+        }
+        Ok(snapshots)
+    }
+
+    /// Chooses the stored snapshot eligibility checks of a specified tournament use
+    ///
+    /// Returns the updated tournament. A snapshot from before snapshots had labels is kept as
+    /// [`LEGACY_SNAPSHOT_LABEL`], so it's possible to switch back to it.
+    pub async fn set_official_snapshot(
+        &self,
+        name: &str,
+        label: &str,
+    ) -> DatabaseResult<TournamentEntry> {
+        let tournament = match self.get_tournament(name).await? {
+            Some(t) => t,
+            None => return Err(DatabaseError::NotFound),
+        };
+        if tournament.snapshot_label() == Some(label) {
+            return Ok(tournament);
+        }
+
+        let snapshot: StoredSnapshot = crate::database::get_entry(
+            &self.snapshots,
+            doc! {"tournament": &tournament.shorthand, "label": label},
+        )
+        .await?
+        .ok_or(DatabaseError::NotFound)?;
+
+        tracing::info!(
+            "Switching tournament {} to stat snapshot {}",
+            tournament.shorthand,
+            label
+        );
+        self.use_snapshot(&tournament, &snapshot).await?;
+        self.get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)
+    }
+
+    // Copies a stored snapshot into the tournament entry, so eligibility checks use it
+    async fn use_snapshot(
+        &self,
+        tournament: &TournamentEntry,
+        snapshot: &StoredSnapshot,
+    ) -> DatabaseResult<()> {
+        self.archive_legacy_snapshot(tournament).await?;
+
+        let users: Vec<Document> = snapshot
+            .users
+            .iter()
+            .map(|u| bson::to_document(u).expect("Bad document"))
+            .collect();
+
+        // The filter also checks the old timestamp, so a snapshot taken in the meantime isn't replaced
+        let filter = match tournament.snapshot_at {
+            Some(taken_at) => doc! {"shorthand": &tournament.shorthand, "snapshot_at": *taken_at},
+            None => doc! {"shorthand": &tournament.shorthand, "snapshot_at": null},
+        };
+
+        match self
+            .collection
+            .update_one(
+                filter,
+                doc! {"$set": {
+                    "player_stats_snapshot": &users,
+                    "snapshot_at": snapshot.taken_at,
+                    "snapshot_label": &snapshot.label
+                }},
+                None,
+            )
+            .await
+        {
+            Ok(result) if result.matched_count == 0 => {
+                match self
+                    .get_tournament(&tournament.shorthand)
+                    .await?
+                    .and_then(|t| t.snapshot_at())
+                {
+                    Some(taken_at) => Err(DatabaseError::SnapshotExists { taken_at }),
+                    None => Err(DatabaseError::CouldNotPush),
+                }
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    // Stores a snapshot from before snapshots had labels, so replacing it doesn't lose it
+    async fn archive_legacy_snapshot(&self, tournament: &TournamentEntry) -> DatabaseResult<()> {
+        let taken_at = match (&tournament.snapshot_label, tournament.snapshot_at) {
+            (None, Some(taken_at)) => taken_at,
+            _ => return Ok(()),
+        };
+
+        let snapshot = StoredSnapshot {
+            tournament: tournament.shorthand.clone(),
+            label: LEGACY_SNAPSHOT_LABEL.to_string(),
+            taken_at,
+            users: tournament.player_stats_snapshot.clone(),
+        };
+        let document = bson::to_document(&snapshot).expect("Bad document");
+        match self.snapshots.insert_one(document, None).await {
+            Ok(_) => Ok(()),
+            // archived by an earlier attempt
+            Err(err) if violated_unique_index(&err).is_some() => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    /// Registered players of a specified tournament in the CSV format of Challonge's bulk add
+    ///
+    /// See [`TournamentEntry::challonge_participants()`] for the format and seeding.
+    pub async fn export_participants_challonge(
+        &self,
+        name: &str,
+        players: &PlayerCollection,
+        checked_in_only: bool,
+    ) -> DatabaseResult<String> {
+        let tournament = match self.get_tournament(name).await? {
+            Some(t) => t,
+            None => return Err(DatabaseError::NotFound),
+        };
+        let registered: Vec<&str> = tournament
+            .registered_players()
+            .iter()
+            .map(|r| r.tetrio_id.as_str())
+            .collect();
+        let entries = players
+            .get_players(doc! {"tetrio_id": {"$in": registered}}, None)
+            .await?;
+        Ok(tournament.challonge_participants(&entries, checked_in_only))
+    }
+
+    /// Set a specified tournament as active
+    ///
+    /// If `None` is passed, then it will set all tournaments as inactive.
+    pub async fn set_active(&self, name: Option<&str>) -> DatabaseResult<Option<TournamentEntry>> {
+        let tournament = match name {
+            Some(name) => match self.get_tournament(name).await? {
+                Some(t) => t,
+                None => return Err(DatabaseError::NotFound),
+            },
+            None => {
+                self.collection
+                    .update_many(doc! {}, doc! {"$set": {"active": false}}, None)
+                    .await
+                    .map_err(mongo_error)?;
+                tracing::info!("Set all tournaments to inactive");
+                return Ok(None);
+            }
+        };
+
+        // other active tournaments stay active, see set_inactive()
+        self.collection
+            .update_one(
+                doc! {"name": &tournament.name},
+                doc! {"$set": {"active": true}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        tracing::info!("Set tournament {} to active", tournament.name);
+
+        Ok(Some(tournament))
+    }
+
+    /// Sets a single tournament inactive, other active tournaments stay active
+    pub async fn set_inactive(&self, name: &str) -> DatabaseResult<TournamentEntry> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$set": {"active": false}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        tracing::info!("Set tournament {} to inactive", tournament.name);
+
+        Ok(tournament)
+    }
+
+    /// Get the currently active tournament
+    ///
+    /// If more than one tournament is active, the one that was created first is returned.
+    /// Use [`TournamentCollection::resolve_active()`] where picking the wrong one matters.
+    pub async fn get_active(&self) -> DatabaseResult<Option<TournamentEntry>> {
+        Ok(self.get_all_active().await?.into_iter().next())
+    }
+
+    /// Every active tournament, oldest first
+    pub async fn get_all_active(&self) -> DatabaseResult<Vec<TournamentEntry>> {
+        let mut active: Vec<TournamentEntry> =
+            crate::database::get_entries(&self.collection, doc! {"active": true}).await?;
+        active.sort_by_key(|t| t.created_at());
+        Ok(active)
+    }
+
+    /// Every tournament, active or not, oldest first
+    pub async fn get_all(&self) -> DatabaseResult<Vec<TournamentEntry>> {
+        let mut tournaments: Vec<TournamentEntry> =
+            crate::database::get_entries(&self.collection, doc! {}).await?;
+        tournaments.sort_by_key(|t| t.created_at());
+        Ok(tournaments)
+    }
+
+    /// Picks the active tournament a command is meant for
+    ///
+    /// With a name or shorthand, that tournament has to be active. Without one, there has to be exactly
+    /// one active tournament, otherwise [`RegistrationError::AmbiguousTournament`] lists the choices.
+    pub async fn resolve_active(
+        &self,
+        name: Option<&str>,
+    ) -> Result<TournamentEntry, RegistrationError> {
+        let mut active = self.get_all_active().await?;
+        match name {
+            Some(name) => active
+                .into_iter()
+                .find(|t| t.shorthand.eq_ignore_ascii_case(name) || t.name == name)
+                .ok_or(RegistrationError::NoTournamentActive),
+            None if active.len() > 1 => Err(RegistrationError::AmbiguousTournament(
+                active.into_iter().map(|t| t.shorthand).collect(),
+            )),
+            None => active.pop().ok_or(RegistrationError::NoTournamentActive),
+        }
+    }
+
+    /// Player entries of everyone registered to a tournament, in registration order
+    ///
+    /// Uses the active tournament if no name is given. Withdrawn players are left out, and so are
+    /// registrations without a player entry.
+    pub async fn get_registered_players(
+        &self,
+        players: &PlayerCollection,
+        name: Option<&str>,
+    ) -> DatabaseResult<Vec<PlayerEntry>> {
+        let tournament = match name {
+            Some(name) => self.get_tournament(name).await?,
+            None => self.get_active().await?,
+        }
+        .ok_or(DatabaseError::NotFound)?;
+
+        let registrations = tournament.registered_players();
+        let ids: Vec<&str> = registrations
+            .iter()
+            .map(|reg| reg.tetrio_id.as_str())
+            .collect();
+        let mut entries = players
+            .get_players(doc! {"tetrio_id": {"$in": ids.clone()}}, None)
+            .await?;
+        entries.sort_by_key(|entry| ids.iter().position(|id| *id == entry.tetrio_id));
+        Ok(entries)
+    }
+
+    /// Records a check-in made in the given generation, replacing an older record of the player
+    pub async fn set_checked_in(
+        &self,
+        name: &str,
+        discord_id: u64,
+        tetrio_id: &str,
+        generation: u32,
+    ) -> DatabaseResult<()> {
+        self.record_check_in(
+            name,
+            &CheckInRecord {
+                discord_id,
+                tetrio_id: Some(tetrio_id.to_string()),
+                at: Some(BsonDateTime::from(Utc::now())),
+                generation,
+                carried_over: false,
+                forced_by: None,
+            },
+        )
+        .await
+    }
+
+    /// Checks a player in by hand, for players who can't react to the check-in message
+    ///
+    /// Works while the check-in is closed as well. `staff_id` is kept with the record, see
+    /// [`CheckInRecord::forced_by`]. Checking the player out again is done with
+    /// [`TournamentCollection::remove_checked_in()`].
+    pub async fn force_check_in(
+        &self,
+        name: &str,
+        discord_id: u64,
+        tetrio_id: &str,
+        generation: u32,
+        staff_id: u64,
+    ) -> DatabaseResult<()> {
+        self.record_check_in(
+            name,
+            &CheckInRecord {
+                discord_id,
+                tetrio_id: Some(tetrio_id.to_string()),
+                at: Some(BsonDateTime::from(Utc::now())),
+                generation,
+                carried_over: false,
+                forced_by: Some(staff_id),
+            },
+        )
+        .await
+    }
+
+    /// Writes a check-in record, replacing an older record of the same player
+    ///
+    /// Both updates only match if the other one didn't, so a player never ends up with two records.
+    async fn record_check_in(&self, name: &str, record: &CheckInRecord) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        let discord_id = record.discord_id;
+        let record = bson::to_bson(record).expect("could not convert to bson");
+
+        let replaced = self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand, "check_ins.discord_id": discord_id},
+                doc! {"$set": {"check_ins.$": &record}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        if replaced.matched_count > 0 {
+            return Ok(());
+        }
+
+        self.collection
+            .update_one(
+                doc! {"shorthand": &shorthand, "check_ins.discord_id": {"$ne": discord_id}},
+                doc! {"$addToSet": {"check_ins": record}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        Ok(())
+    }
+
+    /// Recorded check-ins of the current generation, see [`TournamentEntry::checked_in()`]
+    pub async fn get_checked_in(&self, name: &str) -> DatabaseResult<Vec<CheckInRecord>> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        Ok(tournament.checked_in().into_iter().cloned().collect())
+    }
+
+    /// Updates fields of a single registration, fails with [`DatabaseError::NotFound`] if there is none
+    async fn update_registration(
+        &self,
+        name: &str,
+        tetrio_id: &str,
+        set: Document,
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        let result = self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand, "registered_players.tetrio_id": tetrio_id},
+                doc! {"$set": set},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Disqualifies a registered player, they can't register to the tournament again
+    pub async fn disqualify(
+        &self,
+        name: &str,
+        tetrio_id: &str,
+        reason: &str,
+        by: u64,
+    ) -> DatabaseResult<()> {
+        tracing::info!("Disqualifying {} from tournament {}", tetrio_id, name);
+        let status = bson::to_bson(&RegistrationStatus::Disqualified {
+            at: BsonDateTime::from(Utc::now()),
+            reason: reason.to_string(),
+            by,
+        })
+        .expect("could not convert to bson");
+        self.update_registration(
+            name,
+            tetrio_id,
+            doc! {"registered_players.$.status": status},
+        )
+        .await
+    }
+
+    /// Adds a staff note to a registration
+    pub async fn add_registration_note(
+        &self,
+        name: &str,
+        tetrio_id: &str,
+        text: &str,
+        by: u64,
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        let note = bson::to_bson(&RegistrationNote {
+            at: BsonDateTime::from(Utc::now()),
+            by,
+            text: text.to_string(),
+        })
+        .expect("could not convert to bson");
+        let result = self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand, "registered_players.tetrio_id": tetrio_id},
+                doc! {"$push": {"registered_players.$.notes": note}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Marks a registration as reviewed by a staff member
+    pub async fn mark_reviewed(&self, name: &str, tetrio_id: &str, by: u64) -> DatabaseResult<()> {
+        self.update_registration(
+            name,
+            tetrio_id,
+            doc! {
+                "registered_players.$.reviewed_by": by,
+                "registered_players.$.reviewed_at": BsonDateTime::from(Utc::now())
+            },
+        )
+        .await
+    }
+
+    /// Adds an alert to a tournament's alert list
+    pub async fn add_alert(&self, name: &str, alert: &StaffAlert) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        let alert = bson::to_bson(alert).expect("could not convert to bson");
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$push": {"alerts": alert}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
+
+    /// Removes a player's recorded check-in, after they checked out
+    pub async fn remove_checked_in(&self, name: &str, discord_id: u64) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$pull": {"check_ins": {"discord_id": discord_id}}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
         }
     }
 
-    /// Create a tournament entry with specified information
-    pub fn create_tournament(
+    /// Resets the check-in, so everyone has to check in again
+    ///
+    /// Stores the snapshot in `previous_check_ins`, clears the recorded check-ins and sets the new
+    /// generation, both from [`TournamentEntry::check_in_reset()`]. Reactions on the check-in message
+    /// are left alone, reactions from before the reset are ignored (see [`is_stale_check_in()`]).
+    /// A closed check-in is opened again.
+    pub async fn reset_check_in(
         &self,
         name: &str,
-        shorthand: &str,
-        restrictions: TournamentRestrictions,
-    ) -> DatabaseResult<TournamentEntry> {
-        tracing::info!("Creating tournament {} ({})", name, shorthand);
-        let entry = TournamentEntry::new(name, shorthand, restrictions);
+        snapshot: &CheckInSnapshot,
+        generation: u32,
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
 
-        if self.get_tournament(name)?.is_some() {
-            return Err(DatabaseError::DuplicateTournamentEntry);
+        let snapshot = bson::to_bson(snapshot).expect("could not convert to bson");
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {
+                    "$push": {"previous_check_ins": snapshot},
+                    "$set": {"check_in_generation": generation, "check_ins": [], "check_in_closed": false}
+                },
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
         }
+    }
 
-        match self.collection.insert_one(
-            bson::to_document(&entry).expect("could not convert to document"),
-            None,
-        ) {
-            Ok(_) => Ok(entry),
-            Err(_) => Err(DatabaseError::CouldNotPush),
+    /// Closes the check-in, usually once the bracket is made
+    ///
+    /// Reactions to the check-in message don't check anyone in or out afterwards, staff can still use
+    /// [`TournamentCollection::force_check_in()`]. Returns the closed tournament, closing it twice
+    /// is an [`DatabaseError::InvalidInput`].
+    pub async fn close_check_in(&self, name: &str) -> DatabaseResult<TournamentEntry> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        if tournament.check_in_closed {
+            return Err(DatabaseError::InvalidInput(
+                "the check-in is closed already".to_string(),
+            ));
         }
-    }
 
-    /// Gets a tournament by name or shorthand
-    pub fn get_tournament(&self, name: &str) -> DatabaseResult<Option<TournamentEntry>> {
-        crate::database::get_entry(
-            &self.collection,
-            doc! {"$or":[{"name": name}, {"shorthand": name}]},
-        )
+        self.collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$set": {"check_in_closed": true}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+        Ok(TournamentEntry {
+            check_in_closed: true,
+            ..tournament
+        })
     }
 
-    /// Registers a player to the active tournament
+    /// Replaces the check-in message, for example after it was deleted
     ///
-    /// Will call [`PlayerCollection::link()`] internally, so the player is always linked.
-    /// If no username is given, then it will try to use the linked player.
-    pub fn register_to_active(
+    /// `check_ins` replaces the recorded check-ins, see [`carry_over_check_ins()`].
+    pub async fn repost_check_in(
         &self,
-        players: &PlayerCollection,
-        tetrio_id: Option<&str>,
-        discord_id: u64,
-        bypass_restrictions: bool,
-    ) -> Result<PlayerEntry, RegistrationError> {
-        let tournament = match self.get_active()? {
-            Some(t) => t,
-            None => {
-                return Err(RegistrationError::NoTournamentActive);
-            }
-        };
+        name: &str,
+        channel_id: u64,
+        message_id: u64,
+        check_ins: &[CheckInRecord],
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
 
-        // Use the linked player if no username is provided
-        // Link already takes care of the cases where tetrio id or discord id do not match
-        let player = match tetrio_id {
-            None => match players.get_player_by_discord(discord_id)? {
-                Some(linked_entry) => linked_entry,
-                None => {
-                    return Err(RegistrationError::MissingArgument("username".to_string()));
-                }
-            },
-            Some(id) => match players.link(discord_id, id) {
-                Ok(new_entry) => new_entry,
-                Err(err) => match err {
-                    DatabaseError::AlreadyLinked => {
-                        players.get_player_by_discord(discord_id)?.unwrap()
-                    }
-                    _ => {
-                        return Err(RegistrationError::DatabaseError(err));
-                    }
-                },
-            },
-        };
+        let check_ins: Vec<bson::Bson> = check_ins
+            .iter()
+            .map(|record| bson::to_bson(record).expect("could not convert to bson"))
+            .collect();
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$set": {"check_in_channel": channel_id, "check_in_msg": message_id, "check_ins": check_ins}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
 
-        let stats = player.tetrio_data.unwrap();
-        tracing::info!(
-            "Registering {} to tournament {}",
-            &stats.username,
-            tournament.name
-        );
+    /// Marks a check-in message as being created, see [`crate::check_in::Step::Reserve`]
+    ///
+    /// Fails if another check-in message is already being created, unless that started more than
+    /// `PENDING_CHECK_IN_MINUTES` ago.
+    pub async fn reserve_check_in(&self, name: &str) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        let now = Utc::now();
+        let stale = BsonDateTime::from(now - Duration::minutes(PENDING_CHECK_IN_MINUTES));
+        let result = self
+            .collection
+            .update_one(
+                doc! {"$and": [
+                    {"shorthand": &shorthand},
+                    {"$or": [{"pending_check_in": null}, {"pending_check_in": {"$lt": stale}}]}
+                ]},
+                doc! {"$set": {"pending_check_in": BsonDateTime::from(now)}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
 
-        // throws an error if invalid
-        if !bypass_restrictions {
-            tournament.check_player_stats(&stats)?;
+        if result.matched_count == 0 {
+            return Err(DatabaseError::InvalidInput(
+                "A check-in message is already being created".to_string(),
+            ));
         }
+        Ok(())
+    }
 
-        let tetrio_id = player.tetrio_id;
-        if tournament
-            .registered_players
-            .iter()
-            .any(|entry| entry.tetrio_id == tetrio_id)
+    /// Sets the check-in message and clears the pending marker, see [`crate::check_in::Step::Finalize`]
+    ///
+    /// The check-in of a new message is open, even if the one before it was closed.
+    pub async fn finalize_check_in(
+        &self,
+        name: &str,
+        channel_id: u64,
+        message_id: u64,
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {
+                    "$set": {"check_in_channel": channel_id, "check_in_msg": message_id, "check_in_closed": false},
+                    "$unset": {"pending_check_in": ""}
+                },
+                None,
+            )
+            .await
         {
-            return Err(RegistrationError::AlreadyRegistered);
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
         }
+    }
 
-        let reg_entry =
-            bson::to_document(&RegistrationEntry::new(&tetrio_id)).expect("bad document");
-
-        self.collection
+    /// Clears the pending marker without setting a check-in message
+    pub async fn clear_check_in_reservation(&self, name: &str) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        match self
+            .collection
             .update_one(
-                doc! {"active": true},
-                doc! {"$push": {"registered_players": reg_entry}},
+                doc! {"shorthand": &shorthand},
+                doc! {"$unset": {"pending_check_in": ""}},
                 None,
             )
-            .map_err(|_| RegistrationError::DatabaseError(DatabaseError::CouldNotPush))?;
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
+    }
 
-        Ok(players.get_player_by_discord(discord_id)?.unwrap())
+    /// Removes the check-in message, if it's still the given message
+    pub async fn untrack_check_in_msg(&self, name: &str, message_id: u64) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand, "check_in_msg": message_id},
+                doc! {"$set": {"check_in_channel": null, "check_in_msg": null}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
+        }
     }
 
-    /// Unregisters a player from the current tournament
-    ///
-    /// Function to be used internally, you're probably looking for
-    /// [`unregister_by_tetrio()`] or [`unregister_by_discord()`]
-    fn unregister(&self, player: &PlayerEntry, tournament: &TournamentEntry) -> RegistrationResult {
-        if tournament
-            .registered_players
-            .iter()
-            .find(|reg| reg.tetrio_id == player.tetrio_id)
-            .is_none()
+    /// Set a check-in message for a tournament, along with the channel it was posted in
+    pub async fn set_check_in_msg(
+        &self,
+        name: &str,
+        channel_id: u64,
+        message_id: u64,
+    ) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$set": {"check_in_channel": channel_id, "check_in_msg": message_id}},
+                None,
+            )
+            .await
         {
-            return Err(RegistrationError::NotRegistered);
+            Ok(_) => Ok(()),
+            Err(err) => Err(mongo_error(err)),
         }
+    }
+
+    /// Replaces the lifecycle dates of a tournament
+    ///
+    /// Fails with [`DatabaseError::InvalidInput`] if the dates are out of order.
+    pub async fn set_dates(
+        &self,
+        name: &str,
+        dates: &TournamentDates,
+    ) -> DatabaseResult<TournamentEntry> {
+        dates.validate()?;
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
 
         tracing::info!(
-            "Unregistering {} from tournament {}",
-            &player.tetrio_id,
-            tournament.name
+            "Setting the dates of {} to {:?}",
+            tournament.shorthand,
+            dates
         );
-
-        if self
+        let dates = bson::to_bson(dates).expect("could not convert to bson");
+        match self
             .collection
             .update_one(
                 doc! {"shorthand": &tournament.shorthand},
-                doc! {"$pull": {"registered_players": {"tetrio_id": &player.tetrio_id}}},
+                doc! {"$set": {"dates": dates}},
                 None,
             )
-            .is_err()
+            .await
         {
-            return Err(RegistrationError::DatabaseError(
-                DatabaseError::CouldNotPush,
-            ));
+            Ok(_) => Ok(self.get_tournament(&tournament.shorthand).await?.unwrap()),
+            Err(err) => Err(mongo_error(err)),
         }
-
-        Ok(())
     }
 
-    /// Unregisters a player specified by username or ID from the active tournament
-    pub fn unregister_by_tetrio(
+    /// Sets the Discord role given to registered players, or removes it with `None`
+    ///
+    /// The role itself is handed out by the Discord side, this only stores which one it is.
+    pub async fn set_participant_role(
         &self,
-        players: &PlayerCollection,
-        tetrio_id: &str,
-    ) -> RegistrationResult {
-        let tournament = match self.get_active()? {
-            Some(t) => t,
-            None => {
-                return Err(RegistrationError::NoTournamentActive);
-            }
-        };
-
-        let specified = match players.get_player_by_tetrio(tetrio_id)? {
-            Some(p) => p,
-            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
-        };
+        name: &str,
+        role_id: Option<u64>,
+    ) -> DatabaseResult<TournamentEntry> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
 
-        self.unregister(&specified, &tournament)
+        tracing::info!(
+            "Setting the participant role of {} to {:?}",
+            tournament.shorthand,
+            role_id
+        );
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$set": {"participant_role": role_id}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(self.get_tournament(&tournament.shorthand).await?.unwrap()),
+            Err(err) => Err(mongo_error(err)),
+        }
     }
 
-    /// Unregisters a player specified by Discord ID from the active tournament
-    pub fn unregister_by_discord(
+    /// Links a tournament to the qualifier feeding it, or removes the link with `None`
+    ///
+    /// Once linked, only players added with [`TournamentCollection::qualify()`] can register.
+    pub async fn set_parent(
         &self,
-        players: &PlayerCollection,
-        discord_id: u64,
-    ) -> RegistrationResult {
-        let tournament = match self.get_active()? {
-            Some(t) => t,
-            None => {
-                return Err(RegistrationError::NoTournamentActive);
+        name: &str,
+        parent: Option<&str>,
+        skip_restrictions: bool,
+    ) -> DatabaseResult<TournamentEntry> {
+        let tournament = self
+            .get_tournament(name)
+            .await?
+            .ok_or(DatabaseError::NotFound)?;
+        let parent = match parent {
+            Some(parent) => {
+                let parent = self
+                    .get_tournament(parent)
+                    .await?
+                    .ok_or(DatabaseError::NotFound)?;
+                if parent.shorthand == tournament.shorthand {
+                    return Err(DatabaseError::InvalidInput(
+                        "A tournament can't qualify for itself".to_string(),
+                    ));
+                }
+                Some(parent.shorthand)
             }
+            None => None,
         };
 
-        let specified = match players.get_player_by_discord(discord_id)? {
-            Some(p) => p,
-            None => return Err(RegistrationError::DatabaseError(DatabaseError::NotFound)),
-        };
-
-        self.unregister(&specified, &tournament)
+        tracing::info!(
+            "Setting the parent tournament of {} to {:?}",
+            tournament.shorthand,
+            parent
+        );
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &tournament.shorthand},
+                doc! {"$set": {"parent_tournament": parent, "qualified_skip_restrictions": skip_restrictions}},
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(self.get_tournament(&tournament.shorthand).await?.unwrap()),
+            Err(err) => Err(mongo_error(err)),
+        }
     }
 
-    /// Adds a stat snapshot of the current leaderboard entry to a specified tournament
-    ///
-    /// This data is used to compare announcement stats when registering.
-    /// It's around 4MB in size (as measured in March 2021), so hitting a size
-    /// limit with MongoDB Atlas (512MB min.) is unlikely, unless hundreds of snapshots are saved.
-    pub fn add_snapshot(&self, name: &str) -> DatabaseResult<()> {
-        if self.get_tournament(name)?.is_none() {
+    /// Adds players to the qualified players of a tournament, players who already qualified are kept once
+    pub async fn qualify(&self, name: &str, tetrio_ids: &[String]) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
+        tracing::info!("Qualifying {} players for {}", tetrio_ids.len(), name);
+        let result = self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$addToSet": {"qualified_players": {"$each": tetrio_ids.to_vec()}}},
+                None,
+            )
+            .await
+            .map_err(mongo_error)?;
+
+        if result.matched_count == 0 {
             return Err(DatabaseError::NotFound);
         }
+        Ok(())
+    }
 
-        tracing::info!("Adding stat snapshot for tournament {}", name);
+    /// Saves the result of a bracket side split for a tournament, replacing the previous one
+    pub async fn set_bracket_split(&self, name: &str, split: &BracketSplit) -> DatabaseResult<()> {
+        let shorthand = self.stored_shorthand(name).await?;
 
-        // Will ensure that unranked players are not in the snapshot and are therefore easy to identify,
-        // since the players collection doesn't remove them when they become unranked
-        let snapshot: Vec<Document> = match tetrio::leaderboard::request() {
-            Ok(response) => response.data.users,
-            Err(e) => return Err(DatabaseError::TetrioApiError(e)),
-        }
-        .iter()
-        .map(|u| bson::to_document(u).expect("Bad document"))
-        .collect();
+        let split =
+            bson::to_document(split).map_err(|e| DatabaseError::CouldNotParse(e.to_string()))?;
 
-        match self.collection.update_one(
-            doc! {"$or":[{"name": name}, {"shorthand": name}]},
-            doc! {"$set": {"player_stats_snapshot": &snapshot, "snapshot_at": Utc::now()}},
-            None,
-        ) {
+        match self
+            .collection
+            .update_one(
+                doc! {"shorthand": &shorthand},
+                doc! {"$set": {"bracket_split": split}},
+                None,
+            )
+            .await
+        {
             Ok(_) => Ok(()),
-            Err(_) => Err(DatabaseError::CouldNotPush),
+            Err(err) => Err(mongo_error(err)),
         }
     }
+}
 
-    /// Set a specified tournament as active
-    ///
-    /// If `None` is passed, then it will set all tournaments as inactive.
-    pub fn set_active(&self, name: Option<&str>) -> DatabaseResult<Option<TournamentEntry>> {
-        let tournament = if let Some(name) = name {
-            match self.get_tournament(name)? {
-                Some(t) => Some(t),
-                None => return Err(DatabaseError::NotFound),
-            }
-        } else {
-            None
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::leaderboard::LeagueData;
+    use chrono::TimeZone;
 
-        // set all inactive
-        if self
-            .collection
-            .update_many(doc! {}, doc! {"$set": {"active": false}}, None)
-            .is_err()
-        {
-            return Err(DatabaseError::CouldNotPush);
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    fn user(rank: Rank, games: i64, rd: f64) -> LeaderboardUser {
+        LeaderboardUser {
+            _id: "5e47696db7c60f23a497ee6c".to_string(),
+            username: "caboozled_pie".to_string(),
+            role: "user".to_string(),
+            country: None,
+            supporter: Some(false),
+            verified: false,
+            league: LeagueData {
+                gamesplayed: games,
+                gameswon: 0,
+                rating: 20000f64,
+                rank: rank.to_str().to_string(),
+                glicko: Some(1500f64),
+                rd: Some(rd),
+                apm: Some(30f64),
+                pps: Some(1.5),
+                vs: Some(60f64),
+            },
         }
+    }
 
-        tracing::info!("Set all tournaments to inactive");
+    fn restrictions() -> TournamentRestrictions {
+        TournamentRestrictions::new(Rank::SPlus, 100f64, 10)
+    }
 
-        // set specified tournament active
-        if let Some(tournament) = &tournament {
-            if self
-                .collection
-                .update_one(
-                    doc! {"name": &tournament.name},
-                    doc! {"$set": {"active": true}},
-                    None,
-                )
-                .is_err()
-            {
-                return Err(DatabaseError::CouldNotPush);
+    fn check(
+        restrictions: &TournamentRestrictions,
+        snapshot: Option<&LeaderboardUser>,
+        current: &LeaderboardUser,
+        highest_rank: Option<Rank>,
+    ) -> RegistrationResult {
+        let announced = Some(now() - Duration::days(7));
+        check_eligibility(
+            restrictions,
+            snapshot,
+            announced,
+            current,
+            highest_rank,
+            None,
+            now(),
+        )
+    }
+
+    // Criteria that failed, panics unless the result is an ineligible verdict
+    fn failed(result: RegistrationResult) -> Vec<Criterion> {
+        match result {
+            Err(RegistrationError::Ineligible(verdict)) => {
+                verdict.failures().map(|check| check.criterion).collect()
             }
-            tracing::info!("Set tournament {} to active", tournament.name);
+            other => panic!("expected an ineligible verdict, got {:?}", other),
         }
+    }
 
-        Ok(tournament)
+    #[test]
+    fn eligible_player_passes() {
+        let fine = user(Rank::S, 50, 80f64);
+        assert!(check(&restrictions(), Some(&fine), &fine, None).is_ok());
+
+        // peaks up to one above the max rank are fine, RD exactly on the limit as well
+        assert!(check(&restrictions(), Some(&fine), &fine, Some(Rank::SS)).is_ok());
+        let on_limit = user(Rank::S, 50, 100f64);
+        assert!(check(&restrictions(), Some(&on_limit), &on_limit, None).is_ok());
     }
 
-    /// Get the currently active tournament
-    pub fn get_active(&self) -> DatabaseResult<Option<TournamentEntry>> {
-        crate::database::get_entry(&self.collection, doc! {"active": true})
+    #[test]
+    fn missing_snapshot() {
+        let fine = user(Rank::S, 50, 80f64);
+        let result =
+            check_eligibility(&restrictions(), Some(&fine), None, &fine, None, None, now());
+        assert!(matches!(result, Err(RegistrationError::SnapshotMissing)));
+
+        // allowing unranked players doesn't help without a snapshot
+        let unranked = restrictions().with_allow_unranked();
+        let result = check_eligibility(&unranked, None, None, &fine, None, None, now());
+        assert!(matches!(result, Err(RegistrationError::SnapshotMissing)));
     }
 
-    /// Set a check-in message for a tournament
-    pub fn set_check_in_msg(&self, name: &str, message_id: u64) -> DatabaseResult<()> {
-        if self.get_tournament(name)?.is_none() {
-            return Err(DatabaseError::NotFound);
-        }
+    #[test]
+    fn peak_rank_alone_is_too_high() {
+        let fine = user(Rank::S, 50, 80f64);
+        assert!(matches!(
+            check(&restrictions(), Some(&fine), &fine, Some(Rank::U)),
+            Err(RegistrationError::HighestRankTooHigh {
+                rank: Rank::U,
+                expected: Rank::SS
+            })
+        ));
+
+        // along with another failure the whole verdict is returned
+        let new = user(Rank::S, 9, 80f64);
+        assert_eq!(
+            failed(check(&restrictions(), Some(&new), &fine, Some(Rank::U))),
+            vec![Criterion::RankedGames, Criterion::HighestRank]
+        );
+    }
 
-        match self.collection.update_one(
-            doc! {"$or":[{"name": name}, {"shorthand": name}]},
-            doc! {"$set": {"check_in_msg": message_id}},
+    #[test]
+    fn ineligible_on_every_criterion() {
+        let fine = user(Rank::S, 50, 80f64);
+        let ss = user(Rank::SS, 50, 80f64);
+        assert_eq!(
+            failed(check(&restrictions(), None, &fine, None)),
+            vec![Criterion::RankedOnAnnouncement]
+        );
+        assert_eq!(
+            failed(check(&restrictions(), Some(&ss), &fine, None)),
+            vec![Criterion::AnnouncementRank]
+        );
+        assert_eq!(
+            failed(check(
+                &restrictions(),
+                Some(&user(Rank::S, 9, 80f64)),
+                &fine,
+                None
+            )),
+            vec![Criterion::RankedGames]
+        );
+        assert_eq!(
+            failed(check(
+                &restrictions(),
+                Some(&user(Rank::S, 50, 101f64)),
+                &fine,
+                None
+            )),
+            vec![Criterion::RatingDeviation]
+        );
+        assert_eq!(
+            failed(check(&restrictions(), Some(&fine), &ss, None)),
+            vec![Criterion::CurrentRank]
+        );
+
+        let aged = restrictions().with_min_account_age(30);
+        let result = check_eligibility(
+            &aged,
+            Some(&fine),
+            Some(now() - Duration::days(7)),
+            &fine,
             None,
-        ) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(DatabaseError::CouldNotPush),
-        }
+            Some(now() - Duration::days(29)),
+            now(),
+        );
+        assert_eq!(failed(result), vec![Criterion::AccountAge]);
+    }
+
+    #[test]
+    fn unranked_players_use_their_current_stats() {
+        let allowed = restrictions().with_allow_unranked();
+        let unranked = user(Rank::Unranked, 10, 90f64);
+        assert!(check(&allowed, None, &unranked, None).is_ok());
+        assert!(matches!(
+            check(&allowed, None, &user(Rank::Unranked, 3, 90f64), None),
+            Err(RegistrationError::Ineligible(_))
+        ));
+    }
+
+    #[test]
+    fn backdates_end_after_registration_closes() {
+        let created = now() - Duration::days(7);
+        let closes = now() - Duration::days(1);
+        let grace = Duration::hours(BACKDATE_GRACE_HOURS);
+        let builder = || TournamentEntryBuilder::new("Test Tournament 1", "TT1", restrictions());
+
+        let open = builder().created_at(created).build().unwrap();
+        assert_eq!(open.backdate_bounds(now()), (created - grace, now()));
+
+        let closed = builder()
+            .created_at(created)
+            .dates(TournamentDates::new().registration_closes_at(closes))
+            .build()
+            .unwrap();
+        assert_eq!(
+            closed.backdate_bounds(now()),
+            (created - grace, closes + grace)
+        );
+        assert!(closed.check_backdate(closes + grace, now()).is_ok());
+        assert!(matches!(
+            closed.check_backdate(closes + grace + Duration::seconds(1), now()),
+            Err(RegistrationError::InvalidBackdate { .. })
+        ));
     }
 }
@@ -0,0 +1,393 @@
+//! Daily command usage rollups, for long term trends
+//!
+//! Command invocations are counted in memory with [`UsageCounters`] and periodically flushed into
+//! the collection with [`UsageCollection::flush()`]. Each flush increments the daily documents,
+//! so restarting in the middle of a day doesn't lose or double count anything that was flushed.
+//!
+//! Unique users are tracked as a set of Discord IDs per day and command, capped at
+//! [`USER_CAP`] per flush. The count is exact below the cap and a lower bound above it.
+
+use std::collections::{HashMap, HashSet};
+
+use bson::doc;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use mongodb::options::UpdateOptions;
+use mongodb::{Collection, Database};
+use serde::{Deserialize, Serialize};
+
+use crate::database::{mongo_error, DatabaseResult};
+
+/// Amount of user IDs that are kept per day and command in a single flush
+pub const USER_CAP: usize = 500;
+
+/// Format of the `date` field, sorts chronologically as a string
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// In-memory usage of a single command on a single day
+pub struct UsageCounter {
+    /// Amount of invocations
+    pub invocations: i64,
+    /// Amount of invocations that returned an error
+    pub errors: i64,
+    /// Discord IDs of users who used the command, at most [`USER_CAP`]
+    pub users: HashSet<u64>,
+}
+
+#[derive(Debug, Default)]
+/// Command usage that hasn't been flushed to the database yet, keyed by day and command
+pub struct UsageCounters(HashMap<(NaiveDate, String), UsageCounter>);
+
+impl UsageCounters {
+    /// Records a single invocation
+    pub fn record(&mut self, command: &str, user_id: u64, failed: bool, now: DateTime<Utc>) {
+        let counter = self
+            .0
+            .entry((now.date().naive_utc(), command.to_string()))
+            .or_insert_with(UsageCounter::default);
+        counter.invocations += 1;
+        if failed {
+            counter.errors += 1;
+        }
+        if counter.users.len() < USER_CAP {
+            counter.users.insert(user_id);
+        }
+    }
+
+    /// Takes every counter, leaving nothing behind
+    pub fn drain(&mut self) -> Vec<(NaiveDate, String, UsageCounter)> {
+        self.0
+            .drain()
+            .map(|((date, command), counter)| (date, command, counter))
+            .collect()
+    }
+
+    /// Puts counters back, for example after a failed flush
+    pub fn restore(&mut self, counters: Vec<(NaiveDate, String, UsageCounter)>) {
+        for (date, command, counter) in counters {
+            let existing = self
+                .0
+                .entry((date, command))
+                .or_insert_with(UsageCounter::default);
+            existing.invocations += counter.invocations;
+            existing.errors += counter.errors;
+            for user in counter.users {
+                if existing.users.len() >= USER_CAP {
+                    break;
+                }
+                existing.users.insert(user);
+            }
+        }
+    }
+
+    /// Whether there is nothing to flush
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Represents a daily rollup as it's saved in the collection
+pub struct UsageRollup {
+    /// Day in `YYYY-MM-DD` format (UTC)
+    pub date: String,
+    /// Command name
+    pub command: String,
+    /// Amount of invocations
+    pub invocations: i64,
+    /// Amount of invocations that returned an error
+    #[serde(default)]
+    pub errors: i64,
+    /// Discord IDs of users who used the command
+    #[serde(default)]
+    pub users: Vec<u64>,
+}
+
+/// Wrapper around the command usage collection
+pub struct UsageCollection {
+    collection: Collection,
+}
+
+impl UsageCollection {
+    /// Creates the wrapper
+    pub fn new(database: &Database) -> UsageCollection {
+        UsageCollection {
+            collection: database.collection("command_usage"),
+        }
+    }
+
+    /// Adds drained counters to the daily rollups
+    ///
+    /// Returns the counters that could not be written, so they can be restored and retried later.
+    pub async fn flush(
+        &self,
+        counters: Vec<(NaiveDate, String, UsageCounter)>,
+    ) -> Vec<(NaiveDate, String, UsageCounter)> {
+        let mut failed = Vec::new();
+
+        for (date, command, counter) in counters {
+            let users: Vec<u64> = counter.users.iter().copied().collect();
+            let options = UpdateOptions::builder().upsert(true).build();
+            let result = self
+                .collection
+                .update_one(
+                    doc! {"date": date.format(DATE_FORMAT).to_string(), "command": &command},
+                    doc! {
+                        "$inc": {"invocations": counter.invocations, "errors": counter.errors},
+                        "$addToSet": {"users": {"$each": users}}
+                    },
+                    options,
+                )
+                .await;
+
+            if result.is_err() {
+                failed.push((date, command, counter));
+            }
+        }
+
+        if !failed.is_empty() {
+            tracing::warn!("Could not flush {} usage counters", failed.len());
+        }
+        failed
+    }
+
+    /// Daily rollups of the last `days` days, optionally only for a single command
+    pub async fn get_usage(
+        &self,
+        command: Option<&str>,
+        days: i64,
+        now: DateTime<Utc>,
+    ) -> DatabaseResult<Vec<UsageRollup>> {
+        let since = (now - Duration::days(days - 1))
+            .format(DATE_FORMAT)
+            .to_string();
+        let mut filter = doc! {"date": {"$gte": since}};
+        if let Some(command) = command {
+            filter.insert("command", command);
+        }
+        crate::database::get_entries(&self.collection, filter).await
+    }
+
+    /// Deletes rollups older than `max_age`, returns how many were deleted
+    pub async fn prune(&self, max_age: Duration, now: DateTime<Utc>) -> DatabaseResult<i64> {
+        self.collection
+            .delete_many(doc! {"date": {"$lt": retention_cutoff(max_age, now)}}, None)
+            .await
+            .map(|result| result.deleted_count)
+            .map_err(mongo_error)
+    }
+
+    /// Wipes all rollups from the collection
+    ///
+    /// Created for testing purposes, don't actually use this on a live database please
+    pub async fn remove_all(&self) -> DatabaseResult<()> {
+        self.collection.drop(None).await.map_err(mongo_error)
+    }
+}
+
+/// Date of the oldest rollup kept by [`UsageCollection::prune()`], rollups of earlier days are deleted
+///
+/// Dates sort chronologically as strings, so this can be compared to the `date` field directly.
+fn retention_cutoff(max_age: Duration, now: DateTime<Utc>) -> String {
+    (now - max_age).format(DATE_FORMAT).to_string()
+}
+
+/// Sums rollups per day, over all commands
+///
+/// Returns `(date, invocations, errors, unique users)` for each of the last `days` days, oldest first,
+/// including days without any usage. Users are counted once per day, even across commands.
+pub fn daily_totals(
+    rollups: &[UsageRollup],
+    days: i64,
+    now: DateTime<Utc>,
+) -> Vec<(String, i64, i64, usize)> {
+    (0..days)
+        .rev()
+        .map(|offset| {
+            let date = (now - Duration::days(offset))
+                .format(DATE_FORMAT)
+                .to_string();
+            let of_day: Vec<&UsageRollup> = rollups.iter().filter(|r| r.date == date).collect();
+            let users: HashSet<u64> = of_day
+                .iter()
+                .flat_map(|r| r.users.iter().copied())
+                .collect();
+            (
+                date,
+                of_day.iter().map(|r| r.invocations).sum(),
+                of_day.iter().map(|r| r.errors).sum(),
+                users.len(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    // Deterministic pseudo random user IDs out of `distinct` different ones
+    fn synthetic_users(count: usize, distinct: u64) -> Vec<u64> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                100_000 + state % distinct
+            })
+            .collect()
+    }
+
+    // What a flush would leave in the collection, for a fresh collection
+    fn as_rollups(drained: Vec<(NaiveDate, String, UsageCounter)>) -> Vec<UsageRollup> {
+        drained
+            .into_iter()
+            .map(|(date, command, counter)| UsageRollup {
+                date: date.format(DATE_FORMAT).to_string(),
+                command,
+                invocations: counter.invocations,
+                errors: counter.errors,
+                users: counter.users.into_iter().collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn invocations_are_counted_per_day_and_command() {
+        let mut counters = UsageCounters::default();
+        counters.record("register", 1, false, now());
+        counters.record("register", 1, true, now());
+        counters.record("register", 2, false, now() + Duration::days(1));
+        counters.record("stats", 1, false, now());
+
+        let mut drained = counters.drain();
+        assert!(counters.is_empty());
+        drained.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        let summary: Vec<(String, &str, i64, i64, usize)> = drained
+            .iter()
+            .map(|(date, command, c)| {
+                (
+                    date.to_string(),
+                    command.as_str(),
+                    c.invocations,
+                    c.errors,
+                    c.users.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("2021-03-14".to_string(), "register", 2, 1, 1),
+                ("2021-03-14".to_string(), "stats", 1, 0, 1),
+                ("2021-03-15".to_string(), "register", 1, 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn unique_users_are_exact_below_the_cap() {
+        let users = synthetic_users(5000, 300);
+        let distinct: HashSet<u64> = users.iter().copied().collect();
+        assert!(distinct.len() < USER_CAP);
+
+        let mut counters = UsageCounters::default();
+        for user in &users {
+            counters.record("register", *user, false, now());
+        }
+        let drained = counters.drain();
+        assert_eq!(drained[0].2.invocations, 5000);
+        assert_eq!(drained[0].2.users, distinct);
+    }
+
+    #[test]
+    fn unique_users_are_a_lower_bound_above_the_cap() {
+        let users = synthetic_users(20_000, 5000);
+        let distinct: HashSet<u64> = users.iter().copied().collect();
+        assert!(distinct.len() > USER_CAP);
+
+        let mut counters = UsageCounters::default();
+        for user in &users {
+            counters.record("register", *user, false, now());
+        }
+        let counter = &counters.drain()[0].2;
+        assert_eq!(counter.users.len(), USER_CAP);
+        assert!(counter.users.is_subset(&distinct));
+        assert_eq!(counter.invocations, 20_000);
+    }
+
+    #[test]
+    fn failed_flushes_are_restored() {
+        let mut counters = UsageCounters::default();
+        counters.record("register", 1, false, now());
+        let failed = counters.drain();
+
+        // recorded while the flush was running
+        counters.record("register", 2, true, now());
+        counters.restore(failed);
+
+        let drained = counters.drain();
+        assert_eq!(drained.len(), 1);
+        let counter = &drained[0].2;
+        assert_eq!((counter.invocations, counter.errors), (2, 1));
+        assert_eq!(
+            counter.users,
+            [1, 2].iter().copied().collect::<HashSet<u64>>()
+        );
+    }
+
+    #[test]
+    fn restoring_keeps_the_cap() {
+        let mut counters = UsageCounters::default();
+        for user in synthetic_users(2000, 1000) {
+            counters.record("register", user, false, now());
+        }
+        let failed = counters.drain();
+        for user in synthetic_users(10, 10) {
+            counters.record("register", user + 1_000_000, false, now());
+        }
+        counters.restore(failed);
+        assert_eq!(counters.drain()[0].2.users.len(), USER_CAP);
+    }
+
+    #[test]
+    fn daily_totals_count_users_once_per_day() {
+        let mut counters = UsageCounters::default();
+        counters.record("register", 1, false, now());
+        counters.record("stats", 1, true, now());
+        counters.record("stats", 2, false, now());
+        counters.record("stats", 3, false, now() - Duration::days(2));
+        // outside the range
+        counters.record("stats", 4, false, now() - Duration::days(3));
+        let rollups = as_rollups(counters.drain());
+
+        assert_eq!(
+            daily_totals(&rollups, 3, now()),
+            vec![
+                ("2021-03-12".to_string(), 1, 0, 1),
+                ("2021-03-13".to_string(), 0, 0, 0),
+                ("2021-03-14".to_string(), 3, 1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn rollups_before_the_retention_cutoff_are_pruned() {
+        let cutoff = retention_cutoff(Duration::days(365), now());
+        assert_eq!(cutoff, "2020-03-14");
+        // the same comparison the collection does
+        assert!("2020-03-13".to_string() < cutoff);
+        assert!("2020-03-14".to_string() >= cutoff);
+        assert!("2021-01-01".to_string() >= cutoff);
+
+        // the day of the cutoff is kept, even late in the day
+        let late = Utc.ymd(2021, 3, 14).and_hms(23, 59, 59);
+        assert_eq!(retention_cutoff(Duration::days(1), late), "2021-03-13");
+    }
+}
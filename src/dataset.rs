@@ -0,0 +1,378 @@
+//! Anonymized per-tournament datasets for community statisticians
+//!
+//! Players opt in with `.datasharing on`. In a research dataset, consenting players are listed by
+//! name with every field, everyone else appears under a pseudonym with only coarse fields. The
+//! public dataset lists everyone like a non-consenting player.
+//!
+//! What a player's row contains is decided by [`allowed()`], from their [`Consent`] and the
+//! [`Sensitivity`] of each [`Field`]. Every field has to be classified in [`Field::sensitivity()`],
+//! so a new field can't end up in the dataset without deciding who may see it.
+//!
+//! Pseudonyms are an HMAC-SHA256 of the Tetrio ID, keyed with the `DATASET_SECRET` environment
+//! variable and salted with the tournament shorthand. They can't be reversed without the secret,
+//! and the same player gets a different pseudonym in every tournament, so datasets can't be joined.
+//! Without a secret, no datasets can be published.
+
+#![warn(missing_docs)]
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::tetrio::leaderboard::LeaderboardUser;
+use crate::tetrio::Rank;
+
+/// Environment variable holding the pseudonym secret
+pub const SECRET_ENV: &str = "DATASET_SECRET";
+
+/// Amount of bytes of the HMAC that are kept for a pseudonym
+const PSEUDONYM_BYTES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Whether a player agreed to share their data
+pub enum Consent {
+    /// Opted in with `.datasharing on`
+    Shared,
+    /// Didn't opt in, or the dataset is public
+    Pseudonymous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How much a field tells about a player
+pub enum Sensitivity {
+    /// Broad enough to be shared about anyone, like a rank bucket
+    Coarse,
+    /// Detailed stats that make a player easy to pick out
+    Extended,
+    /// Names the player directly
+    Identifying,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A column of the dataset
+pub enum Field {
+    /// Username for consenting players, see [`Field::Pseudonym`] for everyone else
+    Username,
+    /// Irreversible per-tournament pseudonym
+    Pseudonym,
+    /// Rank group on announcement day, see [`rank_bucket()`]
+    RankBucket,
+    /// Tetra rating on announcement day
+    AnnouncementRating,
+    /// APM on announcement day
+    AnnouncementApm,
+    /// PPS on announcement day
+    AnnouncementPps,
+    /// VS score on announcement day
+    AnnouncementVs,
+    /// Tetra rating at the time of publishing
+    CurrentRating,
+    /// APM at the time of publishing
+    CurrentApm,
+    /// PPS at the time of publishing
+    CurrentPps,
+    /// VS score at the time of publishing
+    CurrentVs,
+}
+
+impl Field {
+    /// Every field, in column order
+    pub const ALL: [Field; 11] = [
+        Field::Username,
+        Field::Pseudonym,
+        Field::RankBucket,
+        Field::AnnouncementRating,
+        Field::AnnouncementApm,
+        Field::AnnouncementPps,
+        Field::AnnouncementVs,
+        Field::CurrentRating,
+        Field::CurrentApm,
+        Field::CurrentPps,
+        Field::CurrentVs,
+    ];
+
+    /// Column name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Username => "username",
+            Field::Pseudonym => "pseudonym",
+            Field::RankBucket => "rank_bucket",
+            Field::AnnouncementRating => "announcement_tr",
+            Field::AnnouncementApm => "announcement_apm",
+            Field::AnnouncementPps => "announcement_pps",
+            Field::AnnouncementVs => "announcement_vs",
+            Field::CurrentRating => "current_tr",
+            Field::CurrentApm => "current_apm",
+            Field::CurrentPps => "current_pps",
+            Field::CurrentVs => "current_vs",
+        }
+    }
+
+    /// How much the field tells about a player
+    pub fn sensitivity(&self) -> Sensitivity {
+        match self {
+            Field::Username => Sensitivity::Identifying,
+            Field::Pseudonym | Field::RankBucket => Sensitivity::Coarse,
+            Field::AnnouncementRating
+            | Field::AnnouncementApm
+            | Field::AnnouncementPps
+            | Field::AnnouncementVs
+            | Field::CurrentRating
+            | Field::CurrentApm
+            | Field::CurrentPps
+            | Field::CurrentVs => Sensitivity::Extended,
+        }
+    }
+}
+
+/// Whether a field may be shared about a player
+pub fn allowed(consent: Consent, field: Field) -> bool {
+    match (consent, field.sensitivity()) {
+        (_, Sensitivity::Coarse) => true,
+        (Consent::Shared, _) => true,
+        (Consent::Pseudonymous, _) => false,
+    }
+}
+
+/// Rank group a rank falls into, so single ranks of rare players don't stand out
+pub fn rank_bucket(rank: Rank) -> &'static str {
+    match rank {
+        Rank::Unranked => "unranked",
+        Rank::D | Rank::DPlus => "D",
+        Rank::CMinus | Rank::C | Rank::CPlus => "C",
+        Rank::BMinus | Rank::B | Rank::BPlus => "B",
+        Rank::AMinus | Rank::A | Rank::APlus => "A",
+        Rank::SMinus | Rank::S | Rank::SPlus => "S",
+        Rank::SS | Rank::U | Rank::X => "SS+",
+    }
+}
+
+lazy_static! {
+    static ref KEY: Option<DatasetKey> = std::env::var(SECRET_ENV)
+        .ok()
+        .and_then(|secret| DatasetKey::new(secret.as_bytes()));
+}
+
+/// Dataset key taken from the environment, `None` if datasets are disabled
+pub fn key() -> Option<&'static DatasetKey> {
+    KEY.as_ref()
+}
+
+#[derive(Clone)]
+/// Secret used to derive pseudonyms
+pub struct DatasetKey(Vec<u8>);
+
+impl DatasetKey {
+    /// Creates a key, an empty secret counts as no secret
+    pub fn new(secret: &[u8]) -> Option<DatasetKey> {
+        if secret.is_empty() {
+            None
+        } else {
+            Some(DatasetKey(secret.to_vec()))
+        }
+    }
+
+    /// Pseudonym of a player in a tournament, as lowercase hex
+    pub fn pseudonym(&self, shorthand: &str, tetrio_id: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.0).expect("HMAC accepts any key length");
+        mac.input(format!("{}:{}", shorthand.to_lowercase(), tetrio_id).as_bytes());
+        mac.result().code()[..PSEUDONYM_BYTES]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for DatasetKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatasetKey(..)")
+    }
+}
+
+/// Everything known about a registered player, before any field is withheld
+pub struct PlayerData<'a> {
+    /// Tetrio ID, only used for the pseudonym
+    pub tetrio_id: &'a str,
+    /// Whether the player agreed to share their data
+    pub consent: Consent,
+    /// Stats on announcement day, `None` if they were unranked
+    pub announcement: Option<&'a LeaderboardUser>,
+    /// Current stats, `None` if they aren't known
+    pub current: Option<&'a LeaderboardUser>,
+}
+
+/// Value of a field, regardless of whether it may be shared
+fn value(field: Field, player: &PlayerData, key: &DatasetKey, shorthand: &str) -> Option<String> {
+    let stat = |user: Option<&LeaderboardUser>, pick: fn(&LeaderboardUser) -> Option<f64>| {
+        user.and_then(pick).map(|v| format!("{:.2}", v))
+    };
+    match field {
+        Field::Username => player
+            .current
+            .or(player.announcement)
+            .map(|u| u.username.clone()),
+        Field::Pseudonym => Some(key.pseudonym(shorthand, player.tetrio_id)),
+        Field::RankBucket => Some(
+            rank_bucket(player.announcement.map_or(Rank::Unranked, |u| {
+                u.league.rank.parse().unwrap_or(Rank::Unranked)
+            }))
+            .to_string(),
+        ),
+        Field::AnnouncementRating => stat(player.announcement, |u| Some(u.league.rating)),
+        Field::AnnouncementApm => stat(player.announcement, |u| u.league.apm),
+        Field::AnnouncementPps => stat(player.announcement, |u| u.league.pps),
+        Field::AnnouncementVs => stat(player.announcement, |u| u.league.vs),
+        Field::CurrentRating => stat(player.current, |u| Some(u.league.rating)),
+        Field::CurrentApm => stat(player.current, |u| u.league.apm),
+        Field::CurrentPps => stat(player.current, |u| u.league.pps),
+        Field::CurrentVs => stat(player.current, |u| u.league.vs),
+    }
+}
+
+/// Row of a player, fields that may not be shared are empty
+///
+/// Withheld and unknown values look the same, so an empty cell doesn't reveal anything either.
+pub fn row(player: &PlayerData, key: &DatasetKey, shorthand: &str) -> Vec<String> {
+    Field::ALL
+        .iter()
+        .map(|field| {
+            if allowed(player.consent, *field) {
+                value(*field, player, key, shorthand).unwrap_or_default()
+            } else {
+                String::new()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::leaderboard::LeagueData;
+
+    const TETRIO_ID: &str = "5e47696db7c60f23a497ee6c";
+
+    fn test_key() -> DatasetKey {
+        DatasetKey::new(b"secret").unwrap()
+    }
+
+    fn user(rating: f64) -> LeaderboardUser {
+        LeaderboardUser {
+            _id: TETRIO_ID.to_string(),
+            username: "caboozled_pie".to_string(),
+            role: "user".to_string(),
+            country: Some("DE".to_string()),
+            supporter: Some(false),
+            verified: false,
+            league: LeagueData {
+                gamesplayed: 100,
+                gameswon: 50,
+                rating,
+                rank: Rank::S.to_str().to_string(),
+                glicko: Some(1500f64),
+                rd: Some(80f64),
+                apm: Some(30f64),
+                pps: Some(1.5),
+                vs: Some(60f64),
+            },
+        }
+    }
+
+    #[test]
+    fn pseudonyms_are_stable_per_tournament() {
+        let pseudonym = test_key().pseudonym("UC12", TETRIO_ID);
+        assert_eq!(pseudonym, "c62f5722c4299e73");
+        assert_eq!(test_key().pseudonym("uc12", TETRIO_ID), pseudonym);
+
+        // another salt, another secret or another player all give another pseudonym
+        assert_ne!(test_key().pseudonym("UC13", TETRIO_ID), pseudonym);
+        let other_key = DatasetKey::new(b"other secret").unwrap();
+        assert_ne!(other_key.pseudonym("UC12", TETRIO_ID), pseudonym);
+        assert_ne!(
+            test_key().pseudonym("UC12", "5e47696db7c60f23a497ee6d"),
+            pseudonym
+        );
+    }
+
+    #[test]
+    fn empty_secrets_disable_datasets() {
+        assert!(DatasetKey::new(b"").is_none());
+        assert_eq!(format!("{:?}", test_key()), "DatasetKey(..)");
+    }
+
+    #[test]
+    fn consent_decides_which_fields_are_shared() {
+        let fields = |consent: Consent| -> Vec<Field> {
+            Field::ALL
+                .iter()
+                .copied()
+                .filter(|field| allowed(consent, *field))
+                .collect()
+        };
+
+        // Listed by hand on purpose, a new field has to be added here to be shared at all
+        assert_eq!(fields(Consent::Shared), Field::ALL.to_vec());
+        assert_eq!(
+            fields(Consent::Pseudonymous),
+            vec![Field::Pseudonym, Field::RankBucket]
+        );
+    }
+
+    #[test]
+    fn rows_withhold_everything_not_allowed() {
+        let (announcement, current) = (user(20000f64), user(21000.5));
+        let mut player = PlayerData {
+            tetrio_id: TETRIO_ID,
+            consent: Consent::Pseudonymous,
+            announcement: Some(&announcement),
+            current: Some(&current),
+        };
+
+        let pseudonymous = row(&player, &test_key(), "UC12");
+        assert_eq!(pseudonymous.len(), Field::ALL.len());
+        assert_eq!(pseudonymous[0], "");
+        assert_eq!(pseudonymous[1], "c62f5722c4299e73");
+        assert_eq!(pseudonymous[2], "S");
+        assert!(pseudonymous[3..].iter().all(String::is_empty));
+
+        player.consent = Consent::Shared;
+        let shared = row(&player, &test_key(), "UC12");
+        assert_eq!(
+            shared,
+            vec![
+                "caboozled_pie",
+                "c62f5722c4299e73",
+                "S",
+                "20000.00",
+                "30.00",
+                "1.50",
+                "60.00",
+                "21000.50",
+                "30.00",
+                "1.50",
+                "60.00",
+            ]
+        );
+    }
+
+    #[test]
+    fn unranked_players_have_no_stats() {
+        let player = PlayerData {
+            tetrio_id: TETRIO_ID,
+            consent: Consent::Shared,
+            announcement: None,
+            current: None,
+        };
+        let row = row(&player, &test_key(), "UC12");
+        assert_eq!(row[2], "unranked");
+        assert_eq!(row[0], "");
+        assert!(row[3..].iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn rank_buckets() {
+        assert_eq!(rank_bucket(Rank::DPlus), "D");
+        assert_eq!(rank_bucket(Rank::APlus), "A");
+        assert_eq!(rank_bucket(Rank::X), "SS+");
+        assert_eq!(rank_bucket(Rank::Unranked), "unranked");
+    }
+}
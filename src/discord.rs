@@ -7,16 +7,17 @@
 //! ```
 //! use uc_helper_rust as uc;
 //!
-//! let db = uc::database::connect().expect("Failed to connect to database");
+//! let db = uc::database::connect().await.expect("Failed to connect to database");
 //! let mut bot = uc::discord::new_client(db).await;
 //!     if let Err(why) = bot.start().await {
 //!     println!("Client error: {:?}", why);
 //! }
 //! ```
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+use chrono::{DateTime, Duration, Utc};
 use serenity::framework::standard::{
     help_commands,
     macros::{group, help, hook},
@@ -35,7 +36,21 @@ use serenity::{
 use tracing::{error, info};
 
 use crate::commands::{global::*, owner::*, player::*, staff::*, tournament::*};
+use crate::database::settings::GuildSettings;
+use crate::database::usage::UsageCounters;
 use crate::database::LocalDatabase;
+use crate::member_watch::MemberEvent;
+use crate::metrics::Metrics;
+use crate::status::StatusReport;
+use crate::tasks::TaskRegistry;
+
+pub mod auto_update;
+pub mod janitor;
+pub mod messages;
+pub mod news;
+pub mod report;
+pub mod scheduler;
+pub mod usage;
 
 pub const PREFIX: &str = ".";
 pub const CONFIRM_EMOJI: &str = "✅";
@@ -43,7 +58,20 @@ pub const ERROR_EMOJI: &str = "❌";
 pub const UC_GUILD_ID: u64 = 718603683624910941;
 
 #[group]
-#[commands(owner_ping, owner_echo)]
+#[commands(
+    owner_ping,
+    owner_echo,
+    janitor,
+    auto_update,
+    bot_stats,
+    status,
+    flags,
+    sync_from_file,
+    jobs,
+    settings,
+    staff_register_bulk,
+    find_duplicates
+)]
 #[owners_only]
 struct Owner;
 
@@ -51,11 +79,48 @@ struct Owner;
 #[commands(
     update_all,
     update_registered,
+    purge_overranked,
+    rd_check,
+    inactive_check,
+    tetrio_status,
     staff_register,
     staff_unregister,
     staff_link,
     staff_unlink,
-    set_active
+    suggest_link,
+    set_active,
+    set_inactive,
+    bracket_split,
+    pairings_preview,
+    export_challonge,
+    lookup,
+    registration_history,
+    orphaned_links,
+    audit_links,
+    missing_seeds,
+    seeding,
+    check_in_status,
+    reset_checkin,
+    force_check_in,
+    force_check_out,
+    close_check_in,
+    setup_status,
+    tasks,
+    command_usage,
+    who_is_bulk,
+    verify_receipt,
+    timezone_report,
+    distribution,
+    possible_dupes,
+    publish_dataset,
+    review,
+    qualify,
+    set_dates,
+    set_participant_role,
+    set_cap,
+    waitlist,
+    sync_roles,
+    set_news_channel
 )]
 #[checks(has_staff_role)]
 #[only_in(guilds)]
@@ -64,7 +129,20 @@ struct Staff;
 
 #[group]
 #[checks(bot_channel_check)]
-#[commands(stats, link, unlink)]
+#[commands(
+    stats,
+    aka,
+    versus,
+    recent,
+    leaderboard,
+    announcement_stats,
+    link,
+    verify_link,
+    confirm_link,
+    unlink,
+    datasharing,
+    language
+)]
 #[description("Tetr.io player related commands")]
 struct Player;
 
@@ -75,12 +153,21 @@ struct Global;
 
 #[group]
 #[commands(
+    create_tournament,
     add_snapshot,
+    snapshot_diff,
+    list_snapshots,
+    use_snapshot,
     create_check_in,
     export_check_in,
     resume_check_in,
     register,
-    unregister
+    withdraw,
+    receipt,
+    can_participate,
+    player_list,
+    country_stats,
+    tournament_info
 )]
 #[only_in(guilds)]
 #[checks(bot_channel_check)]
@@ -102,14 +189,12 @@ async fn bot_channel_check(
         return Ok(());
     }
 
-    let allowed_channels: Vec<u64> = vec![
-        901939376815218719, // register
-        752703502173863966, // bot spam
-        776806403884056616, // bot testing
-    ];
-
-    if !allowed_channels.contains(&msg.channel_id.0) {
-        return Err(Reason::Log("Not in correct channel".to_string()));
+    // DMs are not restricted by the bot channels
+    if let Some(guild_id) = msg.guild_id {
+        let settings = get_guild_settings(&ctx, guild_id.0).await;
+        if !settings.is_bot_channel(msg.channel_id.0) {
+            return Err(Reason::Log("Not in correct channel".to_string()));
+        }
     }
 
     Ok(())
@@ -141,18 +226,28 @@ async fn has_staff_role(
                 None => return Err(Reason::Log("Not in guild".to_string())),
             };
 
-            let roles = ctx
-                .cache
-                .guild_field(guild_id, |guild| guild.roles.clone())
-                .await
-                .unwrap();
-
-            let staff_role = match roles.values().find(|role| role.name == "Staff") {
-                Some(role) => role,
-                None => return Err(Reason::Log("No staff role on guild".to_string())),
+            let settings = get_guild_settings(&ctx, guild_id.0).await;
+            let staff_roles: Vec<RoleId> = if settings.staff_roles.is_empty() {
+                // Nothing configured, fall back to matching the role name
+                ctx.cache
+                    .guild_field(guild_id, |guild| {
+                        guild
+                            .roles
+                            .values()
+                            .filter(|role| role.name == settings.staff_role_name)
+                            .map(|role| role.id)
+                            .collect()
+                    })
+                    .await
+                    .unwrap_or_default()
+            } else {
+                settings.staff_roles.iter().map(|id| RoleId(*id)).collect()
             };
+            if staff_roles.is_empty() {
+                return Err(Reason::Log("No staff role on guild".to_string()));
+            }
 
-            match member.roles.contains(&staff_role.id) {
+            match member.roles.iter().any(|role| staff_roles.contains(role)) {
                 true => Ok(()),
                 false => Err(Reason::Log("No staff role".to_string())),
             }
@@ -172,24 +267,48 @@ pub async fn new_client(database: LocalDatabase) -> Client {
             GatewayIntents::GUILDS
                 | GatewayIntents::GUILD_MESSAGES
                 | GatewayIntents::DIRECT_MESSAGES
-                | GatewayIntents::GUILD_MESSAGE_REACTIONS,
+                | GatewayIntents::GUILD_MESSAGE_REACTIONS
+                | GatewayIntents::GUILD_MEMBERS,
         )
         .await
         .expect("Couldn't create client");
 
     setup_shared_data(database, &client).await;
     setup_ctrl_c(&client);
+    janitor::spawn(client.data.clone());
+    scheduler::spawn(client.data.clone(), client.cache_and_http.http.clone());
+    news::spawn(client.data.clone(), client.cache_and_http.http.clone());
+    usage::spawn(client.data.clone());
+    auto_update::spawn(client.data.clone());
 
     client
 }
 
+// How long ctrl+c waits for loops like the check-in handling to finish their current work
+const SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+
 fn setup_ctrl_c(client: &Client) {
     let shard_manager = client.shard_manager.clone();
+    let data = client.data.clone();
 
     tokio::spawn(async move {
         tokio::signal::ctrl_c()
             .await
             .expect("Could not register ctrl+c handler");
+        info!("Shutting down, waiting for running loops to finish");
+        let shutdown = data.read().await.get::<Shutdown>().cloned();
+        if let Some(shutdown) = shutdown {
+            if tokio::time::timeout(SHUTDOWN_GRACE, shutdown.trigger())
+                .await
+                .is_err()
+            {
+                error!(
+                    "Loops didn't finish within {:?}, shutting down anyway",
+                    SHUTDOWN_GRACE
+                );
+            }
+        }
+        usage::flush(&data).await;
         shard_manager.lock().await.shutdown_all().await;
     });
 }
@@ -229,17 +348,299 @@ async fn setup_shared_data(database: LocalDatabase, client: &Client) {
     let mut data = client.data.write().await;
     data.insert::<LocalDatabase>(Arc::new(database));
     data.insert::<ShardManagerContainer>(client.shard_manager.clone());
-    data.insert::<IdCollection>(Mutex::new(IdCollection(HashSet::new())));
+    data.insert::<Shutdown>(Arc::new(Shutdown::new()));
+    data.insert::<IdCollection>(Mutex::new(IdCollection(HashMap::new())));
+    data.insert::<PingCooldowns>(Mutex::new(PingCooldowns(HashMap::new())));
+    data.insert::<RefreshCooldowns>(Mutex::new(RefreshCooldowns(HashMap::new())));
+    data.insert::<DegradedServings>(Mutex::new(DegradedServings(0)));
+    data.insert::<SettingsCache>(Mutex::new(SettingsCache(HashMap::new())));
+    data.insert::<TaskRegistry>(Mutex::new(TaskRegistry::new()));
+    data.insert::<UsageCounters>(Mutex::new(UsageCounters::default()));
+    data.insert::<Metrics>(crate::metrics::global());
+    data.insert::<janitor::JanitorStatus>(Mutex::new(janitor::JanitorStatus::default()));
+    data.insert::<scheduler::SchedulerStatus>(Mutex::new(scheduler::SchedulerStatus::default()));
+    data.insert::<news::NewsWatcherStatus>(Mutex::new(news::NewsWatcherStatus::default()));
+    data.insert::<auto_update::AutoUpdateStatus>(Mutex::new(
+        auto_update::AutoUpdateStatus::from_env(),
+    ));
+}
+
+// Hardcoded configuration, reported in `.status`
+pub struct BotConfig;
+
+impl StatusReport for BotConfig {
+    fn name(&self) -> String {
+        "Configuration".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            ("Prefix".to_string(), PREFIX.to_string()),
+            ("Guild".to_string(), UC_GUILD_ID.to_string()),
+            (
+                "Player/Tournament groups".to_string(),
+                "bot channels only, staff bypass".to_string(),
+            ),
+            (
+                "Staff group".to_string(),
+                "staff role, guild only".to_string(),
+            ),
+            ("Owner group".to_string(), "bot owners only".to_string()),
+            (
+                "Bot channels, staff roles, check-in log".to_string(),
+                "per guild, see `.settings`".to_string(),
+            ),
+            (
+                "Legacy check-in channel".to_string(),
+                crate::commands::tournament::CHECK_IN_CHANNEL_ID.to_string(),
+            ),
+        ]
+    }
 }
 
 // Used during check-in to track which users do not require another confirmation message
 // Prevents the bot from reaching a rate limit by spamming reactions
-pub struct IdCollection(pub HashSet<u64>);
+// Maps the Discord ID to when it was added, so the janitor can drop old entries
+pub struct IdCollection(pub HashMap<u64, DateTime<Utc>>);
+
+impl IdCollection {
+    pub fn contains(&self, discord_id: u64) -> bool {
+        self.0.contains_key(&discord_id)
+    }
+
+    pub fn insert(&mut self, discord_id: u64) {
+        self.0.insert(discord_id, Utc::now());
+    }
+
+    // Removes entries older than `max_age`, returns how many were removed
+    pub fn prune(&mut self, max_age: Duration, now: DateTime<Utc>) -> usize {
+        let before = self.0.len();
+        self.0.retain(|_, added_at| now - *added_at <= max_age);
+        before - self.0.len()
+    }
+}
+
+impl StatusReport for IdCollection {
+    fn name(&self) -> String {
+        "Check-in".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Debounced reactions".to_string(), self.0.len().to_string())]
+    }
+}
 
 impl TypeMapKey for IdCollection {
     type Value = Mutex<IdCollection>;
 }
 
+// Last time a consolidated ping was sent, keyed by what was pinged
+// Prevents staff from spamming the same players
+pub struct PingCooldowns(pub HashMap<String, DateTime<Utc>>);
+
+impl PingCooldowns {
+    // Records a ping if the last one with the same key is older than `interval`
+    // Returns when pinging is allowed again otherwise
+    pub fn try_ping(
+        &mut self,
+        key: &str,
+        interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<(), DateTime<Utc>> {
+        if let Some(last) = self.0.get(key) {
+            if now - *last < interval {
+                return Err(*last + interval);
+            }
+        }
+        self.0.insert(key.to_string(), now);
+        Ok(())
+    }
+}
+
+impl StatusReport for PingCooldowns {
+    fn name(&self) -> String {
+        "Check-in".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Ping cooldowns".to_string(), self.0.len().to_string())]
+    }
+}
+
+impl TypeMapKey for PingCooldowns {
+    type Value = Mutex<PingCooldowns>;
+}
+
+// Tells long running loops that the bot is shutting down
+// Loops hold a guard while they run, shutting down waits until every guard is dropped,
+// so the current piece of work is never cut off halfway
+pub struct Shutdown {
+    signal: tokio::sync::watch::Sender<bool>,
+    receiver: tokio::sync::watch::Receiver<bool>,
+    running: Arc<tokio::sync::RwLock<()>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        let (signal, receiver) = tokio::sync::watch::channel(false);
+        Shutdown {
+            signal,
+            receiver,
+            running: Arc::new(tokio::sync::RwLock::new(())),
+        }
+    }
+
+    // Registers a running loop, which should stop once `ShutdownGuard::triggered()` returns
+    pub async fn guard(&self) -> ShutdownGuard {
+        ShutdownGuard {
+            receiver: self.receiver.clone(),
+            _running: Arc::clone(&self.running).read_owned().await,
+        }
+    }
+
+    // Signals every loop to stop and waits until all of them did
+    pub async fn trigger(&self) {
+        let _ = self.signal.send(true);
+        // only returns once every guard is dropped, the write guard itself isn't needed
+        drop(self.running.write().await);
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+pub struct ShutdownGuard {
+    receiver: tokio::sync::watch::Receiver<bool>,
+    _running: tokio::sync::OwnedRwLockReadGuard<()>,
+}
+
+impl ShutdownGuard {
+    // Returns once shutting down was requested, meant to be used in `tokio::select!`
+    pub async fn triggered(&mut self) {
+        while !*self.receiver.borrow() {
+            if self.receiver.changed().await.is_err() {
+                // nobody can trigger anymore
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl TypeMapKey for Shutdown {
+    type Value = Arc<Shutdown>;
+}
+
+// Last forced `.stats` refresh of each Discord user
+// Keeps people from requesting the Tetrio API over and over
+pub struct RefreshCooldowns(pub HashMap<u64, DateTime<Utc>>);
+
+impl RefreshCooldowns {
+    // Records a refresh if the user's last one is older than `interval`
+    // Returns when refreshing is allowed again otherwise. Expired entries are dropped on the way.
+    pub fn try_refresh(
+        &mut self,
+        discord_id: u64,
+        interval: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<(), DateTime<Utc>> {
+        self.0.retain(|_, last| now - *last < interval);
+        if let Some(last) = self.0.get(&discord_id) {
+            return Err(*last + interval);
+        }
+        self.0.insert(discord_id, now);
+        Ok(())
+    }
+}
+
+impl StatusReport for RefreshCooldowns {
+    fn name(&self) -> String {
+        "Stats".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Refresh cooldowns".to_string(), self.0.len().to_string())]
+    }
+}
+
+impl TypeMapKey for RefreshCooldowns {
+    type Value = Mutex<RefreshCooldowns>;
+}
+
+// Amount of `.stats` replies that were served from the Tetrio API while the database was down
+pub struct DegradedServings(pub usize);
+
+impl StatusReport for DegradedServings {
+    fn name(&self) -> String {
+        "Database".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Degraded .stats servings".to_string(), self.0.to_string())]
+    }
+}
+
+impl TypeMapKey for DegradedServings {
+    type Value = Mutex<DegradedServings>;
+}
+
+// Copy of the guild settings, so checks don't need to query the database for every command
+// Filled on first use and replaced whenever a setting is changed through the bot
+pub struct SettingsCache(pub HashMap<u64, GuildSettings>);
+
+impl StatusReport for SettingsCache {
+    fn name(&self) -> String {
+        "Settings".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![("Cached guilds".to_string(), self.0.len().to_string())]
+    }
+}
+
+impl TypeMapKey for SettingsCache {
+    type Value = Mutex<SettingsCache>;
+}
+
+// Settings of a guild, from the cache if possible
+// Falls back to defaults without caching them if the database can't be read
+pub async fn get_guild_settings(ctx: &Context, guild_id: u64) -> GuildSettings {
+    {
+        let data_read = ctx.data.read().await;
+        if let Some(cache) = data_read.get::<SettingsCache>() {
+            if let Some(settings) = cache.lock().await.0.get(&guild_id) {
+                return settings.clone();
+            }
+        }
+    }
+
+    let db = get_database(&ctx).await;
+    match db.settings.get_guild(guild_id).await {
+        Ok(settings) => {
+            cache_guild_settings(&ctx, settings.clone()).await;
+            settings
+        }
+        Err(err) => {
+            error!("Could not read settings of {}: {}", guild_id, err);
+            GuildSettings::new(guild_id)
+        }
+    }
+}
+
+// Replaces the cached copy after a setting was saved
+pub async fn cache_guild_settings(ctx: &Context, settings: GuildSettings) {
+    let data_read = ctx.data.read().await;
+    if let Some(cache) = data_read.get::<SettingsCache>() {
+        cache.lock().await.0.insert(settings.guild_id, settings);
+    }
+}
+
+impl TypeMapKey for TaskRegistry {
+    type Value = Mutex<TaskRegistry>;
+}
+
 pub async fn get_database(ctx: &Context) -> Arc<LocalDatabase> {
     let data_read = ctx.data.read().await;
     data_read
@@ -280,6 +681,18 @@ async fn after_command(
     command_name: &str,
     command_result: CommandResult,
 ) {
+    if let Some(counters) = ctx.data.read().await.get::<UsageCounters>() {
+        counters.lock().await.record(
+            command_name,
+            msg.author.id.0,
+            command_result.is_err(),
+            Utc::now(),
+        );
+    }
+    if let Some(metrics) = ctx.data.read().await.get::<Metrics>() {
+        metrics.record_command(command_name);
+    }
+
     match command_result {
         Ok(()) => {
             info!("Processed command '{}'", command_name);
@@ -304,6 +717,51 @@ impl EventHandler for Handler {
     async fn resume(&self, _ctx: Context, _: ResumedEvent) {
         info!("Resumed");
     }
+
+    // Catch the check-in message being deleted right away, instead of on the next fetch
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        let db = get_database(&ctx).await;
+        if let Ok(Some(tournament)) = db.tournaments.get_active().await {
+            if crate::commands::tournament::check_in_channel(&tournament) == channel_id
+                && tournament.check_in_msg == Some(deleted_message_id.0)
+            {
+                util::alert_staff(
+                    &ctx,
+                    &crate::commands::tournament::check_in_lost_alert(&tournament),
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _member_data_if_available: Option<Member>,
+    ) {
+        if guild_id.0 == UC_GUILD_ID {
+            crate::commands::tournament::member_changed(&ctx, user.id.0, MemberEvent::Left).await;
+        }
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, guild_id: GuildId, new_member: Member) {
+        if guild_id.0 == UC_GUILD_ID {
+            crate::commands::tournament::member_changed(
+                &ctx,
+                new_member.user.id.0,
+                MemberEvent::Joined,
+            )
+            .await;
+        }
+    }
 }
 
 struct ShardManagerContainer;
@@ -313,59 +771,302 @@ impl TypeMapKey for ShardManagerContainer {
 }
 
 pub mod util {
+    use std::collections::HashMap;
+    use std::future::Future;
     use std::str::FromStr;
 
-    use chrono::{TimeZone, Utc};
-    use serenity::builder::CreateEmbed;
-    use serenity::framework::standard::CommandResult;
+    use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+    use reqwest::StatusCode;
+    use serenity::builder::{CreateEmbed, ParseValue};
+    use serenity::framework::standard::{Args, CommandResult};
+    use serenity::http::HttpError;
     use serenity::model::prelude::*;
     use serenity::prelude::*;
     use tokio::time;
 
-    use crate::database::players::PlayerEntry;
+    use crate::database::players::{AccountStatus, DiscordAccountStatus, PlayerEntry};
+    use crate::database::tournaments::{
+        MessageRef, SetupStatus, SetupStep, TournamentEntry, UnregisteredBy,
+    };
+    use crate::database::{DatabaseError, DatabaseResult};
     use crate::discord::{CONFIRM_EMOJI, ERROR_EMOJI};
+    use crate::tasks::{TaskHandle, TaskRegistry};
+    use crate::tetrio::leaderboard::LeaderboardUser;
+    use crate::tetrio::TetrioApiError;
+
+    pub const TETRIO_DOWN_MESSAGE: &str =
+        "Tetr.io appears to be down or under maintenance, using cached data where possible";
+
+    pub const TETRIO_ERROR_MESSAGE: &str = "Tetr.io returned an error, try again in a minute";
+
+    pub const DATABASE_DOWN_MESSAGE: &str =
+        "Database unavailable, showing live data, link status unknown";
+
+    pub fn is_tetrio_down(err: &DatabaseError) -> bool {
+        matches!(
+            err,
+            DatabaseError::TetrioApiError(TetrioApiError::CircuitOpen { .. })
+        )
+    }
+
+    // User facing message for failed Tetr.io requests
+    pub fn tetrio_error_reply(err: &TetrioApiError) -> &'static str {
+        match err {
+            TetrioApiError::CircuitOpen { .. } => TETRIO_DOWN_MESSAGE,
+            _ => TETRIO_ERROR_MESSAGE,
+        }
+    }
+
+    // Short prefix for replies concerning a tournament, like `[UC12]`
+    pub fn tournament_badge(tournament: &TournamentEntry) -> String {
+        format!("[{}]", tournament.shorthand)
+    }
 
-    pub fn player_data_to_embed(entry: &PlayerEntry) -> CreateEmbed {
+    // Prefixes a reply with the tournament badge
+    pub fn badged(tournament: &TournamentEntry, reply: &str) -> String {
+        format!("{} {}", tournament_badge(tournament), reply)
+    }
+
+    // Marks an embed as belonging to a tournament, keeping an existing author line (like the verified mark)
+    pub fn badge_embed(embed: &mut CreateEmbed, tournament: &TournamentEntry) {
+        let existing = embed
+            .0
+            .get("author")
+            .and_then(|author| author["name"].as_str())
+            .map(|name| name.to_string());
+        let name = match existing {
+            Some(existing) => format!("{} · {}", tournament.shorthand, existing),
+            None => tournament.shorthand.clone(),
+        };
+        embed.author(|a| a.name(name));
+    }
+
+    // Checklist of a tournament's configuration, with the command to run for every missing piece
+    pub fn setup_status_embed(shorthand: &str, status: &SetupStatus) -> CreateEmbed {
         let mut e = CreateEmbed::default();
+        e.title(format!("{}: Setup status", shorthand));
 
-        if let Some(player) = &entry.tetrio_data {
-            e.title(&player.username);
-            e.url(format!("https://ch.tetr.io/u/{}", player._id));
-            let league = &player.league;
-            let rank = crate::tetrio::Rank::from_str(&league.rank).unwrap();
-            e.color(u64::from_str_radix(rank.to_color(), 16).unwrap_or(0));
-            e.thumbnail(rank.to_img_url());
-            e.fields(vec![
-                (
-                    "Tetra Rating",
-                    format!(
-                        "{:.0} ± {:.1}",
-                        &league.rating,
-                        &league.rd.unwrap_or_default()
+        let lines: Vec<String> = status
+            .steps
+            .iter()
+            .map(|(step, done)| {
+                let (label, fix) = match step {
+                    SetupStep::Restrictions => (
+                        "Restrictions are valid",
+                        "Recreate the tournament with valid restrictions".to_string(),
                     ),
-                    false,
-                ),
-                (
-                    "APM",
-                    format!("{:.2}", &league.apm.unwrap_or_default()),
-                    true,
-                ),
-                (
-                    "PPS",
-                    format!("{:.2}", &league.pps.unwrap_or_default()),
-                    true,
-                ),
-                ("VS", format!("{:.2}", &league.vs.unwrap_or_default()), true),
-            ]);
+                    SetupStep::Active => ("Set as active", format!("`.set_active {}`", shorthand)),
+                    SetupStep::Snapshot => (
+                        "Announcement snapshot taken",
+                        format!("`.add_snapshot {}`", shorthand),
+                    ),
+                    SetupStep::CheckIn => (
+                        "Check-in message posted",
+                        "`.create_check_in` (once active)".to_string(),
+                    ),
+                };
+
+                if *done {
+                    format!("{} {}", CONFIRM_EMOJI, label)
+                } else {
+                    format!("{} {}: {}", ERROR_EMOJI, label, fix)
+                }
+            })
+            .collect();
+
+        e.description(lines.join("\n"));
+        e
+    }
+
+    pub const VERIFIED_LABEL: &str = "✓ Verified";
+
+    // Heart after the username of supporters
+    pub fn supporter_suffix(user: &LeaderboardUser) -> &'static str {
+        if user.supporter.unwrap_or(false) {
+            " ❤️"
+        } else {
+            ""
+        }
+    }
+
+    // Yes/no cell for reports
+    pub fn flag_cell(flag: bool) -> String {
+        if flag { "yes" } else { "no" }.to_string()
+    }
+
+    // Player stats with when they linked, and when they registered if they're in the given tournament
+    pub fn player_data_to_embed(
+        entry: &PlayerEntry,
+        tournament: Option<&TournamentEntry>,
+    ) -> CreateEmbed {
+        let mut e = match &entry.tetrio_data {
+            Some(player) => leaderboard_user_to_embed(player),
+            None => CreateEmbed::default(),
+        };
+
+        if let Some(linked_at) = entry.linked_at() {
+            e.field("Linked since", discord_timestamp(linked_at, 'R'), true);
+        }
+        let registration = tournament
+            .and_then(|t| t.registration(&entry.tetrio_id))
+            .filter(|r| r.is_active());
+        if let Some(registration) = registration {
+            e.field(
+                "Registered",
+                discord_timestamp(*registration.date, 'R'),
+                true,
+            );
         }
 
         if let Some(cache_data) = &entry.cache_data {
             e.timestamp(Utc.timestamp(cache_data.cached_at / 1000, 0).to_rfc3339());
         }
+        if entry.verified_link {
+            e.footer(|f| f.text("Link verified through the Tetr.io bio"));
+        }
 
         e
     }
 
+    // Embed for a player whose Tetrio account doesn't exist anymore, with the last known name
+    pub fn unavailable_account_embed(entry: &PlayerEntry, status: AccountStatus) -> CreateEmbed {
+        let username = entry
+            .tetrio_data
+            .as_ref()
+            .map_or(entry.tetrio_id.clone(), |data| data.username.clone());
+        let mut e = CreateEmbed::default();
+        e.title(username);
+        e.description(match status {
+            AccountStatus::Banned => "This account was banned from Tetr.io",
+            _ => "This account no longer exists on Tetr.io",
+        });
+        e.color(u64::from_str_radix(crate::tetrio::Rank::Unranked.to_color(), 16).unwrap_or(0));
+        if let Some(linked_at) = entry.linked_at() {
+            e.footer(|f| f.text(format!("Linked on {}", linked_at.format("%Y-%m-%d"))));
+        }
+        e
+    }
+
+    // Embed of the stats alone, without anything that needs a player entry
+    pub fn leaderboard_user_to_embed(player: &LeaderboardUser) -> CreateEmbed {
+        let mut e = CreateEmbed::default();
+
+        e.title(format!("{}{}", player.username, supporter_suffix(player)));
+        if player.verified {
+            e.author(|a| a.name(VERIFIED_LABEL));
+        }
+        e.url(format!("https://ch.tetr.io/u/{}", player._id));
+        let league = &player.league;
+        let rank = crate::tetrio::Rank::from_str(&league.rank).unwrap();
+        e.color(u64::from_str_radix(rank.to_color(), 16).unwrap_or(0));
+        e.thumbnail(rank.to_img_url());
+        e.fields(vec![
+            (
+                "Tetra Rating",
+                format!(
+                    "{:.0} ± {:.1}",
+                    &league.rating,
+                    &league.rd.unwrap_or_default()
+                ),
+                false,
+            ),
+            (
+                "APM",
+                format!("{:.2}", &league.apm.unwrap_or_default()),
+                true,
+            ),
+            (
+                "PPS",
+                format!("{:.2}", &league.pps.unwrap_or_default()),
+                true,
+            ),
+            ("VS", format!("{:.2}", &league.vs.unwrap_or_default()), true),
+        ]);
+
+        e
+    }
+
+    // One side of `versus_embed`, unranked players get dashes instead of league stats
+    fn versus_column(player: &LeaderboardUser) -> String {
+        let league = &player.league;
+        let rank =
+            crate::tetrio::Rank::from_str(&league.rank).unwrap_or(crate::tetrio::Rank::Unranked);
+        let ranked = rank != crate::tetrio::Rank::Unranked;
+        let stat = |value: Option<f64>| match value {
+            Some(value) if ranked => format!("{:.2}", value),
+            _ => "-".to_string(),
+        };
+        let win_rate = if league.gamesplayed > 0 {
+            format!(
+                "{:.1}%",
+                100.0 * league.gameswon as f64 / league.gamesplayed as f64
+            )
+        } else {
+            "-".to_string()
+        };
+
+        vec![
+            if ranked {
+                format!("Rank: {} {}", rank.to_emoji(), rank)
+            } else {
+                "Rank: -".to_string()
+            },
+            if ranked {
+                format!("TR: {:.0}", league.rating)
+            } else {
+                "TR: -".to_string()
+            },
+            format!("APM: {}", stat(league.apm)),
+            format!("PPS: {}", stat(league.pps)),
+            format!("VS: {}", stat(league.vs)),
+            format!("Games: {}", league.gamesplayed),
+            format!("Win rate: {}", win_rate),
+        ]
+        .join("\n")
+    }
+
+    // Side by side comparison of two players, for `.versus` and anything announcing matches
+    // The TR delta is only shown if both are ranked, the color is the rank of the higher rated one
+    pub fn versus_embed(left: &LeaderboardUser, right: &LeaderboardUser) -> CreateEmbed {
+        let rank_of = |player: &LeaderboardUser| {
+            crate::tetrio::Rank::from_str(&player.league.rank)
+                .unwrap_or(crate::tetrio::Rank::Unranked)
+        };
+        let ranked = |player: &LeaderboardUser| rank_of(player) != crate::tetrio::Rank::Unranked;
+
+        let mut e = CreateEmbed::default();
+        e.title(format!("{} vs {}", left.username, right.username));
+        let delta = if ranked(left) && ranked(right) {
+            let (ahead, behind) = if left.league.rating >= right.league.rating {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            e.color(u64::from_str_radix(rank_of(ahead).to_color(), 16).unwrap_or(0));
+            format!(
+                "TR delta: {:.0} in favor of {}",
+                ahead.league.rating - behind.league.rating,
+                ahead.username
+            )
+        } else {
+            "TR delta: -".to_string()
+        };
+        e.description(delta);
+        e.fields(vec![
+            (
+                format!("{}{}", left.username, supporter_suffix(left)),
+                versus_column(left),
+                true,
+            ),
+            (
+                format!("{}{}", right.username, supporter_suffix(right)),
+                versus_column(right),
+                true,
+            ),
+        ]);
+        e
+    }
+
     pub async fn react_confirm(ctx: &Context, msg: &Message) {
         msg.react(&ctx.http, ReactionType::Unicode(CONFIRM_EMOJI.to_string()))
             .await
@@ -378,6 +1079,455 @@ pub mod util {
             .expect("Could not react?");
     }
 
+    // Splits arguments into positional arguments and `--flags` (returned without the dashes, lowercase)
+    // Flags in `value_flags` take the next argument as their value, other flags have an empty value
+    pub fn split_flags(
+        args: &Args,
+        value_flags: &[&str],
+    ) -> (Vec<String>, HashMap<String, String>) {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+        let mut raw = args.raw_quoted();
+
+        while let Some(arg) = raw.next() {
+            match arg.strip_prefix("--") {
+                Some(flag) => {
+                    let flag = flag.to_lowercase();
+                    let value = if value_flags.contains(&flag.as_str()) {
+                        raw.next().unwrap_or_default().to_string()
+                    } else {
+                        String::new()
+                    };
+                    flags.insert(flag, value);
+                }
+                None => positional.push(arg.to_string()),
+            }
+        }
+
+        (positional, flags)
+    }
+
+    // Discord timestamp markup, shown in every reader's own timezone
+    // `style` is one of Discord's timestamp styles, like `R` for relative or `f` for date and time
+    pub fn discord_timestamp(date: DateTime<Utc>, style: char) -> String {
+        format!("<t:{}:{}>", date.timestamp(), style)
+    }
+
+    // Parses a UTC date like `2021-03-14 18:30`, or an RFC 3339 date with a timezone
+    pub fn parse_datetime(input: &str) -> Option<DateTime<Utc>> {
+        if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+            return Some(date.with_timezone(&Utc));
+        }
+
+        ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+            .map(|date| Utc.from_utc_datetime(&date))
+    }
+
+    // Asks the author of `msg` to confirm an action with a reaction
+    // Returns false if they deny or don't react in time
+    pub async fn confirm_prompt(
+        ctx: &Context,
+        msg: &Message,
+        prompt: &str,
+    ) -> Result<bool, SerenityError> {
+        let prompt_msg = say(&ctx, msg.channel_id, prompt).await?;
+        react_confirm(&ctx, &prompt_msg).await;
+        react_deny(&ctx, &prompt_msg).await;
+
+        let confirm = ReactionType::Unicode(CONFIRM_EMOJI.to_string());
+        let deny = ReactionType::Unicode(ERROR_EMOJI.to_string());
+        let reaction = prompt_msg
+            .await_reaction(&ctx)
+            .author_id(msg.author.id)
+            .filter(move |r| r.emoji == confirm || r.emoji == deny)
+            .timeout(time::Duration::from_secs(60))
+            .await;
+
+        Ok(reaction.map_or(false, |action| {
+            action.as_inner_ref().emoji == ReactionType::Unicode(CONFIRM_EMOJI.to_string())
+        }))
+    }
+
+    // Checks what happened to a linked Discord account
+    // Only a 404 on the user object counts as deleted, failing to fetch the member is not enough
+    pub async fn resolve_discord_account(
+        ctx: &Context,
+        guild_id: GuildId,
+        discord_id: u64,
+    ) -> DiscordAccountStatus {
+        if guild_id.member(&ctx.http, discord_id).await.is_ok() {
+            return DiscordAccountStatus::Member;
+        }
+
+        match ctx.http.get_user(discord_id).await {
+            Ok(_) => DiscordAccountStatus::NotInGuild,
+            Err(SerenityError::Http(err)) => match err.as_ref() {
+                HttpError::UnsuccessfulRequest(response)
+                    if response.status_code == StatusCode::NOT_FOUND =>
+                {
+                    DiscordAccountStatus::Deleted
+                }
+                _ => DiscordAccountStatus::Unresolved,
+            },
+            Err(_) => DiscordAccountStatus::Unresolved,
+        }
+    }
+
+    // Escapes everything in user provided text that could turn into a mention
+    // A zero-width space after every @ breaks @everyone, @here and <@id>, <@!id>, <@&id> mentions
+    pub fn sanitize_mentions(text: &str) -> String {
+        text.replace('@', "@\u{200B}")
+    }
+
+    // Identifiers of a command message, to record where a registration came from
+    pub fn message_ref(msg: &Message) -> MessageRef {
+        MessageRef::new(msg.guild_id.map(|id| id.0), msg.channel_id.0, msg.id.0)
+    }
+
+    // Sends a message that can't ping anyone, even if it contains mentions
+    // Everything should be sent through this or the `say_pinging` variants, so stored names or
+    // arguments that happen to be mentions never ping people
+    pub async fn say(
+        ctx: &Context,
+        channel_id: ChannelId,
+        content: impl std::fmt::Display,
+    ) -> Result<Message, SerenityError> {
+        channel_id
+            .send_message(&ctx.http, |m| {
+                m.content(content).allowed_mentions(|am| am.empty_parse())
+            })
+            .await
+    }
+
+    // Same as `say`, but pings the given users
+    pub async fn say_pinging(
+        ctx: &Context,
+        channel_id: ChannelId,
+        content: impl std::fmt::Display,
+        users: &[u64],
+    ) -> Result<Message, SerenityError> {
+        channel_id
+            .send_message(&ctx.http, |m| {
+                m.content(content).allowed_mentions(|am| {
+                    am.empty_parse().users(users.iter().map(|id| UserId(*id)))
+                })
+            })
+            .await
+    }
+
+    // Same as `say`, but allows @here and @everyone, for announcements meant to reach everyone
+    pub async fn say_pinging_everyone(
+        ctx: &Context,
+        channel_id: ChannelId,
+        content: impl std::fmt::Display,
+    ) -> Result<Message, SerenityError> {
+        channel_id
+            .send_message(&ctx.http, |m| {
+                m.content(content)
+                    .allowed_mentions(|am| am.parse(ParseValue::Everyone))
+            })
+            .await
+    }
+
+    // Discord's error code for a message that doesn't exist (anymore)
+    const UNKNOWN_MESSAGE: isize = 10008;
+
+    // Whether a request failed because the message was deleted
+    pub fn is_unknown_message(err: &SerenityError) -> bool {
+        match err {
+            SerenityError::Http(err) => matches!(
+                err.as_ref(),
+                HttpError::UnsuccessfulRequest(response) if response.error.code == UNKNOWN_MESSAGE
+            ),
+            _ => false,
+        }
+    }
+
+    // Posts an alert in the channel set by STAFF_ALERT_CHANNEL_ID, only logs it if there is none
+    pub async fn alert_staff(ctx: &Context, text: &str) {
+        tracing::warn!("{}", text);
+        let channel = std::env::var("STAFF_ALERT_CHANNEL_ID")
+            .ok()
+            .and_then(|id| id.parse::<u64>().ok());
+        if let Some(channel) = channel {
+            if let Err(err) = say(&ctx, ChannelId(channel), text).await {
+                tracing::error!("Could not alert staff: {}", err);
+            }
+        }
+    }
+
+    // Gives or takes the participant role of a tournament, if it has one.
+    // Failures (usually missing permissions) are only logged, they shouldn't undo a registration.
+    pub async fn update_participant_role(
+        ctx: &Context,
+        tournament: &TournamentEntry,
+        discord_id: u64,
+        registered: bool,
+    ) {
+        let role_id = match tournament.participant_role {
+            Some(role_id) => role_id,
+            None => return,
+        };
+
+        let guild_id = crate::discord::UC_GUILD_ID;
+        let result = if registered {
+            ctx.http
+                .add_member_role(guild_id, discord_id, role_id)
+                .await
+        } else {
+            ctx.http
+                .remove_member_role(guild_id, discord_id, role_id)
+                .await
+        };
+        if let Err(err) = result {
+            tracing::warn!(
+                "Could not {} the participant role of {} for {}: {}",
+                if registered { "add" } else { "remove" },
+                tournament.shorthand,
+                discord_id,
+                err
+            );
+        }
+    }
+
+    // Tells a player in their DMs that they were removed from a tournament, so they don't find out on bracket day
+    // Returns why the player couldn't be told, for the staff reply
+    pub async fn notify_removed(
+        ctx: &Context,
+        tournament: &TournamentEntry,
+        player: &PlayerEntry,
+        by: UnregisteredBy,
+        reason: Option<&str>,
+    ) -> Result<(), String> {
+        let discord_id = match player.discord_id {
+            Some(discord_id) => discord_id,
+            None => return Err("no linked Discord account".to_string()),
+        };
+        let by = match by {
+            UnregisteredBy::Staff { .. } => "by staff",
+            UnregisteredBy::Automatic => "automatically",
+            UnregisteredBy::Player => return Ok(()),
+        };
+
+        let mut text = format!("You were removed from {} {}", tournament.name, by);
+        match reason {
+            Some(reason) => text.push_str(&format!(", reason: {}.", reason)),
+            None => text.push('.'),
+        }
+        text.push_str(" Reach out to staff if you think this is a mistake.");
+        let text = badged(tournament, &text);
+
+        let dm = match UserId(discord_id).create_dm_channel(&ctx.http).await {
+            Ok(channel) => say(&ctx, channel.id, text).await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+        dm.map_err(|err| {
+            tracing::warn!("Could not tell {} about their removal: {}", discord_id, err);
+            "DMs are closed".to_string()
+        })
+    }
+
+    // Promotes waitlisted players into free spots of a tournament, hands them the participant role
+    // and tells them in their DMs. Failures are only logged, the spot that opened up is still free then.
+    pub async fn fill_from_waitlist(
+        ctx: &Context,
+        db: &crate::database::LocalDatabase,
+        tournament: &TournamentEntry,
+    ) {
+        if tournament.waitlist.is_empty() {
+            return;
+        }
+        let (tournament, promoted) = match db
+            .tournaments
+            .promote_waitlisted(&tournament.shorthand)
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!(
+                    "Could not promote the waitlist of {}: {}",
+                    tournament.shorthand,
+                    err
+                );
+                return;
+            }
+        };
+
+        for entry in promoted {
+            let discord_id = match db.players.get_player_by_tetrio(&entry.tetrio_id).await {
+                Ok(Some(player)) => player.discord_id,
+                _ => None,
+            };
+            let discord_id = match discord_id {
+                Some(discord_id) => discord_id,
+                None => {
+                    tracing::warn!(
+                        "Promoted {} from the waitlist, but they're not linked",
+                        entry.tetrio_id
+                    );
+                    continue;
+                }
+            };
+
+            update_participant_role(&ctx, &tournament, discord_id, true).await;
+            let text = badged(
+                &tournament,
+                &format!(
+                    "A spot opened up in {}, you're registered now! Use `.withdraw` if you can't make it anymore.",
+                    tournament.name
+                ),
+            );
+            let dm = match UserId(discord_id).create_dm_channel(&ctx.http).await {
+                Ok(channel) => say(&ctx, channel.id, text).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = dm {
+                tracing::warn!(
+                    "Could not tell {} about their promotion: {}",
+                    discord_id,
+                    err
+                );
+            }
+        }
+    }
+
+    // Flag emoji of a two-letter country code, made of the matching regional indicator symbols
+    // `None` for anything that isn't two letters
+    pub fn country_flag(code: &str) -> Option<String> {
+        if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        code.to_ascii_uppercase()
+            .chars()
+            .map(|c| std::char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+            .collect()
+    }
+
+    // Splits a pasted list of names (newline, comma or space separated), without duplicates
+    pub fn parse_identifiers(text: &str) -> Vec<String> {
+        let mut identifiers: Vec<String> = Vec::new();
+        for identifier in text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .map(|i| i.trim().to_lowercase())
+            .filter(|i| !i.is_empty())
+        {
+            if !identifiers.contains(&identifier) {
+                identifiers.push(identifier);
+            }
+        }
+        identifiers
+    }
+
+    // Splits a pasted list of `discord_mention,tetrio_username` rows, one per line
+    // Every non-empty row is returned along with either the parsed pair or why it couldn't be parsed,
+    // lines starting with `--` are command flags and skipped
+    pub fn parse_registration_rows(text: &str) -> Vec<(String, Result<(u64, String), String>)> {
+        text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("--"))
+            .map(|line| {
+                let mut parts = line.splitn(2, ',');
+                let parsed = match (parts.next(), parts.next()) {
+                    (Some(discord), Some(username)) => {
+                        let discord = discord.trim();
+                        let username = username.trim().to_lowercase();
+                        match serenity::utils::parse_mention(discord)
+                            .or_else(|| discord.parse::<u64>().ok())
+                        {
+                            Some(_) if username.is_empty() => Err("Missing username".to_string()),
+                            Some(discord_id) => Ok((discord_id, username)),
+                            None => {
+                                Err("Discord user was not valid (use a mention or ID)".to_string())
+                            }
+                        }
+                    }
+                    _ => Err("Expected `discord_mention,tetrio_username`".to_string()),
+                };
+                (line.to_string(), parsed)
+            })
+            .collect()
+    }
+
+    // How long a long running task may take before it's cancelled, configurable via env
+    pub fn task_timeout() -> chrono::Duration {
+        let minutes = std::env::var("TASK_TIMEOUT_MINUTES")
+            .ok()
+            .and_then(|m| m.parse::<i64>().ok())
+            .unwrap_or(15);
+        chrono::Duration::minutes(minutes)
+    }
+
+    // Runs work in the background as a registered task, so it shows up in `.tasks` and can be cancelled
+    pub async fn run_long_task<F, Fut, T>(
+        ctx: &Context,
+        description: &str,
+        work: F,
+    ) -> (TaskHandle, DatabaseResult<T>)
+    where
+        F: FnOnce(TaskHandle) -> Fut,
+        Fut: Future<Output = DatabaseResult<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let handle = {
+            let data_read = ctx.data.read().await;
+            match data_read.get::<TaskRegistry>() {
+                Some(registry) => registry.lock().await.register(description, task_timeout()),
+                None => TaskHandle::detached(description),
+            }
+        };
+
+        let worker_handle = handle.clone();
+        let result = tokio::spawn(work(worker_handle))
+            .await
+            .expect("Long running task panicked");
+
+        let data_read = ctx.data.read().await;
+        if let Some(registry) = data_read.get::<TaskRegistry>() {
+            registry.lock().await.complete(handle.id);
+        }
+
+        (handle, result)
+    }
+
+    // Joins mentions into messages with at most `per_message` mentions each
+    pub fn chunk_mentions(discord_ids: &[u64], per_message: usize) -> Vec<String> {
+        discord_ids
+            .chunks(per_message.max(1))
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|id| format!("<@{}>", id))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    // Joins items into chunks of at most `max_len` characters, for embed fields and messages
+    // An item that is too long on its own gets a chunk of its own and isn't cut
+    pub fn chunk_joined(items: &[String], separator: &str, max_len: usize) -> Vec<String> {
+        let mut chunks: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for item in items {
+            if !current.is_empty()
+                && current.chars().count() + separator.chars().count() + item.chars().count()
+                    > max_len
+            {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str(separator);
+            }
+            current.push_str(item);
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
     pub async fn delay_delete(ctx: &Context, reply: Option<Message>) -> CommandResult {
         if let Some(reply) = reply {
             time::sleep(time::Duration::from_secs(120)).await;
@@ -386,3 +1536,233 @@ pub mod util {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::util::{
+        sanitize_mentions, tetrio_error_reply, versus_embed, TETRIO_DOWN_MESSAGE,
+        TETRIO_ERROR_MESSAGE,
+    };
+    use super::IdCollection;
+    use crate::tetrio::leaderboard::{LeaderboardUser, LeagueData};
+    use crate::tetrio::TetrioApiError;
+    use chrono::{Duration, TimeZone, Utc};
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    // Sources of everything that sends messages, checked by `raw_sends_disable_mentions`
+    const SOURCES: &[(&str, &str)] = &[
+        ("commands/global.rs", include_str!("commands/global.rs")),
+        ("commands/owner.rs", include_str!("commands/owner.rs")),
+        ("commands/player.rs", include_str!("commands/player.rs")),
+        ("commands/staff.rs", include_str!("commands/staff.rs")),
+        (
+            "commands/tournament.rs",
+            include_str!("commands/tournament.rs"),
+        ),
+        (
+            "discord/auto_update.rs",
+            include_str!("discord/auto_update.rs"),
+        ),
+        ("discord/janitor.rs", include_str!("discord/janitor.rs")),
+        ("discord/news.rs", include_str!("discord/news.rs")),
+        ("discord/report.rs", include_str!("discord/report.rs")),
+        ("discord/scheduler.rs", include_str!("discord/scheduler.rs")),
+    ];
+
+    // Returns the argument list of every call to `method` in `source`, with the line it starts on
+    fn calls<'a>(source: &'a str, method: &str) -> Vec<(usize, &'a str)> {
+        let mut found = Vec::new();
+        let mut rest = 0;
+        while let Some(offset) = source[rest..].find(method) {
+            let start = rest + offset + method.len();
+            let mut depth = 1;
+            let mut end = start;
+            for (i, c) in source[start..].char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    end = start + i;
+                    break;
+                }
+            }
+            let line = source[..start].lines().count();
+            found.push((line, &source[start..end]));
+            rest = end;
+        }
+        found
+    }
+
+    #[test]
+    fn mass_mentions_are_broken() {
+        for text in &["@everyone", "@here", "hey @everyone and @here!"] {
+            let sanitized = sanitize_mentions(text);
+            assert!(!sanitized.contains("@everyone"), "{}", sanitized);
+            assert!(!sanitized.contains("@here"), "{}", sanitized);
+        }
+    }
+
+    #[test]
+    fn id_mentions_are_broken() {
+        assert_eq!(sanitize_mentions("<@123>"), "<@\u{200B}123>");
+        assert_eq!(sanitize_mentions("<@!123>"), "<@\u{200B}!123>");
+        assert_eq!(sanitize_mentions("<@&123>"), "<@\u{200B}&123>");
+        assert_eq!(
+            sanitize_mentions("@@everyone<@<@123>>"),
+            "@\u{200B}@\u{200B}everyone<@\u{200B}<@\u{200B}123>>"
+        );
+    }
+
+    #[test]
+    fn plain_text_is_unchanged() {
+        for text in &["", "icedynamix", "UC12 check-in <#123>", "x_x :rank_ss:"] {
+            assert_eq!(sanitize_mentions(text), *text);
+        }
+    }
+
+    #[test]
+    fn commands_use_the_mention_safe_wrappers() {
+        for (name, source) in SOURCES {
+            // `ChannelId::say` allows every mention, `util::say` is called as `say(&ctx, ..)`
+            assert!(
+                !source.contains(".say(&ctx.http"),
+                "{} sends through ChannelId::say",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn raw_sends_disable_mentions() {
+        for (name, source) in SOURCES {
+            for method in &[".send_message(", ".send_files("] {
+                for (line, args) in calls(source, method) {
+                    if args.contains(".content(") {
+                        assert!(
+                            args.contains("allowed_mentions"),
+                            "{}:{} sends content without setting allowed_mentions",
+                            name,
+                            line
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn old_debounce_entries_are_pruned() {
+        let now = Utc.ymd(2021, 3, 14).and_hms(18, 0, 0);
+        let mut added = HashMap::new();
+        added.insert(1, now - Duration::hours(7));
+        added.insert(2, now - Duration::hours(6));
+        added.insert(3, now);
+        let mut ids = IdCollection(added);
+
+        assert_eq!(ids.prune(Duration::hours(6), now), 1);
+        assert!(!ids.contains(1));
+        assert!(ids.contains(2) && ids.contains(3));
+        assert_eq!(ids.prune(Duration::hours(6), now), 0);
+    }
+
+    #[test]
+    fn tetrio_errors_pick_their_reply() {
+        let until = Utc.ymd(2021, 3, 14).and_hms(18, 0, 0);
+        assert_eq!(
+            tetrio_error_reply(&TetrioApiError::CircuitOpen { until }),
+            TETRIO_DOWN_MESSAGE
+        );
+        assert_eq!(
+            tetrio_error_reply(&TetrioApiError::Upstream(
+                "Internal server error".to_string()
+            )),
+            TETRIO_ERROR_MESSAGE
+        );
+        assert_eq!(
+            tetrio_error_reply(&TetrioApiError::NotFound),
+            TETRIO_ERROR_MESSAGE
+        );
+    }
+
+    fn player(username: &str, rank: &str, rating: f64, played: i64, won: i64) -> LeaderboardUser {
+        LeaderboardUser {
+            _id: format!("id_{}", username),
+            username: username.to_string(),
+            role: "user".to_string(),
+            country: None,
+            supporter: Some(false),
+            verified: false,
+            league: LeagueData {
+                gamesplayed: played,
+                gameswon: won,
+                rating,
+                rank: rank.to_string(),
+                glicko: Some(1500f64),
+                rd: Some(80f64),
+                apm: Some(30f64),
+                pps: Some(1.5),
+                vs: Some(60f64),
+            },
+        }
+    }
+
+    // Description, color and the two columns of a versus embed
+    fn versus(
+        left: &LeaderboardUser,
+        right: &LeaderboardUser,
+    ) -> (String, Option<u64>, Vec<String>) {
+        let embed = versus_embed(left, right).0;
+        let description = embed["description"].as_str().unwrap().to_string();
+        let color = embed.get("color").and_then(Value::as_u64);
+        let columns = embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["value"].as_str().unwrap().to_string())
+            .collect();
+        (description, color, columns)
+    }
+
+    #[test]
+    fn versus_shows_the_delta_for_the_higher_rated_player() {
+        let left = player("caboozled_pie", "a", 18000f64, 40, 10);
+        let right = player("icedynamix", "s", 21500f64, 50, 25);
+        let (description, color, columns) = versus(&left, &right);
+        assert_eq!(description, "TR delta: 3500 in favor of icedynamix");
+        assert_eq!(color, Some(0xd19e26));
+        assert!(columns[0].contains("TR: 18000"), "{}", columns[0]);
+        assert!(columns[0].contains("Win rate: 25.0%"), "{}", columns[0]);
+        assert!(columns[1].contains("APM: 30.00"), "{}", columns[1]);
+        assert!(columns[1].contains("Win rate: 50.0%"), "{}", columns[1]);
+
+        let (description, color, _) = versus(&right, &left);
+        assert_eq!(description, "TR delta: 3500 in favor of icedynamix");
+        assert_eq!(color, Some(0xd19e26));
+    }
+
+    #[test]
+    fn versus_with_an_unranked_player_has_no_delta() {
+        let ranked = player("icedynamix", "s", 21500f64, 50, 25);
+        let unranked = player("caboozled_pie", "z", -1f64, 3, 1);
+        let (description, color, columns) = versus(&ranked, &unranked);
+        assert_eq!(description, "TR delta: -");
+        assert_eq!(color, None);
+        assert!(columns[0].contains("TR: 21500"), "{}", columns[0]);
+        for line in &["Rank: -", "TR: -", "APM: -", "PPS: -", "VS: -"] {
+            assert!(columns[1].contains(line), "{} in {}", line, columns[1]);
+        }
+        assert!(columns[1].contains("Games: 3"), "{}", columns[1]);
+    }
+
+    #[test]
+    fn versus_without_games_has_no_win_rate() {
+        let left = player("icedynamix", "s", 21500f64, 50, 25);
+        let right = player("caboozled_pie", "a", 18000f64, 0, 0);
+        let (_, _, columns) = versus(&left, &right);
+        assert!(columns[1].contains("Win rate: -"), "{}", columns[1]);
+        assert!(columns[1].contains("Games: 0"), "{}", columns[1]);
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serenity::prelude::*;
+use tokio::time;
+
+use crate::database::LocalDatabase;
+use crate::rng::SplitMix64;
+use crate::status::{format_time, StatusReport};
+
+// Interval if `AUTO_UPDATE_MINUTES` isn't set, the refresh starts disabled then
+const DEFAULT_INTERVAL_MINUTES: u64 = 60;
+
+// Every cycle waits up to this share of the interval longer, so refreshes don't hit ch.tetr.io on a fixed beat
+const JITTER_SHARE: f64 = 0.1;
+
+// Whether player data is refreshed in the background and how it went, for `.status` and `.auto_update`
+#[derive(Debug)]
+pub struct AutoUpdateStatus {
+    pub enabled: bool,
+    pub interval: time::Duration,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_updated: Option<usize>,
+    pub skipped: u64,
+    pub last_error: Option<String>,
+}
+
+impl AutoUpdateStatus {
+    // Enabled with the interval from `AUTO_UPDATE_MINUTES` if it's set to a positive number
+    pub fn from_env() -> AutoUpdateStatus {
+        let minutes = std::env::var("AUTO_UPDATE_MINUTES")
+            .ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0);
+        AutoUpdateStatus {
+            enabled: minutes.is_some(),
+            interval: time::Duration::from_secs(60 * minutes.unwrap_or(DEFAULT_INTERVAL_MINUTES)),
+            last_run: None,
+            last_updated: None,
+            skipped: 0,
+            last_error: None,
+        }
+    }
+}
+
+impl TypeMapKey for AutoUpdateStatus {
+    type Value = Mutex<AutoUpdateStatus>;
+}
+
+impl StatusReport for AutoUpdateStatus {
+    fn name(&self) -> String {
+        "Auto update".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            ("Enabled".to_string(), self.enabled.to_string()),
+            (
+                "Interval".to_string(),
+                format!("{}min", self.interval.as_secs() / 60),
+            ),
+            ("Last run".to_string(), format_time(self.last_run)),
+            (
+                "Last updated players".to_string(),
+                self.last_updated
+                    .map_or("-".to_string(), |count| count.to_string()),
+            ),
+            ("Skipped cycles".to_string(), self.skipped.to_string()),
+            (
+                "Last error".to_string(),
+                self.last_error.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+        ]
+    }
+}
+
+// Refreshes every ranked player, if the auto update is enabled
+// A cycle is skipped while another update is running or the Tetrio API is down
+pub async fn run(data: &Arc<RwLock<TypeMap>>) {
+    let db = match data.read().await.get::<LocalDatabase>() {
+        Some(db) => db.clone(),
+        None => return,
+    };
+    let enabled = match data.read().await.get::<AutoUpdateStatus>() {
+        Some(status) => status.lock().await.enabled,
+        None => false,
+    };
+    if !enabled {
+        return;
+    }
+
+    let result = if crate::tetrio::is_circuit_open() {
+        Ok(None)
+    } else {
+        tracing::info!("Starting automatic leaderboard update");
+        db.refresh_ranked_players()
+            .await
+            .map_err(|err| err.to_string())
+    };
+
+    match &result {
+        Ok(Some(updated)) => tracing::info!(
+            "Automatic leaderboard update finished, {} players updated",
+            updated
+        ),
+        Ok(None) => tracing::info!(
+            "Skipping automatic leaderboard update, another update is running or Tetrio is down"
+        ),
+        Err(err) => tracing::warn!("Automatic leaderboard update failed: {}", err),
+    }
+
+    if let Some(status) = data.read().await.get::<AutoUpdateStatus>() {
+        let mut status = status.lock().await;
+        status.last_run = Some(Utc::now());
+        match result {
+            Ok(Some(updated)) => {
+                status.last_updated = Some(updated);
+                status.last_error = None;
+            }
+            Ok(None) => status.skipped += 1,
+            Err(err) => status.last_error = Some(err),
+        }
+    }
+}
+
+// Refreshes player data in the background until the bot shuts down
+// Cycles keep running while it's disabled, so `.auto_update on` takes effect without a restart
+pub fn spawn(data: Arc<RwLock<TypeMap>>) {
+    tokio::spawn(async move {
+        let mut rng = SplitMix64::new(Utc::now().timestamp_nanos() as u64);
+        loop {
+            let interval = match data.read().await.get::<AutoUpdateStatus>() {
+                Some(status) => status.lock().await.interval,
+                None => return,
+            };
+            let jitter = interval.mul_f64(JITTER_SHARE * rng.next_f64());
+            time::sleep(interval + jitter).await;
+            run(&data).await;
+        }
+    });
+}
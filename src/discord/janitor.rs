@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::prelude::*;
+use tokio::time;
+
+use crate::database::LocalDatabase;
+use crate::discord::IdCollection;
+use crate::status::{format_time, StatusReport};
+
+// How often the janitor runs on its own
+const INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+// Check-in debounce entries older than this are dropped, so users get a reply again eventually
+const DEBOUNCE_MAX_AGE_HOURS: i64 = 6;
+
+// Daily command usage rollups older than this are deleted
+const USAGE_MAX_AGE_DAYS: i64 = 365;
+
+// What a single janitor run removed
+#[derive(Debug, Clone, Default)]
+pub struct JanitorSummary {
+    pub debounce_entries: usize,
+    pub usage_rollups: i64,
+    pub pending_links: i64,
+}
+
+impl std::fmt::Display for JanitorSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Janitor removed {} check-in debounce entries, {} command usage rollups and {} expired link verifications",
+            self.debounce_entries, self.usage_rollups, self.pending_links
+        )
+    }
+}
+
+// When the janitor last ran and what it did, for `.status`
+#[derive(Debug, Default)]
+pub struct JanitorStatus {
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_summary: Option<JanitorSummary>,
+}
+
+impl TypeMapKey for JanitorStatus {
+    type Value = Mutex<JanitorStatus>;
+}
+
+impl StatusReport for JanitorStatus {
+    fn name(&self) -> String {
+        "Janitor".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            ("Interval".to_string(), format!("{}s", INTERVAL.as_secs())),
+            ("Last run".to_string(), format_time(self.last_run)),
+            (
+                "Last result".to_string(),
+                self.last_summary
+                    .as_ref()
+                    .map_or("-".to_string(), |s| s.to_string()),
+            ),
+        ]
+    }
+}
+
+pub async fn run(data: &Arc<RwLock<TypeMap>>) -> JanitorSummary {
+    let mut summary = JanitorSummary::default();
+    let now = Utc::now();
+
+    if let Some(ids) = data.read().await.get::<IdCollection>() {
+        summary.debounce_entries = ids
+            .lock()
+            .await
+            .prune(Duration::hours(DEBOUNCE_MAX_AGE_HOURS), now);
+    }
+
+    // cloned out, so the TypeMap isn't locked while waiting for the database
+    let db = data.read().await.get::<LocalDatabase>().cloned();
+    if let Some(db) = db {
+        match db
+            .usage
+            .prune(Duration::days(USAGE_MAX_AGE_DAYS), now)
+            .await
+        {
+            Ok(deleted) => summary.usage_rollups = deleted,
+            Err(err) => tracing::warn!("Could not prune command usage: {}", err),
+        }
+        match db.players.expire_pending_links(now).await {
+            Ok(expired) => summary.pending_links = expired,
+            Err(err) => tracing::warn!("Could not expire link verifications: {}", err),
+        }
+    }
+
+    tracing::info!("{}", summary);
+    if let Some(status) = data.read().await.get::<JanitorStatus>() {
+        let mut status = status.lock().await;
+        status.last_run = Some(now);
+        status.last_summary = Some(summary.clone());
+    }
+
+    summary
+}
+
+// Runs the janitor in the background until the bot shuts down
+pub fn spawn(data: Arc<RwLock<TypeMap>>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(INTERVAL);
+        // the first tick completes immediately, nothing has accumulated yet on startup
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            run(&data).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_fits_on_one_line() {
+        let summary = JanitorSummary {
+            debounce_entries: 3,
+            usage_rollups: 2,
+            pending_links: 1,
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Janitor removed 3 check-in debounce entries, 2 command usage rollups and 1 expired link verifications"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use crate::database::players::PlayerEntry;
+use crate::database::tournaments::RegistrationError;
+use crate::database::{DatabaseError, LocalDatabase};
+use crate::discord::util::discord_timestamp;
+use crate::eligibility::{verdict_to_lines, Style};
+use crate::tetrio::TetrioApiError;
+
+// Languages replies to players can be shown in, picked with `.language`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+    Portuguese,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::Spanish, Language::Portuguese];
+
+    // Code stored on the player entry and typed in `.language`
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+            Language::Portuguese => "pt",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        let code = code.trim();
+        Language::ALL
+            .iter()
+            .copied()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    // Name of the language in the language itself
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+            Language::Portuguese => "Português",
+        }
+    }
+
+    // Language a player picked, English for everyone else
+    pub fn of(player: Option<&PlayerEntry>) -> Language {
+        player
+            .and_then(|player| player.language.as_deref())
+            .and_then(Language::from_code)
+            .unwrap_or(Language::English)
+    }
+
+    // Every language as `name (code)`, for command replies
+    pub fn list() -> String {
+        Language::ALL
+            .iter()
+            .map(|language| format!("{} (`{}`)", language.name(), language.code()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+// Language of a Discord user, English if they aren't linked or the database can't be reached
+pub async fn language_of(db: &LocalDatabase, discord_id: u64) -> Language {
+    let player = db
+        .players
+        .get_player_by_discord(discord_id)
+        .await
+        .ok()
+        .flatten();
+    Language::of(player.as_ref())
+}
+
+// Every reply in the catalog, each has an English template and possibly translations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKey {
+    MissingAccount,
+    AlreadyRegistered,
+    AlreadyWaitlisted,
+    NotRegistered,
+    HighestRankTooHigh,
+    NotQualified,
+    AccountUnavailable,
+    RegistrationNotOpen,
+    RegistrationClosed,
+    Ineligible,
+    NoTournamentActive,
+    AmbiguousTournament,
+    Disqualified,
+    SnapshotMissing,
+    InvalidBackdate,
+    LinkedToDifferentAccount,
+    AlreadyLinkedToOther,
+    AccountTaken,
+    AlreadyLinked,
+    PlayerNotFound,
+    NotLinked,
+    TetrioDown,
+    TetrioError,
+    InvalidInput,
+    DatabaseFailure,
+    LanguageSet,
+    LanguageUnknown,
+    LanguageCurrent,
+}
+
+impl MessageKey {
+    pub const ALL: [MessageKey; 28] = [
+        MessageKey::MissingAccount,
+        MessageKey::AlreadyRegistered,
+        MessageKey::AlreadyWaitlisted,
+        MessageKey::NotRegistered,
+        MessageKey::HighestRankTooHigh,
+        MessageKey::NotQualified,
+        MessageKey::AccountUnavailable,
+        MessageKey::RegistrationNotOpen,
+        MessageKey::RegistrationClosed,
+        MessageKey::Ineligible,
+        MessageKey::NoTournamentActive,
+        MessageKey::AmbiguousTournament,
+        MessageKey::Disqualified,
+        MessageKey::SnapshotMissing,
+        MessageKey::InvalidBackdate,
+        MessageKey::LinkedToDifferentAccount,
+        MessageKey::AlreadyLinkedToOther,
+        MessageKey::AccountTaken,
+        MessageKey::AlreadyLinked,
+        MessageKey::PlayerNotFound,
+        MessageKey::NotLinked,
+        MessageKey::TetrioDown,
+        MessageKey::TetrioError,
+        MessageKey::InvalidInput,
+        MessageKey::DatabaseFailure,
+        MessageKey::LanguageSet,
+        MessageKey::LanguageUnknown,
+        MessageKey::LanguageCurrent,
+    ];
+}
+
+// Template of a message in a language, `None` if it wasn't translated
+// Parameters are written as `{name}`, translations have to use the same ones as the English template
+pub fn template(key: MessageKey, language: Language) -> Option<&'static str> {
+    match language {
+        Language::English => Some(english(key)),
+        Language::Spanish => spanish(key),
+        Language::Portuguese => portuguese(key),
+    }
+}
+
+// Names of the parameters a template uses, in order of appearance
+pub fn placeholders(template: &str) -> Vec<&str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+        .collect()
+}
+
+// Exhaustive, so every key has at least the English template
+fn english(key: MessageKey) -> &'static str {
+    match key {
+        MessageKey::MissingAccount => "There is no Tetr.io account linked to you right now, please provide a username. `.register [username]`",
+        MessageKey::AlreadyRegistered => "You're already registered!",
+        MessageKey::AlreadyWaitlisted => "You're already #{position} on the waitlist!",
+        MessageKey::NotRegistered => "You're not registered to this tournament",
+        MessageKey::HighestRankTooHigh => "You've reached {rank} before, but only players who never went above {expected} can participate in this tournament",
+        MessageKey::NotQualified => "Only players who qualified through {parent} can register for this tournament",
+        MessageKey::AccountUnavailable => "Your linked Tetr.io account is {status}, please contact staff",
+        MessageKey::RegistrationNotOpen => "Registration opens {opens}, please come back then!",
+        MessageKey::RegistrationClosed => "Registration closed {closed}, see you next time!",
+        MessageKey::Ineligible => "{reasons}",
+        MessageKey::NoTournamentActive => "There is no tournament ongoing",
+        MessageKey::AmbiguousTournament => "Multiple tournaments are ongoing, please pick one: `.register <{shorthands}> [username]`",
+        MessageKey::Disqualified => "You were disqualified from this tournament",
+        MessageKey::SnapshotMissing => "The stats for this tournament haven't been taken yet, please contact staff",
+        MessageKey::InvalidBackdate => "Registration date `{date}` is outside of the allowed range (`{earliest}` to `{latest}`)",
+        MessageKey::LinkedToDifferentAccount => "You're linked to {linked}, not {requested}",
+        MessageKey::AlreadyLinkedToOther => "You're already linked to a Tetr.io user! Use the `unlink` command before linking to another Tetr.io user",
+        MessageKey::AccountTaken => "Someone else has already linked this Tetr.io user!",
+        MessageKey::AlreadyLinked => "You're already linked to this Tetr.io user!",
+        MessageKey::PlayerNotFound => "Could not find that user on Tetr.io, check the spelling!",
+        MessageKey::NotLinked => "There is no Tetr.io user linked to you right now, use the `link` command to link one",
+        MessageKey::TetrioDown => "Tetr.io appears to be down or under maintenance, using cached data where possible",
+        MessageKey::TetrioError => "Tetr.io returned an error, try again in a minute",
+        MessageKey::InvalidInput => "Invalid input: {reason}",
+        MessageKey::DatabaseFailure => "Something went wrong while accessing the database, please try again later",
+        MessageKey::LanguageSet => "Replies to you are in {language} now",
+        MessageKey::LanguageUnknown => "Unknown language `{code}`, available are {languages}",
+        MessageKey::LanguageCurrent => "Replies to you are in {language}, available are {languages}. `.language <code>`",
+    }
+}
+
+// Staff only messages, like backdating, are left in English
+fn spanish(key: MessageKey) -> Option<&'static str> {
+    let template = match key {
+        MessageKey::MissingAccount => "No tienes ninguna cuenta de Tetr.io vinculada, indica un nombre de usuario. `.register [usuario]`",
+        MessageKey::AlreadyRegistered => "¡Ya estás inscrito!",
+        MessageKey::AlreadyWaitlisted => "¡Ya estás en la lista de espera (#{position})!",
+        MessageKey::NotRegistered => "No estás inscrito en este torneo",
+        MessageKey::HighestRankTooHigh => "Ya llegaste a {rank}, pero solo pueden participar jugadores que nunca pasaron de {expected}",
+        MessageKey::NotQualified => "Solo pueden inscribirse los jugadores que se clasificaron en {parent}",
+        MessageKey::AccountUnavailable => "El estado de tu cuenta de Tetr.io vinculada es `{status}`, contacta al staff",
+        MessageKey::RegistrationNotOpen => "Las inscripciones abren {opens}, ¡vuelve entonces!",
+        MessageKey::RegistrationClosed => "Las inscripciones cerraron {closed}, ¡hasta la próxima!",
+        MessageKey::Ineligible => "No cumples con las restricciones de este torneo (detalles en inglés):\n{reasons}",
+        MessageKey::NoTournamentActive => "No hay ningún torneo en curso",
+        MessageKey::AmbiguousTournament => "Hay varios torneos en curso, elige uno: `.register <{shorthands}> [usuario]`",
+        MessageKey::Disqualified => "Fuiste descalificado de este torneo",
+        MessageKey::SnapshotMissing => "Las estadísticas de este torneo aún no se han tomado, contacta al staff",
+        MessageKey::LinkedToDifferentAccount => "Estás vinculado a {linked}, no a {requested}",
+        MessageKey::AlreadyLinkedToOther => "¡Ya estás vinculado a un usuario de Tetr.io! Usa el comando `unlink` antes de vincular otro",
+        MessageKey::AccountTaken => "¡Otra persona ya vinculó este usuario de Tetr.io!",
+        MessageKey::AlreadyLinked => "¡Ya estás vinculado a este usuario de Tetr.io!",
+        MessageKey::PlayerNotFound => "No se encontró ese usuario en Tetr.io, ¡revisa cómo está escrito!",
+        MessageKey::NotLinked => "No tienes ningún usuario de Tetr.io vinculado, usa el comando `link` para vincular uno",
+        MessageKey::TetrioDown => "Tetr.io parece estar caído o en mantenimiento, se usan datos guardados cuando es posible",
+        MessageKey::TetrioError => "Tetr.io devolvió un error, inténtalo de nuevo en un minuto",
+        MessageKey::InvalidInput => "Entrada no válida: {reason}",
+        MessageKey::DatabaseFailure => "Algo salió mal al acceder a la base de datos, inténtalo más tarde",
+        MessageKey::LanguageSet => "Ahora te respondo en {language}",
+        MessageKey::LanguageUnknown => "Idioma `{code}` desconocido, los disponibles son {languages}",
+        MessageKey::LanguageCurrent => "Te respondo en {language}, los idiomas disponibles son {languages}. `.language <código>`",
+        MessageKey::InvalidBackdate => return None,
+    };
+    Some(template)
+}
+
+fn portuguese(key: MessageKey) -> Option<&'static str> {
+    let template = match key {
+        MessageKey::MissingAccount => "Não há nenhuma conta do Tetr.io vinculada a você, informe um nome de usuário. `.register [usuário]`",
+        MessageKey::AlreadyRegistered => "Você já está inscrito!",
+        MessageKey::AlreadyWaitlisted => "Você já está na lista de espera (#{position})!",
+        MessageKey::NotRegistered => "Você não está inscrito neste torneio",
+        MessageKey::HighestRankTooHigh => "Você já chegou a {rank}, mas só podem participar jogadores que nunca passaram de {expected}",
+        MessageKey::NotQualified => "Só podem se inscrever jogadores que se classificaram pelo {parent}",
+        MessageKey::AccountUnavailable => "O status da sua conta do Tetr.io vinculada é `{status}`, entre em contato com a staff",
+        MessageKey::RegistrationNotOpen => "As inscrições abrem {opens}, volte nesse momento!",
+        MessageKey::RegistrationClosed => "As inscrições fecharam {closed}, até a próxima!",
+        MessageKey::Ineligible => "Você não cumpre as restrições deste torneio (detalhes em inglês):\n{reasons}",
+        MessageKey::NoTournamentActive => "Não há nenhum torneio em andamento",
+        MessageKey::AmbiguousTournament => "Há vários torneios em andamento, escolha um: `.register <{shorthands}> [usuário]`",
+        MessageKey::Disqualified => "Você foi desclassificado deste torneio",
+        MessageKey::SnapshotMissing => "As estatísticas deste torneio ainda não foram registradas, entre em contato com a staff",
+        MessageKey::LinkedToDifferentAccount => "Você está vinculado a {linked}, não a {requested}",
+        MessageKey::AlreadyLinkedToOther => "Você já está vinculado a um usuário do Tetr.io! Use o comando `unlink` antes de vincular outro",
+        MessageKey::AccountTaken => "Outra pessoa já vinculou este usuário do Tetr.io!",
+        MessageKey::AlreadyLinked => "Você já está vinculado a este usuário do Tetr.io!",
+        MessageKey::PlayerNotFound => "Não foi possível encontrar esse usuário no Tetr.io, confira a ortografia!",
+        MessageKey::NotLinked => "Não há nenhum usuário do Tetr.io vinculado a você, use o comando `link` para vincular um",
+        MessageKey::TetrioDown => "O Tetr.io parece estar fora do ar ou em manutenção, usando dados salvos quando possível",
+        MessageKey::TetrioError => "O Tetr.io retornou um erro, tente novamente em um minuto",
+        MessageKey::InvalidInput => "Entrada inválida: {reason}",
+        MessageKey::DatabaseFailure => "Algo deu errado ao acessar o banco de dados, tente novamente mais tarde",
+        MessageKey::LanguageSet => "Agora respondo a você em {language}",
+        MessageKey::LanguageUnknown => "Idioma `{code}` desconhecido, os disponíveis são {languages}",
+        MessageKey::LanguageCurrent => "Respondo a você em {language}, os idiomas disponíveis são {languages}. `.language <código>`",
+        MessageKey::InvalidBackdate => return None,
+    };
+    Some(template)
+}
+
+// A message of the catalog with the values for its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedMessage {
+    pub key: MessageKey,
+    pub params: Vec<(&'static str, String)>,
+}
+
+impl LocalizedMessage {
+    pub fn new(key: MessageKey) -> LocalizedMessage {
+        LocalizedMessage {
+            key,
+            params: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &'static str, value: impl ToString) -> LocalizedMessage {
+        self.params.push((name, value.to_string()));
+        self
+    }
+
+    // Fills in the template of the language, or the English one if it wasn't translated
+    pub fn render(&self, language: Language) -> String {
+        let template = template(self.key, language).unwrap_or_else(|| english(self.key));
+        self.params
+            .iter()
+            .fold(template.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+    }
+}
+
+// Reply for a failed registration or withdrawal
+pub fn registration_error(err: &RegistrationError) -> LocalizedMessage {
+    match err {
+        // TODO: refer to a faq command for rd
+        RegistrationError::Ineligible(verdict) => LocalizedMessage::new(MessageKey::Ineligible)
+            .with(
+                "reasons",
+                verdict_to_lines(verdict, Style::Discord).join("\n"),
+            ),
+        RegistrationError::NoTournamentActive => {
+            LocalizedMessage::new(MessageKey::NoTournamentActive)
+        }
+        RegistrationError::AmbiguousTournament(shorthands) => {
+            LocalizedMessage::new(MessageKey::AmbiguousTournament)
+                .with("shorthands", shorthands.join("|"))
+        }
+        RegistrationError::MissingArgument(_) => LocalizedMessage::new(MessageKey::MissingAccount),
+        RegistrationError::DatabaseError(err) => database_error(err),
+        RegistrationError::AlreadyRegistered => {
+            LocalizedMessage::new(MessageKey::AlreadyRegistered)
+        }
+        RegistrationError::NotRegistered => LocalizedMessage::new(MessageKey::NotRegistered),
+        RegistrationError::SnapshotMissing => LocalizedMessage::new(MessageKey::SnapshotMissing),
+        RegistrationError::Disqualified => LocalizedMessage::new(MessageKey::Disqualified),
+        RegistrationError::HighestRankTooHigh { rank, expected } => {
+            LocalizedMessage::new(MessageKey::HighestRankTooHigh)
+                .with("rank", rank)
+                .with("expected", expected)
+        }
+        RegistrationError::RegistrationNotOpen(opens) => {
+            LocalizedMessage::new(MessageKey::RegistrationNotOpen)
+                .with("opens", discord_timestamp(*opens, 'R'))
+        }
+        RegistrationError::RegistrationClosed(closed) => {
+            LocalizedMessage::new(MessageKey::RegistrationClosed)
+                .with("closed", discord_timestamp(*closed, 'R'))
+        }
+        RegistrationError::NotQualified(parent) => {
+            LocalizedMessage::new(MessageKey::NotQualified).with("parent", parent)
+        }
+        RegistrationError::InvalidBackdate {
+            date,
+            earliest,
+            latest,
+        } => LocalizedMessage::new(MessageKey::InvalidBackdate)
+            .with("date", date)
+            .with("earliest", earliest)
+            .with("latest", latest),
+        RegistrationError::AccountUnavailable(status) => {
+            LocalizedMessage::new(MessageKey::AccountUnavailable).with("status", status)
+        }
+        RegistrationError::AlreadyWaitlisted(position) => {
+            LocalizedMessage::new(MessageKey::AlreadyWaitlisted).with("position", position)
+        }
+        RegistrationError::LinkedToDifferentAccount { linked, requested } => {
+            LocalizedMessage::new(MessageKey::LinkedToDifferentAccount)
+                .with("linked", linked)
+                .with("requested", requested)
+        }
+    }
+}
+
+// Reply for a failed database operation while registering or linking
+// Internal failures all get the same message, the details are only for the logs
+pub fn database_error(err: &DatabaseError) -> LocalizedMessage {
+    match err {
+        DatabaseError::NotFound | DatabaseError::TetrioApiError(TetrioApiError::NotFound) => {
+            LocalizedMessage::new(MessageKey::PlayerNotFound)
+        }
+        DatabaseError::TetrioApiError(TetrioApiError::CircuitOpen { .. }) => {
+            LocalizedMessage::new(MessageKey::TetrioDown)
+        }
+        DatabaseError::TetrioApiError(TetrioApiError::Upstream(_)) => {
+            LocalizedMessage::new(MessageKey::TetrioError)
+        }
+        DatabaseError::DuplicateDiscordEntry => {
+            LocalizedMessage::new(MessageKey::AlreadyLinkedToOther)
+        }
+        DatabaseError::DuplicateTetrioEntry => LocalizedMessage::new(MessageKey::AccountTaken),
+        DatabaseError::AlreadyLinked => LocalizedMessage::new(MessageKey::AlreadyLinked),
+        DatabaseError::InvalidInput(reason) => {
+            LocalizedMessage::new(MessageKey::InvalidInput).with("reason", reason)
+        }
+        DatabaseError::ConnectionFailed
+        | DatabaseError::Mongo(_)
+        | DatabaseError::CouldNotPush
+        | DatabaseError::DuplicateTournamentEntry
+        | DatabaseError::CouldNotParse(_)
+        | DatabaseError::FieldNotSet
+        | DatabaseError::SnapshotExists { .. } => {
+            LocalizedMessage::new(MessageKey::DatabaseFailure)
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tokio::time;
+
+use crate::database::flags::Feature;
+use crate::database::{DatabaseResult, LocalDatabase};
+use crate::status::{format_time, StatusReport};
+use crate::tetrio::news::posts_after;
+use crate::tetrio::Rank;
+
+// How often the global news stream is polled
+const POLL_INTERVAL: time::Duration = time::Duration::from_secs(5 * 60);
+
+// What the news watcher did since startup, for `.status`
+#[derive(Debug, Default)]
+pub struct NewsWatcherStatus {
+    pub last_poll: Option<DateTime<Utc>>,
+    pub announced: u64,
+    pub last_error: Option<String>,
+}
+
+impl TypeMapKey for NewsWatcherStatus {
+    type Value = Mutex<NewsWatcherStatus>;
+}
+
+impl StatusReport for NewsWatcherStatus {
+    fn name(&self) -> String {
+        "News watcher".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            (
+                "Poll interval".to_string(),
+                format!("{}s", POLL_INTERVAL.as_secs()),
+            ),
+            ("Last poll".to_string(), format_time(self.last_poll)),
+            ("Announced rank-ups".to_string(), self.announced.to_string()),
+            (
+                "Last error".to_string(),
+                self.last_error.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+        ]
+    }
+}
+
+// A rank-up of a linked player that should be announced
+struct Announcement {
+    discord_id: u64,
+    username: String,
+    rank: Rank,
+}
+
+// Reads the news since the last poll and moves the cursor past them.
+// The first poll only sets the cursor, so the whole backlog isn't announced at once.
+// Without a channel nothing is announced, but the cursor still moves.
+async fn collect(db: &LocalDatabase) -> DatabaseResult<(Option<u64>, Vec<Announcement>)> {
+    let settings = db.settings.get().await?;
+    let posts = crate::tetrio::news::request("global").await?.data.news;
+    let newest = match posts.iter().map(|post| &post._id).max() {
+        Some(newest) => newest.clone(),
+        None => return Ok((settings.news_channel, Vec::new())),
+    };
+
+    let mut announcements = Vec::new();
+    if let Some(last) = &settings.last_news_post {
+        for rankup in posts_after(&posts, last)
+            .iter()
+            .filter_map(|post| post.rankup())
+        {
+            let player = db.players.get_player_by_tetrio(&rankup.username).await?;
+            if let Some(discord_id) = player.and_then(|p| p.discord_id) {
+                announcements.push(Announcement {
+                    discord_id,
+                    username: rankup.username,
+                    rank: rankup.rank,
+                });
+            }
+        }
+    }
+
+    if settings.last_news_post.as_ref() != Some(&newest) {
+        db.settings.set_last_news_post(&newest).await?;
+    }
+    Ok((settings.news_channel, announcements))
+}
+
+async fn announce(http: &Http, channel_id: u64, announcement: &Announcement) -> Result<(), String> {
+    let rank = announcement.rank;
+    ChannelId(channel_id)
+        .send_message(http, |m| {
+            m.embed(|e| {
+                e.title(format!("{} ranked up!", announcement.username))
+                    .description(format!(
+                        "{} <@{}> reached **{}**, congratulations!",
+                        rank.to_emoji(),
+                        announcement.discord_id,
+                        rank
+                    ))
+                    .color(u64::from_str_radix(rank.to_color(), 16).unwrap_or(0))
+                    .thumbnail(rank.to_img_url())
+            })
+            .allowed_mentions(|am| am.empty_parse())
+        })
+        .await
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+// Announces the rank-ups since the last poll, if the news watcher is enabled
+pub async fn run(data: &Arc<RwLock<TypeMap>>, http: &Http) {
+    let db = match data.read().await.get::<LocalDatabase>() {
+        Some(db) => db.clone(),
+        None => return,
+    };
+    if !db.flags.is_enabled(Feature::NewsPoller).await {
+        return;
+    }
+
+    let collected = collect(&db).await.map_err(|err| err.to_string());
+
+    let mut announced = 0;
+    let mut last_error = None;
+    match collected {
+        Ok((Some(channel_id), announcements)) => {
+            for announcement in &announcements {
+                match announce(http, channel_id, announcement).await {
+                    Ok(()) => announced += 1,
+                    Err(err) => {
+                        tracing::warn!("Could not announce a rank-up: {}", err);
+                        last_error = Some(err);
+                    }
+                }
+            }
+        }
+        Ok((None, _)) => {}
+        Err(err) => {
+            tracing::warn!("Could not poll the news: {}", err);
+            last_error = Some(err);
+        }
+    }
+
+    if let Some(status) = data.read().await.get::<NewsWatcherStatus>() {
+        let mut status = status.lock().await;
+        status.last_poll = Some(Utc::now());
+        status.announced += announced;
+        status.last_error = last_error;
+    }
+}
+
+// Polls the news in the background until the bot shuts down
+pub fn spawn(data: Arc<RwLock<TypeMap>>, http: Arc<Http>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            run(&data, &http).await;
+        }
+    });
+}
@@ -0,0 +1,142 @@
+use serenity::framework::standard::CommandResult;
+use serenity::http::AttachmentType;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+
+use crate::discord::util::{sanitize_mentions, say};
+
+// Leaves some room for the title and code block markers
+const MAX_INLINE_LENGTH: usize = 1800;
+
+// Tabular output for staff facing reports
+//
+// Small reports get posted as a code block, larger ones get attached as a text table and a CSV file.
+pub struct Report {
+    pub title: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub notes: Vec<String>,
+}
+
+impl Report {
+    pub fn new(title: &str, columns: &[&str]) -> Report {
+        Report {
+            title: title.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn push_note(&mut self, note: &str) {
+        self.notes.push(note.to_string());
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| c.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if let Some(width) = widths.get_mut(i) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+        }
+
+        let format_row = |row: &[String]| {
+            row.iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let width = widths.get(i).copied().unwrap_or(0);
+                    format!("{:width$}", cell, width = width)
+                })
+                .collect::<Vec<String>>()
+                .join(" | ")
+                .trim_end()
+                .to_string()
+        };
+
+        let mut lines = vec![format_row(&self.columns)];
+        lines.push(
+            widths
+                .iter()
+                .map(|w| "-".repeat(*w))
+                .collect::<Vec<String>>()
+                .join("-+-"),
+        );
+        lines.extend(self.rows.iter().map(|row| format_row(row)));
+        lines.join("\n")
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec![csv_line(&self.columns)];
+        lines.extend(self.rows.iter().map(|row| csv_line(row)));
+        lines.join("\n")
+    }
+
+    pub fn file_name(&self) -> String {
+        let name: String = self
+            .title
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        name.trim_matches('_').to_string()
+    }
+}
+
+// Text bar for charts, scaled so the largest value gets `width` characters
+pub fn bar(value: usize, max: usize, width: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    let length = (value * width + max - 1) / max;
+    "█".repeat(length)
+}
+
+pub fn csv_line(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            if cell.contains(',') || cell.contains('"') || cell.contains('\n') {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+pub async fn send_report(ctx: &Context, channel_id: ChannelId, report: &Report) -> CommandResult {
+    let table = report.to_table();
+    // Notes can contain names and reasons that users typed
+    let notes = sanitize_mentions(&report.notes.join("\n"));
+
+    if table.len() + notes.len() <= MAX_INLINE_LENGTH {
+        say(
+            &ctx,
+            channel_id,
+            format!("**{}**\n```\n{}\n```{}", report.title, table, notes),
+        )
+        .await?;
+    } else {
+        let file_name = report.file_name();
+        let csv = report.to_csv();
+        let attachments = vec![
+            AttachmentType::from((table.as_bytes(), format!("{}.txt", file_name).as_str())),
+            AttachmentType::from((csv.as_bytes(), format!("{}.csv", file_name).as_str())),
+        ];
+        channel_id
+            .send_files(&ctx.http, attachments, |m| {
+                m.content(format!("**{}**\n{}", report.title, notes))
+                    .allowed_mentions(|am| am.empty_parse())
+            })
+            .await?;
+    }
+
+    Ok(())
+}
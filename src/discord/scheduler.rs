@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serenity::http::Http;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tokio::time;
+
+use crate::database::jobs::{
+    after_failure, after_success, AnnouncementPayload, JobKind, RegistrationPhase, ReminderPayload,
+    ScheduledJob,
+};
+use crate::database::LocalDatabase;
+use crate::discord::util::badged;
+use crate::status::{format_time, StatusReport};
+
+// How often the dispatcher looks for due jobs
+const POLL_INTERVAL: time::Duration = time::Duration::from_secs(30);
+
+// What the dispatcher did since startup, for `.status`
+#[derive(Debug, Default)]
+pub struct SchedulerStatus {
+    pub last_poll: Option<DateTime<Utc>>,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub interrupted_on_startup: i64,
+}
+
+impl TypeMapKey for SchedulerStatus {
+    type Value = Mutex<SchedulerStatus>;
+}
+
+impl StatusReport for SchedulerStatus {
+    fn name(&self) -> String {
+        "Scheduler".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            (
+                "Poll interval".to_string(),
+                format!("{}s", POLL_INTERVAL.as_secs()),
+            ),
+            ("Last poll".to_string(), format_time(self.last_poll)),
+            ("Succeeded runs".to_string(), self.succeeded.to_string()),
+            ("Failed runs".to_string(), self.failed.to_string()),
+            (
+                "Interrupted on startup".to_string(),
+                self.interrupted_on_startup.to_string(),
+            ),
+        ]
+    }
+}
+
+async fn post(http: &Http, channel_id: u64, text: &str) -> Result<(), String> {
+    ChannelId(channel_id)
+        .send_message(http, |m| {
+            m.content(text).allowed_mentions(|am| am.empty_parse())
+        })
+        .await
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+async fn registration_announcement(
+    http: &Http,
+    db: &LocalDatabase,
+    job: &ScheduledJob,
+) -> Result<(), String> {
+    let payload: AnnouncementPayload = job.payload().map_err(|err| err.to_string())?;
+    let tournament = db
+        .tournaments
+        .get_tournament(&payload.tournament)
+        .await
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("Tournament {} not found", payload.tournament))?;
+
+    let text = match payload.phase {
+        RegistrationPhase::Open => format!(
+            "Registration for {} is now open! Use `.register <username>` in the bot channels to sign up.",
+            tournament.name
+        ),
+        RegistrationPhase::Close => format!(
+            "Registration for {} is now closed, {} players registered.",
+            tournament.name,
+            tournament.registered_players().len()
+        ),
+    };
+    post(http, payload.channel_id, &badged(&tournament, &text)).await
+}
+
+async fn check_in_reminder(
+    http: &Http,
+    db: &LocalDatabase,
+    job: &ScheduledJob,
+) -> Result<(), String> {
+    let payload: ReminderPayload = job.payload().map_err(|err| err.to_string())?;
+    let tournament = db
+        .tournaments
+        .get_tournament(&payload.tournament)
+        .await
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("Tournament {} not found", payload.tournament))?;
+
+    if tournament.check_in_msg.is_none() {
+        return Err("There is no check-in message yet".to_string());
+    }
+
+    let registered = tournament.registered_players().len();
+    let checked_in = tournament
+        .check_ins
+        .iter()
+        .filter(|record| record.generation == tournament.check_in_generation)
+        .count();
+    let text = format!(
+        "Check-in is open! {} of {} registered players haven't checked in yet, react to the check-in message in <#{}> to check in.",
+        registered.saturating_sub(checked_in),
+        registered,
+        crate::commands::tournament::check_in_channel(&tournament)
+    );
+    post(http, payload.channel_id, &badged(&tournament, &text)).await
+}
+
+// Runs the handler of a job's kind
+async fn execute(http: &Http, db: &LocalDatabase, job: &ScheduledJob) -> Result<(), String> {
+    match job.kind {
+        JobKind::RegistrationAnnouncement => registration_announcement(http, db, job).await,
+        JobKind::CheckInReminder => check_in_reminder(http, db, job).await,
+    }
+}
+
+// Runs every due job, one after another
+pub async fn run_due(data: &Arc<RwLock<TypeMap>>, http: &Http) {
+    // the data lock isn't held while jobs run, they might take a while
+    let db = match data.read().await.get::<LocalDatabase>() {
+        Some(db) => db.clone(),
+        None => return,
+    };
+
+    let (mut succeeded, mut failed) = (0, 0);
+    loop {
+        let job = match db.jobs.claim_due(Utc::now()).await {
+            Ok(Some(job)) => job,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::warn!("Could not claim scheduled jobs: {}", err);
+                break;
+            }
+        };
+
+        let result = execute(http, &db, &job).await;
+        let now = Utc::now();
+        let finished = match &result {
+            Ok(()) => {
+                succeeded += 1;
+                tracing::info!("Ran {} job {}", job.kind, job._id);
+                db.jobs.finish(&job, after_success(&job, now), None).await
+            }
+            Err(err) => {
+                failed += 1;
+                tracing::warn!("{} job {} failed: {}", job.kind, job._id, err);
+                db.jobs
+                    .finish(&job, after_failure(&job, now), Some(err))
+                    .await
+            }
+        };
+        if let Err(err) = finished {
+            tracing::error!("Could not save the outcome of job {}: {}", job._id, err);
+        }
+    }
+
+    if let Some(status) = data.read().await.get::<SchedulerStatus>() {
+        let mut status = status.lock().await;
+        status.last_poll = Some(Utc::now());
+        status.succeeded += succeeded;
+        status.failed += failed;
+    }
+}
+
+// Runs the dispatcher in the background until the bot shuts down
+pub fn spawn(data: Arc<RwLock<TypeMap>>, http: Arc<Http>) {
+    tokio::spawn(async move {
+        {
+            let data_read = data.read().await;
+            if let Some(db) = data_read.get::<LocalDatabase>() {
+                match db.jobs.interrupted().await {
+                    Ok(count) => {
+                        if count > 0 {
+                            tracing::warn!(
+                                "{} scheduled jobs were interrupted by a restart",
+                                count
+                            );
+                        }
+                        if let Some(status) = data_read.get::<SchedulerStatus>() {
+                            status.lock().await.interrupted_on_startup = count;
+                        }
+                    }
+                    Err(err) => tracing::warn!("Could not check for interrupted jobs: {}", err),
+                }
+            }
+        }
+
+        let mut interval = time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            run_due(&data, &http).await;
+        }
+    });
+}
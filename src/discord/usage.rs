@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use serenity::prelude::*;
+use tokio::time;
+
+use crate::database::usage::UsageCounters;
+use crate::database::LocalDatabase;
+
+// How often in-memory usage counters are written to the database
+const FLUSH_INTERVAL: time::Duration = time::Duration::from_secs(5 * 60);
+
+impl TypeMapKey for UsageCounters {
+    type Value = Mutex<UsageCounters>;
+}
+
+// Writes the in-memory counters to the database, failed ones are kept for the next flush
+pub async fn flush(data: &Arc<RwLock<TypeMap>>) {
+    let data_read = data.read().await;
+    let (counters, db) = match (
+        data_read.get::<UsageCounters>(),
+        data_read.get::<LocalDatabase>(),
+    ) {
+        (Some(counters), Some(db)) => (counters, db),
+        _ => return,
+    };
+
+    let drained = counters.lock().await.drain();
+    if drained.is_empty() {
+        return;
+    }
+
+    let failed = db.usage.flush(drained).await;
+    if !failed.is_empty() {
+        counters.lock().await.restore(failed);
+    }
+}
+
+// Flushes usage counters in the background until the bot shuts down
+pub fn spawn(data: Arc<RwLock<TypeMap>>) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(FLUSH_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            flush(&data).await;
+        }
+    });
+}
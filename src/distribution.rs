@@ -0,0 +1,154 @@
+//! Rank distribution of eligible players, for the announcement graphics
+//!
+//! The distribution itself comes from [`crate::database::players::PlayerCollection::eligible_distribution()`],
+//! this module only renders it. The SVG is written by hand, it's a handful of rectangles and labels,
+//! which isn't worth a plotting dependency.
+
+#![warn(missing_docs)]
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::tetrio::Rank;
+
+/// Width of a single bar in pixels
+const BAR_WIDTH: u64 = 40;
+
+/// Space between bars and around the chart in pixels
+const GAP: u64 = 10;
+
+/// Height of the tallest bar in pixels
+const CHART_HEIGHT: u64 = 200;
+
+/// Space above the bars for the counts and below the bars for the rank labels in pixels
+const LABEL_HEIGHT: u64 = 20;
+
+/// Amount of players over all ranks
+pub fn total(distribution: &BTreeMap<Rank, u64>) -> u64 {
+    distribution.values().sum()
+}
+
+/// Share of a rank in percent, `0.0` if there are no players at all
+pub fn percentage(distribution: &BTreeMap<Rank, u64>, rank: Rank) -> f64 {
+    let total = total(distribution);
+    if total == 0 {
+        return 0.0;
+    }
+    distribution.get(&rank).copied().unwrap_or(0) as f64 * 100.0 / total as f64
+}
+
+/// Renders the distribution as an SVG bar chart, lowest rank on the left
+///
+/// Every rank in the map gets a bar in its rank color, scaled to the largest count, with the
+/// count above and the rank below. Ranks that aren't in the map are left out.
+pub fn to_svg(distribution: &BTreeMap<Rank, u64>) -> String {
+    let bars = distribution.len() as u64;
+    let width = bars.max(1) * (BAR_WIDTH + GAP) + GAP;
+    let height = CHART_HEIGHT + 2 * LABEL_HEIGHT + 2 * GAP;
+    let baseline = GAP + LABEL_HEIGHT + CHART_HEIGHT;
+    let max = distribution.values().copied().max().unwrap_or(0);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<rect width="{}" height="{}" fill="#ffffff"/>"##,
+        width, height
+    )
+    .unwrap();
+
+    if max == 0 {
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-family="sans-serif" font-size="14">No players</text>"#,
+            width / 2,
+            height / 2
+        )
+        .unwrap();
+    }
+
+    for (i, (rank, count)) in distribution.iter().enumerate() {
+        let x = GAP + i as u64 * (BAR_WIDTH + GAP);
+        let center = x + BAR_WIDTH / 2;
+        let bar_height = if max == 0 {
+            0
+        } else {
+            count * CHART_HEIGHT / max
+        };
+
+        writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{}"/>"##,
+            x,
+            baseline - bar_height,
+            BAR_WIDTH,
+            bar_height,
+            rank.to_color()
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-family="sans-serif" font-size="12">{}</text>"#,
+            center,
+            baseline - bar_height - 4,
+            count
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-family="sans-serif" font-size="12">{}</text>"#,
+            center,
+            baseline + LABEL_HEIGHT - 4,
+            rank
+        )
+        .unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distribution() -> BTreeMap<Rank, u64> {
+        let mut distribution = BTreeMap::new();
+        distribution.insert(Rank::A, 30);
+        distribution.insert(Rank::SPlus, 0);
+        distribution.insert(Rank::B, 12);
+        distribution.insert(Rank::Unranked, 5);
+        distribution.insert(Rank::S, 7);
+        distribution
+    }
+
+    #[test]
+    fn svg_matches_golden_file() {
+        // Update the file when the layout changes on purpose, and check it in a browser
+        let golden = include_str!("../tests/golden/rank_distribution.svg");
+        assert_eq!(to_svg(&distribution()), golden);
+    }
+
+    #[test]
+    fn empty_distribution_has_a_placeholder() {
+        let svg = to_svg(&BTreeMap::new());
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="60""#));
+        assert!(svg.contains(">No players</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn totals_and_percentages() {
+        let distribution = distribution();
+        assert_eq!(total(&distribution), 54);
+        assert!((percentage(&distribution, Rank::A) - 3000.0 / 54.0).abs() < 1e-9);
+        assert_eq!(percentage(&distribution, Rank::X), 0.0);
+        assert_eq!(percentage(&BTreeMap::new(), Rank::A), 0.0);
+    }
+}
@@ -0,0 +1,450 @@
+//! Heuristics for finding one person registered twice with two Discord and two Tetrio accounts
+//!
+//! Every pair of registered players gets scored on a couple of weak signals, which only become
+//! interesting when several of them add up. The result is a list of pairs worth a closer look,
+//! not proof of anything, so staff always have to verify manually.
+
+#![warn(missing_docs)]
+
+use chrono::{DateTime, Utc};
+
+use crate::tetrio::Rank;
+
+#[derive(Debug, Clone)]
+/// A registered player with everything the heuristics look at
+pub struct DupeCandidate {
+    /// Player's Tetrio ID
+    pub tetrio_id: String,
+    /// Player's Tetrio username
+    pub tetrio_username: String,
+    /// Linked Discord ID, if any
+    pub discord_id: Option<u64>,
+    /// Discord username and server nickname, whatever is known
+    pub discord_names: Vec<String>,
+    /// When the Discord account joined the server, if known
+    pub joined_guild_at: Option<DateTime<Utc>>,
+    /// When the player registered
+    pub registered_at: DateTime<Utc>,
+    /// Rank used for seeding
+    pub rank: Rank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Weights and thresholds of the individual signals
+pub struct DupeWeights {
+    /// Weight of similar Discord names, scaled by similarity
+    pub discord_name: f64,
+    /// Name similarity (0 to 1) from which Discord names count as similar
+    pub min_name_similarity: f64,
+    /// Weight of joining the server shortly after each other, scaled by how close the joins are
+    pub guild_join: f64,
+    /// Window in minutes in which server joins count as close
+    pub guild_join_minutes: i64,
+    /// Weight of registering shortly after each other within the same rank band
+    pub registration: f64,
+    /// Window in minutes in which registrations count as close
+    pub registration_minutes: i64,
+    /// Weight of Tetrio usernames sharing a long common substring, scaled by its length
+    pub tetrio_substring: f64,
+    /// Minimum length of a common substring
+    pub min_substring_length: usize,
+    /// Minimum score for a pair to be reported
+    pub threshold: f64,
+}
+
+impl Default for DupeWeights {
+    fn default() -> Self {
+        DupeWeights {
+            discord_name: 3.0,
+            min_name_similarity: 0.8,
+            guild_join: 2.0,
+            guild_join_minutes: 10,
+            registration: 1.5,
+            registration_minutes: 10,
+            tetrio_substring: 2.0,
+            min_substring_length: 4,
+            threshold: 2.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A signal that contributed to a pair's score
+pub enum Signal {
+    /// Discord names are similar, with the best matching names and their similarity
+    DiscordName(String, String, f64),
+    /// Both joined the server within the amount of minutes
+    GuildJoin(i64),
+    /// Both registered within the amount of minutes, in the same rank band
+    Registration(i64),
+    /// Tetrio usernames share the substring
+    TetrioSubstring(String),
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Signal::DiscordName(a, b, similarity) => write!(
+                f,
+                "Discord names {} / {} ({:.0}% similar)",
+                a,
+                b,
+                similarity * 100f64
+            ),
+            Signal::GuildJoin(minutes) => write!(f, "joined the server {}min apart", minutes),
+            Signal::Registration(minutes) => {
+                write!(f, "registered {}min apart, same rank band", minutes)
+            }
+            Signal::TetrioSubstring(substring) => {
+                write!(f, "Tetrio names share \"{}\"", substring)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A pair of registrations that might belong to the same person
+pub struct DupePair {
+    /// Index of the first player in the candidate list
+    pub a: usize,
+    /// Index of the second player in the candidate list
+    pub b: usize,
+    /// Combined score of all signals
+    pub score: f64,
+    /// Signals that contributed to the score
+    pub signals: Vec<Signal>,
+}
+
+/// Lowercases a name and removes everything that's not a letter or digit
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Edit distance between two strings
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Similarity of two names from 0 to 1, based on the edit distance of their normalized forms
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 0f64;
+    }
+    1f64 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// Longest substring both strings contain, after normalizing
+pub fn longest_common_substring(a: &str, b: &str) -> String {
+    let a: Vec<char> = normalize(a).chars().collect();
+    let b: Vec<char> = normalize(b).chars().collect();
+    let mut lengths = vec![0usize; b.len() + 1];
+    let (mut best_length, mut best_end) = (0, 0);
+
+    for i in 1..=a.len() {
+        let mut diagonal = 0;
+        for j in 1..=b.len() {
+            let above = lengths[j];
+            lengths[j] = if a[i - 1] == b[j - 1] {
+                diagonal + 1
+            } else {
+                0
+            };
+            if lengths[j] > best_length {
+                best_length = lengths[j];
+                best_end = i;
+            }
+            diagonal = above;
+        }
+    }
+
+    a[best_end - best_length..best_end].iter().collect()
+}
+
+/// Scores a single pair, returning the score and the contributing signals
+pub fn score_pair(
+    a: &DupeCandidate,
+    b: &DupeCandidate,
+    weights: &DupeWeights,
+) -> (f64, Vec<Signal>) {
+    let mut score = 0f64;
+    let mut signals = Vec::new();
+
+    let best_names = a
+        .discord_names
+        .iter()
+        .flat_map(|name_a| {
+            b.discord_names
+                .iter()
+                .map(move |name_b| (name_a, name_b, name_similarity(name_a, name_b)))
+        })
+        .fold(
+            None,
+            |best: Option<(&String, &String, f64)>, current| match best {
+                Some(best) if best.2 >= current.2 => Some(best),
+                _ => Some(current),
+            },
+        );
+    if let Some((name_a, name_b, similarity)) = best_names {
+        if similarity >= weights.min_name_similarity {
+            score += weights.discord_name * similarity;
+            signals.push(Signal::DiscordName(
+                name_a.clone(),
+                name_b.clone(),
+                similarity,
+            ));
+        }
+    }
+
+    if let (Some(joined_a), Some(joined_b)) = (a.joined_guild_at, b.joined_guild_at) {
+        let minutes = (joined_a - joined_b).num_minutes().abs();
+        if minutes <= weights.guild_join_minutes {
+            let closeness = 1f64 - minutes as f64 / (weights.guild_join_minutes + 1) as f64;
+            score += weights.guild_join * closeness;
+            signals.push(Signal::GuildJoin(minutes));
+        }
+    }
+
+    let minutes = (a.registered_at - b.registered_at).num_minutes().abs();
+    let same_band = a.rank.max(b.rank) <= a.rank.min(b.rank) + 1;
+    if minutes <= weights.registration_minutes && same_band {
+        score += weights.registration;
+        signals.push(Signal::Registration(minutes));
+    }
+
+    let substring = longest_common_substring(&a.tetrio_username, &b.tetrio_username);
+    let substring_length = substring.chars().count();
+    if substring_length >= weights.min_substring_length {
+        let shortest = normalize(&a.tetrio_username)
+            .chars()
+            .count()
+            .min(normalize(&b.tetrio_username).chars().count());
+        score += weights.tetrio_substring * substring_length as f64 / shortest as f64;
+        signals.push(Signal::TetrioSubstring(substring));
+    }
+
+    (score, signals)
+}
+
+/// Scores every pair of candidates and returns those above the threshold, highest score first
+///
+/// Pairs need at least two signals, a single one (like similar names) is too common on its own.
+pub fn possible_dupes(candidates: &[DupeCandidate], weights: &DupeWeights) -> Vec<DupePair> {
+    let mut pairs = Vec::new();
+    for a in 0..candidates.len() {
+        for b in a + 1..candidates.len() {
+            if candidates[a].discord_id.is_some()
+                && candidates[a].discord_id == candidates[b].discord_id
+            {
+                continue;
+            }
+            let (score, signals) = score_pair(&candidates[a], &candidates[b], weights);
+            if score >= weights.threshold && signals.len() >= 2 {
+                pairs.push(DupePair {
+                    a,
+                    b,
+                    score,
+                    signals,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by(|x, y| {
+        y.score
+            .partial_cmp(&x.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    fn candidate(id: u64, username: &str, name: &str) -> DupeCandidate {
+        DupeCandidate {
+            tetrio_id: format!("5e47696db7c60f23a497ee{:02}", id),
+            tetrio_username: username.to_string(),
+            discord_id: Some(id),
+            discord_names: vec![name.to_string()],
+            joined_guild_at: None,
+            registered_at: now() + Duration::days(id as i64),
+            rank: Rank::S,
+        }
+    }
+
+    // Two players without anything in common
+    fn unrelated() -> (DupeCandidate, DupeCandidate) {
+        (candidate(1, "qwer", "alpha"), candidate(2, "asdf", "zzzz"))
+    }
+
+    fn approx(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn string_helpers() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert!(approx(name_similarity("Ice_Dynamix", "icedynamix"), 1f64));
+        assert!(approx(name_similarity("abcd", "abce"), 0.75));
+        assert!(approx(name_similarity("", "!!"), 0f64));
+        assert_eq!(
+            longest_common_substring("Storm_Breaker", "xstormbreaker99"),
+            "stormbreaker"
+        );
+        assert_eq!(longest_common_substring("abc", "xyz"), "");
+    }
+
+    #[test]
+    fn unrelated_players_score_nothing() {
+        let (a, b) = unrelated();
+        assert_eq!(score_pair(&a, &b, &DupeWeights::default()), (0f64, vec![]));
+    }
+
+    #[test]
+    fn similar_discord_names() {
+        let (a, mut b) = unrelated();
+        b.discord_names = vec!["zzzz".to_string(), "Alpha.".to_string()];
+        let (score, signals) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 3f64));
+        assert_eq!(
+            signals,
+            vec![Signal::DiscordName(
+                "alpha".to_string(),
+                "Alpha.".to_string(),
+                1f64
+            )]
+        );
+
+        // below the minimum similarity
+        b.discord_names = vec!["alphabet".to_string()];
+        assert_eq!(score_pair(&a, &b, &DupeWeights::default()).0, 0f64);
+    }
+
+    #[test]
+    fn close_guild_joins_scale_with_closeness() {
+        let (mut a, mut b) = unrelated();
+        a.joined_guild_at = Some(now());
+        b.joined_guild_at = Some(now());
+        let (score, signals) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 2f64));
+        assert_eq!(signals, vec![Signal::GuildJoin(0)]);
+
+        b.joined_guild_at = Some(now() - Duration::minutes(5));
+        let (score, signals) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 2f64 * (1f64 - 5f64 / 11f64)));
+        assert_eq!(signals, vec![Signal::GuildJoin(5)]);
+
+        b.joined_guild_at = Some(now() - Duration::minutes(11));
+        assert!(score_pair(&a, &b, &DupeWeights::default()).1.is_empty());
+    }
+
+    #[test]
+    fn close_registrations_need_the_same_rank_band() {
+        let (mut a, mut b) = unrelated();
+        a.registered_at = now();
+        b.registered_at = now() + Duration::minutes(3);
+        b.rank = Rank::SPlus;
+        let (score, signals) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 1.5));
+        assert_eq!(signals, vec![Signal::Registration(3)]);
+
+        b.rank = Rank::U;
+        assert!(score_pair(&a, &b, &DupeWeights::default()).1.is_empty());
+    }
+
+    #[test]
+    fn shared_tetrio_substring_scales_with_length() {
+        let (mut a, mut b) = unrelated();
+        a.tetrio_username = "stormbreaker".to_string();
+        b.tetrio_username = "stormbreaker99".to_string();
+        let (score, signals) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 2f64));
+        assert_eq!(
+            signals,
+            vec![Signal::TetrioSubstring("stormbreaker".to_string())]
+        );
+
+        b.tetrio_username = "xstorm".to_string();
+        let (score, _) = score_pair(&a, &b, &DupeWeights::default());
+        assert!(approx(score, 2f64 * 5f64 / 6f64));
+
+        b.tetrio_username = "sto".to_string();
+        assert!(score_pair(&a, &b, &DupeWeights::default()).1.is_empty());
+    }
+
+    // Player 0 shares a name and a registration window with player 1, and a server join and a
+    // Tetrio name with player 2
+    fn trio() -> Vec<DupeCandidate> {
+        let mut first = candidate(0, "stormbreaker", "Nebula");
+        first.joined_guild_at = Some(now());
+        first.registered_at = now();
+        let mut second = candidate(1, "qwxz", "nebula.");
+        second.registered_at = now() + Duration::minutes(2);
+        let mut third = candidate(2, "stormbreaker99", "Zyx");
+        third.joined_guild_at = Some(now() + Duration::minutes(1));
+        vec![first, second, third]
+    }
+
+    #[test]
+    fn weights_change_the_ranking() {
+        let candidates = trio();
+        let pairs = |weights: &DupeWeights| -> Vec<(usize, usize)> {
+            possible_dupes(&candidates, weights)
+                .iter()
+                .map(|pair| (pair.a, pair.b))
+                .collect()
+        };
+
+        assert_eq!(pairs(&DupeWeights::default()), vec![(0, 1), (0, 2)]);
+        let names_matter_less = DupeWeights {
+            discord_name: 1.0,
+            threshold: 1.0,
+            ..DupeWeights::default()
+        };
+        assert_eq!(pairs(&names_matter_less), vec![(0, 2), (0, 1)]);
+        let strict = DupeWeights {
+            threshold: 4.0,
+            ..DupeWeights::default()
+        };
+        assert_eq!(pairs(&strict), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn single_signals_and_same_accounts_are_not_reported() {
+        let (a, mut b) = unrelated();
+        b.discord_names = vec!["alpha".to_string()];
+        let weights = DupeWeights::default();
+        assert!(score_pair(&a, &b, &weights).0 >= weights.threshold);
+        assert!(possible_dupes(&[a.clone(), b.clone()], &weights).is_empty());
+
+        let mut candidates = trio();
+        candidates[1].discord_id = candidates[0].discord_id;
+        let pairs = possible_dupes(&candidates, &weights);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].a, pairs[0].b), (0, 2));
+    }
+}
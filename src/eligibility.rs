@@ -0,0 +1,668 @@
+//! Structured tournament eligibility results and their human readable explanations
+//!
+//! The eligibility check produces an [`EligibilityVerdict`] containing every criterion with the
+//! compared values, which is then turned into text with [`verdict_to_lines()`]. Every place that
+//! explains eligibility to a user should go through the formatter, so the wording stays consistent.
+//!
+//! Before the announcement snapshot is taken, a preview can be evaluated with [`evaluate_preview()`].
+//! It only looks at current stats and is never eligible, see [`EligibilityMode`].
+
+#![warn(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::database::tournaments::TournamentRestrictions;
+use crate::tetrio::leaderboard::LeaderboardUser;
+use crate::tetrio::Rank;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// A single requirement of the tournament restrictions
+pub enum Criterion {
+    /// Player has to be ranked on announcement day, unless the tournament allows unranked players
+    RankedOnAnnouncement,
+    /// Rank on announcement day has to be at most the max rank
+    AnnouncementRank,
+    /// Ranked games played by announcement day have to be at least the minimum
+    RankedGames,
+    /// Rating deviation on announcement day has to be at most the maximum
+    RatingDeviation,
+    /// Current rank has to be at most the max rank
+    CurrentRank,
+    /// Highest rank reached has to be at most the peak rank limit, one above the max rank by default
+    HighestRank,
+    /// Account has to be at least a certain amount of days old
+    AccountAge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+/// A value that was compared during a check
+pub enum Measure {
+    /// A league rank
+    Rank(Rank),
+    /// An amount, like played games
+    Count(i64),
+    /// A rating deviation
+    Deviation(f64),
+    /// A point in time
+    Date(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// Result of checking a single criterion
+pub struct Check {
+    /// Checked criterion
+    pub criterion: Criterion,
+    /// Whether the criterion is fulfilled
+    pub passed: bool,
+    /// Player's value, `None` if the player has no such value (e.g. no recorded rank ups)
+    pub value: Option<Measure>,
+    /// Limit the value was compared against
+    pub limit: Option<Measure>,
+}
+
+/// Label of every line of a preview verdict
+pub const PREVIEW_NOTE: &str = "preview — final eligibility will use announcement-day stats";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Whether a verdict is final or only a preview
+pub enum EligibilityMode {
+    /// Evaluated with the announcement snapshot, used for registrations
+    Final,
+    /// Evaluated with current stats only, because there is no snapshot yet
+    Preview,
+}
+
+/// Picks the mode depending on whether the announcement snapshot was taken
+pub fn mode_for(snapshot_at: Option<DateTime<Utc>>) -> EligibilityMode {
+    match snapshot_at {
+        Some(_) => EligibilityMode::Final,
+        None => EligibilityMode::Preview,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// Outcome of an eligibility check for a single player
+pub struct EligibilityVerdict {
+    /// Checked player's username
+    pub username: String,
+    /// Whether this is a final verdict or a preview
+    pub mode: EligibilityMode,
+    /// When the stats used for the announcement checks were taken, `None` for previews
+    pub announced_at: Option<DateTime<Utc>>,
+    /// Every evaluated criterion, in the order they were checked
+    pub checks: Vec<Check>,
+}
+
+impl EligibilityVerdict {
+    /// Whether every criterion is fulfilled and the verdict is final
+    ///
+    /// A preview is never eligible, use [`EligibilityVerdict::all_passed()`] to see how it looks.
+    pub fn is_eligible(&self) -> bool {
+        self.mode == EligibilityMode::Final && self.all_passed()
+    }
+
+    /// Whether every evaluated criterion is fulfilled, regardless of the mode
+    pub fn all_passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// All criteria that aren't fulfilled
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+
+    /// Whether ranked games and RD were checked with current stats instead of announcement day ones
+    ///
+    /// True for previews and for players who were unranked on announcement day in a tournament
+    /// allowing unranked players.
+    pub fn uses_current_stats(&self) -> bool {
+        self.mode == EligibilityMode::Preview
+            || self.checks.iter().any(|c| {
+                c.criterion == Criterion::RankedOnAnnouncement && c.passed && c.value.is_some()
+            })
+    }
+}
+
+/// Evaluates the tournament restrictions for a player
+///
+/// `snapshot` is the player's data on announcement day (`None` if they were unranked back then),
+/// `highest_rank` the highest rank taken from the player's rankup news posts and the ranks the bot has
+/// seen them at (`None` if there are none).
+/// If the player was unranked on announcement day, the other announcement checks are skipped.
+/// Tournaments with [`TournamentRestrictions::allow_unranked`] let them pass that check instead,
+/// their current ranked games and RD are checked and the rank checks are skipped, except for the
+/// peak rank.
+///
+/// ```
+/// use chrono::{Duration, Utc};
+/// use serde_json::json;
+/// use uc_helper_rust::database::tournaments::TournamentRestrictions;
+/// use uc_helper_rust::eligibility::{evaluate, Criterion};
+/// use uc_helper_rust::tetrio::leaderboard::LeaderboardUser;
+/// use uc_helper_rust::tetrio::Rank;
+///
+/// let user = |rank: Rank, games: i64, rd: f64| -> LeaderboardUser {
+///     serde_json::from_value(json!({
+///         "_id": "5e47696db7c60f23a497ee6c", "username": "caboozled_pie", "role": "user",
+///         "country": null, "supporter": false, "verified": false,
+///         "league": {"gamesplayed": games, "gameswon": 0, "rating": 20000.0, "rank": rank.to_str(),
+///             "glicko": 1500.0, "rd": rd, "apm": 30.0, "pps": 1.5, "vs": 60.0}
+///     }))
+///     .unwrap()
+/// };
+/// let now = Utc::now();
+/// let announced = now - Duration::days(7);
+/// let ranked_only = TournamentRestrictions::new(Rank::SPlus, 100f64, 10);
+/// let unranked_allowed = TournamentRestrictions::new(Rank::SPlus, 100f64, 10).with_allow_unranked();
+/// let failed = |restrictions: &TournamentRestrictions, snapshot: Option<&LeaderboardUser>, current: &LeaderboardUser| {
+///     evaluate(restrictions, snapshot, announced, current, None, None, now)
+///         .failures()
+///         .map(|c| c.criterion)
+///         .collect::<Vec<_>>()
+/// };
+///
+/// // ranked players are checked the same way in both modes
+/// let fine = user(Rank::S, 50, 80.0);
+/// let ss = user(Rank::SS, 50, 80.0);
+/// for &restrictions in &[&ranked_only, &unranked_allowed] {
+///     assert!(failed(restrictions, Some(&fine), &fine).is_empty());
+///     assert_eq!(failed(restrictions, Some(&ss), &fine), vec![Criterion::AnnouncementRank]);
+///     assert_eq!(failed(restrictions, Some(&fine), &ss), vec![Criterion::CurrentRank]);
+/// }
+///
+/// // unranked on announcement day and still unranked
+/// let unranked = user(Rank::Unranked, 10, 90.0);
+/// assert_eq!(failed(&ranked_only, None, &unranked), vec![Criterion::RankedOnAnnouncement]);
+/// let verdict = evaluate(&unranked_allowed, None, announced, &unranked, None, None, now);
+/// assert!(verdict.is_eligible());
+/// assert!(verdict.uses_current_stats());
+/// let too_few = user(Rank::Unranked, 3, 90.0);
+/// assert_eq!(failed(&unranked_allowed, None, &too_few), vec![Criterion::RankedGames]);
+/// let unsettled = user(Rank::Unranked, 10, 150.0);
+/// assert_eq!(failed(&unranked_allowed, None, &unsettled), vec![Criterion::RatingDeviation]);
+///
+/// // unranked on announcement day, ranked by now, only their current games and RD count
+/// assert_eq!(failed(&ranked_only, None, &ss), vec![Criterion::RankedOnAnnouncement]);
+/// assert!(failed(&unranked_allowed, None, &ss).is_empty());
+/// assert!(failed(&unranked_allowed, None, &fine).is_empty());
+///
+/// // the peak rank still counts
+/// let peaked = evaluate(&unranked_allowed, None, announced, &fine, Some(Rank::U), None, now);
+/// assert_eq!(peaked.failures().map(|c| c.criterion).collect::<Vec<_>>(), vec![Criterion::HighestRank]);
+/// ```
+///
+/// The account age is only checked if the restrictions require it. Accounts without a creation date
+/// predate Tetrio recording join dates, so they always pass.
+pub fn evaluate(
+    restrictions: &TournamentRestrictions,
+    snapshot: Option<&LeaderboardUser>,
+    announced_at: DateTime<Utc>,
+    current: &LeaderboardUser,
+    highest_rank: Option<Rank>,
+    account_created_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> EligibilityVerdict {
+    let mut checks = announcement_checks(restrictions, snapshot, announced_at);
+    let unranked_allowed = snapshot.is_none() && restrictions.allow_unranked;
+    if unranked_allowed {
+        checks.extend(stat_checks(restrictions, current));
+    }
+    checks.extend(current_checks(
+        restrictions,
+        current,
+        !unranked_allowed,
+        highest_rank,
+        account_created_at,
+        now,
+    ));
+
+    EligibilityVerdict {
+        username: current.username.clone(),
+        mode: EligibilityMode::Final,
+        announced_at: Some(announced_at),
+        checks,
+    }
+}
+
+/// Evaluates the restrictions against current stats only, for tournaments without a snapshot
+///
+/// The announcement checks are left out, except for the ranked games and RD which use the current
+/// values instead. The verdict is a preview and never eligible, see [`EligibilityVerdict::is_eligible()`].
+pub fn evaluate_preview(
+    restrictions: &TournamentRestrictions,
+    current: &LeaderboardUser,
+    highest_rank: Option<Rank>,
+    account_created_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> EligibilityVerdict {
+    let mut checks = stat_checks(restrictions, current);
+    checks.extend(current_checks(
+        restrictions,
+        current,
+        true,
+        highest_rank,
+        account_created_at,
+        now,
+    ));
+
+    EligibilityVerdict {
+        username: current.username.clone(),
+        mode: EligibilityMode::Preview,
+        announced_at: None,
+        checks,
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// How replacing the announcement snapshot would change the verdicts of registered players
+pub struct SnapshotImpact {
+    /// Players who would become eligible
+    pub became_eligible: usize,
+    /// Players who would become ineligible
+    pub became_ineligible: usize,
+    /// Players whose verdict stays the same
+    pub unchanged: usize,
+}
+
+impl SnapshotImpact {
+    /// Amount of players whose verdict would change
+    pub fn changed(&self) -> usize {
+        self.became_eligible + self.became_ineligible
+    }
+}
+
+/// Compares the verdicts of registered players under the old snapshot and a fresh leaderboard
+///
+/// Only the announcement checks are compared, everything else uses current data and comes out the
+/// same either way. `registered` are the Tetrio IDs of the active registrations.
+pub fn snapshot_impact(
+    restrictions: &TournamentRestrictions,
+    old_snapshot: &[LeaderboardUser],
+    old_taken_at: DateTime<Utc>,
+    new_leaderboard: &[LeaderboardUser],
+    new_taken_at: DateTime<Utc>,
+    registered: &[&str],
+) -> SnapshotImpact {
+    let passes = |snapshot: &[LeaderboardUser], taken_at: DateTime<Utc>, id: &str| {
+        let entry = snapshot.iter().find(|u| u._id == id);
+        announcement_checks(restrictions, entry, taken_at)
+            .iter()
+            .all(|c| c.passed)
+    };
+
+    let mut impact = SnapshotImpact::default();
+    for id in registered {
+        match (
+            passes(old_snapshot, old_taken_at, id),
+            passes(new_leaderboard, new_taken_at, id),
+        ) {
+            (false, true) => impact.became_eligible += 1,
+            (true, false) => impact.became_ineligible += 1,
+            _ => impact.unchanged += 1,
+        }
+    }
+    impact
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Who replacing the announcement snapshot would affect, by username
+pub struct SnapshotDiff {
+    /// Registered players who pass the announcement checks now, but wouldn't with the new snapshot
+    pub became_ineligible: Vec<String>,
+    /// Ranked players who fail the announcement checks now, but would pass with the new snapshot
+    pub became_eligible: Vec<String>,
+    /// Players whose rank differs between the snapshots, with the old and the new rank
+    pub rank_changed: Vec<(String, Rank, Rank)>,
+}
+
+/// Lists the players whose announcement checks or rank would change with a new snapshot
+///
+/// Like [`snapshot_impact()`], but names the players and also looks at players who aren't
+/// registered. `registered` are the Tetrio IDs of the active registrations, only they can become
+/// ineligible. Players missing from a snapshot count as unranked in it. Lists are sorted by username.
+///
+/// ```
+/// use chrono::{Duration, Utc};
+/// use serde_json::json;
+/// use uc_helper_rust::database::tournaments::TournamentRestrictions;
+/// use uc_helper_rust::eligibility::snapshot_diff;
+/// use uc_helper_rust::tetrio::leaderboard::LeaderboardUser;
+/// use uc_helper_rust::tetrio::Rank;
+///
+/// let user = |id: &str, rank: Rank| -> LeaderboardUser {
+///     serde_json::from_value(json!({
+///         "_id": id, "username": id, "role": "user", "country": null, "supporter": false,
+///         "verified": false,
+///         "league": {"gamesplayed": 50, "gameswon": 25, "rating": 20000.0, "rank": rank.to_str(),
+///             "glicko": 1500.0, "rd": 80.0, "apm": 30.0, "pps": 1.5, "vs": 60.0}
+///     }))
+///     .unwrap()
+/// };
+/// let restrictions = TournamentRestrictions::new(Rank::SPlus, 100f64, 10);
+/// let now = Utc::now();
+/// let old = vec![user("climber", Rank::S), user("faller", Rank::SS), user("steady", Rank::A)];
+/// let new = vec![user("climber", Rank::SS), user("faller", Rank::S), user("steady", Rank::A)];
+///
+/// let diff = snapshot_diff(&restrictions, &old, now - Duration::days(7), &new, now, &["climber", "steady"]);
+/// assert_eq!(diff.became_ineligible, vec!["climber"]);
+/// assert_eq!(diff.became_eligible, vec!["faller"]);
+/// assert_eq!(diff.rank_changed.len(), 2);
+/// ```
+pub fn snapshot_diff(
+    restrictions: &TournamentRestrictions,
+    old_snapshot: &[LeaderboardUser],
+    old_taken_at: DateTime<Utc>,
+    new_leaderboard: &[LeaderboardUser],
+    new_taken_at: DateTime<Utc>,
+    registered: &[&str],
+) -> SnapshotDiff {
+    let passes = |entry: Option<&LeaderboardUser>, taken_at: DateTime<Utc>| {
+        announcement_checks(restrictions, entry, taken_at)
+            .iter()
+            .all(|c| c.passed)
+    };
+    let rank_of = |entry: Option<&LeaderboardUser>| {
+        entry.map_or(Rank::Unranked, |u| {
+            Rank::from_str(&u.league.rank).unwrap_or(Rank::Unranked)
+        })
+    };
+
+    // both snapshots contain the whole leaderboard, so look players up by ID
+    fn by_id(snapshot: &[LeaderboardUser]) -> HashMap<&str, &LeaderboardUser> {
+        snapshot.iter().map(|u| (u._id.as_str(), u)).collect()
+    }
+    let (old_by_id, new_by_id) = (by_id(old_snapshot), by_id(new_leaderboard));
+
+    let mut diff = SnapshotDiff::default();
+    let mut seen = HashSet::new();
+    for user in old_snapshot.iter().chain(new_leaderboard) {
+        if !seen.insert(user._id.as_str()) {
+            continue;
+        }
+        let old = old_by_id.get(user._id.as_str()).copied();
+        let new = new_by_id.get(user._id.as_str()).copied();
+        let username = new.unwrap_or(user).username.clone();
+
+        match (passes(old, old_taken_at), passes(new, new_taken_at)) {
+            (true, false) if registered.contains(&user._id.as_str()) => {
+                diff.became_ineligible.push(username.clone())
+            }
+            (false, true) => diff.became_eligible.push(username.clone()),
+            _ => {}
+        }
+
+        let (old_rank, new_rank) = (rank_of(old), rank_of(new));
+        if old_rank != new_rank {
+            diff.rank_changed.push((username, old_rank, new_rank));
+        }
+    }
+
+    diff.became_ineligible.sort();
+    diff.became_eligible.sort();
+    diff.rank_changed.sort_by(|a, b| a.0.cmp(&b.0));
+    diff
+}
+
+// Glicko-2 works on a scale where 1500 ± 173.7178 maps to 0 ± 1
+const GLICKO_SCALE: f64 = 173.7178;
+
+// Information a single game against an evenly matched, established opponent (RD 60) adds to 1/φ²
+const INFORMATION_PER_GAME: f64 = 0.2417;
+
+/// Roughly estimates how many ranked games bring the RD from `current_rd` down to `target_rd`
+///
+/// Assumes games against evenly matched opponents played in quick succession, so the RD doesn't
+/// grow back in between. The estimate is meant for telling players what to expect, not an exact number.
+///
+/// ```
+/// use uc_helper_rust::eligibility::estimated_games_to_rd;
+///
+/// assert_eq!(estimated_games_to_rd(80.0, 100.0), 0);
+/// assert_eq!(estimated_games_to_rd(100.0, 80.0), 8);
+/// assert!(estimated_games_to_rd(150.0, 80.0) > estimated_games_to_rd(120.0, 80.0));
+/// ```
+pub fn estimated_games_to_rd(current_rd: f64, target_rd: f64) -> u32 {
+    if current_rd <= target_rd || target_rd <= 0f64 {
+        return 0;
+    }
+    let precision = |rd: f64| (GLICKO_SCALE / rd).powi(2);
+    let missing = precision(target_rd) - precision(current_rd);
+    (missing / INFORMATION_PER_GAME).ceil() as u32
+}
+
+/// Checks that depend on announcement day, the first check is whether the player was ranked back then
+///
+/// Unranked players pass it if the tournament allows them, with [`Rank::Unranked`] as the value.
+fn announcement_checks(
+    restrictions: &TournamentRestrictions,
+    snapshot: Option<&LeaderboardUser>,
+    announced_at: DateTime<Utc>,
+) -> Vec<Check> {
+    let unranked_allowed = snapshot.is_none() && restrictions.allow_unranked;
+    let mut checks = vec![Check {
+        criterion: Criterion::RankedOnAnnouncement,
+        passed: snapshot.is_some() || unranked_allowed,
+        value: if unranked_allowed {
+            Some(Measure::Rank(Rank::Unranked))
+        } else {
+            None
+        },
+        limit: Some(Measure::Date(announced_at)),
+    }];
+
+    if let Some(snap) = snapshot {
+        let announce_rank = Rank::from_str(&snap.league.rank).unwrap();
+        checks.push(Check {
+            criterion: Criterion::AnnouncementRank,
+            passed: announce_rank <= restrictions.max_rank,
+            value: Some(Measure::Rank(announce_rank)),
+            limit: Some(Measure::Rank(restrictions.max_rank)),
+        });
+        checks.extend(stat_checks(restrictions, snap));
+    }
+
+    checks
+}
+
+/// Ranked games and RD of the given data, from announcement day or current
+fn stat_checks(restrictions: &TournamentRestrictions, data: &LeaderboardUser) -> Vec<Check> {
+    let games_played = data.league.gamesplayed;
+    let rd = data.league.rd.unwrap_or(999f64);
+    vec![
+        Check {
+            criterion: Criterion::RankedGames,
+            passed: games_played >= restrictions.min_ranked_games,
+            value: Some(Measure::Count(games_played)),
+            limit: Some(Measure::Count(restrictions.min_ranked_games)),
+        },
+        Check {
+            criterion: Criterion::RatingDeviation,
+            passed: rd <= restrictions.max_rd,
+            value: Some(Measure::Deviation(rd)),
+            limit: Some(Measure::Deviation(restrictions.max_rd)),
+        },
+    ]
+}
+
+/// Checks that don't depend on announcement day, shared by final verdicts and previews
+///
+/// The current rank is left out without `check_rank`, for players allowed in while unranked.
+fn current_checks(
+    restrictions: &TournamentRestrictions,
+    current: &LeaderboardUser,
+    check_rank: bool,
+    highest_rank: Option<Rank>,
+    account_created_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if check_rank {
+        let current_rank = Rank::from_str(&current.league.rank).unwrap();
+        checks.push(Check {
+            criterion: Criterion::CurrentRank,
+            passed: current_rank <= restrictions.max_rank,
+            value: Some(Measure::Rank(current_rank)),
+            limit: Some(Measure::Rank(restrictions.max_rank)),
+        });
+    }
+
+    // If there were no rankup posts, then it means that they never ranked up after the news post system was implemented.
+    // Therefore, the current rank must be the highest rank
+    let highest_limit = restrictions.highest_rank_limit();
+    checks.push(Check {
+        criterion: Criterion::HighestRank,
+        passed: highest_rank.map_or(true, |rank| rank <= highest_limit),
+        value: highest_rank.map(Measure::Rank),
+        limit: Some(Measure::Rank(highest_limit)),
+    });
+
+    if let Some(required_days) = restrictions.min_account_age_days {
+        checks.push(Check {
+            criterion: Criterion::AccountAge,
+            passed: account_created_at.map_or(true, |created_at| {
+                (now - created_at).num_days() >= required_days
+            }),
+            value: account_created_at.map(Measure::Date),
+            limit: Some(Measure::Count(required_days)),
+        });
+    }
+
+    checks
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Output style of [`verdict_to_lines()`]
+pub enum Style {
+    /// Discord markdown with emojis
+    Discord,
+    /// Plain text, for logs and terminals
+    Plain,
+}
+
+impl Style {
+    fn code(&self, text: &str) -> String {
+        match self {
+            Style::Discord => format!("`{}`", text),
+            Style::Plain => text.to_string(),
+        }
+    }
+
+    fn marker(&self, passed: bool) -> &'static str {
+        match (self, passed) {
+            (Style::Discord, true) => "✅",
+            (Style::Discord, false) => "❌",
+            (Style::Plain, true) => "[PASS]",
+            (Style::Plain, false) => "[FAIL]",
+        }
+    }
+}
+
+fn measure_to_string(measure: &Measure) -> String {
+    match measure {
+        Measure::Rank(rank) => rank.to_string(),
+        Measure::Count(count) => count.to_string(),
+        Measure::Deviation(rd) => format!("{:.1}", rd),
+        Measure::Date(date) => date.format("%Y-%m-%d %H:%M UTC").to_string(),
+    }
+}
+
+fn check_to_line(
+    check: &Check,
+    mode: EligibilityMode,
+    current_stats: bool,
+    style: Style,
+) -> String {
+    let value = check
+        .value
+        .as_ref()
+        .map_or("-".to_string(), |v| style.code(&measure_to_string(v)));
+    let limit = check
+        .limit
+        .as_ref()
+        .map_or("-".to_string(), |l| style.code(&measure_to_string(l)));
+
+    let text = match check.criterion {
+        Criterion::RankedOnAnnouncement if check.passed && check.value.is_some() => format!(
+            "Unranked on announcement day ({}), unranked players are allowed",
+            limit
+        ),
+        Criterion::RankedOnAnnouncement if check.passed => {
+            format!("Ranked on announcement day ({})", limit)
+        }
+        Criterion::RankedOnAnnouncement => format!("Unranked on announcement day ({})", limit),
+        Criterion::RankedGames if current_stats => {
+            format!("Ranked games so far: {} (≥ {} required)", value, limit)
+        }
+        Criterion::RatingDeviation if current_stats => {
+            format!("Current RD: {} (≤ {} required)", value, limit)
+        }
+        Criterion::AnnouncementRank => {
+            format!("Rank on announcement day: {} (≤ {} required)", value, limit)
+        }
+        Criterion::RankedGames => format!(
+            "Ranked games by announcement day: {} (≥ {} required)",
+            value, limit
+        ),
+        Criterion::RatingDeviation => {
+            format!("RD on announcement day: {} (≤ {} required)", value, limit)
+        }
+        Criterion::CurrentRank => format!("Current rank: {} (≤ {} required)", value, limit),
+        Criterion::HighestRank if check.value.is_none() => {
+            format!("Highest rank: no rank ups recorded (≤ {} required)", limit)
+        }
+        Criterion::HighestRank => format!("Highest rank: {} (≤ {} required)", value, limit),
+        Criterion::AccountAge if check.value.is_none() => {
+            "Account age: created before join dates were recorded".to_string()
+        }
+        Criterion::AccountAge => format!(
+            "Account created: {} (at least {} days old required)",
+            value, limit
+        ),
+    };
+
+    match mode {
+        EligibilityMode::Final => format!("{} {}", style.marker(check.passed), text),
+        EligibilityMode::Preview => {
+            format!("{} {} ({})", style.marker(check.passed), text, PREVIEW_NOTE)
+        }
+    }
+}
+
+/// Explains a verdict, one line per checked criterion after a summary line
+///
+/// Every line of a preview is labeled with [`PREVIEW_NOTE`].
+pub fn verdict_to_lines(verdict: &EligibilityVerdict, style: Style) -> Vec<String> {
+    let username = style.code(&verdict.username);
+    let summary = match (verdict.mode, verdict.all_passed()) {
+        (EligibilityMode::Final, true) => format!("{} is eligible", username),
+        (EligibilityMode::Final, false) => format!("{} is not eligible", username),
+        (EligibilityMode::Preview, true) => {
+            format!(
+                "{} would currently be eligible ({})",
+                username, PREVIEW_NOTE
+            )
+        }
+        (EligibilityMode::Preview, false) => format!(
+            "{} would currently not be eligible ({})",
+            username, PREVIEW_NOTE
+        ),
+    };
+
+    let current_stats = verdict.uses_current_stats();
+    let mut lines = vec![summary];
+    lines.extend(
+        verdict
+            .checks
+            .iter()
+            .map(|c| check_to_line(c, verdict.mode, current_stats, style)),
+    );
+    lines
+}
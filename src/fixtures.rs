@@ -0,0 +1,503 @@
+//! Synthetic but realistic data for local development
+//!
+//! Generates a leaderboard of made up players, links some of them to made up Discord accounts and
+//! sets up an active tournament with a stat snapshot, registrations and a partial check-in. Everything
+//! is derived from [`FixtureConfig::seed`], so the same config always generates the same dataset.
+//!
+//! Stats are plausible, not accurate:
+//! - Glicko is normally distributed, TR is computed from glicko and RD like Tetra League does
+//! - Ranks follow the leaderboard percentiles, see [`Rank::from_percentile()`]
+//! - PPS, APM and VS grow with glicko, with some noise
+//! - Registrations cluster on announcement day and the last day, mostly in the (UTC) evening
+//!
+//! Use the `seed_fixtures` binary to write a dataset into a database, never into the production one.
+
+#![warn(missing_docs)]
+
+use std::collections::HashSet;
+use std::f64::consts::PI;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::database::players::PlayerEntry;
+use crate::database::tournaments::{
+    CheckInRecord, RegistrationEntry, TournamentEntry, TournamentEntryBuilder,
+    TournamentRestrictions,
+};
+use crate::database::{DatabaseResult, LocalDatabase};
+use crate::rng::SplitMix64;
+use crate::tetrio::leaderboard::{LeaderboardUser, LeagueData};
+use crate::tetrio::Rank;
+use crate::timezone::OFFSETS;
+
+/// Shorthand of the generated tournament
+pub const TOURNAMENT_SHORTHAND: &str = "DEV";
+
+/// Syllables usernames are made of
+const SYLLABLES: &[&str] = &[
+    "ka", "zu", "mi", "to", "ri", "ne", "sha", "lo", "vex", "ion", "tet", "ris", "blo", "ck",
+    "spin", "t", "ace", "dro", "pix", "neo", "kai", "ryu", "sol", "zen", "qua", "dex", "mo", "fin",
+    "ly",
+];
+
+#[derive(Debug, Clone)]
+/// Parameters of a generated dataset
+pub struct FixtureConfig {
+    /// Amount of players
+    pub players: usize,
+    /// Share of players without a ranked standing (0 to 1)
+    pub unranked_share: f64,
+    /// Share of unregistered players linked to a Discord account, registered players are always linked (0 to 1)
+    pub linked_share: f64,
+    /// Amount of registrations, capped at the amount of eligible players
+    pub registrations: usize,
+    /// Days between the announcement and now, registrations are spread over them
+    pub registration_days: i64,
+    /// Share of registered players who checked in (0 to 1)
+    pub checked_in_share: f64,
+    /// Highest rank allowed to register
+    pub max_rank: Rank,
+    /// Seed everything is derived from
+    pub seed: u64,
+}
+
+impl Default for FixtureConfig {
+    fn default() -> Self {
+        FixtureConfig {
+            players: 2000,
+            unranked_share: 0.1,
+            linked_share: 0.3,
+            registrations: 150,
+            registration_days: 7,
+            checked_in_share: 0.6,
+            max_rank: Rank::SPlus,
+            seed: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A generated dataset
+pub struct Fixtures {
+    /// Player entries, ranked players first by descending TR
+    pub players: Vec<PlayerEntry>,
+    /// Active tournament with a snapshot, registrations and check-ins
+    pub tournament: TournamentEntry,
+}
+
+/// Standard normal distributed number (Box-Muller)
+fn normal(rng: &mut SplitMix64) -> f64 {
+    let u1 = rng.next_f64().max(f64::MIN_POSITIVE);
+    let u2 = rng.next_f64();
+    (-2f64 * u1.ln()).sqrt() * (2f64 * PI * u2).cos()
+}
+
+/// TR for a glicko rating and RD, the same way Tetra League computes it
+pub fn tr_from_glicko(glicko: f64, rd: f64) -> f64 {
+    let ln10 = 10f64.ln();
+    let spread = (3f64 * ln10.powi(2) * rd.powi(2)
+        + 2500f64 * (64f64 * PI.powi(2) + 147f64 * ln10.powi(2)))
+    .sqrt();
+    25000f64 / (1f64 + 10f64.powf((1500f64 - glicko) * PI / spread))
+}
+
+fn username(rng: &mut SplitMix64, taken: &mut HashSet<String>) -> String {
+    let syllables = 2 + rng.next_below(2);
+    let mut name: String = (0..syllables)
+        .map(|_| SYLLABLES[rng.next_below(SYLLABLES.len())])
+        .collect();
+    if rng.next_f64() < 0.4 {
+        name.push_str(&rng.next_below(1000).to_string());
+    }
+
+    let mut unique = name.clone();
+    let mut suffix = 1;
+    while !taken.insert(unique.clone()) {
+        suffix += 1;
+        unique = format!("{}{}", name, suffix);
+    }
+    unique
+}
+
+fn tetrio_id(rng: &mut SplitMix64) -> String {
+    format!("{:016x}{:08x}", rng.next_u64(), rng.next_u64() as u32)
+}
+
+fn discord_id(rng: &mut SplitMix64, taken: &mut HashSet<u64>) -> u64 {
+    loop {
+        let id = 100_000_000_000_000_000 + rng.next_u64() % 900_000_000_000_000_000;
+        if taken.insert(id) {
+            return id;
+        }
+    }
+}
+
+/// League data of a ranked player, the rank is filled in once everyone is sorted
+fn ranked_league(rng: &mut SplitMix64) -> LeagueData {
+    let glicko = (1400f64 + 330f64 * normal(rng)).max(200f64).min(3600f64);
+    let rd = 60f64 + 25f64 * rng.next_f64();
+    let skill = ((glicko - 500f64) / 2500f64).max(0f64).min(1f64);
+
+    let gamesplayed = 10 + (rng.next_f64().powi(2) * 1500f64) as i64;
+    let win_rate = (0.35 + 0.3 * skill + 0.05 * normal(rng))
+        .max(0.05)
+        .min(0.95);
+    let pps = (0.6 + 2.4 * skill + 0.12 * normal(rng)).max(0.3);
+    let apm = (pps * (22f64 + 18f64 * skill) + 4f64 * normal(rng)).max(3f64);
+    let vs = (apm * 2.2 + 6f64 * normal(rng)).max(apm);
+
+    LeagueData {
+        gamesplayed,
+        gameswon: (gamesplayed as f64 * win_rate) as i64,
+        rating: tr_from_glicko(glicko, rd),
+        rank: Rank::Unranked.to_str().to_string(),
+        glicko: Some(glicko),
+        rd: Some(rd),
+        apm: Some(apm),
+        pps: Some(pps),
+        vs: Some(vs),
+    }
+}
+
+/// League data of a player without a ranked standing, who played less than 10 games
+fn unranked_league(rng: &mut SplitMix64) -> LeagueData {
+    let gamesplayed = rng.next_below(10) as i64;
+    let played = gamesplayed > 0;
+    let pps = 0.5 + 1.5 * rng.next_f64();
+    LeagueData {
+        gamesplayed,
+        gameswon: rng.next_below(gamesplayed as usize + 1) as i64,
+        rating: -1f64,
+        rank: Rank::Unranked.to_str().to_string(),
+        glicko: None,
+        rd: None,
+        apm: if played { Some(pps * 25f64) } else { None },
+        pps: if played { Some(pps) } else { None },
+        vs: if played { Some(pps * 55f64) } else { None },
+    }
+}
+
+/// Registration date within the window, weighted towards the first and last day and the evening
+fn registration_date(
+    rng: &mut SplitMix64,
+    announced_at: DateTime<Utc>,
+    days: i64,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let days = days.max(1);
+    let weights: Vec<f64> = (0..days)
+        .map(|day| match day {
+            0 => 4f64,
+            d if d == days - 1 => 3f64,
+            _ => 1f64,
+        })
+        .collect();
+    let mut pick = rng.next_f64() * weights.iter().sum::<f64>();
+    let mut day = 0;
+    for (index, weight) in weights.iter().enumerate() {
+        if pick < *weight {
+            day = index as i64;
+            break;
+        }
+        pick -= weight;
+    }
+
+    let hour = (18f64 + 3f64 * normal(rng)).round() as i64;
+    let minutes = day * 24 * 60 + hour.rem_euclid(24) * 60 + rng.next_below(60) as i64;
+    let date = announced_at + Duration::minutes(minutes);
+    date.max(announced_at).min(now - Duration::minutes(1))
+}
+
+/// Generates a dataset
+///
+/// The announcement (and snapshot) is `config.registration_days` before `now`.
+pub fn generate(config: &FixtureConfig, now: DateTime<Utc>) -> Fixtures {
+    let mut rng = SplitMix64::new(config.seed);
+    let mut usernames = HashSet::new();
+    let mut discord_ids = HashSet::new();
+    let announced_at = now - Duration::days(config.registration_days.max(1));
+
+    let mut users: Vec<LeaderboardUser> = (0..config.players)
+        .map(|_| {
+            let league = if rng.next_f64() < config.unranked_share {
+                unranked_league(&mut rng)
+            } else {
+                ranked_league(&mut rng)
+            };
+            let country = if rng.next_f64() < 0.85 {
+                Some(OFFSETS[rng.next_below(OFFSETS.len())].0.to_string())
+            } else {
+                None
+            };
+            LeaderboardUser {
+                _id: tetrio_id(&mut rng),
+                username: username(&mut rng, &mut usernames),
+                role: "user".to_string(),
+                country,
+                supporter: Some(rng.next_f64() < 0.05),
+                verified: false,
+                league,
+            }
+        })
+        .collect();
+
+    // Ranks follow the leaderboard position of the ranked players, unranked ones go last
+    users.sort_by(|a, b| {
+        b.league
+            .rating
+            .partial_cmp(&a.league.rating)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let ranked_count = users.iter().filter(|u| u.league.rating >= 0f64).count();
+    for (position, user) in users.iter_mut().take(ranked_count).enumerate() {
+        let percentile = position as f64 / ranked_count as f64;
+        user.league.rank = Rank::from_percentile(percentile).to_str().to_string();
+    }
+
+    let restrictions = TournamentRestrictions::new(config.max_rank, 100f64, 10);
+    let mut eligible: Vec<usize> = users
+        .iter()
+        .enumerate()
+        .filter(|(_, u)| {
+            u.league.rating >= 0f64
+                && u.league.rank.parse::<Rank>().unwrap() <= restrictions.max_rank
+                && u.league.rd.map_or(false, |rd| rd <= restrictions.max_rd)
+                && u.league.gamesplayed >= restrictions.min_ranked_games
+        })
+        .map(|(index, _)| index)
+        .collect();
+    rng.shuffle(&mut eligible);
+    eligible.truncate(config.registrations);
+
+    let mut registrations: Vec<RegistrationEntry> = Vec::new();
+    let mut players: Vec<PlayerEntry> = Vec::new();
+    let mut registered_discord_ids = Vec::new();
+    for (index, user) in users.iter().enumerate() {
+        let registered = eligible.contains(&index);
+        let mut entry = if registered {
+            let date = registration_date(&mut rng, announced_at, config.registration_days, now);
+            registrations
+                .push(RegistrationEntry::with_date(&user._id, date).registered_as(&user.username));
+            let id = discord_id(&mut rng, &mut discord_ids);
+            registered_discord_ids.push(id);
+            PlayerEntry::new(&user._id, Some(id))
+                .with_link_timestamp(date - Duration::minutes(rng.next_below(30) as i64))
+        } else if rng.next_f64() < config.linked_share {
+            let linked_at = now - Duration::minutes(rng.next_below(90 * 24 * 60) as i64);
+            PlayerEntry::new(&user._id, Some(discord_id(&mut rng, &mut discord_ids)))
+                .with_link_timestamp(linked_at)
+        } else {
+            PlayerEntry::new(&user._id, None)
+        };
+        entry.tetrio_data = Some(user.clone());
+        players.push(entry);
+    }
+    registrations.sort_by_key(|r| *r.date);
+
+    // There is no check-in message to react to, so the check-ins count as carried over
+    // (see `TournamentEntry::live_check_ins()`)
+    rng.shuffle(&mut registered_discord_ids);
+    let checked_in = (registered_discord_ids.len() as f64 * config.checked_in_share) as usize;
+    let check_ins = registered_discord_ids
+        .iter()
+        .take(checked_in)
+        .map(|id| CheckInRecord {
+            discord_id: *id,
+            tetrio_id: None,
+            at: None,
+            generation: 0,
+            carried_over: true,
+            forced_by: None,
+        })
+        .collect();
+
+    let snapshot: Vec<LeaderboardUser> = users.into_iter().take(ranked_count).collect();
+    let tournament = TournamentEntryBuilder::new(
+        &format!("Development Cup (seed {})", config.seed),
+        TOURNAMENT_SHORTHAND,
+        restrictions,
+    )
+    .created_at(announced_at - Duration::days(1))
+    .active(true)
+    .snapshot(snapshot, announced_at)
+    .registrations(registrations)
+    .check_ins(check_ins)
+    .build()
+    .expect("generated restrictions are valid");
+
+    Fixtures {
+        players,
+        tournament,
+    }
+}
+
+impl Fixtures {
+    /// Every ranked player as they would appear on the leaderboard
+    pub fn leaderboard(&self) -> Vec<&LeaderboardUser> {
+        self.players
+            .iter()
+            .filter_map(|p| p.tetrio_data.as_ref())
+            .filter(|u| u.league.rating >= 0f64)
+            .collect()
+    }
+
+    /// Checks that the dataset is consistent, returns a description of everything that isn't
+    pub fn invariant_violations(&self, config: &FixtureConfig) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if self.players.len() != config.players {
+            violations.push(format!(
+                "expected {} players, got {}",
+                config.players,
+                self.players.len()
+            ));
+        }
+
+        let registrations = self.tournament.registered_players();
+        if registrations.len() > config.registrations {
+            violations.push(format!(
+                "expected at most {} registrations, got {}",
+                config.registrations,
+                registrations.len()
+            ));
+        }
+        for registration in &registrations {
+            match self
+                .players
+                .iter()
+                .find(|p| p.tetrio_id == registration.tetrio_id)
+            {
+                Some(player) if player.discord_id.is_none() => violations.push(format!(
+                    "registered player {} is not linked",
+                    registration.tetrio_id
+                )),
+                Some(_) => {}
+                None => violations.push(format!(
+                    "registration {} has no player",
+                    registration.tetrio_id
+                )),
+            }
+        }
+
+        let leaderboard = self.leaderboard();
+        let snapshot = self.tournament.snapshot();
+        if snapshot.len() != leaderboard.len() {
+            violations.push(format!(
+                "snapshot has {} players, leaderboard has {}",
+                snapshot.len(),
+                leaderboard.len()
+            ));
+        }
+        for user in leaderboard {
+            if snapshot.iter().all(|s| s._id != user._id) {
+                violations.push(format!("ranked player {} is not in the snapshot", user._id));
+            }
+        }
+
+        let registered_discord_ids: Vec<u64> = registrations
+            .iter()
+            .filter_map(|r| self.players.iter().find(|p| p.tetrio_id == r.tetrio_id))
+            .filter_map(|p| p.discord_id)
+            .collect();
+        for record in &self.tournament.check_ins {
+            if !registered_discord_ids.contains(&record.discord_id) {
+                violations.push(format!(
+                    "check-in of {} is not a registered player",
+                    record.discord_id
+                ));
+            }
+        }
+
+        violations
+    }
+
+    /// Replaces all players and tournaments in the database with the dataset
+    pub async fn write(&self, db: &LocalDatabase) -> DatabaseResult<()> {
+        db.players.remove_all().await?;
+        db.tournaments.remove_all().await?;
+        db.players.insert_players(&self.players).await?;
+        db.tournaments.insert_tournament(&self.tournament).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config() -> FixtureConfig {
+        FixtureConfig {
+            players: 300,
+            registrations: 40,
+            ..FixtureConfig::default()
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    #[test]
+    fn small_dataset_is_consistent() {
+        let config = config();
+        let fixtures = generate(&config, now());
+        assert_eq!(fixtures.invariant_violations(&config), Vec::<String>::new());
+
+        assert_eq!(fixtures.players.len(), config.players);
+        assert_eq!(
+            fixtures.tournament.registered_players().len(),
+            config.registrations
+        );
+        assert_eq!(fixtures.tournament.shorthand, TOURNAMENT_SHORTHAND);
+        assert!(fixtures.tournament.is_active());
+
+        // registrations are within the window and everyone registered is eligible
+        let announced_at = now() - Duration::days(config.registration_days);
+        for registration in fixtures.tournament.registered_players() {
+            assert!(*registration.date >= announced_at && *registration.date < now());
+            let snapshot = fixtures.tournament.snapshot_of(&registration.tetrio_id);
+            let rank: Rank = snapshot.unwrap().league.rank.parse().unwrap();
+            assert!(rank <= config.max_rank);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_dataset() {
+        let ids = |fixtures: &Fixtures| -> Vec<(String, Option<u64>)> {
+            fixtures
+                .players
+                .iter()
+                .map(|p| (p.tetrio_id.clone(), p.discord_id))
+                .collect()
+        };
+        let registrations = |fixtures: &Fixtures| -> Vec<String> {
+            fixtures
+                .tournament
+                .registered_players()
+                .iter()
+                .map(|r| r.tetrio_id.clone())
+                .collect()
+        };
+
+        let (a, b) = (generate(&config(), now()), generate(&config(), now()));
+        assert_eq!(ids(&a), ids(&b));
+        assert_eq!(registrations(&a), registrations(&b));
+
+        let other = generate(
+            &FixtureConfig {
+                seed: 2,
+                ..config()
+            },
+            now(),
+        );
+        assert_ne!(ids(&a), ids(&other));
+    }
+
+    #[test]
+    fn tr_grows_with_glicko_and_shrinks_with_rd() {
+        assert!(tr_from_glicko(1600f64, 80f64) > tr_from_glicko(1500f64, 80f64));
+        assert!(tr_from_glicko(1500f64, 60f64) > tr_from_glicko(1500f64, 100f64));
+        let tr = tr_from_glicko(1500f64, 60f64);
+        assert!(tr > 0f64 && tr < 25000f64, "{}", tr);
+    }
+}
@@ -1,7 +1,58 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod bracket;
+pub mod check_in;
 mod commands;
 pub mod database;
+pub mod dataset;
 pub mod discord;
+pub mod distribution;
+pub mod dupes;
+pub mod eligibility;
+pub mod fixtures;
+pub mod member_watch;
+pub mod metrics;
+pub mod receipt;
+pub mod review;
+pub mod rng;
+pub mod status;
+pub mod tasks;
 pub mod tetrio;
+pub mod timezone;
+
+/// Commonly used types and functions, re-exported for use as a library
+///
+/// The Tetrio endpoints all name their request function `request`, so they're re-exported under the
+/// name of their endpoint.
+///
+/// ```no_run
+/// use uc_helper_rust::prelude::*;
+///
+/// # async fn print_registrations() -> Result<(), DatabaseError> {
+/// let db: LocalDatabase = connect().await?;
+/// match db.tournaments.get_active().await? {
+///     Some(tournament) => println!(
+///         "{}: {} registered",
+///         tournament.shorthand,
+///         tournament.registration_count()
+///     ),
+///     None => println!("No active tournament"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub mod prelude {
+    pub use crate::database::players::{PlayerCollection, PlayerEntry};
+    pub use crate::database::tournaments::{
+        RegistrationError, TournamentCollection, TournamentEntry, TournamentRestrictions,
+    };
+    pub use crate::database::{connect, DatabaseError, LocalDatabase};
+    pub use crate::tetrio::leaderboard::request as request_leaderboard;
+    pub use crate::tetrio::leaderboard::request_cached as request_leaderboard_cached;
+    pub use crate::tetrio::news::request as request_news;
+    pub use crate::tetrio::search::request as request_search;
+    pub use crate::tetrio::stream::request as request_stream;
+    pub use crate::tetrio::user::request as request_user;
+    pub use crate::tetrio::Rank;
+}
@@ -0,0 +1,151 @@
+//! What to do when a registered player leaves or rejoins the Discord server
+//!
+//! Tournament rules require participants to stay in the server. The event handlers look up the
+//! player and their registration in the active tournament, [`decide()`] picks the [`Action`].
+//! Withdrawing automatically is opt-in with the `auto_withdraw_on_leave` flag, see
+//! [`crate::database::flags::Feature::AutoWithdrawOnLeave`].
+
+#![warn(missing_docs)]
+
+use crate::database::players::PlayerEntry;
+use crate::database::tournaments::RegistrationStatus;
+
+/// Withdrawal reason for players who were withdrawn because they left the server
+pub const LEFT_SERVER_REASON: &str = "left server";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A change in server membership
+pub enum MemberEvent {
+    /// The member left or was removed from the server
+    Left,
+    /// The member joined the server
+    Joined,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What to do about a membership change
+pub enum Action {
+    /// Nothing, the member isn't registered or nothing changed for the tournament
+    Ignore,
+    /// Tell staff a registered player left
+    Alert,
+    /// Withdraw the registration and tell staff
+    Withdraw,
+    /// Tell staff a player withdrawn for leaving is back, with the command to reinstate them
+    OfferReinstatement,
+}
+
+/// Picks the action for a membership change
+///
+/// `player` is the player linked to the member, `status` the status of their registration in the
+/// active tournament, `None` if they aren't registered.
+pub fn decide(
+    event: MemberEvent,
+    player: Option<&PlayerEntry>,
+    status: Option<&RegistrationStatus>,
+    auto_withdraw: bool,
+) -> Action {
+    if player.is_none() {
+        return Action::Ignore;
+    }
+
+    match (event, status) {
+        (MemberEvent::Left, Some(RegistrationStatus::Active)) if auto_withdraw => Action::Withdraw,
+        (MemberEvent::Left, Some(RegistrationStatus::Active)) => Action::Alert,
+        (MemberEvent::Joined, Some(RegistrationStatus::Withdrawn { reason, .. }))
+            if reason.as_deref() == Some(LEFT_SERVER_REASON) =>
+        {
+            Action::OfferReinstatement
+        }
+        _ => Action::Ignore,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn player() -> PlayerEntry {
+        PlayerEntry::new("5e47696db7c60f23a497ee6c", Some(1))
+    }
+
+    fn withdrawn(reason: Option<&str>) -> RegistrationStatus {
+        RegistrationStatus::Withdrawn {
+            at: Utc.ymd(2021, 3, 14).and_hms(18, 0, 0).into(),
+            reason: reason.map(String::from),
+        }
+    }
+
+    #[test]
+    fn leaving_alerts_or_withdraws() {
+        let player = player();
+        let active = RegistrationStatus::Active;
+        assert_eq!(
+            decide(MemberEvent::Left, Some(&player), Some(&active), false),
+            Action::Alert
+        );
+        assert_eq!(
+            decide(MemberEvent::Left, Some(&player), Some(&active), true),
+            Action::Withdraw
+        );
+    }
+
+    #[test]
+    fn rejoining_after_leaving_offers_reinstatement() {
+        let player = player();
+        let left = withdrawn(Some(LEFT_SERVER_REASON));
+        assert_eq!(
+            decide(MemberEvent::Joined, Some(&player), Some(&left), false),
+            Action::OfferReinstatement
+        );
+        assert_eq!(
+            decide(MemberEvent::Joined, Some(&player), Some(&left), true),
+            Action::OfferReinstatement
+        );
+
+        // withdrawn for anything else, they aren't offered to come back
+        for other in &[withdrawn(None), withdrawn(Some("busy"))] {
+            assert_eq!(
+                decide(MemberEvent::Joined, Some(&player), Some(other), true),
+                Action::Ignore
+            );
+        }
+    }
+
+    #[test]
+    fn everything_else_is_ignored() {
+        let player = player();
+        let active = RegistrationStatus::Active;
+        let left = withdrawn(Some(LEFT_SERVER_REASON));
+        let disqualified = RegistrationStatus::Disqualified {
+            at: Utc.ymd(2021, 3, 14).and_hms(18, 0, 0).into(),
+            reason: "smurfing".to_string(),
+            by: 2,
+        };
+
+        let cases = vec![
+            // not linked, or not registered
+            (MemberEvent::Left, None, Some(&active)),
+            (MemberEvent::Joined, None, Some(&left)),
+            (MemberEvent::Left, Some(&player), None),
+            (MemberEvent::Joined, Some(&player), None),
+            // joining while registered, or leaving while not taking part anymore
+            (MemberEvent::Joined, Some(&player), Some(&active)),
+            (MemberEvent::Left, Some(&player), Some(&left)),
+            (MemberEvent::Left, Some(&player), Some(&disqualified)),
+            (MemberEvent::Joined, Some(&player), Some(&disqualified)),
+        ];
+        for (event, player, status) in cases {
+            for auto_withdraw in [false, true].iter().copied() {
+                assert_eq!(
+                    decide(event, player, status, auto_withdraw),
+                    Action::Ignore,
+                    "{:?} with {:?}",
+                    event,
+                    status
+                );
+            }
+        }
+    }
+}
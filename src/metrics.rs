@@ -0,0 +1,176 @@
+//! In-memory counters of how the bot is used, shown by `.bot_stats`
+//!
+//! There is a single [`Metrics`] instance per process, see [`global()`]. The Discord client keeps it
+//! in its data map, while the database and Tetrio modules, which don't have access to it, count
+//! through [`global()`] directly. Nothing is persisted, long term command usage is tracked by
+//! [`crate::database::usage`] instead.
+
+#![warn(missing_docs)]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+use serenity::prelude::TypeMapKey;
+
+lazy_static! {
+    /// Counters shared by the whole process
+    static ref METRICS: Arc<Metrics> = Arc::new(Metrics::new(Utc::now()));
+}
+
+/// The counters of this process
+///
+/// The first call sets the start of the uptime, so the bot calls it right on startup.
+pub fn global() -> Arc<Metrics> {
+    Arc::clone(&METRICS)
+}
+
+#[derive(Debug)]
+/// Counters since the process started or they were last reset
+pub struct Metrics {
+    started_at: DateTime<Utc>,
+    counting_since: Mutex<DateTime<Utc>>,
+    commands: Mutex<HashMap<String, u64>>,
+    registration_failures: Mutex<HashMap<&'static str, u64>>,
+    registrations: AtomicU64,
+    tetrio_requests: AtomicU64,
+    tetrio_failures: AtomicU64,
+    database_errors: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Copy of the counters at a point in time
+pub struct MetricsSnapshot {
+    /// When the process started
+    pub started_at: DateTime<Utc>,
+    /// When counting started, the last reset or the process start
+    pub counting_since: DateTime<Utc>,
+    /// Processed commands per command name, most used first
+    pub commands: Vec<(String, u64)>,
+    /// Successful registrations
+    pub registrations: u64,
+    /// Failed registrations per [`crate::database::tournaments::RegistrationError`] variant, most common first
+    pub registration_failures: Vec<(String, u64)>,
+    /// Requests sent to the Tetrio API
+    pub tetrio_requests: u64,
+    /// Requests to the Tetrio API that failed or returned an error
+    pub tetrio_failures: u64,
+    /// Errors returned by MongoDB
+    pub database_errors: u64,
+}
+
+// Counters are plain numbers, so a poisoned lock still holds usable data
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Sorted by count, highest first, ties by name
+fn sorted<K: ToString>(counts: &HashMap<K, u64>) -> Vec<(String, u64)> {
+    let mut sorted: Vec<(String, u64)> = counts
+        .iter()
+        .map(|(key, count)| (key.to_string(), *count))
+        .collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sorted
+}
+
+impl Metrics {
+    /// Creates empty counters for a process started at `started_at`
+    ///
+    /// ```
+    /// use chrono::Utc;
+    /// use uc_helper_rust::metrics::Metrics;
+    ///
+    /// let metrics = Metrics::new(Utc::now());
+    /// metrics.record_command("stats");
+    /// metrics.record_command("register");
+    /// metrics.record_command("stats");
+    /// metrics.record_registration(Ok(()));
+    /// metrics.record_registration(Err("already_registered"));
+    /// metrics.record_tetrio_request(false);
+    /// metrics.record_tetrio_request(true);
+    ///
+    /// let snapshot = metrics.snapshot();
+    /// assert_eq!(snapshot.commands, vec![("stats".to_string(), 2), ("register".to_string(), 1)]);
+    /// assert_eq!(snapshot.registrations, 1);
+    /// assert_eq!(snapshot.registration_failures, vec![("already_registered".to_string(), 1)]);
+    /// assert_eq!((snapshot.tetrio_requests, snapshot.tetrio_failures), (2, 1));
+    ///
+    /// metrics.reset(Utc::now());
+    /// let snapshot = metrics.snapshot();
+    /// assert!(snapshot.commands.is_empty());
+    /// assert_eq!(snapshot.tetrio_requests, 0);
+    /// ```
+    pub fn new(started_at: DateTime<Utc>) -> Metrics {
+        Metrics {
+            started_at,
+            counting_since: Mutex::new(started_at),
+            commands: Mutex::new(HashMap::new()),
+            registration_failures: Mutex::new(HashMap::new()),
+            registrations: AtomicU64::new(0),
+            tetrio_requests: AtomicU64::new(0),
+            tetrio_failures: AtomicU64::new(0),
+            database_errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a processed command
+    pub fn record_command(&self, command: &str) {
+        *lock(&self.commands).entry(command.to_string()).or_insert(0) += 1;
+    }
+
+    /// Counts a registration attempt, failures by the kind of error
+    pub fn record_registration(&self, result: Result<(), &'static str>) {
+        match result {
+            Ok(()) => {
+                self.registrations.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(kind) => *lock(&self.registration_failures).entry(kind).or_insert(0) += 1,
+        }
+    }
+
+    /// Counts a request to the Tetrio API
+    pub fn record_tetrio_request(&self, failed: bool) {
+        self.tetrio_requests.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.tetrio_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts an error returned by MongoDB
+    pub fn record_database_error(&self) {
+        self.database_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Sets every counter back to zero, the uptime keeps going
+    pub fn reset(&self, now: DateTime<Utc>) {
+        *lock(&self.counting_since) = now;
+        lock(&self.commands).clear();
+        lock(&self.registration_failures).clear();
+        self.registrations.store(0, Ordering::Relaxed);
+        self.tetrio_requests.store(0, Ordering::Relaxed);
+        self.tetrio_failures.store(0, Ordering::Relaxed);
+        self.database_errors.store(0, Ordering::Relaxed);
+    }
+
+    /// Copies the current counters
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            started_at: self.started_at,
+            counting_since: *lock(&self.counting_since),
+            commands: sorted(&*lock(&self.commands)),
+            registrations: self.registrations.load(Ordering::Relaxed),
+            registration_failures: sorted(&*lock(&self.registration_failures)),
+            tetrio_requests: self.tetrio_requests.load(Ordering::Relaxed),
+            tetrio_failures: self.tetrio_failures.load(Ordering::Relaxed),
+            database_errors: self.database_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl TypeMapKey for Metrics {
+    type Value = Arc<Metrics>;
+}
@@ -0,0 +1,219 @@
+//! Verifiable registration receipts
+//!
+//! A receipt is an HMAC-SHA256 over the tournament shorthand, the Tetrio ID and the registration
+//! timestamp, keyed with the `RECEIPT_SECRET` environment variable. Only the bot can create valid
+//! receipts, so staff can tell whether a presented code belongs to a real registration.
+//!
+//! Codes are truncated to 40 bits and written as 8 Crockford base32 characters (`XXXX-XXXX`).
+//! That's plenty to stop guessing through Discord commands (about one in a trillion per attempt),
+//! and codes are always checked against a specific registration, so two registrations sharing a
+//! code by chance doesn't matter. It's not meant to hold up against offline brute force of the secret.
+//!
+//! If the secret is not set, registrations simply don't get receipts.
+
+#![warn(missing_docs)]
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Environment variable holding the receipt secret
+pub const SECRET_ENV: &str = "RECEIPT_SECRET";
+
+/// Amount of bytes of the HMAC that are kept
+const CODE_BYTES: usize = 5;
+
+/// Crockford base32 alphabet, which leaves out easily confused letters
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+lazy_static! {
+    static ref KEY: Option<ReceiptKey> = std::env::var(SECRET_ENV)
+        .ok()
+        .and_then(|secret| ReceiptKey::new(secret.as_bytes()));
+}
+
+/// Receipt key taken from the environment, `None` if receipts are disabled
+pub fn key() -> Option<&'static ReceiptKey> {
+    KEY.as_ref()
+}
+
+#[derive(Clone)]
+/// Secret used to sign receipts
+pub struct ReceiptKey(Vec<u8>);
+
+impl ReceiptKey {
+    /// Creates a key, an empty secret counts as no secret
+    pub fn new(secret: &[u8]) -> Option<ReceiptKey> {
+        if secret.is_empty() {
+            None
+        } else {
+            Some(ReceiptKey(secret.to_vec()))
+        }
+    }
+
+    /// Creates the receipt code of a registration
+    pub fn generate(
+        &self,
+        shorthand: &str,
+        tetrio_id: &str,
+        registered_at: DateTime<Utc>,
+    ) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(&self.0).expect("HMAC accepts any key length");
+        // Millisecond precision, since that's what gets stored in the database
+        let message = format!(
+            "{}:{}:{}",
+            shorthand,
+            tetrio_id,
+            registered_at.timestamp_millis()
+        );
+        mac.input(message.as_bytes());
+        let digest = mac.result().code();
+
+        let code = encode(&digest[..CODE_BYTES]);
+        format!("{}-{}", &code[..4], &code[4..])
+    }
+
+    /// Whether a presented code belongs to the given registration
+    pub fn verify(
+        &self,
+        code: &str,
+        shorthand: &str,
+        tetrio_id: &str,
+        registered_at: DateTime<Utc>,
+    ) -> bool {
+        let expected = normalize(&self.generate(shorthand, tetrio_id, registered_at));
+        let presented = normalize(code);
+        expected.len() == presented.len()
+            && expected
+                .bytes()
+                .zip(presented.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+impl std::fmt::Debug for ReceiptKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReceiptKey(..)")
+    }
+}
+
+/// Encodes bytes as Crockford base32, without padding
+fn encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    output
+}
+
+/// Normalizes a code as typed by a user
+///
+/// Ignores case, dashes and spaces, and maps the letters Crockford base32 leaves out to their look-alikes.
+pub fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn key() -> ReceiptKey {
+        ReceiptKey::new(b"not the real secret").unwrap()
+    }
+
+    fn registered_at() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms_milli(18, 0, 0, 123)
+    }
+
+    fn code() -> String {
+        key().generate("UC12", "5e47696db7c60f23a497ee6c", registered_at())
+    }
+
+    #[test]
+    fn empty_secret_disables_receipts() {
+        assert!(ReceiptKey::new(b"").is_none());
+    }
+
+    #[test]
+    fn codes_are_two_groups_of_base32() {
+        let code = code();
+        assert_eq!(code.len(), 9);
+        assert_eq!(&code[4..5], "-");
+        assert!(code
+            .bytes()
+            .filter(|c| *c != b'-')
+            .all(|c| ALPHABET.contains(&c)));
+        assert_eq!(encode(&[0; 5]), "00000000");
+        assert_eq!(encode(&[0xFF; 5]), "ZZZZZZZZ");
+    }
+
+    #[test]
+    fn generated_codes_verify() {
+        let code = code();
+        assert_eq!(code, self::code());
+        let verify =
+            |code: &str| key().verify(code, "UC12", "5e47696db7c60f23a497ee6c", registered_at());
+        assert!(verify(&code));
+        assert!(verify(&code.to_lowercase()));
+        assert!(verify(&code.replace('-', " ")));
+        assert!(verify(&code.replace('0', "o").replace('1', "l")));
+
+        // only milliseconds are stored, so anything finer doesn't matter
+        let finer = registered_at() + Duration::nanoseconds(999);
+        assert!(key().verify(&code, "UC12", "5e47696db7c60f23a497ee6c", finer));
+    }
+
+    #[test]
+    fn tampered_fields_are_rejected() {
+        let code = code();
+        let tetrio_id = "5e47696db7c60f23a497ee6c";
+        assert!(!key().verify(&code, "UC13", tetrio_id, registered_at()));
+        assert!(!key().verify(&code, "UC12", "5e4a3b1d9f8e7c6b5a4d3c2b", registered_at()));
+        let later = registered_at() + Duration::milliseconds(1);
+        assert!(!key().verify(&code, "UC12", tetrio_id, later));
+
+        let other_key = ReceiptKey::new(b"another secret").unwrap();
+        assert!(!other_key.verify(&code, "UC12", tetrio_id, registered_at()));
+    }
+
+    #[test]
+    fn tampered_codes_are_rejected() {
+        let code = code();
+        let verify =
+            |code: &str| key().verify(code, "UC12", "5e47696db7c60f23a497ee6c", registered_at());
+        for position in (0..code.len()).filter(|i| *i != 4) {
+            let mut tampered = code.clone().into_bytes();
+            tampered[position] = if tampered[position] == b'Z' {
+                b'Y'
+            } else {
+                b'Z'
+            };
+            let tampered = String::from_utf8(tampered).unwrap();
+            assert!(!verify(&tampered), "{} accepted", tampered);
+        }
+        assert!(!verify(""));
+        assert!(!verify(&code[..8]));
+        assert!(!verify(&format!("{}0", code)));
+    }
+}
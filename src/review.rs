@@ -0,0 +1,310 @@
+//! State of an interactive registration review session
+//!
+//! Staff page through the registrations of the active tournament one entry at a time and act on
+//! the entry they are looking at. [`ReviewSession`] only keeps track of which entry is shown and
+//! turns an [`Action`] into an [`Effect`], the Discord side renders pages, collects reasons and
+//! writes to the database. It doesn't know anything about Discord, so it can be used from anywhere.
+
+#![warn(missing_docs)]
+
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Something staff can do on a page, every action has its own reaction
+pub enum Action {
+    /// Show the previous entry
+    Previous,
+    /// Show the next entry
+    Next,
+    /// Disqualify the shown player, asks for a reason first
+    Disqualify,
+    /// Add a note to the shown registration
+    Note,
+    /// Mark the shown registration as reviewed
+    MarkReviewed,
+}
+
+impl Action {
+    /// Every action, in the order the reactions are added
+    pub const ALL: [Action; 5] = [
+        Action::Previous,
+        Action::Next,
+        Action::Disqualify,
+        Action::Note,
+        Action::MarkReviewed,
+    ];
+
+    /// Reaction emoji of the action
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Action::Previous => "⏮️",
+            Action::Next => "⏭️",
+            Action::Disqualify => "🚫",
+            Action::Note => "📝",
+            Action::MarkReviewed => "✅",
+        }
+    }
+
+    /// Action of a reaction emoji, `None` for anything else
+    pub fn from_emoji(emoji: &str) -> Option<Action> {
+        Action::ALL.iter().find(|a| a.emoji() == emoji).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which registrations are shown
+pub enum Filter {
+    /// Every registration
+    All,
+    /// Only registrations nobody reviewed yet, reviewed ones disappear from the session
+    Unreviewed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// What the Discord side has to do after an action
+pub enum Effect {
+    /// Show the entry with this Tetrio ID
+    Show(String),
+    /// Ask for a reason, then disqualify the player with this Tetrio ID
+    AskDisqualifyReason(String),
+    /// Ask for the text, then add a note to the registration with this Tetrio ID
+    AskNote(String),
+    /// Mark the registration with this Tetrio ID as reviewed, then call [`ReviewSession::reviewed()`]
+    MarkReviewed(String),
+    /// There is nothing to review
+    Empty,
+}
+
+#[derive(Debug, Clone)]
+/// Page state of a review session
+pub struct ReviewSession {
+    /// Tetrio IDs of every registration, in the chosen order
+    entries: Vec<String>,
+    /// Tetrio IDs of reviewed registrations
+    reviewed: HashSet<String>,
+    filter: Filter,
+    /// Position in the visible entries
+    index: usize,
+}
+
+impl ReviewSession {
+    /// Starts a session at the first visible entry
+    ///
+    /// `entries` are the Tetrio IDs of the registrations in the order they should be shown,
+    /// `reviewed` the ones that were already reviewed.
+    pub fn new(entries: Vec<String>, reviewed: HashSet<String>, filter: Filter) -> ReviewSession {
+        ReviewSession {
+            entries,
+            reviewed,
+            filter,
+            index: 0,
+        }
+    }
+
+    /// Tetrio IDs of the entries the filter lets through
+    fn visible(&self) -> Vec<&String> {
+        self.entries
+            .iter()
+            .filter(|id| self.filter == Filter::All || !self.reviewed.contains(*id))
+            .collect()
+    }
+
+    /// Tetrio ID of the shown entry, `None` if there is nothing to show
+    pub fn current(&self) -> Option<&str> {
+        self.visible().get(self.index).map(|id| id.as_str())
+    }
+
+    /// Position of the shown entry (starting at 1) and the amount of visible entries
+    pub fn position(&self) -> (usize, usize) {
+        let total = self.visible().len();
+        ((self.index + 1).min(total), total)
+    }
+
+    /// Whether a registration was reviewed, in this session or before
+    pub fn is_reviewed(&self, tetrio_id: &str) -> bool {
+        self.reviewed.contains(tetrio_id)
+    }
+
+    /// What to show right now
+    pub fn show(&self) -> Effect {
+        match self.current() {
+            Some(id) => Effect::Show(id.to_string()),
+            None => Effect::Empty,
+        }
+    }
+
+    /// Handles an action on the shown entry
+    ///
+    /// Navigation wraps around at both ends.
+    pub fn handle(&mut self, action: Action) -> Effect {
+        let total = self.visible().len();
+        if total == 0 {
+            return Effect::Empty;
+        }
+
+        match action {
+            Action::Previous => {
+                self.index = (self.index + total - 1) % total;
+                self.show()
+            }
+            Action::Next => {
+                self.index = (self.index + 1) % total;
+                self.show()
+            }
+            Action::Disqualify => match self.current() {
+                Some(id) => Effect::AskDisqualifyReason(id.to_string()),
+                None => Effect::Empty,
+            },
+            Action::Note => match self.current() {
+                Some(id) => Effect::AskNote(id.to_string()),
+                None => Effect::Empty,
+            },
+            Action::MarkReviewed => match self.current() {
+                Some(id) => Effect::MarkReviewed(id.to_string()),
+                None => Effect::Empty,
+            },
+        }
+    }
+
+    /// Records that a registration was marked as reviewed, returns what to show next
+    ///
+    /// With [`Filter::Unreviewed`] the entry disappears, so the next one moves into its place.
+    /// Otherwise the session moves on to the next entry, unless it was the last one.
+    pub fn reviewed(&mut self, tetrio_id: &str) -> Effect {
+        self.reviewed.insert(tetrio_id.to_string());
+        let total = self.visible().len();
+        match self.filter {
+            Filter::Unreviewed => {
+                if self.index >= total {
+                    self.index = total.saturating_sub(1);
+                }
+            }
+            Filter::All => {
+                if self.index + 1 < total {
+                    self.index += 1;
+                }
+            }
+        }
+        self.show()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(filter: Filter, reviewed: &[&str]) -> ReviewSession {
+        ReviewSession::new(
+            vec!["a", "b", "c", "d"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            reviewed.iter().map(|id| id.to_string()).collect(),
+            filter,
+        )
+    }
+
+    fn show(id: &str) -> Effect {
+        Effect::Show(id.to_string())
+    }
+
+    #[test]
+    fn emojis_round_trip() {
+        for action in Action::ALL.iter() {
+            assert_eq!(Action::from_emoji(action.emoji()), Some(*action));
+        }
+        assert_eq!(Action::from_emoji("👍"), None);
+    }
+
+    #[test]
+    fn navigation_wraps_at_both_ends() {
+        let mut session = session(Filter::All, &[]);
+        assert_eq!(session.show(), show("a"));
+        assert_eq!(session.position(), (1, 4));
+
+        assert_eq!(session.handle(Action::Previous), show("d"));
+        assert_eq!(session.position(), (4, 4));
+        assert_eq!(session.handle(Action::Next), show("a"));
+        assert_eq!(session.handle(Action::Next), show("b"));
+        assert_eq!(session.handle(Action::Previous), show("a"));
+    }
+
+    #[test]
+    fn filter_hides_reviewed_entries() {
+        let all = session(Filter::All, &["a", "c"]);
+        assert_eq!(all.position(), (1, 4));
+        assert_eq!(all.current(), Some("a"));
+        assert!(all.is_reviewed("a"));
+        assert!(!all.is_reviewed("b"));
+
+        let mut unreviewed = session(Filter::Unreviewed, &["a", "c"]);
+        assert_eq!(unreviewed.position(), (1, 2));
+        assert_eq!(unreviewed.show(), show("b"));
+        assert_eq!(unreviewed.handle(Action::Next), show("d"));
+        assert_eq!(unreviewed.handle(Action::Next), show("b"));
+    }
+
+    #[test]
+    fn actions_target_the_shown_entry() {
+        let mut session = session(Filter::All, &[]);
+        session.handle(Action::Next);
+        assert_eq!(
+            session.handle(Action::Disqualify),
+            Effect::AskDisqualifyReason("b".to_string())
+        );
+        assert_eq!(
+            session.handle(Action::Note),
+            Effect::AskNote("b".to_string())
+        );
+        assert_eq!(
+            session.handle(Action::MarkReviewed),
+            Effect::MarkReviewed("b".to_string())
+        );
+        // asking doesn't move anywhere
+        assert_eq!(session.current(), Some("b"));
+    }
+
+    #[test]
+    fn reviewed_entries_disappear_from_unreviewed_sessions() {
+        let mut session = session(Filter::Unreviewed, &[]);
+        session.handle(Action::Next);
+        assert_eq!(session.reviewed("b"), show("c"));
+        assert_eq!(session.position(), (2, 3));
+
+        // the last entry is removed, the one before it is shown
+        session.handle(Action::Next);
+        assert_eq!(session.current(), Some("d"));
+        assert_eq!(session.reviewed("d"), show("c"));
+        assert_eq!(session.position(), (2, 2));
+
+        assert_eq!(session.reviewed("c"), show("a"));
+        assert_eq!(session.reviewed("a"), Effect::Empty);
+        assert_eq!(session.position(), (0, 0));
+        assert_eq!(session.handle(Action::Next), Effect::Empty);
+        assert_eq!(session.handle(Action::Disqualify), Effect::Empty);
+    }
+
+    #[test]
+    fn reviewed_entries_stay_in_full_sessions() {
+        let mut session = session(Filter::All, &[]);
+        assert_eq!(session.reviewed("a"), show("b"));
+        assert_eq!(session.position(), (2, 4));
+
+        session.handle(Action::Previous);
+        session.handle(Action::Previous);
+        assert_eq!(session.current(), Some("d"));
+        // nothing after the last entry, it stays shown
+        assert_eq!(session.reviewed("d"), show("d"));
+        assert!(session.is_reviewed("a") && session.is_reviewed("d"));
+    }
+
+    #[test]
+    fn empty_sessions() {
+        let mut session = ReviewSession::new(Vec::new(), HashSet::new(), Filter::All);
+        assert_eq!(session.show(), Effect::Empty);
+        assert_eq!(session.position(), (0, 0));
+        for action in Action::ALL.iter() {
+            assert_eq!(session.handle(*action), Effect::Empty);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Small, dependency-free deterministic random number generation
+//!
+//! Everything in here is meant to be reproducible across platforms and compiler versions, so that
+//! anyone can re-run a seeded operation (like a bracket split) and get the exact same result.
+//! Do not use any of this for anything security related.
+
+#![warn(missing_docs)]
+
+/// SplitMix64 pseudo random number generator
+///
+/// Tiny and fast, with good enough statistical properties for shuffling player lists.
+///
+/// # Example
+///
+/// ```
+/// use uc_helper_rust::rng::SplitMix64;
+///
+/// let mut a = SplitMix64::new(42);
+/// let mut b = SplitMix64::new(42);
+/// assert_eq!(a.next_u64(), b.next_u64());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    /// Creates a new generator from a seed
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    /// Returns the next pseudo random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound`
+    ///
+    /// Uses a plain modulo, so there is a negligible bias for bounds that aren't powers of two.
+    /// Player lists are small enough for this to not matter.
+    pub fn next_below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            return 0;
+        }
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Returns a float in `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Shuffles a slice in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.next_below(i + 1);
+            slice.swap(i, j);
+        }
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of a string
+///
+/// Used to derive reproducible seeds from human readable input. The standard library hasher is not
+/// guaranteed to be stable between Rust versions, so it can't be used for this.
+pub fn fnv1a(input: &str) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in input.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    hash
+}
@@ -0,0 +1,314 @@
+//! Runtime status of the bot's subsystems, to answer "why did the bot do that?" during incidents
+//!
+//! Every subsystem that has state worth knowing about implements [`StatusReport`], and the reports
+//! get combined into a [`RuntimeStatus`]. Reports are built from already loaded or cached state only,
+//! so collecting them never blocks on the network.
+
+#![warn(missing_docs)]
+
+use chrono::{DateTime, Utc};
+
+use crate::database::flags::{Feature, FlagSnapshot};
+use crate::database::tournaments::TournamentEntry;
+use crate::tasks::TaskRegistry;
+use crate::tetrio::breaker::CircuitBreaker;
+
+/// A subsystem that can describe its current state
+pub trait StatusReport {
+    /// Name of the subsystem, used as the group heading
+    fn name(&self) -> String;
+
+    /// Key value pairs describing the current state
+    ///
+    /// Must not do any I/O, only read state that is already available.
+    fn report(&self) -> Vec<(String, String)>;
+}
+
+#[derive(Debug, Clone, Default)]
+/// Combined reports of several subsystems, in the order they were registered
+pub struct RuntimeStatus {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl RuntimeStatus {
+    /// Creates an empty status
+    pub fn new() -> RuntimeStatus {
+        RuntimeStatus::default()
+    }
+
+    /// Adds the report of a subsystem
+    ///
+    /// Reports of subsystems with the same name get merged into the same group.
+    pub fn register(&mut self, reporter: &dyn StatusReport) {
+        let name = reporter.name();
+        let entries = reporter.report();
+        match self.sections.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => existing.extend(entries),
+            None => self.sections.push((name, entries)),
+        }
+    }
+
+    /// Reports grouped by subsystem
+    pub fn sections(&self) -> &[(String, Vec<(String, String)>)] {
+        &self.sections
+    }
+
+    /// Flattens the reports into `[subsystem, key, value]` rows
+    ///
+    /// The subsystem is only filled in on the first row of each group.
+    pub fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        for (name, entries) in &self.sections {
+            if entries.is_empty() {
+                rows.push(vec![name.clone(), "-".to_string(), "-".to_string()]);
+            }
+            for (i, (key, value)) in entries.iter().enumerate() {
+                let subsystem = if i == 0 { name.clone() } else { String::new() };
+                rows.push(vec![subsystem, key.clone(), value.clone()]);
+            }
+        }
+        rows
+    }
+}
+
+/// Formats an optional point in time for a report
+pub fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map_or("never".to_string(), |t| {
+        t.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+    })
+}
+
+impl StatusReport for CircuitBreaker {
+    fn name(&self) -> String {
+        "Tetr.io circuit breaker".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        vec![
+            ("State".to_string(), self.state().to_string()),
+            (
+                "Counted failures".to_string(),
+                format!("{}/{}", self.failure_count(), self.failure_threshold),
+            ),
+            (
+                "Failure window".to_string(),
+                format!("{}s", self.window.num_seconds()),
+            ),
+            (
+                "Cooldown".to_string(),
+                format!("{}s", self.cooldown.num_seconds()),
+            ),
+        ]
+    }
+}
+
+impl StatusReport for FlagSnapshot {
+    fn name(&self) -> String {
+        "Feature flags".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = Feature::ALL
+            .iter()
+            .map(|feature| {
+                let (enabled, source) = self.resolve(*feature);
+                let value = if enabled { "on" } else { "off" };
+                (feature.to_string(), format!("{} ({})", value, source))
+            })
+            .collect();
+        if self.stored.is_none() {
+            entries.push(("Stored values".to_string(), "not loaded yet".to_string()));
+        }
+        let unknown = self.unknown();
+        if !unknown.is_empty() {
+            entries.push(("Unknown flags".to_string(), unknown.join(", ")));
+        }
+        entries
+    }
+}
+
+impl StatusReport for TournamentEntry {
+    fn name(&self) -> String {
+        "Active tournament".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        let restrictions = &self.restrictions;
+        let setup = self.setup_status();
+        let missing: Vec<String> = setup.missing().iter().map(|s| format!("{:?}", s)).collect();
+        let dates: Vec<String> = self
+            .dates
+            .list()
+            .iter()
+            .map(|(name, date)| format!("{} {}", name.to_lowercase(), format_time(Some(*date))))
+            .collect();
+
+        vec![
+            (
+                "Tournament".to_string(),
+                format!("{} ({})", self.name, self.shorthand),
+            ),
+            (
+                "Setup".to_string(),
+                if missing.is_empty() {
+                    "complete".to_string()
+                } else {
+                    format!("missing {}", missing.join(", "))
+                },
+            ),
+            (
+                "Registrations".to_string(),
+                format!(
+                    "{} ({} withdrawn)",
+                    self.registered_players().len(),
+                    self.all_registrations().len() - self.registered_players().len()
+                ),
+            ),
+            (
+                "Check-in message".to_string(),
+                match (self.check_in_msg, self.check_in_channel) {
+                    (Some(id), Some(channel)) => format!("{} (channel {})", id, channel),
+                    (Some(id), None) => id.to_string(),
+                    (None, _) => "-".to_string(),
+                },
+            ),
+            (
+                "Dates".to_string(),
+                if dates.is_empty() {
+                    "-".to_string()
+                } else {
+                    dates.join(", ")
+                },
+            ),
+            (
+                "Restrictions".to_string(),
+                format!(
+                    "max rank {}, max peak rank {}, max RD {}, min games {}, min account age {}",
+                    restrictions.max_rank,
+                    restrictions.highest_rank_limit(),
+                    restrictions.max_rd,
+                    restrictions.min_ranked_games,
+                    restrictions
+                        .min_account_age_days
+                        .map_or("-".to_string(), |days| format!("{}d", days))
+                ),
+            ),
+        ]
+    }
+}
+
+impl StatusReport for TaskRegistry {
+    fn name(&self) -> String {
+        "Long running tasks".to_string()
+    }
+
+    fn report(&self) -> Vec<(String, String)> {
+        let running = self.running();
+        if running.is_empty() {
+            return vec![("Running".to_string(), "none".to_string())];
+        }
+
+        running
+            .iter()
+            .map(|task| {
+                let (done, total) = task.progress();
+                (
+                    format!("#{} {}", task.id, task.description),
+                    format!(
+                        "{}/{}, started {}",
+                        done,
+                        total,
+                        format_time(Some(task.started_at))
+                    ),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    struct FakeReporter {
+        name: &'static str,
+        entries: Vec<(&'static str, &'static str)>,
+    }
+
+    impl StatusReport for FakeReporter {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn report(&self) -> Vec<(String, String)> {
+            self.entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        }
+    }
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    #[test]
+    fn reports_are_grouped_in_registration_order() {
+        let cache = FakeReporter {
+            name: "Cache",
+            entries: vec![("Entries", "3"), ("Hits", "10")],
+        };
+        let breaker = FakeReporter {
+            name: "Breaker",
+            entries: vec![("State", "closed")],
+        };
+        let more_cache = FakeReporter {
+            name: "Cache",
+            entries: vec![("Misses", "2")],
+        };
+
+        let mut status = RuntimeStatus::new();
+        status.register(&cache);
+        status.register(&breaker);
+        status.register(&more_cache);
+
+        let names: Vec<&str> = status
+            .sections()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Cache", "Breaker"]);
+        assert_eq!(status.sections()[0].1.len(), 3);
+
+        assert_eq!(
+            status.rows(),
+            vec![
+                row(&["Cache", "Entries", "3"]),
+                row(&["", "Hits", "10"]),
+                row(&["", "Misses", "2"]),
+                row(&["Breaker", "State", "closed"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_reports_still_get_a_row() {
+        let mut status = RuntimeStatus::new();
+        status.register(&FakeReporter {
+            name: "Idle",
+            entries: vec![],
+        });
+        assert_eq!(status.rows(), vec![row(&["Idle", "-", "-"])]);
+        assert!(RuntimeStatus::new().rows().is_empty());
+    }
+
+    #[test]
+    fn times_are_formatted() {
+        assert_eq!(format_time(None), "never");
+        assert_eq!(
+            format_time(Some(Utc.ymd(2021, 3, 14).and_hms(18, 0, 5))),
+            "2021-03-14 18:00:05 UTC"
+        );
+    }
+}
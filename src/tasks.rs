@@ -0,0 +1,321 @@
+//! Bookkeeping for long running tasks, so staff can see and stop them
+//!
+//! A task is registered in the [`TaskRegistry`] and gets a [`TaskHandle`]. The code doing the work
+//! checks [`TaskHandle::is_cancelled()`] between units of work (a player, a row, ...) and stops early
+//! if it returns `true`, reporting what it got done so far. A task is cancelled either on request
+//! or once its timeout has passed, so nothing can hang forever.
+
+#![warn(missing_docs)]
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why a task was cancelled
+pub enum CancelReason {
+    /// Someone requested it, with their Discord ID
+    Requested(u64),
+    /// The task ran longer than its timeout
+    Timeout,
+}
+
+impl std::fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CancelReason::Requested(by) => write!(f, "cancelled by <@{}>", by),
+            CancelReason::Timeout => f.write_str("timed out"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TaskState {
+    cancelled: AtomicBool,
+    reason: Mutex<Option<CancelReason>>,
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+#[derive(Debug, Clone)]
+/// Handle of a running task, shared between the registry and the code doing the work
+pub struct TaskHandle {
+    /// ID in the registry
+    pub id: u64,
+    /// What the task does
+    pub description: String,
+    /// When the task was registered
+    pub started_at: DateTime<Utc>,
+    /// When the task gets cancelled automatically
+    pub deadline: DateTime<Utc>,
+    state: Arc<TaskState>,
+}
+
+impl TaskHandle {
+    /// Creates a handle that isn't tracked by any registry
+    ///
+    /// Useful for calling cancellable functions that should just run to completion.
+    pub fn detached(description: &str) -> TaskHandle {
+        TaskHandle::new(0, description, Utc::now(), Duration::weeks(52))
+    }
+
+    fn new(id: u64, description: &str, now: DateTime<Utc>, timeout: Duration) -> TaskHandle {
+        TaskHandle {
+            id,
+            description: description.to_string(),
+            started_at: now,
+            deadline: now + timeout,
+            state: Arc::new(TaskState {
+                cancelled: AtomicBool::new(false),
+                reason: Mutex::new(None),
+                done: AtomicUsize::new(0),
+                total: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Signals the task to stop, returns `false` if it was already cancelled
+    pub fn cancel(&self, reason: CancelReason) -> bool {
+        let mut current = self
+            .state
+            .reason
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if current.is_some() {
+            return false;
+        }
+        *current = Some(reason);
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Whether the task should stop, checked between units of work
+    ///
+    /// Cancels the task if the deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled_at(Utc::now())
+    }
+
+    /// Same as [`TaskHandle::is_cancelled()`], with an explicit current time
+    pub fn is_cancelled_at(&self, now: DateTime<Utc>) -> bool {
+        if self.state.cancelled.load(Ordering::SeqCst) {
+            return true;
+        }
+        if now >= self.deadline {
+            self.cancel(CancelReason::Timeout);
+            return true;
+        }
+        false
+    }
+
+    /// Why the task was cancelled, `None` if it wasn't
+    pub fn cancel_reason(&self) -> Option<CancelReason> {
+        *self
+            .state
+            .reason
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Sets the amount of units of work, once known
+    pub fn set_total(&self, total: usize) {
+        self.state.total.store(total, Ordering::SeqCst);
+    }
+
+    /// Marks a unit of work as done
+    pub fn advance(&self) {
+        self.state.done.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Marks several units of work as done at once
+    pub fn advance_by(&self, units: usize) {
+        self.state.done.fetch_add(units, Ordering::SeqCst);
+    }
+
+    /// Units of work done and the total amount (0 if not known yet)
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.state.done.load(Ordering::SeqCst),
+            self.state.total.load(Ordering::SeqCst),
+        )
+    }
+
+    /// Human readable summary of how the task went, meant to be called once the work returned
+    pub fn summary(&self) -> String {
+        let (done, total) = self.progress();
+        match self.cancel_reason() {
+            Some(reason) => format!(
+                "{} {} after {}/{} items, the rest was skipped",
+                self.description, reason, done, total
+            ),
+            None => format!("{} finished, {} items processed", self.description, done),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// Long running tasks that are currently running
+pub struct TaskRegistry {
+    next_id: u64,
+    tasks: BTreeMap<u64, TaskHandle>,
+}
+
+impl TaskRegistry {
+    /// Creates an empty registry
+    pub fn new() -> TaskRegistry {
+        TaskRegistry::default()
+    }
+
+    /// Registers a new task that gets cancelled after `timeout`
+    pub fn register(&mut self, description: &str, timeout: Duration) -> TaskHandle {
+        self.register_at(description, timeout, Utc::now())
+    }
+
+    /// Same as [`TaskRegistry::register()`], with an explicit current time
+    pub fn register_at(
+        &mut self,
+        description: &str,
+        timeout: Duration,
+        now: DateTime<Utc>,
+    ) -> TaskHandle {
+        self.next_id += 1;
+        let handle = TaskHandle::new(self.next_id, description, now, timeout);
+        self.tasks.insert(handle.id, handle.clone());
+        handle
+    }
+
+    /// Removes a task once it's done, whether it finished or was cancelled
+    pub fn complete(&mut self, id: u64) -> Option<TaskHandle> {
+        self.tasks.remove(&id)
+    }
+
+    /// Signals a task to stop
+    ///
+    /// The task stays listed until it actually stops and calls [`TaskRegistry::complete()`].
+    /// Returns `None` if there is no such task, `Some(false)` if it was already cancelled.
+    pub fn cancel(&self, id: u64, reason: CancelReason) -> Option<bool> {
+        self.tasks.get(&id).map(|task| task.cancel(reason))
+    }
+
+    /// Currently running tasks, oldest first
+    pub fn running(&self) -> Vec<&TaskHandle> {
+        self.tasks.values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    // Works through `units` items like a real task would, yielding between them
+    async fn fake_task(task: TaskHandle, units: usize) {
+        task.set_total(units);
+        for _ in 0..units {
+            if task.is_cancelled() {
+                return;
+            }
+            tokio::task::yield_now().await;
+            task.advance();
+        }
+    }
+
+    #[test]
+    fn register_and_complete() {
+        let mut registry = TaskRegistry::new();
+        let first = registry.register("Leaderboard update", Duration::minutes(10));
+        let second = registry.register("Registered update", Duration::minutes(10));
+        assert_eq!((first.id, second.id), (1, 2));
+        let ids: Vec<u64> = registry.running().iter().map(|task| task.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        assert_eq!(registry.complete(first.id).map(|task| task.id), Some(1));
+        assert!(registry.complete(first.id).is_none());
+        let ids: Vec<u64> = registry.running().iter().map(|task| task.id).collect();
+        assert_eq!(ids, vec![2]);
+
+        // IDs aren't reused
+        assert_eq!(registry.register("Another", Duration::minutes(1)).id, 3);
+    }
+
+    #[tokio::test]
+    async fn cancelled_task_stops_early() {
+        let mut registry = TaskRegistry::new();
+        let handle = registry.register("Leaderboard update", Duration::minutes(10));
+        let running = tokio::spawn(fake_task(handle.clone(), 1000));
+
+        while handle.progress().0 < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            registry.cancel(handle.id, CancelReason::Requested(1)),
+            Some(true)
+        );
+        assert_eq!(
+            registry.cancel(handle.id, CancelReason::Requested(2)),
+            Some(false)
+        );
+        assert_eq!(registry.cancel(99, CancelReason::Requested(1)), None);
+        running.await.unwrap();
+
+        let (done, total) = handle.progress();
+        assert!(done >= 3 && done < total, "{}/{}", done, total);
+        assert_eq!(handle.cancel_reason(), Some(CancelReason::Requested(1)));
+        assert!(handle.summary().contains("cancelled by <@1>"));
+
+        // still listed until whoever started it completes it
+        assert_eq!(registry.running().len(), 1);
+        registry.complete(handle.id);
+        assert!(registry.running().is_empty());
+    }
+
+    #[tokio::test]
+    async fn timed_out_task_stops_on_its_own() {
+        let mut registry = TaskRegistry::new();
+        let started = Utc::now() - Duration::minutes(11);
+        let handle = registry.register_at("Leaderboard update", Duration::minutes(10), started);
+        tokio::spawn(fake_task(handle.clone(), 1000)).await.unwrap();
+
+        assert_eq!(handle.progress(), (0, 1000));
+        assert_eq!(handle.cancel_reason(), Some(CancelReason::Timeout));
+        assert_eq!(
+            handle.summary(),
+            "Leaderboard update timed out after 0/1000 items, the rest was skipped"
+        );
+        // a timeout doesn't get overwritten by a later request
+        assert_eq!(
+            registry.cancel(handle.id, CancelReason::Requested(1)),
+            Some(false)
+        );
+        assert_eq!(registry.running().len(), 1);
+    }
+
+    #[test]
+    fn deadline_is_checked_against_the_given_time() {
+        let mut registry = TaskRegistry::new();
+        let handle = registry.register_at("Snapshot", Duration::minutes(10), now());
+        assert!(!handle.is_cancelled_at(now() + Duration::minutes(9)));
+        assert!(handle.cancel_reason().is_none());
+        assert!(handle.is_cancelled_at(now() + Duration::minutes(10)));
+        assert_eq!(handle.cancel_reason(), Some(CancelReason::Timeout));
+
+        assert!(!TaskHandle::detached("Manual update").is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn finished_task_summary() {
+        let handle = TaskHandle::detached("Manual update");
+        fake_task(handle.clone(), 5).await;
+        assert_eq!(handle.progress(), (5, 5));
+        assert_eq!(
+            handle.summary(),
+            "Manual update finished, 5 items processed"
+        );
+    }
+}
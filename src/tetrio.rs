@@ -1,42 +1,68 @@
 //! Data classes and functions regarding the Tetrio API.
 //!
-//! All functions are written synchronously, for tokio runtime reasons related to usage in the Discord bot client.
+//! All requests are async and meant to be awaited on the tokio runtime of the Discord bot client.
 //!
-//! Only Leaderboard and User endpoints are implemented for now. There is no caching going on, all of the caching is managed by the database module.
+//! Only Leaderboard, User, News, Search and Streams endpoints are implemented for now. There is no caching going on, all of the caching is managed by the database module.
 //! Therefore in the optimal use-case, the [crate::discord] module should never call from this module directly, only the [crate::database] commands.
 //!
 //! # Example
 //!
-//! ```
+//! ```no_run
 //! use uc_helper_rust::tetrio;
 //!
-//! let leaderboard = tetrio::leaderboard::request()?;
-//! let user = tetrio::user::request("icedynamix")?;
+//! # async fn fetch() -> Result<(), tetrio::TetrioApiError> {
+//! let leaderboard = tetrio::leaderboard::request().await?;
+//! let user = tetrio::user::request("icedynamix").await?;
+//! # Ok(())
+//! # }
 //! ```
 
 #![warn(missing_docs)]
 
 use std::fmt::Formatter;
+use std::sync::{Mutex, MutexGuard};
 
-use reqwest::blocking::Client;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 
+use self::breaker::{BreakerState, CircuitBreaker};
+use self::latency::{CacheStatus, Family, LatencyRegistry, Outcome};
+use self::retry::{RateLimiter, RetryPolicy};
+
+pub mod breaker;
+pub mod latency;
 pub mod leaderboard;
 pub mod news;
+pub mod retry;
+pub mod search;
+pub mod stream;
 pub mod user;
 
 /// The base URL of the Tetrio API
 const API_URL: &str = "https://ch.tetr.io/api";
 
+/// Environment variable to request from a different base URL than [`API_URL`], like a local mock
+pub const API_URL_ENV: &str = "TETRIO_API_URL";
+
 #[derive(Error, Debug)]
 /// Something that can go wrong while requesting from the Tetrio API
 pub enum TetrioApiError {
     #[error("Something happened while requesting from tetrio: {0}")]
-    /// Error returned by the Tetrio API on an unsuccessful request
-    Error(String),
+    /// Error returned by the Tetrio API on an unsuccessful request, or a network/parsing failure
+    Upstream(String),
+    #[error("The requested resource does not exist on tetrio")]
+    /// The Tetrio API reported that the requested resource (usually a user) does not exist
+    NotFound,
+    #[error("Tetr.io appears to be down or under maintenance (until {until})")]
+    /// Too many requests failed recently, so requests are not being executed until the specified time
+    CircuitOpen {
+        /// When requests will be attempted again
+        until: DateTime<Utc>,
+    },
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -79,54 +105,324 @@ pub struct SuccessfulResponse<T> {
 /// Tetrio API response, represented as a Result type
 type TetrioResponse<T> = Result<SuccessfulResponse<T>, TetrioApiError>;
 
+/// Timeout for a single request to the Tetrio API
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Error message for a 429 response, used to tell rate limits apart from other failures
+const RATE_LIMITED_MESSAGE: &str = "Rate limited";
+
+lazy_static! {
+    /// Circuit breaker shared by every request to the Tetrio API
+    static ref BREAKER: Mutex<CircuitBreaker> = Mutex::new(CircuitBreaker::default());
+    /// Latencies and outcomes of every request to the Tetrio API
+    static ref LATENCY: Mutex<LatencyRegistry> = Mutex::new(LatencyRegistry::default());
+    /// Minimum delay between requests to the Tetrio API
+    static ref RATE_LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::from_env());
+    /// How failed requests to the Tetrio API are retried
+    static ref RETRY_POLICY: RetryPolicy = RetryPolicy::from_env();
+    /// Base URL every request goes to
+    static ref BASE_URL: String = std::env::var(API_URL_ENV).unwrap_or_else(|_| API_URL.to_string());
+}
+
+/// Waits for the next free request slot, without holding the lock while waiting
+async fn wait_for_slot() {
+    let wait = RATE_LIMITER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .reserve(std::time::Instant::now());
+    if wait > std::time::Duration::from_millis(0) {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn breaker() -> MutexGuard<'static, CircuitBreaker> {
+    BREAKER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Current state of the circuit breaker around the Tetrio API, along with the counted failures
+pub fn breaker_status() -> (BreakerState, usize) {
+    let breaker = breaker();
+    (breaker.state(), breaker.failure_count())
+}
+
+/// Copy of the circuit breaker around the Tetrio API, for status reports
+pub fn breaker_snapshot() -> CircuitBreaker {
+    breaker().clone()
+}
+
+/// Copy of the recorded request latencies, for status reports
+pub fn latency_snapshot() -> LatencyRegistry {
+    LATENCY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Records a finished request, only holds the lock for the increments
+fn record_latency(
+    endpoint: &str,
+    started: std::time::Instant,
+    outcome: Outcome,
+    cache: Option<CacheStatus>,
+) {
+    let millis = started.elapsed().as_millis() as u64;
+    LATENCY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .record(
+            Family::of_endpoint(endpoint),
+            millis,
+            outcome,
+            cache,
+            Utc::now(),
+        );
+}
+
+/// Gives up the probe of a half-open circuit if the request is dropped before it reports back
+///
+/// Holds the time the probe was let through, see [`CircuitBreaker::cancel_probe()`]. `None` if the
+/// request isn't a probe or already reported back.
+struct ProbeGuard(Option<DateTime<Utc>>);
+
+impl Drop for ProbeGuard {
+    fn drop(&mut self) {
+        if let Some(started) = self.0 {
+            breaker().cancel_probe(started);
+        }
+    }
+}
+
+/// Whether requests to the Tetrio API are currently failing fast
+///
+/// Background tasks should skip their cycle if this is the case.
+pub fn is_circuit_open() -> bool {
+    matches!(breaker().state(), BreakerState::Open { until } if Utc::now() < until)
+}
+
+/// Why a single attempt failed
+enum AttemptError {
+    /// Might work on the next attempt (timeouts, connection problems, 5xx and 429 responses),
+    /// with the wait the API asked for, if any
+    Transient(TetrioApiError, Option<std::time::Duration>),
+    /// Won't work on another attempt either
+    Permanent(TetrioApiError),
+}
+
+impl AttemptError {
+    fn into_inner(self) -> TetrioApiError {
+        match self {
+            AttemptError::Transient(e, _) | AttemptError::Permanent(e) => e,
+        }
+    }
+}
+
+/// Executes the HTTP request and parses the general response structure
+async fn execute_request(
+    base_url: &str,
+    endpoint: &str,
+) -> Result<TetrioResponseStruct, AttemptError> {
+    let permanent =
+        |e: reqwest::Error| AttemptError::Permanent(TetrioApiError::Upstream(e.to_string()));
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(permanent)?;
+    let url = format!("{}/{}", base_url, endpoint);
+    let request = client
+        .request(reqwest::Method::GET, &url)
+        .header("X-Session-Header", "IceDynamix") // i have no idea whether im doing this right
+        .build()
+        .map_err(permanent)?;
+
+    let response = client
+        .execute(request)
+        .await
+        .map_err(|e| AttemptError::Transient(TetrioApiError::Upstream(e.to_string()), None))?;
+
+    if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(retry::parse_retry_after);
+        return Err(AttemptError::Transient(
+            TetrioApiError::Upstream(RATE_LIMITED_MESSAGE.to_string()),
+            retry_after,
+        ));
+    }
+
+    if retry::is_transient_status(response.status().as_u16()) {
+        return Err(AttemptError::Transient(
+            TetrioApiError::Upstream(format!("Server responded with {}", response.status())),
+            None,
+        ));
+    }
+
+    response.json().await.map_err(|e| {
+        AttemptError::Permanent(TetrioApiError::Upstream(format!(
+            "Could not parse response ({})",
+            e
+        )))
+    })
+}
+
+/// Executes the HTTP request, retrying transient failures according to [`RETRY_POLICY`]
+///
+/// Every attempt waits for a free slot of the rate limiter first.
+async fn execute_with_retries(
+    base_url: &str,
+    endpoint: &str,
+) -> Result<TetrioResponseStruct, TetrioApiError> {
+    let mut attempt = 1;
+    loop {
+        wait_for_slot().await;
+        match execute_request(base_url, endpoint).await {
+            Ok(response) => return Ok(response),
+            Err(AttemptError::Transient(e, retry_after)) if RETRY_POLICY.should_retry(attempt) => {
+                let delay = RETRY_POLICY.delay(attempt, retry_after);
+                tracing::info!(
+                    "Attempt {}/{} to request {} failed ({}), retrying in {}ms",
+                    attempt,
+                    RETRY_POLICY.max_attempts,
+                    endpoint,
+                    e,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into_inner()),
+        }
+    }
+}
+
 /// General function to request from a Tetrio endpoint.
 ///
 /// While this function is public, you should instead call the request functions directly from the data classes defined for each endpoint.
 ///
 /// - [`leaderboard::request()`]
 /// - [`user::request()`]
-pub fn request<T: DeserializeOwned>(endpoint: &str) -> TetrioResponse<T> {
+///
+/// Fails fast with [`TetrioApiError::CircuitOpen`] if too many requests failed recently.
+/// Timeouts, server errors and rate limits are retried a few times before giving up, see [`retry`].
+/// A successful response without data is an error, see [`request_optional()`] for endpoints where that's expected.
+pub async fn request<T: DeserializeOwned>(endpoint: &str) -> TetrioResponse<T> {
+    request_from(&BASE_URL, endpoint).await
+}
+
+/// [`request()`] against the given base URL
+async fn request_from<T: DeserializeOwned>(base_url: &str, endpoint: &str) -> TetrioResponse<T> {
+    let response = request_optional_from::<T>(base_url, endpoint).await?;
+    match response.data {
+        Some(data) => Ok(SuccessfulResponse {
+            data,
+            cache: response.cache,
+        }),
+        None => Err(TetrioApiError::Upstream("No data".to_string())),
+    }
+}
+
+/// Same as [`request()`], but a successful response with `null` data is `None` instead of an error
+///
+/// Some endpoints, like [`search::request()`], return `null` if nothing was found.
+pub async fn request_optional<T: DeserializeOwned>(endpoint: &str) -> TetrioResponse<Option<T>> {
+    request_optional_from(&BASE_URL, endpoint).await
+}
+
+/// [`request_optional()`] against the given base URL
+async fn request_optional_from<T: DeserializeOwned>(
+    base_url: &str,
+    endpoint: &str,
+) -> TetrioResponse<Option<T>> {
+    let mut probe = {
+        let mut breaker = breaker();
+        if let Err(until) = breaker.check(Utc::now()) {
+            tracing::warn!("Circuit is open, not requesting from {}", endpoint);
+            return Err(TetrioApiError::CircuitOpen { until });
+        }
+        ProbeGuard(breaker.probe_started())
+    };
+
     tracing::info!("Requesting from endpoint {}", endpoint);
 
-    let parsed_response: TetrioResponseStruct = tokio::task::block_in_place(|| {
-        let client = Client::new();
-        let url = format!("{}/{}", API_URL, endpoint);
-        let request = client
-            .request(reqwest::Method::GET, &url)
-            .header("X-Session-Header", "IceDynamix") // i have no idea whether im doing this right
-            .build()
-            .expect("Could not build request");
+    let started = std::time::Instant::now();
+    let parsed_response = match execute_with_retries(base_url, endpoint).await {
+        Ok(response) => {
+            probe.0 = None;
+            breaker().record_success(Utc::now());
+            response
+        }
+        Err(e) => {
+            tracing::warn!("Request to {} failed: {}", endpoint, e);
+            probe.0 = None;
+            breaker().record_failure(Utc::now());
+            crate::metrics::global().record_tetrio_request(true);
+            let outcome = match &e {
+                TetrioApiError::Upstream(message) if message == RATE_LIMITED_MESSAGE => {
+                    Outcome::RateLimited
+                }
+                _ => Outcome::NetworkError,
+            };
+            record_latency(endpoint, started, outcome, None);
+            return Err(e);
+        }
+    };
 
-        let response = client.execute(request).expect("Could not execute request");
+    let (outcome, cache) = match (&parsed_response.cache, parsed_response.success) {
+        (Some(cache), true) => (Outcome::Success, CacheStatus::parse(&cache.status)),
+        (None, true) => (Outcome::Success, None),
+        (_, false) => (Outcome::ApiError, None),
+    };
+    record_latency(endpoint, started, outcome, cache);
+    crate::metrics::global().record_tetrio_request(!parsed_response.success);
 
-        response.json().expect("Could not parse")
-    });
+    parse_response(parsed_response)
+}
 
-    if !parsed_response.success {
-        return Err(TetrioApiError::Error(parsed_response.error.unwrap()));
+/// Turns a response body into the data of an endpoint
+///
+/// Unsuccessful responses become [`TetrioApiError::Upstream`] with the error message of the API,
+/// endpoints tell apart specific messages themselves, like [`user::classify_error()`].
+fn parse_response<T: DeserializeOwned>(
+    response: TetrioResponseStruct,
+) -> TetrioResponse<Option<T>> {
+    if !response.success {
+        return Err(TetrioApiError::Upstream(
+            response
+                .error
+                .unwrap_or_else(|| "Unknown error".to_string()),
+        ));
     }
 
-    if parsed_response.data.is_none() {
-        return Err(TetrioApiError::Error("No data".to_string()));
-    }
+    let cache = match response.cache {
+        Some(cache) => cache,
+        None => return Err(TetrioApiError::Upstream("No cache data".to_string())),
+    };
 
-    if parsed_response.cache.is_none() {
-        return Err(TetrioApiError::Error("No cache data".to_string()));
-    }
+    let data = match response.data {
+        Some(data) => data,
+        None => return Ok(SuccessfulResponse { data: None, cache }),
+    };
 
-    match serde_json::from_value::<T>(parsed_response.data.unwrap()) {
+    match serde_json::from_value::<T>(data) {
         Ok(parsed_data) => Ok(SuccessfulResponse {
-            data: parsed_data,
-            cache: parsed_response.cache.unwrap(),
+            data: Some(parsed_data),
+            cache,
         }),
-        Err(_) => Err(TetrioApiError::Error("Could not parse".to_string())),
+        Err(_) => Err(TetrioApiError::Upstream("Could not parse".to_string())),
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
 /// A player's league rank
 ///
-/// Supports addition of usizes and comparison of ranks
+/// Supports addition and subtraction of usizes and comparison of ranks. Ranks are ordered from lowest to highest,
+/// with `Unranked` below every rank. The order comes from the explicit discriminants, which follow
+/// the order of the API's `percentile_rank` (see [`Rank::PERCENTILE_BOUNDS`]). Every eligibility
+/// check compares ranks, so a new rank has to be inserted with a discriminant at its place in that
+/// order, shifting the ranks above it, and added to [`Rank::ALL`].
 ///
 /// # Example
 ///
@@ -134,33 +430,41 @@ pub fn request<T: DeserializeOwned>(endpoint: &str) -> TetrioResponse<T> {
 /// use uc_helper_rust::tetrio::Rank;
 /// use std::str::FromStr;
 ///
-/// let rank = Rank::from_str("s+");
+/// let rank = Rank::from_str("s+").unwrap();
 ///
 /// assert_eq!(Rank::SPlus, rank);
 /// assert_eq!(Rank::SS, rank + 1);
+/// assert_eq!(Rank::S, rank - 1);
 /// assert!(Rank::U > rank);
+/// assert!(Rank::Unranked < Rank::D);
+///
+/// // both directions saturate instead of wrapping around
+/// assert_eq!(Rank::X, Rank::U + 5);
+/// assert_eq!(Rank::Unranked, Rank::D - 5);
+/// assert_eq!(3, Rank::S.distance(Rank::U));
+/// assert_eq!(-3, Rank::U.distance(Rank::S));
 /// ```
 #[allow(missing_docs)]
 pub enum Rank {
-    Unranked,
-    D,
-    DPlus,
-    CMinus,
-    C,
-    CPlus,
-    BMinus,
-    B,
-    BPlus,
-    AMinus,
-    A,
-    APlus,
-    SMinus,
-    S,
-    SPlus,
+    Unranked = 0,
+    D = 1,
+    DPlus = 2,
+    CMinus = 3,
+    C = 4,
+    CPlus = 5,
+    BMinus = 6,
+    B = 7,
+    BPlus = 8,
+    AMinus = 9,
+    A = 10,
+    APlus = 11,
+    SMinus = 12,
+    S = 13,
+    SPlus = 14,
     #[allow(clippy::upper_case_acronyms)]
-    SS,
-    U,
-    X,
+    SS = 15,
+    U = 16,
+    X = 17,
 }
 
 impl Rank {
@@ -246,21 +550,150 @@ impl Rank {
         format!("https://tetr.io/res/league-ranks/{}.png", self.to_str())
     }
 
-    /// Returns an iterator, which follows the rank order
+    /// Lower bound of the leaderboard percentile for each rank, best rank first
+    ///
+    /// A player at the top 3% of the leaderboard (percentile `0.03`) falls into the first rank whose bound
+    /// is above that, so U. These are the cutoffs Tetra League uses, see [`Rank::from_percentile()`].
+    pub const PERCENTILE_BOUNDS: [(Rank, f64); 17] = [
+        (Rank::X, 0.01),
+        (Rank::U, 0.05),
+        (Rank::SS, 0.11),
+        (Rank::SPlus, 0.17),
+        (Rank::S, 0.23),
+        (Rank::SMinus, 0.30),
+        (Rank::APlus, 0.38),
+        (Rank::A, 0.46),
+        (Rank::AMinus, 0.54),
+        (Rank::BPlus, 0.62),
+        (Rank::B, 0.70),
+        (Rank::BMinus, 0.78),
+        (Rank::CPlus, 0.84),
+        (Rank::C, 0.90),
+        (Rank::CMinus, 0.95),
+        (Rank::DPlus, 0.975),
+        (Rank::D, 1.0),
+    ];
+
+    /// Rank of a player at a leaderboard percentile (`0.0` is the top, `1.0` the bottom)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use uc_helper_rust::tetrio::Rank;
+    ///
+    /// assert_eq!(Rank::X, Rank::from_percentile(0.005));
+    /// assert_eq!(Rank::SPlus, Rank::from_percentile(0.15));
+    /// assert_eq!(Rank::D, Rank::from_percentile(1.0));
+    /// ```
+    pub fn from_percentile(percentile: f64) -> Rank {
+        Rank::PERCENTILE_BOUNDS
+            .iter()
+            .find(|(_, bound)| percentile < *bound)
+            .map_or(Rank::D, |(rank, _)| *rank)
+    }
+
+    /// Every rank, lowest first, in the same order as their discriminants
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use uc_helper_rust::tetrio::Rank;
+    ///
+    /// // the order is strict and matches the discriminants
+    /// assert!(Rank::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    /// for (index, rank) in Rank::ALL.iter().enumerate() {
+    ///     assert_eq!(index, *rank as usize);
+    /// }
+    ///
+    /// // the ranked part matches the percentile bounds, which are listed best rank first
+    /// let from_bounds: Vec<Rank> = Rank::PERCENTILE_BOUNDS.iter().rev().map(|(rank, _)| *rank).collect();
+    /// assert_eq!(&Rank::ALL[1..], from_bounds.as_slice());
+    /// ```
+    pub const ALL: [Rank; 18] = [
+        Rank::Unranked,
+        Rank::D,
+        Rank::DPlus,
+        Rank::CMinus,
+        Rank::C,
+        Rank::CPlus,
+        Rank::BMinus,
+        Rank::B,
+        Rank::BPlus,
+        Rank::AMinus,
+        Rank::A,
+        Rank::APlus,
+        Rank::SMinus,
+        Rank::S,
+        Rank::SPlus,
+        Rank::SS,
+        Rank::U,
+        Rank::X,
+    ];
+
+    /// Returns an iterator over [`Rank::ALL`], which follows the rank order
     pub fn iter() -> std::slice::Iter<'static, Rank> {
-        use Rank::*;
-        static RANKS: [Rank; 18] = [
-            Unranked, D, DPlus, CMinus, C, CPlus, BMinus, B, BPlus, AMinus, A, APlus, SMinus, S,
-            SPlus, SS, U, X,
-        ];
-        RANKS.iter()
+        Rank::ALL.iter()
+    }
+
+    /// Parses a rank typed by a user, case insensitive
+    ///
+    /// Unlike the [`FromStr`](std::str::FromStr) implementation, which is meant for API data, unknown
+    /// strings are an error, so a typo in a restriction doesn't silently turn into `Unranked`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use uc_helper_rust::tetrio::Rank;
+    /// use std::str::FromStr;
+    ///
+    /// assert_eq!(Ok(Rank::SPlus), Rank::try_from_str("S+"));
+    /// assert_eq!(Ok(Rank::Unranked), Rank::try_from_str("z"));
+    /// assert!(Rank::try_from_str("s=").is_err());
+    ///
+    /// // the lenient parser maps the same typo to unranked
+    /// assert_eq!(Rank::Unranked, Rank::from_str("s=").unwrap());
+    /// ```
+    pub fn try_from_str(s: &str) -> Result<Rank, UnknownRank> {
+        let lowercase = s.trim().to_lowercase();
+        Rank::iter()
+            .find(|rank| rank.to_str() == lowercase)
+            .copied()
+            .ok_or_else(|| UnknownRank(s.to_string()))
+    }
+
+    /// Number of ranks from this rank up to `other`, negative if `other` is lower
+    pub fn distance(&self, other: Rank) -> i32 {
+        other as i32 - *self as i32
     }
 }
 
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown rank `{0}`, expected one of d, d+, c-, ..., ss, u, x")]
+/// A string that isn't a known rank, see [`Rank::try_from_str()`]
+pub struct UnknownRank(pub String);
+
 impl std::str::FromStr for Rank {
     type Err = ();
 
     /// Parses a string into a Rank. Will return `Rank::Unranked` if not parsable
+    ///
+    /// Ranks Tetr.io adds in the future are unknown, so they are treated as unranked until they
+    /// are added here.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use uc_helper_rust::tetrio::Rank;
+    /// use std::str::FromStr;
+    ///
+    /// for rank in Rank::ALL.iter() {
+    ///     assert_eq!(*rank, Rank::from_str(rank.to_str()).unwrap());
+    ///     assert_eq!(*rank, Rank::from_str(&rank.to_string().to_lowercase()).unwrap());
+    /// }
+    ///
+    /// // an unknown rank, update this when it's added
+    /// assert_eq!(Rank::Unranked, Rank::from_str("x+").unwrap());
+    /// ```
     fn from_str(s: &str) -> Result<Self, ()> {
         let parsed = match s {
             "d" => Rank::D,
@@ -302,3 +735,165 @@ impl std::ops::Add<usize> for Rank {
         *Rank::iter().nth(index + n).unwrap_or(&Rank::X)
     }
 }
+
+impl std::ops::Sub<usize> for Rank {
+    type Output = Rank;
+
+    fn sub(self, n: usize) -> Self::Output {
+        let index = Rank::iter().position(|r| self == *r).unwrap_or(0);
+        Rank::ALL[index.saturating_sub(n)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+    use std::hash::{Hash, Hasher};
+    use std::str::FromStr;
+
+    fn hash_of(rank: Rank) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        rank.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn ord_matches_the_discriminants() {
+        for a in Rank::iter() {
+            for b in Rank::iter() {
+                assert_eq!(a.cmp(b), (*a as u8).cmp(&(*b as u8)), "{:?} {:?}", a, b);
+                assert_eq!(a.partial_cmp(b), Some(a.cmp(b)));
+            }
+        }
+        assert_eq!(Rank::iter().min(), Some(&Rank::Unranked));
+        assert_eq!(Rank::iter().max(), Some(&Rank::X));
+    }
+
+    #[test]
+    fn hash_is_consistent_with_eq() {
+        for rank in Rank::iter() {
+            let parsed = Rank::try_from_str(rank.to_str()).unwrap();
+            assert_eq!(*rank, parsed);
+            assert_eq!(hash_of(*rank), hash_of(parsed));
+        }
+
+        let set: HashSet<Rank> = Rank::iter().copied().collect();
+        assert_eq!(set.len(), Rank::ALL.len());
+        assert!(set.contains(&Rank::from_str("s+").unwrap()));
+    }
+
+    #[test]
+    fn every_rank_has_a_color() {
+        let mut colors = HashSet::new();
+        for rank in Rank::iter() {
+            let color = rank.to_color();
+            assert_eq!(color.len(), 6, "{:?}", rank);
+            assert!(u32::from_str_radix(color, 16).is_ok(), "{:?}", rank);
+            assert!(
+                colors.insert(color.to_lowercase()),
+                "{:?} shares its color",
+                rank
+            );
+        }
+    }
+
+    #[test]
+    fn every_rank_has_an_emoji() {
+        let mut emojis = HashSet::new();
+        for rank in Rank::iter() {
+            let emoji = rank.to_emoji();
+            let name = match rank {
+                Rank::Unranked => "unranked".to_string(),
+                rank => rank.to_str().replace('+', "plus").replace('-', "minus"),
+            };
+            assert!(
+                emoji.starts_with(&format!("<:rank_{}:", name)) && emoji.ends_with('>'),
+                "{:?} has {}",
+                rank,
+                emoji
+            );
+            assert!(emojis.insert(emoji), "{:?} shares its emoji", rank);
+        }
+    }
+
+    #[test]
+    fn ranks_form_a_single_chain() {
+        let chain = [
+            Rank::Unranked,
+            Rank::D,
+            Rank::DPlus,
+            Rank::CMinus,
+            Rank::C,
+            Rank::CPlus,
+            Rank::BMinus,
+            Rank::B,
+            Rank::BPlus,
+            Rank::AMinus,
+            Rank::A,
+            Rank::APlus,
+            Rank::SMinus,
+            Rank::S,
+            Rank::SPlus,
+            Rank::SS,
+            Rank::U,
+            Rank::X,
+        ];
+        assert_eq!(chain, Rank::ALL);
+        for pair in chain.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} < {:?}", pair[0], pair[1]);
+            assert_eq!(pair[0] + 1, pair[1]);
+            assert_eq!(pair[1] - 1, pair[0]);
+        }
+    }
+
+    #[test]
+    fn display_round_trips_through_from_str() {
+        for rank in Rank::iter() {
+            let displayed = rank.to_string();
+            assert_eq!(displayed, rank.to_str().to_uppercase());
+            assert_eq!(Rank::from_str(&displayed.to_lowercase()), Ok(*rank));
+            assert_eq!(Rank::try_from_str(&displayed), Ok(*rank));
+        }
+    }
+
+    #[test]
+    fn unknown_ranks() {
+        for unknown in &["x+", "s=", "", "SS"] {
+            assert_eq!(Rank::from_str(unknown), Ok(Rank::Unranked), "{}", unknown);
+        }
+        assert_eq!(Rank::try_from_str("x+"), Err(UnknownRank("x+".to_string())));
+        assert!(Rank::try_from_str("").is_err());
+    }
+
+    #[test]
+    fn arithmetic_saturates() {
+        assert_eq!(Rank::SPlus + 1, Rank::SS);
+        assert_eq!(Rank::SPlus - 1, Rank::S);
+        assert_eq!(Rank::X + 1, Rank::X);
+        assert_eq!(Rank::U + 5, Rank::X);
+        assert_eq!(Rank::Unranked - 1, Rank::Unranked);
+        assert_eq!(Rank::D - 5, Rank::Unranked);
+        assert_eq!(Rank::Unranked + 0, Rank::Unranked);
+        assert_eq!(Rank::Unranked + usize::MAX, Rank::X);
+        assert_eq!(Rank::X - usize::MAX, Rank::Unranked);
+
+        assert_eq!(Rank::S.distance(Rank::U), 3);
+        assert_eq!(Rank::U.distance(Rank::S), -3);
+        assert_eq!(Rank::Unranked.distance(Rank::X), Rank::ALL.len() as i32 - 1);
+        for rank in Rank::iter() {
+            assert_eq!(rank.distance(*rank), 0);
+        }
+    }
+
+    #[test]
+    fn strict_parser_rejects_what_the_lenient_one_maps_to_unranked() {
+        assert_eq!(Rank::try_from_str(" S+ "), Ok(Rank::SPlus));
+        assert_eq!(Rank::from_str("S+"), Ok(Rank::Unranked));
+        assert_eq!(Rank::from_str("s+"), Ok(Rank::SPlus));
+
+        assert_eq!(Rank::from_str("s="), Ok(Rank::Unranked));
+        assert_eq!(Rank::try_from_str("s="), Err(UnknownRank("s=".to_string())));
+    }
+}
@@ -0,0 +1,277 @@
+//! Circuit breaker used to stop hammering the Tetrio API while it's down
+//!
+//! After [`CircuitBreaker::failure_threshold`] consecutive failures within a time window,
+//! the circuit opens and every request fails fast until the cooldown has passed.
+//! Afterwards a single probing request is let through (half-open), which either closes the
+//! circuit again or reopens it for another cooldown. A probe that never reports back, for example
+//! because its future was dropped, is given up after [`CircuitBreaker::probe_timeout`].
+//!
+//! The current time is passed into every method, so the state machine can be driven with any clock.
+
+use chrono::{DateTime, Duration, Utc};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// State of a circuit breaker
+pub enum BreakerState {
+    /// Requests go through normally
+    Closed,
+    /// Requests fail fast until the specified time
+    Open {
+        /// When the circuit will be half-open again
+        until: DateTime<Utc>,
+    },
+    /// A single probing request is allowed to go through
+    HalfOpen,
+}
+
+impl std::fmt::Display for BreakerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BreakerState::Closed => f.write_str("closed"),
+            BreakerState::Open { until } => {
+                write!(f, "open until {}", until.format("%Y-%m-%d %H:%M:%S UTC"))
+            }
+            BreakerState::HalfOpen => f.write_str("half-open"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Circuit breaker state machine
+pub struct CircuitBreaker {
+    /// Amount of consecutive failures within the window that open the circuit
+    pub failure_threshold: usize,
+    /// Time window in which failures are counted
+    pub window: Duration,
+    /// How long the circuit stays open
+    pub cooldown: Duration,
+    /// How long a probe may take before another request is let through instead
+    pub probe_timeout: Duration,
+    failures: Vec<DateTime<Utc>>,
+    state: BreakerState,
+    probe_started: Option<DateTime<Utc>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new(5, Duration::minutes(2), Duration::minutes(5))
+    }
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker
+    pub fn new(failure_threshold: usize, window: Duration, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold,
+            window,
+            cooldown,
+            probe_timeout: Duration::minutes(1),
+            failures: Vec::new(),
+            state: BreakerState::Closed,
+            probe_started: None,
+        }
+    }
+
+    /// Current state, without transitioning from open to half-open
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    /// Amount of consecutive failures that are currently counted
+    pub fn failure_count(&self) -> usize {
+        self.failures.len()
+    }
+
+    /// When the probe of a half-open circuit was let through, `None` if there is none
+    pub fn probe_started(&self) -> Option<DateTime<Utc>> {
+        self.probe_started
+    }
+
+    /// Whether a request is allowed to go through
+    ///
+    /// Returns the time until which the circuit is open otherwise.
+    /// Transitions from open to half-open once the cooldown has passed.
+    pub fn check(&mut self, now: DateTime<Utc>) -> Result<(), DateTime<Utc>> {
+        match (self.state, self.probe_started) {
+            (BreakerState::Closed, _) => Ok(()),
+            (BreakerState::Open { until }, _) if now < until => Err(until),
+            (BreakerState::HalfOpen, Some(started)) if now < started + self.probe_timeout => {
+                Err(started + self.probe_timeout)
+            }
+            (BreakerState::Open { .. }, _) | (BreakerState::HalfOpen, _) => {
+                self.state = BreakerState::HalfOpen;
+                self.probe_started = Some(now);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gives up on a probe that won't report back, so the next request can probe instead
+    ///
+    /// `started` is [`CircuitBreaker::probe_started()`] at the time the probe was let through, a
+    /// newer probe is left alone.
+    pub fn cancel_probe(&mut self, started: DateTime<Utc>) {
+        if self.probe_started == Some(started) {
+            self.probe_started = None;
+        }
+    }
+
+    /// Records a request that reached the API, closing the circuit
+    pub fn record_success(&mut self, _now: DateTime<Utc>) {
+        self.failures.clear();
+        self.probe_started = None;
+        self.state = BreakerState::Closed;
+    }
+
+    /// Records a failed request, possibly opening the circuit
+    pub fn record_failure(&mut self, now: DateTime<Utc>) {
+        self.probe_started = None;
+
+        if self.state == BreakerState::HalfOpen {
+            self.state = BreakerState::Open {
+                until: now + self.cooldown,
+            };
+            return;
+        }
+
+        let window = self.window;
+        self.failures.retain(|ts| now - *ts <= window);
+        self.failures.push(now);
+
+        if self.failures.len() >= self.failure_threshold {
+            self.state = BreakerState::Open {
+                until: now + self.cooldown,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    // Opened by three failures a second apart, with a one minute cooldown
+    fn opened() -> (CircuitBreaker, DateTime<Utc>) {
+        let mut breaker = CircuitBreaker::new(3, Duration::minutes(2), Duration::minutes(1));
+        for i in 0..3 {
+            assert!(breaker.check(start() + Duration::seconds(i)).is_ok());
+            breaker.record_failure(start() + Duration::seconds(i));
+        }
+        let until = start() + Duration::seconds(2) + Duration::minutes(1);
+        assert_eq!(breaker.state(), BreakerState::Open { until });
+        (breaker, until)
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new(3, Duration::minutes(2), Duration::minutes(1));
+        breaker.record_failure(start());
+        breaker.record_failure(start());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.failure_count(), 2);
+
+        // a success in between starts the count over
+        breaker.record_success(start());
+        assert_eq!(breaker.failure_count(), 0);
+        breaker.record_failure(start());
+        breaker.record_failure(start());
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record_failure(start());
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[test]
+    fn failures_outside_the_window_are_forgotten() {
+        let mut breaker = CircuitBreaker::new(3, Duration::minutes(2), Duration::minutes(1));
+        breaker.record_failure(start());
+        breaker.record_failure(start());
+        breaker.record_failure(start() + Duration::minutes(3));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.failure_count(), 1);
+    }
+
+    #[test]
+    fn open_fails_fast_until_the_cooldown_passed() {
+        let (mut breaker, until) = opened();
+        assert_eq!(breaker.check(until - Duration::seconds(1)), Err(until));
+        assert_eq!(breaker.state(), BreakerState::Open { until });
+
+        assert_eq!(breaker.check(until), Ok(()));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert_eq!(breaker.probe_started(), Some(until));
+    }
+
+    #[test]
+    fn half_open_lets_a_single_probe_through() {
+        let (mut breaker, until) = opened();
+        assert!(breaker.check(until).is_ok());
+        assert_eq!(
+            breaker.check(until + Duration::seconds(1)),
+            Err(until + breaker.probe_timeout)
+        );
+    }
+
+    #[test]
+    fn successful_probe_closes() {
+        let (mut breaker, until) = opened();
+        assert!(breaker.check(until).is_ok());
+        breaker.record_success(until + Duration::seconds(1));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.failure_count(), 0);
+        assert_eq!(breaker.probe_started(), None);
+        assert!(breaker.check(until + Duration::seconds(2)).is_ok());
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let (mut breaker, until) = opened();
+        assert!(breaker.check(until).is_ok());
+        let failed_at = until + Duration::seconds(1);
+        breaker.record_failure(failed_at);
+        let reopened_until = failed_at + breaker.cooldown;
+        assert_eq!(
+            breaker.state(),
+            BreakerState::Open {
+                until: reopened_until
+            }
+        );
+        assert_eq!(breaker.check(failed_at), Err(reopened_until));
+    }
+
+    #[test]
+    fn cancelled_probe_lets_the_next_request_probe() {
+        let (mut breaker, until) = opened();
+        assert!(breaker.check(until).is_ok());
+        breaker.cancel_probe(until);
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+
+        let next = until + Duration::seconds(1);
+        assert!(breaker.check(next).is_ok());
+        assert_eq!(breaker.probe_started(), Some(next));
+
+        // a late cancel of the first probe doesn't release the second one
+        breaker.cancel_probe(until);
+        assert_eq!(breaker.probe_started(), Some(next));
+        assert!(breaker.check(next + Duration::seconds(1)).is_err());
+    }
+
+    #[test]
+    fn stuck_probe_is_given_up_after_the_timeout() {
+        let (mut breaker, until) = opened();
+        assert!(breaker.check(until).is_ok());
+        let deadline = until + breaker.probe_timeout;
+        assert_eq!(
+            breaker.check(deadline - Duration::seconds(1)),
+            Err(deadline)
+        );
+
+        assert!(breaker.check(deadline).is_ok());
+        assert_eq!(breaker.probe_started(), Some(deadline));
+    }
+}
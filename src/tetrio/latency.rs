@@ -0,0 +1,405 @@
+//! Latency, outcome and cache status of requests to the Tetrio API, per endpoint family
+//!
+//! Latencies go into a histogram with fixed bucket boundaries, see [`BUCKETS_MS`], so recording is
+//! a single increment and quantiles are read as the upper bound of the bucket they fall into.
+//! Every family keeps a ring of per-minute windows for the last hour, plus cumulative totals since
+//! startup.
+//!
+//! The current time is passed into every method, like in [`super::breaker`].
+
+use chrono::{DateTime, Utc};
+
+/// Upper bounds of the latency buckets in milliseconds, slower requests go into an overflow bucket
+pub const BUCKETS_MS: [u64; 11] = [50, 100, 200, 350, 500, 750, 1000, 1500, 2500, 5000, 10000];
+
+/// Amount of per-minute windows, one hour
+const WINDOWS: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Group of endpoints that behave alike
+pub enum Family {
+    /// League leaderboard
+    Leaderboard,
+    /// Single users
+    User,
+    /// News streams
+    News,
+    /// Anything else
+    Other,
+}
+
+impl Family {
+    /// Every family, in the order they are reported
+    pub const ALL: [Family; 4] = [
+        Family::Leaderboard,
+        Family::User,
+        Family::News,
+        Family::Other,
+    ];
+
+    /// Family of an endpoint, relative to the base URL
+    pub fn of_endpoint(endpoint: &str) -> Family {
+        if endpoint.starts_with("users/lists/") {
+            Family::Leaderboard
+        } else if endpoint.starts_with("users/") {
+            Family::User
+        } else if endpoint.starts_with("news") {
+            Family::News
+        } else {
+            Family::Other
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Family::Leaderboard => 0,
+            Family::User => 1,
+            Family::News => 2,
+            Family::Other => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for Family {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Family::Leaderboard => "Leaderboard",
+            Family::User => "User",
+            Family::News => "News",
+            Family::Other => "Other",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a request ended
+pub enum Outcome {
+    /// The API returned data
+    Success,
+    /// The API answered with an error, like a user that doesn't exist
+    ApiError,
+    /// The request didn't get a usable answer
+    NetworkError,
+    /// The API answered with 429 Too Many Requests
+    RateLimited,
+}
+
+impl Outcome {
+    fn index(&self) -> usize {
+        match self {
+            Outcome::Success => 0,
+            Outcome::ApiError => 1,
+            Outcome::NetworkError => 2,
+            Outcome::RateLimited => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Cache status the API reported for a successful request, see [`super::CacheData::status`]
+pub enum CacheStatus {
+    /// Served from the cache
+    Hit,
+    /// Not cached yet
+    Miss,
+    /// Someone else was already requesting the same resource
+    Awaited,
+}
+
+impl CacheStatus {
+    /// Parses the status string of the API, `None` for anything unknown
+    pub fn parse(status: &str) -> Option<CacheStatus> {
+        match status {
+            "hit" => Some(CacheStatus::Hit),
+            "miss" => Some(CacheStatus::Miss),
+            "awaited" => Some(CacheStatus::Awaited),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            CacheStatus::Hit => 0,
+            CacheStatus::Miss => 1,
+            CacheStatus::Awaited => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Latency histogram with the buckets from [`BUCKETS_MS`]
+pub struct Histogram {
+    // One count per bucket, plus the overflow bucket
+    counts: [u64; 12],
+}
+
+impl Histogram {
+    /// Adds a latency
+    pub fn record(&mut self, millis: u64) {
+        let bucket = BUCKETS_MS
+            .iter()
+            .position(|bound| millis <= *bound)
+            .unwrap_or(BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// Adds every latency of another histogram
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other;
+        }
+    }
+
+    /// Amount of recorded latencies
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Upper bound of the bucket the quantile (`0.0` to `1.0`) falls into
+    ///
+    /// `None` if nothing was recorded, `Some(u64::MAX)` if it falls into the overflow bucket.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(BUCKETS_MS.get(bucket).copied().unwrap_or(u64::MAX));
+            }
+        }
+        Some(u64::MAX)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Everything recorded for a family in some time span
+pub struct Stats {
+    /// Latencies of every request, regardless of the outcome
+    pub latency: Histogram,
+    /// Requests per [`Outcome`], in declaration order
+    pub outcomes: [u64; 4],
+    /// Successful requests per [`CacheStatus`], in declaration order
+    pub cache: [u64; 3],
+}
+
+impl Stats {
+    fn record(&mut self, millis: u64, outcome: Outcome, cache: Option<CacheStatus>) {
+        self.latency.record(millis);
+        self.outcomes[outcome.index()] += 1;
+        if let Some(cache) = cache {
+            self.cache[cache.index()] += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.latency.merge(&other.latency);
+        for (count, other) in self.outcomes.iter_mut().zip(other.outcomes.iter()) {
+            *count += other;
+        }
+        for (count, other) in self.cache.iter_mut().zip(other.cache.iter()) {
+            *count += other;
+        }
+    }
+
+    /// Amount of requests
+    pub fn requests(&self) -> u64 {
+        self.outcomes.iter().sum()
+    }
+
+    /// Amount of requests with an outcome
+    pub fn outcome(&self, outcome: Outcome) -> u64 {
+        self.outcomes[outcome.index()]
+    }
+
+    /// Share of a cache status among the successful requests with a known status, `0.0` if there are none
+    pub fn cache_ratio(&self, status: CacheStatus) -> f64 {
+        let total: u64 = self.cache.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        self.cache[status.index()] as f64 / total as f64
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct FamilyStats {
+    // Ring of windows, indexed by minute modulo the amount of windows, along with their minute
+    windows: Vec<(i64, Stats)>,
+    cumulative: Stats,
+}
+
+#[derive(Debug, Clone)]
+/// Recorded requests of every family
+pub struct LatencyRegistry {
+    families: Vec<FamilyStats>,
+}
+
+impl Default for LatencyRegistry {
+    fn default() -> Self {
+        LatencyRegistry {
+            families: vec![
+                FamilyStats {
+                    windows: vec![(i64::MIN, Stats::default()); WINDOWS],
+                    cumulative: Stats::default(),
+                };
+                Family::ALL.len()
+            ],
+        }
+    }
+}
+
+impl LatencyRegistry {
+    /// Records a single request
+    ///
+    /// `cache` should only be set for successful requests.
+    pub fn record(
+        &mut self,
+        family: Family,
+        millis: u64,
+        outcome: Outcome,
+        cache: Option<CacheStatus>,
+        now: DateTime<Utc>,
+    ) {
+        let minute = now.timestamp().div_euclid(60);
+        let stats = &mut self.families[family.index()];
+        let window = &mut stats.windows[minute.rem_euclid(WINDOWS as i64) as usize];
+        if window.0 != minute {
+            *window = (minute, Stats::default());
+        }
+        window.1.record(millis, outcome, cache);
+        stats.cumulative.record(millis, outcome, cache);
+    }
+
+    /// Requests of a family within the last hour, including the current minute
+    pub fn last_hour(&self, family: Family, now: DateTime<Utc>) -> Stats {
+        let minute = now.timestamp().div_euclid(60);
+        let mut stats = Stats::default();
+        for (window_minute, window) in &self.families[family.index()].windows {
+            if *window_minute > minute - WINDOWS as i64 && *window_minute <= minute {
+                stats.merge(window);
+            }
+        }
+        stats
+    }
+
+    /// Requests of a family since startup
+    pub fn cumulative(&self, family: Family) -> &Stats {
+        &self.families[family.index()].cumulative
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn start() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 14).and_hms(18, 0, 0)
+    }
+
+    fn record(registry: &mut LatencyRegistry, at: DateTime<Utc>) {
+        registry.record(Family::User, 100, Outcome::Success, None, at);
+    }
+
+    #[test]
+    fn bounds_belong_to_their_bucket() {
+        for (bucket, bound) in BUCKETS_MS.iter().enumerate() {
+            let mut histogram = Histogram::default();
+            histogram.record(*bound);
+            assert_eq!(histogram.counts[bucket], 1, "{} ms", bound);
+
+            let mut histogram = Histogram::default();
+            histogram.record(bound + 1);
+            assert_eq!(histogram.counts[bucket + 1], 1, "{} ms", bound + 1);
+        }
+
+        let mut histogram = Histogram::default();
+        histogram.record(0);
+        assert_eq!(histogram.counts[0], 1);
+        histogram.record(u64::MAX);
+        assert_eq!(histogram.counts[BUCKETS_MS.len()], 1);
+    }
+
+    #[test]
+    fn quantiles_are_bucket_bounds() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for millis in &[10, 60, 60, 400, 20000] {
+            histogram.record(*millis);
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.quantile(0.0), Some(50));
+        assert_eq!(histogram.quantile(0.2), Some(50));
+        assert_eq!(histogram.quantile(0.5), Some(100));
+        assert_eq!(histogram.quantile(0.8), Some(500));
+        assert_eq!(histogram.quantile(0.99), Some(u64::MAX));
+        assert_eq!(histogram.quantile(2.0), Some(u64::MAX));
+    }
+
+    #[test]
+    fn last_hour_includes_the_current_minute_and_the_59_before() {
+        let mut registry = LatencyRegistry::default();
+        record(&mut registry, start());
+        record(&mut registry, start() + Duration::seconds(59));
+        record(&mut registry, start() + Duration::minutes(30));
+
+        let at = |minutes| start() + Duration::minutes(minutes);
+        assert_eq!(registry.last_hour(Family::User, at(30)).requests(), 3);
+        assert_eq!(registry.last_hour(Family::User, at(59)).requests(), 3);
+        assert_eq!(registry.last_hour(Family::User, at(60)).requests(), 1);
+        assert_eq!(registry.last_hour(Family::User, at(90)).requests(), 0);
+
+        // windows in the future of the given time don't count either
+        assert_eq!(registry.last_hour(Family::User, at(10)).requests(), 2);
+        assert_eq!(registry.last_hour(Family::News, at(30)).requests(), 0);
+    }
+
+    #[test]
+    fn ring_reuses_windows_after_an_hour() {
+        let mut registry = LatencyRegistry::default();
+        record(&mut registry, start());
+        record(&mut registry, start());
+
+        // same slot an hour later, the old window is replaced instead of added to
+        let later = start() + Duration::minutes(WINDOWS as i64);
+        record(&mut registry, later);
+        assert_eq!(registry.last_hour(Family::User, later).requests(), 1);
+
+        // everything still counts since startup
+        assert_eq!(registry.cumulative(Family::User).requests(), 3);
+    }
+
+    #[test]
+    fn outcomes_and_cache_statuses_are_counted() {
+        let mut registry = LatencyRegistry::default();
+        let family = Family::of_endpoint("users/lists/league/all");
+        assert_eq!(family, Family::Leaderboard);
+        registry.record(
+            family,
+            80,
+            Outcome::Success,
+            Some(CacheStatus::Hit),
+            start(),
+        );
+        registry.record(
+            family,
+            80,
+            Outcome::Success,
+            Some(CacheStatus::Miss),
+            start(),
+        );
+        registry.record(family, 80, Outcome::RateLimited, None, start());
+
+        let stats = registry.last_hour(family, start());
+        assert_eq!(stats.requests(), 3);
+        assert_eq!(stats.outcome(Outcome::Success), 2);
+        assert_eq!(stats.outcome(Outcome::RateLimited), 1);
+        assert!((stats.cache_ratio(CacheStatus::Hit) - 0.5).abs() < f64::EPSILON);
+        assert!(stats.cache_ratio(CacheStatus::Awaited).abs() < f64::EPSILON);
+    }
+}
@@ -2,13 +2,20 @@
 //!
 //! This represents the endpoint as defined in the [Tetrio API](https://tetr.io/about/api/#userlistsleagueall)
 
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::tetrio::TetrioResponse;
+use crate::tetrio::{CacheData, SuccessfulResponse, TetrioResponse};
 
 /// Endpoint url, relative to the base URL
 const ENDPOINT: &str = "users/lists/league/all";
 
+/// Where [`request_cached()`] keeps the last leaderboard, relative to the working directory
+pub const CACHE_PATH: &str = "./cache/leaderboard.json";
+
 #[allow(missing_docs)]
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// This user's current TETRA LEAGUE standing
@@ -58,11 +65,78 @@ pub struct LeaderboardData {
 /// ```
 /// use uc_helper_rust::tetrio;
 ///
-/// let leaderboard = tetrio::leaderboard::request()?;
+/// let leaderboard = tetrio::leaderboard::request().await?;
 /// for user in leaderboard.users {
 ///     println!("{}", user.username);
 /// }
 /// ```
-pub fn request() -> TetrioResponse<LeaderboardData> {
-    crate::tetrio::request::<LeaderboardData>(ENDPOINT)
+pub async fn request() -> TetrioResponse<LeaderboardData> {
+    crate::tetrio::request::<LeaderboardData>(ENDPOINT).await
+}
+
+#[derive(Deserialize, Serialize)]
+/// A leaderboard response as it's written to [`CACHE_PATH`]
+struct CachedLeaderboard {
+    fetched_at: DateTime<Utc>,
+    cache: CacheData,
+    data: LeaderboardData,
+}
+
+/// Same as [`request()`], but reuses the leaderboard from the last call if it's younger than `max_age`
+///
+/// The leaderboard is a few MB and takes a while to download, so it's kept on disk at [`CACHE_PATH`]
+/// and survives restarts. A missing or unreadable cache file only means requesting again, failing
+/// to write it is logged and otherwise ignored.
+pub async fn request_cached(max_age: Duration) -> TetrioResponse<LeaderboardData> {
+    if let Some(cached) = read_cache(Path::new(CACHE_PATH)) {
+        let age = Utc::now() - cached.fetched_at;
+        if age < max_age {
+            tracing::info!(
+                "Using the cached leaderboard from {} minutes ago",
+                age.num_minutes()
+            );
+            return Ok(SuccessfulResponse {
+                data: cached.data,
+                cache: cached.cache,
+            });
+        }
+    }
+
+    let response = request().await?;
+    let cached = CachedLeaderboard {
+        fetched_at: Utc::now(),
+        cache: response.cache,
+        data: response.data,
+    };
+    if let Err(err) = write_cache(Path::new(CACHE_PATH), &cached) {
+        tracing::warn!("Could not write the leaderboard cache: {}", err);
+    }
+    Ok(SuccessfulResponse {
+        data: cached.data,
+        cache: cached.cache,
+    })
+}
+
+/// Reads the cached leaderboard, `None` if there is none or it can't be parsed
+fn read_cache(path: &Path) -> Option<CachedLeaderboard> {
+    let file = std::fs::File::open(path).ok()?;
+    match serde_json::from_reader(std::io::BufReader::new(file)) {
+        Ok(cached) => Some(cached),
+        Err(err) => {
+            tracing::warn!("Ignoring unreadable leaderboard cache: {}", err);
+            None
+        }
+    }
+}
+
+/// Writes the leaderboard cache, through a temporary file so a crash never leaves half a file behind
+fn write_cache(path: &Path, cached: &CachedLeaderboard) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("json.tmp");
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&temporary)?);
+    serde_json::to_writer(&mut writer, cached)?;
+    writer.flush()?;
+    std::fs::rename(&temporary, path)
 }
@@ -5,7 +5,9 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::tetrio::TetrioResponse;
+use std::str::FromStr;
+
+use crate::tetrio::{Rank, TetrioResponse};
 
 /// Endpoint url, relative to the base URL
 const ENDPOINT: &str = "news";
@@ -21,6 +23,58 @@ pub struct NewsPost {
     pub ts: String,
 }
 
+impl NewsPost {
+    /// Rank-up described by the post, `None` for other kinds of posts
+    pub fn rankup(&self) -> Option<Rankup> {
+        if self.post_type != "rankup" {
+            return None;
+        }
+        Some(Rankup {
+            username: self.data.get("username")?.as_str()?.to_string(),
+            rank: Rank::from_str(self.data.get("rank")?.as_str()?).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A player reaching a new rank for the first time
+pub struct Rankup {
+    /// Username at the time of the rank-up
+    pub username: String,
+    /// Reached rank
+    pub rank: Rank,
+}
+
+/// Posts that are newer than the last processed one, oldest first
+///
+/// Post IDs are MongoDB object IDs, which start with their creation time, so newer posts have
+/// greater IDs.
+///
+/// ```
+/// use serde_json::json;
+/// use uc_helper_rust::tetrio::news::{posts_after, NewsPost};
+///
+/// let post = |id: &str| -> NewsPost {
+///     serde_json::from_value(json!({
+///         "_id": id, "stream": "global", "type": "rankup",
+///         "data": {"username": "caboozled_pie", "rank": "s"}, "ts": "2021-03-14T18:30:00.000Z"
+///     }))
+///     .unwrap()
+/// };
+/// let posts = vec![post("604e5a7a0000000000000003"), post("604e5a7a0000000000000002"), post("604e5a7a0000000000000001")];
+/// let new: Vec<&str> = posts_after(&posts, "604e5a7a0000000000000001").iter().map(|p| p._id.as_str()).collect();
+/// assert_eq!(new, vec!["604e5a7a0000000000000002", "604e5a7a0000000000000003"]);
+/// assert_eq!(posts[0].rankup().unwrap().username, "caboozled_pie");
+/// ```
+pub fn posts_after<'a>(posts: &'a [NewsPost], last_id: &str) -> Vec<&'a NewsPost> {
+    let mut new: Vec<&NewsPost> = posts
+        .iter()
+        .filter(|post| post._id.as_str() > last_id)
+        .collect();
+    new.sort_by(|a, b| a._id.cmp(&b._id));
+    new
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 /// Data structure of response data
 pub struct NewsData {
@@ -34,11 +88,11 @@ pub struct NewsData {
 /// ```
 /// use uc_helper_rust::tetrio;
 ///
-/// let leaderboard = tetrio::leaderboard::request()?;
+/// let leaderboard = tetrio::leaderboard::request().await?;
 /// for user in leaderboard.users {
 ///     println!("{}", user.username);
 /// }
 /// ```
-pub fn request(stream: &str) -> TetrioResponse<NewsData> {
-    crate::tetrio::request::<NewsData>(&format!("{}/{}", ENDPOINT, stream))
+pub async fn request(stream: &str) -> TetrioResponse<NewsData> {
+    crate::tetrio::request::<NewsData>(&format!("{}/{}", ENDPOINT, stream)).await
 }
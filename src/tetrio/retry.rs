@@ -0,0 +1,311 @@
+//! Retrying failed requests and spacing out requests to the Tetrio API
+//!
+//! Timeouts, server errors and rate limits are usually gone after a short wait, so a request is
+//! attempted up to [`RetryPolicy::max_attempts`] times, waiting exponentially longer in between.
+//! A 429 response can say how long to wait, which is used instead if it's longer.
+//!
+//! Independently of retries, [`RateLimiter`] keeps a minimum delay between consecutive requests, so
+//! bulk updates don't run into the rate limit in the first place.
+//!
+//! The current time is passed into every method, like in [`super::breaker`].
+
+use std::time::{Duration, Instant};
+
+/// Environment variable for the amount of attempts per request
+pub const MAX_ATTEMPTS_ENV: &str = "TETRIO_MAX_ATTEMPTS";
+
+/// Environment variable for the minimum delay between requests in milliseconds
+pub const MIN_INTERVAL_ENV: &str = "TETRIO_MIN_INTERVAL_MS";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How often and how long to wait before retrying a request
+pub struct RetryPolicy {
+    /// Amount of attempts, including the first one
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled for every following attempt
+    pub base_delay: Duration,
+    /// Longest wait between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Default policy with the amount of attempts taken from the environment, if set
+    pub fn from_env() -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            max_attempts: std::env::var(MAX_ATTEMPTS_ENV)
+                .ok()
+                .and_then(|attempts| attempts.parse::<u32>().ok())
+                .map_or(default.max_attempts, |attempts| attempts.max(1)),
+            ..default
+        }
+    }
+
+    /// Whether another attempt is allowed after `attempt` attempts failed
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// How long to wait after the `attempt`th attempt failed (starting at 1)
+    ///
+    /// `retry_after` is the wait the API asked for, it's used if it's longer than the backoff.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use uc_helper_rust::tetrio::retry::RetryPolicy;
+    ///
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.delay(1, None), Duration::from_millis(500));
+    /// assert_eq!(policy.delay(2, None), Duration::from_secs(1));
+    /// assert_eq!(policy.delay(30, None), policy.max_delay);
+    /// assert_eq!(policy.delay(1, Some(Duration::from_secs(3))), Duration::from_secs(3));
+    /// ```
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        let backoff = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        retry_after.map_or(backoff, |wait| wait.max(backoff))
+    }
+}
+
+/// Whether a response status is worth another attempt, which are rate limits and server errors
+pub fn is_transient_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// Parses the `Retry-After` header of a 429 response, only the amount of seconds is supported
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[derive(Debug, Clone)]
+/// Keeps a minimum delay between consecutive requests
+pub struct RateLimiter {
+    /// Minimum delay between the start of two requests
+    pub min_interval: Duration,
+    next_slot: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter that lets the first request through right away
+    pub fn new(min_interval: Duration) -> RateLimiter {
+        RateLimiter {
+            min_interval,
+            next_slot: None,
+        }
+    }
+
+    /// Rate limiter with the minimum delay taken from the environment, 250ms if not set
+    pub fn from_env() -> RateLimiter {
+        let millis = std::env::var(MIN_INTERVAL_ENV)
+            .ok()
+            .and_then(|millis| millis.parse::<u64>().ok())
+            .unwrap_or(250);
+        RateLimiter::new(Duration::from_millis(millis))
+    }
+
+    /// Reserves the next free slot and returns how long to wait for it
+    ///
+    /// The slot is taken right away, so callers can wait without holding on to the rate limiter.
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use uc_helper_rust::tetrio::retry::RateLimiter;
+    ///
+    /// let mut limiter = RateLimiter::new(Duration::from_millis(100));
+    /// let now = Instant::now();
+    /// assert_eq!(limiter.reserve(now), Duration::from_millis(0));
+    /// assert_eq!(limiter.reserve(now), Duration::from_millis(100));
+    /// assert_eq!(limiter.reserve(now), Duration::from_millis(200));
+    /// assert_eq!(limiter.reserve(now + Duration::from_secs(1)), Duration::from_millis(0));
+    /// ```
+    pub fn reserve(&mut self, now: Instant) -> Duration {
+        let slot = match self.next_slot {
+            Some(next) if next > now => next,
+            _ => now,
+        };
+        self.next_slot = Some(slot + self.min_interval);
+        slot - now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::{request_from, TetrioApiError, RETRY_POLICY};
+    use serde_json::Value;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const OK_BODY: &str = r#"{"success":true,"cache":{"status":"hit","cached_at":0,"cached_until":0},"data":{"value":1}}"#;
+
+    /// Serves one canned response (status line and extra headers) per connection, in order, and
+    /// counts the requests. Connections after the last response are refused.
+    fn mock_server(responses: Vec<(&'static str, &'static str)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/api", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            for (status, headers) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = if status.starts_with("200") {
+                    OK_BODY
+                } else {
+                    ""
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (base_url, hits)
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried() {
+        let (base_url, hits) = mock_server(vec![("503 Service Unavailable", ""), ("200 OK", "")]);
+        let response = request_from::<Value>(&base_url, "users/icedynamix")
+            .await
+            .unwrap();
+        assert_eq!(response.data["value"], 1);
+        assert_eq!(response.cache.status, "hit");
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_retried() {
+        let (base_url, hits) = mock_server(vec![
+            ("429 Too Many Requests", "Retry-After: 1\r\n"),
+            ("200 OK", ""),
+        ]);
+        let response = request_from::<Value>(&base_url, "users/icedynamix")
+            .await
+            .unwrap();
+        assert_eq!(response.data["value"], 1);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let attempts = RETRY_POLICY.max_attempts as usize;
+        let (base_url, hits) = mock_server(vec![("503 Service Unavailable", ""); attempts + 1]);
+        match request_from::<Value>(&base_url, "users/icedynamix").await {
+            Err(TetrioApiError::Upstream(message)) => {
+                assert!(message.contains("503"), "{}", message)
+            }
+            other => panic!(
+                "expected an upstream error, got {:?}",
+                other.map(|r| r.data)
+            ),
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), attempts);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max_delay() {
+        let policy = RetryPolicy::default();
+        let mut previous = Duration::from_millis(0);
+        for attempt in 1..=40 {
+            let delay = policy.delay(attempt, None);
+            assert!(delay >= policy.base_delay, "attempt {}", attempt);
+            assert!(delay <= policy.max_delay, "attempt {}", attempt);
+            assert!(delay >= previous, "attempt {}", attempt);
+            if delay < policy.max_delay {
+                assert_eq!(delay, policy.base_delay * (1 << (attempt - 1)));
+            }
+            previous = delay;
+        }
+        assert_eq!(policy.delay(0, None), policy.base_delay);
+        assert_eq!(policy.delay(u32::MAX, None), policy.max_delay);
+    }
+
+    #[test]
+    fn retry_after_is_used_if_longer() {
+        let policy = RetryPolicy::default();
+        let short = Duration::from_millis(100);
+        assert_eq!(policy.delay(1, Some(short)), policy.base_delay);
+        let long = Duration::from_secs(30);
+        assert_eq!(policy.delay(1, Some(long)), long);
+        assert_eq!(policy.delay(20, Some(long)), long);
+    }
+
+    #[test]
+    fn attempts_are_limited() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.should_retry(1));
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+
+        let once = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        assert!(!once.should_retry(1));
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_retried() {
+        for status in &[429, 500, 502, 503, 504, 599] {
+            assert!(is_transient_status(*status), "{}", status);
+        }
+        for status in &[200, 301, 400, 401, 403, 404, 428, 430, 600] {
+            assert!(!is_transient_status(*status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn retry_after_seconds() {
+        assert_eq!(parse_retry_after("5"), Some(Duration::from_secs(5)));
+        assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+    }
+
+    #[test]
+    fn rate_limiter_spaces_out_requests() {
+        let mut limiter = RateLimiter::new(Duration::from_millis(250));
+        let now = Instant::now();
+        assert_eq!(limiter.reserve(now), Duration::from_millis(0));
+        assert_eq!(
+            limiter.reserve(now + Duration::from_millis(100)),
+            Duration::from_millis(150)
+        );
+        assert_eq!(
+            limiter.reserve(now + Duration::from_millis(600)),
+            Duration::from_millis(0)
+        );
+    }
+}
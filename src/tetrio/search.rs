@@ -0,0 +1,50 @@
+//! Tetrio API user search endpoint
+//!
+//! Undocumented as of now. Resolves a Discord ID to the Tetrio account that connected it in the
+//! Tetr.io settings, which most players never do.
+
+use serde::{Deserialize, Serialize};
+
+use crate::tetrio::TetrioResponse;
+
+/// Endpoint url, relative to the base URL
+const ENDPOINT: &str = "users/search";
+
+#[derive(Deserialize, Serialize, Debug)]
+/// Data structure of response data
+pub struct UserData {
+    /// Account that connected the Discord account
+    pub user: SearchedUser,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// The few fields the search endpoint returns about a user
+pub struct SearchedUser {
+    /// Tetrio ID
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// Current username
+    pub username: String,
+}
+
+/// Searches the Tetrio account that connected a Discord account
+///
+/// The data is `None` if no account connected it, which isn't an error.
+///
+/// # Example
+/// ```no_run
+/// use uc_helper_rust::tetrio;
+///
+/// # async fn search() {
+/// match tetrio::search::request(287102784954695680).await {
+///     Ok(response) => match response.data {
+///         Some(found) => println!("{}", found.user.username),
+///         None => println!("Nobody connected that Discord account"),
+///     },
+///     Err(e) => println!("{}", e),
+/// }
+/// # }
+/// ```
+pub async fn request(discord_id: u64) -> TetrioResponse<Option<UserData>> {
+    crate::tetrio::request_optional::<UserData>(&format!("{}/{}", ENDPOINT, discord_id)).await
+}
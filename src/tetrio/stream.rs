@@ -0,0 +1,150 @@
+//! Tetrio API streams endpoint
+//!
+//! Only the recent Tetra League matches of a player (`league_userrecent_<id>`) are used. Records
+//! contain a lot more than needed, with one entry per player in `endcontext`, so only the fields of
+//! [`RecentMatch`] are parsed and everything else is ignored.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::tetrio::TetrioResponse;
+
+/// Endpoint url, relative to the base URL
+const ENDPOINT: &str = "streams";
+
+#[derive(Deserialize, Serialize, Debug)]
+/// Data structure of response data
+///
+/// Records are kept as raw JSON, so a single malformed record doesn't fail the whole response,
+/// see [`recent_matches()`].
+pub struct StreamData {
+    /// Records of the stream, newest first
+    pub records: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A finished Tetra League match, from the perspective of one player
+pub struct RecentMatch {
+    /// Username of the opponent at the time of the match
+    pub opponent: String,
+    /// Rounds the player won
+    pub rounds_won: u32,
+    /// Rounds the opponent won
+    pub rounds_lost: u32,
+    /// When the match ended
+    pub played_at: DateTime<Utc>,
+}
+
+impl RecentMatch {
+    /// Whether the player won the match
+    pub fn won(&self) -> bool {
+        self.rounds_won > self.rounds_lost
+    }
+}
+
+#[derive(Deserialize)]
+struct Record {
+    ts: DateTime<Utc>,
+    endcontext: Vec<EndContext>,
+}
+
+#[derive(Deserialize)]
+struct EndContext {
+    user: RecordUser,
+    wins: u32,
+}
+
+#[derive(Deserialize)]
+struct RecordUser {
+    #[serde(rename = "_id")]
+    id: String,
+    username: String,
+}
+
+/// Parses a single record of `league_userrecent_<user_id>`, with the reason if it's malformed
+fn parse_match(record: &Value, user_id: &str) -> Result<RecentMatch, String> {
+    let record = Record::deserialize(record).map_err(|e| e.to_string())?;
+    let (own, other): (Vec<EndContext>, Vec<EndContext>) = record
+        .endcontext
+        .into_iter()
+        .partition(|context| context.user.id == user_id);
+
+    match (own.as_slice(), other.as_slice()) {
+        ([own], [opponent]) => Ok(RecentMatch {
+            opponent: opponent.user.username.clone(),
+            rounds_won: own.wins,
+            rounds_lost: opponent.wins,
+            played_at: record.ts,
+        }),
+        _ => Err(format!(
+            "expected the user and one opponent, found {} and {} entries",
+            own.len(),
+            other.len()
+        )),
+    }
+}
+
+/// Up to `limit` matches of a player from stream records, newest first
+///
+/// Malformed records are skipped with a warning instead of failing the whole list.
+///
+/// ```
+/// use serde_json::json;
+/// use uc_helper_rust::tetrio::stream::recent_matches;
+///
+/// let player = |id: &str, username: &str, wins: u32| {
+///     json!({"user": {"_id": id, "username": username}, "wins": wins, "handling": {"das": 8}, "points": {"primary": wins}})
+/// };
+/// let records = vec![
+///     json!({"_id": "a", "ts": "2021-03-14T18:30:00.000Z", "ismulti": true,
+///            "endcontext": [player("me", "icedynamix", 7), player("them", "caboozled_pie", 4)]}),
+///     json!({"_id": "b", "ts": "2021-03-14T18:20:00.000Z", "endcontext": "not a list"}),
+///     json!({"_id": "c", "ts": "2021-03-14T18:10:00.000Z",
+///            "endcontext": [player("them", "caboozled_pie", 7), player("me", "icedynamix", 2)]}),
+/// ];
+///
+/// let matches = recent_matches(&records, "me", 5);
+/// assert_eq!(matches.len(), 2);
+/// assert_eq!(matches[0].opponent, "caboozled_pie");
+/// assert!(matches[0].won());
+/// assert_eq!((matches[1].rounds_won, matches[1].rounds_lost), (2, 7));
+/// assert!(!matches[1].won());
+/// assert_eq!(recent_matches(&records, "me", 1).len(), 1);
+/// ```
+pub fn recent_matches(records: &[Value], user_id: &str, limit: usize) -> Vec<RecentMatch> {
+    records
+        .iter()
+        .filter_map(|record| match parse_match(record, user_id) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                tracing::warn!(
+                    "Skipping malformed stream record {} of {}: {}",
+                    record.get("_id").and_then(Value::as_str).unwrap_or("?"),
+                    user_id,
+                    e
+                );
+                None
+            }
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Requests the recent Tetra League matches of a user by their Tetrio ID
+///
+/// # Example
+/// ```no_run
+/// use uc_helper_rust::tetrio;
+///
+/// # async fn recent() {
+/// let response = tetrio::stream::request("5e47696db7c60f23a497ee6c").await.unwrap();
+/// for played in tetrio::stream::recent_matches(&response.data.records, "5e47696db7c60f23a497ee6c", 5) {
+///     println!("{} {}-{}", played.opponent, played.rounds_won, played.rounds_lost);
+/// }
+/// # }
+/// ```
+pub async fn request(user_id: &str) -> TetrioResponse<StreamData> {
+    crate::tetrio::request::<StreamData>(&format!("{}/league_userrecent_{}", ENDPOINT, user_id))
+        .await
+}
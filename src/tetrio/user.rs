@@ -2,37 +2,161 @@
 //!
 //! This represents the endpoint as defined in the [Tetrio API](https://tetr.io/about/api/#usersuser)
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use crate::tetrio::leaderboard::LeaderboardUser;
-use crate::tetrio::TetrioResponse;
+use crate::tetrio::{TetrioApiError, TetrioResponse};
 
 /// Endpoint url, relative to the base URL
 const ENDPOINT: &str = "users";
 
+/// Start of the error message the API returns if a user does not exist
+const NOT_FOUND_ERROR: &str = "No such user";
+
 #[derive(Deserialize, Serialize, Debug)]
 /// Data structure of response data
-///
-/// Uses [`super::leaderboard::LeaderboardUser`], since it's a lighter version of the
-/// regular user struct and the extra information is not necessary.
 pub struct UserData {
     /// Requested user
-    pub user: LeaderboardUser,
+    pub user: User,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+/// User object as returned by the user endpoint
+///
+/// Uses [`super::leaderboard::LeaderboardUser`] for most fields, since it's a lighter version of the
+/// regular user struct and most of the extra information is not necessary.
+pub struct User {
+    /// Fields shared with the leaderboard
+    #[serde(flatten)]
+    pub data: LeaderboardUser,
+    /// When the account was created, missing for accounts from before this was recorded
+    pub ts: Option<DateTime<Utc>>,
+    /// About me text of the profile, used to verify account ownership
+    #[serde(default)]
+    pub bio: Option<String>,
+}
+
+/// Separates "user does not exist" from every other kind of failure
+///
+/// The API returns an unsuccessful response with an error message starting with "No such user" if
+/// the user does not exist (or was deleted), everything else is treated as an upstream error.
+pub fn classify_error(err: TetrioApiError) -> TetrioApiError {
+    match err {
+        TetrioApiError::Upstream(message) if message.starts_with(NOT_FOUND_ERROR) => {
+            TetrioApiError::NotFound
+        }
+        err => err,
+    }
 }
 
 /// Requests data from the user endpoint and parses the data into the approriate struct
 ///
+/// Returns [`TetrioApiError::NotFound`] if the user does not exist.
+///
 /// # Example
-/// ```
+/// ```no_run
 /// use uc_helper_rust::tetrio;
 ///
+/// # async fn fetch() {
 /// let username = "icedynamix";
-/// let user = tetrio::user::request(username);
+/// let user = tetrio::user::request(username).await;
 /// match user {
-///     Ok(user) => { println!("{}", user.username); },
-///     Err(e) => { println("{}", e); } // Most often "not found"
+///     Ok(user) => { println!("{}", user.data.user.data.username); },
+///     Err(e) => { println!("{}", e); } // Most often "not found"
 /// }
+/// # }
 /// ```
-pub fn request(tetrio_id: &str) -> TetrioResponse<UserData> {
+pub async fn request(tetrio_id: &str) -> TetrioResponse<UserData> {
     crate::tetrio::request::<UserData>(&format!("{}/{}", ENDPOINT, tetrio_id))
+        .await
+        .map_err(classify_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetrio::{parse_response, TetrioResponseStruct};
+
+    // What `request()` makes of a response body
+    fn canned(body: &str) -> Result<Option<UserData>, TetrioApiError> {
+        let response: TetrioResponseStruct = serde_json::from_str(body).unwrap();
+        parse_response::<UserData>(response)
+            .map(|response| response.data)
+            .map_err(classify_error)
+    }
+
+    #[test]
+    fn missing_users_are_not_found() {
+        let result = canned(
+            r#"{"success": false, "error": "No such user! | Either you mistyped something, or the account no longer exists."}"#,
+        );
+        assert!(matches!(result, Err(TetrioApiError::NotFound)));
+    }
+
+    #[test]
+    fn other_failures_are_upstream_errors() {
+        let result = canned(r#"{"success": false, "error": "Internal server error"}"#);
+        assert!(
+            matches!(result, Err(TetrioApiError::Upstream(ref message)) if message == "Internal server error")
+        );
+
+        // the message only counts at the start, and without one it's unknown
+        let result = canned(r#"{"success": false, "error": "Oops: No such user"}"#);
+        assert!(matches!(result, Err(TetrioApiError::Upstream(_))));
+        let result = canned(r#"{"success": false}"#);
+        assert!(
+            matches!(result, Err(TetrioApiError::Upstream(ref message)) if message == "Unknown error")
+        );
+    }
+
+    #[test]
+    fn malformed_success_is_an_upstream_error() {
+        let result = canned(
+            r#"{"success": true, "cache": {"status": "hit", "cached_at": 0, "cached_until": 0}, "data": {"user": 5}}"#,
+        );
+        assert!(
+            matches!(result, Err(TetrioApiError::Upstream(ref message)) if message == "Could not parse")
+        );
+        let result = canned(r#"{"success": true, "data": {}}"#);
+        assert!(matches!(result, Err(TetrioApiError::Upstream(_))));
+    }
+
+    #[test]
+    fn existing_users_are_parsed() {
+        let result = canned(
+            r#"{
+                "success": true,
+                "cache": {"status": "miss", "cached_at": 1615744800000, "cached_until": 1615744860000},
+                "data": {"user": {
+                    "_id": "5e47696db7c60f23a497ee6c",
+                    "username": "caboozled_pie",
+                    "role": "user",
+                    "country": "DE",
+                    "supporter": false,
+                    "verified": false,
+                    "league": {"gamesplayed": 10, "gameswon": 5, "rating": 12000.5, "rank": "b+"},
+                    "ts": "2020-02-15T03:00:29.000Z",
+                    "bio": "UC-7KQ2MX4P"
+                }}
+            }"#,
+        );
+        let user = result.unwrap().unwrap().user;
+        assert_eq!(user.data.username, "caboozled_pie");
+        assert_eq!(user.bio.as_deref(), Some("UC-7KQ2MX4P"));
+        assert!(user.ts.is_some());
+    }
+
+    #[test]
+    fn classification_keeps_other_errors() {
+        let until = Utc::now();
+        assert!(matches!(
+            classify_error(TetrioApiError::CircuitOpen { until }),
+            TetrioApiError::CircuitOpen { .. }
+        ));
+        assert!(matches!(
+            classify_error(TetrioApiError::NotFound),
+            TetrioApiError::NotFound
+        ));
+    }
 }
@@ -0,0 +1,351 @@
+//! Rough time zone distribution of players, for picking match times
+//!
+//! Tetrio only tells us a player's country, so every country is mapped to a single typical UTC
+//! offset. This is an approximation:
+//! - Countries spanning several zones use the zone most of their population lives in
+//!   (e.g. the US uses Eastern time, Russia uses Moscow time, Australia uses Sydney time)
+//! - Standard time is used, daylight saving time is ignored
+//! - Half hour offsets (India, Iran, ...) are rounded towards UTC
+//!
+//! Good enough to see where players are clustered, not to tell a specific player's local time.
+//! Add countries to [`OFFSETS`] when they show up as unknown.
+
+#![warn(missing_docs)]
+
+use std::collections::BTreeMap;
+
+/// Typical UTC offset in hours for each country, by ISO 3166-1 alpha-2 code
+pub const OFFSETS: &[(&str, i32)] = &[
+    // Americas
+    ("US", -5),
+    ("CA", -5),
+    ("MX", -6),
+    ("GT", -6),
+    ("SV", -6),
+    ("HN", -6),
+    ("NI", -6),
+    ("CR", -6),
+    ("PA", -5),
+    ("CU", -5),
+    ("DO", -4),
+    ("PR", -4),
+    ("JM", -5),
+    ("CO", -5),
+    ("EC", -5),
+    ("PE", -5),
+    ("VE", -4),
+    ("BO", -4),
+    ("CL", -4),
+    ("PY", -4),
+    ("BR", -3),
+    ("AR", -3),
+    ("UY", -3),
+    // Europe
+    ("IS", 0),
+    ("IE", 0),
+    ("GB", 0),
+    ("PT", 0),
+    ("ES", 1),
+    ("FR", 1),
+    ("BE", 1),
+    ("NL", 1),
+    ("LU", 1),
+    ("DE", 1),
+    ("CH", 1),
+    ("AT", 1),
+    ("IT", 1),
+    ("DK", 1),
+    ("NO", 1),
+    ("SE", 1),
+    ("PL", 1),
+    ("CZ", 1),
+    ("SK", 1),
+    ("HU", 1),
+    ("SI", 1),
+    ("HR", 1),
+    ("BA", 1),
+    ("RS", 1),
+    ("ME", 1),
+    ("MK", 1),
+    ("AL", 1),
+    ("MT", 1),
+    ("FI", 2),
+    ("EE", 2),
+    ("LV", 2),
+    ("LT", 2),
+    ("UA", 2),
+    ("MD", 2),
+    ("RO", 2),
+    ("BG", 2),
+    ("GR", 2),
+    ("CY", 2),
+    ("BY", 3),
+    ("RU", 3),
+    ("TR", 3),
+    // Africa and Middle East
+    ("MA", 0),
+    ("DZ", 1),
+    ("TN", 1),
+    ("NG", 1),
+    ("EG", 2),
+    ("ZA", 2),
+    ("IL", 2),
+    ("LB", 2),
+    ("JO", 3),
+    ("SA", 3),
+    ("KE", 3),
+    ("IQ", 3),
+    ("KW", 3),
+    ("QA", 3),
+    ("IR", 3),
+    ("AE", 4),
+    ("OM", 4),
+    ("GE", 4),
+    ("AM", 4),
+    ("AZ", 4),
+    // Asia and Oceania
+    ("PK", 5),
+    ("UZ", 5),
+    ("IN", 5),
+    ("LK", 5),
+    ("NP", 5),
+    ("KZ", 6),
+    ("BD", 6),
+    ("TH", 7),
+    ("VN", 7),
+    ("ID", 7),
+    ("KH", 7),
+    ("CN", 8),
+    ("HK", 8),
+    ("MO", 8),
+    ("TW", 8),
+    ("SG", 8),
+    ("MY", 8),
+    ("PH", 8),
+    ("MN", 8),
+    ("KR", 9),
+    ("JP", 9),
+    ("AU", 10),
+    ("GU", 10),
+    ("NZ", 12),
+];
+
+/// Local hour (inclusive) from which players are assumed to be available
+pub const PLAY_START_HOUR: i32 = 14;
+
+/// Local hour (exclusive) until which players are assumed to be available
+pub const PLAY_END_HOUR: i32 = 24;
+
+/// Typical UTC offset of a country, `None` if it's not in the table
+pub fn typical_offset(country: &str) -> Option<i32> {
+    let country = country.to_uppercase();
+    OFFSETS
+        .iter()
+        .find(|(code, _)| *code == country)
+        .map(|(_, offset)| *offset)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// Amount of players per UTC offset
+pub struct OffsetHistogram {
+    /// Players per offset in hours
+    pub counts: BTreeMap<i32, usize>,
+    /// Players without a country, or with a country that's not in the table
+    pub unknown: usize,
+}
+
+impl OffsetHistogram {
+    /// Builds the histogram from the players' countries
+    pub fn from_countries<'a>(
+        countries: impl IntoIterator<Item = Option<&'a str>>,
+    ) -> OffsetHistogram {
+        let mut histogram = OffsetHistogram::default();
+        for country in countries {
+            match country.and_then(typical_offset) {
+                Some(offset) => *histogram.counts.entry(offset).or_insert(0) += 1,
+                None => histogram.unknown += 1,
+            }
+        }
+        histogram
+    }
+
+    /// Amount of players with a known offset
+    pub fn known(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A match window in UTC
+pub struct Window {
+    /// UTC hour the window starts at
+    pub start_hour: i32,
+    /// Length in hours
+    pub length: i32,
+    /// Players for whom the whole window falls into their available hours
+    pub players: usize,
+}
+
+impl std::fmt::Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:00-{:02}:00 UTC",
+            self.start_hour,
+            (self.start_hour + self.length) % 24
+        )
+    }
+}
+
+/// Whether a window starting at the UTC hour is entirely within the available hours at the offset
+fn covers(start_hour: i32, length: i32, offset: i32) -> bool {
+    let local_start = (start_hour + offset).rem_euclid(24);
+    local_start >= PLAY_START_HOUR && local_start + length <= PLAY_END_HOUR
+}
+
+/// Amount of players a window starting at the UTC hour works for
+pub fn window_players(histogram: &OffsetHistogram, start_hour: i32, length: i32) -> usize {
+    histogram
+        .counts
+        .iter()
+        .filter(|(offset, _)| covers(start_hour, length, **offset))
+        .map(|(_, count)| count)
+        .sum()
+}
+
+/// Picks up to `count` non-overlapping windows that work for the most players
+///
+/// Windows are picked greedily, best first. On ties the earlier UTC hour wins.
+/// Windows that work for nobody are never suggested.
+pub fn best_windows(histogram: &OffsetHistogram, length: i32, count: usize) -> Vec<Window> {
+    let candidates: Vec<Window> = (0..24)
+        .map(|start_hour| Window {
+            start_hour,
+            length,
+            players: window_players(histogram, start_hour, length),
+        })
+        .collect();
+
+    let overlaps = |a: &Window, b: &Window| {
+        let distance = (a.start_hour - b.start_hour).rem_euclid(24);
+        distance.min(24 - distance) < length
+    };
+
+    let mut picked: Vec<Window> = Vec::new();
+    while picked.len() < count {
+        let best = candidates
+            .iter()
+            .filter(|c| c.players > 0 && !picked.iter().any(|p| overlaps(p, c)))
+            .fold(None, |best: Option<&Window>, c| match best {
+                Some(b) if b.players >= c.players => Some(b),
+                _ => Some(c),
+            });
+        match best {
+            Some(window) => picked.push(*window),
+            None => break,
+        }
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 8 players in the US, 6 in Mexico and 3 in Germany, 3 without a known country
+    fn americas_heavy() -> OffsetHistogram {
+        let mut countries = vec![Some("US"); 8];
+        countries.extend(vec![Some("mx"); 6]);
+        countries.extend(vec![Some("DE"); 3]);
+        countries.extend(vec![None, Some("XX"), Some("")]);
+        OffsetHistogram::from_countries(countries)
+    }
+
+    #[test]
+    fn country_offsets() {
+        assert_eq!(typical_offset("US"), Some(-5));
+        assert_eq!(typical_offset("de"), Some(1));
+        assert_eq!(typical_offset("JP"), Some(9));
+        assert_eq!(typical_offset("IN"), Some(5));
+        assert_eq!(typical_offset("NZ"), Some(12));
+        assert_eq!(typical_offset("XX"), None);
+        assert_eq!(typical_offset(""), None);
+    }
+
+    #[test]
+    fn every_country_is_listed_once() {
+        let mut codes: Vec<&str> = OFFSETS.iter().map(|(code, _)| *code).collect();
+        codes.sort_unstable();
+        let listed = codes.len();
+        codes.dedup();
+        assert_eq!(codes.len(), listed);
+        assert!(OFFSETS
+            .iter()
+            .all(|(code, offset)| code.len() == 2 && (-12..=14).contains(offset)));
+    }
+
+    #[test]
+    fn unknown_countries_are_counted_separately() {
+        let histogram = americas_heavy();
+        assert_eq!(histogram.unknown, 3);
+        assert_eq!(histogram.known(), 17);
+        let counts: Vec<(i32, usize)> = histogram.counts.into_iter().collect();
+        assert_eq!(counts, vec![(-6, 6), (-5, 8), (1, 3)]);
+    }
+
+    #[test]
+    fn windows_wrap_around_midnight() {
+        let histogram = americas_heavy();
+        // 22:00 UTC is 17:00 in the US and 16:00 in Mexico, the window ends after midnight UTC
+        assert_eq!(window_players(&histogram, 22, 4), 14);
+        // 00:00 UTC is still the evening before for both
+        assert_eq!(window_players(&histogram, 0, 4), 14);
+        // 19:00 UTC works for the US and Germany, but is too early in Mexico
+        assert_eq!(window_players(&histogram, 19, 4), 11);
+        assert_eq!(window_players(&histogram, 8, 4), 0);
+
+        let window = Window {
+            start_hour: 22,
+            length: 4,
+            players: 14,
+        };
+        assert_eq!(window.to_string(), "22:00-02:00 UTC");
+    }
+
+    #[test]
+    fn best_two_windows_dont_overlap() {
+        let histogram = americas_heavy();
+        let windows = best_windows(&histogram, 4, 2);
+        assert_eq!(
+            windows,
+            vec![
+                Window {
+                    start_hour: 0,
+                    length: 4,
+                    players: 14
+                },
+                Window {
+                    start_hour: 20,
+                    length: 4,
+                    players: 14
+                },
+            ]
+        );
+        assert_eq!(windows[1].to_string(), "20:00-00:00 UTC");
+
+        // every start close to one of the two windows overlaps it, only Germany is left afterwards
+        let third = best_windows(&histogram, 4, 3)[2];
+        assert_eq!((third.start_hour, third.players), (13, 3));
+    }
+
+    #[test]
+    fn windows_for_nobody_are_not_suggested() {
+        assert!(best_windows(&OffsetHistogram::default(), 4, 2).is_empty());
+
+        let europe = OffsetHistogram::from_countries(vec![Some("DE"), Some("FR")]);
+        let windows = best_windows(&europe, 4, 5);
+        // windows starting from 13:00 to 19:00 all work, but only two of them fit without overlapping
+        let starts: Vec<i32> = windows.iter().map(|w| w.start_hour).collect();
+        assert_eq!(starts, vec![13, 17]);
+    }
+}
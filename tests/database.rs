@@ -0,0 +1,560 @@
+//! Links, registers and unregisters a player against a real database
+//!
+//! Skipped unless `TEST_DATABASE` names the database to use. Every test gets its own database with
+//! that name as prefix and everything in it gets replaced, so it refuses to touch the production database. `DATABASE_URL` has to be set as well and the Tetr.io API
+//! has to be reachable, linking requests the player from it.
+//!
+//! ```text
+//! TEST_DATABASE=uc_helper_test cargo test --test database
+//! ```
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use uc::database::jobs::{after_failure, backoff, JobKind, JobStatus, ScheduledJob, MAX_ATTEMPTS};
+use uc::database::players::{PendingLink, BULK_CHUNK_SIZE};
+use uc::database::tournaments::{
+    RegOrder, RegistrationEntry, RegistrationError, RegistrationOutcome, RegistrationStatus,
+    TournamentEntryBuilder, TournamentRestrictions, UnregisteredBy,
+};
+use uc::database::usage::UsageCounters;
+use uc::database::{DatabaseError, LocalDatabase};
+use uc::fixtures::FixtureConfig;
+use uc::prelude::*;
+use uc::tasks::{CancelReason, TaskHandle};
+use uc::tetrio::leaderboard::LeaderboardData;
+use uc::tetrio::{CacheData, SuccessfulResponse};
+use uc_helper_rust as uc;
+
+const TETRIO_USERNAME: &str = "icedynamix";
+const OTHER_USERNAME: &str = "caboozled_pie";
+const DISCORD_ID: u64 = 1;
+const SHORTHAND: &str = "TEST";
+
+// Empty test database, `None` if no test database is configured
+//
+// Tests run in parallel, so each one passes its own suffix for the database name
+async fn test_database(suffix: &str) -> Option<LocalDatabase> {
+    dotenv::dotenv().ok();
+    let name = std::env::var("TEST_DATABASE").ok()? + suffix;
+    assert_ne!(
+        name,
+        uc::database::DATABASE_NAME,
+        "Refusing to run against the production database"
+    );
+
+    let db = uc::database::connect_to(&name)
+        .await
+        .expect("Failed to connect to database");
+    db.players.remove_all().await.unwrap();
+    db.tournaments.remove_all().await.unwrap();
+    db.jobs.remove_all().await.unwrap();
+    db.usage.remove_all().await.unwrap();
+
+    // Dropping the collections dropped their indexes too, connecting again creates them
+    Some(
+        uc::database::connect_to(&name)
+            .await
+            .expect("Failed to connect to database"),
+    )
+}
+
+#[tokio::test]
+async fn link_register_unregister() {
+    let db = match test_database("").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let linked = db
+        .players
+        .link(DISCORD_ID, TETRIO_USERNAME)
+        .await
+        .expect("Could not link");
+    assert_eq!(linked.discord_id, Some(DISCORD_ID));
+    let found = db.players.get_player_by_discord(DISCORD_ID).await.unwrap();
+    assert_eq!(found.map(|p| p.tetrio_id), Some(linked.tetrio_id.clone()));
+
+    db.tournaments
+        .create_tournament(
+            "Test Cup",
+            SHORTHAND,
+            TournamentRestrictions::new(Rank::X, 999f64, 0),
+        )
+        .await
+        .unwrap();
+
+    // Shorthands are found regardless of case, so they can't be taken twice that way either
+    let found = db.tournaments.get_tournament("test").await.unwrap();
+    assert_eq!(found.map(|t| t.shorthand), Some(SHORTHAND.to_string()));
+    assert!(matches!(
+        db.tournaments
+            .create_tournament(
+                "Other Cup",
+                "test",
+                TournamentRestrictions::new(Rank::X, 999f64, 0)
+            )
+            .await,
+        Err(DatabaseError::DuplicateTournamentEntry)
+    ));
+    assert!(matches!(
+        db.tournaments
+            .create_tournament(
+                "Other Cup",
+                "T",
+                TournamentRestrictions::new(Rank::X, 999f64, 0)
+            )
+            .await,
+        Err(DatabaseError::InvalidInput(_))
+    ));
+
+    // Writes find the tournament the same way
+    db.tournaments
+        .qualify("test", &[linked.tetrio_id.clone()])
+        .await
+        .unwrap();
+    let qualified = db.tournaments.get_tournament(SHORTHAND).await.unwrap();
+    assert_eq!(
+        qualified.unwrap().qualified_players,
+        vec![linked.tetrio_id.clone()]
+    );
+
+    db.tournaments.set_active(Some(SHORTHAND)).await.unwrap();
+
+    // Registering with another account than the linked one must not register either of them
+    match db
+        .tournaments
+        .register_to_active(
+            &db.players,
+            None,
+            Some(OTHER_USERNAME),
+            DISCORD_ID,
+            true,
+            None,
+        )
+        .await
+    {
+        Err(RegistrationError::LinkedToDifferentAccount { linked, requested }) => {
+            assert_eq!(linked, TETRIO_USERNAME);
+            assert_eq!(requested, OTHER_USERNAME);
+        }
+        other => panic!(
+            "Expected a different account error, got {:?}",
+            other.map(|r| r.2)
+        ),
+    }
+    let unchanged = db.tournaments.get_tournament(SHORTHAND).await.unwrap();
+    assert!(unchanged.unwrap().registered_players().is_empty());
+
+    // The linked account is recognized regardless of case
+    let (player, tournament, outcome) = db
+        .tournaments
+        .register_to_active(
+            &db.players,
+            None,
+            Some(&TETRIO_USERNAME.to_uppercase()),
+            DISCORD_ID,
+            true,
+            None,
+        )
+        .await
+        .expect("Could not register");
+    assert_eq!(player.tetrio_id, linked.tetrio_id);
+    assert_eq!(outcome, RegistrationOutcome::Registered);
+    assert!(tournament.player_is_registered(&player));
+
+    let (player, tournament) = db
+        .tournaments
+        .unregister_by_discord(&db.players, DISCORD_ID, UnregisteredBy::Player, None)
+        .await
+        .expect("Could not unregister");
+    assert!(!tournament.player_is_registered(&player));
+    let stored = db.tournaments.get_tournament(SHORTHAND).await.unwrap();
+    assert!(!stored.unwrap().player_is_registered(&player));
+
+    // Unlinking returns the entry as it was before, the Discord ID is still in it
+    let unlinked = db
+        .players
+        .unlink_by_discord(DISCORD_ID)
+        .await
+        .expect("Could not unlink");
+    assert_eq!(unlinked.tetrio_id, linked.tetrio_id);
+    assert_eq!(unlinked.discord_id, Some(DISCORD_ID));
+    assert!(db
+        .players
+        .get_player_by_discord(DISCORD_ID)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Unlinking again fails, the player is known but no longer linked
+    assert!(matches!(
+        db.players.unlink_by_tetrio(TETRIO_USERNAME).await,
+        Err(DatabaseError::FieldNotSet)
+    ));
+    assert!(matches!(
+        db.players.unlink_by_discord(DISCORD_ID).await,
+        Err(DatabaseError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn registration_pages_match_in_memory_pages() {
+    let db = match test_database("_pages").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let fixtures = uc::fixtures::generate(
+        &FixtureConfig {
+            players: 200,
+            registrations: 40,
+            ..FixtureConfig::default()
+        },
+        now,
+    );
+
+    // Distinct dates, so the order doesn't depend on how ties are broken
+    let registered = fixtures.tournament.registered_players();
+    let registrations: Vec<RegistrationEntry> = registered
+        .iter()
+        .enumerate()
+        .map(|(i, reg)| {
+            let mut entry =
+                RegistrationEntry::backdated(&reg.tetrio_id, now - Duration::minutes(i as i64));
+            if i % 7 == 3 {
+                entry.status = RegistrationStatus::Withdrawn {
+                    at: now.into(),
+                    reason: None,
+                };
+            }
+            entry
+        })
+        .collect();
+    // Some players are missing from the snapshot, they go last when ordered by seed
+    let snapshot = fixtures
+        .leaderboard()
+        .into_iter()
+        .filter(|user| {
+            registered
+                .iter()
+                .position(|reg| reg.tetrio_id == user._id)
+                .map_or(true, |i| i % 5 != 0)
+        })
+        .cloned()
+        .collect();
+    let tournament =
+        TournamentEntryBuilder::new("Page Cup", "PAGE", TournamentRestrictions::default())
+            .snapshot(snapshot, now)
+            .registrations(registrations)
+            .build()
+            .unwrap();
+    db.tournaments.insert_tournament(&tournament).await.unwrap();
+
+    let total = tournament.registration_count();
+    let ids = |entries: &[RegistrationEntry]| -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.tetrio_id.clone())
+            .collect()
+    };
+    for order in [RegOrder::RegistrationDate, RegOrder::Seed].iter().copied() {
+        for (offset, limit) in [
+            (0, 10),
+            (7, 10),
+            (total - 3, 10),
+            (total, 10),
+            (0, total + 5),
+            (3, 0),
+        ]
+        .iter()
+        .copied()
+        {
+            let (expected, expected_total) = tournament.registrations_page(offset, limit, order);
+            let (page, page_total) = db
+                .tournaments
+                .get_registrations_page("page", offset, limit, order)
+                .await
+                .expect("Could not get page");
+            assert_eq!(page_total, expected_total);
+            assert_eq!(
+                ids(&page),
+                ids(&expected),
+                "{:?} from {} ({} entries)",
+                order,
+                offset,
+                limit
+            );
+        }
+    }
+}
+
+// Now, without the sub-millisecond part that doesn't survive a round trip through the database
+fn now_millis() -> DateTime<Utc> {
+    Utc.timestamp_millis(Utc::now().timestamp_millis())
+}
+
+async fn open_job(db: &LocalDatabase, job: &ScheduledJob) -> ScheduledJob {
+    db.jobs
+        .get_open_jobs()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|open| open._id == job._id)
+        .expect("Job is not open")
+}
+
+#[tokio::test]
+async fn jobs_are_claimed_once_and_retried_with_backoff() {
+    let db = match test_database("_jobs").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let now = now_millis();
+    let due = ScheduledJob::new(
+        JobKind::CheckInReminder,
+        now - Duration::minutes(5),
+        bson::Document::new(),
+        None,
+    );
+    let later = ScheduledJob::new(
+        JobKind::RegistrationAnnouncement,
+        now + Duration::hours(1),
+        bson::Document::new(),
+        None,
+    );
+    db.jobs.schedule(&due).await.unwrap();
+    db.jobs.schedule(&later).await.unwrap();
+
+    // Two dispatchers racing for the same job, only one of them gets it
+    let (first, second) = tokio::join!(db.jobs.claim_due(now), db.jobs.claim_due(now));
+    let claimed: Vec<ScheduledJob> = vec![first.unwrap(), second.unwrap()]
+        .into_iter()
+        .flatten()
+        .collect();
+    assert_eq!(claimed.len(), 1);
+    let job = claimed[0].clone();
+    assert_eq!(job._id, due._id);
+    assert_eq!(job.status, JobStatus::Running);
+    assert!(db.jobs.claim_due(now).await.unwrap().is_none());
+
+    // Every failure reschedules the job further out, until it's given up on
+    let mut job = job;
+    let mut previous_delay = Duration::zero();
+    let mut claimed_at = now;
+    for attempt in 1..MAX_ATTEMPTS {
+        let transition = after_failure(&job, claimed_at);
+        db.jobs
+            .finish(&job, transition, Some("boom"))
+            .await
+            .unwrap();
+
+        let open = open_job(&db, &job).await;
+        assert_eq!(open.status, JobStatus::Pending);
+        assert_eq!(open.attempts, attempt);
+        assert_eq!(open.last_error.as_deref(), Some("boom"));
+        let delay = *open.due_at - claimed_at;
+        assert_eq!(delay, backoff(attempt));
+        assert!(delay > previous_delay);
+        previous_delay = delay;
+
+        // not due yet right before the backoff ends
+        let before = *open.due_at - Duration::seconds(1);
+        assert!(db.jobs.claim_due(before).await.unwrap().is_none());
+        claimed_at = *open.due_at;
+        job = db
+            .jobs
+            .claim_due(claimed_at)
+            .await
+            .unwrap()
+            .expect("Job was not due after its backoff");
+        assert_eq!(job._id, due._id);
+    }
+
+    let transition = after_failure(&job, claimed_at);
+    db.jobs
+        .finish(&job, transition, Some("boom"))
+        .await
+        .unwrap();
+    let failed = open_job(&db, &job).await;
+    assert_eq!(failed.status, JobStatus::Failed);
+    assert_eq!(failed.attempts, MAX_ATTEMPTS);
+    let far_future = now + Duration::days(30);
+    let next = db.jobs.claim_due(far_future).await.unwrap();
+    assert_eq!(next.map(|job| job._id), Some(later._id.clone()));
+
+    // Retrying resets the job, only failed jobs can be retried
+    let retried_at = now_millis();
+    db.jobs.retry(&due._id.to_hex(), retried_at).await.unwrap();
+    let retried = open_job(&db, &due).await;
+    assert_eq!(retried.status, JobStatus::Pending);
+    assert_eq!(retried.attempts, 0);
+    assert_eq!(*retried.due_at, retried_at);
+    assert!(matches!(
+        db.jobs.retry(&due._id.to_hex(), retried_at).await,
+        Err(DatabaseError::NotFound)
+    ));
+    assert!(matches!(
+        db.jobs.retry("not an id", retried_at).await,
+        Err(DatabaseError::InvalidInput(_))
+    ));
+    let claimed = db.jobs.claim_due(retried_at).await.unwrap();
+    assert_eq!(claimed.map(|job| job._id), Some(due._id));
+}
+
+#[tokio::test]
+async fn cancelled_leaderboard_update_stops_between_chunks() {
+    let db = match test_database("_cancel").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let fixtures = uc::fixtures::generate(
+        &FixtureConfig {
+            players: BULK_CHUNK_SIZE * 4,
+            registrations: 0,
+            ..FixtureConfig::default()
+        },
+        now,
+    );
+    let response = SuccessfulResponse {
+        data: LeaderboardData {
+            users: fixtures.leaderboard().into_iter().cloned().collect(),
+        },
+        cache: CacheData {
+            status: "miss".to_string(),
+            cached_at: now.timestamp_millis(),
+            cached_until: now.timestamp_millis(),
+        },
+    };
+    let total = response.data.users.len();
+    assert!(total > BULK_CHUNK_SIZE * 2);
+
+    // Cancels as soon as the first chunk is written, while the update keeps going
+    let task = TaskHandle::detached("Leaderboard update");
+    let update = db.players.update_with_leaderboard(&response, &task);
+    tokio::pin!(update);
+    let result = loop {
+        tokio::select! {
+            result = &mut update => break result,
+            _ = tokio::task::yield_now() => {
+                if task.progress().0 >= BULK_CHUNK_SIZE {
+                    task.cancel(CancelReason::Requested(DISCORD_ID));
+                }
+            }
+        }
+    };
+    result.expect("Could not update");
+
+    let (done, reported_total) = task.progress();
+    assert_eq!(reported_total, total);
+    assert_eq!(done % BULK_CHUNK_SIZE, 0, "stopped within a chunk");
+    assert!(
+        done >= BULK_CHUNK_SIZE && done < total,
+        "{}/{}",
+        done,
+        total
+    );
+    assert_eq!(
+        task.cancel_reason(),
+        Some(CancelReason::Requested(DISCORD_ID))
+    );
+    let stored = db.players.get_players(None, None).await.unwrap();
+    assert_eq!(stored.len(), done);
+}
+
+#[tokio::test]
+async fn usage_flushes_add_up() {
+    let db = match test_database("_usage").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let mut counters = UsageCounters::default();
+    counters.record("register", 1, false, now);
+    counters.record("register", 2, true, now);
+    assert!(db.usage.flush(counters.drain()).await.is_empty());
+
+    // A second flush adds to the same rollup, users already in it aren't counted twice
+    counters.record("register", 2, false, now);
+    counters.record("register", 3, false, now);
+    counters.record("stats", 3, false, now);
+    assert!(db.usage.flush(counters.drain()).await.is_empty());
+
+    let register = db.usage.get_usage(Some("register"), 1, now).await.unwrap();
+    assert_eq!(register.len(), 1);
+    assert_eq!((register[0].invocations, register[0].errors), (4, 1));
+    let mut users = register[0].users.clone();
+    users.sort_unstable();
+    assert_eq!(users, vec![1, 2, 3]);
+
+    let all = db.usage.get_usage(None, 1, now).await.unwrap();
+    let totals = uc::database::usage::daily_totals(&all, 1, now);
+    assert_eq!(totals[0].1, 5);
+    assert_eq!(totals[0].3, 3);
+}
+
+#[tokio::test]
+async fn janitor_cleanups_remove_only_expired_state() {
+    let db = match test_database("_janitor").await {
+        Some(db) => db,
+        None => {
+            eprintln!("TEST_DATABASE is not set, skipping");
+            return;
+        }
+    };
+
+    let now = now_millis();
+    let timeout = Duration::minutes(uc::database::players::VERIFICATION_TIMEOUT_MINUTES);
+    let mut expired = PlayerEntry::new(TETRIO_USERNAME, None);
+    expired.pending_link = Some(PendingLink::new(
+        DISCORD_ID,
+        now - timeout - Duration::minutes(1),
+    ));
+    let mut fresh = PlayerEntry::new(OTHER_USERNAME, None);
+    fresh.pending_link = Some(PendingLink::new(DISCORD_ID + 1, now));
+    db.players.insert_players(&[expired, fresh]).await.unwrap();
+
+    assert_eq!(db.players.expire_pending_links(now).await.unwrap(), 1);
+    let pending = |entry: Option<PlayerEntry>| entry.unwrap().pending_link.is_some();
+    assert!(!pending(
+        db.players
+            .get_player_by_tetrio(TETRIO_USERNAME)
+            .await
+            .unwrap()
+    ));
+    assert!(pending(
+        db.players
+            .get_player_by_tetrio(OTHER_USERNAME)
+            .await
+            .unwrap()
+    ));
+    assert_eq!(db.players.expire_pending_links(now).await.unwrap(), 0);
+
+    // Command usage older than the retention is deleted, the rest is kept
+    let mut counters = UsageCounters::default();
+    counters.record("register", 1, false, now - Duration::days(400));
+    counters.record("register", 1, false, now - Duration::days(2));
+    counters.record("register", 1, false, now);
+    assert!(db.usage.flush(counters.drain()).await.is_empty());
+    assert_eq!(db.usage.prune(Duration::days(365), now).await.unwrap(), 1);
+    assert_eq!(db.usage.get_usage(None, 400, now).await.unwrap().len(), 2);
+    assert_eq!(db.usage.prune(Duration::days(1), now).await.unwrap(), 1);
+}
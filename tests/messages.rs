@@ -0,0 +1,133 @@
+//! Checks the message catalog for missing or broken templates
+//!
+//! Registration and link errors are shown to players through the catalog, so every error needs a
+//! message, and translations have to fill in the same parameters as the English template.
+
+use chrono::{TimeZone, Utc};
+use uc::database::players::AccountStatus;
+use uc::discord::messages::{self, Language, LocalizedMessage, MessageKey};
+use uc::prelude::*;
+use uc::tetrio::TetrioApiError;
+use uc_helper_rust as uc;
+
+#[test]
+fn every_key_has_an_english_template() {
+    for key in MessageKey::ALL.iter().copied() {
+        let template = messages::template(key, Language::English)
+            .unwrap_or_else(|| panic!("{:?} has no English template", key));
+        assert!(!template.trim().is_empty(), "{:?} is empty", key);
+    }
+}
+
+#[test]
+fn translations_use_the_english_parameters() {
+    for key in MessageKey::ALL.iter().copied() {
+        let mut expected =
+            messages::placeholders(messages::template(key, Language::English).unwrap());
+        expected.sort_unstable();
+        for language in Language::ALL.iter().copied() {
+            if let Some(template) = messages::template(key, language) {
+                let mut found = messages::placeholders(template);
+                found.sort_unstable();
+                assert_eq!(found, expected, "{:?} in {:?}", key, language);
+            }
+        }
+    }
+}
+
+#[test]
+fn missing_translations_fall_back_to_english() {
+    let date = Utc.ymd(2021, 3, 14).and_hms(18, 0, 0);
+    let message = messages::registration_error(&RegistrationError::InvalidBackdate {
+        date,
+        earliest: date,
+        latest: date,
+    });
+    assert!(messages::template(message.key, Language::Spanish).is_none());
+    assert_eq!(
+        message.render(Language::Spanish),
+        message.render(Language::English)
+    );
+}
+
+#[test]
+fn errors_are_rendered_with_their_parameters() {
+    let now = Utc::now();
+    let errors = vec![
+        RegistrationError::NoTournamentActive,
+        RegistrationError::AmbiguousTournament(vec!["UC12".to_string(), "CC3".to_string()]),
+        RegistrationError::MissingArgument("username".to_string()),
+        RegistrationError::AlreadyRegistered,
+        RegistrationError::NotRegistered,
+        RegistrationError::SnapshotMissing,
+        RegistrationError::Disqualified,
+        RegistrationError::HighestRankTooHigh {
+            rank: Rank::SS,
+            expected: Rank::S,
+        },
+        RegistrationError::RegistrationNotOpen(now),
+        RegistrationError::RegistrationClosed(now),
+        RegistrationError::NotQualified("UC12".to_string()),
+        RegistrationError::InvalidBackdate {
+            date: now,
+            earliest: now,
+            latest: now,
+        },
+        RegistrationError::AccountUnavailable(AccountStatus::Banned),
+        RegistrationError::AlreadyWaitlisted(3),
+        RegistrationError::LinkedToDifferentAccount {
+            linked: "icedynamix".to_string(),
+            requested: "caboozled_pie".to_string(),
+        },
+        RegistrationError::DatabaseError(DatabaseError::NotFound),
+        RegistrationError::DatabaseError(DatabaseError::DuplicateDiscordEntry),
+        RegistrationError::DatabaseError(DatabaseError::DuplicateTetrioEntry),
+        RegistrationError::DatabaseError(DatabaseError::AlreadyLinked),
+        RegistrationError::DatabaseError(DatabaseError::ConnectionFailed),
+        RegistrationError::DatabaseError(DatabaseError::InvalidInput("bad".to_string())),
+        RegistrationError::DatabaseError(DatabaseError::TetrioApiError(
+            TetrioApiError::CircuitOpen { until: now },
+        )),
+    ];
+
+    for err in &errors {
+        let message = messages::registration_error(err);
+        for language in Language::ALL.iter().copied() {
+            let rendered = message.render(language);
+            assert!(
+                !rendered.contains('{'),
+                "{:?} in {:?} was not filled in: {}",
+                err,
+                language,
+                rendered
+            );
+        }
+    }
+
+    let waitlisted = messages::registration_error(&RegistrationError::AlreadyWaitlisted(3));
+    assert_eq!(
+        waitlisted.render(Language::English),
+        "You're already #3 on the waitlist!"
+    );
+    assert!(waitlisted.render(Language::Portuguese).contains("(#3)"));
+}
+
+#[test]
+fn language_codes() {
+    assert_eq!(Language::from_code(" ES "), Some(Language::Spanish));
+    assert_eq!(Language::from_code("pt"), Some(Language::Portuguese));
+    assert_eq!(Language::from_code("de"), None);
+    assert_eq!(Language::of(None), Language::English);
+
+    let mut player = PlayerEntry::new("5e47696db7c60f23a497ee6c", None);
+    player.language = Some("es".to_string());
+    assert_eq!(Language::of(Some(&player)), Language::Spanish);
+    player.language = Some("unknown".to_string());
+    assert_eq!(Language::of(Some(&player)), Language::English);
+
+    let message = LocalizedMessage::new(MessageKey::LanguageSet).with("language", "Español");
+    assert_eq!(
+        message.render(Language::Spanish),
+        "Ahora te respondo en Español"
+    );
+}